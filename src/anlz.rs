@@ -0,0 +1,159 @@
+//! Beat grids from Rekordbox analysis files.
+//!
+//! Rekordbox keeps each track's grid in an `ANLZ0000.DAT` file under
+//! `share/` next to `master.db`; `djmdContent.AnalysisDataPath` holds its
+//! path relative to that directory. The file is a `PMAI` header followed by
+//! tagged sections, all big-endian. The `PQTZ` section lists every beat as
+//! (beat in bar, tempo × 100, time in ms); consecutive beats at the same
+//! tempo collapse into one `TempoMarker`.
+
+use std::path::{Path, PathBuf};
+
+use crate::types::TempoMarker;
+
+const FILE_MAGIC: &[u8; 4] = b"PMAI";
+const BEAT_GRID_TAG: &[u8; 4] = b"PQTZ";
+/// Tag header bytes before the beat count: fourcc, header length, tag length
+/// and two unknown words.
+const PQTZ_COUNT_OFFSET: usize = 20;
+const BEAT_ENTRY_LEN: usize = 8;
+
+/// Absolute path of a track's analysis file, given the `master.db` path and
+/// the track's `AnalysisDataPath`.
+pub fn analysis_file_path(db_path: &str, analysis_data_path: &str) -> Option<PathBuf> {
+    let relative = analysis_data_path.trim().trim_start_matches('/');
+    if relative.is_empty() {
+        return None;
+    }
+    Some(Path::new(db_path).parent()?.join("share").join(relative))
+}
+
+/// Read the beat grid stored in an analysis file. `Ok(None)` means the file
+/// has no `PQTZ` section (Rekordbox hasn't gridded the track).
+pub fn read_beat_grid(path: &Path) -> Result<Option<Vec<TempoMarker>>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    parse_beat_grid(&bytes)
+}
+
+fn be_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn parse_beat_grid(bytes: &[u8]) -> Result<Option<Vec<TempoMarker>>, String> {
+    if bytes.get(..4) != Some(FILE_MAGIC.as_slice()) {
+        return Err("not a Rekordbox analysis file".into());
+    }
+    let mut offset = be_u32(bytes, 4).ok_or("truncated file header")? as usize;
+    while offset + 12 <= bytes.len() {
+        let header_len = be_u32(bytes, offset + 4).ok_or("truncated tag header")? as usize;
+        let tag_len = be_u32(bytes, offset + 8).ok_or("truncated tag header")? as usize;
+        if tag_len < 12 || header_len > tag_len {
+            return Err(format!("malformed tag at byte {offset}"));
+        }
+        if &bytes[offset..offset + 4] == BEAT_GRID_TAG {
+            let tag = bytes
+                .get(offset..offset + tag_len)
+                .ok_or("truncated beat grid")?;
+            return parse_pqtz(tag, header_len).map(Some);
+        }
+        offset += tag_len;
+    }
+    Ok(None)
+}
+
+fn parse_pqtz(tag: &[u8], header_len: usize) -> Result<Vec<TempoMarker>, String> {
+    let count = be_u32(tag, PQTZ_COUNT_OFFSET).ok_or("truncated beat grid header")? as usize;
+    let mut markers: Vec<TempoMarker> = Vec::new();
+    for i in 0..count {
+        let at = header_len + i * BEAT_ENTRY_LEN;
+        let (Some(beat), Some(tempo), Some(time_ms)) =
+            (be_u16(tag, at), be_u16(tag, at + 2), be_u32(tag, at + 4))
+        else {
+            return Err("truncated beat grid".into());
+        };
+        let bpm = f64::from(tempo) / 100.0;
+        if markers.last().is_some_and(|m| m.bpm == bpm) {
+            continue;
+        }
+        markers.push(TempoMarker {
+            start: f64::from(time_ms) / 1000.0,
+            bpm,
+            beat_in_bar: beat.clamp(1, 4) as u8,
+        });
+    }
+    Ok(markers)
+}
+
+#[cfg(test)]
+pub(crate) fn encode_beat_grid(beats: &[(u16, f64, u32)]) -> Vec<u8> {
+    let mut tag = Vec::new();
+    tag.extend_from_slice(BEAT_GRID_TAG);
+    tag.extend_from_slice(&24u32.to_be_bytes());
+    tag.extend_from_slice(&((24 + beats.len() * BEAT_ENTRY_LEN) as u32).to_be_bytes());
+    tag.extend_from_slice(&0u32.to_be_bytes());
+    tag.extend_from_slice(&0x80000u32.to_be_bytes());
+    tag.extend_from_slice(&(beats.len() as u32).to_be_bytes());
+    for &(beat, bpm, time_ms) in beats {
+        tag.extend_from_slice(&beat.to_be_bytes());
+        tag.extend_from_slice(&((bpm * 100.0).round() as u16).to_be_bytes());
+        tag.extend_from_slice(&time_ms.to_be_bytes());
+    }
+    let mut file = Vec::new();
+    file.extend_from_slice(FILE_MAGIC);
+    file.extend_from_slice(&28u32.to_be_bytes());
+    file.extend_from_slice(&((28 + tag.len()) as u32).to_be_bytes());
+    file.resize(28, 0);
+    file.extend_from_slice(&tag);
+    file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn beats_collapse_into_tempo_segments() {
+        let bytes = encode_beat_grid(&[
+            (2, 128.0, 120),
+            (3, 128.0, 589),
+            (4, 128.0, 1058),
+            (1, 130.0, 1527),
+            (2, 130.0, 1989),
+        ]);
+        let grid = parse_beat_grid(&bytes).unwrap().unwrap();
+        assert_eq!(grid.len(), 2);
+        assert_eq!(grid[0].start, 0.12);
+        assert_eq!(grid[0].bpm, 128.0);
+        assert_eq!(grid[0].beat_in_bar, 2);
+        assert_eq!(grid[1].start, 1.527);
+        assert_eq!(grid[1].bpm, 130.0);
+    }
+
+    #[test]
+    fn file_without_grid_is_none_and_garbage_is_error() {
+        let mut header = FILE_MAGIC.to_vec();
+        header.extend_from_slice(&28u32.to_be_bytes());
+        header.extend_from_slice(&28u32.to_be_bytes());
+        header.resize(28, 0);
+        assert_eq!(parse_beat_grid(&header).unwrap(), None);
+        assert!(parse_beat_grid(b"RIFF....").is_err());
+    }
+
+    #[test]
+    fn analysis_path_resolves_under_share() {
+        assert_eq!(
+            analysis_file_path(
+                "/Users/me/Library/Pioneer/rekordbox/master.db",
+                "/PIONEER/USBANLZ/abc/def/ANLZ0000.DAT"
+            ),
+            Some(PathBuf::from(
+                "/Users/me/Library/Pioneer/rekordbox/share/PIONEER/USBANLZ/abc/def/ANLZ0000.DAT"
+            ))
+        );
+        assert_eq!(analysis_file_path("/x/master.db", ""), None);
+    }
+}
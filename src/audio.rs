@@ -4,11 +4,12 @@ use tokio::process::Command;
 use tokio::time::{Duration, timeout};
 
 use symphonia::core::audio::AudioBufferRef;
-use symphonia::core::codecs::DecoderOptions;
-use symphonia::core::formats::FormatOptions;
+use symphonia::core::codecs::{CODEC_TYPE_OPUS, Decoder, DecoderOptions};
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
 use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
+use symphonia::core::units::{Time, TimeBase};
 use symphonia::default::get_probe;
 
 #[derive(Debug, thiserror::Error)]
@@ -44,15 +45,70 @@ pub struct StratumResult {
     pub analyzer_version: String,
     pub flags: Vec<String>,
     pub warnings: Vec<String>,
+    /// Time of the first grid beat in seconds (within one beat period of the start).
+    /// Absent in analyses cached before offset detection was added.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_beat_sec: Option<f64>,
+    /// Bar position (1-4) of the beat at `first_beat_sec`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_beat_in_bar: Option<u8>,
+    /// Tempo and key over the first `EDGE_WINDOW_BARS` bars, for tracks long
+    /// enough to have separate edges. Absent in analyses cached before
+    /// windowed tracking was added.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opening: Option<WindowAnalysis>,
+    /// Tempo and key over the last `EDGE_WINDOW_BARS` bars.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub closing: Option<WindowAnalysis>,
 }
 
-/// Audio file extensions accepted by all directory scanners.
-pub(crate) const AUDIO_EXTENSIONS: &[&str] = &["flac", "wav", "mp3", "m4a", "aac", "aiff"];
+/// Tempo and key of one stretch of a track.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowAnalysis {
+    pub start_sec: f64,
+    pub end_sec: f64,
+    pub bpm: f64,
+    pub bpm_confidence: f64,
+    pub key: String,
+    pub key_camelot: String,
+    pub key_confidence: f64,
+}
+
+/// Audio file extensions accepted by all directory scanners. Opus is left
+/// out until there is a decoder for it: symphonia has none, so Opus files
+/// could be tagged by path but never analyzed.
+pub(crate) const AUDIO_EXTENSIONS: &[&str] = &[
+    "flac", "wav", "mp3", "m4a", "aac", "aiff", "aif", "aifc", "ogg", "oga",
+];
 
 /// Canonical analyzer name for stratum-dsp (used as DB cache key).
 pub const ANALYZER_STRATUM: &str = "stratum-dsp";
+/// Appended to the stratum-dsp or native version of a result computed on
+/// part of a track (`1.0.0+window:last-10.0min`), so full analyses replace it.
+const WINDOW_VERSION_MARKER: &str = "+window:";
 /// Canonical analyzer name for Essentia (used as DB cache key).
 pub const ANALYZER_ESSENTIA: &str = "essentia";
+/// Version of the Essentia feature script, stored alongside Essentia's own
+/// version. Bumped when the script gains features (`key_essentia`,
+/// `key_strength`) so older cache entries are recomputed.
+pub const ESSENTIA_FEATURES_VERSION: &str = "essentia-features-2";
+/// Canonical analyzer name for acoustic fingerprints (used as DB cache key).
+pub const ANALYZER_FINGERPRINT: &str = "fingerprint";
+/// Canonical analyzer name for the built-in Essentia-equivalent features
+/// (used as DB cache key).
+pub const ANALYZER_NATIVE: &str = "native";
+/// Version stored with native feature analyses.
+pub const NATIVE_FEATURES_VERSION: &str = "native-2";
+/// Canonical analyzer name for intro/outro and section structure (used as DB
+/// cache key).
+pub const ANALYZER_STRUCTURE: &str = "structure";
+/// Version stored with structure analyses.
+pub const STRUCTURE_VERSION: &str = "structure-2";
+/// Canonical analyzer name for peak, clipping and DC offset measurements
+/// (used as DB cache key).
+pub const ANALYZER_QUALITY: &str = "quality";
+/// Version stored with signal quality measurements.
+pub const QUALITY_VERSION: &str = "quality-1";
 
 const ESSENTIA_TIMEOUT_SECS: u64 = 300;
 
@@ -138,6 +194,14 @@ if isinstance(onset_result, (tuple, list)) and len(onset_result) >= 2:
 else:
     features["onset_rate"] = first_scalar_or_none(onset_result)
 
+try:
+    key_name, key_scale, key_strength = es.KeyExtractor(profileType="edma")(audio)
+    features["key_essentia"] = key_name + ("m" if key_scale == "minor" else "")
+    features["key_strength"] = first_scalar_or_none(key_strength)
+except Exception:
+    features["key_essentia"] = None
+    features["key_strength"] = None
+
 beats = rhythm[1]
 if len(beats) > 4:
     bl = es.BeatsLoudness(beats=beats)(audio)
@@ -246,6 +310,9 @@ json.dump(features, sys.stdout)
 #[serde(default)]
 pub struct EssentiaOutput {
     pub analyzer_version: String,
+    /// [`ESSENTIA_FEATURES_VERSION`] for Essentia results; empty for native
+    /// features and for entries cached before the script was versioned.
+    pub features_version: String,
     pub danceability: Option<f64>,
     pub loudness_integrated: Option<f64>,
     pub loudness_range: Option<f64>,
@@ -253,6 +320,11 @@ pub struct EssentiaOutput {
     pub average_loudness: Option<f64>,
     pub bpm_essentia: Option<f64>,
     pub onset_rate: Option<f64>,
+    /// Key as a root with an `m` suffix for minor (e.g. `F#m`), from
+    /// Essentia's KeyExtractor. The native fallback does not estimate key.
+    pub key_essentia: Option<String>,
+    /// KeyExtractor strength, 0-1.
+    pub key_strength: Option<f64>,
     pub rhythm_regularity: Option<f64>,
     pub spectral_centroid_mean: Option<f64>,
     pub dissonance_mean: Option<f64>,
@@ -304,7 +376,9 @@ pub async fn run_essentia(
         )));
     }
 
-    parse_essentia_stdout(&output.stdout)
+    let mut features = parse_essentia_stdout(&output.stdout)?;
+    features.features_version = ESSENTIA_FEATURES_VERSION.to_string();
+    Ok(features)
 }
 
 /// Resolve a Rekordbox file path to an actual filesystem path.
@@ -329,119 +403,426 @@ pub(crate) fn resolve_audio_path(raw_path: &str) -> Result<String, AudioError> {
 }
 
 pub fn decode_to_samples(path: &str) -> Result<(Vec<f32>, u32), AudioError> {
-    let file = std::fs::File::open(path)
-        .map_err(|e| AudioError::Io(format!("Failed to open audio file '{path}': {e}")))?;
-    let media_source_stream = MediaSourceStream::new(Box::new(file), Default::default());
-
-    let mut hint = Hint::new();
-    if let Some(ext) = std::path::Path::new(path)
-        .extension()
-        .and_then(|e| e.to_str())
-    {
-        hint.with_extension(ext);
+    decode_window(path, DecodeWindow::Full)
+}
+
+/// Decode only `window` of the file to mono. Memory is bounded by the
+/// window, not the file, but the window itself is returned as one buffer:
+/// stratum-dsp needs the whole slice at once.
+pub fn decode_window(path: &str, window: DecodeWindow) -> Result<(Vec<f32>, u32), AudioError> {
+    let span = decode(SampleStream::open(path)?, window, None)?;
+    Ok((span.samples, span.sample_rate))
+}
+
+/// Decode to mono like [`decode_to_samples`], metering peaks, clipping and DC
+/// offset on the original channels along the way.
+pub fn decode_with_quality(path: &str) -> Result<(Vec<f32>, u32, SignalQuality), AudioError> {
+    let mut meter = QualityMeter::new();
+    let span = decode(
+        SampleStream::open(path)?,
+        DecodeWindow::Full,
+        Some(&mut meter),
+    )?;
+    Ok((span.samples, span.sample_rate, meter.finish()))
+}
+
+/// A whole track decoded to mono, with the measurements that need the
+/// original channels taken during the same decode.
+pub struct DecodedAudio {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub quality: SignalQuality,
+    /// K-weighted energy per loudness segment, summed over the channels.
+    loudness_segments: Vec<f64>,
+}
+
+/// Decode the whole file once for every analyser: mono samples for stratum,
+/// structure and the native features, with signal quality and per-channel
+/// loudness metered on the original channels.
+pub fn decode_for_analysis(path: &str) -> Result<DecodedAudio, AudioError> {
+    let mut stream = SampleStream::open(path)?;
+    let sample_rate = stream.sample_rate();
+    let mut meter = AnalysisMeter {
+        quality: QualityMeter::new(),
+        loudness: LoudnessMeter::new(sample_rate),
+    };
+    let samples = decode_full(&mut stream, Some(&mut meter))?;
+    if samples.is_empty() {
+        return Err(AudioError::Decode("Decoded zero audio samples".to_string()));
     }
+    Ok(DecodedAudio {
+        samples,
+        sample_rate,
+        quality: meter.quality.finish(),
+        loudness_segments: meter.loudness.finish(),
+    })
+}
 
-    let probed = get_probe()
-        .format(
-            &hint,
-            media_source_stream,
-            &FormatOptions::default(),
-            &MetadataOptions::default(),
-        )
-        .map_err(|e| AudioError::Decode(format!("Failed to probe audio format: {e}")))?;
+/// Meter peaks, clipping and DC offset chunk by chunk without keeping the
+/// decoded samples.
+pub fn measure_signal_quality(path: &str) -> Result<SignalQuality, AudioError> {
+    let mut stream = SampleStream::open(path)?;
+    let mut meter = QualityMeter::new();
+    let mut decoded = 0;
+    while let Some(chunk) = stream.next_chunk(Some(&mut meter))? {
+        decoded += chunk.len();
+    }
+    if decoded == 0 {
+        return Err(AudioError::Decode("Decoded zero audio samples".to_string()));
+    }
+    Ok(meter.finish())
+}
 
-    let mut format_reader = probed.format;
+/// Part of a file to decode.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DecodeWindow {
+    #[default]
+    Full,
+    /// The first `secs` seconds.
+    First(f64),
+    /// The last `secs` seconds.
+    Last(f64),
+}
 
-    let track = format_reader
-        .tracks()
-        .iter()
-        .find(|t| t.codec_params.codec != symphonia::core::codecs::CODEC_TYPE_NULL)
-        .ok_or_else(|| AudioError::Decode("No audio track found in file".to_string()))?;
-
-    let track_id = track.id;
-    let sample_rate = track
-        .codec_params
-        .sample_rate
-        .ok_or_else(|| AudioError::Decode("Audio track has no sample rate".to_string()))?;
-
-    let mut decoder = symphonia::default::get_codecs()
-        .make(&track.codec_params, &DecoderOptions::default())
-        .map_err(|e| AudioError::Decode(format!("Failed to create decoder: {e}")))?;
-
-    let mut all_samples: Vec<f32> = Vec::new();
-    let mut decode_warning_count: u64 = 0;
-
-    loop {
-        let packet = match format_reader.next_packet() {
-            Ok(p) => p,
-            Err(symphonia::core::errors::Error::IoError(ref e))
-                if e.kind() == std::io::ErrorKind::UnexpectedEof =>
-            {
-                break;
+impl DecodeWindow {
+    /// Seconds of a `duration_secs` track this window covers.
+    pub fn span_secs(self, duration_secs: f64) -> f64 {
+        match self {
+            DecodeWindow::Full => duration_secs,
+            DecodeWindow::First(secs) | DecodeWindow::Last(secs) => {
+                secs.max(0.0).min(duration_secs)
             }
-            Err(e) => return Err(AudioError::Decode(format!("Error reading packet: {e}"))),
-        };
-
-        if packet.track_id() != track_id {
-            continue;
         }
+    }
+}
 
-        let decoded = match decoder.decode(&packet) {
-            Ok(d) => d,
-            Err(symphonia::core::errors::Error::DecodeError(_)) => {
-                decode_warning_count += 1;
-                continue;
+/// Size of `secs` of decoded mono `f32` samples.
+pub fn decoded_bytes(secs: f64, sample_rate: u32) -> u64 {
+    (secs.max(0.0) * f64::from(sample_rate)) as u64 * std::mem::size_of::<f32>() as u64
+}
+
+/// Decoded size of `window` from the container header, without decoding.
+/// `None` when the file can't be probed or doesn't declare its length.
+pub fn estimated_decoded_bytes(path: &str, window: DecodeWindow) -> Option<u64> {
+    let stream = SampleStream::open(path).ok()?;
+    let duration = stream.duration_secs()?;
+    Some(decoded_bytes(
+        window.span_secs(duration),
+        stream.sample_rate(),
+    ))
+}
+
+/// Seek this far before a `Last` window starts; seeks in compressed formats
+/// land on packet boundaries and the excess is trimmed afterwards.
+const TAIL_SEEK_MARGIN_SECS: f64 = 1.0;
+
+/// Decode the rest of `stream`, reserving the buffer once rather than letting
+/// it double its way up.
+fn decode_full(
+    stream: &mut SampleStream,
+    mut meter: Option<&mut dyn SampleMeter>,
+) -> Result<Vec<f32>, AudioError> {
+    let mut samples = Vec::new();
+    if let Some(n_frames) = stream.n_frames {
+        let _ = samples.try_reserve_exact(n_frames as usize);
+    }
+    while let Some(chunk) = stream.next_chunk(reborrow(&mut meter))? {
+        samples.extend_from_slice(&chunk);
+    }
+    Ok(samples)
+}
+
+/// Samples decoded from one [`DecodeWindow`] of a file.
+struct DecodedSpan {
+    samples: Vec<f32>,
+    sample_rate: u32,
+    /// Track time of the first sample.
+    start_secs: f64,
+    /// Track frame where metering started; a `Last` window seeks past the
+    /// top before the meter sees anything.
+    metered_from: u64,
+    /// Length of the whole track, decoded or from the container header.
+    track_secs: Option<f64>,
+    /// Whether the span turned out to be the whole track.
+    whole: bool,
+}
+
+fn decode(
+    mut stream: SampleStream,
+    window: DecodeWindow,
+    mut meter: Option<&mut dyn SampleMeter>,
+) -> Result<DecodedSpan, AudioError> {
+    let sample_rate = stream.sample_rate();
+    let fs = f64::from(sample_rate);
+    let window_len = |secs: f64| (secs.max(0.0) * fs) as usize;
+    let mut metered_from = 0;
+
+    let (samples, start_secs, track_secs, whole) = match window {
+        DecodeWindow::Full => {
+            let samples = decode_full(&mut stream, meter)?;
+            let secs = samples.len() as f64 / fs;
+            (samples, 0.0, Some(secs), true)
+        }
+        DecodeWindow::First(secs) => {
+            let limit = window_len(secs);
+            let mut samples = Vec::new();
+            let mut ended = false;
+            while samples.len() < limit {
+                match stream.next_chunk(reborrow(&mut meter))? {
+                    Some(chunk) => samples.extend_from_slice(&chunk),
+                    None => {
+                        ended = true;
+                        break;
+                    }
+                }
             }
-            Err(symphonia::core::errors::Error::ResetRequired) => {
-                decoder.reset();
-                continue;
+            samples.truncate(limit);
+            let track_secs = if ended {
+                Some(samples.len() as f64 / fs)
+            } else {
+                stream.duration_secs()
+            };
+            (samples, 0.0, track_secs, ended)
+        }
+        DecodeWindow::Last(secs) => {
+            let limit = window_len(secs);
+            if let Some(duration) = stream.duration_secs() {
+                let start = duration - secs - TAIL_SEEK_MARGIN_SECS;
+                if start > 0.0 && !stream.seek(start) {
+                    // Fall back to decoding from the top; the rolling buffer
+                    // below still keeps memory to the window.
+                    stream = SampleStream::open(&stream.path)?;
+                }
             }
-            Err(e) => return Err(AudioError::Decode(format!("Decode error: {e}"))),
-        };
+            metered_from = stream.position;
+            let mut tail = std::collections::VecDeque::new();
+            while let Some(chunk) = stream.next_chunk(reborrow(&mut meter))? {
+                tail.extend(chunk);
+                if tail.len() > limit {
+                    tail.drain(..tail.len() - limit);
+                }
+            }
+            let start = stream.position.saturating_sub(tail.len() as u64);
+            let track_secs = stream.position as f64 / fs;
+            (
+                Vec::from(tail),
+                start as f64 / fs,
+                Some(track_secs),
+                start == 0,
+            )
+        }
+    };
+
+    if samples.is_empty() {
+        return Err(AudioError::Decode("Decoded zero audio samples".to_string()));
+    }
+
+    Ok(DecodedSpan {
+        samples,
+        sample_rate,
+        start_secs,
+        metered_from,
+        track_secs,
+        whole,
+    })
+}
+
+/// Decoder that yields the first audio track as mono chunks, one packet at a
+/// time. Signal quality and the native features fold over the chunks
+/// directly; the windowed decodes collect them into one buffer for the
+/// analysers that need the whole slice.
+pub struct SampleStream {
+    path: String,
+    format_reader: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    sample_rate: u32,
+    n_frames: Option<u64>,
+    time_base: Option<TimeBase>,
+    /// Track frame of the next decoded sample.
+    position: u64,
+    decode_warning_count: u64,
+}
+
+impl SampleStream {
+    pub fn open(path: &str) -> Result<Self, AudioError> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| AudioError::Io(format!("Failed to open audio file '{path}': {e}")))?;
+        let media_source_stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+        let mut hint = Hint::new();
+        if let Some(ext) = std::path::Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+        {
+            hint.with_extension(ext);
+        }
+
+        let probed = get_probe()
+            .format(
+                &hint,
+                media_source_stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(|e| AudioError::Decode(format!("Failed to probe audio format: {e}")))?;
 
-        let mono = decode_buffer_to_mono(&decoded);
-        all_samples.extend_from_slice(&mono);
+        let format_reader = probed.format;
+
+        let track = format_reader
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != symphonia::core::codecs::CODEC_TYPE_NULL)
+            .ok_or_else(|| AudioError::Decode("No audio track found in file".to_string()))?;
+
+        let track_id = track.id;
+        let sample_rate = track
+            .codec_params
+            .sample_rate
+            .ok_or_else(|| AudioError::Decode("Audio track has no sample rate".to_string()))?;
+        let n_frames = track.codec_params.n_frames;
+        let time_base = track.codec_params.time_base;
+
+        if track.codec_params.codec == CODEC_TYPE_OPUS {
+            return Err(AudioError::Decode(
+                "Opus audio can't be analyzed: the built-in decoder has no Opus support"
+                    .to_string(),
+            ));
+        }
+        let decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .map_err(|e| AudioError::Decode(format!("Failed to create decoder: {e}")))?;
+
+        Ok(Self {
+            path: path.to_string(),
+            format_reader,
+            decoder,
+            track_id,
+            sample_rate,
+            n_frames,
+            time_base,
+            position: 0,
+            decode_warning_count: 0,
+        })
     }
 
-    if decode_warning_count > 0 {
-        tracing::debug!("{path}: {decode_warning_count} decode warnings (frames skipped)");
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
     }
 
-    if all_samples.is_empty() {
-        return Err(AudioError::Decode("Decoded zero audio samples".to_string()));
+    /// Track length from the container header, when it declares one.
+    pub fn duration_secs(&self) -> Option<f64> {
+        self.n_frames
+            .map(|frames| frames as f64 / f64::from(self.sample_rate))
     }
 
-    Ok((all_samples, sample_rate))
+    /// Move to `secs` into the track. `false` when the format can't seek, in
+    /// which case the stream position is unspecified.
+    fn seek(&mut self, secs: f64) -> bool {
+        let seeked = self.format_reader.seek(
+            SeekMode::Coarse,
+            SeekTo::Time {
+                time: Time::from(secs),
+                track_id: Some(self.track_id),
+            },
+        );
+        self.decoder.reset();
+        let Ok(seeked) = seeked else {
+            return false;
+        };
+        self.position = match self.time_base {
+            Some(time_base) => {
+                let time = time_base.calc_time(seeked.actual_ts);
+                ((time.seconds as f64 + time.frac) * f64::from(self.sample_rate)).round() as u64
+            }
+            None => seeked.actual_ts,
+        };
+        true
+    }
+
+    /// Decode the next packet to mono, metering the original channels when
+    /// `meter` is given. `None` at the end of the stream.
+    pub(crate) fn next_chunk(
+        &mut self,
+        mut meter: Option<&mut dyn SampleMeter>,
+    ) -> Result<Option<Vec<f32>>, AudioError> {
+        loop {
+            let packet = match self.format_reader.next_packet() {
+                Ok(p) => p,
+                Err(symphonia::core::errors::Error::IoError(ref e))
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    if self.decode_warning_count > 0 {
+                        tracing::debug!(
+                            "{}: {} decode warnings (frames skipped)",
+                            self.path,
+                            self.decode_warning_count
+                        );
+                        self.decode_warning_count = 0;
+                    }
+                    return Ok(None);
+                }
+                Err(e) => return Err(AudioError::Decode(format!("Error reading packet: {e}"))),
+            };
+
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(d) => d,
+                Err(symphonia::core::errors::Error::DecodeError(_)) => {
+                    self.decode_warning_count += 1;
+                    continue;
+                }
+                Err(symphonia::core::errors::Error::ResetRequired) => {
+                    self.decoder.reset();
+                    continue;
+                }
+                Err(e) => return Err(AudioError::Decode(format!("Decode error: {e}"))),
+            };
+
+            let chunk = decode_buffer_to_mono(&decoded, reborrow(&mut meter));
+            self.position += chunk.len() as u64;
+            return Ok(Some(chunk));
+        }
+    }
 }
 
-fn decode_buffer_to_mono(buf: &AudioBufferRef) -> Vec<f32> {
+fn decode_buffer_to_mono(buf: &AudioBufferRef, meter: Option<&mut dyn SampleMeter>) -> Vec<f32> {
     match buf {
-        AudioBufferRef::F32(b) => downmix_to_mono(b.planes().planes(), |&v| v),
-        AudioBufferRef::F64(b) => downmix_to_mono(b.planes().planes(), |&v| v as f32),
-        AudioBufferRef::S8(b) => downmix_to_mono(b.planes().planes(), |&v| v as f32 / 128.0),
-        AudioBufferRef::S16(b) => downmix_to_mono(b.planes().planes(), |&v| v as f32 / 32768.0),
+        AudioBufferRef::F32(b) => downmix_to_mono(b.planes().planes(), meter, |&v| v),
+        AudioBufferRef::F64(b) => downmix_to_mono(b.planes().planes(), meter, |&v| v as f32),
+        AudioBufferRef::S8(b) => downmix_to_mono(b.planes().planes(), meter, |&v| v as f32 / 128.0),
+        AudioBufferRef::S16(b) => {
+            downmix_to_mono(b.planes().planes(), meter, |&v| v as f32 / 32768.0)
+        }
         AudioBufferRef::S24(b) => {
-            downmix_to_mono(b.planes().planes(), |v| v.inner() as f32 / 8388608.0)
+            downmix_to_mono(b.planes().planes(), meter, |v| v.inner() as f32 / 8388608.0)
         }
         AudioBufferRef::S32(b) => {
-            downmix_to_mono(b.planes().planes(), |&v| v as f32 / 2147483648.0)
+            downmix_to_mono(b.planes().planes(), meter, |&v| v as f32 / 2147483648.0)
         }
         AudioBufferRef::U8(b) => {
-            downmix_to_mono(b.planes().planes(), |&v| (v as f32 - 128.0) / 128.0)
+            downmix_to_mono(b.planes().planes(), meter, |&v| (v as f32 - 128.0) / 128.0)
         }
-        AudioBufferRef::U16(b) => {
-            downmix_to_mono(b.planes().planes(), |&v| (v as f32 - 32768.0) / 32768.0)
-        }
-        AudioBufferRef::U24(b) => downmix_to_mono(b.planes().planes(), |v| {
+        AudioBufferRef::U16(b) => downmix_to_mono(b.planes().planes(), meter, |&v| {
+            (v as f32 - 32768.0) / 32768.0
+        }),
+        AudioBufferRef::U24(b) => downmix_to_mono(b.planes().planes(), meter, |v| {
             (v.inner() as f32 - 8388608.0) / 8388608.0
         }),
-        AudioBufferRef::U32(b) => downmix_to_mono(b.planes().planes(), |&v| {
+        AudioBufferRef::U32(b) => downmix_to_mono(b.planes().planes(), meter, |&v| {
             (v as f64 - 2147483648.0) as f32 / 2147483648.0
         }),
     }
 }
 
-fn downmix_to_mono<T, F>(planes: &[&[T]], convert: F) -> Vec<f32>
+fn downmix_to_mono<T, F>(
+    planes: &[&[T]],
+    meter: Option<&mut dyn SampleMeter>,
+    convert: F,
+) -> Vec<f32>
 where
     F: Fn(&T) -> f32,
 {
@@ -451,6 +832,14 @@ where
     let num_channels = planes.len();
     let num_frames = planes.iter().map(|ch| ch.len()).min().unwrap_or(0);
 
+    if let Some(meter) = meter {
+        for (channel, plane) in planes.iter().enumerate() {
+            for sample in plane.iter().take(num_frames) {
+                meter.push(channel, convert(sample));
+            }
+        }
+    }
+
     if num_channels == 1 {
         return planes[0].iter().take(num_frames).map(&convert).collect();
     }
@@ -464,6 +853,208 @@ where
         .collect()
 }
 
+/// Meter fed every decoded sample of the original channels before downmixing.
+pub(crate) trait SampleMeter {
+    fn push(&mut self, channel: usize, sample: f32);
+}
+
+/// Reborrow an optional meter for a single call.
+fn reborrow<'a>(meter: &'a mut Option<&mut dyn SampleMeter>) -> Option<&'a mut dyn SampleMeter> {
+    meter.as_mut().map(|m| &mut **m as &mut dyn SampleMeter)
+}
+
+/// Quality and loudness meters driven by one decode.
+struct AnalysisMeter {
+    quality: QualityMeter,
+    loudness: LoudnessMeter,
+}
+
+impl SampleMeter for AnalysisMeter {
+    fn push(&mut self, channel: usize, sample: f32) {
+        self.quality.push(channel, sample);
+        self.loudness.push(channel, sample);
+    }
+}
+
+/// Samples at or above this magnitude are at full scale.
+const CLIP_LEVEL: f32 = 0.999;
+/// Consecutive full-scale samples that make a clipped run; single samples
+/// touching full scale are normal for limited masters.
+const CLIP_RUN_MIN_SAMPLES: usize = 3;
+/// BS.1770 true-peak oversampling factor.
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+/// Interpolation filter length per oversampled phase.
+const TRUE_PEAK_TAPS: usize = 12;
+/// Level reported for digital silence.
+const QUALITY_FLOOR_DB: f64 = -120.0;
+/// Any clipped run counts: the waveform was flattened at full scale.
+pub const CLIPPING_MIN_RUNS: u64 = 1;
+/// True peaks above this overshoot full scale between samples.
+pub const LOW_HEADROOM_DBTP: f64 = 0.0;
+/// Mean level (fraction of full scale) that counts as a DC offset, about -46 dBFS.
+pub const DC_OFFSET_MAX: f64 = 0.005;
+
+/// Peak, clipping and DC measurements taken on the decoded channels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignalQuality {
+    pub analyzer_version: String,
+    pub sample_peak_dbfs: f64,
+    /// 4x-oversampled peak, catching overs between samples.
+    pub true_peak_dbtp: f64,
+    /// Runs of consecutive full-scale samples, summed over channels.
+    pub clipped_runs: u64,
+    pub clipped_samples: u64,
+    /// Largest per-channel mean, as a signed fraction of full scale.
+    pub dc_offset: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum QualityFlag {
+    Clipping,
+    LowHeadroom,
+    DcOffset,
+}
+
+impl SignalQuality {
+    /// Problems worth fixing before the track reaches a mixer.
+    pub fn flags(&self) -> Vec<QualityFlag> {
+        let mut flags = Vec::new();
+        if self.clipped_runs >= CLIPPING_MIN_RUNS {
+            flags.push(QualityFlag::Clipping);
+        } else if self.true_peak_dbtp > LOW_HEADROOM_DBTP {
+            flags.push(QualityFlag::LowHeadroom);
+        }
+        if self.dc_offset.abs() >= DC_OFFSET_MAX {
+            flags.push(QualityFlag::DcOffset);
+        }
+        flags
+    }
+}
+
+#[derive(Default)]
+struct ChannelMeter {
+    peak: f32,
+    true_peak: f32,
+    sum: f64,
+    count: u64,
+    run: usize,
+    clipped_runs: u64,
+    clipped_samples: u64,
+    history: [f32; TRUE_PEAK_TAPS],
+    next: usize,
+}
+
+impl ChannelMeter {
+    fn close_run(&mut self) {
+        if self.run >= CLIP_RUN_MIN_SAMPLES {
+            self.clipped_runs += 1;
+            self.clipped_samples += self.run as u64;
+        }
+        self.run = 0;
+    }
+}
+
+/// Streaming meter fed one sample at a time per channel while decoding.
+pub(crate) struct QualityMeter {
+    /// Windowed-sinc coefficients for each fractional position between samples.
+    phases: Vec<[f32; TRUE_PEAK_TAPS]>,
+    channels: Vec<ChannelMeter>,
+}
+
+impl QualityMeter {
+    pub(crate) fn new() -> Self {
+        let half = TRUE_PEAK_TAPS as f64 / 2.0;
+        let phases = (1..TRUE_PEAK_OVERSAMPLING)
+            .map(|phase| {
+                let offset = phase as f64 / TRUE_PEAK_OVERSAMPLING as f64;
+                let mut taps = [0.0_f64; TRUE_PEAK_TAPS];
+                for (k, tap) in taps.iter_mut().enumerate() {
+                    // Distance from the interpolated point, which sits
+                    // `offset` past the middle of the history.
+                    let t = half - 1.0 + offset - k as f64;
+                    let sinc = if t == 0.0 {
+                        1.0
+                    } else {
+                        (std::f64::consts::PI * t).sin() / (std::f64::consts::PI * t)
+                    };
+                    let window = 0.5 + 0.5 * (std::f64::consts::PI * t / half).cos();
+                    *tap = sinc * window;
+                }
+                let gain: f64 = taps.iter().sum();
+                taps.map(|tap| (tap / gain) as f32)
+            })
+            .collect();
+        Self {
+            phases,
+            channels: Vec::new(),
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> SignalQuality {
+        let amplitude_db =
+            |amplitude: f32| (20.0 * f64::from(amplitude).log10()).max(QUALITY_FLOOR_DB);
+        for meter in &mut self.channels {
+            meter.close_run();
+        }
+        let peak = self.channels.iter().map(|m| m.peak).fold(0.0, f32::max);
+        let true_peak = self
+            .channels
+            .iter()
+            .map(|m| m.true_peak.max(m.peak))
+            .fold(0.0, f32::max);
+        let dc_offset = self
+            .channels
+            .iter()
+            .filter(|m| m.count > 0)
+            .map(|m| m.sum / m.count as f64)
+            .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+            .unwrap_or(0.0);
+        SignalQuality {
+            analyzer_version: QUALITY_VERSION.to_string(),
+            sample_peak_dbfs: round_to_hundredths(amplitude_db(peak)),
+            true_peak_dbtp: round_to_hundredths(amplitude_db(true_peak)),
+            clipped_runs: self.channels.iter().map(|m| m.clipped_runs).sum(),
+            clipped_samples: self.channels.iter().map(|m| m.clipped_samples).sum(),
+            dc_offset: (dc_offset * 1e6).round() / 1e6,
+        }
+    }
+}
+
+impl SampleMeter for QualityMeter {
+    fn push(&mut self, channel: usize, sample: f32) {
+        if channel >= self.channels.len() {
+            self.channels
+                .resize_with(channel + 1, ChannelMeter::default);
+        }
+        let meter = &mut self.channels[channel];
+        let magnitude = sample.abs();
+        meter.peak = meter.peak.max(magnitude);
+        meter.sum += f64::from(sample);
+        meter.count += 1;
+        if magnitude >= CLIP_LEVEL {
+            meter.run += 1;
+        } else {
+            meter.close_run();
+        }
+
+        meter.history[meter.next] = sample;
+        meter.next = (meter.next + 1) % TRUE_PEAK_TAPS;
+        for taps in &self.phases {
+            let interpolated: f32 = taps
+                .iter()
+                .enumerate()
+                .map(|(k, tap)| tap * meter.history[(meter.next + k) % TRUE_PEAK_TAPS])
+                .sum();
+            meter.true_peak = meter.true_peak.max(interpolated.abs());
+        }
+    }
+}
+
+fn round_to_hundredths(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
 /// Convert stratum-dsp's circle-of-fifths notation to standard Camelot.
 ///
 /// stratum-dsp uses its own numbering (A=major, B=minor, C=1).
@@ -488,6 +1079,98 @@ fn stratum_notation_to_camelot(stratum_notation: &str) -> String {
     format!("{camelot_num}{camelot_letter}")
 }
 
+/// Beats fitted when estimating the grid offset; the start of the track anchors the grid.
+const FIRST_BEAT_FIT_BEATS: usize = 64;
+/// Minimum phase coherence (mean resultant length, 0-1) for a usable offset.
+const FIRST_BEAT_MIN_COHERENCE: f64 = 0.5;
+
+/// Fit a constant-tempo grid to detected beat times and return the first grid
+/// beat (seconds, in `[0, 60/bpm)`) with its bar position (1-4).
+///
+/// Uses the circular mean of beat phases against the beat period, so individual
+/// missed or jittered onsets don't shift the grid. Returns `None` when the beats
+/// don't agree on a phase.
+pub(crate) fn detect_first_beat(beats: &[f32], downbeats: &[f32], bpm: f64) -> Option<(f64, u8)> {
+    if !bpm.is_finite() || bpm <= 0.0 || beats.is_empty() {
+        return None;
+    }
+    let period = 60.0 / bpm;
+    let fitted = &beats[..beats.len().min(FIRST_BEAT_FIT_BEATS)];
+    let (sin_sum, cos_sum) = fitted.iter().fold((0.0_f64, 0.0_f64), |(s, c), &t| {
+        let angle = std::f64::consts::TAU * (t as f64 / period);
+        (s + angle.sin(), c + angle.cos())
+    });
+    if sin_sum.hypot(cos_sum) / (fitted.len() as f64) < FIRST_BEAT_MIN_COHERENCE {
+        return None;
+    }
+    let phase = sin_sum.atan2(cos_sum).rem_euclid(std::f64::consts::TAU);
+    let offset = (phase / std::f64::consts::TAU * period) % period;
+
+    // Grid beat 0 sits at `offset`; the first downbeat is grid beat `k`.
+    let beat_in_bar = downbeats.first().map_or(1, |&downbeat| {
+        let k = ((downbeat as f64 - offset) / period).round() as i64;
+        ((-k).rem_euclid(4) + 1) as u8
+    });
+    Some((offset, beat_in_bar))
+}
+
+/// Bars at each end of a track that get their own tempo and key, so key
+/// modulations and tempo drift show up where tracks are mixed.
+pub const EDGE_WINDOW_BARS: usize = 32;
+
+/// Run stratum-dsp over `samples[start..end]`. `None` when the window is
+/// empty or too short for stratum to analyse.
+fn analyze_window(
+    samples: &[f32],
+    sample_rate: u32,
+    start: usize,
+    end: usize,
+) -> Option<WindowAnalysis> {
+    let window = samples.get(start..end).filter(|w| !w.is_empty())?;
+    let config = stratum_dsp::AnalysisConfig::default();
+    let result = stratum_dsp::analyze_audio(window, sample_rate, config).ok()?;
+    if !result.bpm.is_finite() || result.bpm <= 0.0 {
+        return None;
+    }
+    let confidence = stratum_dsp::compute_confidence(&result);
+    let fs = sample_rate as f64;
+    Some(WindowAnalysis {
+        start_sec: round_to_ms(start as f64 / fs),
+        end_sec: round_to_ms(end as f64 / fs),
+        bpm: result.bpm as f64,
+        bpm_confidence: confidence.bpm_confidence as f64,
+        key: result.key.name(),
+        key_camelot: stratum_notation_to_camelot(&result.key.numerical()),
+        key_confidence: confidence.key_confidence as f64,
+    })
+}
+
+/// Analyse the first and last `window_bars` bars at the track's overall
+/// tempo. Tracks shorter than both windows together have no separate edges.
+fn analyze_edges(
+    samples: &[f32],
+    sample_rate: u32,
+    bpm: f64,
+    window_bars: usize,
+) -> (Option<WindowAnalysis>, Option<WindowAnalysis>) {
+    if sample_rate == 0 || !bpm.is_finite() || bpm <= 0.0 {
+        return (None, None);
+    }
+    let window_len = (window_bars as f64 * 4.0 * 60.0 / bpm * sample_rate as f64) as usize;
+    if window_len == 0 || samples.len() < window_len * 2 {
+        return (None, None);
+    }
+    (
+        analyze_window(samples, sample_rate, 0, window_len),
+        analyze_window(
+            samples,
+            sample_rate,
+            samples.len() - window_len,
+            samples.len(),
+        ),
+    )
+}
+
 pub fn analyze_with_stratum(
     samples: &[f32],
     sample_rate: u32,
@@ -502,6 +1185,13 @@ pub fn analyze_with_stratum(
     let confidence = stratum_dsp::compute_confidence(&result);
 
     let duration_seconds = result.metadata.duration_seconds as f64;
+    let first_beat = detect_first_beat(
+        &result.beat_grid.beats,
+        &result.beat_grid.downbeats,
+        result.bpm as f64,
+    );
+    let (opening, closing) =
+        analyze_edges(samples, sample_rate, result.bpm as f64, EDGE_WINDOW_BARS);
 
     Ok(StratumResult {
         bpm: result.bpm as f64,
@@ -521,98 +1211,1499 @@ pub fn analyze_with_stratum(
             .map(|f| format!("{f:?}"))
             .collect(),
         warnings: result.metadata.confidence_warnings.clone(),
+        first_beat_sec: first_beat.map(|(offset, _)| offset),
+        first_beat_in_bar: first_beat.map(|(_, beat)| beat),
+        opening,
+        closing,
     })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::process::Stdio;
-
-    #[test]
-    fn stratum_result_serialization_round_trip() {
-        let result = StratumResult {
-            bpm: 128.0,
-            bpm_confidence: 0.95,
-            key: "Am".to_string(),
-            key_camelot: "8A".to_string(),
-            key_confidence: 0.88,
-            key_clarity: 0.72,
-            grid_stability: 0.91,
-            duration_seconds: 300.5,
-            processing_time_ms: 1234.5,
-            analyzer_version: "stratum-dsp-1.0.0".to_string(),
-            flags: vec!["MultimodalBpm".to_string()],
-            warnings: vec!["Low key clarity".to_string()],
-        };
+/// Whether a stratum-dsp or native analyzer version is for part of a track
+/// only.
+pub fn is_windowed_version(version: &str) -> bool {
+    version.contains(WINDOW_VERSION_MARKER)
+}
 
-        let json = serde_json::to_string(&result).expect("serialize should succeed");
-        let back: StratumResult = serde_json::from_str(&json).expect("deserialize should succeed");
+/// `version` marked as computed on the `label` `minutes` of a track only.
+fn windowed_version(version: &str, label: &str, minutes: f64) -> String {
+    format!("{version}{WINDOW_VERSION_MARKER}{label}-{minutes:.1}min")
+}
 
-        assert!((back.bpm - 128.0).abs() < f64::EPSILON);
-        assert!((back.bpm_confidence - 0.95).abs() < f64::EPSILON);
-        assert_eq!(back.key, "Am");
-        assert_eq!(back.key_camelot, "8A");
-        assert!((back.key_confidence - 0.88).abs() < f64::EPSILON);
-        assert!((back.key_clarity - 0.72).abs() < f64::EPSILON);
-        assert!((back.grid_stability - 0.91).abs() < f64::EPSILON);
-        assert!((back.duration_seconds - 300.5).abs() < f64::EPSILON);
-        assert!((back.processing_time_ms - 1234.5).abs() < f64::EPSILON);
-        assert_eq!(back.analyzer_version, "stratum-dsp-1.0.0");
-        assert_eq!(back.flags, vec!["MultimodalBpm"]);
-        assert_eq!(back.warnings, vec!["Low key clarity"]);
+/// Run stratum-dsp on `window` of the file, decoding only that window. The
+/// result is laid on the whole track's timeline: the grid is extended back to
+/// the track's first beat period, an edge the window doesn't share with the
+/// track is dropped, and `duration_seconds` is the track's length when known.
+/// The analyzer version records the window unless it covered the whole track.
+pub fn analyze_window_with_stratum(
+    path: &str,
+    window: DecodeWindow,
+) -> Result<StratumResult, AudioError> {
+    let span = decode(SampleStream::open(path)?, window, None)?;
+    let mut result = analyze_with_stratum(&span.samples, span.sample_rate)?;
+    if span.whole {
+        return Ok(result);
     }
+    let (label, secs) = match window {
+        DecodeWindow::Full => return Ok(result),
+        DecodeWindow::First(secs) => {
+            result.closing = None;
+            ("first", secs)
+        }
+        DecodeWindow::Last(secs) => {
+            result.opening = None;
+            if let Some(closing) = result.closing.as_mut() {
+                closing.start_sec = round_to_ms(closing.start_sec + span.start_secs);
+                closing.end_sec = round_to_ms(closing.end_sec + span.start_secs);
+            }
+            ("last", secs)
+        }
+    };
 
-    #[test]
-    fn stratum_notation_to_camelot_converts_all_major_keys() {
-        // stratum-dsp A (major) → standard Camelot B (major)
-        // number = (stratum + 6) % 12 + 1
-        assert_eq!(stratum_notation_to_camelot("1A"), "8B"); // C
-        assert_eq!(stratum_notation_to_camelot("2A"), "9B"); // G
-        assert_eq!(stratum_notation_to_camelot("3A"), "10B"); // D
-        assert_eq!(stratum_notation_to_camelot("4A"), "11B"); // A
-        assert_eq!(stratum_notation_to_camelot("5A"), "12B"); // E
-        assert_eq!(stratum_notation_to_camelot("6A"), "1B"); // B
-        assert_eq!(stratum_notation_to_camelot("7A"), "2B"); // F#
-        assert_eq!(stratum_notation_to_camelot("8A"), "3B"); // C#
-        assert_eq!(stratum_notation_to_camelot("9A"), "4B"); // G#
-        assert_eq!(stratum_notation_to_camelot("10A"), "5B"); // D#
-        assert_eq!(stratum_notation_to_camelot("11A"), "6B"); // A#
-        assert_eq!(stratum_notation_to_camelot("12A"), "7B"); // F
+    if let Some(offset) = result.first_beat_sec
+        && result.bpm > 0.0
+    {
+        let period = 60.0 / result.bpm;
+        let beats_back = ((span.start_secs + offset) / period).floor();
+        result.first_beat_sec = Some(span.start_secs + offset - beats_back * period);
+        result.first_beat_in_bar = result
+            .first_beat_in_bar
+            .map(|beat| ((i64::from(beat) - 1 - beats_back as i64).rem_euclid(4) + 1) as u8);
     }
-
-    #[test]
-    fn stratum_notation_to_camelot_converts_all_minor_keys() {
-        // stratum-dsp B (minor) → standard Camelot A (minor)
-        assert_eq!(stratum_notation_to_camelot("1B"), "8A"); // Am
-        assert_eq!(stratum_notation_to_camelot("2B"), "9A"); // Em
-        assert_eq!(stratum_notation_to_camelot("3B"), "10A"); // Bm
-        assert_eq!(stratum_notation_to_camelot("4B"), "11A"); // F#m
-        assert_eq!(stratum_notation_to_camelot("5B"), "12A"); // C#m
-        assert_eq!(stratum_notation_to_camelot("6B"), "1A"); // G#m
-        assert_eq!(stratum_notation_to_camelot("7B"), "2A"); // D#m
-        assert_eq!(stratum_notation_to_camelot("8B"), "3A"); // A#m
-        assert_eq!(stratum_notation_to_camelot("9B"), "4A"); // Fm
-        assert_eq!(stratum_notation_to_camelot("10B"), "5A"); // Cm
-        assert_eq!(stratum_notation_to_camelot("11B"), "6A"); // Gm
-        assert_eq!(stratum_notation_to_camelot("12B"), "7A"); // Dm
+    if let Some(track_secs) = span.track_secs {
+        result.duration_seconds = track_secs;
     }
+    let minutes = secs / 60.0;
+    result
+        .warnings
+        .push(format!("Analyzed the {label} {minutes:.1} min only"));
+    result.analyzer_version = windowed_version(&result.analyzer_version, label, minutes);
+    Ok(result)
+}
 
-    #[test]
-    fn stratum_notation_to_camelot_passes_through_invalid_input() {
-        assert_eq!(stratum_notation_to_camelot(""), "");
-        assert_eq!(stratum_notation_to_camelot("X"), "X");
-        assert_eq!(stratum_notation_to_camelot("0A"), "0A");
-        assert_eq!(stratum_notation_to_camelot("13A"), "13A");
+/// Frame size and hop for the native spectral features.
+const NATIVE_FRAME_SIZE: usize = 2048;
+const NATIVE_HOP_SIZE: usize = 512;
+/// Mean-square level below which a frame is treated as silence (-80 dBFS).
+const NATIVE_SILENCE_POWER: f64 = 1e-8;
+const MFCC_BANDS: usize = 40;
+const MFCC_COEFFICIENTS: usize = 13;
+const MFCC_MAX_FREQ: f64 = 11000.0;
+/// Kick-drum band whose share of each beat's energy drives rhythm regularity.
+const LOW_BAND_HZ: (f64, f64) = (20.0, 150.0);
+const TEMPO_MIN_BPM: f64 = 60.0;
+const TEMPO_MAX_BPM: f64 = 190.0;
+/// Centre of the log-Gaussian tempo prior that breaks half/double-time ties.
+const TEMPO_PRIOR_BPM: f64 = 120.0;
+/// Peaks closer than this are one onset.
+const MIN_ONSET_GAP_SECS: f64 = 0.05;
+/// Flux below this is numerical noise, not an onset (log-magnitude units).
+const MIN_ONSET_FLUX: f64 = 1.0;
+/// Tempo and rhythm regularity need at least this many onsets per second.
+const MIN_PULSE_ONSET_RATE: f64 = 0.5;
+/// EBU R128 block lengths, built from 100 ms segments.
+const LOUDNESS_SEGMENT_SECS: f64 = 0.1;
+const MOMENTARY_SEGMENTS: usize = 4;
+const SHORT_TERM_SEGMENTS: usize = 30;
+const LOUDNESS_ABSOLUTE_GATE: f64 = -70.0;
+const LOUDNESS_RELATIVE_GATE: f64 = -10.0;
+const LOUDNESS_RANGE_RELATIVE_GATE: f64 = -20.0;
+/// Detrended fluctuation analysis scales for danceability, as in Essentia.
+const DFA_FRAME_SECS: f64 = 0.01;
+const DFA_MIN_TAU_SECS: f64 = 0.31;
+const DFA_MAX_TAU_SECS: f64 = 8.8;
+const DFA_TAU_MULTIPLIER: f64 = 1.1;
+/// Top of Essentia's danceability scale; flat signals would exceed it.
+const DANCEABILITY_MAX: f64 = 3.0;
+
+/// Compute the Essentia feature set natively, so energy, brightness and
+/// rhythm scoring work on machines without the Python subprocess.
+///
+/// Loudness follows EBU R128, metered on the original channels during the
+/// decode with every channel weighted equally. Tempo, onsets and rhythm
+/// regularity come from a spectral-flux onset envelope; danceability is the
+/// inverse mean DFA exponent of frame energies, as Essentia computes it.
+/// Spectral contrast, dissonance and intensity have no native equivalent and
+/// are left empty.
+pub fn analyze_native_features(audio: &DecodedAudio) -> Result<EssentiaOutput, AudioError> {
+    let mut features = NativeFeatureMeter::new(audio.sample_rate)?;
+    features.push_buffered(&audio.samples);
+    features.finish(&audio.loudness_segments)
+}
+
+/// Native features and signal quality from one streamed decode of the whole
+/// file, as [`analyze_native_features`] computes them on a decoded buffer.
+/// Only per-frame summaries are kept, never the samples.
+pub fn measure_native_features(path: &str) -> Result<(EssentiaOutput, SignalQuality), AudioError> {
+    let mut stream = SampleStream::open(path)?;
+    let sample_rate = stream.sample_rate();
+    let mut features = NativeFeatureMeter::new(sample_rate)?;
+    let mut meter = AnalysisMeter {
+        quality: QualityMeter::new(),
+        loudness: LoudnessMeter::new(sample_rate),
+    };
+    while let Some(chunk) = stream.next_chunk(Some(&mut meter))? {
+        features.push(&chunk);
     }
+    if features.samples == 0 {
+        return Err(AudioError::Decode("Decoded zero audio samples".to_string()));
+    }
+    let output = features.finish(&meter.loudness.finish())?;
+    Ok((output, meter.quality.finish()))
+}
 
-    #[test]
+/// Native features of `window` of the file, decoding only that window;
+/// loudness keeps the segments metered inside it. The analyzer version
+/// records the window unless it covered the whole track. A full window is
+/// streamed rather than decoded into one buffer.
+pub fn analyze_native_window(
+    path: &str,
+    window: DecodeWindow,
+) -> Result<EssentiaOutput, AudioError> {
+    let (label, secs) = match window {
+        DecodeWindow::Full => return measure_native_features(path).map(|(features, _)| features),
+        DecodeWindow::First(secs) => ("first", secs),
+        DecodeWindow::Last(secs) => ("last", secs),
+    };
+    let stream = SampleStream::open(path)?;
+    let sample_rate = stream.sample_rate();
+    let mut features = NativeFeatureMeter::new(sample_rate)?;
+    let mut loudness = LoudnessMeter::new(sample_rate);
+    let span = decode(stream, window, Some(&mut loudness))?;
+
+    // Segments count from where metering started, before the window's
+    // first sample after a seek or fallback decode, and may run past its end.
+    let segment_len = loudness_segment_len(sample_rate);
+    let start = (span.start_secs * f64::from(sample_rate)).round() as u64;
+    let skip = (start.saturating_sub(span.metered_from) as f64 / segment_len as f64).round();
+    let segments = loudness.finish();
+    let in_window: Vec<f64> = segments
+        .into_iter()
+        .skip(skip as usize)
+        .take(span.samples.len() / segment_len)
+        .collect();
+
+    features.push_buffered(&span.samples);
+    let mut output = features.finish(&in_window)?;
+    if !span.whole {
+        output.analyzer_version = windowed_version(&output.analyzer_version, label, secs / 60.0);
+    }
+    Ok(output)
+}
+
+/// Native features folded over the mono signal chunk by chunk. Spectral
+/// frames and 10 ms DFA levels only need the samples they span, so a chunk
+/// is dropped once its frames are done; loudness is metered separately on
+/// the original channels.
+struct NativeFeatureMeter {
+    sample_rate: u32,
+    spectral: SpectralMeter,
+    /// Samples not yet covered by a whole spectral frame.
+    pending: Vec<f32>,
+    dfa_frame_len: usize,
+    dfa_frame: Vec<f32>,
+    /// RMS level of each complete DFA frame.
+    levels: Vec<f64>,
+    energy: f64,
+    samples: usize,
+}
+
+impl NativeFeatureMeter {
+    fn new(sample_rate: u32) -> Result<Self, AudioError> {
+        if sample_rate == 0 {
+            return Err(AudioError::Analysis(
+                "Sample rate must be non-zero".to_string(),
+            ));
+        }
+        let dfa_frame_len = ((sample_rate as f64 * DFA_FRAME_SECS).round() as usize).max(1);
+        Ok(Self {
+            sample_rate,
+            spectral: SpectralMeter::new(sample_rate),
+            pending: Vec::with_capacity(NATIVE_FRAME_SIZE * 2),
+            dfa_frame_len,
+            dfa_frame: Vec::with_capacity(dfa_frame_len),
+            levels: Vec::new(),
+            energy: 0.0,
+            samples: 0,
+        })
+    }
+
+    fn push(&mut self, chunk: &[f32]) {
+        self.samples += chunk.len();
+        for &sample in chunk {
+            self.energy += f64::from(sample) * f64::from(sample);
+            self.dfa_frame.push(sample);
+            if self.dfa_frame.len() == self.dfa_frame_len {
+                self.levels.push(frame_level(&self.dfa_frame));
+                self.dfa_frame.clear();
+            }
+        }
+
+        self.pending.extend_from_slice(chunk);
+        let mut start = 0;
+        while self.pending.len() - start >= NATIVE_FRAME_SIZE {
+            self.spectral
+                .push_frame(&self.pending[start..start + NATIVE_FRAME_SIZE]);
+            start += NATIVE_HOP_SIZE;
+        }
+        self.pending.drain(..start);
+    }
+
+    /// Push an already decoded buffer a slice at a time, so the pending
+    /// frame doesn't copy all of it.
+    fn push_buffered(&mut self, samples: &[f32]) {
+        for chunk in samples.chunks(NATIVE_FRAME_SIZE * 16) {
+            self.push(chunk);
+        }
+    }
+
+    /// Features of everything pushed, with loudness from the K-weighted
+    /// energy of each loudness segment.
+    fn finish(self, loudness_segments: &[f64]) -> Result<EssentiaOutput, AudioError> {
+        let sample_rate = self.sample_rate;
+        if self.samples < NATIVE_FRAME_SIZE * 4 {
+            return Err(AudioError::Analysis(
+                "Audio too short for feature analysis".to_string(),
+            ));
+        }
+
+        let loudness = ebu_r128_loudness(loudness_segments, loudness_segment_len(sample_rate));
+        let spectral = self.spectral.finish();
+        let frame_rate = sample_rate as f64 / NATIVE_HOP_SIZE as f64;
+        let duration_seconds = self.samples as f64 / sample_rate as f64;
+
+        let onset_rate =
+            count_onsets(&spectral.onset_envelope, frame_rate) as f64 / duration_seconds;
+        // Without a pulse of onsets the envelope's periodicity is noise.
+        let beat_period = (onset_rate >= MIN_PULSE_ONSET_RATE)
+            .then(|| estimate_beat_period(&spectral.onset_envelope, frame_rate))
+            .flatten();
+        let rhythm_regularity = beat_period.and_then(|period| {
+            rhythm_regularity(&spectral.onset_envelope, &spectral.low_band_ratio, period)
+        });
+
+        Ok(EssentiaOutput {
+            analyzer_version: NATIVE_FEATURES_VERSION.to_string(),
+            danceability: danceability(&self.levels),
+            loudness_integrated: loudness.integrated,
+            loudness_range: loudness.range,
+            dynamic_complexity: loudness.dynamic_complexity,
+            average_loudness: Some(self.energy.powf(0.67)),
+            bpm_essentia: beat_period.map(|period| 60.0 * frame_rate / period),
+            onset_rate: Some(onset_rate),
+            rhythm_regularity,
+            spectral_centroid_mean: spectral.centroid_mean,
+            mfcc_mean: spectral.mfcc_mean,
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct LoudnessSummary {
+    integrated: Option<f64>,
+    range: Option<f64>,
+    dynamic_complexity: Option<f64>,
+}
+
+/// Direct-form I biquad with its own state.
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 4],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            state: [0.0; 4],
+        }
+    }
+
+    fn tick(&mut self, x0: f64) -> f64 {
+        let [x1, x2, y1, y2] = self.state;
+        let y0 = self.b[0] * x0 + self.b[1] * x1 + self.b[2] * x2 - self.a[0] * y1 - self.a[1] * y2;
+        self.state = [x0, x1, y0, y1];
+        y0
+    }
+}
+
+/// BS.1770 K-weighting (high-shelf then high-pass) for any sample rate.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let fs = sample_rate as f64;
+
+    let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (std::f64::consts::PI * f0 / fs).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (std::f64::consts::PI * f0 / fs).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+    [shelf, high_pass]
+}
+
+/// Loudness of a mean-square level summed over the channels.
+fn power_to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+/// Samples per channel in one loudness segment.
+fn loudness_segment_len(sample_rate: u32) -> usize {
+    ((sample_rate as f64 * LOUDNESS_SEGMENT_SECS).round() as usize).max(1)
+}
+
+#[derive(Clone, Copy)]
+struct LoudnessChannel {
+    filters: [Biquad; 2],
+    energy: f64,
+    filled: usize,
+    segment: usize,
+}
+
+/// Streaming BS.1770 meter: K-weights each channel on its own and sums the
+/// channels' energy per 100 ms segment, so a mono file counts once and a
+/// stereo file's channels are metered as they are, not as a downmix.
+struct LoudnessMeter {
+    sample_rate: u32,
+    segment_len: usize,
+    channels: Vec<LoudnessChannel>,
+    segments: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            segment_len: loudness_segment_len(sample_rate),
+            channels: Vec::new(),
+            segments: Vec::new(),
+        }
+    }
+
+    /// Energy of each complete segment; a trailing partial segment is dropped.
+    fn finish(self) -> Vec<f64> {
+        self.segments
+    }
+}
+
+impl SampleMeter for LoudnessMeter {
+    fn push(&mut self, channel: usize, sample: f32) {
+        if channel >= self.channels.len() {
+            let fresh = LoudnessChannel {
+                filters: k_weighting(self.sample_rate),
+                energy: 0.0,
+                filled: 0,
+                segment: 0,
+            };
+            self.channels.resize(channel + 1, fresh);
+        }
+        let meter = &mut self.channels[channel];
+        let [shelf, high_pass] = &mut meter.filters;
+        meter.energy += high_pass.tick(shelf.tick(f64::from(sample))).powi(2);
+        meter.filled += 1;
+        if meter.filled == self.segment_len {
+            if meter.segment >= self.segments.len() {
+                self.segments.resize(meter.segment + 1, 0.0);
+            }
+            self.segments[meter.segment] += meter.energy;
+            meter.segment += 1;
+            meter.energy = 0.0;
+            meter.filled = 0;
+        }
+    }
+}
+
+/// Mean power of the blocks above the absolute gate and `relative_gate` LU
+/// below their own mean.
+fn gated_power(block_powers: &[f64], relative_gate: f64) -> Option<(f64, f64)> {
+    let above_absolute: Vec<f64> = block_powers
+        .iter()
+        .copied()
+        .filter(|&p| p > 0.0 && power_to_lufs(p) > LOUDNESS_ABSOLUTE_GATE)
+        .collect();
+    if above_absolute.is_empty() {
+        return None;
+    }
+    let mean = above_absolute.iter().sum::<f64>() / above_absolute.len() as f64;
+    let threshold = power_to_lufs(mean) + relative_gate;
+    let gated: Vec<f64> = above_absolute
+        .into_iter()
+        .filter(|&p| power_to_lufs(p) > threshold)
+        .collect();
+    (!gated.is_empty()).then(|| (gated.iter().sum::<f64>() / gated.len() as f64, threshold))
+}
+
+/// EBU R128 integrated loudness (LUFS), loudness range (LU, per EBU Tech
+/// 3342) and the mean deviation of momentary loudness from the integrated
+/// value (Essentia's dynamic complexity), from the K-weighted energy of each
+/// `segment_len`-sample segment.
+fn ebu_r128_loudness(segments: &[f64], segment_len: usize) -> LoudnessSummary {
+    let block_powers = |len: usize| -> Vec<f64> {
+        segments
+            .windows(len)
+            .map(|window| window.iter().sum::<f64>() / (len * segment_len) as f64)
+            .collect()
+    };
+
+    let momentary = block_powers(MOMENTARY_SEGMENTS);
+    let Some((integrated_power, _)) = gated_power(&momentary, LOUDNESS_RELATIVE_GATE) else {
+        return LoudnessSummary::default();
+    };
+    let integrated = power_to_lufs(integrated_power);
+
+    let audible: Vec<f64> = momentary
+        .iter()
+        .map(|&p| power_to_lufs(p))
+        .filter(|&l| l > LOUDNESS_ABSOLUTE_GATE)
+        .collect();
+    let dynamic_complexity =
+        audible.iter().map(|l| (l - integrated).abs()).sum::<f64>() / audible.len() as f64;
+
+    let short_term = block_powers(SHORT_TERM_SEGMENTS);
+    let range =
+        gated_power(&short_term, LOUDNESS_RANGE_RELATIVE_GATE).and_then(|(_, threshold)| {
+            let mut levels: Vec<f64> = short_term
+                .iter()
+                .map(|&p| power_to_lufs(p))
+                .filter(|&l| l > LOUDNESS_ABSOLUTE_GATE && l > threshold)
+                .collect();
+            if levels.len() < 2 {
+                return None;
+            }
+            levels.sort_by(f64::total_cmp);
+            let percentile = |p: f64| levels[((levels.len() - 1) as f64 * p).round() as usize];
+            Some(percentile(0.95) - percentile(0.10))
+        });
+
+    LoudnessSummary {
+        integrated: Some(integrated),
+        range,
+        dynamic_complexity: Some(dynamic_complexity),
+    }
+}
+
+struct SpectralSummary {
+    centroid_mean: Option<f64>,
+    mfcc_mean: Option<Vec<f64>>,
+    /// Half-wave rectified log-spectral flux per frame.
+    onset_envelope: Vec<f64>,
+    /// Share of each frame's energy in `LOW_BAND_HZ`.
+    low_band_ratio: Vec<f64>,
+}
+
+fn hz_to_mel(hz: f64) -> f64 {
+    2595.0 * (1.0 + hz / 700.0).log10()
+}
+
+fn mel_to_hz(mel: f64) -> f64 {
+    700.0 * (10f64.powf(mel / 2595.0) - 1.0)
+}
+
+/// Triangular mel filters over the power spectrum, each normalised to unit
+/// sum. Returns `(first bin, weights)` per band.
+fn mel_filterbank(sample_rate: u32) -> Vec<(usize, Vec<f64>)> {
+    let bin_hz = sample_rate as f64 / NATIVE_FRAME_SIZE as f64;
+    let max_mel = hz_to_mel(MFCC_MAX_FREQ.min(sample_rate as f64 / 2.0));
+    let edges: Vec<f64> = (0..MFCC_BANDS + 2)
+        .map(|i| mel_to_hz(max_mel * i as f64 / (MFCC_BANDS + 1) as f64))
+        .collect();
+    edges
+        .windows(3)
+        .map(|edge| {
+            let (low, centre, high) = (edge[0], edge[1], edge[2]);
+            let first = (low / bin_hz).ceil() as usize;
+            let last = ((high / bin_hz).floor() as usize).min(NATIVE_FRAME_SIZE / 2);
+            let mut weights: Vec<f64> = (first..=last)
+                .map(|bin| {
+                    let hz = bin as f64 * bin_hz;
+                    if hz <= centre {
+                        (hz - low) / (centre - low)
+                    } else {
+                        (high - hz) / (high - centre)
+                    }
+                    .max(0.0)
+                })
+                .collect();
+            let total: f64 = weights.iter().sum();
+            if total > 0.0 {
+                weights.iter_mut().for_each(|w| *w /= total);
+            }
+            (first, weights)
+        })
+        .collect()
+}
+
+/// Orthonormal DCT-II of log band energies.
+fn dct_coefficients(bands: &[f64]) -> [f64; MFCC_COEFFICIENTS] {
+    let n = bands.len() as f64;
+    std::array::from_fn(|k| {
+        let scale = if k == 0 {
+            (1.0 / n).sqrt()
+        } else {
+            (2.0 / n).sqrt()
+        };
+        scale
+            * bands
+                .iter()
+                .enumerate()
+                .map(|(i, b)| b * (std::f64::consts::PI * k as f64 * (i as f64 + 0.5) / n).cos())
+                .sum::<f64>()
+    })
+}
+
+/// Spectral features of each Hann-windowed frame: the onset envelope and
+/// low-band share per frame, centroid and MFCC sums over voiced frames.
+struct SpectralMeter {
+    fft: std::sync::Arc<dyn rustfft::Fft<f32>>,
+    window: Vec<f32>,
+    bin_hz: f64,
+    low_bins: std::ops::RangeInclusive<usize>,
+    filterbank: Vec<(usize, Vec<f64>)>,
+    buffer: Vec<rustfft::num_complex::Complex<f32>>,
+    power: Vec<f64>,
+    log_magnitude: Vec<f64>,
+    previous: Vec<f64>,
+    centroid_sum: f64,
+    mfcc_sum: [f64; MFCC_COEFFICIENTS],
+    voiced_frames: usize,
+    onset_envelope: Vec<f64>,
+    low_band_ratio: Vec<f64>,
+}
+
+impl SpectralMeter {
+    fn new(sample_rate: u32) -> Self {
+        let window = (0..NATIVE_FRAME_SIZE)
+            .map(|i| {
+                let phase = std::f32::consts::TAU * i as f32 / (NATIVE_FRAME_SIZE - 1) as f32;
+                0.5 - 0.5 * phase.cos()
+            })
+            .collect();
+        let bins = NATIVE_FRAME_SIZE / 2 + 1;
+        let bin_hz = sample_rate as f64 / NATIVE_FRAME_SIZE as f64;
+        Self {
+            fft: rustfft::FftPlanner::new().plan_fft_forward(NATIVE_FRAME_SIZE),
+            window,
+            bin_hz,
+            low_bins: (LOW_BAND_HZ.0 / bin_hz).ceil() as usize..=(LOW_BAND_HZ.1 / bin_hz) as usize,
+            filterbank: mel_filterbank(sample_rate),
+            buffer: vec![rustfft::num_complex::Complex::new(0.0, 0.0); NATIVE_FRAME_SIZE],
+            power: vec![0.0; bins],
+            log_magnitude: vec![0.0; bins],
+            previous: vec![0.0; bins],
+            centroid_sum: 0.0,
+            mfcc_sum: [0.0; MFCC_COEFFICIENTS],
+            voiced_frames: 0,
+            onset_envelope: Vec::new(),
+            low_band_ratio: Vec::new(),
+        }
+    }
+
+    /// Add one `NATIVE_FRAME_SIZE` frame, `NATIVE_HOP_SIZE` after the last.
+    fn push_frame(&mut self, frame: &[f32]) {
+        for (slot, (&sample, &w)) in self.buffer.iter_mut().zip(frame.iter().zip(&self.window)) {
+            *slot = rustfft::num_complex::Complex::new(sample * w, 0.0);
+        }
+        self.fft.process(&mut self.buffer);
+        for bin in 0..self.power.len() {
+            self.power[bin] = f64::from(self.buffer[bin].norm_sqr());
+            self.log_magnitude[bin] = self.power[bin].sqrt().ln_1p();
+        }
+
+        let flux: f64 = self
+            .log_magnitude
+            .iter()
+            .zip(&self.previous)
+            .map(|(now, before)| (now - before).max(0.0))
+            .sum();
+        self.onset_envelope.push(flux);
+        std::mem::swap(&mut self.previous, &mut self.log_magnitude);
+
+        let power = &self.power;
+        let total: f64 = power.iter().sum();
+        let frame_power = frame
+            .iter()
+            .map(|&s| f64::from(s) * f64::from(s))
+            .sum::<f64>()
+            / frame.len() as f64;
+        if frame_power < NATIVE_SILENCE_POWER || total <= 0.0 {
+            self.low_band_ratio.push(0.0);
+            return;
+        }
+        self.low_band_ratio
+            .push(power[self.low_bins.clone()].iter().sum::<f64>() / total);
+
+        let magnitude_sum: f64 = power.iter().map(|p| p.sqrt()).sum();
+        self.centroid_sum += power
+            .iter()
+            .enumerate()
+            .map(|(bin, p)| bin as f64 * self.bin_hz * p.sqrt())
+            .sum::<f64>()
+            / magnitude_sum;
+
+        let band_db: Vec<f64> = self
+            .filterbank
+            .iter()
+            .map(|(first, weights)| {
+                let energy: f64 = weights
+                    .iter()
+                    .zip(&power[*first..])
+                    .map(|(w, p)| w * p)
+                    .sum();
+                10.0 * (energy + 1e-10).log10()
+            })
+            .collect();
+        for (acc, c) in self.mfcc_sum.iter_mut().zip(dct_coefficients(&band_db)) {
+            *acc += c;
+        }
+        self.voiced_frames += 1;
+    }
+
+    fn finish(self) -> SpectralSummary {
+        let voiced_frames = self.voiced_frames;
+        let mean = |sum: f64| sum / voiced_frames as f64;
+        SpectralSummary {
+            centroid_mean: (voiced_frames > 0).then(|| mean(self.centroid_sum)),
+            mfcc_mean: (voiced_frames > 0)
+                .then(|| self.mfcc_sum.iter().map(|&c| mean(c)).collect()),
+            onset_envelope: self.onset_envelope,
+            low_band_ratio: self.low_band_ratio,
+        }
+    }
+}
+
+/// Moving average of `values` over `radius` frames either side.
+fn moving_average(values: &[f64], radius: usize) -> Vec<f64> {
+    let mut prefix = vec![0.0; values.len() + 1];
+    for (i, v) in values.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + v;
+    }
+    (0..values.len())
+        .map(|i| {
+            let (lo, hi) = (i.saturating_sub(radius), (i + radius + 1).min(values.len()));
+            (prefix[hi] - prefix[lo]) / (hi - lo) as f64
+        })
+        .collect()
+}
+
+/// Peaks of the onset envelope that stand out from their surroundings.
+fn count_onsets(envelope: &[f64], frame_rate: f64) -> usize {
+    if envelope.is_empty() {
+        return 0;
+    }
+    let global_mean = envelope.iter().sum::<f64>() / envelope.len() as f64;
+    let local_mean = moving_average(envelope, (frame_rate * 0.25).round() as usize);
+    let radius = (frame_rate * MIN_ONSET_GAP_SECS).ceil() as usize;
+    (0..envelope.len())
+        .filter(|&i| {
+            let (before, after) = (
+                &envelope[i.saturating_sub(radius)..i],
+                &envelope[i + 1..(i + radius + 1).min(envelope.len())],
+            );
+            // First maximum of its neighbourhood, well above the local level.
+            before.iter().all(|&v| v < envelope[i])
+                && after.iter().all(|&v| v <= envelope[i])
+                && envelope[i] > (local_mean[i] * 1.5 + global_mean * 0.1).max(MIN_ONSET_FLUX)
+        })
+        .count()
+}
+
+/// Beat period in frames from the onset envelope's autocorrelation, weighted
+/// towards `TEMPO_PRIOR_BPM`.
+fn estimate_beat_period(envelope: &[f64], frame_rate: f64) -> Option<f64> {
+    let min_lag = (60.0 * frame_rate / TEMPO_MAX_BPM).floor() as usize;
+    let max_lag = (60.0 * frame_rate / TEMPO_MIN_BPM).ceil() as usize;
+    if envelope.len() < max_lag * 4 || min_lag < 2 {
+        return None;
+    }
+    let mean = envelope.iter().sum::<f64>() / envelope.len() as f64;
+    let centred: Vec<f64> = envelope.iter().map(|v| v - mean).collect();
+    let autocorrelation = |lag: usize| -> f64 {
+        centred
+            .iter()
+            .zip(&centred[lag..])
+            .map(|(a, b)| a * b)
+            .sum::<f64>()
+            / (centred.len() - lag) as f64
+    };
+    let prior_lag = 60.0 * frame_rate / TEMPO_PRIOR_BPM;
+    let weighted = |lag: usize| -> f64 {
+        let octaves = (lag as f64 / prior_lag).log2();
+        autocorrelation(lag) * (-0.5 * (octaves / 0.9).powi(2)).exp()
+    };
+
+    let scores: Vec<f64> = (min_lag - 1..=max_lag + 1).map(weighted).collect();
+    let best = (1..scores.len() - 1).max_by(|&a, &b| scores[a].total_cmp(&scores[b]))?;
+    if scores[best] <= 0.0 {
+        return None;
+    }
+    // Parabolic interpolation around the peak for sub-frame precision.
+    let (left, centre, right) = (scores[best - 1], scores[best], scores[best + 1]);
+    let denominator = left - 2.0 * centre + right;
+    let shift = if denominator.abs() > f64::EPSILON {
+        (0.5 * (left - right) / denominator).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    Some((min_lag - 1 + best) as f64 + shift)
+}
+
+/// How much stronger the low band is on the strongest beat of the bar than on
+/// beats overall, with beats placed at the phase where onsets line up best.
+/// Near 1.0 for flat or irregular grooves; higher for a steady four-on-the-floor
+/// downbeat.
+fn rhythm_regularity(envelope: &[f64], low_band_ratio: &[f64], period: f64) -> Option<f64> {
+    let beat_frames = |phase: f64| {
+        (0..)
+            .map(move |k| (phase + k as f64 * period).round() as usize)
+            .take_while(|&frame| frame < envelope.len())
+    };
+    let phase = (0..period.ceil() as usize)
+        .map(|p| p as f64)
+        .max_by(|&a, &b| {
+            let strength = |phase: f64| beat_frames(phase).map(|f| envelope[f]).sum::<f64>();
+            strength(a).total_cmp(&strength(b))
+        })?;
+    let ratios: Vec<f64> = beat_frames(phase).map(|f| low_band_ratio[f]).collect();
+    if ratios.len() < 8 {
+        return None;
+    }
+    let overall = ratios.iter().sum::<f64>() / ratios.len() as f64;
+    if overall <= 0.0 {
+        return None;
+    }
+    (0..4)
+        .map(|bar_position| {
+            let beats: Vec<f64> = ratios
+                .iter()
+                .skip(bar_position)
+                .step_by(4)
+                .copied()
+                .collect();
+            beats.iter().sum::<f64>() / beats.len() as f64
+        })
+        .max_by(f64::total_cmp)
+        .map(|strongest| strongest / overall)
+}
+
+/// RMS deviation of one DFA frame from its own mean.
+fn frame_level(frame: &[f32]) -> f64 {
+    let mean = frame.iter().map(|&s| f64::from(s)).sum::<f64>() / frame.len() as f64;
+    (frame
+        .iter()
+        .map(|&s| (f64::from(s) - mean).powi(2))
+        .sum::<f64>()
+        / frame.len() as f64)
+        .sqrt()
+}
+
+/// Danceability as the inverse of the mean detrended-fluctuation exponent of
+/// 10 ms frame levels (Streich & Herrera), capped to Essentia's 0-3 scale.
+fn danceability(levels: &[f64]) -> Option<f64> {
+    let mean_level = levels.iter().sum::<f64>() / levels.len().max(1) as f64;
+    // Integrated profile and prefix sums for O(1) window regressions.
+    let mut profile = Vec::with_capacity(levels.len());
+    let mut total = 0.0;
+    for level in levels {
+        total += level - mean_level;
+        profile.push(total);
+    }
+    let (mut sum_y, mut sum_yy, mut sum_ty) = (vec![0.0], vec![0.0], vec![0.0]);
+    for (t, y) in profile.iter().enumerate() {
+        sum_y.push(sum_y[t] + y);
+        sum_yy.push(sum_yy[t] + y * y);
+        sum_ty.push(sum_ty[t] + t as f64 * y);
+    }
+
+    let fluctuation = |tau: usize| -> Option<f64> {
+        let windows = profile.len() / tau;
+        if windows < 2 {
+            return None;
+        }
+        let n = tau as f64;
+        let sx = n * (n - 1.0) / 2.0;
+        let sxx = (n - 1.0) * n * (2.0 * n - 1.0) / 6.0;
+        let residual: f64 = (0..windows)
+            .map(|w| {
+                let (s, e) = (w * tau, (w + 1) * tau);
+                let sy = sum_y[e] - sum_y[s];
+                let syy = sum_yy[e] - sum_yy[s];
+                let sxy = sum_ty[e] - sum_ty[s] - s as f64 * sy;
+                let slope_term = (sxy - sx * sy / n).powi(2) / (sxx - sx * sx / n);
+                (syy - sy * sy / n - slope_term).max(0.0)
+            })
+            .sum();
+        let f = (residual / (windows * tau) as f64).sqrt();
+        (f > 0.0).then_some(f)
+    };
+
+    let frames_per_sec = 1.0 / DFA_FRAME_SECS;
+    let mut taus: Vec<usize> = Vec::new();
+    let mut tau = DFA_MIN_TAU_SECS * frames_per_sec;
+    while tau <= DFA_MAX_TAU_SECS * frames_per_sec {
+        let rounded = tau.round() as usize;
+        if taus.last() != Some(&rounded) {
+            taus.push(rounded);
+        }
+        tau *= DFA_TAU_MULTIPLIER;
+    }
+    let points: Vec<(f64, f64)> = taus
+        .into_iter()
+        .filter_map(|tau| Some(((tau as f64).ln(), fluctuation(tau)?.ln())))
+        .collect();
+    let alphas: Vec<f64> = points
+        .windows(2)
+        .map(|pair| (pair[1].1 - pair[0].1) / (pair[1].0 - pair[0].0))
+        .collect();
+    if alphas.is_empty() {
+        return None;
+    }
+    let mean_alpha = alphas.iter().sum::<f64>() / alphas.len() as f64;
+    (mean_alpha > 0.0).then(|| (1.0 / mean_alpha).min(DANCEABILITY_MAX))
+}
+
+/// About 5 Hz per bin at 44.1 kHz, fine enough to place an encoder lowpass.
+const BANDWIDTH_FRAME_SIZE: usize = 8192;
+/// Frames spread evenly across the track; enough for a stable average.
+const BANDWIDTH_MAX_FRAMES: usize = 512;
+/// Width of the level comparison either side of a candidate cutoff.
+const BANDWIDTH_SIDE_HZ: f64 = 500.0;
+/// Gap left out either side of a candidate cutoff for the filter slope.
+const BANDWIDTH_GUARD_HZ: f64 = 100.0;
+/// Encoder lowpasses sit above this; lower drops are arrangement, not codec.
+const BANDWIDTH_MIN_CUTOFF_HZ: f64 = 10_000.0;
+/// Without a cliff, bandwidth is where the spectrum falls this far below its peak.
+const BANDWIDTH_RANGE_DB: f64 = 90.0;
+/// Level drop across a cutoff that starts to look like an encoder lowpass.
+const TRANSCODE_MIN_CLIFF_DB: f64 = 15.0;
+/// Level drop across a cutoff that no natural rolloff produces.
+const TRANSCODE_CERTAIN_CLIFF_DB: f64 = 35.0;
+/// Cutoffs at or below this are typical of lossy encoders only.
+const TRANSCODE_CERTAIN_CUTOFF_HZ: f64 = 18_000.0;
+/// Cutoffs at or above this are as likely a mastering or resampling filter.
+const TRANSCODE_NATURAL_CUTOFF_HZ: f64 = 21_000.0;
+
+/// Effective bandwidth of a decoded file and how likely it is that a lossless
+/// container holds re-encoded lossy audio.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BandwidthEstimate {
+    pub effective_bandwidth_hz: f64,
+    pub nyquist_hz: f64,
+    /// Level drop (dB) across the sharpest high-frequency cutoff, if any.
+    pub cutoff_drop_db: Option<f64>,
+    /// 0.0–1.0; high values mean an encoder lowpass brick wall.
+    pub transcode_confidence: f64,
+    /// Lossy source typical for the cutoff, when one was found.
+    pub likely_source: Option<String>,
+}
+
+/// Estimate effective bandwidth from the long-term average spectrum.
+///
+/// Lossy encoders low-pass at a fixed frequency (16 kHz for 128 kbps MP3,
+/// 19–20 kHz at higher rates), leaving a brick wall with nothing above it.
+/// Real masters roll off gradually. The sharpest drop above 10 kHz is taken
+/// as the cutoff; confidence grows with its depth and falls as it nears the
+/// anti-aliasing band, where genuine masters also end.
+pub fn estimate_bandwidth(
+    samples: &[f32],
+    sample_rate: u32,
+) -> Result<BandwidthEstimate, AudioError> {
+    if sample_rate == 0 {
+        return Err(AudioError::Analysis(
+            "Sample rate must be non-zero".to_string(),
+        ));
+    }
+    if samples.len() < BANDWIDTH_FRAME_SIZE * 4 {
+        return Err(AudioError::Analysis(
+            "Audio too short for bandwidth analysis".to_string(),
+        ));
+    }
+
+    let fft = rustfft::FftPlanner::new().plan_fft_forward(BANDWIDTH_FRAME_SIZE);
+    let window: Vec<f32> = (0..BANDWIDTH_FRAME_SIZE)
+        .map(|i| {
+            let phase = std::f32::consts::TAU * i as f32 / (BANDWIDTH_FRAME_SIZE - 1) as f32;
+            0.5 - 0.5 * phase.cos()
+        })
+        .collect();
+    let bins = BANDWIDTH_FRAME_SIZE / 2 + 1;
+    let bin_hz = sample_rate as f64 / BANDWIDTH_FRAME_SIZE as f64;
+    let nyquist_hz = sample_rate as f64 / 2.0;
+
+    let frame_count = samples.len() / BANDWIDTH_FRAME_SIZE;
+    let step = frame_count.div_ceil(BANDWIDTH_MAX_FRAMES).max(1);
+    let mut buffer = vec![rustfft::num_complex::Complex::new(0.0_f32, 0.0); BANDWIDTH_FRAME_SIZE];
+    let mut power = vec![0.0_f64; bins];
+    let mut voiced_frames = 0usize;
+    for frame_index in (0..frame_count).step_by(step) {
+        let start = frame_index * BANDWIDTH_FRAME_SIZE;
+        let frame = &samples[start..start + BANDWIDTH_FRAME_SIZE];
+        let frame_power = frame
+            .iter()
+            .map(|&s| f64::from(s) * f64::from(s))
+            .sum::<f64>()
+            / frame.len() as f64;
+        if frame_power < NATIVE_SILENCE_POWER {
+            continue;
+        }
+        for (slot, (&sample, &w)) in buffer.iter_mut().zip(frame.iter().zip(&window)) {
+            *slot = rustfft::num_complex::Complex::new(sample * w, 0.0);
+        }
+        fft.process(&mut buffer);
+        for (acc, value) in power.iter_mut().zip(&buffer) {
+            *acc += f64::from(value.norm_sqr());
+        }
+        voiced_frames += 1;
+    }
+    if voiced_frames == 0 {
+        return Err(AudioError::Analysis(
+            "Audio is silent; bandwidth cannot be estimated".to_string(),
+        ));
+    }
+    let level_db: Vec<f64> = power
+        .iter()
+        .map(|p| power_to_db(p / voiced_frames as f64))
+        .collect();
+
+    let side_bins = (BANDWIDTH_SIDE_HZ / bin_hz).round().max(1.0) as usize;
+    let guard_bins = (BANDWIDTH_GUARD_HZ / bin_hz).round() as usize;
+    let mean_db = |range: std::ops::Range<usize>| {
+        let len = range.len() as f64;
+        level_db[range].iter().sum::<f64>() / len
+    };
+    let first_candidate =
+        ((BANDWIDTH_MIN_CUTOFF_HZ / bin_hz).ceil() as usize).max(side_bins + guard_bins);
+    let last_candidate = bins.saturating_sub(side_bins + guard_bins);
+    let cliff = (first_candidate..last_candidate)
+        .map(|bin| {
+            let below = mean_db(bin - guard_bins - side_bins..bin - guard_bins);
+            let above = mean_db(bin + guard_bins..bin + guard_bins + side_bins);
+            (bin, below - above)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .filter(|&(_, drop_db)| drop_db >= TRANSCODE_MIN_CLIFF_DB);
+
+    let Some((cutoff_bin, drop_db)) = cliff else {
+        let peak_db = level_db.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let highest_bin = level_db
+            .iter()
+            .rposition(|&db| db >= peak_db - BANDWIDTH_RANGE_DB)
+            .unwrap_or(bins - 1);
+        return Ok(BandwidthEstimate {
+            effective_bandwidth_hz: (highest_bin as f64 * bin_hz).round(),
+            nyquist_hz,
+            cutoff_drop_db: None,
+            transcode_confidence: 0.0,
+            likely_source: None,
+        });
+    };
+
+    let cutoff_hz = (cutoff_bin as f64 * bin_hz).round();
+    let depth = ((drop_db - TRANSCODE_MIN_CLIFF_DB)
+        / (TRANSCODE_CERTAIN_CLIFF_DB - TRANSCODE_MIN_CLIFF_DB))
+        .clamp(0.0, 1.0);
+    let placement = ((TRANSCODE_NATURAL_CUTOFF_HZ - cutoff_hz)
+        / (TRANSCODE_NATURAL_CUTOFF_HZ - TRANSCODE_CERTAIN_CUTOFF_HZ))
+        .clamp(0.0, 1.0);
+    let transcode_confidence = (depth * placement * 100.0).round() / 100.0;
+
+    Ok(BandwidthEstimate {
+        effective_bandwidth_hz: cutoff_hz,
+        nyquist_hz,
+        cutoff_drop_db: Some((drop_db * 10.0).round() / 10.0),
+        transcode_confidence,
+        likely_source: (transcode_confidence > 0.0).then(|| likely_lossy_source(cutoff_hz)),
+    })
+}
+
+/// Typical lossy source for an encoder lowpass at `cutoff_hz`.
+fn likely_lossy_source(cutoff_hz: f64) -> String {
+    match cutoff_hz {
+        c if c < 12_000.0 => "MP3 ≤96 kbps",
+        c if c < 15_500.0 => "MP3 112–128 kbps (joint stereo)",
+        c if c < 17_500.0 => "MP3 128–160 kbps",
+        c if c < 19_000.0 => "MP3 192 kbps or AAC",
+        c if c < 20_200.0 => "MP3 V0/256 kbps or AAC",
+        _ => "MP3 320 kbps",
+    }
+    .to_string()
+}
+
+/// Tracks shorter than this many bars have no arrangement to find.
+const MIN_STRUCTURE_BARS: usize = 8;
+/// Upper edge of the bass band whose absence marks intros, outros and breakdowns.
+const STRUCTURE_LOW_CUTOFF_HZ: f64 = 150.0;
+/// Lower edge of the treble band that risers and hat patterns live in.
+const STRUCTURE_HIGH_CUTOFF_HZ: f64 = 4000.0;
+/// Percentile of bar levels taken as the track's full-energy reference.
+pub(crate) const STRUCTURE_REFERENCE_PERCENTILE: f64 = 0.75;
+/// A bar this far below the reference overall level is not at full energy.
+pub(crate) const FULL_ENERGY_DROP_DB: f64 = 6.0;
+/// A bar whose bass sits this far below the reference has the low end pulled.
+pub(crate) const LOW_BAND_DROP_DB: f64 = 8.0;
+/// Shortest run of bass-less bars reported as a breakdown.
+const MIN_BREAKDOWN_BARS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SectionKind {
+    Breakdown,
+    Drop,
+}
+
+/// A run of bars between the intro and outro. Bars are zero-based from the
+/// first downbeat; `end_bar` is exclusive.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StructureSection {
+    pub kind: SectionKind,
+    pub start_bar: usize,
+    pub end_bar: usize,
+    pub start_sec: f64,
+}
+
+/// Beat-grid-aligned arrangement of a track and the mix points it suggests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackStructure {
+    pub analyzer_version: String,
+    pub bpm: f64,
+    pub bar_seconds: f64,
+    pub first_downbeat_sec: f64,
+    pub total_bars: usize,
+    pub intro_bars: usize,
+    pub outro_bars: usize,
+    pub sections: Vec<StructureSection>,
+    /// Labelled 8/16/32-bar phrase sections covering every bar.
+    #[serde(default)]
+    pub phrases: Vec<crate::phrase::PhraseSection>,
+    /// Cue point for mixing in: the first downbeat.
+    pub mix_in_sec: f64,
+    /// End of the intro, where the incoming track should be fully in.
+    pub mix_in_end_sec: f64,
+    /// Start of the outro, where the next track can take over.
+    pub mix_out_sec: f64,
+    /// End of the last full bar.
+    pub mix_out_end_sec: f64,
+}
+
+/// Detect intro, outro, breakdowns and drops on the beat grid.
+///
+/// Each bar's overall and bass (below 150 Hz) energy is compared with the
+/// track's 75th-percentile bar. Bars within a few dB of both are "full";
+/// the intro and outro are the leading and trailing bars before the first
+/// and after the last pair of full bars. Inside the body, runs of bars with
+/// the bass pulled are breakdowns and the full bar that ends one is a drop.
+/// `first_beat` is stratum's grid offset and bar position; without it the
+/// grid starts at zero on a downbeat.
+pub fn analyze_structure(
+    samples: &[f32],
+    sample_rate: u32,
+    bpm: f64,
+    first_beat: Option<(f64, u8)>,
+) -> Result<TrackStructure, AudioError> {
+    let grid = BarGrid::new(samples.len(), sample_rate, bpm, first_beat)?;
+    let total_bars = grid.total_bars;
+    if total_bars < MIN_STRUCTURE_BARS {
+        return Err(AudioError::Analysis(
+            "Audio too short for structure analysis".to_string(),
+        ));
+    }
+    let levels = bar_levels(samples, sample_rate, &grid);
+    let total_db: Vec<f64> = levels.iter().map(|l| l.total_db).collect();
+    let low_db: Vec<f64> = levels.iter().map(|l| l.low_db).collect();
+
+    let total_reference = percentile(&total_db, STRUCTURE_REFERENCE_PERCENTILE);
+    let low_reference = percentile(&low_db, STRUCTURE_REFERENCE_PERCENTILE);
+    let bass_pulled: Vec<bool> = low_db
+        .iter()
+        .map(|&db| db < low_reference - LOW_BAND_DROP_DB)
+        .collect();
+    let full: Vec<bool> = (0..total_bars)
+        .map(|bar| total_db[bar] >= total_reference - FULL_ENERGY_DROP_DB && !bass_pulled[bar])
+        .collect();
+
+    // Two consecutive full bars mark the body, so a lone fill doesn't end the intro.
+    let body_start =
+        (0..total_bars).find(|&bar| full[bar] && full.get(bar + 1).copied().unwrap_or(true));
+    let body_end = (0..total_bars)
+        .rev()
+        .find(|&bar| full[bar] && (bar == 0 || full[bar - 1]))
+        .map(|bar| bar + 1);
+    let (intro_bars, outro_bars, sections) = match (body_start, body_end) {
+        (Some(body_start), Some(body_end)) if body_start < body_end => (
+            body_start,
+            total_bars - body_end,
+            body_sections(&bass_pulled, &full, body_start, body_end),
+        ),
+        _ => (0, 0, Vec::new()),
+    };
+
+    let bar_sec = |bar: usize| grid.bar_start_sec(bar);
+    Ok(TrackStructure {
+        analyzer_version: STRUCTURE_VERSION.to_string(),
+        bpm,
+        bar_seconds: round_to_ms(grid.bar_seconds),
+        first_downbeat_sec: bar_sec(0),
+        total_bars,
+        intro_bars,
+        outro_bars,
+        sections: sections
+            .into_iter()
+            .map(|(kind, start_bar, end_bar)| StructureSection {
+                kind,
+                start_bar,
+                end_bar,
+                start_sec: bar_sec(start_bar),
+            })
+            .collect(),
+        phrases: crate::phrase::segment_phrases(&levels, &grid),
+        mix_in_sec: bar_sec(0),
+        mix_in_end_sec: bar_sec(intro_bars),
+        mix_out_sec: bar_sec(total_bars - outro_bars),
+        mix_out_end_sec: bar_sec(total_bars),
+    })
+}
+
+/// Breakdowns (bass pulled for at least `MIN_BREAKDOWN_BARS`) inside
+/// `[body_start, body_end)`, each followed by the drop that runs until the
+/// next breakdown or the end of the body.
+fn body_sections(
+    bass_pulled: &[bool],
+    full: &[bool],
+    body_start: usize,
+    body_end: usize,
+) -> Vec<(SectionKind, usize, usize)> {
+    let mut breakdowns = Vec::new();
+    let mut bar = body_start;
+    while bar < body_end {
+        if !bass_pulled[bar] {
+            bar += 1;
+            continue;
+        }
+        let run_start = bar;
+        while bar < body_end && bass_pulled[bar] {
+            bar += 1;
+        }
+        if bar - run_start >= MIN_BREAKDOWN_BARS {
+            breakdowns.push((run_start, bar));
+        }
+    }
+
+    let mut sections = Vec::new();
+    for (i, &(start, end)) in breakdowns.iter().enumerate() {
+        sections.push((SectionKind::Breakdown, start, end));
+        let next = breakdowns.get(i + 1).map_or(body_end, |&(next, _)| next);
+        if end < next && full[end] {
+            sections.push((SectionKind::Drop, end, next));
+        }
+    }
+    sections
+}
+
+/// Whole bars of a constant-tempo grid, counted from its first downbeat.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BarGrid {
+    pub(crate) first_downbeat_sec: f64,
+    pub(crate) bar_seconds: f64,
+    /// Bars that fit completely in the audio.
+    pub(crate) total_bars: usize,
+}
+
+impl BarGrid {
+    /// Lay out 4/4 bars at `bpm`. `first_beat` is stratum's grid offset and
+    /// bar position; without it the grid starts at zero on a downbeat.
+    pub(crate) fn new(
+        sample_count: usize,
+        sample_rate: u32,
+        bpm: f64,
+        first_beat: Option<(f64, u8)>,
+    ) -> Result<Self, AudioError> {
+        if sample_rate == 0 {
+            return Err(AudioError::Analysis(
+                "Sample rate must be non-zero".to_string(),
+            ));
+        }
+        if !bpm.is_finite() || bpm <= 0.0 {
+            return Err(AudioError::Analysis(format!(
+                "Bar grid needs a positive BPM, got {bpm}"
+            )));
+        }
+        let beat_seconds = 60.0 / bpm;
+        let (offset, beat_in_bar) = first_beat.unwrap_or((0.0, 1));
+        let beats_to_downbeat = (5 - i64::from(beat_in_bar.clamp(1, 4))) % 4;
+        let mut grid = Self {
+            first_downbeat_sec: offset + beats_to_downbeat as f64 * beat_seconds,
+            bar_seconds: beat_seconds * 4.0,
+            total_bars: 0,
+        };
+        while grid.bar_start_sample(grid.total_bars + 1, sample_rate) <= sample_count {
+            grid.total_bars += 1;
+        }
+        Ok(grid)
+    }
+
+    /// Start of `bar` in seconds, rounded to the millisecond.
+    pub(crate) fn bar_start_sec(&self, bar: usize) -> f64 {
+        round_to_ms(self.first_downbeat_sec + bar as f64 * self.bar_seconds)
+    }
+
+    fn bar_start_sample(&self, bar: usize, sample_rate: u32) -> usize {
+        ((self.first_downbeat_sec + bar as f64 * self.bar_seconds) * sample_rate as f64) as usize
+    }
+}
+
+/// Mean-square level of one bar in dB, overall and in the bass and treble bands.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BarLevels {
+    pub(crate) total_db: f64,
+    /// Below 150 Hz: kick and bassline.
+    pub(crate) low_db: f64,
+    /// Above 4 kHz: hats, cymbals, risers.
+    pub(crate) high_db: f64,
+}
+
+/// Per-bar levels for every bar of `grid`.
+pub(crate) fn bar_levels(samples: &[f32], sample_rate: u32, grid: &BarGrid) -> Vec<BarLevels> {
+    let mut low_pass = low_pass_filter(sample_rate, STRUCTURE_LOW_CUTOFF_HZ);
+    let mut high_pass = high_pass_filter(sample_rate, STRUCTURE_HIGH_CUTOFF_HZ);
+    // Settle the filters on the pickup before the first downbeat.
+    for &sample in &samples[..grid.bar_start_sample(0, sample_rate)] {
+        low_pass.tick(f64::from(sample));
+        high_pass.tick(f64::from(sample));
+    }
+    (0..grid.total_bars)
+        .map(|bar| {
+            let bar_samples = &samples[grid.bar_start_sample(bar, sample_rate)
+                ..grid.bar_start_sample(bar + 1, sample_rate)];
+            let (mut total, mut low, mut high) = (0.0, 0.0, 0.0);
+            for &sample in bar_samples {
+                let x = f64::from(sample);
+                let (l, h) = (low_pass.tick(x), high_pass.tick(x));
+                total += x * x;
+                low += l * l;
+                high += h * h;
+            }
+            let n = bar_samples.len().max(1) as f64;
+            BarLevels {
+                total_db: power_to_db(total / n),
+                low_db: power_to_db(low / n),
+                high_db: power_to_db(high / n),
+            }
+        })
+        .collect()
+}
+
+/// RBJ second-order low-pass (Butterworth Q).
+fn low_pass_filter(sample_rate: u32, cutoff_hz: f64) -> Biquad {
+    let w0 = std::f64::consts::TAU * cutoff_hz / sample_rate as f64;
+    let alpha = w0.sin() / (2.0 * std::f64::consts::FRAC_1_SQRT_2);
+    let cos = w0.cos();
+    let a0 = 1.0 + alpha;
+    Biquad::new(
+        [
+            (1.0 - cos) / 2.0 / a0,
+            (1.0 - cos) / a0,
+            (1.0 - cos) / 2.0 / a0,
+        ],
+        [-2.0 * cos / a0, (1.0 - alpha) / a0],
+    )
+}
+
+/// RBJ second-order high-pass (Butterworth Q).
+fn high_pass_filter(sample_rate: u32, cutoff_hz: f64) -> Biquad {
+    let w0 = std::f64::consts::TAU * cutoff_hz / sample_rate as f64;
+    let alpha = w0.sin() / (2.0 * std::f64::consts::FRAC_1_SQRT_2);
+    let cos = w0.cos();
+    let a0 = 1.0 + alpha;
+    Biquad::new(
+        [
+            (1.0 + cos) / 2.0 / a0,
+            -(1.0 + cos) / a0,
+            (1.0 + cos) / 2.0 / a0,
+        ],
+        [-2.0 * cos / a0, (1.0 - alpha) / a0],
+    )
+}
+
+fn power_to_db(power: f64) -> f64 {
+    10.0 * (power + 1e-12).log10()
+}
+
+pub(crate) fn percentile(values: &[f64], fraction: f64) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let index = ((sorted.len() - 1) as f64 * fraction).round() as usize;
+    sorted[index]
+}
+
+fn round_to_ms(seconds: f64) -> f64 {
+    (seconds * 1000.0).round() / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Stdio;
+
+    #[test]
+    fn stratum_result_serialization_round_trip() {
+        let result = StratumResult {
+            bpm: 128.0,
+            bpm_confidence: 0.95,
+            key: "Am".to_string(),
+            key_camelot: "8A".to_string(),
+            key_confidence: 0.88,
+            key_clarity: 0.72,
+            grid_stability: 0.91,
+            duration_seconds: 300.5,
+            processing_time_ms: 1234.5,
+            analyzer_version: "stratum-dsp-1.0.0".to_string(),
+            flags: vec!["MultimodalBpm".to_string()],
+            warnings: vec!["Low key clarity".to_string()],
+            first_beat_sec: Some(0.12),
+            first_beat_in_bar: Some(3),
+            opening: None,
+            closing: Some(WindowAnalysis {
+                start_sec: 240.0,
+                end_sec: 300.5,
+                bpm: 127.5,
+                bpm_confidence: 0.8,
+                key: "Bm".to_string(),
+                key_camelot: "10A".to_string(),
+                key_confidence: 0.7,
+            }),
+        };
+
+        let json = serde_json::to_string(&result).expect("serialize should succeed");
+        let back: StratumResult = serde_json::from_str(&json).expect("deserialize should succeed");
+
+        assert!((back.bpm - 128.0).abs() < f64::EPSILON);
+        assert!((back.bpm_confidence - 0.95).abs() < f64::EPSILON);
+        assert_eq!(back.key, "Am");
+        assert_eq!(back.key_camelot, "8A");
+        assert!((back.key_confidence - 0.88).abs() < f64::EPSILON);
+        assert!((back.key_clarity - 0.72).abs() < f64::EPSILON);
+        assert!((back.grid_stability - 0.91).abs() < f64::EPSILON);
+        assert!((back.duration_seconds - 300.5).abs() < f64::EPSILON);
+        assert!((back.processing_time_ms - 1234.5).abs() < f64::EPSILON);
+        assert_eq!(back.analyzer_version, "stratum-dsp-1.0.0");
+        assert_eq!(back.flags, vec!["MultimodalBpm"]);
+        assert_eq!(back.warnings, vec!["Low key clarity"]);
+        assert_eq!(back.first_beat_sec, Some(0.12));
+        assert_eq!(back.first_beat_in_bar, Some(3));
+        assert_eq!(back.opening, None);
+        assert_eq!(back.closing, result.closing);
+    }
+
+    #[test]
+    fn stratum_result_deserializes_without_first_beat_fields() {
+        let json = r#"{"bpm":128.0,"bpm_confidence":0.9,"key":"Am","key_camelot":"8A",
+            "key_confidence":0.8,"key_clarity":0.7,"grid_stability":0.9,
+            "duration_seconds":300.0,"processing_time_ms":10.0,
+            "analyzer_version":"1.0.0","flags":[],"warnings":[]}"#;
+        let back: StratumResult = serde_json::from_str(json).expect("legacy cache should parse");
+        assert_eq!(back.first_beat_sec, None);
+        assert_eq!(back.first_beat_in_bar, None);
+        assert_eq!(back.opening, None);
+        assert_eq!(back.closing, None);
+    }
+
+    #[test]
+    fn detect_first_beat_fits_offset_and_bar_position() {
+        // 120 BPM grid starting at 0.1s with jitter; first downbeat is the third beat.
+        let beats: Vec<f32> = (0..100)
+            .map(|i| 0.1 + i as f32 * 0.5 + if i % 2 == 0 { 0.004 } else { -0.004 })
+            .collect();
+        let downbeats: Vec<f32> = (0..25).map(|i| 1.1 + i as f32 * 2.0).collect();
+
+        let (offset, beat_in_bar) =
+            detect_first_beat(&beats, &downbeats, 120.0).expect("coherent beats should fit");
+        assert!((offset - 0.1).abs() < 0.005, "offset was {offset}");
+        assert_eq!(beat_in_bar, 3);
+    }
+
+    #[test]
+    fn detect_first_beat_wraps_late_starts_into_first_period() {
+        // Beats starting at 5.3s at 100 BPM (0.6s period) fold back to 5.3 % 0.6 = 0.5s.
+        let beats: Vec<f32> = (0..40).map(|i| 5.3 + i as f32 * 0.6).collect();
+        let (offset, beat_in_bar) = detect_first_beat(&beats, &[], 100.0).unwrap();
+        assert!((offset - 0.5).abs() < 1e-3, "offset was {offset}");
+        assert_eq!(beat_in_bar, 1);
+    }
+
+    #[test]
+    fn detect_first_beat_rejects_incoherent_or_missing_input() {
+        assert_eq!(detect_first_beat(&[], &[], 120.0), None);
+        assert_eq!(detect_first_beat(&[0.5, 1.0], &[], 0.0), None);
+        // Evenly spread phases cancel out.
+        let beats: Vec<f32> = (0..40).map(|i| i as f32 * 0.125).collect();
+        assert_eq!(detect_first_beat(&beats, &[], 120.0), None);
+    }
+
+    #[test]
+    fn stratum_notation_to_camelot_converts_all_major_keys() {
+        // stratum-dsp A (major) → standard Camelot B (major)
+        // number = (stratum + 6) % 12 + 1
+        assert_eq!(stratum_notation_to_camelot("1A"), "8B"); // C
+        assert_eq!(stratum_notation_to_camelot("2A"), "9B"); // G
+        assert_eq!(stratum_notation_to_camelot("3A"), "10B"); // D
+        assert_eq!(stratum_notation_to_camelot("4A"), "11B"); // A
+        assert_eq!(stratum_notation_to_camelot("5A"), "12B"); // E
+        assert_eq!(stratum_notation_to_camelot("6A"), "1B"); // B
+        assert_eq!(stratum_notation_to_camelot("7A"), "2B"); // F#
+        assert_eq!(stratum_notation_to_camelot("8A"), "3B"); // C#
+        assert_eq!(stratum_notation_to_camelot("9A"), "4B"); // G#
+        assert_eq!(stratum_notation_to_camelot("10A"), "5B"); // D#
+        assert_eq!(stratum_notation_to_camelot("11A"), "6B"); // A#
+        assert_eq!(stratum_notation_to_camelot("12A"), "7B"); // F
+    }
+
+    #[test]
+    fn stratum_notation_to_camelot_converts_all_minor_keys() {
+        // stratum-dsp B (minor) → standard Camelot A (minor)
+        assert_eq!(stratum_notation_to_camelot("1B"), "8A"); // Am
+        assert_eq!(stratum_notation_to_camelot("2B"), "9A"); // Em
+        assert_eq!(stratum_notation_to_camelot("3B"), "10A"); // Bm
+        assert_eq!(stratum_notation_to_camelot("4B"), "11A"); // F#m
+        assert_eq!(stratum_notation_to_camelot("5B"), "12A"); // C#m
+        assert_eq!(stratum_notation_to_camelot("6B"), "1A"); // G#m
+        assert_eq!(stratum_notation_to_camelot("7B"), "2A"); // D#m
+        assert_eq!(stratum_notation_to_camelot("8B"), "3A"); // A#m
+        assert_eq!(stratum_notation_to_camelot("9B"), "4A"); // Fm
+        assert_eq!(stratum_notation_to_camelot("10B"), "5A"); // Cm
+        assert_eq!(stratum_notation_to_camelot("11B"), "6A"); // Gm
+        assert_eq!(stratum_notation_to_camelot("12B"), "7A"); // Dm
+    }
+
+    #[test]
+    fn stratum_notation_to_camelot_passes_through_invalid_input() {
+        assert_eq!(stratum_notation_to_camelot(""), "");
+        assert_eq!(stratum_notation_to_camelot("X"), "X");
+        assert_eq!(stratum_notation_to_camelot("0A"), "0A");
+        assert_eq!(stratum_notation_to_camelot("13A"), "13A");
+    }
+
+    #[test]
     fn downmix_to_mono_truncates_to_shortest_channel() {
         let left = [0.25_f32, 0.50, 0.75];
         let right = [0.75_f32, 0.25];
         let planes: &[&[f32]] = &[&left, &right];
 
-        let mono = downmix_to_mono(planes, |&v| v);
+        let mono = downmix_to_mono(planes, None, |&v| v);
 
         assert_eq!(mono.len(), 2, "should use the shortest channel length");
         assert!((mono[0] - 0.50).abs() < f32::EPSILON);
@@ -624,7 +2715,7 @@ mod tests {
         let mono_src = [0.1_f32, 0.2, 0.3];
         let planes: &[&[f32]] = &[&mono_src];
 
-        let mono = downmix_to_mono(planes, |&v| v);
+        let mono = downmix_to_mono(planes, None, |&v| v);
 
         assert_eq!(mono, mono_src);
     }
@@ -635,7 +2726,7 @@ mod tests {
         let right = [0.25_f32, 0.50, 0.75];
         let planes: &[&[f32]] = &[&left, &right];
 
-        let mono = downmix_to_mono(planes, |&v| v);
+        let mono = downmix_to_mono(planes, None, |&v| v);
 
         assert!(
             mono.is_empty(),
@@ -643,6 +2734,518 @@ mod tests {
         );
     }
 
+    #[test]
+    fn decode_reads_little_endian_aifc() {
+        // Mono 16-bit `sowt` AIFF-C: 0.1 s of a full-scale-ish square wave.
+        let frames: Vec<i16> = (0..4410)
+            .map(|i| if (i / 50) % 2 == 0 { 16384 } else { -16384 })
+            .collect();
+        let mut comm = Vec::new();
+        comm.extend_from_slice(&1u16.to_be_bytes());
+        comm.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+        comm.extend_from_slice(&16u16.to_be_bytes());
+        // 44100 as an 80-bit extended float.
+        comm.extend_from_slice(&[0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0]);
+        comm.extend_from_slice(b"sowt");
+        comm.extend_from_slice(&[0, 0]);
+        let mut ssnd = vec![0u8; 8];
+        for frame in &frames {
+            ssnd.extend_from_slice(&frame.to_le_bytes());
+        }
+        let mut body = b"AIFC".to_vec();
+        for (id, chunk) in [(b"COMM", &comm), (b"SSND", &ssnd)] {
+            body.extend_from_slice(id);
+            body.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+            body.extend_from_slice(chunk);
+        }
+        let mut bytes = b"FORM".to_vec();
+        bytes.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&body);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("square.aifc");
+        std::fs::write(&path, bytes).unwrap();
+        let (samples, sample_rate) = decode_to_samples(path.to_str().unwrap()).unwrap();
+        assert_eq!(sample_rate, 44100);
+        assert_eq!(samples.len(), frames.len());
+        assert!((samples[0] - 0.5).abs() < 1e-3, "{}", samples[0]);
+        assert!((samples[60] + 0.5).abs() < 1e-3, "{}", samples[60]);
+    }
+
+    /// Pack `(value, width)` fields into bytes, most significant bit first
+    /// (ALAC) or least significant bit first (Vorbis).
+    fn pack_bits(fields: &[(u32, u32)], msb_first: bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut pos = 0usize;
+        for &(value, width) in fields {
+            for i in 0..width {
+                let bit = if msb_first {
+                    (value >> (width - 1 - i)) & 1
+                } else {
+                    (value >> i) & 1
+                };
+                if pos.is_multiple_of(8) {
+                    bytes.push(0);
+                }
+                if bit == 1 {
+                    let shift = if msb_first { 7 - pos % 8 } else { pos % 8 };
+                    *bytes.last_mut().unwrap() |= 1 << shift;
+                }
+                pos += 1;
+            }
+        }
+        bytes
+    }
+
+    fn mp4_atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut atom = (8 + body.len() as u32).to_be_bytes().to_vec();
+        atom.extend_from_slice(kind);
+        atom.extend_from_slice(body);
+        atom
+    }
+
+    #[test]
+    fn decode_reads_alac_m4a() {
+        // Mono 16-bit ALAC in uncompressed frames of 1024 samples, the last
+        // one partial.
+        let (sample_rate, frame_length) = (44100u32, 1024u32);
+        let frames: Vec<i16> = (0..2500)
+            .map(|i| ((i * 97) % 20_000 - 10_000) as i16)
+            .collect();
+        let packets: Vec<Vec<u8>> = frames
+            .chunks(frame_length as usize)
+            .map(|chunk| {
+                let partial = chunk.len() < frame_length as usize;
+                // SCE tag, instance tag, unused bits, partial flag, shift and
+                // the uncompressed flag.
+                let mut fields = vec![(0, 3), (0, 4), (0, 12), (u32::from(partial), 1), (0, 2)];
+                fields.push((1, 1));
+                if partial {
+                    fields.push((chunk.len() as u32, 32));
+                }
+                fields.extend(chunk.iter().map(|&s| (u32::from(s as u16), 16)));
+                fields.push((7, 3));
+                pack_bits(&fields, true)
+            })
+            .collect();
+
+        let mut cookie = frame_length.to_be_bytes().to_vec();
+        cookie.extend_from_slice(&[0, 16, 40, 10, 14, 1]);
+        cookie.extend_from_slice(&255u16.to_be_bytes());
+        cookie.extend_from_slice(&[0; 8]);
+        cookie.extend_from_slice(&sample_rate.to_be_bytes());
+        let mut alac = vec![0; 4];
+        alac.extend_from_slice(&cookie);
+        let mut entry = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        entry.extend_from_slice(&[0; 8]);
+        entry.extend_from_slice(&1u16.to_be_bytes());
+        entry.extend_from_slice(&16u16.to_be_bytes());
+        entry.extend_from_slice(&[0; 4]);
+        entry.extend_from_slice(&(sample_rate << 16).to_be_bytes());
+        entry.extend_from_slice(&mp4_atom(b"alac", &alac));
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend_from_slice(&mp4_atom(b"alac", &entry));
+
+        let n_packets = packets.len() as u32;
+        let mut stts = vec![0, 0, 0, 0, 0, 0, 0, 2];
+        for (count, delta) in [
+            (n_packets - 1, frame_length),
+            (1, frames.len() as u32 % frame_length),
+        ] {
+            stts.extend_from_slice(&count.to_be_bytes());
+            stts.extend_from_slice(&delta.to_be_bytes());
+        }
+        let mut stsc = vec![0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1];
+        stsc.extend_from_slice(&n_packets.to_be_bytes());
+        stsc.extend_from_slice(&1u32.to_be_bytes());
+        let mut stsz = vec![0; 8];
+        stsz.extend_from_slice(&n_packets.to_be_bytes());
+        for packet in &packets {
+            stsz.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+        }
+        let build_moov = |mdat_offset: u32| {
+            let mut stco = vec![0, 0, 0, 0, 0, 0, 0, 1];
+            stco.extend_from_slice(&mdat_offset.to_be_bytes());
+            let stbl = [
+                mp4_atom(b"stsd", &stsd),
+                mp4_atom(b"stts", &stts),
+                mp4_atom(b"stsc", &stsc),
+                mp4_atom(b"stsz", &stsz),
+                mp4_atom(b"stco", &stco),
+            ]
+            .concat();
+            let minf = [mp4_atom(b"smhd", &[0; 8]), mp4_atom(b"stbl", &stbl)].concat();
+            let mut mdhd = vec![0; 12];
+            mdhd.extend_from_slice(&sample_rate.to_be_bytes());
+            mdhd.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+            mdhd.extend_from_slice(&[0; 4]);
+            let mut hdlr = vec![0; 8];
+            hdlr.extend_from_slice(b"soun");
+            hdlr.extend_from_slice(&[0; 13]);
+            let mdia = [
+                mp4_atom(b"mdhd", &mdhd),
+                mp4_atom(b"hdlr", &hdlr),
+                mp4_atom(b"minf", &minf),
+            ]
+            .concat();
+            let mut tkhd = vec![0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+            tkhd.extend_from_slice(&[0; 68]);
+            let trak = [mp4_atom(b"tkhd", &tkhd), mp4_atom(b"mdia", &mdia)].concat();
+            let mut mvhd = vec![0; 12];
+            mvhd.extend_from_slice(&sample_rate.to_be_bytes());
+            mvhd.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+            mvhd.extend_from_slice(&[0; 80]);
+            let body = [mp4_atom(b"mvhd", &mvhd), mp4_atom(b"trak", &trak)].concat();
+            mp4_atom(b"moov", &body)
+        };
+        let ftyp = mp4_atom(b"ftyp", b"M4A \0\0\0\0M4A mp42isom");
+        let mdat_offset = (ftyp.len() + build_moov(0).len() + 8) as u32;
+        let bytes = [
+            ftyp,
+            build_moov(mdat_offset),
+            mp4_atom(b"mdat", &packets.concat()),
+        ]
+        .concat();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ramp.m4a");
+        std::fs::write(&path, bytes).unwrap();
+        let (samples, decoded_rate) = decode_to_samples(path.to_str().unwrap()).unwrap();
+        assert_eq!(decoded_rate, sample_rate);
+        let expected: Vec<f32> = frames.iter().map(|&s| f32::from(s) / 32768.0).collect();
+        assert_eq!(samples, expected);
+    }
+
+    /// CRC-32 of an Ogg page: polynomial 0x04c11db7, zero initial value, no
+    /// reflection.
+    fn ogg_crc(bytes: &[u8]) -> u32 {
+        let mut crc = 0u32;
+        for &byte in bytes {
+            crc ^= u32::from(byte) << 24;
+            for _ in 0..8 {
+                crc = if crc & 0x8000_0000 != 0 {
+                    (crc << 1) ^ 0x04c1_1db7
+                } else {
+                    crc << 1
+                };
+            }
+        }
+        crc
+    }
+
+    fn ogg_page(packets: &[&[u8]], header_type: u8, granule: u64, sequence: u32) -> Vec<u8> {
+        let mut lacing = Vec::new();
+        for packet in packets {
+            lacing.extend(std::iter::repeat_n(255u8, packet.len() / 255));
+            lacing.push((packet.len() % 255) as u8);
+        }
+        let mut page = b"OggS\0".to_vec();
+        page.push(header_type);
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&1u32.to_le_bytes());
+        page.extend_from_slice(&sequence.to_le_bytes());
+        page.extend_from_slice(&[0; 4]);
+        page.push(lacing.len() as u8);
+        page.extend_from_slice(&lacing);
+        for packet in packets {
+            page.extend_from_slice(packet);
+        }
+        let crc = ogg_crc(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+        page
+    }
+
+    #[test]
+    fn decode_reads_ogg_vorbis() {
+        // Mono Vorbis with one 256-sample block mode and a floor-1 setup
+        // whose audio packets all mark the floor unused, so the decode is
+        // silence of the length the last granule position declares.
+        let mut ident = b"\x01vorbis".to_vec();
+        ident.extend_from_slice(&0u32.to_le_bytes());
+        ident.push(1);
+        ident.extend_from_slice(&8000u32.to_le_bytes());
+        ident.extend_from_slice(&[0; 12]);
+        ident.extend_from_slice(&[0x88, 1]);
+        let mut comment = b"\x03vorbis".to_vec();
+        comment.extend_from_slice(&4u32.to_le_bytes());
+        comment.extend_from_slice(b"test");
+        comment.extend_from_slice(&0u32.to_le_bytes());
+        comment.push(1);
+        let mut setup = b"\x05vorbis".to_vec();
+        setup.extend(pack_bits(
+            &[
+                // One codebook: sync, 1 dimension, 2 entries of length 1,
+                // no lookup table.
+                (0, 8),
+                (0x56_4342, 24),
+                (1, 16),
+                (2, 24),
+                (0, 1),
+                (0, 1),
+                (0, 5),
+                (0, 5),
+                (0, 4),
+                // Time domain transform placeholder.
+                (0, 6),
+                (0, 16),
+                // One floor 1 without partitions.
+                (0, 6),
+                (1, 16),
+                (0, 5),
+                (0, 2),
+                (8, 4),
+                // One residue 0 with a single unused classification.
+                (0, 6),
+                (0, 16),
+                (0, 24),
+                (0, 24),
+                (0, 24),
+                (0, 6),
+                (0, 8),
+                (0, 3),
+                (0, 1),
+                // One mapping with one submap and no coupling.
+                (0, 6),
+                (0, 16),
+                (0, 1),
+                (0, 1),
+                (0, 2),
+                (0, 8),
+                (0, 8),
+                (0, 8),
+                // One short-block mode, then the framing bit.
+                (0, 6),
+                (0, 1),
+                (0, 16),
+                (0, 16),
+                (0, 8),
+                (1, 1),
+            ],
+            false,
+        ));
+
+        // The first block only primes the overlap; each later one adds 128
+        // samples.
+        let audio = vec![0u8; 1];
+        let blocks = 40;
+        let total = (blocks - 1) * 128;
+        let audio_packets: Vec<&[u8]> = (0..blocks).map(|_| audio.as_slice()).collect();
+        let bytes = [
+            ogg_page(&[&ident], 0x02, 0, 0),
+            ogg_page(&[&comment, &setup], 0, 0, 1),
+            ogg_page(&audio_packets, 0x04, total as u64, 2),
+        ]
+        .concat();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("silence.ogg");
+        std::fs::write(&path, bytes).unwrap();
+        let (samples, sample_rate) = decode_to_samples(path.to_str().unwrap()).unwrap();
+        assert_eq!(sample_rate, 8000);
+        assert_eq!(samples.len(), total);
+        assert!(samples.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn scanners_skip_undecodable_opus() {
+        assert!(AUDIO_EXTENSIONS.contains(&"oga"));
+        assert!(AUDIO_EXTENSIONS.contains(&"aifc"));
+        assert!(!AUDIO_EXTENSIONS.contains(&"opus"));
+    }
+
+    /// Mono 16-bit PCM WAV whose samples count up, so windows are easy to
+    /// locate in the full decode.
+    fn write_ramp_wav(path: &std::path::Path, sample_rate: u32, frames: usize) {
+        let samples: Vec<i16> = (0..frames).map(|i| (i % 30_000) as i16).collect();
+        write_mono_wav(path, sample_rate, &samples);
+    }
+
+    /// Mono 16-bit PCM WAV with a short decaying 1 kHz click on every beat.
+    fn write_click_wav(path: &std::path::Path, sample_rate: u32, secs: f64, bpm: f64) {
+        let frames = (secs * f64::from(sample_rate)) as usize;
+        let period = (60.0 / bpm * f64::from(sample_rate)) as usize;
+        let click = sample_rate as usize / 50;
+        let samples: Vec<i16> = (0..frames)
+            .map(|i| {
+                let k = i % period;
+                if k >= click {
+                    return 0;
+                }
+                let t = k as f64 / f64::from(sample_rate);
+                let envelope = 1.0 - k as f64 / click as f64;
+                (20_000.0 * envelope * (2.0 * std::f64::consts::PI * 1000.0 * t).sin()) as i16
+            })
+            .collect();
+        write_mono_wav(path, sample_rate, &samples);
+    }
+
+    fn write_mono_wav(path: &std::path::Path, sample_rate: u32, samples: &[i16]) {
+        let data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&data);
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn windowed_versions_are_marked() {
+        assert!(is_windowed_version("1.0.0+window:last-10.0min"));
+        assert!(!is_windowed_version("1.0.0"));
+    }
+
+    #[test]
+    fn last_window_stratum_lands_on_track_timeline() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clicks.wav");
+        write_click_wav(&path, 22_050, 30.0, 120.0);
+        let path = path.to_str().unwrap();
+
+        let result = analyze_window_with_stratum(path, DecodeWindow::Last(12.0)).unwrap();
+        assert!(is_windowed_version(&result.analyzer_version));
+        assert!(result.analyzer_version.ends_with("+window:last-0.2min"));
+        assert_eq!(result.duration_seconds, 30.0);
+        assert!(result.opening.is_none());
+        if let Some(closing) = &result.closing {
+            assert!(closing.start_sec >= 18.0, "{closing:?}");
+            assert!(closing.end_sec <= 30.0 + 1e-3, "{closing:?}");
+        }
+        // The window starts 18 s in; its first beat must be folded back to
+        // the track's first beat period.
+        let first_beat = result.first_beat_sec.expect("click track has a beat grid");
+        let period = 60.0 / result.bpm;
+        assert!(
+            (0.0..period).contains(&first_beat),
+            "{first_beat} vs {period}"
+        );
+
+        let whole = analyze_window_with_stratum(path, DecodeWindow::Last(120.0)).unwrap();
+        assert!(!is_windowed_version(&whole.analyzer_version));
+        assert!(
+            whole
+                .warnings
+                .iter()
+                .all(|w| !w.starts_with("Analyzed the"))
+        );
+    }
+
+    #[test]
+    fn sample_stream_chunks_match_full_decode() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ramp.wav");
+        write_ramp_wav(&path, 8000, 24_000);
+        let path = path.to_str().unwrap();
+
+        let mut stream = SampleStream::open(path).unwrap();
+        assert_eq!(stream.duration_secs(), Some(3.0));
+        let mut chunks = 0;
+        let mut streamed = Vec::new();
+        while let Some(chunk) = stream.next_chunk(None).unwrap() {
+            chunks += 1;
+            streamed.extend(chunk);
+        }
+        assert!(chunks > 1, "expected several chunks, got {chunks}");
+
+        let (samples, _, quality) = decode_with_quality(path).unwrap();
+        assert_eq!(streamed, samples);
+        assert_eq!(measure_signal_quality(path).unwrap(), quality);
+        let shared = decode_for_analysis(path).unwrap();
+        assert_eq!(shared.samples, samples);
+        assert_eq!(shared.quality, quality);
+        assert_eq!(
+            estimated_decoded_bytes(path, DecodeWindow::Full),
+            Some(24_000 * 4)
+        );
+        assert_eq!(
+            estimated_decoded_bytes(path, DecodeWindow::Last(60.0)),
+            Some(24_000 * 4),
+            "windows longer than the track cover all of it"
+        );
+    }
+
+    #[test]
+    fn decode_window_keeps_only_the_requested_span() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ramp.wav");
+        write_ramp_wav(&path, 8000, 24_000);
+        let path = path.to_str().unwrap();
+        let (full, _) = decode_to_samples(path).unwrap();
+
+        let (first, sample_rate) = decode_window(path, DecodeWindow::First(0.5)).unwrap();
+        assert_eq!(sample_rate, 8000);
+        assert_eq!(first, full[..4000]);
+
+        let (last, _) = decode_window(path, DecodeWindow::Last(0.75)).unwrap();
+        assert_eq!(last, full[full.len() - 6000..]);
+
+        let (all, _) = decode_window(path, DecodeWindow::Last(10.0)).unwrap();
+        assert_eq!(all, full);
+        assert!(decode_window(path, DecodeWindow::First(0.0)).is_err());
+        assert_eq!(
+            estimated_decoded_bytes(path, DecodeWindow::First(0.5)),
+            Some(4000 * 4)
+        );
+    }
+
+    #[test]
+    fn streamed_native_features_match_the_decoded_buffer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clicks.wav");
+        write_click_wav(&path, 22_050, 20.0, 120.0);
+        let path = path.to_str().unwrap();
+
+        let decoded = decode_for_analysis(path).unwrap();
+        let (streamed, quality) = measure_native_features(path).unwrap();
+        assert_eq!(
+            serde_json::to_value(&streamed).unwrap(),
+            serde_json::to_value(analyze_native_features(&decoded).unwrap()).unwrap()
+        );
+        assert_eq!(quality, decoded.quality);
+    }
+
+    #[test]
+    fn windowed_native_features_meter_only_the_window() {
+        // 10 s of tone, then 10 s of the same tone 20 dB down.
+        let sample_rate = 8000;
+        let samples: Vec<i16> = (0..20 * sample_rate)
+            .map(|i| {
+                let amplitude = if i < 10 * sample_rate {
+                    10_000.0
+                } else {
+                    1_000.0
+                };
+                let t = f64::from(i) / f64::from(sample_rate);
+                (amplitude * (std::f64::consts::TAU * 1000.0 * t).sin()) as i16
+            })
+            .collect();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("steps.wav");
+        write_mono_wav(&path, sample_rate, &samples);
+        let path = path.to_str().unwrap();
+
+        let first = analyze_native_window(path, DecodeWindow::First(10.0)).unwrap();
+        let last = analyze_native_window(path, DecodeWindow::Last(10.0)).unwrap();
+        assert!(first.analyzer_version.ends_with("+window:first-0.2min"));
+        assert!(is_windowed_version(&last.analyzer_version));
+        let (loud, quiet) = (
+            first.loudness_integrated.unwrap(),
+            last.loudness_integrated.unwrap(),
+        );
+        assert!((loud - quiet - 20.0).abs() < 0.5, "{loud} vs {quiet}");
+
+        let whole = analyze_native_window(path, DecodeWindow::Last(60.0)).unwrap();
+        assert_eq!(whole.analyzer_version, NATIVE_FEATURES_VERSION);
+    }
+
     #[test]
     fn parse_essentia_stdout_trims_whitespace() {
         let parsed =
@@ -895,6 +3498,443 @@ def column_stack(cols):
         );
     }
 
+    fn sine(freq: f32, amplitude: f32, seconds: f32, sample_rate: u32) -> Vec<f32> {
+        (0..(seconds * sample_rate as f32) as usize)
+            .map(|i| {
+                let phase =
+                    std::f64::consts::TAU * f64::from(freq) * i as f64 / f64::from(sample_rate);
+                amplitude * phase.sin() as f32
+            })
+            .collect()
+    }
+
+    /// Four-on-the-floor at `bpm`: a kick on each downbeat, a hi-hat tick on
+    /// the other beats.
+    fn drum_loop(bpm: f32, seconds: f32, sample_rate: u32) -> Vec<f32> {
+        let mut samples = vec![0.0_f32; (seconds * sample_rate as f32) as usize];
+        let beat_len = (60.0 / bpm * sample_rate as f32) as usize;
+        for (beat, start) in (0..samples.len()).step_by(beat_len).enumerate() {
+            let hit_len = beat_len.min(samples.len() - start);
+            for i in 0..hit_len {
+                let t = i as f32 / sample_rate as f32;
+                samples[start + i] += if beat % 4 == 0 {
+                    0.8 * (std::f32::consts::TAU * 55.0 * t).sin() * (-t * 30.0).exp()
+                } else {
+                    0.3 * (std::f32::consts::TAU * 6000.0 * t).sin() * (-t * 80.0).exp()
+                };
+            }
+        }
+        samples
+    }
+
+    /// Meter `planes` as the decoder would, one plane per channel.
+    fn decoded(planes: &[&[f32]], sample_rate: u32) -> DecodedAudio {
+        let mut meter = AnalysisMeter {
+            quality: QualityMeter::new(),
+            loudness: LoudnessMeter::new(sample_rate),
+        };
+        let samples = downmix_to_mono(planes, Some(&mut meter), |&v| v);
+        DecodedAudio {
+            samples,
+            sample_rate,
+            quality: meter.quality.finish(),
+            loudness_segments: meter.loudness.finish(),
+        }
+    }
+
+    fn mono(samples: &[f32], sample_rate: u32) -> DecodedAudio {
+        decoded(&[samples], sample_rate)
+    }
+
+    fn dual_mono(samples: &[f32], sample_rate: u32) -> DecodedAudio {
+        decoded(&[samples, samples], sample_rate)
+    }
+
+    #[test]
+    fn native_loudness_matches_ebu_reference_levels() {
+        // A 1 kHz sine at -20 dBFS peak on both channels reads -20 LUFS.
+        let steady =
+            analyze_native_features(&dual_mono(&sine(1000.0, 0.1, 20.0, 48000), 48000)).unwrap();
+        let integrated = steady.loudness_integrated.unwrap();
+        assert!((integrated + 20.0).abs() < 0.2, "integrated {integrated}");
+        assert!(steady.loudness_range.unwrap() < 0.5);
+        assert!(steady.dynamic_complexity.unwrap() < 0.5);
+
+        // Alternating 10 s at -20 dBFS and -40 dBFS spans about 20 LU.
+        let mut alternating = Vec::new();
+        for amplitude in [0.1, 0.01, 0.1, 0.01] {
+            alternating.extend(sine(1000.0, amplitude, 10.0, 48000));
+        }
+        let range = analyze_native_features(&dual_mono(&alternating, 48000))
+            .unwrap()
+            .loudness_range
+            .unwrap();
+        assert!((15.0..=21.0).contains(&range), "range {range}");
+    }
+
+    #[test]
+    fn native_loudness_meters_each_channel() {
+        let tone = sine(1000.0, 0.1, 20.0, 48000);
+        let silence = vec![0.0; tone.len()];
+        let integrated = |audio: DecodedAudio| {
+            analyze_native_features(&audio)
+                .unwrap()
+                .loudness_integrated
+                .unwrap()
+        };
+
+        // Half the channels carry half the energy: 3 LU down, not the 6 dB a
+        // halved downmix would read.
+        let both = integrated(dual_mono(&tone, 48000));
+        let left_only = integrated(decoded(&[&tone, &silence], 48000));
+        assert!(
+            (both - left_only - 3.01).abs() < 0.05,
+            "{both} vs {left_only}"
+        );
+
+        // A mono file is one channel, not a centre-panned stereo pair.
+        let single = integrated(mono(&tone, 48000));
+        assert!((single - left_only).abs() < 0.01, "{single} vs {left_only}");
+    }
+
+    #[test]
+    fn native_spectral_centroid_and_mfcc_follow_the_spectrum() {
+        let low = analyze_native_features(&mono(&sine(500.0, 0.5, 5.0, 44100), 44100)).unwrap();
+        let high = analyze_native_features(&mono(&sine(4000.0, 0.5, 5.0, 44100), 44100)).unwrap();
+        let (low_centroid, high_centroid) = (
+            low.spectral_centroid_mean.unwrap(),
+            high.spectral_centroid_mean.unwrap(),
+        );
+        assert!((400.0..700.0).contains(&low_centroid), "{low_centroid}");
+        assert!((3800.0..4400.0).contains(&high_centroid), "{high_centroid}");
+        assert_eq!(
+            low.mfcc_mean.as_ref().map(Vec::len),
+            Some(MFCC_COEFFICIENTS)
+        );
+        assert_eq!(low.analyzer_version, NATIVE_FEATURES_VERSION);
+    }
+
+    #[test]
+    fn native_rhythm_features_track_a_drum_loop() {
+        let features =
+            analyze_native_features(&mono(&drum_loop(124.0, 30.0, 44100), 44100)).unwrap();
+        let bpm = features.bpm_essentia.unwrap();
+        assert!((bpm - 124.0).abs() < 2.0, "bpm {bpm}");
+        let onset_rate = features.onset_rate.unwrap();
+        assert!(
+            (onset_rate - 124.0 / 60.0).abs() < 0.3,
+            "onset rate {onset_rate}"
+        );
+        let regularity = features.rhythm_regularity.unwrap();
+        assert!(
+            regularity > 1.5,
+            "kick downbeats should stand out: {regularity}"
+        );
+        let danceability = features.danceability.unwrap();
+        assert!(
+            danceability > 0.0 && danceability.is_finite(),
+            "{danceability}"
+        );
+
+        let tone = analyze_native_features(&mono(&sine(440.0, 0.5, 30.0, 44100), 44100)).unwrap();
+        assert!(
+            tone.onset_rate.unwrap() < 0.2,
+            "a steady tone has no onsets"
+        );
+        assert_eq!(tone.bpm_essentia, None);
+        assert_eq!(tone.rhythm_regularity, None);
+    }
+
+    #[test]
+    fn native_features_reject_short_or_invalid_input() {
+        assert!(analyze_native_features(&mono(&[0.0; 1000], 44100)).is_err());
+        assert!(analyze_native_features(&mono(&[0.0; 100_000], 0)).is_err());
+        let silence = analyze_native_features(&mono(&[0.0; 100_000], 44100)).unwrap();
+        assert_eq!(silence.loudness_integrated, None);
+        assert_eq!(silence.spectral_centroid_mean, None);
+    }
+
+    /// Render `bars` of an arrangement at `bpm` after `pre_roll` seconds of
+    /// silence: `'h'` hats only, `'f'` kick, bass and hats, `'p'` a pad with
+    /// no bass.
+    fn arrangement(bars: &str, bpm: f32, pre_roll: f32, sample_rate: u32) -> Vec<f32> {
+        let fs = sample_rate as f32;
+        let beat_len = 60.0 / bpm * fs;
+        let mut samples = vec![0.0_f32; (pre_roll * fs) as usize];
+        for (bar, kind) in bars.chars().enumerate() {
+            for beat in 0..4 {
+                let start = (pre_roll * fs + (bar * 4 + beat) as f32 * beat_len) as usize;
+                let end = (pre_roll * fs + (bar * 4 + beat + 1) as f32 * beat_len) as usize;
+                for n in start..end {
+                    let t = (n - start) as f32 / fs;
+                    let abs_t = n as f64 / f64::from(sample_rate);
+                    let hat = 0.2 * (std::f32::consts::TAU * 6000.0 * t).sin() * (-t * 80.0).exp();
+                    let value = match kind {
+                        'f' => {
+                            let kick =
+                                0.8 * (std::f32::consts::TAU * 55.0 * t).sin() * (-t * 20.0).exp();
+                            let bass = 0.3 * (std::f64::consts::TAU * 45.0 * abs_t).sin() as f32;
+                            kick + bass + hat
+                        }
+                        'p' => 0.4 * (std::f64::consts::TAU * 440.0 * abs_t).sin() as f32,
+                        _ => hat,
+                    };
+                    samples.push(value);
+                }
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn structure_finds_intro_breakdown_drop_and_outro() {
+        let bars = format!(
+            "{}{}{}{}{}",
+            "h".repeat(16),
+            "f".repeat(16),
+            "p".repeat(8),
+            "f".repeat(16),
+            "h".repeat(8)
+        );
+        // Stratum's first grid beat is the third beat of a bar, two beats
+        // before the first downbeat at 1.0 s.
+        let beat = 60.0 / 124.0;
+        let samples = arrangement(&bars, 124.0, 1.0, 22050);
+        let structure =
+            analyze_structure(&samples, 22050, 124.0, Some((1.0 - 2.0 * beat, 3))).unwrap();
+
+        assert_eq!(structure.analyzer_version, STRUCTURE_VERSION);
+        assert!((structure.first_downbeat_sec - 1.0).abs() < 0.002);
+        assert_eq!(structure.total_bars, 64);
+        assert_eq!(structure.intro_bars, 16);
+        assert_eq!(structure.outro_bars, 8);
+        let sections: Vec<(SectionKind, usize, usize)> = structure
+            .sections
+            .iter()
+            .map(|s| (s.kind, s.start_bar, s.end_bar))
+            .collect();
+        assert_eq!(
+            sections,
+            vec![
+                (SectionKind::Breakdown, 32, 40),
+                (SectionKind::Drop, 40, 56)
+            ]
+        );
+
+        use crate::phrase::PhraseLabel;
+        let phrases: Vec<(PhraseLabel, usize, usize)> = structure
+            .phrases
+            .iter()
+            .map(|p| (p.label, p.start_bar, p.bars))
+            .collect();
+        assert_eq!(
+            phrases,
+            vec![
+                (PhraseLabel::Intro, 0, 16),
+                (PhraseLabel::Groove, 16, 16),
+                (PhraseLabel::Break, 32, 8),
+                (PhraseLabel::Drop, 40, 16),
+                (PhraseLabel::Outro, 56, 8),
+            ]
+        );
+
+        let bar_seconds = 4.0 * beat;
+        assert!((structure.mix_in_sec - 1.0).abs() < 0.002);
+        assert!((structure.mix_in_end_sec - (1.0 + 16.0 * bar_seconds)).abs() < 0.002);
+        assert!((structure.mix_out_sec - (1.0 + 56.0 * bar_seconds)).abs() < 0.002);
+        assert!((structure.sections[1].start_sec - (1.0 + 40.0 * bar_seconds)).abs() < 0.002);
+    }
+
+    #[test]
+    fn structure_of_a_flat_loop_has_no_intro_or_sections() {
+        let samples = arrangement(&"f".repeat(16), 128.0, 0.0, 22050);
+        let structure = analyze_structure(&samples, 22050, 128.0, None).unwrap();
+        assert_eq!(structure.total_bars, 16);
+        assert_eq!((structure.intro_bars, structure.outro_bars), (0, 0));
+        assert!(structure.sections.is_empty());
+        assert_eq!(structure.mix_in_sec, structure.mix_in_end_sec);
+        assert_eq!(structure.mix_out_sec, structure.mix_out_end_sec);
+    }
+
+    #[test]
+    fn structure_rejects_short_audio_and_missing_tempo() {
+        let samples = arrangement(&"f".repeat(4), 128.0, 0.0, 22050);
+        assert!(analyze_structure(&samples, 22050, 128.0, None).is_err());
+        let samples = arrangement(&"f".repeat(16), 128.0, 0.0, 22050);
+        assert!(analyze_structure(&samples, 22050, 0.0, None).is_err());
+        assert!(analyze_structure(&samples, 0, 128.0, None).is_err());
+    }
+
+    fn triad(midi_notes: [f64; 3], seconds: f32, sample_rate: u32) -> Vec<f32> {
+        (0..(seconds * sample_rate as f32) as usize)
+            .map(|i| {
+                let t = i as f64 / f64::from(sample_rate);
+                midi_notes
+                    .iter()
+                    .map(|&note| {
+                        let freq = 440.0 * 2f64.powf((note - 69.0) / 12.0);
+                        0.1 * (std::f64::consts::TAU * freq * t).sin() as f32
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn edge_windows_track_tempo_drift_and_key_change() {
+        let sample_rate = 22050;
+        // 8 bars at 120 BPM over A minor, then 8 bars at 126 BPM over E minor.
+        let mut samples: Vec<f32> = drum_loop(120.0, 16.0, sample_rate)
+            .iter()
+            .zip(triad([57.0, 60.0, 64.0], 16.0, sample_rate))
+            .map(|(drums, pad)| drums + pad)
+            .collect();
+        samples.extend(
+            drum_loop(126.0, 16.0, sample_rate)
+                .iter()
+                .zip(triad([52.0, 55.0, 59.0], 16.0, sample_rate))
+                .map(|(drums, pad)| drums + pad),
+        );
+
+        let (opening, closing) = analyze_edges(&samples, sample_rate, 123.0, 8);
+        let (opening, closing) = (opening.unwrap(), closing.unwrap());
+        assert!((opening.bpm - 120.0).abs() < 2.0, "opening {}", opening.bpm);
+        assert!((closing.bpm - 126.0).abs() < 2.0, "closing {}", closing.bpm);
+        assert_ne!(opening.key_camelot, closing.key_camelot);
+        assert_eq!(opening.start_sec, 0.0);
+        assert_eq!(closing.end_sec, 32.0);
+        assert!((closing.start_sec - (32.0 - 8.0 * 4.0 * 60.0 / 123.0)).abs() < 0.01);
+
+        // Too short for two separate windows.
+        assert_eq!(
+            analyze_edges(&samples[..sample_rate as usize * 20], sample_rate, 123.0, 8),
+            (None, None)
+        );
+        assert_eq!(analyze_edges(&samples, sample_rate, 0.0, 8), (None, None));
+    }
+
+    /// Noise with a flat spectrum up to `cutoff_hz` and nothing above, built
+    /// by overlap-adding windowed random-phase blocks.
+    fn band_limited_noise(cutoff_hz: f64, seconds: f32, sample_rate: u32) -> Vec<f32> {
+        use rustfft::num_complex::Complex;
+
+        const BLOCK: usize = 4096;
+        let ifft = rustfft::FftPlanner::new().plan_fft_inverse(BLOCK);
+        let top_bin = ((cutoff_hz * BLOCK as f64 / f64::from(sample_rate)) as usize).min(BLOCK / 2);
+        let mut seed = 0x2545_f491_u64;
+        let mut next_phase = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 11) as f64 / (1u64 << 53) as f64 * std::f64::consts::TAU
+        };
+
+        let len = (seconds * sample_rate as f32) as usize;
+        let mut samples = vec![0.0_f32; len + BLOCK];
+        for start in (0..len).step_by(BLOCK / 2) {
+            let mut spectrum = vec![Complex::new(0.0_f32, 0.0); BLOCK];
+            for bin in 1..top_bin {
+                let phase = next_phase();
+                spectrum[bin] = Complex::from_polar(1.0, phase as f32);
+                spectrum[BLOCK - bin] = spectrum[bin].conj();
+            }
+            ifft.process(&mut spectrum);
+            for (i, value) in spectrum.iter().enumerate() {
+                let w = 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / BLOCK as f32).cos();
+                samples[start + i] += 0.005 * value.re * w;
+            }
+        }
+        samples.truncate(len);
+        samples
+    }
+
+    #[test]
+    fn bandwidth_flags_lowpassed_audio_as_transcode() {
+        let transcode =
+            estimate_bandwidth(&band_limited_noise(16_000.0, 10.0, 44100), 44100).unwrap();
+        assert!(
+            (transcode.effective_bandwidth_hz - 16_000.0).abs() < 150.0,
+            "cutoff {}",
+            transcode.effective_bandwidth_hz
+        );
+        assert!(transcode.transcode_confidence > 0.9, "{transcode:?}");
+        assert_eq!(transcode.likely_source.as_deref(), Some("MP3 128–160 kbps"));
+
+        let genuine =
+            estimate_bandwidth(&band_limited_noise(22_050.0, 10.0, 44100), 44100).unwrap();
+        assert_eq!(genuine.transcode_confidence, 0.0, "{genuine:?}");
+        assert!(genuine.effective_bandwidth_hz > 21_000.0, "{genuine:?}");
+        assert_eq!(genuine.nyquist_hz, 22_050.0);
+
+        // A brick wall in the anti-aliasing band is not evidence of a transcode.
+        let mastered =
+            estimate_bandwidth(&band_limited_noise(21_500.0, 10.0, 44100), 44100).unwrap();
+        assert_eq!(mastered.transcode_confidence, 0.0, "{mastered:?}");
+
+        assert!(estimate_bandwidth(&[0.0; 1000], 44100).is_err());
+        assert!(estimate_bandwidth(&[0.0; 100_000], 0).is_err());
+        assert!(estimate_bandwidth(&[0.0; 100_000], 44100).is_err());
+    }
+
+    fn meter_channels(channels: &[Vec<f32>]) -> SignalQuality {
+        let mut meter = QualityMeter::new();
+        for (channel, samples) in channels.iter().enumerate() {
+            for &sample in samples {
+                meter.push(channel, sample);
+            }
+        }
+        meter.finish()
+    }
+
+    #[test]
+    fn quality_meter_reports_true_peak_clipping_and_dc() {
+        // A tone at a quarter of the sample rate, sampled 45° off its crests:
+        // samples sit 3 dB below the waveform's true peak.
+        let quarter_rate: Vec<f32> = (0..44100)
+            .map(|i| {
+                let phase = std::f32::consts::FRAC_PI_2 * (i % 4) as f32;
+                (phase + std::f32::consts::FRAC_PI_4).sin()
+            })
+            .collect();
+        let quality = meter_channels(std::slice::from_ref(&quarter_rate));
+        assert!(
+            (quality.sample_peak_dbfs + 3.01).abs() < 0.05,
+            "{quality:?}"
+        );
+        assert!(quality.true_peak_dbtp.abs() < 0.3, "{quality:?}");
+        assert_eq!(quality.clipped_runs, 0);
+        let quieter: Vec<f32> = quarter_rate.iter().map(|s| s * 0.8).collect();
+        assert!(meter_channels(&[quieter]).flags().is_empty());
+        let hotter: Vec<f32> = quarter_rate.iter().map(|s| s * 1.2).collect();
+        assert_eq!(
+            meter_channels(&[hotter]).flags(),
+            [QualityFlag::LowHeadroom]
+        );
+
+        // Clipping on one channel of a stereo pair is not hidden by the other.
+        let clipped: Vec<f32> = sine(100.0, 2.0, 1.0, 44100)
+            .iter()
+            .map(|s| s.clamp(-1.0, 1.0))
+            .collect();
+        let quality = meter_channels(&[vec![0.0; clipped.len()], clipped]);
+        assert_eq!(quality.clipped_runs, 200, "one run per half-cycle");
+        assert!(quality.clipped_samples > 200 * CLIP_RUN_MIN_SAMPLES as u64);
+        assert_eq!(quality.sample_peak_dbfs, 0.0);
+        assert_eq!(quality.flags(), [QualityFlag::Clipping]);
+
+        let offset: Vec<f32> = sine(100.0, 0.5, 1.0, 44100)
+            .iter()
+            .map(|s| s + 0.01)
+            .collect();
+        let quality = meter_channels(&[offset]);
+        assert!((quality.dc_offset - 0.01).abs() < 1e-4, "{quality:?}");
+        assert_eq!(quality.flags(), [QualityFlag::DcOffset]);
+
+        let silence = meter_channels(&[vec![0.0; 1000]]);
+        assert_eq!(silence.sample_peak_dbfs, QUALITY_FLOOR_DB);
+        assert_eq!(silence.dc_offset, 0.0);
+    }
+
     // ==================== Integration tests (real audio files) ====================
     // Run with: cargo test -- --ignored
 
@@ -912,6 +3952,7 @@ def column_stack(cols):
             bpm_max: Some(140.0),
             key: None,
             playlist: None,
+            my_tag: None,
             has_genre: Some(true),
             label: None,
             path: None,
@@ -1025,6 +4066,7 @@ def column_stack(cols):
             bpm_max: Some(140.0),
             key: None,
             playlist: None,
+            my_tag: None,
             has_genre: Some(true),
             label: None,
             path: None,
@@ -1070,12 +4112,7 @@ def column_stack(cols):
 
         let metadata = std::fs::metadata(&file_path).unwrap();
         let file_size = metadata.len() as i64;
-        let file_mtime = metadata
-            .modified()
-            .ok()
-            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-            .map(|d| d.as_secs() as i64)
-            .unwrap_or(0);
+        let file_mtime = crate::store::file_mtime_unix(&metadata);
 
         crate::store::set_audio_analysis(
             &store_conn,
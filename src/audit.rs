@@ -4,17 +4,23 @@
 //! return detected issues. The scan operation walks the filesystem, reads tags,
 //! applies checks, and persists results to SQLite.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::Path;
 use std::sync::LazyLock;
 
+use lofty::file::AudioFile;
+use lofty::probe::Probe;
 use regex::Regex;
 use rusqlite::Connection;
 use serde::Serialize;
 use unicode_casefold::UnicodeCaseFold;
 
+use crate::audio;
+use crate::normalize::normalize_path_for_matching;
 use crate::store;
 use crate::tags::{self, FileReadResult};
+use crate::types::{FileKind, Track};
+use crate::xml::{self, PlaylistDef};
 
 // ---------------------------------------------------------------------------
 // Issue types & safety tiers
@@ -54,6 +60,14 @@ pub enum IssueType {
     MissingYearInDir,
     #[strum(serialize = "FILENAME_TAG_DRIFT")]
     FilenameTagDrift,
+    #[strum(serialize = "SUSPECT_TRANSCODE")]
+    SuspectTranscode,
+    #[strum(serialize = "CLIPPING")]
+    Clipping,
+    #[strum(serialize = "LOW_HEADROOM")]
+    LowHeadroom,
+    #[strum(serialize = "DC_OFFSET")]
+    DcOffset,
 }
 
 impl IssueType {
@@ -74,6 +88,10 @@ impl IssueType {
             Self::TechSpecsInDir => "TECH_SPECS_IN_DIR",
             Self::MissingYearInDir => "MISSING_YEAR_IN_DIR",
             Self::FilenameTagDrift => "FILENAME_TAG_DRIFT",
+            Self::SuspectTranscode => "SUSPECT_TRANSCODE",
+            Self::Clipping => "CLIPPING",
+            Self::LowHeadroom => "LOW_HEADROOM",
+            Self::DcOffset => "DC_OFFSET",
         }
     }
 
@@ -91,7 +109,11 @@ impl IssueType {
             | Self::NoTags
             | Self::BadFilename
             | Self::MissingYearInDir
-            | Self::FilenameTagDrift => SafetyTier::Review,
+            | Self::FilenameTagDrift
+            | Self::SuspectTranscode
+            | Self::Clipping
+            | Self::LowHeadroom
+            | Self::DcOffset => SafetyTier::Review,
         }
     }
 }
@@ -308,7 +330,7 @@ fn has_bare_year(name: &str) -> bool {
 /// Pre-pass: detect directories that contain 2+ files with track-number
 /// prefixes (e.g. `01 `, `02-`, `03.`). These are album directories even
 /// without a year suffix.
-fn detect_album_dirs(paths: &[std::path::PathBuf]) -> HashSet<std::path::PathBuf> {
+pub(crate) fn detect_album_dirs(paths: &[std::path::PathBuf]) -> HashSet<std::path::PathBuf> {
     static TRACK_PREFIX: LazyLock<Regex> =
         LazyLock::new(|| Regex::new(r"^\d{2,3}[\s.\-]").expect("TRACK_PREFIX must compile"));
 
@@ -384,7 +406,7 @@ pub fn classify_track_context(
 }
 
 /// Get the effective album directory name, climbing past disc subdirectories.
-fn effective_album_dir_name(path: &Path) -> Option<(&Path, &str)> {
+pub(crate) fn effective_album_dir_name(path: &Path) -> Option<(&Path, &str)> {
     let parent = path.parent()?;
     let dir_name = parent.file_name().and_then(|n| n.to_str())?;
 
@@ -973,6 +995,69 @@ pub fn check_filename(
     issues
 }
 
+// ---------------------------------------------------------------------------
+// Audio analysis checks
+// ---------------------------------------------------------------------------
+
+/// Lossless containers whose spectrum can give away a lossy source.
+const TRANSCODE_CHECK_EXTENSIONS: &[&str] = &["flac", "wav", "aiff", "aif", "aifc"];
+/// Transcode confidence at which SUSPECT_TRANSCODE is raised.
+const SUSPECT_TRANSCODE_MIN_CONFIDENCE: f64 = 0.5;
+
+fn is_transcode_candidate(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| TRANSCODE_CHECK_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Issue types only raised by decoding the audio, i.e. by `analyze` scans.
+const AUDIO_ISSUE_TYPES: [IssueType; 4] = [
+    IssueType::SuspectTranscode,
+    IssueType::Clipping,
+    IssueType::LowHeadroom,
+    IssueType::DcOffset,
+];
+
+/// Decode a file and check its signal: clipping, headroom and DC offset for
+/// every file, plus an encoder lowpass for lossless ones. Returns the metered
+/// quality for caching. Errors are decode/analysis failures, not findings.
+pub fn check_audio(
+    path: &Path,
+    skip: &HashSet<IssueType>,
+) -> Result<(Vec<DetectedIssue>, audio::SignalQuality), String> {
+    let (samples, sample_rate, quality) =
+        audio::decode_with_quality(&path.to_string_lossy()).map_err(|e| e.to_string())?;
+    let mut issues = Vec::new();
+
+    let quality_detail = serde_json::to_string(&quality).map_err(|e| e.to_string())?;
+    for flag in quality.flags() {
+        let issue_type = match flag {
+            audio::QualityFlag::Clipping => IssueType::Clipping,
+            audio::QualityFlag::LowHeadroom => IssueType::LowHeadroom,
+            audio::QualityFlag::DcOffset => IssueType::DcOffset,
+        };
+        if !skip.contains(&issue_type) {
+            issues.push(DetectedIssue {
+                issue_type,
+                detail: Some(quality_detail.clone()),
+            });
+        }
+    }
+
+    if is_transcode_candidate(path) && !skip.contains(&IssueType::SuspectTranscode) {
+        let estimate =
+            audio::estimate_bandwidth(&samples, sample_rate).map_err(|e| e.to_string())?;
+        if estimate.transcode_confidence >= SUSPECT_TRANSCODE_MIN_CONFIDENCE {
+            issues.push(DetectedIssue {
+                issue_type: IssueType::SuspectTranscode,
+                detail: Some(serde_json::to_string(&estimate).map_err(|e| e.to_string())?),
+            });
+        }
+    }
+
+    Ok((issues, quality))
+}
+
 // ---------------------------------------------------------------------------
 // Scan operation
 // ---------------------------------------------------------------------------
@@ -984,6 +1069,7 @@ const BATCH_SIZE: usize = 500;
 pub struct ScanSummary {
     pub files_in_scope: usize,
     pub scanned: usize,
+    pub analyzed: usize,
     pub skipped_unchanged: usize,
     pub missing_from_disk: usize,
     pub skipped_issue_types: Vec<String>,
@@ -1004,13 +1090,13 @@ pub fn enforce_trailing_slash(scope: &str) -> String {
     }
 }
 
-struct WalkResult {
-    files: Vec<std::path::PathBuf>,
-    warnings: Vec<String>,
-    had_errors: bool,
+pub(crate) struct WalkResult {
+    pub(crate) files: Vec<std::path::PathBuf>,
+    pub(crate) warnings: Vec<String>,
+    pub(crate) had_errors: bool,
 }
 
-fn walk_audio_files(scope: &Path) -> Result<WalkResult, String> {
+pub(crate) fn walk_audio_files(scope: &Path) -> Result<WalkResult, String> {
     if !scope.is_dir() {
         return Err(format!("Not a directory: {}", scope.display()));
     }
@@ -1122,6 +1208,7 @@ pub fn scan(
     conn: &Connection,
     scope: &str,
     revalidate: bool,
+    analyze: bool,
     skip_issue_types: &HashSet<IssueType>,
     rekordbox_imported: Option<&HashSet<String>>,
 ) -> Result<ScanSummary, String> {
@@ -1160,6 +1247,7 @@ pub fn scan(
     )?;
 
     let mut scanned = 0usize;
+    let mut analyzed = 0usize;
     let mut skipped_unchanged = 0usize;
     let mut new_issues: HashMap<String, usize> = HashMap::new();
     let mut auto_resolved: HashMap<String, usize> = HashMap::new();
@@ -1240,6 +1328,36 @@ pub fn scan(
                 ));
             }
 
+            // Signal checks decode the whole file, so they only run on request.
+            let mut audio_checked = false;
+            if analyze
+                && AUDIO_ISSUE_TYPES
+                    .iter()
+                    .any(|t| !skip_issue_types.contains(t))
+            {
+                match check_audio(file_path, skip_issue_types) {
+                    Ok((issues, quality)) => {
+                        detected.extend(issues);
+                        audio_checked = true;
+                        analyzed += 1;
+                        // Share the measurement with quality_report.
+                        let quality_json = serde_json::to_string(&quality)
+                            .map_err(|e| format!("Serialize error: {e}"))?;
+                        store::set_audio_analysis(
+                            &tx,
+                            &path_str,
+                            audio::ANALYZER_QUALITY,
+                            size,
+                            store::file_mtime_unix(&metadata),
+                            audio::QUALITY_VERSION,
+                            &quality_json,
+                        )
+                        .map_err(|e| format!("DB error caching quality: {e}"))?;
+                    }
+                    Err(e) => warnings.push(format!("Cannot analyze {path_str}: {e}")),
+                }
+            }
+
             // Annotate rename-type issues with Rekordbox import status
             if let Some(imported_set) = rekordbox_imported {
                 for issue in &mut detected {
@@ -1292,7 +1410,12 @@ pub fn scan(
             if existing_file.is_some() && !matches!(read_result, FileReadResult::Error { .. }) {
                 // Skipped issue types should not be auto-resolved — we didn't check them
                 let mut types_still_open: Vec<&str> = detected_types.clone();
-                for skip_type in skip_issue_types {
+                let unchecked = if audio_checked {
+                    &[][..]
+                } else {
+                    &AUDIO_ISSUE_TYPES[..]
+                };
+                for skip_type in skip_issue_types.iter().chain(unchecked) {
                     let s = skip_type.as_str();
                     if !types_still_open.contains(&s) {
                         types_still_open.push(s);
@@ -1373,6 +1496,7 @@ pub fn scan(
     Ok(ScanSummary {
         files_in_scope,
         scanned,
+        analyzed,
         skipped_unchanged,
         missing_from_disk,
         skipped_issue_types: skipped_names,
@@ -1500,6 +1624,210 @@ pub fn get_summary(conn: &Connection, scope: &str) -> Result<SummaryReport, Stri
     })
 }
 
+// ---------------------------------------------------------------------------
+// Orphans — audio on disk that is not in the Rekordbox collection
+// ---------------------------------------------------------------------------
+
+/// Orphaned files sharing one directory (usually one album).
+#[derive(Debug, Serialize)]
+pub struct OrphanGroup {
+    pub directory: String,
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrphanReport {
+    pub scope: String,
+    pub files_in_scope: usize,
+    pub imported: usize,
+    pub orphans: usize,
+    /// Groups sorted by directory; truncated to the requested limit.
+    pub groups: Vec<OrphanGroup>,
+    pub total_groups: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xml_path: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Name of the XML folder holding one playlist per orphan directory.
+const ORPHAN_PLAYLIST_FOLDER: &str = "Orphans";
+
+/// List audio files under `scope` whose path is not in `imported` (from
+/// `db::imported_paths`), grouped by parent directory. Paths are compared
+/// after `normalize_path_for_matching`. When `xml_path` is set, also writes
+/// a Rekordbox XML containing only the orphans, with one playlist per
+/// directory under an "Orphans" folder.
+pub fn find_orphans(
+    scope: &str,
+    imported: &[String],
+    group_limit: usize,
+    xml_path: Option<&Path>,
+) -> Result<OrphanReport, String> {
+    let scope = enforce_trailing_slash(scope);
+    if scope == "/" {
+        return Err("Scope must not be empty or root (/)".to_string());
+    }
+    let walk = walk_audio_files(Path::new(&scope))?;
+    let files_in_scope = walk.files.len();
+    let imported: HashSet<String> = imported
+        .iter()
+        .map(|p| normalize_path_for_matching(p))
+        .collect();
+
+    let mut by_directory: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut orphans = 0;
+    for path in &walk.files {
+        let path_str = path.to_string_lossy().to_string();
+        if imported.contains(&normalize_path_for_matching(&path_str)) {
+            continue;
+        }
+        orphans += 1;
+        let directory = path
+            .parent()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        by_directory.entry(directory).or_default().push(path_str);
+    }
+    let mut groups: Vec<OrphanGroup> = by_directory
+        .into_iter()
+        .map(|(directory, files)| OrphanGroup { directory, files })
+        .collect();
+
+    let xml_path = match xml_path {
+        Some(out) if orphans > 0 => {
+            write_orphan_xml(&groups, out)?;
+            Some(out.display().to_string())
+        }
+        _ => None,
+    };
+
+    let total_groups = groups.len();
+    groups.truncate(group_limit);
+    Ok(OrphanReport {
+        scope,
+        files_in_scope,
+        imported: files_in_scope - orphans,
+        orphans,
+        groups,
+        total_groups,
+        xml_path,
+        warnings: walk.warnings,
+    })
+}
+
+/// Duration (seconds), bit rate (kbps) and sample rate (Hz) from the file
+/// header; zeros when the header can't be read.
+fn read_audio_properties(path: &Path) -> (i32, i32, i32) {
+    let Some(tagged_file) = Probe::open(path).ok().and_then(|p| p.read().ok()) else {
+        return (0, 0, 0);
+    };
+    let properties = tagged_file.properties();
+    (
+        properties.duration().as_secs() as i32,
+        properties.audio_bitrate().unwrap_or(0) as i32,
+        properties.sample_rate().unwrap_or(0) as i32,
+    )
+}
+
+/// Build an XML track for an orphan from its file tags and header; the title
+/// falls back to the file stem. `id` is the collection-local numeric ID.
+fn orphan_track(id: usize, path: &str) -> Track {
+    let fields: Vec<String> = [
+        "title",
+        "artist",
+        "album",
+        "genre",
+        "year",
+        "comment",
+        "publisher",
+        "bpm",
+        "key",
+        "remixer",
+    ]
+    .map(String::from)
+    .to_vec();
+    let tags = match tags::read_file_tags(Path::new(path), Some(&fields), false) {
+        FileReadResult::Single { tags, .. } => tags,
+        FileReadResult::Wav { id3v2, .. } => id3v2,
+        FileReadResult::Error { .. } => HashMap::new(),
+    };
+    let tag = |field: &str| {
+        tags.get(field)
+            .cloned()
+            .flatten()
+            .map(|v| v.trim().to_string())
+            .unwrap_or_default()
+    };
+    let file = Path::new(path);
+    let (length, bit_rate, sample_rate) = read_audio_properties(file);
+    let title = match tag("title") {
+        t if t.is_empty() => file
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        t => t,
+    };
+    Track {
+        id: id.to_string(),
+        title,
+        artist: tag("artist"),
+        album: tag("album"),
+        genre: tag("genre"),
+        bpm: tag("bpm").parse().unwrap_or(0.0),
+        key: tag("key"),
+        rating: 0,
+        comments: tag("comment"),
+        color: String::new(),
+        color_code: 0,
+        label: tag("publisher"),
+        remixer: tag("remixer"),
+        year: tag("year")
+            .get(..4)
+            .and_then(|y| y.parse().ok())
+            .unwrap_or(0),
+        length,
+        file_path: path.to_string(),
+        play_count: 0,
+        bit_rate,
+        sample_rate,
+        file_kind: FileKind::from_extension(
+            file.extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default(),
+        ),
+        date_added: chrono::Local::now().format("%Y-%m-%d").to_string(),
+        my_tags: Vec::new(),
+        hot_cue_banks: Vec::new(),
+        related_track_lists: Vec::new(),
+        position: None,
+        cues: Vec::new(),
+        tempo_markers: Vec::new(),
+    }
+}
+
+fn write_orphan_xml(groups: &[OrphanGroup], out: &Path) -> Result<(), String> {
+    let tracks: Vec<Track> = groups
+        .iter()
+        .flat_map(|g| &g.files)
+        .enumerate()
+        .map(|(i, f)| orphan_track(i + 1, f))
+        .collect();
+    let mut ids = tracks.iter().map(|t| t.id.clone());
+    let playlists: Vec<PlaylistDef> = groups
+        .iter()
+        .map(|g| PlaylistDef {
+            name: Path::new(&g.directory)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| g.directory.clone()),
+            track_ids: ids.by_ref().take(g.files.len()).collect(),
+            folder: vec![ORPHAN_PLAYLIST_FOLDER.to_string()],
+        })
+        .collect();
+    xml::write_xml_with_playlists(&tracks, &playlists, out)
+        .map_err(|e| format!("Failed to write orphan XML to {}: {e}", out.display()))
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -2250,6 +2578,75 @@ mod tests {
         );
     }
 
+    #[test]
+    fn find_orphans_groups_unimported_files_by_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let album = dir.path().join("Artist/Album (2024)");
+        let disc = album.join("CD2");
+        std::fs::create_dir_all(&disc).unwrap();
+        for path in [
+            album.join("01 Artist - One.flac"),
+            album.join("02 Artist - Two.flac"),
+            disc.join("01 Artist - Three.flac"),
+            album.join("03 Caf\u{e9} Noir.flac"),
+        ] {
+            std::fs::write(path, b"not-audio").unwrap();
+        }
+        write_wav(&dir.path().join("Loose - Track.wav"), &[0.0; 88200]);
+        // Rekordbox may hold the decomposed, percent-encoded form of a path.
+        let imported = vec![
+            album
+                .join("01 Artist - One.flac")
+                .to_string_lossy()
+                .to_string(),
+            format!("{}/03%20Cafe\u{301}%20Noir.flac", album.display()),
+        ];
+
+        let xml_path = dir.path().join("out/orphans.xml");
+        let scope = dir.path().to_str().unwrap();
+        let report = find_orphans(scope, &imported, 10, Some(&xml_path)).unwrap();
+
+        assert_eq!(report.files_in_scope, 5);
+        assert_eq!(report.imported, 2);
+        assert_eq!(report.orphans, 3);
+        let dirs: Vec<&str> = report.groups.iter().map(|g| g.directory.as_str()).collect();
+        assert_eq!(
+            dirs,
+            [
+                dir.path().to_str().unwrap(),
+                album.to_str().unwrap(),
+                disc.to_str().unwrap(),
+            ]
+        );
+        assert_eq!(report.groups[1].files.len(), 1);
+
+        let xml = std::fs::read_to_string(&xml_path).unwrap();
+        assert!(xml.contains("<COLLECTION Entries=\"3\">"));
+        assert!(xml.contains("Name=\"Orphans\""));
+        assert!(xml.contains("Name=\"Album (2024)\""));
+        assert!(
+            xml.contains("Name=\"02 Artist - Two\""),
+            "title falls back to the stem"
+        );
+        assert!(!xml.contains("01 Artist - One"));
+        assert!(xml.contains("<TRACK TrackID=\"1\" Name=\"Loose - Track\""));
+        assert!(xml.contains("<TRACK Key=\"1\"/>"));
+        assert!(
+            xml.contains("TotalTime=\"2\"") && xml.contains("SampleRate=\"44100\""),
+            "header properties come from the file"
+        );
+
+        let limited = find_orphans(scope, &imported, 1, None).unwrap();
+        assert_eq!(limited.groups.len(), 1);
+        assert_eq!(limited.total_groups, 3);
+        assert!(limited.xml_path.is_none());
+    }
+
+    #[test]
+    fn find_orphans_rejects_root_scope() {
+        assert!(find_orphans("", &[], 10, None).is_err());
+    }
+
     #[cfg(unix)]
     #[test]
     fn scan_skips_missing_cleanup_when_walk_hits_unreadable_subdir() {
@@ -2277,7 +2674,14 @@ mod tests {
         no_access.set_mode(0o000);
         std::fs::set_permissions(&blocked_dir, no_access).unwrap();
 
-        let scan_result = scan(&conn, dir.path().to_str().unwrap(), false, &HashSet::new(), None);
+        let scan_result = scan(
+            &conn,
+            dir.path().to_str().unwrap(),
+            false,
+            false,
+            &HashSet::new(),
+            None,
+        );
 
         std::fs::set_permissions(&blocked_dir, original_perms).unwrap();
 
@@ -2297,6 +2701,125 @@ mod tests {
         );
     }
 
+    /// Write mono 16-bit 44.1 kHz PCM samples as a WAV file.
+    fn write_wav(path: &Path, samples: &[f32]) {
+        let sample_rate = 44100u32;
+        let data_len = (samples.len() * 2) as u32;
+        let mut bytes = Vec::with_capacity(44 + data_len as usize);
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for &sample in samples {
+            let value = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    /// 1.5 s of equal-level tones every 50 Hz up to `top_hz` — a flat
+    /// spectrum with nothing above the top tone.
+    fn tone_comb(top_hz: f32) -> Vec<f32> {
+        let freqs: Vec<f32> = (2..)
+            .map(|k| k as f32 * 50.0)
+            .take_while(|&f| f <= top_hz)
+            .collect();
+        (0..44100 * 3 / 2)
+            .map(|i| {
+                let t = i as f32 / 44100.0;
+                let value: f32 = freqs
+                    .iter()
+                    .enumerate()
+                    .map(|(k, &freq)| (std::f32::consts::TAU * freq * t + (k * k) as f32).sin())
+                    .sum();
+                value * 0.002
+            })
+            .collect()
+    }
+
+    #[test]
+    fn scan_analyze_flags_suspect_transcodes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("internal.sqlite3");
+        let conn = store::open(db_path.to_str().unwrap()).unwrap();
+        let music = dir.path().join("music");
+        std::fs::create_dir(&music).unwrap();
+        let transcode = music.join("Artist - Transcode.wav");
+        let genuine = music.join("Artist - Genuine.wav");
+        write_wav(&transcode, &tone_comb(16_000.0));
+        write_wav(&genuine, &tone_comb(22_000.0));
+        let scope = music.to_str().unwrap();
+        let suspects = |conn: &Connection| {
+            store::get_open_issues_by_types(conn, scope, &["SUSPECT_TRANSCODE"]).unwrap()
+        };
+
+        let summary = scan(&conn, scope, false, false, &HashSet::new(), None).unwrap();
+        assert_eq!(summary.analyzed, 0);
+        assert!(suspects(&conn).is_empty());
+
+        let summary = scan(&conn, scope, true, true, &HashSet::new(), None).unwrap();
+        assert_eq!(summary.analyzed, 2, "{:?}", summary.warnings);
+        let found = suspects(&conn);
+        assert_eq!(found.len(), 1);
+        let (_, path, _, detail) = &found[0];
+        assert_eq!(path, transcode.to_str().unwrap());
+        let detail: serde_json::Value = serde_json::from_str(detail.as_deref().unwrap()).unwrap();
+        let bandwidth = detail["effective_bandwidth_hz"].as_f64().unwrap();
+        assert!((bandwidth - 16_000.0).abs() < 150.0, "{detail}");
+        assert!(detail["transcode_confidence"].as_f64().unwrap() >= 0.5);
+
+        // A scan that doesn't analyze leaves the finding alone.
+        scan(&conn, scope, true, false, &HashSet::new(), None).unwrap();
+        assert_eq!(suspects(&conn).len(), 1);
+    }
+
+    #[test]
+    fn scan_analyze_flags_clipping_and_caches_quality() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("internal.sqlite3");
+        let conn = store::open(db_path.to_str().unwrap()).unwrap();
+        let music = dir.path().join("music");
+        std::fs::create_dir(&music).unwrap();
+        let clipped = music.join("Artist - Clipped.wav");
+        let clean = music.join("Artist - Clean.wav");
+        let overdriven: Vec<f32> = (0..44100)
+            .map(|i| 2.0 * (std::f32::consts::TAU * 100.0 * i as f32 / 44100.0).sin())
+            .collect();
+        write_wav(&clipped, &overdriven);
+        write_wav(&clean, &tone_comb(22_000.0));
+        let scope = music.to_str().unwrap();
+
+        let skip = HashSet::from([IssueType::SuspectTranscode]);
+        let summary = scan(&conn, scope, false, true, &skip, None).unwrap();
+        assert_eq!(summary.analyzed, 2, "{:?}", summary.warnings);
+        let flagged = store::get_open_issues_by_types(
+            &conn,
+            scope,
+            &["CLIPPING", "LOW_HEADROOM", "DC_OFFSET"],
+        )
+        .unwrap();
+        assert_eq!(flagged.len(), 1, "{flagged:?}");
+        let (_, path, issue_type, _) = &flagged[0];
+        assert_eq!(path, clipped.to_str().unwrap());
+        assert_eq!(issue_type, "CLIPPING");
+
+        let cached =
+            store::get_audio_analysis(&conn, clean.to_str().unwrap(), audio::ANALYZER_QUALITY)
+                .unwrap()
+                .expect("quality should be cached for quality_report");
+        let quality: audio::SignalQuality = serde_json::from_str(&cached.features_json).unwrap();
+        assert_eq!(quality.clipped_runs, 0);
+        assert!(quality.sample_peak_dbfs < -1.0);
+    }
+
     // -- has_year_suffix: compound parenthetical content --
 
     #[test]
@@ -1,8 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Mutex, MutexGuard};
 
+use serde::Serialize;
+
 use crate::color;
-use crate::types::{EditableField, FieldDiff, Track, TrackChange, TrackDiff};
+use crate::types::{
+    CuePoint, EditableField, FieldDiff, StagedCue, Track, TrackChange, TrackDiff, apply_staged_cues,
+};
 
 fn acquire_or_recover_lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
     mutex.lock().unwrap_or_else(|e| {
@@ -11,20 +15,148 @@ fn acquire_or_recover_lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
     })
 }
 
+/// Maximum number of undo steps retained; the oldest steps are dropped first.
+const MAX_HISTORY_STEPS: usize = 100;
+
+/// A recorded stage/clear operation, as reported by `change_history`.
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub step: u64,
+    /// Tool call that produced the step (e.g. `update_tracks`).
+    pub tool: String,
+    pub summary: String,
+    pub track_ids: Vec<String>,
+    pub recorded_at: String,
+}
+
+/// Staged state of each touched track before and after a step (`None` = no entry).
+struct HistoryStep {
+    entry: HistoryEntry,
+    before: Vec<(String, Option<TrackChange>)>,
+    after: Vec<(String, Option<TrackChange>)>,
+}
+
+#[derive(Default)]
+struct History {
+    undo: Vec<HistoryStep>,
+    redo: Vec<HistoryStep>,
+    next_step: u64,
+}
+
 pub struct ChangeManager {
     changes: Mutex<HashMap<String, TrackChange>>,
+    /// Internal-store session the staged changes are persisted to, if any.
+    session_id: Mutex<Option<String>>,
+    /// Undo/redo log. Always locked after `changes`.
+    history: Mutex<History>,
+    /// Tracks whose staged state changed since the session was last persisted.
+    /// Always locked after `changes`.
+    dirty: Mutex<HashSet<String>>,
+    /// Held while persisting, so writes reach the store in mutation order.
+    persist: Mutex<()>,
 }
 
+/// Staged state of a track to persist; `None` removes its persisted row.
+pub type PersistEntry = (String, Option<TrackChange>);
+
 impl ChangeManager {
     pub fn new() -> Self {
         Self {
             changes: Mutex::new(HashMap::new()),
+            session_id: Mutex::new(None),
+            history: Mutex::new(History::default()),
+            dirty: Mutex::new(HashSet::new()),
+            persist: Mutex::new(()),
         }
     }
 
+    pub fn session_id(&self) -> Option<String> {
+        acquire_or_recover_lock(&self.session_id).clone()
+    }
+
+    pub fn set_session_id(&self, session_id: Option<String>) {
+        *acquire_or_recover_lock(&self.session_id) = session_id;
+    }
+
+    /// Copy of all staged changes, sorted by track ID. Does not modify staged state.
+    pub fn snapshot(&self) -> Vec<TrackChange> {
+        let map = acquire_or_recover_lock(&self.changes);
+        let mut changes: Vec<TrackChange> = map.values().cloned().collect();
+        changes.sort_by(|a, b| a.track_id.cmp(&b.track_id));
+        changes
+    }
+
+    /// Replace all staged changes with `changes` loaded from `session_id` and make
+    /// it the session they persist to (e.g. when resuming a saved session). The
+    /// session already holds them, so nothing is left to persist. Undo/redo history
+    /// is discarded since it described the previous queue.
+    pub fn replace_all(&self, session_id: Option<String>, changes: Vec<TrackChange>) -> usize {
+        let mut map = acquire_or_recover_lock(&self.changes);
+        map.clear();
+        for change in changes {
+            if has_any_staged_field(&change) {
+                map.insert(change.track_id.clone(), change);
+            }
+        }
+        acquire_or_recover_lock(&self.dirty).clear();
+        self.set_session_id(session_id);
+        self.clear_history();
+        map.len()
+    }
+
+    /// Hand the tracks changed since the last call to `write` along with the
+    /// session they belong to. The persist lock is held throughout and the delta
+    /// is taken under the staging lock, so concurrent callers write in mutation
+    /// order. On failure the tracks stay pending for the next call. Without a
+    /// session the delta is dropped, as there is nothing to write it to.
+    pub fn persist_with<E>(
+        &self,
+        write: impl FnOnce(&str, &[PersistEntry]) -> Result<(), E>,
+    ) -> Result<usize, E> {
+        let _writer = acquire_or_recover_lock(&self.persist);
+        let (session_id, delta) = {
+            let map = acquire_or_recover_lock(&self.changes);
+            let mut dirty = acquire_or_recover_lock(&self.dirty);
+            let mut delta: Vec<PersistEntry> = dirty
+                .drain()
+                .map(|id| {
+                    let change = map.get(&id).cloned();
+                    (id, change)
+                })
+                .collect();
+            delta.sort_by(|a, b| a.0.cmp(&b.0));
+            (self.session_id(), delta)
+        };
+        let Some(session_id) = session_id else {
+            return Ok(0);
+        };
+        if delta.is_empty() {
+            return Ok(0);
+        }
+        if let Err(e) = write(&session_id, &delta) {
+            acquire_or_recover_lock(&self.dirty).extend(delta.into_iter().map(|(id, _)| id));
+            return Err(e);
+        }
+        Ok(delta.len())
+    }
+
+    /// Note tracks whose staged state may have changed. Call with `changes` locked.
+    fn mark_dirty<'a>(&self, track_ids: impl Iterator<Item = &'a str>) {
+        acquire_or_recover_lock(&self.dirty).extend(track_ids.map(str::to_string));
+    }
+
     /// Stage changes for one or more tracks. Merges with previously staged changes for the same track.
-    pub fn stage(&self, changes: Vec<TrackChange>) -> (usize, usize) {
+    /// `tool` names the caller in the undo history.
+    pub fn stage(&self, tool: &str, changes: Vec<TrackChange>) -> (usize, usize) {
         let mut staged_changes_by_track_id = acquire_or_recover_lock(&self.changes);
+        let before = capture_entries(
+            &staged_changes_by_track_id,
+            changes
+                .iter()
+                .filter(|c| has_any_staged_field(c))
+                .map(|c| c.track_id.as_str()),
+        );
+        self.mark_dirty(before.iter().map(|(id, _)| id.as_str()));
         let mut staged = 0;
         for change in changes {
             if !has_any_staged_field(&change) {
@@ -36,9 +168,103 @@ impl ChangeManager {
                 .and_modify(|existing| merge_track_change(existing, &change))
                 .or_insert(change);
         }
+        self.record_step(
+            &staged_changes_by_track_id,
+            tool,
+            format!("staged {staged} change(s)"),
+            before,
+        );
         (staged, staged_changes_by_track_id.len())
     }
 
+    /// Revert up to `steps` recorded operations, newest first.
+    /// Returns the undone entries and the number of tracks still pending.
+    pub fn undo(&self, steps: usize) -> (Vec<HistoryEntry>, usize) {
+        let mut map = acquire_or_recover_lock(&self.changes);
+        let mut history = acquire_or_recover_lock(&self.history);
+        let mut undone = Vec::new();
+        for _ in 0..steps {
+            let Some(step) = history.undo.pop() else {
+                break;
+            };
+            restore_entries(&mut map, &step.before);
+            self.mark_dirty(step.before.iter().map(|(id, _)| id.as_str()));
+            undone.push(step.entry.clone());
+            history.redo.push(step);
+        }
+        (undone, map.len())
+    }
+
+    /// Re-apply up to `steps` previously undone operations, oldest first.
+    /// Returns the redone entries and the number of tracks pending.
+    pub fn redo(&self, steps: usize) -> (Vec<HistoryEntry>, usize) {
+        let mut map = acquire_or_recover_lock(&self.changes);
+        let mut history = acquire_or_recover_lock(&self.history);
+        let mut redone = Vec::new();
+        for _ in 0..steps {
+            let Some(step) = history.redo.pop() else {
+                break;
+            };
+            restore_entries(&mut map, &step.after);
+            self.mark_dirty(step.after.iter().map(|(id, _)| id.as_str()));
+            redone.push(step.entry.clone());
+            history.undo.push(step);
+        }
+        (redone, map.len())
+    }
+
+    /// Recorded steps as `(undoable, redoable)`, each ordered by the next step to apply first.
+    pub fn history(&self) -> (Vec<HistoryEntry>, Vec<HistoryEntry>) {
+        let history = acquire_or_recover_lock(&self.history);
+        let undo = history.undo.iter().rev().map(|s| s.entry.clone()).collect();
+        let redo = history.redo.iter().rev().map(|s| s.entry.clone()).collect();
+        (undo, redo)
+    }
+
+    /// Drop all undo/redo steps (e.g. after staged changes are written out).
+    pub fn clear_history(&self) {
+        let mut history = acquire_or_recover_lock(&self.history);
+        history.undo.clear();
+        history.redo.clear();
+    }
+
+    /// Record a reversible step for the tracks in `before`, skipping tracks whose
+    /// staged state did not change. A new step invalidates the redo stack.
+    fn record_step(
+        &self,
+        map: &HashMap<String, TrackChange>,
+        tool: &str,
+        summary: String,
+        before: Vec<(String, Option<TrackChange>)>,
+    ) {
+        let before: Vec<(String, Option<TrackChange>)> = before
+            .into_iter()
+            .filter(|(id, prev)| map.get(id) != prev.as_ref())
+            .collect();
+        if before.is_empty() {
+            return;
+        }
+        let after = capture_entries(map, before.iter().map(|(id, _)| id.as_str()));
+        let mut history = acquire_or_recover_lock(&self.history);
+        history.next_step += 1;
+        let entry = HistoryEntry {
+            step: history.next_step,
+            tool: tool.to_string(),
+            summary,
+            track_ids: before.iter().map(|(id, _)| id.clone()).collect(),
+            recorded_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        };
+        history.undo.push(HistoryStep {
+            entry,
+            before,
+            after,
+        });
+        if history.undo.len() > MAX_HISTORY_STEPS {
+            history.undo.remove(0);
+        }
+        history.redo.clear();
+    }
+
     pub fn pending_ids(&self) -> Vec<String> {
         let map = acquire_or_recover_lock(&self.changes);
         let mut ids: Vec<String> = map.keys().cloned().collect();
@@ -72,44 +298,132 @@ impl ChangeManager {
             };
 
             let mut field_diffs = Vec::new();
+            push_text_diff(
+                &mut field_diffs,
+                EditableField::Genre,
+                &track.genre,
+                &change.genre,
+            );
+            // Staged My Tags are exported as a block in Comments, so show the rewrite.
+            let staged_comments = match change.my_tags {
+                Some(ref my_tags) => Some(comments_with_my_tags(
+                    change.comments.as_deref().unwrap_or(&track.comments),
+                    my_tags,
+                )),
+                None => change.comments.clone(),
+            };
+            push_text_diff(
+                &mut field_diffs,
+                EditableField::Comments,
+                &track.comments,
+                &staged_comments,
+            );
 
-            if let Some(ref new_genre) = change.genre
-                && *new_genre != track.genre
+            if let Some(new_rating) = change.rating
+                && new_rating != track.rating
             {
                 field_diffs.push(FieldDiff {
-                    field: "genre".to_string(),
-                    old_value: track.genre.clone(),
-                    new_value: new_genre.clone(),
+                    field: "rating".to_string(),
+                    old_value: track.rating.to_string(),
+                    new_value: new_rating.to_string(),
                 });
             }
 
-            if let Some(ref new_comments) = change.comments
-                && *new_comments != track.comments
+            push_text_diff(
+                &mut field_diffs,
+                EditableField::Color,
+                &track.color,
+                &change.color,
+            );
+            push_text_diff(
+                &mut field_diffs,
+                EditableField::Title,
+                &track.title,
+                &change.title,
+            );
+            push_text_diff(
+                &mut field_diffs,
+                EditableField::Artist,
+                &track.artist,
+                &change.artist,
+            );
+            push_text_diff(
+                &mut field_diffs,
+                EditableField::Album,
+                &track.album,
+                &change.album,
+            );
+            push_text_diff(
+                &mut field_diffs,
+                EditableField::Label,
+                &track.label,
+                &change.label,
+            );
+            push_text_diff(
+                &mut field_diffs,
+                EditableField::Remixer,
+                &track.remixer,
+                &change.remixer,
+            );
+            push_text_diff(
+                &mut field_diffs,
+                EditableField::Key,
+                &track.key,
+                &change.key,
+            );
+            push_text_diff(
+                &mut field_diffs,
+                EditableField::FilePath,
+                &track.file_path,
+                &change.file_path,
+            );
+            push_text_diff(
+                &mut field_diffs,
+                EditableField::MergeInto,
+                "",
+                &change.merge_into,
+            );
+
+            if let Some(new_year) = change.year
+                && new_year != track.year
             {
                 field_diffs.push(FieldDiff {
-                    field: "comments".to_string(),
-                    old_value: track.comments.clone(),
-                    new_value: new_comments.clone(),
+                    field: "year".to_string(),
+                    old_value: track.year.to_string(),
+                    new_value: new_year.to_string(),
                 });
             }
 
-            if let Some(new_rating) = change.rating
-                && new_rating != track.rating
+            if let Some(ref new_tags) = change.my_tags
+                && *new_tags != track.my_tags
             {
                 field_diffs.push(FieldDiff {
-                    field: "rating".to_string(),
-                    old_value: track.rating.to_string(),
-                    new_value: new_rating.to_string(),
+                    field: EditableField::MyTags.as_str().to_string(),
+                    old_value: track.my_tags.join(MY_TAG_SEPARATOR),
+                    new_value: new_tags.join(MY_TAG_SEPARATOR),
                 });
             }
 
-            if let Some(ref new_color) = change.color
-                && *new_color != track.color
+            if let Some(ref staged) = change.cues {
+                let mut cues = track.cues.clone();
+                apply_staged_cues(&mut cues, staged);
+                if cues != track.cues {
+                    field_diffs.push(FieldDiff {
+                        field: EditableField::Cues.as_str().to_string(),
+                        old_value: describe_cues(&track.cues),
+                        new_value: describe_cues(&cues),
+                    });
+                }
+            }
+
+            // BPM is stored in hundredths in master.db, so compare at that resolution.
+            if let Some(new_bpm) = change.bpm
+                && (new_bpm * 100.0).round() != (track.bpm * 100.0).round()
             {
                 field_diffs.push(FieldDiff {
-                    field: "color".to_string(),
-                    old_value: track.color.clone(),
-                    new_value: new_color.clone(),
+                    field: "bpm".to_string(),
+                    old_value: format!("{:.2}", track.bpm),
+                    new_value: format!("{new_bpm:.2}"),
                 });
             }
 
@@ -146,20 +460,23 @@ impl ChangeManager {
     /// Remove and return staged changes. If `track_ids` is None, drains all staged changes.
     pub fn take(&self, track_ids: Option<Vec<String>>) -> Vec<TrackChange> {
         let mut map = acquire_or_recover_lock(&self.changes);
-        match track_ids {
+        let taken: Vec<TrackChange> = match track_ids {
             Some(ids) => ids.into_iter().filter_map(|id| map.remove(&id)).collect(),
             None => {
                 let mut drained: Vec<TrackChange> = map.drain().map(|(_, change)| change).collect();
                 drained.sort_by(|a, b| a.track_id.cmp(&b.track_id));
                 drained
             }
-        }
+        };
+        self.mark_dirty(taken.iter().map(|c| c.track_id.as_str()));
+        taken
     }
 
     /// Restore previously taken changes without overwriting newer staged values.
     pub fn restore(&self, snapshot: Vec<TrackChange>) -> (usize, usize) {
         let mut map = acquire_or_recover_lock(&self.changes);
         let restored = snapshot.len();
+        self.mark_dirty(snapshot.iter().map(|c| c.track_id.as_str()));
         for change in snapshot {
             map.entry(change.track_id.clone())
                 .and_modify(|existing| merge_missing_fields(existing, &change))
@@ -172,6 +489,7 @@ impl ChangeManager {
     /// If all fields on a track become None, the entry is removed entirely.
     pub fn clear_fields(
         &self,
+        tool: &str,
         track_ids: Option<Vec<String>>,
         fields: &[String],
     ) -> (usize, usize) {
@@ -180,50 +498,45 @@ impl ChangeManager {
             Some(ids) => ids,
             None => map.keys().cloned().collect(),
         };
+        let before = capture_entries(&map, target_ids.iter().map(String::as_str));
+        self.mark_dirty(target_ids.iter().map(String::as_str));
 
         let mut affected = 0;
         for id in &target_ids {
             if let Some(entry) = map.get_mut(id) {
                 let mut touched = false;
                 for field in fields {
-                    match EditableField::from_str(field.as_str()) {
-                        Some(EditableField::Genre) if entry.genre.is_some() => {
-                            entry.genre = None;
-                            touched = true;
-                        }
-                        Some(EditableField::Comments) if entry.comments.is_some() => {
-                            entry.comments = None;
-                            touched = true;
-                        }
-                        Some(EditableField::Rating) if entry.rating.is_some() => {
-                            entry.rating = None;
-                            touched = true;
-                        }
-                        Some(EditableField::Color) if entry.color.is_some() => {
-                            entry.color = None;
-                            touched = true;
-                        }
-                        _ => {}
+                    if let Some(field) = EditableField::from_str(field.as_str())
+                        && clear_field(entry, field)
+                    {
+                        touched = true;
                     }
                 }
                 if touched {
                     affected += 1;
                 }
                 // Remove entry if all fields are now None
-                if entry.genre.is_none()
-                    && entry.comments.is_none()
-                    && entry.rating.is_none()
-                    && entry.color.is_none()
-                {
+                if !has_any_staged_field(entry) {
                     map.remove(id);
                 }
             }
         }
+        self.record_step(
+            &map,
+            tool,
+            format!("cleared {} on {affected} track(s)", fields.join(", ")),
+            before,
+        );
         (affected, map.len())
     }
 
-    pub fn clear(&self, track_ids: Option<Vec<String>>) -> (usize, usize) {
+    pub fn clear(&self, tool: &str, track_ids: Option<Vec<String>>) -> (usize, usize) {
         let mut map = acquire_or_recover_lock(&self.changes);
+        let before = match &track_ids {
+            Some(ids) => capture_entries(&map, ids.iter().map(String::as_str)),
+            None => capture_entries(&map, map.keys().map(String::as_str)),
+        };
+        self.mark_dirty(before.iter().map(|(id, _)| id.as_str()));
         let cleared = match track_ids {
             Some(ids) => {
                 let mut count = 0;
@@ -240,15 +553,152 @@ impl ChangeManager {
                 count
             }
         };
+        self.record_step(&map, tool, format!("cleared {cleared} track(s)"), before);
         (cleared, map.len())
     }
 }
 
+/// Current staged entry for each distinct ID, in first-seen order.
+fn capture_entries<'a>(
+    map: &HashMap<String, TrackChange>,
+    track_ids: impl Iterator<Item = &'a str>,
+) -> Vec<(String, Option<TrackChange>)> {
+    let mut seen = std::collections::HashSet::new();
+    track_ids
+        .filter(|id| seen.insert(*id))
+        .map(|id| (id.to_string(), map.get(id).cloned()))
+        .collect()
+}
+
+fn restore_entries(
+    map: &mut HashMap<String, TrackChange>,
+    entries: &[(String, Option<TrackChange>)],
+) {
+    for (id, change) in entries {
+        match change {
+            Some(change) => {
+                map.insert(id.clone(), change.clone());
+            }
+            None => {
+                map.remove(id);
+            }
+        }
+    }
+}
+
 fn has_any_staged_field(change: &TrackChange) -> bool {
     change.genre.is_some()
         || change.comments.is_some()
         || change.rating.is_some()
         || change.color.is_some()
+        || change.title.is_some()
+        || change.artist.is_some()
+        || change.album.is_some()
+        || change.label.is_some()
+        || change.remixer.is_some()
+        || change.year.is_some()
+        || change.bpm.is_some()
+        || change.key.is_some()
+        || change.my_tags.is_some()
+        || change.file_path.is_some()
+        || change.merge_into.is_some()
+        || change.cues.is_some()
+}
+
+/// Separator between tag names in Rekordbox's "Add My Tag to the Comments" block.
+const MY_TAG_SEPARATOR: &str = " / ";
+
+/// Rewrite `comments` with a trailing `/* Tag / Tag */` block, the format Rekordbox
+/// uses for "Add My Tag to the Comments". XML has no My Tag element, so this is how
+/// staged tags reach Rekordbox. Any existing block is replaced; no tags removes it.
+pub fn comments_with_my_tags(comments: &str, my_tags: &[String]) -> String {
+    let block_range = comments
+        .find("/*")
+        .and_then(|start| Some((start, start + comments[start..].find("*/")? + 2)));
+    let base = match block_range {
+        Some((start, end)) => format!(
+            "{} {}",
+            comments[..start].trim_end(),
+            comments[end..].trim_start()
+        )
+        .trim()
+        .to_string(),
+        None => comments.trim().to_string(),
+    };
+    if my_tags.is_empty() {
+        return base;
+    }
+    let block = format!("/* {} */", my_tags.join(MY_TAG_SEPARATOR));
+    if base.is_empty() {
+        block
+    } else {
+        format!("{base} {block}")
+    }
+}
+
+/// One-line summary of cues for previews, e.g. `memory 0.250s, hot A 64.000s`.
+fn describe_cues(cues: &[CuePoint]) -> String {
+    cues.iter()
+        .map(|cue| {
+            let kind = match cue.hot_cue {
+                Some(slot) => format!(
+                    "hot {}",
+                    crate::types::HOT_CUE_BANKS
+                        .get(slot as usize)
+                        .copied()
+                        .unwrap_or('?')
+                ),
+                None if cue.end.is_some() => "loop".to_string(),
+                None => "memory".to_string(),
+            };
+            let name = if cue.name.is_empty() {
+                String::new()
+            } else {
+                format!(" \"{}\"", cue.name)
+            };
+            format!("{kind} {:.3}s{name}", cue.start)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn push_text_diff(
+    diffs: &mut Vec<FieldDiff>,
+    field: EditableField,
+    current: &str,
+    staged: &Option<String>,
+) {
+    if let Some(new_value) = staged
+        && new_value != current
+    {
+        diffs.push(FieldDiff {
+            field: field.as_str().to_string(),
+            old_value: current.to_string(),
+            new_value: new_value.clone(),
+        });
+    }
+}
+
+/// Reset one staged field to `None`. Returns true if the field was set.
+fn clear_field(entry: &mut TrackChange, field: EditableField) -> bool {
+    match field {
+        EditableField::Genre => entry.genre.take().is_some(),
+        EditableField::Comments => entry.comments.take().is_some(),
+        EditableField::Rating => entry.rating.take().is_some(),
+        EditableField::Color => entry.color.take().is_some(),
+        EditableField::Title => entry.title.take().is_some(),
+        EditableField::Artist => entry.artist.take().is_some(),
+        EditableField::Album => entry.album.take().is_some(),
+        EditableField::Label => entry.label.take().is_some(),
+        EditableField::Remixer => entry.remixer.take().is_some(),
+        EditableField::Year => entry.year.take().is_some(),
+        EditableField::Bpm => entry.bpm.take().is_some(),
+        EditableField::Key => entry.key.take().is_some(),
+        EditableField::MyTags => entry.my_tags.take().is_some(),
+        EditableField::FilePath => entry.file_path.take().is_some(),
+        EditableField::MergeInto => entry.merge_into.take().is_some(),
+        EditableField::Cues => entry.cues.take().is_some(),
+    }
 }
 
 fn merge_track_change(existing: &mut TrackChange, incoming: &TrackChange) {
@@ -264,6 +714,51 @@ fn merge_track_change(existing: &mut TrackChange, incoming: &TrackChange) {
     if incoming.color.is_some() {
         existing.color = incoming.color.clone();
     }
+    if incoming.title.is_some() {
+        existing.title = incoming.title.clone();
+    }
+    if incoming.artist.is_some() {
+        existing.artist = incoming.artist.clone();
+    }
+    if incoming.album.is_some() {
+        existing.album = incoming.album.clone();
+    }
+    if incoming.label.is_some() {
+        existing.label = incoming.label.clone();
+    }
+    if incoming.remixer.is_some() {
+        existing.remixer = incoming.remixer.clone();
+    }
+    if incoming.year.is_some() {
+        existing.year = incoming.year;
+    }
+    if incoming.bpm.is_some() {
+        existing.bpm = incoming.bpm;
+    }
+    if incoming.key.is_some() {
+        existing.key = incoming.key.clone();
+    }
+    if incoming.my_tags.is_some() {
+        existing.my_tags = incoming.my_tags.clone();
+    }
+    if incoming.file_path.is_some() {
+        existing.file_path = incoming.file_path.clone();
+    }
+    if incoming.merge_into.is_some() {
+        existing.merge_into = incoming.merge_into.clone();
+    }
+    if let Some(ref cues) = incoming.cues {
+        merge_staged_cues(existing.cues.get_or_insert_with(Vec::new), cues);
+    }
+}
+
+/// Cues accumulate across calls; a cue in the same slot as an earlier one
+/// replaces it.
+fn merge_staged_cues(existing: &mut Vec<StagedCue>, incoming: &[StagedCue]) {
+    for cue in incoming {
+        existing.retain(|earlier| !cue.replaces(&earlier.to_cue_point()));
+        existing.push(cue.clone());
+    }
 }
 
 fn merge_missing_fields(existing: &mut TrackChange, incoming: &TrackChange) {
@@ -279,6 +774,42 @@ fn merge_missing_fields(existing: &mut TrackChange, incoming: &TrackChange) {
     if existing.color.is_none() {
         existing.color = incoming.color.clone();
     }
+    if existing.title.is_none() {
+        existing.title = incoming.title.clone();
+    }
+    if existing.artist.is_none() {
+        existing.artist = incoming.artist.clone();
+    }
+    if existing.album.is_none() {
+        existing.album = incoming.album.clone();
+    }
+    if existing.label.is_none() {
+        existing.label = incoming.label.clone();
+    }
+    if existing.remixer.is_none() {
+        existing.remixer = incoming.remixer.clone();
+    }
+    if existing.year.is_none() {
+        existing.year = incoming.year;
+    }
+    if existing.bpm.is_none() {
+        existing.bpm = incoming.bpm;
+    }
+    if existing.key.is_none() {
+        existing.key = incoming.key.clone();
+    }
+    if existing.my_tags.is_none() {
+        existing.my_tags = incoming.my_tags.clone();
+    }
+    if existing.file_path.is_none() {
+        existing.file_path = incoming.file_path.clone();
+    }
+    if existing.merge_into.is_none() {
+        existing.merge_into = incoming.merge_into.clone();
+    }
+    if existing.cues.is_none() {
+        existing.cues = incoming.cues.clone();
+    }
 }
 
 fn apply_changes_with_map(
@@ -303,6 +834,40 @@ fn apply_changes_with_map(
                     modified.color = color.clone();
                     modified.color_code = color::color_name_to_code(color).unwrap_or(0);
                 }
+                if let Some(ref title) = change.title {
+                    modified.title = title.clone();
+                }
+                if let Some(ref artist) = change.artist {
+                    modified.artist = artist.clone();
+                }
+                if let Some(ref album) = change.album {
+                    modified.album = album.clone();
+                }
+                if let Some(ref label) = change.label {
+                    modified.label = label.clone();
+                }
+                if let Some(ref remixer) = change.remixer {
+                    modified.remixer = remixer.clone();
+                }
+                if let Some(year) = change.year {
+                    modified.year = year;
+                }
+                if let Some(bpm) = change.bpm {
+                    modified.bpm = bpm;
+                }
+                if let Some(ref key) = change.key {
+                    modified.key = key.clone();
+                }
+                if let Some(ref my_tags) = change.my_tags {
+                    modified.comments = comments_with_my_tags(&modified.comments, my_tags);
+                    modified.my_tags = my_tags.clone();
+                }
+                if let Some(ref file_path) = change.file_path {
+                    modified.file_path = file_path.clone();
+                }
+                if let Some(ref cues) = change.cues {
+                    apply_staged_cues(&mut modified.cues, cues);
+                }
                 modified
             } else {
                 track.clone()
@@ -339,29 +904,39 @@ mod tests {
             sample_rate: 44100,
             file_kind: FileKind::Flac,
             date_added: "2023-01-01".to_string(),
+            my_tags: Vec::new(),
+            hot_cue_banks: Vec::new(),
+            related_track_lists: Vec::new(),
             position: None,
+            cues: Vec::new(),
+            tempo_markers: Vec::new(),
         }
     }
 
     #[test]
     fn test_stage_and_count() {
         let cm = ChangeManager::new();
-        let (staged, total) = cm.stage(vec![
-            TrackChange {
-                track_id: "t1".to_string(),
-                genre: Some("Deep House".to_string()),
-                comments: None,
-                rating: None,
-                color: None,
-            },
-            TrackChange {
-                track_id: "t2".to_string(),
-                genre: Some("Techno".to_string()),
-                comments: None,
-                rating: None,
-                color: None,
-            },
-        ]);
+        let (staged, total) = cm.stage(
+            "update_tracks",
+            vec![
+                TrackChange {
+                    track_id: "t1".to_string(),
+                    genre: Some("Deep House".to_string()),
+                    comments: None,
+                    rating: None,
+                    color: None,
+                    ..Default::default()
+                },
+                TrackChange {
+                    track_id: "t2".to_string(),
+                    genre: Some("Techno".to_string()),
+                    comments: None,
+                    rating: None,
+                    color: None,
+                    ..Default::default()
+                },
+            ],
+        );
         assert_eq!(staged, 2);
         assert_eq!(total, 2);
         assert_eq!(cm.pending_count(), 2);
@@ -370,21 +945,29 @@ mod tests {
     #[test]
     fn test_stage_merges() {
         let cm = ChangeManager::new();
-        cm.stage(vec![TrackChange {
-            track_id: "t1".to_string(),
-            genre: Some("House".to_string()),
-            comments: None,
-            rating: Some(4),
-            color: None,
-        }]);
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: Some("House".to_string()),
+                comments: None,
+                rating: Some(4),
+                color: None,
+                ..Default::default()
+            }],
+        );
         // Second stage for same track: genre updates, rating preserved from first stage
-        cm.stage(vec![TrackChange {
-            track_id: "t1".to_string(),
-            genre: Some("Deep House".to_string()),
-            comments: Some("great track".to_string()),
-            rating: None,
-            color: None,
-        }]);
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: Some("Deep House".to_string()),
+                comments: Some("great track".to_string()),
+                rating: None,
+                color: None,
+                ..Default::default()
+            }],
+        );
         assert_eq!(cm.pending_count(), 1);
 
         // Verify merge: genre updated, comments added, rating preserved
@@ -413,13 +996,17 @@ mod tests {
     #[test]
     fn test_stage_ignores_noop_changes() {
         let cm = ChangeManager::new();
-        let (staged, total) = cm.stage(vec![TrackChange {
-            track_id: "t1".to_string(),
-            genre: None,
-            comments: None,
-            rating: None,
-            color: None,
-        }]);
+        let (staged, total) = cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: None,
+                comments: None,
+                rating: None,
+                color: None,
+                ..Default::default()
+            }],
+        );
         assert_eq!(staged, 0);
         assert_eq!(total, 0);
         assert_eq!(cm.pending_count(), 0);
@@ -429,21 +1016,29 @@ mod tests {
     #[test]
     fn test_stage_noop_does_not_modify_existing_change() {
         let cm = ChangeManager::new();
-        cm.stage(vec![TrackChange {
-            track_id: "t1".to_string(),
-            genre: Some("House".to_string()),
-            comments: None,
-            rating: Some(4),
-            color: None,
-        }]);
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: Some("House".to_string()),
+                comments: None,
+                rating: Some(4),
+                color: None,
+                ..Default::default()
+            }],
+        );
 
-        let (staged, total) = cm.stage(vec![TrackChange {
-            track_id: "t1".to_string(),
-            genre: None,
-            comments: None,
-            rating: None,
-            color: None,
-        }]);
+        let (staged, total) = cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: None,
+                comments: None,
+                rating: None,
+                color: None,
+                ..Default::default()
+            }],
+        );
 
         assert_eq!(staged, 0);
         assert_eq!(total, 1);
@@ -456,13 +1051,17 @@ mod tests {
     fn test_preview() {
         let cm = ChangeManager::new();
         let tracks = vec![make_track("t1", "House", 3)];
-        cm.stage(vec![TrackChange {
-            track_id: "t1".to_string(),
-            genre: Some("Deep House".to_string()),
-            comments: Some("great bassline".to_string()),
-            rating: Some(5),
-            color: None,
-        }]);
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: Some("Deep House".to_string()),
+                comments: Some("great bassline".to_string()),
+                rating: Some(5),
+                color: None,
+                ..Default::default()
+            }],
+        );
 
         let diffs = cm.preview(&tracks);
         assert_eq!(diffs.len(), 1); // one track with changes
@@ -489,13 +1088,17 @@ mod tests {
     fn test_preview_no_change() {
         let cm = ChangeManager::new();
         let tracks = vec![make_track("t1", "House", 3)];
-        cm.stage(vec![TrackChange {
-            track_id: "t1".to_string(),
-            genre: Some("House".to_string()), // same as current
-            comments: None,
-            rating: None,
-            color: None,
-        }]);
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: Some("House".to_string()), // same as current
+                comments: None,
+                rating: None,
+                color: None,
+                ..Default::default()
+            }],
+        );
         let diffs = cm.preview(&tracks);
         assert!(diffs.is_empty()); // no actual change
     }
@@ -504,13 +1107,17 @@ mod tests {
     fn test_apply_changes() {
         let cm = ChangeManager::new();
         let tracks = vec![make_track("t1", "House", 3), make_track("t2", "Techno", 2)];
-        cm.stage(vec![TrackChange {
-            track_id: "t1".to_string(),
-            genre: Some("Deep House".to_string()),
-            comments: None,
-            rating: Some(5),
-            color: None,
-        }]);
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: Some("Deep House".to_string()),
+                comments: None,
+                rating: Some(5),
+                color: None,
+                ..Default::default()
+            }],
+        );
 
         let modified = cm.apply_changes(&tracks);
         assert_eq!(modified[0].genre, "Deep House");
@@ -522,24 +1129,29 @@ mod tests {
     #[test]
     fn test_clear_specific() {
         let cm = ChangeManager::new();
-        cm.stage(vec![
-            TrackChange {
-                track_id: "t1".to_string(),
-                genre: Some("A".to_string()),
-                comments: None,
-                rating: None,
-                color: None,
-            },
-            TrackChange {
-                track_id: "t2".to_string(),
-                genre: Some("B".to_string()),
-                comments: None,
-                rating: None,
-                color: None,
-            },
-        ]);
+        cm.stage(
+            "update_tracks",
+            vec![
+                TrackChange {
+                    track_id: "t1".to_string(),
+                    genre: Some("A".to_string()),
+                    comments: None,
+                    rating: None,
+                    color: None,
+                    ..Default::default()
+                },
+                TrackChange {
+                    track_id: "t2".to_string(),
+                    genre: Some("B".to_string()),
+                    comments: None,
+                    rating: None,
+                    color: None,
+                    ..Default::default()
+                },
+            ],
+        );
 
-        let (cleared, remaining) = cm.clear(Some(vec!["t1".to_string()]));
+        let (cleared, remaining) = cm.clear("clear_changes", Some(vec!["t1".to_string()]));
         assert_eq!(cleared, 1);
         assert_eq!(remaining, 1);
     }
@@ -547,24 +1159,29 @@ mod tests {
     #[test]
     fn test_clear_all() {
         let cm = ChangeManager::new();
-        cm.stage(vec![
-            TrackChange {
-                track_id: "t1".to_string(),
-                genre: Some("A".to_string()),
-                comments: None,
-                rating: None,
-                color: None,
-            },
-            TrackChange {
-                track_id: "t2".to_string(),
-                genre: Some("B".to_string()),
-                comments: None,
-                rating: None,
-                color: None,
-            },
-        ]);
+        cm.stage(
+            "update_tracks",
+            vec![
+                TrackChange {
+                    track_id: "t1".to_string(),
+                    genre: Some("A".to_string()),
+                    comments: None,
+                    rating: None,
+                    color: None,
+                    ..Default::default()
+                },
+                TrackChange {
+                    track_id: "t2".to_string(),
+                    genre: Some("B".to_string()),
+                    comments: None,
+                    rating: None,
+                    color: None,
+                    ..Default::default()
+                },
+            ],
+        );
 
-        let (cleared, remaining) = cm.clear(None);
+        let (cleared, remaining) = cm.clear("clear_changes", None);
         assert_eq!(cleared, 2);
         assert_eq!(remaining, 0);
     }
@@ -572,17 +1189,24 @@ mod tests {
     #[test]
     fn test_clear_fields() {
         let cm = ChangeManager::new();
-        cm.stage(vec![TrackChange {
-            track_id: "t1".to_string(),
-            genre: Some("House".to_string()),
-            comments: Some("great".to_string()),
-            rating: Some(4),
-            color: Some("Green".to_string()),
-        }]);
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: Some("House".to_string()),
+                comments: Some("great".to_string()),
+                rating: Some(4),
+                color: Some("Green".to_string()),
+                ..Default::default()
+            }],
+        );
 
         // Clear just the color field
-        let (affected, remaining) =
-            cm.clear_fields(Some(vec!["t1".to_string()]), &["color".to_string()]);
+        let (affected, remaining) = cm.clear_fields(
+            "clear_changes",
+            Some(vec!["t1".to_string()]),
+            &["color".to_string()],
+        );
         assert_eq!(affected, 1);
         assert_eq!(remaining, 1); // entry still exists (other fields set)
 
@@ -595,16 +1219,23 @@ mod tests {
     #[test]
     fn test_clear_fields_removes_empty_entry() {
         let cm = ChangeManager::new();
-        cm.stage(vec![TrackChange {
-            track_id: "t1".to_string(),
-            genre: Some("House".to_string()),
-            comments: None,
-            rating: None,
-            color: None,
-        }]);
-
-        let (affected, remaining) =
-            cm.clear_fields(Some(vec!["t1".to_string()]), &["genre".to_string()]);
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: Some("House".to_string()),
+                comments: None,
+                rating: None,
+                color: None,
+                ..Default::default()
+            }],
+        );
+
+        let (affected, remaining) = cm.clear_fields(
+            "clear_changes",
+            Some(vec!["t1".to_string()]),
+            &["genre".to_string()],
+        );
         assert_eq!(affected, 1);
         assert_eq!(remaining, 0); // entry removed since all fields are None
         assert!(cm.get("t1").is_none());
@@ -613,25 +1244,30 @@ mod tests {
     #[test]
     fn test_clear_fields_all_tracks() {
         let cm = ChangeManager::new();
-        cm.stage(vec![
-            TrackChange {
-                track_id: "t1".to_string(),
-                genre: Some("House".to_string()),
-                comments: None,
-                rating: Some(3),
-                color: None,
-            },
-            TrackChange {
-                track_id: "t2".to_string(),
-                genre: Some("Techno".to_string()),
-                comments: None,
-                rating: None,
-                color: None,
-            },
-        ]);
+        cm.stage(
+            "update_tracks",
+            vec![
+                TrackChange {
+                    track_id: "t1".to_string(),
+                    genre: Some("House".to_string()),
+                    comments: None,
+                    rating: Some(3),
+                    color: None,
+                    ..Default::default()
+                },
+                TrackChange {
+                    track_id: "t2".to_string(),
+                    genre: Some("Techno".to_string()),
+                    comments: None,
+                    rating: None,
+                    color: None,
+                    ..Default::default()
+                },
+            ],
+        );
 
         // Clear genre from all tracks (no track_ids filter)
-        let (affected, remaining) = cm.clear_fields(None, &["genre".to_string()]);
+        let (affected, remaining) = cm.clear_fields("clear_changes", None, &["genre".to_string()]);
         assert_eq!(affected, 2);
         assert_eq!(remaining, 1); // t1 still has rating, t2 removed entirely
 
@@ -644,22 +1280,27 @@ mod tests {
     fn test_preview_grouped() {
         let cm = ChangeManager::new();
         let tracks = vec![make_track("t1", "House", 3), make_track("t2", "Techno", 2)];
-        cm.stage(vec![
-            TrackChange {
-                track_id: "t1".to_string(),
-                genre: Some("Deep House".to_string()),
-                comments: None,
-                rating: Some(5),
-                color: None,
-            },
-            TrackChange {
-                track_id: "t2".to_string(),
-                genre: None,
-                comments: Some("nice track".to_string()),
-                rating: None,
-                color: None,
-            },
-        ]);
+        cm.stage(
+            "update_tracks",
+            vec![
+                TrackChange {
+                    track_id: "t1".to_string(),
+                    genre: Some("Deep House".to_string()),
+                    comments: None,
+                    rating: Some(5),
+                    color: None,
+                    ..Default::default()
+                },
+                TrackChange {
+                    track_id: "t2".to_string(),
+                    genre: None,
+                    comments: Some("nice track".to_string()),
+                    rating: None,
+                    color: None,
+                    ..Default::default()
+                },
+            ],
+        );
 
         let diffs = cm.preview(&tracks);
         assert_eq!(diffs.len(), 2); // two tracks
@@ -675,13 +1316,17 @@ mod tests {
     fn test_apply_changes_with_color_code() {
         let cm = ChangeManager::new();
         let tracks = vec![make_track("t1", "House", 3)];
-        cm.stage(vec![TrackChange {
-            track_id: "t1".to_string(),
-            genre: None,
-            comments: None,
-            rating: None,
-            color: Some("Green".to_string()),
-        }]);
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: None,
+                comments: None,
+                rating: None,
+                color: Some("Green".to_string()),
+                ..Default::default()
+            }],
+        );
 
         let modified = cm.apply_changes(&tracks);
         assert_eq!(modified[0].color, "Green");
@@ -697,13 +1342,17 @@ mod tests {
         let tracks = vec![track];
 
         // Stage a genre change only, no color change
-        cm.stage(vec![TrackChange {
-            track_id: "t1".to_string(),
-            genre: Some("Techno".to_string()),
-            comments: None,
-            rating: None,
-            color: None,
-        }]);
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: Some("Techno".to_string()),
+                comments: None,
+                rating: None,
+                color: None,
+                ..Default::default()
+            }],
+        );
 
         let modified = cm.apply_changes(&tracks);
         assert_eq!(modified[0].color, "Red");
@@ -718,13 +1367,17 @@ mod tests {
         track.color_code = 0xFF0000;
         let tracks = vec![track];
 
-        cm.stage(vec![TrackChange {
-            track_id: "t1".to_string(),
-            genre: None,
-            comments: None,
-            rating: None,
-            color: Some("Purple".to_string()),
-        }]);
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: None,
+                comments: None,
+                rating: None,
+                color: Some("Purple".to_string()),
+                ..Default::default()
+            }],
+        );
 
         let modified = cm.apply_changes(&tracks);
         assert_eq!(modified[0].color, "Purple");
@@ -734,22 +1387,27 @@ mod tests {
     #[test]
     fn test_take_all_drains_pending_changes() {
         let cm = ChangeManager::new();
-        cm.stage(vec![
-            TrackChange {
-                track_id: "t1".to_string(),
-                genre: Some("House".to_string()),
-                comments: None,
-                rating: None,
-                color: None,
-            },
-            TrackChange {
-                track_id: "t2".to_string(),
-                genre: Some("Techno".to_string()),
-                comments: None,
-                rating: None,
-                color: None,
-            },
-        ]);
+        cm.stage(
+            "update_tracks",
+            vec![
+                TrackChange {
+                    track_id: "t1".to_string(),
+                    genre: Some("House".to_string()),
+                    comments: None,
+                    rating: None,
+                    color: None,
+                    ..Default::default()
+                },
+                TrackChange {
+                    track_id: "t2".to_string(),
+                    genre: Some("Techno".to_string()),
+                    comments: None,
+                    rating: None,
+                    color: None,
+                    ..Default::default()
+                },
+            ],
+        );
 
         let snapshot = cm.take(None);
         assert_eq!(snapshot.len(), 2);
@@ -759,22 +1417,27 @@ mod tests {
     #[test]
     fn test_pending_ids_are_sorted() {
         let cm = ChangeManager::new();
-        cm.stage(vec![
-            TrackChange {
-                track_id: "t2".to_string(),
-                genre: Some("House".to_string()),
-                comments: None,
-                rating: None,
-                color: None,
-            },
-            TrackChange {
-                track_id: "t1".to_string(),
-                genre: Some("Techno".to_string()),
-                comments: None,
-                rating: None,
-                color: None,
-            },
-        ]);
+        cm.stage(
+            "update_tracks",
+            vec![
+                TrackChange {
+                    track_id: "t2".to_string(),
+                    genre: Some("House".to_string()),
+                    comments: None,
+                    rating: None,
+                    color: None,
+                    ..Default::default()
+                },
+                TrackChange {
+                    track_id: "t1".to_string(),
+                    genre: Some("Techno".to_string()),
+                    comments: None,
+                    rating: None,
+                    color: None,
+                    ..Default::default()
+                },
+            ],
+        );
 
         assert_eq!(cm.pending_ids(), vec!["t1".to_string(), "t2".to_string()]);
     }
@@ -782,22 +1445,27 @@ mod tests {
     #[test]
     fn test_take_all_returns_sorted_snapshot() {
         let cm = ChangeManager::new();
-        cm.stage(vec![
-            TrackChange {
-                track_id: "t2".to_string(),
-                genre: Some("House".to_string()),
-                comments: None,
-                rating: None,
-                color: None,
-            },
-            TrackChange {
-                track_id: "t1".to_string(),
-                genre: Some("Techno".to_string()),
-                comments: None,
-                rating: None,
-                color: None,
-            },
-        ]);
+        cm.stage(
+            "update_tracks",
+            vec![
+                TrackChange {
+                    track_id: "t2".to_string(),
+                    genre: Some("House".to_string()),
+                    comments: None,
+                    rating: None,
+                    color: None,
+                    ..Default::default()
+                },
+                TrackChange {
+                    track_id: "t1".to_string(),
+                    genre: Some("Techno".to_string()),
+                    comments: None,
+                    rating: None,
+                    color: None,
+                    ..Default::default()
+                },
+            ],
+        );
 
         let snapshot = cm.take(None);
         assert_eq!(snapshot.len(), 2);
@@ -808,25 +1476,33 @@ mod tests {
     #[test]
     fn test_restore_keeps_newer_fields_and_restores_missing_ones() {
         let cm = ChangeManager::new();
-        cm.stage(vec![TrackChange {
-            track_id: "t1".to_string(),
-            genre: Some("House".to_string()),
-            comments: Some("old".to_string()),
-            rating: None,
-            color: None,
-        }]);
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: Some("House".to_string()),
+                comments: Some("old".to_string()),
+                rating: None,
+                color: None,
+                ..Default::default()
+            }],
+        );
 
         let snapshot = cm.take(None);
         assert_eq!(cm.pending_count(), 0);
 
         // Simulate newer changes arriving while export is in progress.
-        cm.stage(vec![TrackChange {
-            track_id: "t1".to_string(),
-            genre: None,
-            comments: Some("new".to_string()),
-            rating: Some(5),
-            color: None,
-        }]);
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: None,
+                comments: Some("new".to_string()),
+                rating: Some(5),
+                color: None,
+                ..Default::default()
+            }],
+        );
 
         cm.restore(snapshot);
         let restored = cm.get("t1").expect("t1 should be restored");
@@ -835,8 +1511,234 @@ mod tests {
         assert_eq!(restored.rating, Some(5));
     }
 
+    #[test]
+    fn test_preview_and_apply_extended_fields() {
+        let cm = ChangeManager::new();
+        let tracks = vec![make_track("t1", "House", 3)];
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                title: Some("Track t1 (Extended Mix)".to_string()),
+                label: Some("Hyperdub".to_string()),
+                year: Some(2007),
+                bpm: Some(128.004),
+                key: Some("Fm".to_string()),
+                ..Default::default()
+            }],
+        );
+
+        let diffs = cm.preview(&tracks);
+        assert_eq!(diffs.len(), 1);
+        let fields: Vec<&str> = diffs[0].changes.iter().map(|f| f.field.as_str()).collect();
+        // BPM differs only below DB precision, year changes 2023 -> 2007.
+        assert_eq!(fields, vec!["key", "label", "title", "year"]);
+
+        let modified = cm.apply_changes(&tracks);
+        assert_eq!(modified[0].title, "Track t1 (Extended Mix)");
+        assert_eq!(modified[0].label, "Hyperdub");
+        assert_eq!(modified[0].year, 2007);
+        assert_eq!(modified[0].key, "Fm");
+    }
+
+    #[test]
+    fn test_my_tags_preview_and_comment_block() {
+        let cm = ChangeManager::new();
+        let mut track = make_track("t1", "House", 3);
+        track.comments = "warm-up /* Old */ tool".to_string();
+        track.my_tags = vec!["Old".to_string()];
+        let tracks = vec![track];
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                my_tags: Some(vec!["Peak".to_string(), "Vocal".to_string()]),
+                ..Default::default()
+            }],
+        );
+
+        let diffs = cm.preview(&tracks);
+        assert_eq!(diffs[0].changes[0].field, "comments");
+        assert_eq!(diffs[0].changes[0].old_value, "warm-up /* Old */ tool");
+        assert_eq!(
+            diffs[0].changes[0].new_value,
+            "warm-up tool /* Peak / Vocal */"
+        );
+        assert_eq!(diffs[0].changes[1].field, "my_tags");
+        assert_eq!(diffs[0].changes[1].old_value, "Old");
+        assert_eq!(diffs[0].changes[1].new_value, "Peak / Vocal");
+
+        let modified = cm.apply_changes(&tracks);
+        assert_eq!(modified[0].my_tags, ["Peak", "Vocal"]);
+        assert_eq!(modified[0].comments, "warm-up tool /* Peak / Vocal */");
+
+        assert_eq!(comments_with_my_tags("  /* A */ ", &[]), "");
+        assert_eq!(comments_with_my_tags("", &["A".to_string()]), "/* A */");
+        assert_eq!(comments_with_my_tags("ends /* open", &[]), "ends /* open");
+    }
+
+    #[test]
+    fn test_clear_fields_extended_field_keeps_entry() {
+        let cm = ChangeManager::new();
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                artist: Some("Burial".to_string()),
+                bpm: Some(140.0),
+                ..Default::default()
+            }],
+        );
+
+        let (affected, remaining) = cm.clear_fields(
+            "clear_changes",
+            Some(vec!["t1".to_string()]),
+            &["bpm".to_string()],
+        );
+        assert_eq!(affected, 1);
+        assert_eq!(remaining, 1);
+        let change = cm.get("t1").unwrap();
+        assert!(change.bpm.is_none());
+        assert_eq!(change.artist.as_deref(), Some("Burial"));
+
+        cm.clear_fields("clear_changes", None, &["artist".to_string()]);
+        assert!(cm.get("t1").is_none());
+    }
+
     // ==================== Integration tests (real DB) ====================
 
+    #[test]
+    fn test_undo_redo_restores_previous_staged_values() {
+        let cm = ChangeManager::new();
+        cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: "t1".to_string(),
+                genre: Some("Techno".to_string()),
+                ..Default::default()
+            }],
+        );
+        cm.stage(
+            "update_tracks",
+            vec![
+                TrackChange {
+                    track_id: "t1".to_string(),
+                    genre: Some("House".to_string()),
+                    rating: Some(4),
+                    ..Default::default()
+                },
+                TrackChange {
+                    track_id: "t2".to_string(),
+                    genre: Some("Dub".to_string()),
+                    ..Default::default()
+                },
+            ],
+        );
+
+        let (undone, remaining) = cm.undo(1);
+        assert_eq!(undone.len(), 1);
+        assert_eq!(undone[0].track_ids, vec!["t1", "t2"]);
+        assert_eq!(remaining, 1);
+        let t1 = cm.get("t1").unwrap();
+        assert_eq!(t1.genre.as_deref(), Some("Techno"));
+        assert!(t1.rating.is_none());
+        assert!(cm.get("t2").is_none());
+
+        let (redone, remaining) = cm.redo(5);
+        assert_eq!(redone.len(), 1);
+        assert_eq!(remaining, 2);
+        assert_eq!(cm.get("t1").unwrap().rating, Some(4));
+
+        let (undo, redo) = cm.history();
+        assert_eq!(undo.len(), 2);
+        assert!(redo.is_empty());
+        assert!(undo[0].step > undo[1].step, "newest step listed first");
+    }
+
+    #[test]
+    fn test_history_records_clears_and_skips_noops() {
+        let cm = ChangeManager::new();
+        let change = TrackChange {
+            track_id: "t1".to_string(),
+            genre: Some("Techno".to_string()),
+            ..Default::default()
+        };
+        cm.stage("update_tracks", vec![change.clone()]);
+        cm.stage("update_tracks", vec![change]);
+        cm.clear("clear_changes", Some(vec!["missing".to_string()]));
+        assert_eq!(cm.history().0.len(), 1, "no-op calls record no step");
+
+        cm.clear("clear_caches", None);
+        cm.undo(1);
+        assert_eq!(cm.get("t1").unwrap().genre.as_deref(), Some("Techno"));
+
+        // A new step invalidates anything left to redo.
+        cm.clear_fields("clear_changes", None, &["genre".to_string()]);
+        let (undo, redo) = cm.history();
+        assert!(redo.is_empty());
+        assert_eq!(undo[0].tool, "clear_changes");
+        assert!(cm.get("t1").is_none());
+
+        cm.replace_all(None, Vec::new());
+        assert!(cm.history().0.is_empty());
+    }
+
+    #[test]
+    fn test_persist_with_writes_only_changed_tracks() {
+        let cm = ChangeManager::new();
+        let genre = |id: &str, genre: &str| TrackChange {
+            track_id: id.to_string(),
+            genre: Some(genre.to_string()),
+            ..Default::default()
+        };
+        let written = std::cell::RefCell::new(Vec::new());
+        let persist = |fail: bool| {
+            cm.persist_with(|session_id, entries| {
+                assert_eq!(session_id, "s1");
+                if fail {
+                    return Err("store unavailable");
+                }
+                written.borrow_mut().extend_from_slice(entries);
+                Ok(())
+            })
+        };
+
+        cm.stage("update_tracks", vec![genre("t1", "Techno")]);
+        assert_eq!(
+            persist(false),
+            Ok(0),
+            "nothing is written without a session"
+        );
+
+        cm.replace_all(Some("s1".to_string()), vec![genre("t1", "Techno")]);
+        assert_eq!(
+            persist(false),
+            Ok(0),
+            "a loaded session is already persisted"
+        );
+
+        cm.stage("update_tracks", vec![genre("t2", "House")]);
+        assert_eq!(persist(true), Err("store unavailable"));
+        cm.clear("clear_changes", Some(vec!["t1".to_string()]));
+        assert_eq!(persist(false), Ok(2), "failed tracks are retried");
+        assert_eq!(
+            *written.borrow(),
+            vec![
+                ("t1".to_string(), None),
+                ("t2".to_string(), Some(genre("t2", "House"))),
+            ]
+        );
+        assert_eq!(persist(false), Ok(0));
+
+        written.borrow_mut().clear();
+        cm.undo(1);
+        assert_eq!(persist(false), Ok(1));
+        assert_eq!(
+            *written.borrow(),
+            vec![("t1".to_string(), Some(genre("t1", "Techno")))]
+        );
+    }
+
     #[test]
     #[ignore]
     fn test_real_change_pipeline() {
@@ -852,6 +1754,7 @@ mod tests {
             bpm_max: Some(130.0),
             key: None,
             playlist: None,
+            my_tag: None,
             has_genre: None,
             label: None,
             path: None,
@@ -869,13 +1772,17 @@ mod tests {
 
         // 2. Stage changes
         let cm = ChangeManager::new();
-        let (staged, total) = cm.stage(vec![TrackChange {
-            track_id: track.id.clone(),
-            genre: Some("Deep House".to_string()),
-            comments: Some("integration test".to_string()),
-            rating: Some(4),
-            color: None,
-        }]);
+        let (staged, total) = cm.stage(
+            "update_tracks",
+            vec![TrackChange {
+                track_id: track.id.clone(),
+                genre: Some("Deep House".to_string()),
+                comments: Some("integration test".to_string()),
+                rating: Some(4),
+                color: None,
+                ..Default::default()
+            }],
+        );
         assert_eq!(staged, 1);
         assert_eq!(total, 1);
 
@@ -2,11 +2,13 @@ use std::time::Instant;
 
 use crate::{audio, db, store, tools};
 
-use super::{cache_probe_for_path, cache_status_for_track, CliCacheWriteMsg};
+use super::{
+    CliCacheWriteMsg, cache_probe_for_path, cache_status_for_track, has_current_native_entry,
+};
 
 #[derive(clap::Args)]
 pub(crate) struct AnalyzeArgs {
-    /// Filter by playlist ID
+    /// Filter by playlist ID (smart playlists match by their rules)
     #[arg(long)]
     playlist: Option<String>,
     /// Filter by artist name (partial match)
@@ -27,6 +29,9 @@ pub(crate) struct AnalyzeArgs {
     /// Filter by label name (partial match)
     #[arg(long)]
     label: Option<String>,
+    /// Filter by My Tag name (exact, case-insensitive)
+    #[arg(long)]
+    my_tag: Option<String>,
     /// Filter by file path/folder (partial match)
     #[arg(long)]
     path: Option<String>,
@@ -48,12 +53,80 @@ pub(crate) struct AnalyzeArgs {
     /// Don't skip already-cached tracks
     #[arg(long)]
     no_skip_cached: bool,
-    /// Skip Essentia analysis, only run stratum-dsp
+    /// Skip Essentia and native feature analysis, only run stratum-dsp
     #[arg(long)]
     stratum_only: bool,
     /// Max concurrent track analyses (default: half CPU cores, min 2, max 16)
     #[arg(long, short = 'j')]
     concurrency: Option<u32>,
+    /// Memory budget in MB for concurrent analyses, estimated from each
+    /// track's decoded size; a track larger than the budget runs alone
+    #[arg(long, default_value = "4096")]
+    memory_budget_mb: u32,
+    /// Only analyse the first N minutes of each track with stratum-dsp and the
+    /// native features
+    #[arg(long, conflicts_with = "last_minutes")]
+    first_minutes: Option<f64>,
+    /// Only analyse the last N minutes of each track with stratum-dsp and the
+    /// native features
+    #[arg(long)]
+    last_minutes: Option<f64>,
+}
+
+/// Peak memory of a stratum-dsp analysis as a multiple of its decoded mono
+/// samples. Measured with a counting global allocator on 20-40 s noise and
+/// click WAVs at 44.1 and 48 kHz: the decoded buffer (1x) plus stratum-dsp's
+/// own peak (about 28x: copies of the samples and its STFT frames). Structure
+/// and native features on that buffer add under 0.1x, so sharing it with them
+/// costs nothing extra.
+const ANALYSIS_MEMORY_FACTOR: u64 = 29;
+
+/// Assumed sample rate when a file's header gives no length and the estimate
+/// falls back to the Rekordbox track length.
+const FALLBACK_SAMPLE_RATE: u32 = 44_100;
+
+impl AnalyzeArgs {
+    fn decode_window(&self) -> audio::DecodeWindow {
+        match (self.first_minutes, self.last_minutes) {
+            (Some(minutes), _) => audio::DecodeWindow::First(minutes * 60.0),
+            (None, Some(minutes)) => audio::DecodeWindow::Last(minutes * 60.0),
+            (None, None) => audio::DecodeWindow::Full,
+        }
+    }
+}
+
+/// Estimated peak memory in MB for analysing `track`, from the file header
+/// or, failing that, the track length in Rekordbox. Without stratum-dsp only
+/// a windowed native run holds samples, and only its window; whole-track
+/// native features are streamed.
+fn estimated_track_mb(
+    track: &crate::types::Track,
+    window: audio::DecodeWindow,
+    needs_stratum: bool,
+) -> u64 {
+    let factor = if needs_stratum {
+        ANALYSIS_MEMORY_FACTOR
+    } else if window != audio::DecodeWindow::Full {
+        1
+    } else {
+        return 0;
+    };
+    let decoded = audio::resolve_audio_path(&track.file_path)
+        .ok()
+        .and_then(|path| audio::estimated_decoded_bytes(&path, window))
+        .unwrap_or_else(|| {
+            audio::decoded_bytes(
+                window.span_secs(f64::from(track.length.max(0))),
+                FALLBACK_SAMPLE_RATE,
+            )
+        });
+    (decoded * factor).div_ceil(1024 * 1024)
+}
+
+/// Permits a track takes from the memory budget: its estimate, at least one,
+/// and at most the whole budget so oversized tracks still run (alone).
+pub(super) fn memory_permits(estimated_mb: u64, budget_mb: u32) -> u32 {
+    estimated_mb.clamp(1, u64::from(budget_mb.max(1))) as u32
 }
 
 pub(crate) async fn run_analyze(args: AnalyzeArgs) -> Result<(), Box<dyn std::error::Error>> {
@@ -85,11 +158,16 @@ pub(crate) async fn run_analyze(args: AnalyzeArgs) -> Result<(), Box<dyn std::er
         } else {
             match &essentia_python {
                 Some(p) => format!("available ({p})"),
-                None => "not found (stratum-dsp only)".to_string(),
+                None => "not found (native features instead)".to_string(),
             }
         }
     );
 
+    // Native features stand in for Essentia when it isn't installed
+    let native_features = !args.stratum_only && essentia_python.is_none();
+    let window = args.decode_window();
+    let memory_budget_mb = args.memory_budget_mb.max(1);
+
     // Search tracks
     let params = db::SearchParams {
         query: args.query,
@@ -100,6 +178,7 @@ pub(crate) async fn run_analyze(args: AnalyzeArgs) -> Result<(), Box<dyn std::er
         bpm_max: args.bpm_max,
         key: args.key,
         playlist: args.playlist,
+        my_tag: args.my_tag,
         has_genre: None,
         label: args.label,
         path: args.path,
@@ -129,12 +208,23 @@ pub(crate) async fn run_analyze(args: AnalyzeArgs) -> Result<(), Box<dyn std::er
             cache_probe.as_ref(),
             skip_cached,
             essentia_python.is_some(),
+            window != audio::DecodeWindow::Full,
         )?;
+        let has_features = if native_features {
+            skip_cached
+                && has_current_native_entry(
+                    &store_conn,
+                    cache_probe.as_ref(),
+                    window != audio::DecodeWindow::Full,
+                )?
+        } else {
+            has_essentia
+        };
 
-        if has_stratum && has_essentia {
+        if has_stratum && has_features {
             cached_count += 1;
         } else {
-            to_analyze.push((track.clone(), !has_stratum, !has_essentia));
+            to_analyze.push((track.clone(), !has_stratum, !has_features));
         }
     }
 
@@ -153,7 +243,7 @@ pub(crate) async fn run_analyze(args: AnalyzeArgs) -> Result<(), Box<dyn std::er
     } as usize;
 
     tracing::info!(
-        "Scanning {total} tracks ({cached_count} cached, {pending} to analyze, concurrency={concurrency})"
+        "Scanning {total} tracks ({cached_count} cached, {pending} to analyze, concurrency={concurrency}, memory budget={memory_budget_mb} MB)"
     );
 
     if to_analyze.is_empty() {
@@ -200,12 +290,14 @@ pub(crate) async fn run_analyze(args: AnalyzeArgs) -> Result<(), Box<dyn std::er
         }
     });
 
-    // Spawn analysis tasks bounded by semaphore
+    // Spawn analysis tasks bounded by task count and by estimated memory
     let sem = std::sync::Arc::new(tokio::sync::Semaphore::new(concurrency));
+    let memory = std::sync::Arc::new(tokio::sync::Semaphore::new(memory_budget_mb as usize));
     let mut handles = Vec::with_capacity(pending);
 
-    for (track, needs_stratum, needs_essentia) in to_analyze {
+    for (track, needs_stratum, needs_features) in to_analyze {
         let permit = sem.clone().acquire_owned().await?;
+        let memory = memory.clone();
         let label = format!("{} - {}", track.artist, track.title);
         let essentia_python = essentia_python.clone();
         let cache_tx = cache_tx.clone();
@@ -214,11 +306,28 @@ pub(crate) async fn run_analyze(args: AnalyzeArgs) -> Result<(), Box<dyn std::er
         let completed_count = completed_count.clone();
 
         handles.push(tokio::spawn(async move {
+            // Probe the header here, not before scheduling, so a large batch
+            // doesn't open every file up front
+            let estimate_track = track.clone();
+            let estimated_mb = tokio::task::spawn_blocking(move || {
+                estimated_track_mb(&estimate_track, window, needs_stratum)
+            })
+            .await
+            .unwrap_or(u64::from(memory_budget_mb));
+            let Ok(memory_permit) = memory
+                .acquire_many_owned(memory_permits(estimated_mb, memory_budget_mb))
+                .await
+            else {
+                return;
+            };
+
             let result = cli_analyze_single_track(
                 &track.file_path,
                 needs_stratum,
-                needs_essentia,
+                needs_features,
+                window,
                 essentia_python.as_deref(),
+                native_features,
                 &cache_tx,
             )
             .await;
@@ -229,20 +338,21 @@ pub(crate) async fn run_analyze(args: AnalyzeArgs) -> Result<(), Box<dyn std::er
                 Ok(outcome) => {
                     let elapsed = outcome.elapsed;
                     match outcome.kind {
-                        CliTrackOutcome::StratumAndEssentia {
+                        CliTrackOutcome::StratumAndFeatures {
                             bpm,
                             key_camelot,
-                            essentia_ok,
+                            analyzer,
+                            features_ok,
                         } => {
-                            let essentia_status = if essentia_ok {
-                                " +essentia"
+                            let features_status = if features_ok {
+                                format!(" +{analyzer}")
                             } else {
-                                " (essentia failed)"
+                                format!(" ({analyzer} failed)")
                             };
                             tracing::info!(
-                                "[{idx}/{pending}] {label} ... BPM={bpm:.1} Key={key_camelot}{essentia_status} ({elapsed:.1}s)"
+                                "[{idx}/{pending}] {label} ... BPM={bpm:.1} Key={key_camelot}{features_status} ({elapsed:.1}s)"
                             );
-                            if essentia_ok {
+                            if features_ok {
                                 analyzed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                             } else {
                                 failed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
@@ -254,15 +364,15 @@ pub(crate) async fn run_analyze(args: AnalyzeArgs) -> Result<(), Box<dyn std::er
                             );
                             analyzed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                         }
-                        CliTrackOutcome::EssentiaOnly { ok } => {
+                        CliTrackOutcome::FeaturesOnly { analyzer, ok } => {
                             if ok {
                                 tracing::info!(
-                                    "[{idx}/{pending}] {label} ... +essentia ({elapsed:.1}s)"
+                                    "[{idx}/{pending}] {label} ... +{analyzer} ({elapsed:.1}s)"
                                 );
                                 analyzed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                             } else {
                                 tracing::error!(
-                                    "[{idx}/{pending}] FAIL {label}: Essentia error ({elapsed:.1}s)"
+                                    "[{idx}/{pending}] FAIL {label}: {analyzer} error ({elapsed:.1}s)"
                                 );
                                 failed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                             }
@@ -275,6 +385,7 @@ pub(crate) async fn run_analyze(args: AnalyzeArgs) -> Result<(), Box<dyn std::er
                 }
             }
 
+            drop(memory_permit);
             drop(permit);
         }));
     }
@@ -299,16 +410,18 @@ pub(crate) async fn run_analyze(args: AnalyzeArgs) -> Result<(), Box<dyn std::er
 }
 
 enum CliTrackOutcome {
-    StratumAndEssentia {
+    StratumAndFeatures {
         bpm: f64,
         key_camelot: String,
-        essentia_ok: bool,
+        analyzer: &'static str,
+        features_ok: bool,
     },
     StratumOnly {
         bpm: f64,
         key_camelot: String,
     },
-    EssentiaOnly {
+    FeaturesOnly {
+        analyzer: &'static str,
         ok: bool,
     },
 }
@@ -318,11 +431,14 @@ struct CliTrackResult {
     elapsed: f64,
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn cli_analyze_single_track(
     raw_file_path: &str,
     needs_stratum: bool,
-    needs_essentia: bool,
+    needs_features: bool,
+    window: audio::DecodeWindow,
     essentia_python: Option<&str>,
+    native_features: bool,
     cache_tx: &tokio::sync::mpsc::Sender<CliCacheWriteMsg>,
 ) -> Result<CliTrackResult, String> {
     let file_path =
@@ -330,24 +446,43 @@ async fn cli_analyze_single_track(
     let metadata =
         std::fs::metadata(&file_path).map_err(|e| format!("Cannot stat file: {e}"))?;
     let file_size = metadata.len() as i64;
-    let file_mtime = super::file_mtime_unix(&metadata);
+    let file_mtime = store::file_mtime_unix(&metadata);
     let track_start = Instant::now();
+    let needs_native = needs_features && essentia_python.is_none() && native_features;
 
-    if needs_stratum {
-        // Decode + analyze stratum
+    // A whole-track stratum run decodes once for structure and the native
+    // features too; otherwise stratum decodes only its window and the native
+    // features stream the track or decode the same window
+    let full_window = window == audio::DecodeWindow::Full;
+    let shared = if needs_stratum && full_window {
         let path_clone = file_path.clone();
-        let (samples, sample_rate) =
-            tokio::task::spawn_blocking(move || audio::decode_to_samples(&path_clone))
-                .await
-                .map_err(|e| format!("Decode task failed: {e}"))?
-                .map_err(|e| format!("Decode error: {e}"))?;
+        let decoded = tokio::task::spawn_blocking(move || audio::decode_for_analysis(&path_clone))
+            .await
+            .map_err(|e| format!("Decode task failed: {e}"))?
+            .map_err(|e| format!("Decode error: {e}"))?;
+        Some(std::sync::Arc::new(decoded))
+    } else {
+        None
+    };
 
-        let stratum_result = tokio::task::spawn_blocking(move || {
-            audio::analyze_with_stratum(&samples, sample_rate)
-        })
-        .await
-        .map_err(|e| format!("Analysis task failed: {e}"))?
-        .map_err(|e| format!("Analysis error: {e}"))?;
+    let stratum = if needs_stratum {
+        let stratum_result = match shared.clone() {
+            Some(decoded) => tokio::task::spawn_blocking(move || {
+                audio::analyze_with_stratum(&decoded.samples, decoded.sample_rate)
+            })
+            .await
+            .map_err(|e| format!("Analysis task failed: {e}"))?
+            .map_err(|e| format!("Analysis error: {e}"))?,
+            None => {
+                let path_clone = file_path.clone();
+                tokio::task::spawn_blocking(move || {
+                    audio::analyze_window_with_stratum(&path_clone, window)
+                })
+                .await
+                .map_err(|e| format!("Analysis task failed: {e}"))?
+                .map_err(|e| format!("Analysis error: {e}"))?
+            }
+        };
 
         let features_json = serde_json::to_string(&stratum_result).unwrap_or_default();
         let _ = cache_tx
@@ -361,43 +496,140 @@ async fn cli_analyze_single_track(
             })
             .await;
 
-        if needs_essentia
-            && let Some(python) = essentia_python
-        {
-                let essentia_ok =
-                    cli_run_and_send_essentia(python, &file_path, file_size, file_mtime, cache_tx)
-                        .await;
-                return Ok(CliTrackResult {
-                    kind: CliTrackOutcome::StratumAndEssentia {
-                        bpm: stratum_result.bpm,
-                        key_camelot: stratum_result.key_camelot,
-                        essentia_ok,
-                    },
-                    elapsed: track_start.elapsed().as_secs_f64(),
-                });
+        // Structure is laid out on this beat grid from the same decode; a
+        // windowed grid doesn't cover the whole track
+        if let Some(decoded) = shared.clone() {
+            cli_run_and_send_structure(
+                decoded,
+                &stratum_result,
+                &file_path,
+                file_size,
+                file_mtime,
+                cache_tx,
+            )
+            .await;
         }
+        Some(stratum_result)
+    } else {
+        None
+    };
+
+    let features = if !needs_features {
+        None
+    } else if let Some(python) = essentia_python {
+        let ok =
+            cli_run_and_send_essentia(python, &file_path, file_size, file_mtime, cache_tx).await;
+        Some((audio::ANALYZER_ESSENTIA, ok))
+    } else if needs_native {
+        let ok =
+            cli_run_and_send_native(shared, &file_path, window, file_size, file_mtime, cache_tx)
+                .await;
+        Some((audio::ANALYZER_NATIVE, ok))
+    } else {
+        None
+    };
 
-        Ok(CliTrackResult {
-            kind: CliTrackOutcome::StratumOnly {
-                bpm: stratum_result.bpm,
-                key_camelot: stratum_result.key_camelot,
-            },
-            elapsed: track_start.elapsed().as_secs_f64(),
+    let kind = match (stratum, features) {
+        (Some(stratum), Some((analyzer, features_ok))) => CliTrackOutcome::StratumAndFeatures {
+            bpm: stratum.bpm,
+            key_camelot: stratum.key_camelot,
+            analyzer,
+            features_ok,
+        },
+        (Some(stratum), None) => CliTrackOutcome::StratumOnly {
+            bpm: stratum.bpm,
+            key_camelot: stratum.key_camelot,
+        },
+        (None, Some((analyzer, ok))) => CliTrackOutcome::FeaturesOnly { analyzer, ok },
+        (None, None) if needs_features => return Err("Essentia not available".to_string()),
+        (None, None) => return Err("Nothing to analyze".to_string()),
+    };
+    Ok(CliTrackResult {
+        kind,
+        elapsed: track_start.elapsed().as_secs_f64(),
+    })
+}
+
+/// Detect the arrangement of an already decoded track on its stratum beat
+/// grid and queue it for the cache writer. Failures are logged; the track's
+/// BPM and key still count.
+async fn cli_run_and_send_structure(
+    decoded: std::sync::Arc<audio::DecodedAudio>,
+    stratum: &audio::StratumResult,
+    file_path: &str,
+    file_size: i64,
+    file_mtime: i64,
+    cache_tx: &tokio::sync::mpsc::Sender<CliCacheWriteMsg>,
+) {
+    let bpm = stratum.bpm;
+    let first_beat = stratum.first_beat_sec.zip(stratum.first_beat_in_bar);
+    let structure = match tokio::task::spawn_blocking(move || {
+        audio::analyze_structure(&decoded.samples, decoded.sample_rate, bpm, first_beat)
+    })
+    .await
+    {
+        Ok(Ok(structure)) => structure,
+        Ok(Err(e)) => {
+            tracing::warn!("Structure error for {file_path}: {e}");
+            return;
+        }
+        Err(e) => {
+            tracing::warn!("Structure task failed for {file_path}: {e}");
+            return;
+        }
+    };
+    let features_json = serde_json::to_string(&structure).unwrap_or_default();
+    let _ = cache_tx
+        .send(CliCacheWriteMsg {
+            file_path: file_path.to_string(),
+            analyzer: audio::ANALYZER_STRUCTURE.to_string(),
+            file_size,
+            file_mtime,
+            analyzer_version: structure.analyzer_version,
+            features_json,
         })
-    } else if needs_essentia {
-        if let Some(python) = essentia_python {
-            let ok =
-                cli_run_and_send_essentia(python, &file_path, file_size, file_mtime, cache_tx)
-                    .await;
-            return Ok(CliTrackResult {
-                kind: CliTrackOutcome::EssentiaOnly { ok },
-                elapsed: track_start.elapsed().as_secs_f64(),
-            });
+        .await;
+}
+
+/// Compute native features on the shared decode when there is one, else on
+/// `window` of the file, and queue them for the cache writer.
+async fn cli_run_and_send_native(
+    decoded: Option<std::sync::Arc<audio::DecodedAudio>>,
+    file_path: &str,
+    window: audio::DecodeWindow,
+    file_size: i64,
+    file_mtime: i64,
+    cache_tx: &tokio::sync::mpsc::Sender<CliCacheWriteMsg>,
+) -> bool {
+    let path = file_path.to_string();
+    let features = match tokio::task::spawn_blocking(move || match decoded {
+        Some(decoded) => audio::analyze_native_features(&decoded),
+        None => audio::analyze_native_window(&path, window),
+    })
+    .await
+    {
+        Ok(Ok(features)) => features,
+        Ok(Err(e)) => {
+            tracing::error!("Native features error for {file_path}: {e}");
+            return false;
         }
-        Err("Essentia not available".to_string())
-    } else {
-        Err("Nothing to analyze".to_string())
-    }
+        Err(e) => {
+            tracing::error!("Native features task failed for {file_path}: {e}");
+            return false;
+        }
+    };
+    let features_json = serde_json::to_string(&features).unwrap_or_default();
+    let _ = cache_tx
+        .send(CliCacheWriteMsg {
+            file_path: file_path.to_string(),
+            analyzer: audio::ANALYZER_NATIVE.to_string(),
+            file_size,
+            file_mtime,
+            analyzer_version: features.analyzer_version,
+            features_json,
+        })
+        .await;
+    true
 }
 
 async fn cli_run_and_send_essentia(
@@ -7,7 +7,7 @@ use tokio_util::sync::CancellationToken;
 
 use crate::{audio, beatport, db, discogs, normalize, store, tools};
 
-use super::{CliCacheWriteMsg, cache_probe_for_path, cache_status_for_track, file_mtime_unix};
+use super::{CliCacheWriteMsg, cache_probe_for_path, cache_status_for_track};
 
 // ---------------------------------------------------------------------------
 // CLI args
@@ -71,6 +71,9 @@ pub(crate) struct HydrateArgs {
     /// Filter by label name (partial match)
     #[arg(long)]
     label: Option<String>,
+    /// Filter by My Tag name (exact, case-insensitive)
+    #[arg(long)]
+    my_tag: Option<String>,
     /// Filter by file path/folder (partial match)
     #[arg(long)]
     path: Option<String>,
@@ -183,6 +186,7 @@ pub(crate) async fn run_hydrate(args: HydrateArgs) -> Result<(), Box<dyn std::er
         bpm_max: args.bpm_max,
         key: args.key,
         playlist: args.playlist,
+        my_tag: args.my_tag,
         has_genre: None,
         label: args.label,
         path: args.path,
@@ -259,6 +263,7 @@ pub(crate) async fn run_hydrate(args: HydrateArgs) -> Result<(), Box<dyn std::er
                 cache_probe.as_ref(),
                 true,
                 essentia_python.is_some(),
+                false,
             )?;
             if has_stratum && has_essentia {
                 analysis_cached += 1;
@@ -968,7 +973,7 @@ async fn cli_analyze_for_hydrate(
         Err(_) => return false,
     };
     let file_size = metadata.len() as i64;
-    let file_mtime = file_mtime_unix(&metadata);
+    let file_mtime = store::file_mtime_unix(&metadata);
     let mut ok = true;
 
     if needs_stratum {
@@ -1,5 +1,6 @@
 mod analyze;
 mod hydrate;
+mod orphans;
 mod tags;
 
 use std::path::{Path, PathBuf};
@@ -11,7 +12,7 @@ use crate::{audio, store};
 #[derive(Parser)]
 #[command(name = "reklawdbox", version)]
 enum Cli {
-    /// Batch audio analysis (stratum-dsp + Essentia)
+    /// Batch audio analysis (stratum-dsp + Essentia or native features)
     Analyze(analyze::AnalyzeArgs),
     /// Batch enrichment + analysis (Discogs, Beatport, audio analysis)
     Hydrate(hydrate::HydrateArgs),
@@ -23,6 +24,8 @@ enum Cli {
     ExtractArt(tags::ExtractArtArgs),
     /// Embed cover art into audio files
     EmbedArt(tags::EmbedArtArgs),
+    /// List audio files under a directory that are not in the Rekordbox collection
+    Orphans(orphans::OrphansArgs),
 }
 
 pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -34,6 +37,7 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Cli::WriteTags(args) => tags::run_write_tags(args),
         Cli::ExtractArt(args) => tags::run_extract_art(args),
         Cli::EmbedArt(args) => tags::run_embed_art(args),
+        Cli::Orphans(args) => orphans::run_orphans(args),
     }
 }
 
@@ -41,15 +45,6 @@ pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
 // Shared helpers used by analyze + hydrate
 // ---------------------------------------------------------------------------
 
-fn file_mtime_unix(metadata: &std::fs::Metadata) -> i64 {
-    metadata
-        .modified()
-        .ok()
-        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-        .map(|d| d.as_secs() as i64)
-        .unwrap_or(0)
-}
-
 fn is_cache_fresh(
     cached: Option<&store::CachedAudioAnalysis>,
     file_size: i64,
@@ -68,34 +63,82 @@ fn cache_probe_for_path(file_path: &str, skip_cached: bool) -> Option<(String, i
 
     match audio::resolve_audio_path(file_path) {
         Ok(path) => match std::fs::metadata(&path) {
-            Ok(metadata) => Some((path, metadata.len() as i64, file_mtime_unix(&metadata))),
+            Ok(metadata) => Some((path, metadata.len() as i64, store::file_mtime_unix(&metadata))),
             Err(_) => None,
         },
         Err(_) => None,
     }
 }
 
-fn has_fresh_cache_entry(
+/// Whether the probed file has a fresh stratum-dsp entry.
+fn has_stratum_entry(
     store_conn: &rusqlite::Connection,
     cache_probe: Option<&(String, i64, i64)>,
-    analyzer: &str,
+    accept_windowed: bool,
 ) -> Result<bool, rusqlite::Error> {
-    if let Some((cache_key, file_size, file_mtime)) = cache_probe {
-        let cached = store::get_audio_analysis(store_conn, cache_key, analyzer)?;
-        Ok(is_cache_fresh(cached.as_ref(), *file_size, *file_mtime))
-    } else {
-        Ok(false)
-    }
+    let Some((cache_key, file_size, file_mtime)) = cache_probe else {
+        return Ok(false);
+    };
+    let cached = store::get_audio_analysis(store_conn, cache_key, audio::ANALYZER_STRATUM)?;
+    Ok(is_cache_fresh(cached.as_ref(), *file_size, *file_mtime)
+        && cached.is_some_and(|entry| {
+            accept_windowed || !audio::is_windowed_version(&entry.analysis_version)
+        }))
+}
+
+/// Whether native features for the probed file are fresh and come from the
+/// current analyzer version. Features for part of the track only count when
+/// `accept_windowed` is set.
+fn has_current_native_entry(
+    store_conn: &rusqlite::Connection,
+    cache_probe: Option<&(String, i64, i64)>,
+    accept_windowed: bool,
+) -> Result<bool, rusqlite::Error> {
+    let Some((cache_key, file_size, file_mtime)) = cache_probe else {
+        return Ok(false);
+    };
+    let cached = store::get_audio_analysis(store_conn, cache_key, audio::ANALYZER_NATIVE)?;
+    Ok(is_cache_fresh(cached.as_ref(), *file_size, *file_mtime)
+        && cached.is_some_and(|entry| {
+            entry
+                .analysis_version
+                .strip_prefix(audio::NATIVE_FEATURES_VERSION)
+                .is_some_and(|window| {
+                    window.is_empty() || (accept_windowed && audio::is_windowed_version(window))
+                })
+        }))
 }
 
+/// Whether Essentia features for the probed file are fresh and come from the
+/// current feature script.
+fn has_current_essentia_entry(
+    store_conn: &rusqlite::Connection,
+    cache_probe: Option<&(String, i64, i64)>,
+) -> Result<bool, rusqlite::Error> {
+    let Some((cache_key, file_size, file_mtime)) = cache_probe else {
+        return Ok(false);
+    };
+    let cached = store::get_audio_analysis(store_conn, cache_key, audio::ANALYZER_ESSENTIA)?;
+    Ok(is_cache_fresh(cached.as_ref(), *file_size, *file_mtime)
+        && cached.is_some_and(|entry| {
+            serde_json::from_str::<audio::EssentiaOutput>(&entry.features_json).is_ok_and(
+                |features| features.features_version == audio::ESSENTIA_FEATURES_VERSION,
+            )
+        }))
+}
+
+/// Whether fresh stratum-dsp and Essentia entries exist for the probed file.
+/// A stratum-dsp result for part of the track only counts when
+/// `accept_windowed_stratum` is set.
 fn cache_status_for_track(
     store_conn: &rusqlite::Connection,
     cache_probe: Option<&(String, i64, i64)>,
     skip_cached: bool,
     essentia_available: bool,
+    accept_windowed_stratum: bool,
 ) -> Result<(bool, bool), rusqlite::Error> {
     let has_stratum = if skip_cached {
-        has_fresh_cache_entry(store_conn, cache_probe, audio::ANALYZER_STRATUM)?
+        has_stratum_entry(store_conn, cache_probe, accept_windowed_stratum)?
     } else {
         false
     };
@@ -103,7 +146,7 @@ fn cache_status_for_track(
     let has_essentia = if !essentia_available {
         true
     } else if skip_cached {
-        has_fresh_cache_entry(store_conn, cache_probe, audio::ANALYZER_ESSENTIA)?
+        has_current_essentia_entry(store_conn, cache_probe)?
     } else {
         false
     };
@@ -181,10 +224,13 @@ fn display_field_name(field: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::{
-        cache_status_for_track, file_mtime_unix, is_cache_fresh,
+        cache_status_for_track, has_current_native_entry, is_cache_fresh,
+    };
+    use super::analyze::{
+        handle_analysis_result, handle_decode_result, mark_track_outcome, memory_permits,
     };
-    use super::analyze::{handle_analysis_result, handle_decode_result, mark_track_outcome};
-    use crate::{audio::AudioError, audio::StratumResult, store, store::CachedAudioAnalysis};
+    use crate::store::{self, CachedAudioAnalysis, file_mtime_unix};
+    use crate::{audio::AudioError, audio::StratumResult};
     use std::time::Duration;
 
     fn cached(file_size: i64, file_mtime: i64) -> CachedAudioAnalysis {
@@ -213,6 +259,10 @@ mod tests {
             analyzer_version: "1.0.0".to_string(),
             flags: vec![],
             warnings: vec![],
+            first_beat_sec: None,
+            first_beat_in_bar: None,
+            opening: None,
+            closing: None,
         }
     }
 
@@ -257,6 +307,14 @@ mod tests {
         assert!(file_mtime_unix(&metadata) >= 0);
     }
 
+    /// Essentia cache JSON from the current feature script.
+    fn current_essentia_json() -> String {
+        format!(
+            r#"{{"features_version":"{}"}}"#,
+            crate::audio::ESSENTIA_FEATURES_VERSION
+        )
+    }
+
     #[test]
     fn cache_status_skips_track_when_both_fresh_entries_exist() {
         let (_dir, conn, probe) = open_temp_store_with_probe();
@@ -273,12 +331,18 @@ mod tests {
         )
         .expect("set stratum");
         store::set_audio_analysis(
-            &conn, &cache_key, "essentia", file_size, file_mtime, "1.0.0", "{}",
+            &conn,
+            &cache_key,
+            "essentia",
+            file_size,
+            file_mtime,
+            "1.0.0",
+            &current_essentia_json(),
         )
         .expect("set essentia");
 
         let (has_stratum, has_essentia) =
-            cache_status_for_track(&conn, Some(&probe), true, true).expect("cache status");
+            cache_status_for_track(&conn, Some(&probe), true, true, false).expect("cache status");
         assert!(has_stratum);
         assert!(has_essentia);
     }
@@ -299,16 +363,113 @@ mod tests {
         )
         .expect("set stale stratum");
         store::set_audio_analysis(
-            &conn, &cache_key, "essentia", file_size, file_mtime, "1.0.0", "{}",
+            &conn,
+            &cache_key,
+            "essentia",
+            file_size,
+            file_mtime,
+            "1.0.0",
+            &current_essentia_json(),
         )
         .expect("set fresh essentia");
 
         let (has_stratum, has_essentia) =
-            cache_status_for_track(&conn, Some(&probe), true, true).expect("cache status");
+            cache_status_for_track(&conn, Some(&probe), true, true, false).expect("cache status");
         assert!(!has_stratum, "stale stratum cache must be re-analyzed");
         assert!(has_essentia, "fresh essentia cache should still be skipped");
     }
 
+    #[test]
+    fn windowed_stratum_cache_only_counts_for_windowed_runs() {
+        let (_dir, conn, probe) = open_temp_store_with_probe();
+        let (cache_key, file_size, file_mtime) = probe.clone();
+
+        store::set_audio_analysis(
+            &conn,
+            &cache_key,
+            "stratum-dsp",
+            file_size,
+            file_mtime,
+            "1.0.0+window:last-10.0min",
+            "{}",
+        )
+        .expect("set windowed stratum");
+
+        let (has_stratum, _) =
+            cache_status_for_track(&conn, Some(&probe), true, false, false).expect("cache status");
+        assert!(!has_stratum, "a full run must replace a windowed result");
+        let (has_stratum, _) =
+            cache_status_for_track(&conn, Some(&probe), true, false, true).expect("cache status");
+        assert!(has_stratum, "a windowed run may reuse a windowed result");
+    }
+
+    #[test]
+    fn essentia_cache_needs_the_current_feature_script() {
+        let (_dir, conn, probe) = open_temp_store_with_probe();
+        let (cache_key, file_size, file_mtime) = probe.clone();
+
+        store::set_audio_analysis(
+            &conn, &cache_key, "essentia", file_size, file_mtime, "2.1", "{}",
+        )
+        .expect("set unversioned essentia");
+        let (_, has_essentia) =
+            cache_status_for_track(&conn, Some(&probe), true, true, false).expect("cache status");
+        assert!(
+            !has_essentia,
+            "features from before the key estimate must be recomputed"
+        );
+    }
+
+    #[test]
+    fn native_cache_needs_the_current_analyzer_version() {
+        let (_dir, conn, probe) = open_temp_store_with_probe();
+        let (cache_key, file_size, file_mtime) = probe.clone();
+        assert!(!has_current_native_entry(&conn, Some(&probe), false).expect("cache status"));
+
+        store::set_audio_analysis(
+            &conn, &cache_key, "native", file_size, file_mtime, "native-1", "{}",
+        )
+        .expect("set stale native");
+        assert!(
+            !has_current_native_entry(&conn, Some(&probe), false).expect("cache status"),
+            "features metered by an older analyzer must be recomputed"
+        );
+
+        store::set_audio_analysis(
+            &conn,
+            &cache_key,
+            "native",
+            file_size,
+            file_mtime,
+            crate::audio::NATIVE_FEATURES_VERSION,
+            "{}",
+        )
+        .expect("set native");
+        assert!(has_current_native_entry(&conn, Some(&probe), false).expect("cache status"));
+
+        store::set_audio_analysis(
+            &conn,
+            &cache_key,
+            "native",
+            file_size,
+            file_mtime,
+            &format!(
+                "{}+window:first-5.0min",
+                crate::audio::NATIVE_FEATURES_VERSION
+            ),
+            "{}",
+        )
+        .expect("set windowed native");
+        assert!(
+            !has_current_native_entry(&conn, Some(&probe), false).expect("cache status"),
+            "a full run must replace windowed features"
+        );
+        assert!(
+            has_current_native_entry(&conn, Some(&probe), true).expect("cache status"),
+            "a windowed run may reuse windowed features"
+        );
+    }
+
     #[tokio::test]
     async fn decode_join_error_marks_failed_and_allows_next_track() {
         let handle = tokio::spawn(async {
@@ -367,4 +528,16 @@ mod tests {
         assert_eq!(analyzed, 1);
         assert_eq!(failed, 1);
     }
+
+    #[test]
+    fn memory_permits_stay_within_the_budget() {
+        assert_eq!(memory_permits(0, 4096), 1, "every track takes a permit");
+        assert_eq!(memory_permits(750, 4096), 750);
+        assert_eq!(
+            memory_permits(9000, 4096),
+            4096,
+            "an oversized track takes the whole budget and runs alone"
+        );
+        assert_eq!(memory_permits(10, 0), 1);
+    }
 }
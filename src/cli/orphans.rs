@@ -0,0 +1,71 @@
+use std::path::Path;
+
+use crate::{audit, db};
+
+#[derive(clap::Args)]
+pub(crate) struct OrphansArgs {
+    /// Directory to scan for audio files missing from the collection
+    #[arg(required = true)]
+    scope: String,
+    /// Write a Rekordbox XML with the orphans (one playlist per directory)
+    #[arg(long)]
+    xml: Option<String>,
+    /// Max directory groups to list
+    #[arg(long, default_value = "100")]
+    limit: usize,
+    /// Output as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+pub(crate) fn run_orphans(args: OrphansArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let db_path = db::resolve_db_path().ok_or(
+        "Cannot find Rekordbox database. Set REKORDBOX_DB_PATH or ensure Rekordbox is installed.",
+    )?;
+    let conn = db::open(&db_path)?;
+
+    let scope = audit::enforce_trailing_slash(&args.scope);
+    let imported = db::imported_paths(&conn)?;
+    let report = audit::find_orphans(
+        &scope,
+        &imported,
+        args.limit,
+        args.xml.as_deref().map(Path::new),
+    )?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!(
+        "{} audio file(s) in {}: {} imported, {} orphan(s) in {} director(ies)",
+        report.files_in_scope, report.scope, report.imported, report.orphans, report.total_groups
+    );
+    for group in &report.groups {
+        println!();
+        println!("{} ({})", group.directory, group.files.len());
+        for file in &group.files {
+            let name = Path::new(file)
+                .file_name()
+                .map(|n| n.to_string_lossy())
+                .unwrap_or_default();
+            println!("  {name}");
+        }
+    }
+    if report.groups.len() < report.total_groups {
+        println!();
+        println!(
+            "... {} more director(ies); raise --limit to list them",
+            report.total_groups - report.groups.len()
+        );
+    }
+    for warning in &report.warnings {
+        eprintln!("warning: {warning}");
+    }
+    if let Some(path) = &report.xml_path {
+        println!();
+        println!("Wrote {path}");
+    }
+    Ok(())
+}
@@ -3,7 +3,8 @@ use std::collections::{HashMap, HashSet};
 use rusqlite::{Connection, OpenFlags, params};
 
 use crate::types::{
-    FileKind, GenreCount, KeyCount, LibraryStats, Playlist, Track, rating_to_stars,
+    CuePoint, FileKind, GenreCount, HistorySession, KeyCount, LibraryStats, Playlist, Track,
+    rating_to_stars,
 };
 
 /// The universal Rekordbox 6/7 SQLCipher key (publicly known, same for all installations).
@@ -57,6 +58,15 @@ LEFT JOIN djmdColor col ON c.ColorID = col.ID
 LEFT JOIN djmdArtist ra ON c.RemixerID = ra.ID
 ";
 
+/// The FROM/JOIN part of `TRACK_SELECT`, for queries that filter on the same
+/// aliases without reading track columns.
+fn track_select_from() -> &'static str {
+    let from = TRACK_SELECT
+        .find("\nFROM djmdContent c")
+        .expect("TRACK_SELECT has a FROM clause");
+    &TRACK_SELECT[from..]
+}
+
 pub(crate) fn row_to_track(row: &rusqlite::Row) -> Result<Track, rusqlite::Error> {
     let bpm_raw: i32 = row.get("BPM")?;
     let rating_raw: i32 = row.get("Rating")?;
@@ -103,7 +113,12 @@ pub(crate) fn row_to_track(row: &rusqlite::Row) -> Result<Track, rusqlite::Error
         sample_rate: row.get("SampleRate")?,
         file_kind: FileKind::from_raw(file_type_raw),
         date_added: row.get::<_, String>("DateAdded")?.trim().to_string(),
+        my_tags: Vec::new(),
+        hot_cue_banks: Vec::new(),
+        related_track_lists: Vec::new(),
         position: None,
+        cues: Vec::new(),
+        tempo_markers: Vec::new(),
     })
 }
 
@@ -137,6 +152,7 @@ pub struct SearchParams {
     pub bpm_max: Option<f64>,
     pub key: Option<String>,
     pub playlist: Option<String>,
+    pub my_tag: Option<String>,
     pub has_genre: Option<bool>,
     pub label: Option<String>,
     pub path: Option<String>,
@@ -148,11 +164,14 @@ pub struct SearchParams {
     pub offset: Option<u32>,
 }
 
+/// Append the `params` filters to `sql`. `smart_list` holds the rules of
+/// `params.playlist` when it is a smart playlist.
 fn apply_search_filters(
     sql: &mut String,
     params: &SearchParams,
+    smart_list: Option<&SmartList>,
     bind_values: &mut Vec<Box<dyn rusqlite::types::ToSql>>,
-) {
+) -> Result<(), SmartListError> {
     if let Some(ref query_text) = params.query {
         let bind_index = bind_values.len() + 1;
         sql.push_str(&format!(
@@ -246,14 +265,29 @@ fn apply_search_filters(
         bind_values.push(Box::new(sampler_path_like_pattern()));
     }
 
-    // Playlist filter: join through djmdSongPlaylist
-    if let Some(ref playlist_id) = params.playlist {
+    if let Some(ref my_tag) = params.my_tag {
+        let idx = bind_values.len() + 1;
+        sql.push_str(&format!(
+            " AND c.ID IN (SELECT smt.ContentID FROM djmdSongMyTag smt
+                 INNER JOIN djmdMyTag mt ON mt.ID = smt.MyTagID
+                 WHERE smt.rb_local_deleted = 0 AND mt.rb_local_deleted = 0
+                   AND mt.Name = ?{idx} COLLATE NOCASE)"
+        ));
+        bind_values.push(Box::new(my_tag.trim().to_string()));
+    }
+
+    // Playlist filter: smart playlists by their rules, others through djmdSongPlaylist
+    if let Some(smart_list) = smart_list {
+        let filter = smart_list_sql(smart_list, bind_values)?;
+        sql.push_str(&format!(" AND ({filter})"));
+    } else if let Some(ref playlist_id) = params.playlist {
         let idx = bind_values.len() + 1;
         sql.push_str(&format!(
             " AND c.ID IN (SELECT sp.ContentID FROM djmdSongPlaylist sp WHERE sp.PlaylistID = ?{idx})"
         ));
         bind_values.push(Box::new(playlist_id.clone()));
     }
+    Ok(())
 }
 
 fn search_tracks_with_limit_policy(
@@ -261,10 +295,14 @@ fn search_tracks_with_limit_policy(
     params: &SearchParams,
     default_limit: Option<u32>,
     max_limit: Option<u32>,
-) -> Result<Vec<Track>, rusqlite::Error> {
+) -> Result<Vec<Track>, PlaylistError> {
+    let smart_list = match params.playlist.as_deref() {
+        Some(playlist_id) => get_smart_list(conn, playlist_id)?,
+        None => None,
+    };
     let mut sql = format!("{TRACK_SELECT} WHERE c.rb_local_deleted = 0");
     let mut bind_values: Vec<Box<dyn rusqlite::types::ToSql>> = vec![];
-    apply_search_filters(&mut sql, params, &mut bind_values);
+    apply_search_filters(&mut sql, params, smart_list.as_ref(), &mut bind_values)?;
 
     sql.push_str(" ORDER BY c.Title");
     if let Some(mut limit) = params.limit.or(default_limit) {
@@ -285,7 +323,7 @@ fn search_tracks_with_limit_policy(
     let bind_params: Vec<&dyn rusqlite::types::ToSql> =
         bind_values.iter().map(|b| b.as_ref()).collect();
     let rows = stmt.query_map(bind_params.as_slice(), row_to_track)?;
-    rows.collect()
+    Ok(rows.collect::<Result<_, _>>()?)
 }
 
 pub(crate) fn escape_like(s: &str) -> String {
@@ -297,7 +335,7 @@ pub(crate) fn escape_like(s: &str) -> String {
 pub fn search_tracks(
     conn: &Connection,
     params: &SearchParams,
-) -> Result<Vec<Track>, rusqlite::Error> {
+) -> Result<Vec<Track>, PlaylistError> {
     search_tracks_with_limit_policy(conn, params, Some(50), Some(200))
 }
 
@@ -305,7 +343,7 @@ pub fn search_tracks(
 pub fn search_tracks_unbounded(
     conn: &Connection,
     params: &SearchParams,
-) -> Result<Vec<Track>, rusqlite::Error> {
+) -> Result<Vec<Track>, PlaylistError> {
     search_tracks_with_limit_policy(conn, params, None, None)
 }
 
@@ -315,7 +353,7 @@ fn get_playlist_tracks_with_limit_policy(
     limit: Option<u32>,
     default_limit: Option<u32>,
     max_limit: Option<u32>,
-) -> Result<Vec<Track>, rusqlite::Error> {
+) -> Result<Vec<Track>, PlaylistError> {
     let resolved_limit = limit.or(default_limit).map(|value| {
         if let Some(max_limit) = max_limit {
             value.min(max_limit)
@@ -324,6 +362,10 @@ fn get_playlist_tracks_with_limit_policy(
         }
     });
 
+    if let Some(smart_list) = get_smart_list(conn, playlist_id)? {
+        return get_smart_playlist_tracks(conn, &smart_list, resolved_limit);
+    }
+
     // Insert sp.TrackNo column before the FROM clause in TRACK_SELECT
     let base_sql = TRACK_SELECT.replace(
         "\nFROM djmdContent c",
@@ -345,7 +387,7 @@ fn get_playlist_tracks_with_limit_policy(
 
     let mut stmt = conn.prepare(&sql)?;
     let rows = stmt.query_map(params![playlist_id], row_to_playlist_track)?;
-    rows.collect()
+    Ok(rows.collect::<Result<_, _>>()?)
 }
 
 pub fn get_track(conn: &Connection, track_id: &str) -> Result<Option<Track>, rusqlite::Error> {
@@ -385,10 +427,18 @@ pub fn get_playlists(conn: &Connection) -> Result<Vec<Playlist>, rusqlite::Error
             track_count: row.get("TrackCount")?,
             parent_id: row.get("ParentID")?,
             is_folder: playlist_attribute == 1,
-            is_smart: playlist_attribute == 4,
+            is_smart: playlist_attribute == SMART_PLAYLIST_ATTRIBUTE,
         })
     })?;
-    rows.collect()
+    let mut playlists = rows.collect::<Result<Vec<_>, _>>()?;
+    // Smart playlists have no djmdSongPlaylist rows; count their current matches.
+    for playlist in playlists.iter_mut().filter(|p| p.is_smart) {
+        match count_smart_playlist_tracks(conn, &playlist.id) {
+            Ok(count) => playlist.track_count = count,
+            Err(e) => tracing::debug!("smart playlist {} not evaluated: {e}", playlist.id),
+        }
+    }
+    Ok(playlists)
 }
 
 fn row_to_playlist_track(row: &rusqlite::Row) -> Result<Track, rusqlite::Error> {
@@ -401,19 +451,608 @@ pub fn get_playlist_tracks(
     conn: &Connection,
     playlist_id: &str,
     limit: Option<u32>,
-) -> Result<Vec<Track>, rusqlite::Error> {
+) -> Result<Vec<Track>, PlaylistError> {
     get_playlist_tracks_with_limit_policy(conn, playlist_id, limit, Some(200), Some(200))
 }
 
+/// Track IDs of a playlist in playlist order, skipping deleted tracks.
+/// Smart playlists are evaluated against the current library.
+pub fn get_playlist_track_ids(
+    conn: &Connection,
+    playlist_id: &str,
+) -> Result<Vec<String>, PlaylistError> {
+    if let Some(smart_list) = get_smart_list(conn, playlist_id)? {
+        let tracks = get_smart_playlist_tracks(conn, &smart_list, None)?;
+        return Ok(tracks.into_iter().map(|t| t.id).collect());
+    }
+    let mut stmt = conn.prepare(
+        "SELECT sp.ContentID
+         FROM djmdSongPlaylist sp
+         INNER JOIN djmdContent c ON c.ID = sp.ContentID
+         WHERE sp.PlaylistID = ?1 AND c.rb_local_deleted = 0
+         ORDER BY sp.TrackNo",
+    )?;
+    let rows = stmt.query_map(params![playlist_id], |row| row.get(0))?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
 /// Unbounded variant of `get_playlist_tracks` with no safety limit. Intended for `cache_coverage` only.
 pub fn get_playlist_tracks_unbounded(
     conn: &Connection,
     playlist_id: &str,
     limit: Option<u32>,
-) -> Result<Vec<Track>, rusqlite::Error> {
+) -> Result<Vec<Track>, PlaylistError> {
     get_playlist_tracks_with_limit_policy(conn, playlist_id, limit, None, None)
 }
 
+/// `djmdPlaylist.Attribute` value marking a smart playlist.
+const SMART_PLAYLIST_ATTRIBUTE: i32 = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SmartListError {
+    #[error("malformed smart playlist rules: {0}")]
+    Malformed(String),
+    #[error("unsupported smart playlist property '{0}'")]
+    UnsupportedProperty(String),
+    #[error("unsupported operator {operator} for smart playlist property '{property}'")]
+    UnsupportedOperator { property: String, operator: u8 },
+}
+
+/// Errors reading playlist contents: a database failure, or smart playlist
+/// rules that can't be evaluated.
+#[derive(Debug, thiserror::Error)]
+pub enum PlaylistError {
+    #[error(transparent)]
+    Sql(#[from] rusqlite::Error),
+    #[error(transparent)]
+    SmartList(#[from] SmartListError),
+}
+
+/// Smart playlist rules stored in `djmdPlaylist.SmartList`:
+/// `<NODE LogicalOperator="1"><CONDITION PropertyName="genre" Operator="1" ValueLeft="House" .../></NODE>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmartList {
+    /// `LogicalOperator` 1 requires every condition to match; 2 requires any.
+    pub match_all: bool,
+    pub conditions: Vec<SmartCondition>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmartCondition {
+    pub property: String,
+    pub operator: u8,
+    /// Unit for relative date operators ("day", "week", "month", "year").
+    pub unit: String,
+    pub value_left: String,
+    pub value_right: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SmartOperator {
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+    InRange,
+    InLast,
+    NotInLast,
+    Contains,
+    NotContains,
+    StartsWith,
+    EndsWith,
+}
+
+impl SmartOperator {
+    fn from_raw(raw: u8) -> Option<Self> {
+        Some(match raw {
+            1 => Self::Equal,
+            2 => Self::NotEqual,
+            3 => Self::Greater,
+            4 => Self::Less,
+            5 => Self::InRange,
+            6 => Self::InLast,
+            7 => Self::NotInLast,
+            8 => Self::Contains,
+            9 => Self::NotContains,
+            10 => Self::StartsWith,
+            11 => Self::EndsWith,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SmartValueKind {
+    Text,
+    Number,
+    Rating,
+    Date,
+    MyTag,
+}
+
+/// Star rating (0-5) from `c.Rating`, which holds either stars or a 0-255 byte.
+/// Mirrors `decode_rating_stars`.
+const RATING_STARS_SQL: &str = "CASE
+    WHEN COALESCE(c.Rating, 0) <= 0 THEN 0
+    WHEN c.Rating <= 5 THEN c.Rating
+    WHEN c.Rating <= 25 THEN 0
+    WHEN c.Rating <= 76 THEN 1
+    WHEN c.Rating <= 127 THEN 2
+    WHEN c.Rating <= 178 THEN 3
+    WHEN c.Rating <= 229 THEN 4
+    ELSE 5
+END";
+
+/// SQL expression (over the `TRACK_SELECT` joins) and value kind for a smart-list property.
+fn smart_property(property: &str) -> Option<(&'static str, SmartValueKind)> {
+    use SmartValueKind::*;
+    Some(match property {
+        "name" => ("c.Title", Text),
+        "artist" => ("a.Name", Text),
+        "album" => ("al.Name", Text),
+        "albumArtist" => (
+            "(SELECT aa.Name FROM djmdAlbum x INNER JOIN djmdArtist aa ON aa.ID = x.AlbumArtistID WHERE x.ID = c.AlbumID)",
+            Text,
+        ),
+        "originalArtist" => (
+            "(SELECT oa.Name FROM djmdArtist oa WHERE oa.ID = c.OrgArtistID)",
+            Text,
+        ),
+        "composer" => (
+            "(SELECT ca.Name FROM djmdArtist ca WHERE ca.ID = c.ComposerID)",
+            Text,
+        ),
+        "remixer" => ("ra.Name", Text),
+        "genre" => ("g.Name", Text),
+        "key" => ("k.ScaleName", Text),
+        "label" => ("l.Name", Text),
+        "comments" => ("c.Commnt", Text),
+        "fileName" => ("c.FileNameL", Text),
+        "bpm" => ("c.BPM / 100.0", Number),
+        "counter" => ("CAST(c.DJPlayCount AS INTEGER)", Number),
+        "duration" => ("c.Length", Number),
+        "year" => ("c.ReleaseYear", Number),
+        "rating" => (RATING_STARS_SQL, Rating),
+        "dateCreated" => ("c.created_at", Date),
+        "stockDate" => ("c.StockDate", Date),
+        "dateReleased" => ("c.ReleaseDate", Date),
+        "myTag" => ("c.ID", MyTag),
+        _ => return None,
+    })
+}
+
+/// Parse the `djmdPlaylist.SmartList` XML.
+pub fn parse_smart_list(xml: &str) -> Result<SmartList, SmartListError> {
+    use quick_xml::events::Event;
+
+    let mut reader = quick_xml::reader::Reader::from_str(xml);
+    let mut match_all = None;
+    let mut conditions = Vec::new();
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| SmartListError::Malformed(e.to_string()))?;
+        let element = match &event {
+            Event::Start(e) | Event::Empty(e) => e,
+            Event::Eof => break,
+            _ => continue,
+        };
+        let mut attrs = HashMap::new();
+        for attr in element.attributes() {
+            let attr = attr.map_err(|e| SmartListError::Malformed(e.to_string()))?;
+            let value = attr
+                .normalized_value(quick_xml::XmlVersion::Implicit1_0)
+                .map_err(|e| SmartListError::Malformed(e.to_string()))?;
+            attrs.insert(attr.key.into_inner().to_string(), value.into_owned());
+        }
+        let attr = |key: &str| attrs.get(key).cloned().unwrap_or_default();
+        match element.name().into_inner() {
+            "NODE" if match_all.is_none() => {
+                match_all = Some(attr("LogicalOperator").trim() != "2");
+            }
+            "NODE" => {
+                return Err(SmartListError::Malformed(
+                    "nested condition groups are not supported".to_string(),
+                ));
+            }
+            "CONDITION" => conditions.push(SmartCondition {
+                property: attr("PropertyName"),
+                operator: attr("Operator").trim().parse().map_err(|_| {
+                    SmartListError::Malformed(format!("invalid operator '{}'", attr("Operator")))
+                })?,
+                unit: attr("ValueUnit"),
+                value_left: attr("ValueLeft"),
+                value_right: attr("ValueRight"),
+            }),
+            _ => {}
+        }
+    }
+    let Some(match_all) = match_all else {
+        return Err(SmartListError::Malformed(
+            "missing NODE element".to_string(),
+        ));
+    };
+    if conditions.is_empty() {
+        return Err(SmartListError::Malformed("no conditions".to_string()));
+    }
+    Ok(SmartList {
+        match_all,
+        conditions,
+    })
+}
+
+fn smart_number(condition: &SmartCondition, raw: &str) -> Result<f64, SmartListError> {
+    raw.trim().parse::<f64>().map_err(|_| {
+        SmartListError::Malformed(format!(
+            "'{}' expects a number, got '{raw}'",
+            condition.property
+        ))
+    })
+}
+
+/// SQLite date modifier for `InLast`/`NotInLast`, e.g. "-30 days".
+fn smart_date_modifier(condition: &SmartCondition) -> Result<String, SmartListError> {
+    let amount = smart_number(condition, &condition.value_left)? as i64;
+    let unit = condition.unit.trim().to_ascii_lowercase();
+    Ok(match unit.trim_end_matches('s') {
+        "" | "day" => format!("-{amount} days"),
+        "week" => format!("-{} days", amount * 7),
+        "month" => format!("-{amount} months"),
+        "year" => format!("-{amount} years"),
+        _ => {
+            return Err(SmartListError::Malformed(format!(
+                "unknown date unit '{}'",
+                condition.unit
+            )));
+        }
+    })
+}
+
+/// Build the WHERE fragment for one condition, appending its bind values.
+fn smart_condition_sql(
+    condition: &SmartCondition,
+    bind_values: &mut Vec<Box<dyn rusqlite::types::ToSql>>,
+) -> Result<String, SmartListError> {
+    use SmartOperator::*;
+
+    let (expr, kind) = smart_property(&condition.property)
+        .ok_or_else(|| SmartListError::UnsupportedProperty(condition.property.clone()))?;
+    let unsupported = || SmartListError::UnsupportedOperator {
+        property: condition.property.clone(),
+        operator: condition.operator,
+    };
+    let op = SmartOperator::from_raw(condition.operator).ok_or_else(unsupported)?;
+    let idx = bind_values.len() + 1;
+
+    let sql = match kind {
+        SmartValueKind::Text => {
+            let expr = format!("COALESCE({expr}, '')");
+            let value = condition.value_left.trim();
+            let (sql, bind) = match op {
+                Equal => (format!("{expr} = ?{idx} COLLATE NOCASE"), value.to_string()),
+                NotEqual => (
+                    format!("{expr} <> ?{idx} COLLATE NOCASE"),
+                    value.to_string(),
+                ),
+                Contains | NotContains | StartsWith | EndsWith => {
+                    let escaped = escape_like(value);
+                    let pattern = match op {
+                        StartsWith => format!("{escaped}%"),
+                        EndsWith => format!("%{escaped}"),
+                        _ => format!("%{escaped}%"),
+                    };
+                    let not = if op == NotContains { "NOT " } else { "" };
+                    (format!("{expr} {not}LIKE ?{idx} ESCAPE '\\'"), pattern)
+                }
+                _ => return Err(unsupported()),
+            };
+            bind_values.push(Box::new(bind));
+            sql
+        }
+        SmartValueKind::Number | SmartValueKind::Rating => {
+            let value = |raw: &str| -> Result<f64, SmartListError> {
+                let n = smart_number(condition, raw)?;
+                // Ratings may be stored as stars or as the 0-255 byte.
+                Ok(if kind == SmartValueKind::Rating && n > 5.0 {
+                    rating_to_stars(n as u16) as f64
+                } else {
+                    n
+                })
+            };
+            let comparison = match op {
+                Equal => "=",
+                NotEqual => "<>",
+                Greater => ">",
+                Less => "<",
+                InRange => {
+                    bind_values.push(Box::new(value(&condition.value_left)?));
+                    bind_values.push(Box::new(value(&condition.value_right)?));
+                    return Ok(format!("({expr}) BETWEEN ?{idx} AND ?{}", idx + 1));
+                }
+                _ => return Err(unsupported()),
+            };
+            bind_values.push(Box::new(value(&condition.value_left)?));
+            format!("({expr}) {comparison} ?{idx}")
+        }
+        SmartValueKind::Date => {
+            let expr = format!("date({expr})");
+            match op {
+                Equal | NotEqual | Greater | Less => {
+                    let comparison = match op {
+                        Equal => "=",
+                        NotEqual => "<>",
+                        Greater => ">",
+                        _ => "<",
+                    };
+                    bind_values.push(Box::new(condition.value_left.trim().to_string()));
+                    format!("{expr} {comparison} date(?{idx})")
+                }
+                InRange => {
+                    bind_values.push(Box::new(condition.value_left.trim().to_string()));
+                    bind_values.push(Box::new(condition.value_right.trim().to_string()));
+                    format!("{expr} BETWEEN date(?{idx}) AND date(?{})", idx + 1)
+                }
+                InLast | NotInLast => {
+                    bind_values.push(Box::new(smart_date_modifier(condition)?));
+                    let comparison = if op == InLast { ">=" } else { "<" };
+                    format!("{expr} {comparison} date('now', ?{idx})")
+                }
+                _ => return Err(unsupported()),
+            }
+        }
+        SmartValueKind::MyTag => {
+            let not = match op {
+                Equal => "",
+                NotEqual => "NOT ",
+                _ => return Err(unsupported()),
+            };
+            bind_values.push(Box::new(condition.value_left.trim().to_string()));
+            format!(
+                "{not}EXISTS (SELECT 1 FROM djmdSongMyTag smt WHERE smt.ContentID = {expr} AND smt.MyTagID = ?{idx})"
+            )
+        }
+    };
+    Ok(sql)
+}
+
+/// WHERE fragment matching every track selected by `smart_list`.
+pub fn smart_list_sql(
+    smart_list: &SmartList,
+    bind_values: &mut Vec<Box<dyn rusqlite::types::ToSql>>,
+) -> Result<String, SmartListError> {
+    let joiner = if smart_list.match_all {
+        " AND "
+    } else {
+        " OR "
+    };
+    let parts = smart_list
+        .conditions
+        .iter()
+        .map(|condition| smart_condition_sql(condition, bind_values).map(|sql| format!("({sql})")))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(parts.join(joiner))
+}
+
+/// Parsed rules for `playlist_id` if it is a smart playlist, `None` otherwise.
+pub fn get_smart_list(
+    conn: &Connection,
+    playlist_id: &str,
+) -> Result<Option<SmartList>, PlaylistError> {
+    use rusqlite::OptionalExtension;
+
+    let raw: Option<Option<String>> = conn
+        .query_row(
+            "SELECT SmartList FROM djmdPlaylist
+             WHERE ID = ?1 AND Attribute = ?2 AND rb_local_deleted = 0",
+            params![playlist_id, SMART_PLAYLIST_ATTRIBUTE],
+            |row| row.get(0),
+        )
+        .optional()?;
+    match raw {
+        None => Ok(None),
+        Some(xml) => Ok(Some(parse_smart_list(xml.as_deref().unwrap_or_default())?)),
+    }
+}
+
+/// Evaluate smart playlist rules against `djmdContent`. Tracks are ordered by
+/// date added; `position` is the 1-based rank in that order.
+pub fn get_smart_playlist_tracks(
+    conn: &Connection,
+    smart_list: &SmartList,
+    limit: Option<u32>,
+) -> Result<Vec<Track>, PlaylistError> {
+    let mut bind_values: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+    let filter = smart_list_sql(smart_list, &mut bind_values)?;
+    let base_sql = TRACK_SELECT.replace(
+        "\nFROM djmdContent c",
+        ",\n    ROW_NUMBER() OVER (ORDER BY c.created_at, c.ID) AS Position\nFROM djmdContent c",
+    );
+    let mut sql = format!(
+        "{base_sql} WHERE c.rb_local_deleted = 0 AND ({filter}) ORDER BY c.created_at, c.ID"
+    );
+    if let Some(limit) = limit {
+        sql.push_str(&format!(" LIMIT {limit}"));
+    }
+
+    let mut stmt = conn.prepare(&sql)?;
+    let bind_params: Vec<&dyn rusqlite::types::ToSql> =
+        bind_values.iter().map(|b| b.as_ref()).collect();
+    let rows = stmt.query_map(bind_params.as_slice(), row_to_playlist_track)?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+/// Count a smart playlist's matches over the `TRACK_SELECT` joins only,
+/// skipping the per-row columns and subqueries a full track read needs.
+fn count_smart_playlist_tracks(conn: &Connection, playlist_id: &str) -> Result<i32, PlaylistError> {
+    let Some(smart_list) = get_smart_list(conn, playlist_id)? else {
+        return Ok(0);
+    };
+    let mut bind_values: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+    let filter = smart_list_sql(&smart_list, &mut bind_values)?;
+    let sql = format!(
+        "SELECT COUNT(*) {} WHERE c.rb_local_deleted = 0 AND ({filter})",
+        track_select_from()
+    );
+    let bind_params: Vec<&dyn rusqlite::types::ToSql> =
+        bind_values.iter().map(|b| b.as_ref()).collect();
+    Ok(conn.query_row(&sql, bind_params.as_slice(), |row| row.get(0))?)
+}
+
+/// Play sessions from `djmdHistory`, newest first. Rekordbox groups sessions
+/// into year/month folders; folders are included so the tree can be rebuilt.
+/// `since` (`YYYY-MM-DD`) drops sessions recorded before that date.
+pub fn get_history_sessions(
+    conn: &Connection,
+    since: Option<&str>,
+) -> Result<Vec<HistorySession>, rusqlite::Error> {
+    let mut sql = "
+        SELECT
+            h.ID,
+            COALESCE(h.Name, '') AS Name,
+            COALESCE(h.ParentID, '') AS ParentID,
+            COALESCE(h.Attribute, 0) AS Attribute,
+            COALESCE(h.DateCreated, '') AS DateCreated,
+            (
+                SELECT COUNT(*)
+                FROM djmdSongHistory sh
+                INNER JOIN djmdContent c ON c.ID = sh.ContentID
+                WHERE sh.HistoryID = h.ID AND sh.rb_local_deleted = 0 AND c.rb_local_deleted = 0
+            ) AS TrackCount
+        FROM djmdHistory h
+        WHERE h.rb_local_deleted = 0"
+        .to_string();
+    let mut bind_values: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+    if let Some(since) = since {
+        bind_values.push(Box::new(since.to_string()));
+        sql.push_str(" AND (h.Attribute = 1 OR substr(h.DateCreated, 1, 10) >= ?1)");
+    }
+    sql.push_str(" ORDER BY h.DateCreated DESC, h.Seq DESC");
+
+    let mut stmt = conn.prepare(&sql)?;
+    let bind_params: Vec<&dyn rusqlite::types::ToSql> =
+        bind_values.iter().map(|b| b.as_ref()).collect();
+    let rows = stmt.query_map(bind_params.as_slice(), |row| {
+        let date: String = row.get("DateCreated")?;
+        Ok(HistorySession {
+            id: row.get("ID")?,
+            name: row.get::<_, String>("Name")?.trim().to_string(),
+            parent_id: row.get("ParentID")?,
+            is_folder: row.get::<_, i32>("Attribute")? == 1,
+            date: date.chars().take(10).collect(),
+            track_count: row.get("TrackCount")?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Tracks of a play session in the order they were played.
+pub fn get_history_tracks(
+    conn: &Connection,
+    history_id: &str,
+    limit: Option<u32>,
+) -> Result<Vec<Track>, rusqlite::Error> {
+    let base_sql = TRACK_SELECT.replace(
+        "\nFROM djmdContent c",
+        ",\n    sh.TrackNo AS Position\nFROM djmdContent c",
+    );
+    let mut sql = format!(
+        "{base_sql}
+         INNER JOIN djmdSongHistory sh ON sh.ContentID = c.ID
+         WHERE sh.HistoryID = ?1 AND sh.rb_local_deleted = 0 AND c.rb_local_deleted = 0
+         ORDER BY sh.TrackNo"
+    );
+    if let Some(limit) = limit {
+        sql.push_str(&format!(" LIMIT {limit}"));
+    }
+
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params![history_id], row_to_playlist_track)?;
+    rows.collect()
+}
+
+/// How often `to_id` was played directly after `from_id` across all play
+/// sessions, and the date of the most recent such session.
+pub fn get_played_transition(
+    conn: &Connection,
+    from_id: &str,
+    to_id: &str,
+) -> Result<(u32, Option<String>), rusqlite::Error> {
+    conn.query_row(
+        "SELECT COUNT(*), MAX(substr(h.DateCreated, 1, 10))
+         FROM djmdSongHistory a
+         INNER JOIN djmdSongHistory b
+             ON b.HistoryID = a.HistoryID AND b.TrackNo = a.TrackNo + 1
+         INNER JOIN djmdHistory h ON h.ID = a.HistoryID
+         WHERE a.ContentID = ?1 AND b.ContentID = ?2
+           AND a.rb_local_deleted = 0 AND b.rb_local_deleted = 0
+           AND h.rb_local_deleted = 0",
+        params![from_id, to_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+/// Transitions played live among `ids`: for each track, how often each other
+/// track in `ids` was played directly after it.
+pub fn get_played_transitions(
+    conn: &Connection,
+    ids: &[String],
+) -> Result<HashMap<String, HashMap<String, u32>>, rusqlite::Error> {
+    const MAX_BIND_VARS_PER_QUERY: usize = 900;
+
+    let mut played: HashMap<String, HashMap<String, u32>> = HashMap::new();
+    let wanted: HashSet<&str> = ids.iter().map(String::as_str).collect();
+    for chunk in ids.chunks(MAX_BIND_VARS_PER_QUERY) {
+        let placeholders: Vec<String> = (1..=chunk.len()).map(|i| format!("?{i}")).collect();
+        let sql = format!(
+            "SELECT a.ContentID, b.ContentID, COUNT(*)
+             FROM djmdSongHistory a
+             INNER JOIN djmdSongHistory b
+                 ON b.HistoryID = a.HistoryID AND b.TrackNo = a.TrackNo + 1
+             INNER JOIN djmdHistory h ON h.ID = a.HistoryID
+             WHERE a.ContentID IN ({})
+               AND a.rb_local_deleted = 0 AND b.rb_local_deleted = 0
+               AND h.rb_local_deleted = 0
+             GROUP BY a.ContentID, b.ContentID",
+            placeholders.join(", ")
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let refs: Vec<&dyn rusqlite::types::ToSql> = chunk
+            .iter()
+            .map(|s| s as &dyn rusqlite::types::ToSql)
+            .collect();
+        let rows = stmt.query_map(refs.as_slice(), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, u32>(2)?,
+            ))
+        })?;
+        for row in rows {
+            let (from_id, to_id, count) = row?;
+            if wanted.contains(to_id.as_str()) {
+                played.entry(from_id).or_default().insert(to_id, count);
+            }
+        }
+    }
+    Ok(played)
+}
+
+/// IDs of tracks played in any session on or after `since` (`YYYY-MM-DD`).
+pub fn get_tracks_played_since(
+    conn: &Connection,
+    since: &str,
+) -> Result<HashSet<String>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT sh.ContentID
+         FROM djmdSongHistory sh
+         INNER JOIN djmdHistory h ON h.ID = sh.HistoryID
+         WHERE sh.rb_local_deleted = 0 AND h.rb_local_deleted = 0
+           AND substr(h.DateCreated, 1, 10) >= ?1",
+    )?;
+    let rows = stmt.query_map(params![since], |row| row.get(0))?;
+    rows.collect()
+}
+
 pub fn get_library_stats(conn: &Connection) -> Result<LibraryStats, rusqlite::Error> {
     get_library_stats_filtered(conn, true)
 }
@@ -563,6 +1202,253 @@ pub fn get_tracks_by_ids(conn: &Connection, ids: &[String]) -> Result<Vec<Track>
     Ok(result)
 }
 
+/// Map a `djmdCue.Kind` to a hot cue slot (0 = A). Kind 0 is a memory cue and
+/// Rekordbox skips kind 4, so hot cues D-H are stored as kinds 5-9.
+fn hot_cue_slot(kind: i32) -> Option<u8> {
+    match kind {
+        1..=3 => Some((kind - 1) as u8),
+        5..=9 => Some((kind - 2) as u8),
+        _ => None,
+    }
+}
+
+/// Load memory cues, hot cues and loops from `djmdCue` onto `tracks`, ordered by position.
+pub fn attach_cues(conn: &Connection, tracks: &mut [Track]) -> Result<(), rusqlite::Error> {
+    const MAX_BIND_VARS_PER_QUERY: usize = 900;
+
+    let ids: Vec<String> = tracks.iter().map(|t| t.id.clone()).collect();
+    let mut cues_by_id: HashMap<String, Vec<CuePoint>> = HashMap::new();
+    for chunk in ids.chunks(MAX_BIND_VARS_PER_QUERY) {
+        let placeholders: Vec<String> = (1..=chunk.len()).map(|i| format!("?{i}")).collect();
+        let sql = format!(
+            "SELECT ContentID, COALESCE(InMsec, 0), COALESCE(OutMsec, -1), COALESCE(Kind, 0),
+                    COALESCE(Comment, '')
+             FROM djmdCue
+             WHERE ContentID IN ({}) AND rb_local_deleted = 0
+             ORDER BY ContentID, InMsec, Kind",
+            placeholders.join(", ")
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let refs: Vec<&dyn rusqlite::types::ToSql> = chunk
+            .iter()
+            .map(|s| s as &dyn rusqlite::types::ToSql)
+            .collect();
+        let rows = stmt.query_map(refs.as_slice(), |row| {
+            let in_msec: i64 = row.get(1)?;
+            let out_msec: i64 = row.get(2)?;
+            let kind: i32 = row.get(3)?;
+            let cue = CuePoint {
+                name: row.get::<_, String>(4)?.trim().to_string(),
+                start: in_msec.max(0) as f64 / 1000.0,
+                end: (out_msec > in_msec).then(|| out_msec as f64 / 1000.0),
+                hot_cue: hot_cue_slot(kind),
+            };
+            Ok((row.get::<_, String>(0)?, cue))
+        })?;
+        for row in rows {
+            let (track_id, cue) = row?;
+            cues_by_id.entry(track_id).or_default().push(cue);
+        }
+    }
+
+    for track in tracks.iter_mut() {
+        track.cues = cues_by_id.remove(&track.id).unwrap_or_default();
+    }
+    Ok(())
+}
+
+/// Whether `table` exists. Rekordbox versions differ in which optional
+/// tables (My Tags, Hot Cue Bank lists, Related Tracks) a library has.
+fn table_exists(conn: &Connection, table: &str) -> Result<bool, rusqlite::Error> {
+    conn.query_row(
+        "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        params![table],
+        |row| row.get(0),
+    )
+}
+
+/// Names per track from a `(ContentID, Name)` query whose `IN ({})` is filled
+/// with `ids`, in query order and without duplicates.
+fn names_by_track(
+    conn: &Connection,
+    ids: &[String],
+    sql_template: &str,
+) -> Result<HashMap<String, Vec<String>>, rusqlite::Error> {
+    const MAX_BIND_VARS_PER_QUERY: usize = 900;
+
+    let mut names: HashMap<String, Vec<String>> = HashMap::new();
+    for chunk in ids.chunks(MAX_BIND_VARS_PER_QUERY) {
+        let placeholders: Vec<String> = (1..=chunk.len()).map(|i| format!("?{i}")).collect();
+        let sql = sql_template.replace("{}", &placeholders.join(", "));
+        let mut stmt = conn.prepare(&sql)?;
+        let refs: Vec<&dyn rusqlite::types::ToSql> = chunk
+            .iter()
+            .map(|s| s as &dyn rusqlite::types::ToSql)
+            .collect();
+        let rows = stmt.query_map(refs.as_slice(), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (track_id, name) = row?;
+            let name = name.trim().to_string();
+            let list = names.entry(track_id).or_default();
+            if !name.is_empty() && !list.contains(&name) {
+                list.push(name);
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Load My Tags, Hot Cue Bank lists and Related Tracks lists into `tracks`.
+/// They're left empty when the library has no such tables.
+pub fn attach_track_lists(conn: &Connection, tracks: &mut [Track]) -> Result<(), rusqlite::Error> {
+    let ids: Vec<String> = tracks.iter().map(|t| t.id.clone()).collect();
+    let lookup = |tables: [&str; 2], sql: &str| -> Result<_, rusqlite::Error> {
+        if table_exists(conn, tables[0])? && table_exists(conn, tables[1])? {
+            names_by_track(conn, &ids, sql)
+        } else {
+            Ok(HashMap::new())
+        }
+    };
+    let mut my_tags = lookup(
+        ["djmdMyTag", "djmdSongMyTag"],
+        "SELECT smt.ContentID, mt.Name FROM djmdSongMyTag smt
+         INNER JOIN djmdMyTag mt ON mt.ID = smt.MyTagID
+         LEFT JOIN djmdMyTag cat ON cat.ID = mt.ParentID
+         WHERE smt.ContentID IN ({}) AND smt.rb_local_deleted = 0 AND mt.rb_local_deleted = 0
+         ORDER BY COALESCE(cat.Seq, 0), mt.Seq",
+    )?;
+    let mut hot_cue_banks = lookup(
+        ["djmdHotCueBanklist", "djmdSongHotCueBanklist"],
+        "SELECT shb.ContentID, hb.Name FROM djmdSongHotCueBanklist shb
+         INNER JOIN djmdHotCueBanklist hb ON hb.ID = shb.HotCueBanklistID
+         WHERE shb.ContentID IN ({}) AND shb.rb_local_deleted = 0 AND hb.rb_local_deleted = 0
+         ORDER BY hb.Seq",
+    )?;
+    let mut related_track_lists = lookup(
+        ["djmdRelatedTracks", "djmdSongRelatedTracks"],
+        "SELECT srt.ContentID, rt.Name FROM djmdSongRelatedTracks srt
+         INNER JOIN djmdRelatedTracks rt ON rt.ID = srt.RelatedTracksID
+         WHERE srt.ContentID IN ({}) AND srt.rb_local_deleted = 0 AND rt.rb_local_deleted = 0
+         ORDER BY rt.Seq",
+    )?;
+    for track in tracks.iter_mut() {
+        track.my_tags = my_tags.remove(&track.id).unwrap_or_default();
+        track.hot_cue_banks = hot_cue_banks.remove(&track.id).unwrap_or_default();
+        track.related_track_lists = related_track_lists.remove(&track.id).unwrap_or_default();
+    }
+    Ok(())
+}
+
+/// `djmdContent.AnalysisDataPath` per track, for locating Rekordbox's
+/// analysis files. Empty when the column doesn't exist (older schemas and
+/// test fixtures) or a track has never been analysed.
+pub fn get_analysis_data_paths(
+    conn: &Connection,
+    ids: &[String],
+) -> Result<HashMap<String, String>, rusqlite::Error> {
+    const MAX_BIND_VARS_PER_QUERY: usize = 900;
+
+    let has_column: bool = conn.query_row(
+        "SELECT count(*) > 0 FROM pragma_table_info('djmdContent') WHERE name = 'AnalysisDataPath'",
+        [],
+        |row| row.get(0),
+    )?;
+    let mut paths = HashMap::new();
+    if !has_column {
+        return Ok(paths);
+    }
+    for chunk in ids.chunks(MAX_BIND_VARS_PER_QUERY) {
+        let placeholders: Vec<String> = (1..=chunk.len()).map(|i| format!("?{i}")).collect();
+        let sql = format!(
+            "SELECT ID, AnalysisDataPath FROM djmdContent
+             WHERE ID IN ({}) AND COALESCE(AnalysisDataPath, '') != ''",
+            placeholders.join(", ")
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let refs: Vec<&dyn rusqlite::types::ToSql> = chunk
+            .iter()
+            .map(|s| s as &dyn rusqlite::types::ToSql)
+            .collect();
+        let rows = stmt.query_map(refs.as_slice(), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (id, path) = row?;
+            paths.insert(id, path);
+        }
+    }
+    Ok(paths)
+}
+
+/// IDs of the regular (non-smart) playlists each track appears in, in
+/// playlist order. Tracks in no playlist are omitted.
+pub fn get_track_playlist_ids(
+    conn: &Connection,
+    ids: &[String],
+) -> Result<HashMap<String, Vec<String>>, rusqlite::Error> {
+    const MAX_BIND_VARS_PER_QUERY: usize = 900;
+
+    let mut memberships: HashMap<String, Vec<String>> = HashMap::new();
+    for chunk in ids.chunks(MAX_BIND_VARS_PER_QUERY) {
+        let placeholders: Vec<String> = (1..=chunk.len()).map(|i| format!("?{i}")).collect();
+        let sql = format!(
+            "SELECT DISTINCT sp.ContentID, sp.PlaylistID, p.Seq
+             FROM djmdSongPlaylist sp
+             INNER JOIN djmdPlaylist p ON p.ID = sp.PlaylistID
+             WHERE sp.ContentID IN ({}) AND p.rb_local_deleted = 0
+             ORDER BY sp.ContentID, p.Seq",
+            placeholders.join(", ")
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let refs: Vec<&dyn rusqlite::types::ToSql> = chunk
+            .iter()
+            .map(|s| s as &dyn rusqlite::types::ToSql)
+            .collect();
+        let rows = stmt.query_map(refs.as_slice(), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (track_id, playlist_id) = row?;
+            memberships.entry(track_id).or_default().push(playlist_id);
+        }
+    }
+    Ok(memberships)
+}
+
+/// `djmdContent.FileSize` in bytes for the given tracks. Tracks with no
+/// recorded size (0 or NULL) are omitted.
+pub fn get_file_sizes(
+    conn: &Connection,
+    ids: &[String],
+) -> Result<HashMap<String, u64>, rusqlite::Error> {
+    const MAX_BIND_VARS_PER_QUERY: usize = 900;
+
+    let mut sizes = HashMap::new();
+    for chunk in ids.chunks(MAX_BIND_VARS_PER_QUERY) {
+        let placeholders: Vec<String> = (1..=chunk.len()).map(|i| format!("?{i}")).collect();
+        let sql = format!(
+            "SELECT ID, FileSize FROM djmdContent
+             WHERE ID IN ({}) AND rb_local_deleted = 0 AND FileSize > 0",
+            placeholders.join(", ")
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let refs: Vec<&dyn rusqlite::types::ToSql> = chunk
+            .iter()
+            .map(|s| s as &dyn rusqlite::types::ToSql)
+            .collect();
+        let rows = stmt.query_map(refs.as_slice(), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64))
+        })?;
+        for row in rows {
+            let (id, size) = row?;
+            sizes.insert(id, size);
+        }
+    }
+    Ok(sizes)
+}
+
 pub fn default_db_path() -> Option<String> {
     let home = std::env::var("HOME").ok()?;
     let path = format!("{home}/Library/Pioneer/rekordbox/master.db");
@@ -592,6 +1478,15 @@ pub fn paths_imported_in_scope(
     Ok(set)
 }
 
+/// Every `FolderPath` in the collection (non-deleted rows). Callers that
+/// compare against filesystem paths should normalise both sides first; a
+/// `LIKE` prefix can't see past NFC/NFD or percent-encoding differences.
+pub fn imported_paths(conn: &Connection) -> Result<Vec<String>, rusqlite::Error> {
+    let mut stmt = conn.prepare("SELECT FolderPath FROM djmdContent WHERE rb_local_deleted = 0")?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    rows.collect()
+}
+
 pub fn resolve_db_path() -> Option<String> {
     if let Ok(path) = std::env::var("REKORDBOX_DB_PATH")
         && std::path::Path::new(&path).exists()
@@ -718,6 +1613,7 @@ mod tests {
                 BitRate INTEGER DEFAULT 0,
                 SampleRate INTEGER DEFAULT 0,
                 FileType INTEGER DEFAULT 0,
+                FileSize INTEGER DEFAULT 0,
                 created_at TEXT DEFAULT '',
                 rb_local_deleted INTEGER DEFAULT 0
             );
@@ -727,6 +1623,7 @@ mod tests {
                 Name VARCHAR(255),
                 Attribute INTEGER DEFAULT 0,
                 ParentID VARCHAR(255) DEFAULT '',
+                SmartList TEXT,
                 rb_local_deleted INTEGER DEFAULT 0
             );
             CREATE TABLE djmdSongPlaylist (
@@ -735,6 +1632,31 @@ mod tests {
                 ContentID VARCHAR(255),
                 TrackNo INTEGER
             );
+            CREATE TABLE djmdHistory (
+                ID VARCHAR(255) PRIMARY KEY,
+                Seq INTEGER,
+                Name VARCHAR(255),
+                Attribute INTEGER DEFAULT 0,
+                ParentID VARCHAR(255) DEFAULT 'root',
+                DateCreated VARCHAR(255),
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+            CREATE TABLE djmdSongHistory (
+                ID VARCHAR(255) PRIMARY KEY,
+                HistoryID VARCHAR(255),
+                ContentID VARCHAR(255),
+                TrackNo INTEGER,
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+            CREATE TABLE djmdCue (
+                ID VARCHAR(255) PRIMARY KEY,
+                ContentID VARCHAR(255),
+                InMsec INTEGER,
+                OutMsec INTEGER DEFAULT -1,
+                Kind INTEGER DEFAULT 0,
+                Comment VARCHAR(255) DEFAULT '',
+                rb_local_deleted INTEGER DEFAULT 0
+            );
 
             -- Lookup data
             INSERT INTO djmdArtist (ID, Name) VALUES ('a1', 'Burial');
@@ -772,6 +1694,23 @@ mod tests {
             INSERT INTO djmdPlaylist (ID, Seq, Name, Attribute, ParentID) VALUES ('p2', 2, 'Folders', 1, 'root');
             INSERT INTO djmdSongPlaylist (ID, PlaylistID, ContentID, TrackNo) VALUES ('sp1', 'p1', 't1', 1);
             INSERT INTO djmdSongPlaylist (ID, PlaylistID, ContentID, TrackNo) VALUES ('sp2', 'p1', 't3', 2);
+
+
+            -- Play history: a 2024 folder with two sessions; t1 -> t2 played twice
+            INSERT INTO djmdHistory (ID, Seq, Name, Attribute, ParentID, DateCreated) VALUES ('h2024', 1, '2024', 1, 'root', '2024-01-01');
+            INSERT INTO djmdHistory (ID, Seq, Name, ParentID, DateCreated) VALUES ('h1', 1, 'HISTORY 2024-03-01', 'h2024', '2024-03-01');
+            INSERT INTO djmdHistory (ID, Seq, Name, ParentID, DateCreated) VALUES ('h2', 2, 'HISTORY 2024-05-10', 'h2024', '2024-05-10');
+            INSERT INTO djmdSongHistory (ID, HistoryID, ContentID, TrackNo) VALUES ('sh1', 'h1', 't1', 1);
+            INSERT INTO djmdSongHistory (ID, HistoryID, ContentID, TrackNo) VALUES ('sh2', 'h1', 't2', 2);
+            INSERT INTO djmdSongHistory (ID, HistoryID, ContentID, TrackNo) VALUES ('sh3', 'h2', 't3', 1);
+            INSERT INTO djmdSongHistory (ID, HistoryID, ContentID, TrackNo) VALUES ('sh4', 'h2', 't1', 2);
+            INSERT INTO djmdSongHistory (ID, HistoryID, ContentID, TrackNo) VALUES ('sh5', 'h2', 't2', 3);
+
+            -- Cues
+            INSERT INTO djmdCue (ID, ContentID, InMsec, OutMsec, Kind, Comment) VALUES ('cue1', 't1', 1200, -1, 0, '');
+            INSERT INTO djmdCue (ID, ContentID, InMsec, OutMsec, Kind, Comment) VALUES ('cue2', 't1', 500, -1, 5, 'Drop');
+            INSERT INTO djmdCue (ID, ContentID, InMsec, OutMsec, Kind, Comment) VALUES ('cue3', 't1', 64000, 72000, 1, 'Loop');
+            INSERT INTO djmdCue (ID, ContentID, InMsec, OutMsec, Kind, Comment, rb_local_deleted) VALUES ('cue4', 't1', 9000, -1, 0, '', 1);
             ",
         )
         .unwrap();
@@ -960,6 +1899,244 @@ mod tests {
         assert_eq!(tracks.len(), 0); // literal "%" doesn't appear in any path
     }
 
+    /// My Tag, Hot Cue Bank and Related Tracks tables, which not every
+    /// Rekordbox library has: a 'Mood' category with two tags, one bank, one list.
+    fn create_track_list_tables(conn: &Connection) {
+        conn.execute_batch(
+            "
+            CREATE TABLE djmdMyTag (
+                ID VARCHAR(255) PRIMARY KEY,
+                Seq INTEGER,
+                Name VARCHAR(255),
+                Attribute INTEGER DEFAULT 0,
+                ParentID VARCHAR(255) DEFAULT 'root',
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+            CREATE TABLE djmdSongMyTag (
+                ID VARCHAR(255) PRIMARY KEY,
+                MyTagID VARCHAR(255),
+                ContentID VARCHAR(255),
+                TrackNo INTEGER,
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+            CREATE TABLE djmdHotCueBanklist (
+                ID VARCHAR(255) PRIMARY KEY,
+                Seq INTEGER,
+                Name VARCHAR(255),
+                Attribute INTEGER DEFAULT 0,
+                ParentID VARCHAR(255) DEFAULT 'root',
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+            CREATE TABLE djmdSongHotCueBanklist (
+                ID VARCHAR(255) PRIMARY KEY,
+                HotCueBanklistID VARCHAR(255),
+                ContentID VARCHAR(255),
+                TrackNo INTEGER,
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+            CREATE TABLE djmdRelatedTracks (
+                ID VARCHAR(255) PRIMARY KEY,
+                Seq INTEGER,
+                Name VARCHAR(255),
+                Attribute INTEGER DEFAULT 0,
+                ParentID VARCHAR(255) DEFAULT 'root',
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+            CREATE TABLE djmdSongRelatedTracks (
+                ID VARCHAR(255) PRIMARY KEY,
+                RelatedTracksID VARCHAR(255),
+                ContentID VARCHAR(255),
+                TrackNo INTEGER,
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+
+            INSERT INTO djmdMyTag (ID, Seq, Name, Attribute, ParentID) VALUES ('mt-mood', 1, 'Mood', 1, 'root');
+            INSERT INTO djmdMyTag (ID, Seq, Name, ParentID) VALUES ('mt-dark', 2, 'Dark', 'mt-mood');
+            INSERT INTO djmdMyTag (ID, Seq, Name, ParentID) VALUES ('mt-vocal', 1, 'Vocal', 'mt-mood');
+            INSERT INTO djmdSongMyTag (ID, MyTagID, ContentID) VALUES ('smt1', 'mt-dark', 't1');
+            INSERT INTO djmdSongMyTag (ID, MyTagID, ContentID) VALUES ('smt2', 'mt-vocal', 't1');
+            INSERT INTO djmdSongMyTag (ID, MyTagID, ContentID) VALUES ('smt3', 'mt-dark', 't2');
+            INSERT INTO djmdSongMyTag (ID, MyTagID, ContentID, rb_local_deleted) VALUES ('smt4', 'mt-vocal', 't3', 1);
+            INSERT INTO djmdHotCueBanklist (ID, Seq, Name) VALUES ('hb1', 1, 'Garage Stabs');
+            INSERT INTO djmdSongHotCueBanklist (ID, HotCueBanklistID, ContentID) VALUES ('shb1', 'hb1', 't1');
+            INSERT INTO djmdRelatedTracks (ID, Seq, Name) VALUES ('rt1', 1, 'Same Label');
+            INSERT INTO djmdSongRelatedTracks (ID, RelatedTracksID, ContentID) VALUES ('srt1', 'rt1', 't2');
+            ",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_track_my_tags_hot_cue_banks_and_related_lists() {
+        let conn = create_test_db();
+        create_track_list_tables(&conn);
+        let mut tracks =
+            get_tracks_by_ids(&conn, &["t1".into(), "t2".into(), "t3".into()]).unwrap();
+        assert!(tracks[0].my_tags.is_empty(), "lists are loaded on demand");
+        attach_track_lists(&conn, &mut tracks).unwrap();
+        // Tag order follows djmdMyTag.Seq within the category.
+        assert_eq!(tracks[0].my_tags, ["Vocal", "Dark"]);
+        assert_eq!(tracks[0].hot_cue_banks, ["Garage Stabs"]);
+        assert!(tracks[0].related_track_lists.is_empty());
+        assert_eq!(tracks[1].related_track_lists, ["Same Label"]);
+        // Soft-deleted tag assignments are ignored.
+        assert!(tracks[2].my_tags.is_empty());
+    }
+
+    #[test]
+    fn test_track_lists_tolerate_missing_tables() {
+        let conn = create_test_db();
+        let mut tracks = get_tracks_by_ids(&conn, &["t1".into()]).unwrap();
+        attach_track_lists(&conn, &mut tracks).unwrap();
+        assert!(tracks[0].my_tags.is_empty());
+        assert!(tracks[0].hot_cue_banks.is_empty());
+        assert!(tracks[0].related_track_lists.is_empty());
+    }
+
+    #[test]
+    fn test_search_by_my_tag() {
+        let conn = create_test_db();
+        create_track_list_tables(&conn);
+        let params = SearchParams {
+            my_tag: Some(" dark ".to_string()),
+            ..Default::default()
+        };
+        let ids: Vec<String> = search_tracks(&conn, &params)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&"t1".to_string()) && ids.contains(&"t2".to_string()));
+
+        let params = SearchParams {
+            my_tag: Some("Vocal".to_string()),
+            ..Default::default()
+        };
+        let tracks = search_tracks(&conn, &params).unwrap();
+        assert_eq!(tracks.len(), 1, "deleted t3 assignment must not match");
+    }
+
+    #[test]
+    fn test_search_by_smart_playlist() {
+        let conn = create_test_db();
+        insert_smart_playlist(&conn, "s1", &smart_rules(1, &[("genre", 1, "dubstep", "")]));
+        let params = SearchParams {
+            playlist: Some("s1".to_string()),
+            ..Default::default()
+        };
+        let ids: Vec<String> = search_tracks(&conn, &params)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(ids, ["t1", "t2"]);
+
+        // Other filters still narrow the smart playlist's matches.
+        let params = SearchParams {
+            playlist: Some("s1".to_string()),
+            bpm_min: Some(139.8),
+            ..Default::default()
+        };
+        let tracks = search_tracks(&conn, &params).unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].id, "t2");
+
+        insert_smart_playlist(
+            &conn,
+            "s2",
+            &smart_rules(1, &[("unknownField", 1, "x", "")]),
+        );
+        let params = SearchParams {
+            playlist: Some("s2".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            search_tracks(&conn, &params),
+            Err(PlaylistError::SmartList(_))
+        ));
+    }
+
+    #[test]
+    fn test_history_sessions_and_tracks() {
+        let conn = create_test_db();
+        let sessions = get_history_sessions(&conn, None).unwrap();
+        let ids: Vec<&str> = sessions.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["h2", "h1", "h2024"]);
+        assert_eq!(sessions[0].date, "2024-05-10");
+        assert_eq!(sessions[0].track_count, 3);
+        assert!(sessions[2].is_folder);
+
+        let recent = get_history_sessions(&conn, Some("2024-04-01")).unwrap();
+        let ids: Vec<&str> = recent.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["h2", "h2024"], "folders are kept regardless of date");
+
+        let tracks = get_history_tracks(&conn, "h2", None).unwrap();
+        let ids: Vec<&str> = tracks.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, ["t3", "t1", "t2"]);
+        assert_eq!(tracks[2].position, Some(3));
+        assert_eq!(get_history_tracks(&conn, "h2", Some(1)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_played_transition() {
+        let conn = create_test_db();
+        let (count, last) = get_played_transition(&conn, "t1", "t2").unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(last.as_deref(), Some("2024-05-10"));
+
+        let (count, last) = get_played_transition(&conn, "t2", "t1").unwrap();
+        assert_eq!(count, 0);
+        assert!(last.is_none());
+    }
+
+    #[test]
+    fn test_get_track_playlist_ids() {
+        let conn = create_test_db();
+        let ids = ["t1", "t2", "t3"].map(String::from);
+        let memberships = get_track_playlist_ids(&conn, &ids).unwrap();
+        assert_eq!(memberships.len(), 2);
+        assert_eq!(memberships["t1"], ["p1"]);
+        assert_eq!(memberships["t3"], ["p1"]);
+    }
+
+    #[test]
+    fn test_get_file_sizes_skips_unknown_sizes() {
+        let conn = create_test_db();
+        conn.execute("UPDATE djmdContent SET FileSize = 4096 WHERE ID = 't1'", [])
+            .unwrap();
+        let ids = ["t1", "t2", "missing"].map(String::from);
+        let sizes = get_file_sizes(&conn, &ids).unwrap();
+        assert_eq!(sizes.len(), 1);
+        assert_eq!(sizes["t1"], 4096);
+
+        // Lookups past the bind-variable limit span several queries.
+        let mut many: Vec<String> = (0..1000).map(|i| format!("missing-{i}")).collect();
+        many.push("t1".into());
+        assert_eq!(get_file_sizes(&conn, &many).unwrap()["t1"], 4096);
+    }
+
+    #[test]
+    fn test_played_transitions_and_recent_plays() {
+        let conn = create_test_db();
+        let ids: Vec<String> = ["t1", "t2", "t3"].iter().map(|s| s.to_string()).collect();
+        let played = get_played_transitions(&conn, &ids).unwrap();
+        assert_eq!(played["t1"]["t2"], 2);
+        assert_eq!(played["t3"]["t1"], 1);
+        assert!(!played.contains_key("t2"));
+        // Transitions into tracks outside the pool are dropped.
+        let played = get_played_transitions(&conn, &ids[..1]).unwrap();
+        assert!(played.is_empty());
+
+        let recent = get_tracks_played_since(&conn, "2024-04-01").unwrap();
+        assert_eq!(
+            recent,
+            HashSet::from(["t1".into(), "t2".into(), "t3".into()])
+        );
+        let recent = get_tracks_played_since(&conn, "2024-06-01").unwrap();
+        assert!(recent.is_empty());
+    }
+
     #[test]
     fn test_get_track() {
         let conn = create_test_db();
@@ -1028,6 +2205,166 @@ mod tests {
         assert_eq!(tracks[0].file_kind, FileKind::Flac);
     }
 
+    #[test]
+    fn test_get_playlist_track_ids() {
+        let conn = create_test_db();
+        let ids = get_playlist_track_ids(&conn, "p1").unwrap();
+        assert_eq!(ids, vec!["t1", "t3"]);
+        assert!(get_playlist_track_ids(&conn, "p2").unwrap().is_empty());
+    }
+
+    fn insert_smart_playlist(conn: &Connection, id: &str, rules: &str) {
+        conn.execute(
+            "INSERT INTO djmdPlaylist (ID, Seq, Name, Attribute, ParentID, SmartList)
+             VALUES (?1, 9, 'Smart', 4, 'root', ?2)",
+            params![id, rules],
+        )
+        .unwrap();
+    }
+
+    fn smart_rules(logical_operator: u8, conditions: &[(&str, u8, &str, &str)]) -> String {
+        let conditions: String = conditions
+            .iter()
+            .map(|(property, operator, left, right)| {
+                format!(
+                    r#"<CONDITION PropertyName="{property}" Operator="{operator}" ValueUnit="" ValueLeft="{left}" ValueRight="{right}"/>"#
+                )
+            })
+            .collect();
+        format!(r#"<NODE Id="1" LogicalOperator="{logical_operator}">{conditions}</NODE>"#)
+    }
+
+    #[test]
+    fn test_parse_smart_list() {
+        let smart = parse_smart_list(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <NODE Id="42" LogicalOperator="2" AutomaticUpdate="0">
+              <CONDITION PropertyName="genre" Operator="1" ValueUnit="" ValueLeft="Drum &amp; Bass" ValueRight=""/>
+              <CONDITION PropertyName="dateCreated" Operator="6" ValueUnit="month" ValueLeft="3" ValueRight=""/>
+            </NODE>"#,
+        )
+        .unwrap();
+        assert!(!smart.match_all);
+        assert_eq!(smart.conditions.len(), 2);
+        assert_eq!(smart.conditions[0].value_left, "Drum & Bass");
+        assert_eq!(smart.conditions[1].operator, 6);
+        assert_eq!(smart.conditions[1].unit, "month");
+
+        assert!(matches!(
+            parse_smart_list(""),
+            Err(SmartListError::Malformed(_))
+        ));
+        assert!(matches!(
+            parse_smart_list(r#"<NODE LogicalOperator="1"></NODE>"#),
+            Err(SmartListError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_smart_playlist_tracks_match_all_conditions() {
+        let conn = create_test_db();
+        insert_smart_playlist(
+            &conn,
+            "s1",
+            &smart_rules(1, &[("genre", 1, "dubstep", ""), ("bpm", 3, "139.6", "")]),
+        );
+        let tracks = get_playlist_tracks(&conn, "s1", None).unwrap();
+        let ids: Vec<&str> = tracks.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, ["t2"]);
+        assert_eq!(tracks[0].position, Some(1));
+        assert_eq!(get_playlist_track_ids(&conn, "s1").unwrap(), ["t2"]);
+
+        let smart = get_playlists(&conn)
+            .unwrap()
+            .into_iter()
+            .find(|p| p.id == "s1")
+            .unwrap();
+        assert!(smart.is_smart);
+        assert_eq!(smart.track_count, 1);
+    }
+
+    #[test]
+    fn test_smart_playlist_tracks_match_any_condition() {
+        let conn = create_test_db();
+        insert_smart_playlist(
+            &conn,
+            "s1",
+            &smart_rules(
+                2,
+                &[
+                    ("artist", 8, "villa", ""),
+                    ("rating", 5, "4", "5"),
+                    ("name", 10, "R.I", ""),
+                ],
+            ),
+        );
+        let ids = get_playlist_track_ids(&conn, "s1").unwrap();
+        // Ordered by date added: t1/t3 by rating and title prefix, t4 by artist.
+        assert_eq!(ids, ["t1", "t3", "t4"]);
+    }
+
+    #[test]
+    fn test_smart_playlist_dates_and_my_tags() {
+        let conn = create_test_db();
+        conn.execute_batch(
+            "CREATE TABLE djmdSongMyTag (
+                ID VARCHAR(255) PRIMARY KEY,
+                MyTagID VARCHAR(255),
+                ContentID VARCHAR(255)
+            );
+            INSERT INTO djmdSongMyTag (ID, MyTagID, ContentID) VALUES ('smt1', 'tag-peak', 't3');
+            UPDATE djmdContent SET created_at = date('now', '-2 days') WHERE ID = 't4';",
+        )
+        .unwrap();
+        insert_smart_playlist(
+            &conn,
+            "recent",
+            &smart_rules(1, &[("dateCreated", 6, "7", "")]),
+        );
+        insert_smart_playlist(
+            &conn,
+            "tagged",
+            &smart_rules(1, &[("myTag", 1, "tag-peak", "")]),
+        );
+        insert_smart_playlist(
+            &conn,
+            "range",
+            &smart_rules(1, &[("dateCreated", 5, "2023-01-10", "2023-02-28")]),
+        );
+        assert_eq!(get_playlist_track_ids(&conn, "recent").unwrap(), ["t4"]);
+        assert_eq!(get_playlist_track_ids(&conn, "tagged").unwrap(), ["t3"]);
+        assert_eq!(
+            get_playlist_track_ids(&conn, "range").unwrap(),
+            ["t1", "t2", "t3"]
+        );
+    }
+
+    #[test]
+    fn test_smart_playlist_unsupported_rules_error() {
+        let conn = create_test_db();
+        insert_smart_playlist(&conn, "s1", &smart_rules(1, &[("grouping", 1, "x", "")]));
+        insert_smart_playlist(&conn, "s2", &smart_rules(1, &[("bpm", 8, "12", "")]));
+        let err = get_playlist_tracks(&conn, "s1", None).unwrap_err();
+        assert!(matches!(
+            &err,
+            PlaylistError::SmartList(SmartListError::UnsupportedProperty(p)) if p == "grouping"
+        ));
+        assert!(
+            err.to_string()
+                .contains("unsupported smart playlist property 'grouping'")
+        );
+        let err = get_playlist_track_ids(&conn, "s2").unwrap_err();
+        assert!(matches!(
+            err,
+            PlaylistError::SmartList(SmartListError::UnsupportedOperator { operator: 8, .. })
+        ));
+
+        // get_playlists still lists the playlist, without a count.
+        let playlists = get_playlists(&conn).unwrap();
+        let smart = playlists.iter().find(|p| p.id == "s1").unwrap();
+        assert_eq!(smart.track_count, 0);
+    }
+
     #[test]
     fn test_library_stats() {
         let conn = create_test_db();
@@ -1080,6 +2417,24 @@ mod tests {
         assert_eq!(tracks[0].id, "t1");
     }
 
+    #[test]
+    fn test_attach_cues() {
+        let conn = create_test_db();
+        let mut tracks = get_tracks_by_ids(&conn, &["t1".to_string(), "t2".to_string()]).unwrap();
+        attach_cues(&conn, &mut tracks).unwrap();
+
+        let cues = &tracks[0].cues;
+        assert_eq!(cues.len(), 3, "deleted cues are skipped");
+        assert_eq!(cues[0].start, 0.5);
+        assert_eq!(cues[0].hot_cue, Some(3), "kind 5 is hot cue D");
+        assert_eq!(cues[0].name, "Drop");
+        assert_eq!(cues[1].hot_cue, None);
+        assert_eq!(cues[1].end, None);
+        assert_eq!(cues[2].hot_cue, Some(0));
+        assert_eq!(cues[2].end, Some(72.0));
+        assert!(tracks[1].cues.is_empty());
+    }
+
     /// Load all tracks from the DB by paging with OFFSET.
     fn load_all_tracks(conn: &Connection) -> Vec<Track> {
         let mut all = Vec::new();
@@ -1163,7 +2518,7 @@ mod tests {
             ("djmdColor", "ID, ColorCode, Commnt"),
             (
                 "djmdPlaylist",
-                "ID, Name, Attribute, ParentID, Seq, rb_local_deleted",
+                "ID, Name, Attribute, ParentID, Seq, SmartList, rb_local_deleted",
             ),
             ("djmdSongPlaylist", "ID, PlaylistID, ContentID, TrackNo"),
         ];
@@ -0,0 +1,480 @@
+//! Duplicate detection: cluster library tracks that are the same recording
+//! imported more than once (FLAC and MP3 copies, a promo and a retail copy,
+//! the same file in two folders) and pick the copy to keep.
+//!
+//! Tracks are grouped by normalised artist/title, split by duration, and
+//! checked against cached fingerprints (or, failing that, cached tempo/key
+//! analysis) where both copies have one.
+//! Nothing is written here — non-keepers are staged as `merge_into` changes,
+//! which `write_xml` exports by pointing their playlist entries at the keeper.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::fingerprint::{self, Fingerprint};
+use crate::normalize::normalize_for_matching;
+use crate::types::{FileKind, MatchConfidence, Track};
+
+/// Max difference in library `Length` for two copies to be the same edit.
+const DURATION_TOLERANCE_SECS: i32 = 2;
+/// Max relative BPM difference (after octave folding) for matching audio.
+const BPM_TOLERANCE_RATIO: f64 = 0.02;
+/// Suffixes that distinguish nothing; "Track (Original Mix)" is "Track".
+const NEUTRAL_TITLE_SUFFIXES: &[&str] = &["original mix", "original"];
+
+/// Tempo and key from the cached stratum-dsp analysis. Much weaker than a
+/// fingerprint: different edits of a track usually share both.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoKeySignature {
+    pub bpm: f64,
+    pub key_camelot: String,
+}
+
+impl TempoKeySignature {
+    /// True when tempo agrees (allowing half/double time) and keys match
+    /// where both are known.
+    fn matches(&self, other: &Self) -> bool {
+        let bpm_matches = self.bpm > 0.0
+            && other.bpm > 0.0
+            && [1.0, 2.0, 0.5].iter().any(|factor| {
+                (self.bpm - other.bpm * factor).abs() <= self.bpm * BPM_TOLERANCE_RATIO
+            });
+        let key_matches = self.key_camelot.is_empty()
+            || other.key_camelot.is_empty()
+            || self.key_camelot == other.key_camelot;
+        bpm_matches && key_matches
+    }
+}
+
+/// One library copy with the evidence used to choose a keeper.
+#[derive(Debug, Clone)]
+pub struct LibraryCopy {
+    pub track: Track,
+    /// Names of the playlists the copy appears in.
+    pub playlists: Vec<String>,
+    pub cue_count: usize,
+    pub tempo_key: Option<TempoKeySignature>,
+    pub fingerprint: Option<Fingerprint>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateCopy {
+    pub track_id: String,
+    pub file_path: String,
+    pub file_type: FileKind,
+    pub bit_rate: i32,
+    pub length: i32,
+    pub play_count: i32,
+    pub cue_count: usize,
+    pub playlists: Vec<String>,
+    pub date_added: String,
+    pub keep: bool,
+}
+
+/// A set of copies of the same recording; the keeper is listed first.
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateCluster {
+    pub artist: String,
+    pub title: String,
+    pub confidence: MatchConfidence,
+    /// Signals shared by every copy: `artist_title`, `duration`,
+    /// `fingerprint`, `tempo_key`.
+    pub matched_on: Vec<&'static str>,
+    pub keep: String,
+    pub keep_reasons: Vec<String>,
+    pub copies: Vec<DuplicateCopy>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl DuplicateCluster {
+    /// Track IDs to merge into the keeper.
+    pub fn duplicate_ids(&self) -> impl Iterator<Item = &str> {
+        self.copies
+            .iter()
+            .filter(|copy| !copy.keep)
+            .map(|copy| copy.track_id.as_str())
+    }
+}
+
+/// Normalised `(artist, title)` key, or `None` when either is blank.
+fn title_key(track: &Track) -> Option<(String, String)> {
+    let collapse = |s: &str| {
+        normalize_for_matching(s)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let artist = collapse(&track.artist);
+    let mut title = collapse(&track.title);
+    for suffix in NEUTRAL_TITLE_SUFFIXES {
+        if let Some(stripped) = title.strip_suffix(suffix)
+            && !stripped.trim().is_empty()
+        {
+            title = stripped.trim_end().to_string();
+            break;
+        }
+    }
+    (!artist.is_empty() && !title.is_empty()).then_some((artist, title))
+}
+
+/// Tracks sharing a normalised artist/title with at least one other track.
+/// Used to narrow the library before loading keeper evidence.
+pub fn candidate_tracks(tracks: Vec<Track>) -> Vec<Track> {
+    let mut groups: HashMap<(String, String), Vec<Track>> = HashMap::new();
+    for track in tracks {
+        if let Some(key) = title_key(&track) {
+            groups.entry(key).or_default().push(track);
+        }
+    }
+    groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .flatten()
+        .collect()
+}
+
+fn is_lossless(kind: FileKind) -> bool {
+    matches!(kind, FileKind::Flac | FileKind::Wav | FileKind::Aiff)
+}
+
+/// Coarse audio quality: lossless, then lossy by bitrate.
+fn quality_tier(track: &Track) -> u8 {
+    match track.bit_rate {
+        _ if is_lossless(track.file_kind) => 3,
+        320.. => 2,
+        256.. => 1,
+        _ => 0,
+    }
+}
+
+/// Keeper ordering: quality first, then the copy carrying the most prep
+/// (cues, playlists, plays), then the earliest import.
+fn keeper_order(a: &LibraryCopy, b: &LibraryCopy) -> std::cmp::Ordering {
+    let rank = |c: &LibraryCopy| {
+        (
+            Reverse(quality_tier(&c.track)),
+            Reverse(c.cue_count),
+            Reverse(c.playlists.len()),
+            Reverse(c.track.play_count),
+        )
+    };
+    rank(a)
+        .cmp(&rank(b))
+        .then_with(|| a.track.date_added.cmp(&b.track.date_added))
+        .then_with(|| a.track.id.cmp(&b.track.id))
+}
+
+fn keep_reasons(keeper: &LibraryCopy, others: &[LibraryCopy]) -> Vec<String> {
+    let mut reasons = Vec::new();
+    let tier = quality_tier(&keeper.track);
+    if others.iter().all(|o| quality_tier(&o.track) < tier) {
+        reasons.push(if is_lossless(keeper.track.file_kind) {
+            format!("lossless ({})", keeper.track.file_kind.as_kind_str())
+        } else {
+            format!("highest bitrate ({} kbps)", keeper.track.bit_rate)
+        });
+    }
+    let mut most = |label: &str, value: usize, other: fn(&LibraryCopy) -> usize| {
+        if value > 0 && others.iter().all(|o| other(o) < value) {
+            reasons.push(format!("most {label} ({value})"));
+        }
+    };
+    most("cues", keeper.cue_count, |o| o.cue_count);
+    most("playlists", keeper.playlists.len(), |o| o.playlists.len());
+    most("plays", keeper.track.play_count.max(0) as usize, |o| {
+        o.track.play_count.max(0) as usize
+    });
+    if reasons.is_empty() {
+        reasons.push("earliest import".to_string());
+    }
+    reasons
+}
+
+fn to_copy(copy: &LibraryCopy, keep: bool) -> DuplicateCopy {
+    DuplicateCopy {
+        track_id: copy.track.id.clone(),
+        file_path: copy.track.file_path.clone(),
+        file_type: copy.track.file_kind,
+        bit_rate: copy.track.bit_rate,
+        length: copy.track.length,
+        play_count: copy.track.play_count,
+        cue_count: copy.cue_count,
+        playlists: copy.playlists.clone(),
+        date_added: copy.track.date_added.clone(),
+        keep,
+    }
+}
+
+fn build_cluster(mut members: Vec<LibraryCopy>) -> DuplicateCluster {
+    members.sort_by(keeper_order);
+    let (keeper, others) = members.split_first().expect("cluster has members");
+
+    let mut matched_on = vec!["artist_title"];
+    if members.iter().all(|c| c.track.length > 0) {
+        matched_on.push("duration");
+    }
+    // Fingerprints are the stronger evidence; tempo/key only fills in
+    // where a pair has no comparable fingerprints.
+    let comparisons: Vec<(bool, &'static str)> = others
+        .iter()
+        .filter_map(|other| {
+            let by_fingerprint = keeper
+                .fingerprint
+                .as_ref()
+                .zip(other.fingerprint.as_ref())
+                .and_then(|(a, b)| fingerprint::compare(a, b))
+                .map(|comparison| (comparison.is_match, "fingerprint"));
+            by_fingerprint.or_else(|| {
+                let (a, b) = (keeper.tempo_key.as_ref()?, other.tempo_key.as_ref()?);
+                Some((a.matches(b), "tempo_key"))
+            })
+        })
+        .collect();
+    let mismatched: Vec<&str> = comparisons
+        .iter()
+        .filter(|(matched, _)| !matched)
+        .map(|(_, via)| *via)
+        .collect();
+    let confidence = if !mismatched.is_empty() {
+        MatchConfidence::Low
+    } else if comparisons.len() == others.len() {
+        for via in ["fingerprint", "tempo_key"] {
+            if comparisons.iter().any(|(_, v)| *v == via) {
+                matched_on.push(via);
+            }
+        }
+        MatchConfidence::High
+    } else {
+        MatchConfidence::Medium
+    };
+
+    let mut warnings = Vec::new();
+    if mismatched.contains(&"fingerprint") {
+        warnings.push(
+            "Fingerprints differ; these are likely different recordings or versions".to_string(),
+        );
+    }
+    if mismatched.contains(&"tempo_key") {
+        warnings.push(
+            "Cached analysis disagrees on tempo or key; these may be different versions"
+                .to_string(),
+        );
+    }
+    for other in others.iter().filter(|o| o.cue_count > keeper.cue_count) {
+        warnings.push(format!(
+            "{} has {} cue(s) to the keeper's {}; cues are not merged",
+            other.track.id, other.cue_count, keeper.cue_count
+        ));
+    }
+
+    DuplicateCluster {
+        artist: keeper.track.artist.clone(),
+        title: keeper.track.title.clone(),
+        confidence,
+        matched_on,
+        keep: keeper.track.id.clone(),
+        keep_reasons: keep_reasons(keeper, others),
+        copies: std::iter::once(to_copy(keeper, true))
+            .chain(others.iter().map(|o| to_copy(o, false)))
+            .collect(),
+        warnings,
+    }
+}
+
+/// Cluster copies of the same recording. Copies sharing a normalised
+/// artist/title are split wherever their durations differ by more than the
+/// tolerance (an extended mix is not a duplicate of the radio edit); unknown
+/// durations join the first cluster. Clusters are sorted by artist and title.
+pub fn find_duplicates(copies: Vec<LibraryCopy>) -> Vec<DuplicateCluster> {
+    let mut groups: HashMap<(String, String), Vec<LibraryCopy>> = HashMap::new();
+    for copy in copies {
+        if let Some(key) = title_key(&copy.track) {
+            groups.entry(key).or_default().push(copy);
+        }
+    }
+
+    let mut clusters = Vec::new();
+    let mut keys: Vec<_> = groups.keys().cloned().collect();
+    keys.sort();
+    for key in keys {
+        let mut group = groups.remove(&key).unwrap_or_default();
+        group.sort_by_key(|c| c.track.length);
+        let (unknown, known): (Vec<_>, Vec<_>) =
+            group.into_iter().partition(|c| c.track.length <= 0);
+
+        let mut splits: Vec<Vec<LibraryCopy>> = Vec::new();
+        for copy in known {
+            match splits.last_mut() {
+                Some(current)
+                    if copy.track.length - current[0].track.length <= DURATION_TOLERANCE_SECS =>
+                {
+                    current.push(copy)
+                }
+                _ => splits.push(vec![copy]),
+            }
+        }
+        match splits.first_mut() {
+            Some(first) => first.extend(unknown),
+            None => splits.push(unknown),
+        }
+        clusters.extend(
+            splits
+                .into_iter()
+                .filter(|members| members.len() > 1)
+                .map(build_cluster),
+        );
+    }
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn copy(id: &str, title: &str, kind: FileKind, bit_rate: i32, length: i32) -> LibraryCopy {
+        LibraryCopy {
+            track: Track {
+                id: id.to_string(),
+                title: title.to_string(),
+                artist: "Burial".to_string(),
+                album: String::new(),
+                genre: String::new(),
+                bpm: 0.0,
+                key: String::new(),
+                rating: 0,
+                comments: String::new(),
+                color: String::new(),
+                color_code: 0,
+                label: String::new(),
+                remixer: String::new(),
+                year: 0,
+                length,
+                file_path: format!("/music/{id}"),
+                play_count: 0,
+                bit_rate,
+                sample_rate: 44100,
+                file_kind: kind,
+                date_added: "2024-01-01".to_string(),
+                my_tags: Vec::new(),
+                hot_cue_banks: Vec::new(),
+                related_track_lists: Vec::new(),
+                position: None,
+                cues: Vec::new(),
+                tempo_markers: Vec::new(),
+            },
+            playlists: Vec::new(),
+            cue_count: 0,
+            tempo_key: None,
+            fingerprint: None,
+        }
+    }
+
+    fn tempo_key(bpm: f64, key: &str) -> Option<TempoKeySignature> {
+        Some(TempoKeySignature {
+            bpm,
+            key_camelot: key.to_string(),
+        })
+    }
+
+    #[test]
+    fn lossless_copy_is_kept_and_tempo_key_match_is_high_confidence() {
+        let mut mp3 = copy("mp3", "Archangel", FileKind::Mp3, 320, 241);
+        mp3.cue_count = 2;
+        mp3.playlists = vec!["Deep".to_string()];
+        mp3.tempo_key = tempo_key(139.5, "8A");
+        let mut flac = copy("flac", "Archangel (Original Mix)", FileKind::Flac, 900, 240);
+        flac.tempo_key = tempo_key(69.8, "8A");
+
+        let clusters = find_duplicates(vec![mp3, flac]);
+        assert_eq!(clusters.len(), 1);
+        let cluster = &clusters[0];
+        assert_eq!(cluster.keep, "flac");
+        assert_eq!(cluster.confidence, MatchConfidence::High);
+        assert_eq!(
+            cluster.matched_on,
+            ["artist_title", "duration", "tempo_key"]
+        );
+        assert_eq!(cluster.keep_reasons, ["lossless (FLAC File)"]);
+        assert_eq!(cluster.duplicate_ids().collect::<Vec<_>>(), ["mp3"]);
+        assert_eq!(cluster.warnings.len(), 1, "cues on the MP3 are flagged");
+    }
+
+    #[test]
+    fn prep_breaks_ties_between_equal_quality_copies() {
+        let promo = copy("promo", "Archangel", FileKind::Mp3, 320, 240);
+        let mut retail = copy("retail", "Archangel", FileKind::Mp3, 320, 240);
+        retail.playlists = vec!["A".to_string(), "B".to_string()];
+
+        let clusters = find_duplicates(vec![promo, retail]);
+        assert_eq!(clusters[0].keep, "retail");
+        assert_eq!(clusters[0].keep_reasons, ["most playlists (2)"]);
+        assert_eq!(clusters[0].confidence, MatchConfidence::Medium);
+    }
+
+    #[test]
+    fn different_durations_and_titles_are_not_duplicates() {
+        let clusters = find_duplicates(vec![
+            copy("edit", "Archangel", FileKind::Mp3, 320, 240),
+            copy("extended", "Archangel", FileKind::Mp3, 320, 420),
+            copy("other", "Ghost Hardware", FileKind::Mp3, 320, 240),
+        ]);
+        assert!(clusters.is_empty());
+    }
+
+    #[test]
+    fn disagreeing_analysis_lowers_confidence() {
+        let mut a = copy("a", "Archangel", FileKind::Flac, 900, 240);
+        a.tempo_key = tempo_key(139.5, "8A");
+        let mut b = copy("b", "Archangel", FileKind::Mp3, 320, 240);
+        b.tempo_key = tempo_key(124.0, "3B");
+
+        let clusters = find_duplicates(vec![a, b]);
+        assert_eq!(clusters[0].confidence, MatchConfidence::Low);
+        assert!(!clusters[0].matched_on.contains(&"tempo_key"));
+    }
+
+    #[test]
+    fn fingerprints_take_precedence_over_tempo_and_key() {
+        let fp = |seed: u32| Fingerprint {
+            version: fingerprint::FINGERPRINT_VERSION.to_string(),
+            duration_seconds: 60.0,
+            items: (0..500u32)
+                .map(|i| (i ^ seed).wrapping_mul(2_654_435_761))
+                .collect(),
+        };
+        let mut a = copy("a", "Archangel", FileKind::Flac, 900, 240);
+        a.tempo_key = tempo_key(139.5, "8A");
+        a.fingerprint = Some(fp(0));
+        let mut b = copy("b", "Archangel", FileKind::Mp3, 320, 240);
+        b.tempo_key = tempo_key(124.0, "3B");
+        b.fingerprint = Some(fp(0));
+
+        let clusters = find_duplicates(vec![a.clone(), b.clone()]);
+        assert_eq!(clusters[0].confidence, MatchConfidence::High);
+        assert_eq!(
+            clusters[0].matched_on,
+            ["artist_title", "duration", "fingerprint"]
+        );
+
+        b.fingerprint = Some(fp(0x5a5a_5a5a));
+        b.tempo_key = a.tempo_key.clone();
+        let clusters = find_duplicates(vec![a, b]);
+        assert_eq!(clusters[0].confidence, MatchConfidence::Low);
+        assert!(clusters[0].warnings[0].starts_with("Fingerprints differ"));
+    }
+
+    #[test]
+    fn candidate_tracks_keeps_only_shared_titles() {
+        let tracks = vec![
+            copy("a", "Archangel", FileKind::Mp3, 320, 240).track,
+            copy("b", "archangel!", FileKind::Flac, 900, 240).track,
+            copy("c", "Ghost Hardware", FileKind::Mp3, 320, 300).track,
+        ];
+        let mut ids: Vec<String> = candidate_tracks(tracks).into_iter().map(|t| t.id).collect();
+        ids.sort();
+        assert_eq!(ids, ["a", "b"]);
+    }
+}
@@ -0,0 +1,450 @@
+//! Chromaprint-style acoustic fingerprints.
+//!
+//! Audio is resampled to 11025 Hz and cut into overlapping frames whose
+//! spectra are folded into 12 chroma (pitch class) bins. Sixteen Haar-like
+//! filters over the smoothed chroma image are each quantised to a 2-bit gray
+//! code at the filter's quartiles, giving one 32-bit sub-fingerprint per
+//! frame. Copies of the same recording differ in few bits across codecs and
+//! bitrates, so two fingerprints are compared by their bit error rate at the
+//! best alignment.
+
+use std::sync::Arc;
+
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+use crate::audio::{self, AudioError};
+
+/// Algorithm identifier stored with cached fingerprints.
+pub const FINGERPRINT_VERSION: &str = "chroma-1";
+/// Only the start of each file is fingerprinted, as `fpcalc` does by default.
+pub const MAX_FINGERPRINT_SECS: f64 = 120.0;
+/// At or below this bit error rate two fingerprints are the same recording.
+/// Unrelated audio sits near 0.5.
+pub const MATCH_MAX_BIT_ERROR_RATE: f64 = 0.25;
+
+const SAMPLE_RATE: u32 = 11025;
+const FRAME_SIZE: usize = 4096;
+const HOP_SIZE: usize = FRAME_SIZE / 3;
+const MIN_FREQ: f64 = 28.0;
+const MAX_FREQ: f64 = 3520.0;
+/// Time smoothing applied to the chroma sequence before filtering.
+const CHROMA_SMOOTHING: [f64; 5] = [0.25, 0.75, 1.0, 0.75, 0.25];
+/// Minimum aligned overlap for a comparison (about 5 seconds).
+const MIN_OVERLAP_ITEMS: usize = 40;
+/// Max alignment shift searched in either direction (about 10 seconds).
+const MAX_OFFSET_ITEMS: usize = 80;
+
+/// Seconds of audio per sub-fingerprint.
+pub const ITEM_DURATION_SECS: f64 = HOP_SIZE as f64 / SAMPLE_RATE as f64;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub version: String,
+    /// Length of the fingerprinted audio in seconds.
+    pub duration_seconds: f64,
+    /// One 32-bit sub-fingerprint per chroma frame.
+    pub items: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FingerprintComparison {
+    /// `1 - bit_error_rate`, rounded.
+    pub similarity: f64,
+    pub bit_error_rate: f64,
+    /// How much later the audio starts in the second fingerprint.
+    pub offset_seconds: f64,
+    pub overlap_seconds: f64,
+    #[serde(rename = "match")]
+    pub is_match: bool,
+}
+
+#[derive(Clone, Copy)]
+enum FilterKind {
+    /// Earlier half of the window against the later half.
+    Time,
+    /// Lower pitch classes against higher ones.
+    Pitch,
+    /// One diagonal of a 2x2 split against the other.
+    Checker,
+    /// Middle third in time against the outer thirds.
+    Centre,
+}
+
+/// `(kind, first chroma bin, bins, frames)`. Bins and frames divide evenly
+/// by the filter's split.
+const CLASSIFIERS: [(FilterKind, usize, usize, usize); 16] = [
+    (FilterKind::Time, 0, 4, 2),
+    (FilterKind::Time, 4, 4, 4),
+    (FilterKind::Time, 8, 4, 2),
+    (FilterKind::Time, 2, 6, 4),
+    (FilterKind::Pitch, 0, 4, 1),
+    (FilterKind::Pitch, 3, 4, 2),
+    (FilterKind::Pitch, 6, 4, 1),
+    (FilterKind::Pitch, 9, 2, 3),
+    (FilterKind::Checker, 1, 4, 2),
+    (FilterKind::Checker, 5, 4, 4),
+    (FilterKind::Checker, 7, 4, 2),
+    (FilterKind::Checker, 0, 6, 4),
+    (FilterKind::Centre, 0, 3, 3),
+    (FilterKind::Centre, 3, 3, 6),
+    (FilterKind::Centre, 6, 3, 3),
+    (FilterKind::Centre, 9, 3, 6),
+];
+const MAX_FILTER_FRAMES: usize = 6;
+
+/// Decode `path` and fingerprint its first `MAX_FINGERPRINT_SECS`.
+pub fn fingerprint_file(path: &str) -> Result<Fingerprint, AudioError> {
+    let (samples, sample_rate) =
+        audio::decode_window(path, audio::DecodeWindow::First(MAX_FINGERPRINT_SECS))?;
+    compute_fingerprint(&samples, sample_rate)
+}
+
+/// Fingerprint mono samples at any sample rate.
+pub fn compute_fingerprint(samples: &[f32], sample_rate: u32) -> Result<Fingerprint, AudioError> {
+    if sample_rate == 0 {
+        return Err(AudioError::Analysis(
+            "Sample rate must be non-zero".to_string(),
+        ));
+    }
+    let max_input = (MAX_FINGERPRINT_SECS * sample_rate as f64) as usize;
+    let input = &samples[..samples.len().min(max_input)];
+    let resampled = resample(input, sample_rate, SAMPLE_RATE);
+
+    let chroma = smooth(&chroma_frames(&resampled));
+    if chroma.len() < MAX_FILTER_FRAMES {
+        return Err(AudioError::Analysis(
+            "Audio too short to fingerprint".to_string(),
+        ));
+    }
+    let image = IntegralImage::new(&chroma);
+    let frames = chroma.len() - MAX_FILTER_FRAMES + 1;
+    let mut items = vec![0_u32; frames];
+    for classifier in 0..CLASSIFIERS.len() {
+        let responses: Vec<f64> = (0..frames)
+            .map(|frame| filter_response(&image, frame, classifier))
+            .collect();
+        let thresholds = quartiles(&responses);
+        for (item, response) in items.iter_mut().zip(responses) {
+            *item = (*item << 2) | gray_code(response, &thresholds);
+        }
+    }
+
+    Ok(Fingerprint {
+        version: FINGERPRINT_VERSION.to_string(),
+        duration_seconds: input.len() as f64 / sample_rate as f64,
+        items,
+    })
+}
+
+/// Resample by box-filtering then linear interpolation. Crude, but chroma
+/// only needs content below ~3.5 kHz.
+fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let ratio = from as f64 / to as f64;
+    let filtered: Vec<f32> = if ratio > 1.0 {
+        let width = ratio.ceil() as usize;
+        let mut sum = 0.0_f32;
+        samples
+            .iter()
+            .enumerate()
+            .map(|(i, &sample)| {
+                sum += sample;
+                if i >= width {
+                    sum -= samples[i - width];
+                }
+                sum / width.min(i + 1) as f32
+            })
+            .collect()
+    } else {
+        samples.to_vec()
+    };
+    let out_len = (samples.len() as f64 / ratio) as usize;
+    (0..out_len)
+        .map(|i| {
+            let pos = i as f64 * ratio;
+            let idx = pos as usize;
+            let frac = (pos - idx as f64) as f32;
+            let a = filtered[idx];
+            let b = filtered.get(idx + 1).copied().unwrap_or(a);
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+/// Energy per pitch class for each Hann-windowed frame.
+fn chroma_frames(samples: &[f32]) -> Vec<[f64; 12]> {
+    if samples.len() < FRAME_SIZE {
+        return Vec::new();
+    }
+    let fft: Arc<dyn Fft<f32>> = FftPlanner::new().plan_fft_forward(FRAME_SIZE);
+    let window: Vec<f32> = (0..FRAME_SIZE)
+        .map(|i| {
+            let phase = std::f32::consts::TAU * i as f32 / (FRAME_SIZE - 1) as f32;
+            0.5 - 0.5 * phase.cos()
+        })
+        .collect();
+    let bin_classes: Vec<Option<usize>> = (0..FRAME_SIZE / 2)
+        .map(|bin| {
+            let freq = bin as f64 * SAMPLE_RATE as f64 / FRAME_SIZE as f64;
+            (MIN_FREQ..=MAX_FREQ).contains(&freq).then(|| {
+                let note = 12.0 * (freq / 440.0).log2() + 69.0;
+                (note.round() as i64).rem_euclid(12) as usize
+            })
+        })
+        .collect();
+
+    let mut buffer = vec![Complex::new(0.0_f32, 0.0); FRAME_SIZE];
+    (0..=(samples.len() - FRAME_SIZE) / HOP_SIZE)
+        .map(|frame| {
+            let start = frame * HOP_SIZE;
+            for (slot, (&sample, &w)) in buffer
+                .iter_mut()
+                .zip(samples[start..start + FRAME_SIZE].iter().zip(&window))
+            {
+                *slot = Complex::new(sample * w, 0.0);
+            }
+            fft.process(&mut buffer);
+            let mut chroma = [0.0_f64; 12];
+            for (bin, class) in bin_classes.iter().enumerate() {
+                if let Some(class) = class {
+                    chroma[*class] += buffer[bin].norm_sqr() as f64;
+                }
+            }
+            chroma
+        })
+        .collect()
+}
+
+/// Smooth chroma over time, then L2-normalise each frame so loudness and
+/// mastering differences drop out. Near-silent frames become all zero.
+fn smooth(frames: &[[f64; 12]]) -> Vec<[f64; 12]> {
+    frames
+        .windows(CHROMA_SMOOTHING.len())
+        .map(|window| {
+            let mut out = [0.0; 12];
+            for (frame, weight) in window.iter().zip(CHROMA_SMOOTHING) {
+                for (acc, value) in out.iter_mut().zip(frame) {
+                    *acc += value * weight;
+                }
+            }
+            let norm = out.iter().map(|v| v * v).sum::<f64>().sqrt();
+            if norm < 0.01 {
+                [0.0; 12]
+            } else {
+                out.map(|v| v / norm)
+            }
+        })
+        .collect()
+}
+
+/// Summed-area table over (frame, chroma bin) for constant-time box sums.
+struct IntegralImage {
+    sums: Vec<[f64; 13]>,
+}
+
+impl IntegralImage {
+    fn new(chroma: &[[f64; 12]]) -> Self {
+        let mut sums = vec![[0.0; 13]; chroma.len() + 1];
+        for (t, frame) in chroma.iter().enumerate() {
+            let mut row_sum = 0.0;
+            for bin in 0..12 {
+                row_sum += frame[bin];
+                sums[t + 1][bin + 1] = sums[t][bin + 1] + row_sum;
+            }
+        }
+        Self { sums }
+    }
+
+    /// Sum over frames `t0..t1` and bins `y0..y1`.
+    fn area(&self, t0: usize, t1: usize, y0: usize, y1: usize) -> f64 {
+        self.sums[t1][y1] - self.sums[t0][y1] - self.sums[t1][y0] + self.sums[t0][y0]
+    }
+}
+
+fn filter_response(image: &IntegralImage, frame: usize, classifier: usize) -> f64 {
+    let (kind, y, bins, frames) = CLASSIFIERS[classifier];
+    let (t0, t1, y1) = (frame, frame + frames, y + bins);
+    let (a, b) = match kind {
+        FilterKind::Time => {
+            let mid = t0 + frames / 2;
+            (image.area(t0, mid, y, y1), image.area(mid, t1, y, y1))
+        }
+        FilterKind::Pitch => {
+            let mid = y + bins / 2;
+            (image.area(t0, t1, y, mid), image.area(t0, t1, mid, y1))
+        }
+        FilterKind::Checker => {
+            let (tm, ym) = (t0 + frames / 2, y + bins / 2);
+            (
+                image.area(t0, tm, y, ym) + image.area(tm, t1, ym, y1),
+                image.area(t0, tm, ym, y1) + image.area(tm, t1, y, ym),
+            )
+        }
+        FilterKind::Centre => {
+            let third = frames / 3;
+            let centre = image.area(t0 + third, t1 - third, y, y1);
+            (centre * 2.0, image.area(t0, t1, y, y1) - centre)
+        }
+    };
+    ((1.0 + a) / (1.0 + b)).ln()
+}
+
+/// 25th, 50th and 75th percentiles. Quantising at a filter's own quartiles
+/// keeps every bit balanced, so unrelated audio differs in about half of them.
+fn quartiles(values: &[f64]) -> [f64; 3] {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    [1, 2, 3].map(|q| sorted[(sorted.len() * q / 4).min(sorted.len() - 1)])
+}
+
+/// The response's quantisation level as a 2-bit gray code, so neighbouring
+/// levels differ in one bit.
+fn gray_code(response: f64, thresholds: &[f64; 3]) -> u32 {
+    match response {
+        r if r < thresholds[0] => 0b00,
+        r if r < thresholds[1] => 0b01,
+        r if r < thresholds[2] => 0b11,
+        _ => 0b10,
+    }
+}
+
+fn round3(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
+
+/// Compare two fingerprints at the alignment with the fewest differing bits.
+/// `None` when they cannot overlap by enough frames to judge.
+pub fn compare(a: &Fingerprint, b: &Fingerprint) -> Option<FingerprintComparison> {
+    let min_overlap = MIN_OVERLAP_ITEMS.min(a.items.len()).min(b.items.len());
+    if min_overlap == 0 {
+        return None;
+    }
+    let max_offset = MAX_OFFSET_ITEMS as isize;
+    let mut best: Option<(f64, isize, usize)> = None;
+    for offset in -max_offset..=max_offset {
+        // Item `i` of `a` lines up with item `i + offset` of `b`.
+        let start = offset.min(0).unsigned_abs();
+        let end = a
+            .items
+            .len()
+            .min((b.items.len() as isize - offset).max(0) as usize);
+        if end < start + min_overlap {
+            continue;
+        }
+        let errors: u32 = (start..end)
+            .map(|i| (a.items[i] ^ b.items[(i as isize + offset) as usize]).count_ones())
+            .sum();
+        let overlap = end - start;
+        let rate = errors as f64 / (overlap * 32) as f64;
+        if best.is_none_or(|(best_rate, _, _)| rate < best_rate) {
+            best = Some((rate, offset, overlap));
+        }
+    }
+    best.map(|(rate, offset, overlap)| FingerprintComparison {
+        similarity: round3(1.0 - rate),
+        bit_error_rate: round3(rate),
+        offset_seconds: round3(offset as f64 * ITEM_DURATION_SECS),
+        overlap_seconds: round3(overlap as f64 * ITEM_DURATION_SECS),
+        is_match: rate <= MATCH_MAX_BIT_ERROR_RATE,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A deterministic "song": a new three-note chord every half second.
+    fn synth(seconds: f64, sample_rate: u32, seed: u64) -> Vec<f32> {
+        let mut state = seed;
+        let mut next = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as usize
+        };
+        let segment = (sample_rate as f64 * 0.5) as usize;
+        let total = (seconds * sample_rate as f64) as usize;
+        let mut samples = Vec::with_capacity(total);
+        while samples.len() < total {
+            let root = 48 + next() % 24;
+            let notes = [root, root + 3 + next() % 2, root + 7];
+            for i in 0..segment.min(total - samples.len()) {
+                let t = i as f32 / sample_rate as f32;
+                let value: f32 = notes
+                    .iter()
+                    .map(|&n| {
+                        let freq = 440.0 * 2f32.powf((n as f32 - 69.0) / 12.0);
+                        (std::f32::consts::TAU * freq * t).sin()
+                    })
+                    .sum();
+                samples.push(value * 0.2);
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn same_audio_at_different_rates_matches() {
+        let a = compute_fingerprint(&synth(30.0, 44100, 7), 44100).unwrap();
+        let b = compute_fingerprint(&synth(30.0, 22050, 7), 22050).unwrap();
+        assert_eq!(a.version, FINGERPRINT_VERSION);
+        assert!((a.duration_seconds - 30.0).abs() < 0.01);
+        let comparison = compare(&a, &b).unwrap();
+        assert!(comparison.is_match, "{comparison:?}");
+        assert!(comparison.bit_error_rate < 0.1, "{comparison:?}");
+        assert_eq!(comparison.offset_seconds, 0.0);
+    }
+
+    #[test]
+    fn added_noise_still_matches() {
+        let clean = synth(30.0, 22050, 5);
+        let mut state = 99_u32;
+        let noisy: Vec<f32> = clean
+            .iter()
+            .map(|&x| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                x + (state as f32 / u32::MAX as f32 - 0.5) * 0.1
+            })
+            .collect();
+        let a = compute_fingerprint(&clean, 22050).unwrap();
+        let b = compute_fingerprint(&noisy, 22050).unwrap();
+        let comparison = compare(&a, &b).unwrap();
+        assert!(comparison.is_match, "{comparison:?}");
+    }
+
+    #[test]
+    fn different_audio_does_not_match() {
+        let a = compute_fingerprint(&synth(30.0, 22050, 1), 22050).unwrap();
+        let b = compute_fingerprint(&synth(30.0, 22050, 2), 22050).unwrap();
+        let comparison = compare(&a, &b).unwrap();
+        assert!(!comparison.is_match, "{comparison:?}");
+    }
+
+    #[test]
+    fn leading_silence_is_found_as_an_offset() {
+        let sample_rate = 22050;
+        let song = synth(30.0, sample_rate, 3);
+        let lead = 16 * HOP_SIZE * 2;
+        let delayed: Vec<f32> = std::iter::repeat_n(0.0, lead)
+            .chain(song.iter().copied())
+            .collect();
+        let a = compute_fingerprint(&song, sample_rate).unwrap();
+        let b = compute_fingerprint(&delayed, sample_rate).unwrap();
+        let comparison = compare(&a, &b).unwrap();
+        assert!(comparison.is_match, "{comparison:?}");
+        assert!(
+            (comparison.offset_seconds - 16.0 * ITEM_DURATION_SECS).abs() < 0.01,
+            "{comparison:?}"
+        );
+    }
+
+    #[test]
+    fn too_short_audio_is_rejected() {
+        assert!(compute_fingerprint(&[0.0; 1000], 44100).is_err());
+        assert!(compute_fingerprint(&[0.0; 1000], 0).is_err());
+    }
+}
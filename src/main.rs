@@ -1,3 +1,4 @@
+mod anlz;
 mod audio;
 mod audit;
 mod beatport;
@@ -7,15 +8,21 @@ mod color;
 mod corpus;
 mod db;
 mod discogs;
+mod duplicates;
 mod eval_routing;
 mod eval_tasks;
+mod fingerprint;
 mod genre;
 mod normalize;
+mod phrase;
+mod relocate;
+mod replaygain;
 mod store;
 mod tags;
 mod tools;
 mod types;
 mod xml;
+mod xml_import;
 
 use rmcp::ServiceExt;
 use rmcp::transport::stdio;
@@ -54,7 +61,13 @@ where
         let a = arg.as_ref();
         matches!(
             a,
-            "analyze" | "hydrate" | "read-tags" | "write-tags" | "extract-art" | "embed-art"
+            "analyze"
+                | "hydrate"
+                | "read-tags"
+                | "write-tags"
+                | "extract-art"
+                | "embed-art"
+                | "orphans"
         )
     })
 }
@@ -76,6 +89,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
             .init();
         let server = tools::ReklawdboxServer::new(db::resolve_db_path());
+        match server.restore_staging_session() {
+            Ok(0) => {}
+            Ok(restored) => tracing::info!("Restored {restored} staged change(s) from last session"),
+            Err(e) => tracing::warn!("Staged changes will not persist: {e}"),
+        }
         let service = server.serve(stdio()).await?;
         service.waiting().await?;
         Ok(())
@@ -123,6 +141,11 @@ mod tests {
         assert!(should_run_cli(vec!["reklawdbox", "embed-art"].into_iter()));
     }
 
+    #[test]
+    fn runs_cli_for_orphans_subcommand() {
+        assert!(should_run_cli(vec!["reklawdbox", "orphans"].into_iter()));
+    }
+
     #[test]
     fn runs_server_for_unrecognized_args() {
         assert!(!should_run_cli(
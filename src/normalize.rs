@@ -8,3 +8,14 @@ pub fn normalize_for_matching(input: &str) -> String {
         .trim()
         .to_string()
 }
+
+/// Canonical form of a file path for comparing paths from different sources:
+/// percent-escapes are decoded and the result is NFC-composed, so a path
+/// Rekordbox stored decomposed (as macOS filesystems report it) matches the
+/// same path read back composed.
+pub fn normalize_path_for_matching(path: &str) -> String {
+    let decoded = percent_encoding::percent_decode_str(path).decode_utf8_lossy();
+    icu_normalizer::ComposingNormalizerBorrowed::new_nfc()
+        .normalize(&decoded)
+        .into_owned()
+}
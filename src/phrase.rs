@@ -0,0 +1,427 @@
+//! Phrase segmentation on the beat grid.
+//!
+//! Dance music is built from 8-bar phrases grouped into 16- and 32-bar
+//! sections. Candidate boundaries sit every 8 bars from the first downbeat;
+//! one is kept where the novelty curve — the largest change in mean overall,
+//! bass or treble level between the four bars either side — jumps, and
+//! sections longer than 32 bars are split at their most novel candidate.
+//! Sections are then labelled from their level against the track's
+//! full-energy bars and their trend: quiet sections before the first full
+//! one are the intro and after the last one the outro, bass-less sections in
+//! between are breaks (or builds when they rise into a full section), and a
+//! full section after a break is a drop.
+//!
+//! The same sections drive memory-cue suggestions for the XML export and the
+//! phrase-alignment check between two tracks in a transition.
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio::{self, BarGrid, BarLevels};
+use crate::types::CuePoint;
+
+/// Bars per phrase; section boundaries fall on multiples of this.
+pub const PHRASE_BARS: usize = 8;
+/// Longest section before it is split at its most novel phrase boundary.
+pub const MAX_SECTION_BARS: usize = 32;
+/// Bars averaged either side of a candidate boundary.
+const NOVELTY_WINDOW_BARS: usize = 4;
+/// A boundary needs a level change of at least this much in some band.
+const MIN_BOUNDARY_NOVELTY_DB: f64 = 3.0;
+/// Quantisation of novelty when choosing where to split a long section, so
+/// near-equal candidates fall back to the 16/32-bar grid.
+const SPLIT_NOVELTY_STEP_DB: f64 = 1.0;
+/// Level gain from the first to the second half of a section that makes a
+/// bass-less section a build rather than a break.
+const BUILD_RISE_DB: f64 = 3.0;
+/// Share of boundaries that must coincide for two tracks' phrases to line up.
+const ALIGNED_MIN_SCORE: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PhraseLabel {
+    Intro,
+    Groove,
+    Build,
+    Drop,
+    Break,
+    Outro,
+}
+
+impl PhraseLabel {
+    fn display_name(self) -> &'static str {
+        match self {
+            Self::Intro => "Intro",
+            Self::Groove => "Groove",
+            Self::Build => "Build",
+            Self::Drop => "Drop",
+            Self::Break => "Break",
+            Self::Outro => "Outro",
+        }
+    }
+}
+
+/// A labelled run of whole phrases. Bars are zero-based from the first downbeat.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhraseSection {
+    pub label: PhraseLabel,
+    pub start_bar: usize,
+    pub bars: usize,
+    pub start_sec: f64,
+}
+
+impl PhraseSection {
+    fn end_bar(&self) -> usize {
+        self.start_bar + self.bars
+    }
+}
+
+/// How well the outgoing track's closing phrases line up with the incoming
+/// track's opening phrases when the incoming track starts on the outgoing
+/// track's mix-out phrase.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PhraseAlignment {
+    /// Bars from the outgoing mix-out phrase to the end of the outgoing track.
+    pub blend_bars: usize,
+    /// Section boundaries inside the blend that both tracks share.
+    pub shared_boundaries: usize,
+    /// Section boundaries inside the blend in either track.
+    pub total_boundaries: usize,
+    /// `shared_boundaries / total_boundaries`.
+    pub score: f64,
+    pub aligned: bool,
+}
+
+/// Split the bars of `grid` into labelled phrase sections.
+pub(crate) fn segment_phrases(levels: &[BarLevels], grid: &BarGrid) -> Vec<PhraseSection> {
+    if levels.is_empty() {
+        return Vec::new();
+    }
+    let mut edges = vec![0];
+    edges.extend(section_boundaries(levels));
+    edges.push(levels.len());
+    let labels = label_sections(levels, &edges);
+    edges
+        .windows(2)
+        .zip(labels)
+        .map(|(edge, label)| PhraseSection {
+            label,
+            start_bar: edge[0],
+            bars: edge[1] - edge[0],
+            start_sec: grid.bar_start_sec(edge[0]),
+        })
+        .collect()
+}
+
+/// Largest change in mean band level between the windows either side of `bar`.
+fn novelty_at(levels: &[BarLevels], bar: usize) -> f64 {
+    let before = &levels[bar.saturating_sub(NOVELTY_WINDOW_BARS)..bar];
+    let after = &levels[bar..(bar + NOVELTY_WINDOW_BARS).min(levels.len())];
+    if before.is_empty() || after.is_empty() {
+        return 0.0;
+    }
+    let bands: [fn(&BarLevels) -> f64; 3] = [|l| l.total_db, |l| l.low_db, |l| l.high_db];
+    bands
+        .iter()
+        .map(|band| (mean_of(after, band) - mean_of(before, band)).abs())
+        .fold(0.0, f64::max)
+}
+
+fn mean_of(levels: &[BarLevels], band: impl Fn(&BarLevels) -> f64) -> f64 {
+    levels.iter().map(band).sum::<f64>() / levels.len().max(1) as f64
+}
+
+/// Interior section boundaries, in ascending order.
+fn section_boundaries(levels: &[BarLevels]) -> Vec<usize> {
+    let total_bars = levels.len();
+    let candidates: Vec<(usize, f64)> = (PHRASE_BARS..total_bars)
+        .step_by(PHRASE_BARS)
+        .map(|bar| (bar, novelty_at(levels, bar)))
+        .collect();
+    let mut kept: Vec<usize> = candidates
+        .iter()
+        .filter(|(_, novelty)| *novelty >= MIN_BOUNDARY_NOVELTY_DB)
+        .map(|&(bar, _)| bar)
+        .collect();
+
+    loop {
+        let mut edges = vec![0];
+        edges.extend(&kept);
+        edges.push(total_bars);
+        let Some((start, end)) = edges
+            .windows(2)
+            .map(|edge| (edge[0], edge[1]))
+            .find(|(start, end)| end - start > MAX_SECTION_BARS)
+        else {
+            break;
+        };
+        // Most novel first; among near-equals prefer the 16-bar grid, then
+        // the split that keeps the first part at 32 bars or under and longest.
+        let split = candidates
+            .iter()
+            .filter(|(bar, _)| *bar > start && *bar < end)
+            .max_by_key(|&&(bar, novelty)| {
+                let offset = bar - start;
+                (
+                    (novelty / SPLIT_NOVELTY_STEP_DB).floor() as i64,
+                    offset % (PHRASE_BARS * 2) == 0,
+                    offset <= MAX_SECTION_BARS,
+                    offset,
+                )
+            })
+            .map(|&(bar, _)| bar);
+        match split {
+            Some(bar) => {
+                kept.push(bar);
+                kept.sort_unstable();
+            }
+            None => break,
+        }
+    }
+    kept
+}
+
+fn label_sections(levels: &[BarLevels], edges: &[usize]) -> Vec<PhraseLabel> {
+    let total_db: Vec<f64> = levels.iter().map(|l| l.total_db).collect();
+    let low_db: Vec<f64> = levels.iter().map(|l| l.low_db).collect();
+    let total_reference = audio::percentile(&total_db, audio::STRUCTURE_REFERENCE_PERCENTILE);
+    let low_reference = audio::percentile(&low_db, audio::STRUCTURE_REFERENCE_PERCENTILE);
+
+    let sections: Vec<&[BarLevels]> = edges
+        .windows(2)
+        .map(|edge| &levels[edge[0]..edge[1]])
+        .collect();
+    let full: Vec<bool> = sections
+        .iter()
+        .map(|bars| {
+            mean_of(bars, |l| l.total_db) >= total_reference - audio::FULL_ENERGY_DROP_DB
+                && mean_of(bars, |l| l.low_db) >= low_reference - audio::LOW_BAND_DROP_DB
+        })
+        .collect();
+    let (Some(first_full), Some(last_full)) =
+        (full.iter().position(|&f| f), full.iter().rposition(|&f| f))
+    else {
+        return vec![PhraseLabel::Groove; sections.len()];
+    };
+
+    let mut labels: Vec<PhraseLabel> = Vec::with_capacity(sections.len());
+    for (index, bars) in sections.iter().enumerate() {
+        let label = if index < first_full {
+            PhraseLabel::Intro
+        } else if index > last_full {
+            PhraseLabel::Outro
+        } else if full[index] {
+            match labels.last() {
+                Some(PhraseLabel::Break | PhraseLabel::Build) => PhraseLabel::Drop,
+                _ => PhraseLabel::Groove,
+            }
+        } else if rise_db(bars) >= BUILD_RISE_DB && full.get(index + 1) == Some(&true) {
+            PhraseLabel::Build
+        } else {
+            PhraseLabel::Break
+        };
+        labels.push(label);
+    }
+    labels
+}
+
+/// Level gain from the first to the second half of a section, overall or in
+/// the treble, whichever rises more.
+fn rise_db(bars: &[BarLevels]) -> f64 {
+    if bars.len() < 2 {
+        return 0.0;
+    }
+    let (first, second) = bars.split_at(bars.len() / 2);
+    let total = mean_of(second, |l| l.total_db) - mean_of(first, |l| l.total_db);
+    let high = mean_of(second, |l| l.high_db) - mean_of(first, |l| l.high_db);
+    total.max(high)
+}
+
+/// Memory cues at the start of each section, named after its label and length.
+pub fn phrase_cues(sections: &[PhraseSection]) -> Vec<CuePoint> {
+    sections
+        .iter()
+        .map(|section| CuePoint {
+            name: format!("{} ({} bars)", section.label.display_name(), section.bars),
+            start: section.start_sec,
+            end: None,
+            hot_cue: None,
+        })
+        .collect()
+}
+
+/// Compare phrase boundaries across a blend that starts `from`'s outro (or
+/// its last section) on `to`'s first downbeat. `None` when either track has
+/// no sections.
+pub fn phrase_alignment(from: &[PhraseSection], to: &[PhraseSection]) -> Option<PhraseAlignment> {
+    let last = from.last()?;
+    to.first()?;
+    let mix_out_bar = from
+        .iter()
+        .find(|section| section.label == PhraseLabel::Outro)
+        .unwrap_or(last)
+        .start_bar;
+    let blend_bars = last.end_bar() - mix_out_bar;
+
+    let from_boundaries: Vec<usize> = from
+        .iter()
+        .map(PhraseSection::end_bar)
+        .filter(|&bar| bar > mix_out_bar)
+        .map(|bar| bar - mix_out_bar)
+        .collect();
+    let to_boundaries: Vec<usize> = to
+        .iter()
+        .map(PhraseSection::end_bar)
+        .filter(|&bar| bar <= blend_bars)
+        .collect();
+    let shared_boundaries = from_boundaries
+        .iter()
+        .filter(|bar| to_boundaries.contains(bar))
+        .count();
+    let total_boundaries = from_boundaries.len() + to_boundaries.len() - shared_boundaries;
+    let score = if total_boundaries == 0 {
+        1.0
+    } else {
+        shared_boundaries as f64 / total_boundaries as f64
+    };
+    Some(PhraseAlignment {
+        blend_bars,
+        shared_boundaries,
+        total_boundaries,
+        score: (score * 1000.0).round() / 1000.0,
+        aligned: score >= ALIGNED_MIN_SCORE,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(total_bars: usize) -> BarGrid {
+        BarGrid {
+            first_downbeat_sec: 0.5,
+            bar_seconds: 2.0,
+            total_bars,
+        }
+    }
+
+    /// Bars from `(count, total_db, low_db, high_db)` runs.
+    fn bars(runs: &[(usize, f64, f64, f64)]) -> Vec<BarLevels> {
+        runs.iter()
+            .flat_map(|&(count, total_db, low_db, high_db)| {
+                std::iter::repeat_n(
+                    BarLevels {
+                        total_db,
+                        low_db,
+                        high_db,
+                    },
+                    count,
+                )
+            })
+            .collect()
+    }
+
+    fn section(label: PhraseLabel, start_bar: usize, bars: usize) -> PhraseSection {
+        PhraseSection {
+            label,
+            start_bar,
+            bars,
+            start_sec: 0.0,
+        }
+    }
+
+    fn summary(sections: &[PhraseSection]) -> Vec<(PhraseLabel, usize, usize)> {
+        sections
+            .iter()
+            .map(|s| (s.label, s.start_bar, s.bars))
+            .collect()
+    }
+
+    #[test]
+    fn labels_a_full_arrangement_on_phrase_boundaries() {
+        let mut levels = bars(&[(16, -24.0, -40.0, -30.0), (32, -12.0, -15.0, -28.0)]);
+        // An 8-bar break, then an 8-bar riser into the drop.
+        levels.extend(bars(&[(8, -16.0, -35.0, -40.0)]));
+        levels.extend((0..8).map(|bar| BarLevels {
+            total_db: -18.0 + bar as f64,
+            low_db: -35.0,
+            high_db: -32.0 + 2.0 * bar as f64,
+        }));
+        levels.extend(bars(&[
+            (32, -12.0, -15.0, -28.0),
+            (16, -24.0, -40.0, -30.0),
+        ]));
+
+        let sections = segment_phrases(&levels, &grid(levels.len()));
+        assert_eq!(
+            summary(&sections),
+            vec![
+                (PhraseLabel::Intro, 0, 16),
+                (PhraseLabel::Groove, 16, 32),
+                (PhraseLabel::Break, 48, 8),
+                (PhraseLabel::Build, 56, 8),
+                (PhraseLabel::Drop, 64, 32),
+                (PhraseLabel::Outro, 96, 16),
+            ]
+        );
+        assert_eq!(sections[2].start_sec, 0.5 + 48.0 * 2.0);
+    }
+
+    #[test]
+    fn long_flat_sections_split_on_the_32_bar_grid() {
+        let levels = bars(&[(64, -12.0, -15.0, -28.0)]);
+        let sections = segment_phrases(&levels, &grid(64));
+        assert_eq!(
+            summary(&sections),
+            vec![(PhraseLabel::Groove, 0, 32), (PhraseLabel::Groove, 32, 32)]
+        );
+    }
+
+    #[test]
+    fn phrase_cues_are_memory_cues_at_section_starts() {
+        let cues = phrase_cues(&[
+            PhraseSection {
+                start_sec: 0.5,
+                ..section(PhraseLabel::Intro, 0, 16)
+            },
+            PhraseSection {
+                start_sec: 32.5,
+                ..section(PhraseLabel::Groove, 16, 32)
+            },
+        ]);
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].name, "Intro (16 bars)");
+        assert_eq!(cues[1].start, 32.5);
+        assert!(cues.iter().all(|c| c.hot_cue.is_none() && c.end.is_none()));
+    }
+
+    #[test]
+    fn phrase_alignment_matches_outro_against_intro() {
+        let from = [
+            section(PhraseLabel::Groove, 0, 64),
+            section(PhraseLabel::Outro, 64, 16),
+        ];
+        let matching = [
+            section(PhraseLabel::Intro, 0, 16),
+            section(PhraseLabel::Groove, 16, 32),
+        ];
+        let aligned = phrase_alignment(&from, &matching).unwrap();
+        assert_eq!(aligned.blend_bars, 16);
+        assert_eq!(
+            (aligned.shared_boundaries, aligned.total_boundaries),
+            (1, 1)
+        );
+        assert!(aligned.aligned);
+
+        let offset = [
+            section(PhraseLabel::Intro, 0, 8),
+            section(PhraseLabel::Build, 8, 4),
+            section(PhraseLabel::Drop, 12, 32),
+        ];
+        let misaligned = phrase_alignment(&from, &offset).unwrap();
+        assert_eq!(misaligned.shared_boundaries, 0);
+        assert_eq!(misaligned.score, 0.0);
+        assert!(!misaligned.aligned);
+
+        assert!(phrase_alignment(&from, &[]).is_none());
+    }
+}
@@ -0,0 +1,463 @@
+//! Missing-file relocation: match library tracks whose file no longer exists
+//! against audio files found under new root directories.
+//!
+//! Candidates are shortlisted by file name and exact file size, then scored on
+//! name, size, duration, title/artist tags and — when the missing file was
+//! fingerprinted before it moved — its acoustic fingerprint. Nothing is
+//! written here; the resulting plan is reviewed and staged as `file_path`
+//! changes, which `write_xml` exports as corrected `Location` URIs.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use lofty::file::AudioFile;
+use lofty::probe::Probe;
+use serde::Serialize;
+
+use crate::audio;
+use crate::audit;
+use crate::fingerprint::{self, Fingerprint};
+use crate::normalize::normalize_for_matching;
+use crate::tags::{self, FileReadResult};
+use crate::types::{MatchConfidence, Track};
+use crate::xml::path_to_rekordbox_location_uri;
+
+// Scores are the matched share of the weight that could be checked, so they
+// stay in 0..=1 whether or not a fingerprint comparison was possible.
+const NAME_WEIGHT: f64 = 0.35;
+/// Same name with a different extension (e.g. a re-encoded file).
+const STEM_WEIGHT: f64 = 0.2;
+const SIZE_WEIGHT: f64 = 0.35;
+const DURATION_WEIGHT: f64 = 0.15;
+const TAGS_WEIGHT: f64 = 0.15;
+const FINGERPRINT_WEIGHT: f64 = 0.35;
+/// Weight available without a fingerprint comparison.
+const BASE_WEIGHT: f64 = NAME_WEIGHT + SIZE_WEIGHT + DURATION_WEIGHT + TAGS_WEIGHT;
+/// Max difference between the library `Length` and the candidate's duration.
+const DURATION_TOLERANCE_SECS: f64 = 1.5;
+/// Minimum score for a candidate to be rated high confidence.
+const HIGH_CONFIDENCE_SCORE: f64 = 0.7;
+/// A high-confidence top candidate must lead the runner-up by at least this much.
+const HIGH_CONFIDENCE_MARGIN: f64 = 0.15;
+const MEDIUM_CONFIDENCE_SCORE: f64 = 0.45;
+
+/// True when the track's file cannot be found at its recorded path (raw or
+/// percent-decoded).
+pub fn is_missing(file_path: &str) -> bool {
+    audio::resolve_audio_path(file_path).is_err()
+}
+
+struct IndexedFile {
+    path: PathBuf,
+    size: u64,
+}
+
+/// Audio files under the search roots, keyed for shortlisting.
+#[derive(Default)]
+pub struct CandidateIndex {
+    files: Vec<IndexedFile>,
+    by_name: HashMap<String, Vec<usize>>,
+    by_stem: HashMap<String, Vec<usize>>,
+    by_size: HashMap<u64, Vec<usize>>,
+    /// Unreadable directories or entries encountered while walking.
+    pub warnings: Vec<String>,
+}
+
+impl CandidateIndex {
+    /// Walk each root recursively, indexing every audio file.
+    pub fn build(roots: &[PathBuf]) -> Result<Self, String> {
+        let mut index = Self::default();
+        let mut seen = HashSet::new();
+        for root in roots {
+            let walk = audit::walk_audio_files(root)?;
+            index.warnings.extend(walk.warnings);
+            for path in walk.files {
+                if !seen.insert(path.clone()) {
+                    continue;
+                }
+                let size = match std::fs::metadata(&path) {
+                    Ok(meta) => meta.len(),
+                    Err(e) => {
+                        index
+                            .warnings
+                            .push(format!("Cannot stat {}: {e}", path.display()));
+                        continue;
+                    }
+                };
+                index.insert(path, size);
+            }
+        }
+        Ok(index)
+    }
+
+    fn insert(&mut self, path: PathBuf, size: u64) {
+        let idx = self.files.len();
+        if let Some(name) = file_name_key(&path) {
+            self.by_name.entry(name).or_default().push(idx);
+        }
+        if let Some(stem) = file_stem_key(&path) {
+            self.by_stem.entry(stem).or_default().push(idx);
+        }
+        self.by_size.entry(size).or_default().push(idx);
+        self.files.push(IndexedFile { path, size });
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Paths of the files `plan_relocation` would score for the track, so
+    /// their fingerprints can be loaded beforehand.
+    pub fn shortlisted_paths(&self, track: &Track, file_size: Option<u64>) -> Vec<String> {
+        self.shortlist(Path::new(&track.file_path), file_size)
+            .into_iter()
+            .map(|idx| self.files[idx].path.to_string_lossy().to_string())
+            .collect()
+    }
+
+    /// Indexed files sharing the track's file name, stem or exact size.
+    fn shortlist(&self, old_path: &Path, file_size: Option<u64>) -> Vec<usize> {
+        let mut ids: Vec<usize> = Vec::new();
+        if let Some(name) = file_name_key(old_path) {
+            ids.extend(self.by_name.get(&name).into_iter().flatten());
+        }
+        if let Some(stem) = file_stem_key(old_path) {
+            ids.extend(self.by_stem.get(&stem).into_iter().flatten());
+        }
+        if let Some(size) = file_size {
+            ids.extend(self.by_size.get(&size).into_iter().flatten());
+        }
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+}
+
+fn file_name_key(path: &Path) -> Option<String> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(str::to_lowercase)
+}
+
+fn file_stem_key(path: &Path) -> Option<String> {
+    path.file_stem()
+        .and_then(|n| n.to_str())
+        .map(str::to_lowercase)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RelocationCandidate {
+    pub path: String,
+    /// Rekordbox XML `Location` for `path`.
+    pub location: String,
+    pub score: f64,
+    /// Signals that matched: `file_name`, `file_stem`, `file_size`, `duration`,
+    /// `tags`, `fingerprint`.
+    pub evidence: Vec<&'static str>,
+}
+
+/// One missing track and its ranked relocation candidates.
+#[derive(Debug, Clone, Serialize)]
+pub struct RelocationEntry {
+    pub track_id: String,
+    pub title: String,
+    pub artist: String,
+    pub old_path: String,
+    /// Confidence in the top candidate; `None` when nothing matched.
+    pub confidence: Option<MatchConfidence>,
+    pub candidates: Vec<RelocationCandidate>,
+}
+
+impl RelocationEntry {
+    /// The top candidate's path when it is a high-confidence match.
+    pub fn confident_path(&self) -> Option<&str> {
+        match self.confidence {
+            Some(MatchConfidence::High) => self.candidates.first().map(|c| c.path.as_str()),
+            _ => None,
+        }
+    }
+}
+
+fn read_duration_secs(path: &Path) -> Option<f64> {
+    let tagged_file = Probe::open(path).ok()?.read().ok()?;
+    Some(tagged_file.properties().duration().as_secs_f64())
+}
+
+fn read_title_artist(path: &Path) -> (Option<String>, Option<String>) {
+    let fields = ["title".to_string(), "artist".to_string()];
+    let tags = match tags::read_file_tags(path, Some(&fields), false) {
+        FileReadResult::Single { tags, .. } => tags,
+        FileReadResult::Wav { id3v2, .. } => id3v2,
+        FileReadResult::Error { .. } => return (None, None),
+    };
+    let get = |field: &str| tags.get(field).cloned().flatten();
+    (get("title"), get("artist"))
+}
+
+fn score_candidate(
+    track: &Track,
+    file_size: Option<u64>,
+    known_fingerprint: Option<&Fingerprint>,
+    candidate_fingerprint: Option<&Fingerprint>,
+    file: &IndexedFile,
+) -> (f64, Vec<&'static str>) {
+    let old_path = Path::new(&track.file_path);
+    let mut score = 0.0;
+    let mut available = BASE_WEIGHT;
+    let mut evidence = Vec::new();
+
+    if file_name_key(old_path).is_some() && file_name_key(old_path) == file_name_key(&file.path) {
+        score += NAME_WEIGHT;
+        evidence.push("file_name");
+    } else if file_stem_key(old_path).is_some()
+        && file_stem_key(old_path) == file_stem_key(&file.path)
+    {
+        score += STEM_WEIGHT;
+        evidence.push("file_stem");
+    }
+
+    if file_size == Some(file.size) {
+        score += SIZE_WEIGHT;
+        evidence.push("file_size");
+    }
+
+    if track.length > 0
+        && read_duration_secs(&file.path)
+            .is_some_and(|d| (d - f64::from(track.length)).abs() <= DURATION_TOLERANCE_SECS)
+    {
+        score += DURATION_WEIGHT;
+        evidence.push("duration");
+    }
+
+    let (title, artist) = read_title_artist(&file.path);
+    let matches = |tag: Option<String>, library: &str| {
+        let library = normalize_for_matching(library);
+        !library.is_empty() && tag.is_some_and(|t| normalize_for_matching(&t) == library)
+    };
+    if matches(title, &track.title) && matches(artist, &track.artist) {
+        score += TAGS_WEIGHT;
+        evidence.push("tags");
+    }
+
+    if let Some(comparison) = known_fingerprint
+        .zip(candidate_fingerprint)
+        .and_then(|(known, candidate)| fingerprint::compare(known, candidate))
+    {
+        available += FINGERPRINT_WEIGHT;
+        if comparison.is_match {
+            score += FINGERPRINT_WEIGHT;
+            evidence.push("fingerprint");
+        }
+    }
+
+    (score / available, evidence)
+}
+
+fn round_score(score: f64) -> f64 {
+    (score * 1000.0).round() / 1000.0
+}
+
+/// Rank relocation candidates for a missing track. `file_size` is the
+/// library's recorded size in bytes, when known; `known_fingerprint` is the
+/// fingerprint cached for the old path, when there is one, and
+/// `candidate_fingerprints` holds fingerprints of shortlisted files by path.
+pub fn plan_relocation(
+    track: &Track,
+    file_size: Option<u64>,
+    known_fingerprint: Option<&Fingerprint>,
+    candidate_fingerprints: &HashMap<String, Fingerprint>,
+    index: &CandidateIndex,
+    max_candidates: usize,
+) -> RelocationEntry {
+    let mut candidates: Vec<RelocationCandidate> = index
+        .shortlist(Path::new(&track.file_path), file_size)
+        .into_iter()
+        .map(|idx| {
+            let file = &index.files[idx];
+            let path = file.path.to_string_lossy().to_string();
+            let (score, evidence) = score_candidate(
+                track,
+                file_size,
+                known_fingerprint,
+                candidate_fingerprints.get(&path),
+                file,
+            );
+            RelocationCandidate {
+                location: path_to_rekordbox_location_uri(&path),
+                path,
+                score: round_score(score),
+                evidence,
+            }
+        })
+        .collect();
+    candidates.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+
+    let confidence = candidates.first().map(|top| {
+        let runner_up = candidates.get(1).map_or(0.0, |c| c.score);
+        if top.score >= HIGH_CONFIDENCE_SCORE && top.score - runner_up >= HIGH_CONFIDENCE_MARGIN {
+            MatchConfidence::High
+        } else if top.score >= MEDIUM_CONFIDENCE_SCORE {
+            MatchConfidence::Medium
+        } else {
+            MatchConfidence::Low
+        }
+    });
+    candidates.truncate(max_candidates);
+
+    RelocationEntry {
+        track_id: track.id.clone(),
+        title: track.title.clone(),
+        artist: track.artist.clone(),
+        old_path: track.file_path.clone(),
+        confidence,
+        candidates,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FileKind;
+
+    fn make_track(path: &str, length: i32) -> Track {
+        Track {
+            id: "t1".to_string(),
+            title: "Archangel".to_string(),
+            artist: "Burial".to_string(),
+            album: String::new(),
+            genre: String::new(),
+            bpm: 0.0,
+            key: String::new(),
+            rating: 0,
+            comments: String::new(),
+            color: String::new(),
+            color_code: 0,
+            label: String::new(),
+            remixer: String::new(),
+            year: 0,
+            length,
+            file_path: path.to_string(),
+            play_count: 0,
+            bit_rate: 0,
+            sample_rate: 0,
+            file_kind: FileKind::Flac,
+            date_added: String::new(),
+            my_tags: Vec::new(),
+            hot_cue_banks: Vec::new(),
+            related_track_lists: Vec::new(),
+            position: None,
+            cues: Vec::new(),
+            tempo_markers: Vec::new(),
+        }
+    }
+
+    fn write_file(dir: &Path, rel: &str, bytes: usize) -> PathBuf {
+        let path = dir.join(rel);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, vec![0u8; bytes]).unwrap();
+        path
+    }
+
+    #[test]
+    fn name_and_size_match_is_high_confidence() {
+        let dir = tempfile::tempdir().unwrap();
+        let moved = write_file(dir.path(), "Burial/01 Archangel.flac", 64);
+        write_file(dir.path(), "Other/01 Archangel.flac", 32);
+        write_file(dir.path(), "Other/unrelated.mp3", 16);
+
+        let index = CandidateIndex::build(&[dir.path().to_path_buf()]).unwrap();
+        assert_eq!(index.len(), 3);
+        let track = make_track("/Volumes/Old/Burial/01 Archangel.flac", 0);
+        let entry = plan_relocation(&track, Some(64), None, &HashMap::new(), &index, 3);
+
+        assert_eq!(entry.confidence, Some(MatchConfidence::High));
+        assert_eq!(entry.candidates.len(), 2);
+        assert_eq!(entry.candidates[0].path, moved.to_string_lossy());
+        assert_eq!(entry.candidates[0].evidence, ["file_name", "file_size"]);
+        assert!(
+            entry.candidates[0]
+                .location
+                .starts_with("file://localhost/")
+        );
+        assert_eq!(entry.confident_path(), Some(moved.to_str().unwrap()));
+    }
+
+    #[test]
+    fn ambiguous_name_only_matches_are_not_high_confidence() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "a/Track.flac", 10);
+        write_file(dir.path(), "b/track.flac", 20);
+        write_file(dir.path(), "c/Track.wav", 30);
+
+        let index = CandidateIndex::build(&[dir.path().to_path_buf()]).unwrap();
+        let track = make_track("/old/Track.flac", 0);
+        let entry = plan_relocation(&track, None, None, &HashMap::new(), &index, 2);
+
+        assert_eq!(entry.confidence, Some(MatchConfidence::Low));
+        assert_eq!(entry.candidates.len(), 2);
+        assert!(entry.candidates.iter().all(|c| c.evidence == ["file_name"]));
+        assert!(entry.confident_path().is_none());
+    }
+
+    #[test]
+    fn no_shortlisted_files_yields_no_candidates() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(dir.path(), "x.mp3", 10);
+        let index = CandidateIndex::build(&[dir.path().to_path_buf()]).unwrap();
+        let track = make_track("/old/gone.flac", 0);
+        let entry = plan_relocation(&track, Some(99), None, &HashMap::new(), &index, 3);
+        assert!(entry.confidence.is_none());
+        assert!(entry.candidates.is_empty());
+    }
+
+    #[test]
+    fn fingerprints_stay_within_unit_score_and_demote_mismatches() {
+        let fp = |seed: u32| Fingerprint {
+            version: fingerprint::FINGERPRINT_VERSION.to_string(),
+            duration_seconds: 60.0,
+            items: (0..500u32)
+                .map(|i| (i ^ seed).wrapping_mul(2_654_435_761))
+                .collect(),
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let moved = write_file(dir.path(), "Burial/01 Archangel.flac", 64);
+        let index = CandidateIndex::build(&[dir.path().to_path_buf()]).unwrap();
+        let track = make_track("/Volumes/Old/Burial/01 Archangel.flac", 0);
+        let moved = moved.to_string_lossy().to_string();
+        assert_eq!(
+            index.shortlisted_paths(&track, Some(64)),
+            std::slice::from_ref(&moved)
+        );
+
+        let matching = HashMap::from([(moved.clone(), fp(0))]);
+        let entry = plan_relocation(&track, Some(64), Some(&fp(0)), &matching, &index, 3);
+        let top = &entry.candidates[0];
+        assert_eq!(top.evidence, ["file_name", "file_size", "fingerprint"]);
+        assert_eq!(top.score, round_score(1.05 / 1.35));
+        assert_eq!(entry.confidence, Some(MatchConfidence::High));
+
+        let different = HashMap::from([(moved, fp(0x5a5a_5a5a))]);
+        let entry = plan_relocation(&track, Some(64), Some(&fp(0)), &different, &index, 3);
+        assert_eq!(entry.candidates[0].score, round_score(0.7 / 1.35));
+        assert_eq!(entry.confidence, Some(MatchConfidence::Medium));
+
+        // No candidate fingerprint: scored on the other signals alone.
+        let entry = plan_relocation(&track, Some(64), Some(&fp(0)), &HashMap::new(), &index, 3);
+        assert_eq!(entry.candidates[0].score, 0.7);
+    }
+
+    #[test]
+    fn is_missing_checks_raw_and_decoded_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(dir.path(), "my track.mp3", 1);
+        assert!(!is_missing(path.to_str().unwrap()));
+        let encoded = path.to_str().unwrap().replace(' ', "%20");
+        assert!(!is_missing(&encoded));
+        assert!(is_missing("/definitely/not/here.mp3"));
+    }
+}
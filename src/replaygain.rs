@@ -0,0 +1,286 @@
+//! Loudness normalisation: ReplayGain 2.0 track and album gain from
+//! integrated loudness, written as `REPLAYGAIN_*` tags.
+//!
+//! Loudness is the EBU R128 integrated value already computed by the Essentia
+//! or native analysis (`EssentiaOutput::loudness_integrated`) and peaks come
+//! from the signal-quality meter; this module only does the arithmetic and
+//! album grouping. A track belongs to an album when the audit would classify
+//! it as an album track, keyed by its album directory (climbing past disc
+//! subdirectories) and album tag. Loose tracks get track gain only.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use lofty::file::AudioFile;
+use lofty::probe::Probe;
+use serde::Serialize;
+
+use crate::audit::{self, AuditContext};
+use crate::tags::{self, FileReadResult};
+
+/// ReplayGain 2.0 reference level.
+pub const REFERENCE_LUFS: f64 = -18.0;
+
+/// Measured loudness of one audio file.
+#[derive(Debug, Clone)]
+pub struct TrackLoudness {
+    pub path: PathBuf,
+    pub loudness_lufs: f64,
+    /// Linear sample peak, 1.0 being full scale.
+    pub peak: f64,
+    pub duration_secs: f64,
+    pub album_tag: Option<String>,
+}
+
+/// Gain for one album: every selected track sharing an album directory and
+/// album tag.
+#[derive(Debug, Clone, Serialize)]
+pub struct AlbumGain {
+    pub album_dir: String,
+    pub album: Option<String>,
+    pub tracks: usize,
+    pub loudness_lufs: f64,
+    pub gain_db: f64,
+    pub peak: f64,
+}
+
+/// Gains to write to one file.
+#[derive(Debug, Clone, Serialize)]
+pub struct GainPlan {
+    pub path: String,
+    pub loudness_lufs: f64,
+    pub track_gain_db: f64,
+    pub track_peak: f64,
+    /// Index into the album list; `None` for loose tracks.
+    #[serde(skip)]
+    pub album: Option<usize>,
+}
+
+/// Gain that brings `loudness_lufs` to the reference level.
+pub fn gain_db(loudness_lufs: f64) -> f64 {
+    ((REFERENCE_LUFS - loudness_lufs) * 100.0).round() / 100.0
+}
+
+/// Loudness of tracks played back to back: their mean power, weighted by
+/// duration. Close to gating the concatenated album, without keeping every
+/// track's loudness blocks around.
+pub fn album_loudness(tracks: &[&TrackLoudness]) -> Option<f64> {
+    let total_secs: f64 = tracks.iter().map(|t| t.duration_secs).sum();
+    if total_secs <= 0.0 {
+        return None;
+    }
+    let power = tracks
+        .iter()
+        .map(|t| t.duration_secs * 10f64.powf(t.loudness_lufs / 10.0))
+        .sum::<f64>()
+        / total_secs;
+    Some(10.0 * power.log10())
+}
+
+/// Plan track gain for every file and album gain per album group. Albums are
+/// returned in directory order.
+pub fn plan_gains(tracks: &[TrackLoudness], with_albums: bool) -> (Vec<GainPlan>, Vec<AlbumGain>) {
+    let mut groups: BTreeMap<(PathBuf, Option<String>), Vec<usize>> = BTreeMap::new();
+    if with_albums {
+        let paths: Vec<PathBuf> = tracks.iter().map(|t| t.path.clone()).collect();
+        let album_dirs = audit::detect_album_dirs(&paths);
+        for (i, track) in tracks.iter().enumerate() {
+            if audit::classify_track_context(&track.path, &album_dirs) != AuditContext::AlbumTrack {
+                continue;
+            }
+            let Some((album_dir, _)) = audit::effective_album_dir_name(&track.path) else {
+                continue;
+            };
+            let album_tag = track
+                .album_tag
+                .as_deref()
+                .map(str::trim)
+                .filter(|album| !album.is_empty())
+                .map(str::to_string);
+            groups
+                .entry((album_dir.to_path_buf(), album_tag))
+                .or_default()
+                .push(i);
+        }
+    }
+
+    let mut plans: Vec<GainPlan> = tracks
+        .iter()
+        .map(|track| GainPlan {
+            path: track.path.display().to_string(),
+            loudness_lufs: track.loudness_lufs,
+            track_gain_db: gain_db(track.loudness_lufs),
+            track_peak: track.peak,
+            album: None,
+        })
+        .collect();
+    let mut albums = Vec::with_capacity(groups.len());
+    for ((album_dir, album), members) in groups {
+        let album_tracks: Vec<&TrackLoudness> = members.iter().map(|&i| &tracks[i]).collect();
+        let Some(loudness_lufs) = album_loudness(&album_tracks) else {
+            continue;
+        };
+        for &i in &members {
+            plans[i].album = Some(albums.len());
+        }
+        albums.push(AlbumGain {
+            album_dir: album_dir.display().to_string(),
+            album,
+            tracks: members.len(),
+            loudness_lufs: (loudness_lufs * 100.0).round() / 100.0,
+            gain_db: gain_db(loudness_lufs),
+            peak: album_tracks.iter().map(|t| t.peak).fold(0.0, f64::max),
+        });
+    }
+    (plans, albums)
+}
+
+/// `REPLAYGAIN_*` tag values for a plan, formatted as `-6.54 dB` and
+/// `0.988831`. Album fields are left untouched on loose tracks.
+pub fn replaygain_tags(
+    plan: &GainPlan,
+    album: Option<&AlbumGain>,
+) -> HashMap<String, Option<String>> {
+    let mut tags = HashMap::from([
+        (
+            "replaygain_track_gain".to_string(),
+            Some(format_gain(plan.track_gain_db)),
+        ),
+        (
+            "replaygain_track_peak".to_string(),
+            Some(format_peak(plan.track_peak)),
+        ),
+    ]);
+    if let Some(album) = album {
+        tags.insert(
+            "replaygain_album_gain".to_string(),
+            Some(format_gain(album.gain_db)),
+        );
+        tags.insert(
+            "replaygain_album_peak".to_string(),
+            Some(format_peak(album.peak)),
+        );
+    }
+    tags
+}
+
+fn format_gain(gain_db: f64) -> String {
+    format!("{gain_db:+.2} dB")
+}
+
+fn format_peak(peak: f64) -> String {
+    format!("{peak:.6}")
+}
+
+/// Convert a peak in dBFS to the linear scale ReplayGain stores.
+pub fn dbfs_to_linear(dbfs: f64) -> f64 {
+    10f64.powf(dbfs / 20.0)
+}
+
+pub fn read_duration_secs(path: &Path) -> Option<f64> {
+    let tagged_file = Probe::open(path).ok()?.read().ok()?;
+    Some(tagged_file.properties().duration().as_secs_f64())
+}
+
+pub fn read_album_tag(path: &Path) -> Option<String> {
+    let fields = ["album".to_string()];
+    let tags = match tags::read_file_tags(path, Some(&fields), false) {
+        FileReadResult::Single { tags, .. } => tags,
+        FileReadResult::Wav { id3v2, .. } => id3v2,
+        FileReadResult::Error { .. } => return None,
+    };
+    tags.get("album").cloned().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(path: &str, loudness_lufs: f64, peak: f64, album: Option<&str>) -> TrackLoudness {
+        TrackLoudness {
+            path: PathBuf::from(path),
+            loudness_lufs,
+            peak,
+            duration_secs: 300.0,
+            album_tag: album.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn gain_targets_reference_level() {
+        assert_eq!(gain_db(-18.0), 0.0);
+        assert_eq!(gain_db(-8.46), -9.54);
+        assert_eq!(gain_db(-23.0), 5.0);
+    }
+
+    #[test]
+    fn album_loudness_is_duration_weighted_power_mean() {
+        let a = track("/m/a.flac", -10.0, 0.9, None);
+        let b = track("/m/b.flac", -10.0, 0.9, None);
+        assert!((album_loudness(&[&a, &b]).unwrap() + 10.0).abs() < 1e-9);
+
+        // A quiet short track barely moves a long loud one.
+        let mut quiet = track("/m/c.flac", -30.0, 0.1, None);
+        quiet.duration_secs = 30.0;
+        let loudness = album_loudness(&[&a, &quiet]).unwrap();
+        assert!(loudness < -10.0 && loudness > -10.5, "{loudness}");
+
+        let mut empty = a.clone();
+        empty.duration_secs = 0.0;
+        assert_eq!(album_loudness(&[&empty]), None);
+    }
+
+    #[test]
+    fn plan_groups_album_directories_by_album_tag() {
+        let tracks = vec![
+            track("/m/Artist - LP (2020)/01 One.flac", -8.0, 0.99, Some("LP")),
+            track("/m/Artist - LP (2020)/02 Two.flac", -12.0, 0.5, Some("LP")),
+            track(
+                "/m/Artist - LP (2020)/03 Bonus.flac",
+                -9.0,
+                0.7,
+                Some("LP Bonus"),
+            ),
+            track("/m/loose/Some Track.flac", -7.0, 1.0, Some("LP")),
+        ];
+        let (plans, albums) = plan_gains(&tracks, true);
+
+        assert_eq!(albums.len(), 2);
+        assert_eq!(albums[0].album.as_deref(), Some("LP"));
+        assert_eq!(albums[0].tracks, 2);
+        assert_eq!(albums[0].peak, 0.99);
+        assert!(albums[0].loudness_lufs > -10.0 && albums[0].loudness_lufs < -8.0);
+        assert_eq!(albums[1].album.as_deref(), Some("LP Bonus"));
+        assert_eq!(plans[0].album, Some(0));
+        assert_eq!(plans[1].album, Some(0));
+        assert_eq!(plans[2].album, Some(1));
+        assert_eq!(plans[3].album, None);
+        assert_eq!(plans[3].track_gain_db, -11.0);
+
+        let (plans, albums) = plan_gains(&tracks, false);
+        assert!(albums.is_empty());
+        assert!(plans.iter().all(|plan| plan.album.is_none()));
+    }
+
+    #[test]
+    fn tags_format_gain_and_peak() {
+        let tracks = vec![
+            track("/m/EP (2021)/01 A.wav", -21.5, 0.25, None),
+            track("/m/EP (2021)/02 B.wav", -21.5, 0.5, None),
+        ];
+        let (plans, albums) = plan_gains(&tracks, true);
+        let tags = replaygain_tags(&plans[0], plans[0].album.map(|i| &albums[i]));
+        assert_eq!(tags["replaygain_track_gain"].as_deref(), Some("+3.50 dB"));
+        assert_eq!(tags["replaygain_track_peak"].as_deref(), Some("0.250000"));
+        assert_eq!(tags["replaygain_album_gain"].as_deref(), Some("+3.50 dB"));
+        assert_eq!(tags["replaygain_album_peak"].as_deref(), Some("0.500000"));
+        assert!(tags::validate_write_tags(&tags).is_ok());
+
+        let loose = GainPlan {
+            album: None,
+            ..plans[0].clone()
+        };
+        let tags = replaygain_tags(&loose, None);
+        assert!(!tags.contains_key("replaygain_album_gain"));
+    }
+}
@@ -3,6 +3,7 @@ use std::collections::HashSet;
 use std::path::PathBuf;
 
 use crate::db::escape_like;
+use crate::types::TrackChange;
 
 /// (id, path, issue_type, detail)
 pub type AuditIssueRow = (i64, String, String, Option<String>);
@@ -98,7 +99,20 @@ fn migrate(conn: &Connection) -> Result<(), rusqlite::Error> {
         );
         CREATE INDEX IF NOT EXISTS idx_audit_issues_status ON audit_issues(status);
         CREATE INDEX IF NOT EXISTS idx_audit_issues_path ON audit_issues(path);
-        PRAGMA user_version = 3;",
+        CREATE TABLE IF NOT EXISTS staging_sessions (
+            id         TEXT PRIMARY KEY,
+            name       TEXT NOT NULL,
+            is_active  INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE TABLE IF NOT EXISTS staged_changes (
+            session_id  TEXT NOT NULL REFERENCES staging_sessions(id) ON DELETE CASCADE,
+            track_id    TEXT NOT NULL,
+            change_json TEXT NOT NULL,
+            PRIMARY KEY (session_id, track_id)
+        );
+        PRAGMA user_version = 4;",
     )?;
     Ok(())
 }
@@ -208,6 +222,17 @@ pub struct CachedAudioAnalysis {
     pub created_at: String,
 }
 
+/// File modification time in whole seconds since the Unix epoch, as stored
+/// with cached analyses. 0 when the platform doesn't report one.
+pub fn file_mtime_unix(metadata: &std::fs::Metadata) -> i64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
 pub fn get_audio_analysis(
     conn: &Connection,
     file_path: &str,
@@ -759,6 +784,163 @@ pub fn delete_missing_audit_files(
     Ok(deleted_count)
 }
 
+// ---------------------------------------------------------------------------
+// Staging sessions
+// ---------------------------------------------------------------------------
+
+pub struct StagingSession {
+    pub id: String,
+    pub name: String,
+    pub is_active: bool,
+    pub change_count: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+const STAGING_SESSION_SELECT: &str = "
+    SELECT s.id, s.name, s.is_active, s.created_at, s.updated_at,
+           (SELECT COUNT(*) FROM staged_changes c WHERE c.session_id = s.id) AS change_count
+    FROM staging_sessions s";
+
+fn map_staging_session(row: &rusqlite::Row) -> Result<StagingSession, rusqlite::Error> {
+    Ok(StagingSession {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        is_active: row.get::<_, i64>("is_active")? != 0,
+        change_count: row.get("change_count")?,
+        created_at: row.get("created_at")?,
+        updated_at: row.get("updated_at")?,
+    })
+}
+
+/// Create a new staging session and make it the only active one.
+pub fn create_staging_session(
+    conn: &Connection,
+    id: &str,
+    name: &str,
+) -> Result<(), rusqlite::Error> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("UPDATE staging_sessions SET is_active = 0", [])?;
+    tx.execute(
+        "INSERT INTO staging_sessions (id, name, is_active) VALUES (?1, ?2, 1)",
+        params![id, name],
+    )?;
+    tx.commit()
+}
+
+pub fn get_staging_session(
+    conn: &Connection,
+    id: &str,
+) -> Result<Option<StagingSession>, rusqlite::Error> {
+    let sql = format!("{STAGING_SESSION_SELECT} WHERE s.id = ?1");
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query_map(params![id], map_staging_session)?;
+    rows.next().transpose()
+}
+
+/// Most recently updated active session, if any.
+pub fn get_active_staging_session(
+    conn: &Connection,
+) -> Result<Option<StagingSession>, rusqlite::Error> {
+    let sql = format!(
+        "{STAGING_SESSION_SELECT} WHERE s.is_active = 1 ORDER BY s.updated_at DESC LIMIT 1"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query_map([], map_staging_session)?;
+    rows.next().transpose()
+}
+
+pub fn list_staging_sessions(conn: &Connection) -> Result<Vec<StagingSession>, rusqlite::Error> {
+    let sql = format!("{STAGING_SESSION_SELECT} ORDER BY s.updated_at DESC, s.id");
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map([], map_staging_session)?;
+    rows.collect()
+}
+
+/// Mark one session active and all others inactive. Returns false if the session does not exist.
+pub fn activate_staging_session(conn: &Connection, id: &str) -> Result<bool, rusqlite::Error> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("UPDATE staging_sessions SET is_active = 0", [])?;
+    let updated = tx.execute(
+        "UPDATE staging_sessions SET is_active = 1, updated_at = datetime('now') WHERE id = ?1",
+        params![id],
+    )?;
+    tx.commit()?;
+    Ok(updated > 0)
+}
+
+pub fn rename_staging_session(
+    conn: &Connection,
+    id: &str,
+    name: &str,
+) -> Result<bool, rusqlite::Error> {
+    let updated = conn.execute(
+        "UPDATE staging_sessions SET name = ?2, updated_at = datetime('now') WHERE id = ?1",
+        params![id, name],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Delete a session and its staged changes. Returns false if the session does not exist.
+pub fn delete_staging_session(conn: &Connection, id: &str) -> Result<bool, rusqlite::Error> {
+    let deleted = conn.execute("DELETE FROM staging_sessions WHERE id = ?1", params![id])?;
+    Ok(deleted > 0)
+}
+
+/// Upsert the persisted change for each track in `entries`, or delete it when
+/// the track has no staged change left. Other tracks' rows are untouched.
+pub fn save_staged_changes(
+    conn: &Connection,
+    session_id: &str,
+    entries: &[(String, Option<TrackChange>)],
+) -> Result<(), rusqlite::Error> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut upsert = tx.prepare(
+            "INSERT INTO staged_changes (session_id, track_id, change_json) VALUES (?1, ?2, ?3)
+             ON CONFLICT(session_id, track_id) DO UPDATE SET change_json = excluded.change_json",
+        )?;
+        let mut delete =
+            tx.prepare("DELETE FROM staged_changes WHERE session_id = ?1 AND track_id = ?2")?;
+        for (track_id, change) in entries {
+            match change {
+                Some(change) => {
+                    let change_json = serde_json::to_string(change)
+                        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+                    upsert.execute(params![session_id, track_id, change_json])?;
+                }
+                None => {
+                    delete.execute(params![session_id, track_id])?;
+                }
+            }
+        }
+    }
+    tx.execute(
+        "UPDATE staging_sessions SET updated_at = datetime('now') WHERE id = ?1",
+        params![session_id],
+    )?;
+    tx.commit()
+}
+
+pub fn load_staged_changes(
+    conn: &Connection,
+    session_id: &str,
+) -> Result<Vec<TrackChange>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT change_json FROM staged_changes WHERE session_id = ?1 ORDER BY track_id",
+    )?;
+    let rows = stmt.query_map(params![session_id], |row| row.get::<_, String>(0))?;
+    let mut changes = Vec::new();
+    for row in rows {
+        let raw = row?;
+        match serde_json::from_str::<TrackChange>(&raw) {
+            Ok(change) => changes.push(change),
+            Err(e) => tracing::warn!("skipping corrupt staged change in session {session_id}: {e}"),
+        }
+    }
+    Ok(changes)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -776,7 +958,7 @@ mod tests {
         let version: i32 = conn
             .pragma_query_value(None, "user_version", |r| r.get(0))
             .unwrap();
-        assert_eq!(version, 3);
+        assert_eq!(version, 4);
 
         let tables: Vec<String> = conn
             .prepare("SELECT name FROM sqlite_master WHERE type='table' ORDER BY name")
@@ -790,6 +972,8 @@ mod tests {
         assert!(tables.contains(&"broker_discogs_session".to_string()));
         assert!(tables.contains(&"audit_files".to_string()));
         assert!(tables.contains(&"audit_issues".to_string()));
+        assert!(tables.contains(&"staging_sessions".to_string()));
+        assert!(tables.contains(&"staged_changes".to_string()));
     }
 
     #[test]
@@ -805,7 +989,7 @@ mod tests {
         let version: i32 = conn2
             .pragma_query_value(None, "user_version", |r| r.get(0))
             .unwrap();
-        assert_eq!(version, 3);
+        assert_eq!(version, 4);
     }
 
     #[test]
@@ -862,7 +1046,7 @@ mod tests {
         let path_str = path.to_str().unwrap();
 
         let conn = Connection::open(path_str).unwrap();
-        conn.execute_batch("PRAGMA user_version = 3;").unwrap();
+        conn.execute_batch("PRAGMA user_version = 4;").unwrap();
         drop(conn);
 
         let conn = open(path_str).unwrap();
@@ -878,6 +1062,8 @@ mod tests {
         assert!(tables.contains(&"broker_discogs_session".to_string()));
         assert!(tables.contains(&"audit_files".to_string()));
         assert!(tables.contains(&"audit_issues".to_string()));
+        assert!(tables.contains(&"staging_sessions".to_string()));
+        assert!(tables.contains(&"staged_changes".to_string()));
     }
 
     #[test]
@@ -1585,4 +1771,96 @@ mod tests {
             assert!(result.contains(&(a.clone(), "title".to_string())));
         }
     }
+
+    #[test]
+    fn test_staging_session_round_trip() {
+        let (_dir, conn) = open_temp_store();
+        create_staging_session(&conn, "s1", "Genre pass").unwrap();
+        let changes = [
+            TrackChange {
+                track_id: "t2".to_string(),
+                genre: Some("Techno".to_string()),
+                ..Default::default()
+            },
+            TrackChange {
+                track_id: "t1".to_string(),
+                bpm: Some(128.0),
+                ..Default::default()
+            },
+        ];
+        let entries: Vec<_> = changes
+            .iter()
+            .map(|c| (c.track_id.clone(), Some(c.clone())))
+            .collect();
+        save_staged_changes(&conn, "s1", &entries).unwrap();
+
+        let active = get_active_staging_session(&conn).unwrap().unwrap();
+        assert_eq!(active.id, "s1");
+        assert_eq!(active.name, "Genre pass");
+        assert_eq!(active.change_count, 2);
+
+        let loaded = load_staged_changes(&conn, "s1").unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].track_id, "t1");
+        assert_eq!(loaded[0].bpm, Some(128.0));
+        assert_eq!(loaded[1].genre.as_deref(), Some("Techno"));
+
+        // Saving touches only the listed tracks: t1 is updated, t2 removed.
+        let updated = TrackChange {
+            track_id: "t1".to_string(),
+            bpm: Some(64.0),
+            ..Default::default()
+        };
+        save_staged_changes(
+            &conn,
+            "s1",
+            &[("t1".to_string(), Some(updated)), ("t2".to_string(), None)],
+        )
+        .unwrap();
+        let loaded = load_staged_changes(&conn, "s1").unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].bpm, Some(64.0));
+
+        // Deleting a track with no row is a no-op.
+        save_staged_changes(&conn, "s1", &[("t9".to_string(), None)]).unwrap();
+        assert_eq!(load_staged_changes(&conn, "s1").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_staging_session_activate_rename_delete() {
+        let (_dir, conn) = open_temp_store();
+        create_staging_session(&conn, "s1", "First").unwrap();
+        create_staging_session(&conn, "s2", "Second").unwrap();
+        assert_eq!(get_active_staging_session(&conn).unwrap().unwrap().id, "s2");
+
+        assert!(activate_staging_session(&conn, "s1").unwrap());
+        let sessions = list_staging_sessions(&conn).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions.iter().filter(|s| s.is_active).count(), 1);
+        assert_eq!(get_active_staging_session(&conn).unwrap().unwrap().id, "s1");
+        assert!(!activate_staging_session(&conn, "missing").unwrap());
+
+        assert!(rename_staging_session(&conn, "s2", "Renamed").unwrap());
+        assert_eq!(
+            get_staging_session(&conn, "s2").unwrap().unwrap().name,
+            "Renamed"
+        );
+
+        save_staged_changes(
+            &conn,
+            "s2",
+            &[(
+                "t1".to_string(),
+                Some(TrackChange {
+                    track_id: "t1".to_string(),
+                    rating: Some(3),
+                    ..Default::default()
+                }),
+            )],
+        )
+        .unwrap();
+        assert!(delete_staging_session(&conn, "s2").unwrap());
+        assert!(get_staging_session(&conn, "s2").unwrap().is_none());
+        assert!(load_staged_changes(&conn, "s2").unwrap().is_empty());
+    }
 }
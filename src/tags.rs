@@ -10,6 +10,7 @@ use std::path::{Path, PathBuf};
 
 use lofty::config::{ParseOptions, ParsingMode, WriteOptions};
 use lofty::file::{FileType, TaggedFileExt};
+use lofty::id3::v2::Id3v2Tag;
 use lofty::picture::{MimeType, Picture, PictureType};
 use lofty::prelude::*;
 use lofty::probe::Probe;
@@ -40,7 +41,9 @@ pub enum TagError {
 // Constants
 // ---------------------------------------------------------------------------
 
-/// All 14 canonical field names, in a stable order.
+/// All 14 canonical field names, in a stable order. The `replaygain_*`
+/// fields are writable and readable on request but left out: they are
+/// measurements, not library metadata.
 pub const ALL_FIELDS: &[&str] = &[
     "artist",
     "title",
@@ -87,6 +90,10 @@ pub fn field_to_item_key(field: &str) -> Option<ItemKey> {
         "key" => Some(ItemKey::InitialKey),
         "composer" => Some(ItemKey::Composer),
         "remixer" => Some(ItemKey::Remixer),
+        "replaygain_track_gain" => Some(ItemKey::ReplayGainTrackGain),
+        "replaygain_track_peak" => Some(ItemKey::ReplayGainTrackPeak),
+        "replaygain_album_gain" => Some(ItemKey::ReplayGainAlbumGain),
+        "replaygain_album_peak" => Some(ItemKey::ReplayGainAlbumPeak),
         _ => None,
     }
 }
@@ -111,6 +118,10 @@ fn item_key_to_field(key: &ItemKey) -> Option<&'static str> {
         ItemKey::InitialKey => Some("key"),
         ItemKey::Composer => Some("composer"),
         ItemKey::Remixer => Some("remixer"),
+        ItemKey::ReplayGainTrackGain => Some("replaygain_track_gain"),
+        ItemKey::ReplayGainTrackPeak => Some("replaygain_track_peak"),
+        ItemKey::ReplayGainAlbumGain => Some("replaygain_album_gain"),
+        ItemKey::ReplayGainAlbumPeak => Some("replaygain_album_peak"),
         _ => None,
     }
 }
@@ -509,6 +520,22 @@ pub fn validate_write_tags(tags: &HashMap<String, Option<String>>) -> Result<(),
                     )));
                 }
             },
+            "replaygain_track_gain" | "replaygain_album_gain" => {
+                let number = val.strip_suffix(" dB").unwrap_or(val);
+                if !number.parse::<f64>().is_ok_and(f64::is_finite) {
+                    return Err(TagError::Validation(format!(
+                        "Invalid {field} \"{val}\": must be a gain like \"-6.54 dB\" or null/empty to delete"
+                    )));
+                }
+            }
+            "replaygain_track_peak" | "replaygain_album_peak" => match val.parse::<f64>() {
+                Ok(peak) if peak.is_finite() && peak >= 0.0 => {}
+                _ => {
+                    return Err(TagError::Validation(format!(
+                        "Invalid {field} \"{val}\": must be a non-negative linear peak or null/empty to delete"
+                    )));
+                }
+            },
             _ => {}
         }
     }
@@ -930,13 +957,37 @@ fn write_tag_layer(
     }
 
     if any_changes {
-        tag.save_to_path(path, WriteOptions::default())
+        save_tag(tag, path)
             .map_err(|e| TagError::Io(format!("Failed to write {tag_type:?} tag: {e}")))?;
     }
 
     Ok(())
 }
 
+/// ReplayGain keys and the ID3v2 `TXXX` descriptions lofty reads them from.
+const ID3V2_REPLAYGAIN_FRAMES: &[(ItemKey, &str)] = &[
+    (ItemKey::ReplayGainTrackGain, "REPLAYGAIN_TRACK_GAIN"),
+    (ItemKey::ReplayGainTrackPeak, "REPLAYGAIN_TRACK_PEAK"),
+    (ItemKey::ReplayGainAlbumGain, "REPLAYGAIN_ALBUM_GAIN"),
+    (ItemKey::ReplayGainAlbumPeak, "REPLAYGAIN_ALBUM_PEAK"),
+];
+
+/// Save a generic tag. lofty reads ReplayGain from ID3v2 `TXXX` frames but
+/// drops those items when converting a generic tag back to ID3v2, so they
+/// are re-added as `TXXX` frames before saving.
+fn save_tag(tag: &Tag, path: &Path) -> lofty::error::Result<()> {
+    if tag.tag_type() != TagType::Id3v2 {
+        return tag.save_to_path(path, WriteOptions::default());
+    }
+    let mut id3v2 = Id3v2Tag::from(tag.clone());
+    for &(key, description) in ID3V2_REPLAYGAIN_FRAMES {
+        if let Some(value) = tag.get_string(key) {
+            id3v2.insert_user_text(description.to_string(), value.to_string());
+        }
+    }
+    id3v2.save_to_path(path, WriteOptions::default())
+}
+
 // ---------------------------------------------------------------------------
 // 3. write_file_tags_dry_run
 // ---------------------------------------------------------------------------
@@ -1198,7 +1249,7 @@ fn embed_cover_art_inner(
         tag.remove_picture_type(pic_type);
         tag.push_picture(picture);
 
-        tag.save_to_path(target_path, WriteOptions::default())
+        save_tag(tag, target_path)
             .map_err(|e| TagError::Io(format!("Failed to write ID3v2 tag: {e}")))?;
     } else {
         // Single tag layer — use primary tag type
@@ -1216,7 +1267,7 @@ fn embed_cover_art_inner(
         tag.remove_picture_type(pic_type);
         tag.push_picture(picture);
 
-        tag.save_to_path(target_path, WriteOptions::default())
+        save_tag(tag, target_path)
             .map_err(|e| TagError::Io(format!("Failed to write tag: {e}")))?;
     }
 
@@ -1242,6 +1293,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn replaygain_fields_roundtrip_and_stay_out_of_riff_info() {
+        for field in [
+            "replaygain_track_gain",
+            "replaygain_track_peak",
+            "replaygain_album_gain",
+            "replaygain_album_peak",
+        ] {
+            let key = field_to_item_key(field)
+                .unwrap_or_else(|| panic!("No ItemKey for field \"{field}\""));
+            assert_eq!(item_key_to_field(&key), Some(field));
+            assert!(!is_riff_info_field(field));
+        }
+    }
+
     #[test]
     fn riff_info_field_set() {
         assert!(is_riff_info_field("artist"));
@@ -1353,6 +1419,39 @@ mod tests {
         assert!(validate_write_tags(&tags).is_ok());
     }
 
+    #[test]
+    fn validate_replaygain_values() {
+        let valid = HashMap::from([
+            (
+                "replaygain_track_gain".to_string(),
+                Some("-6.54 dB".to_string()),
+            ),
+            (
+                "replaygain_album_gain".to_string(),
+                Some("+1.20 dB".to_string()),
+            ),
+            (
+                "replaygain_track_peak".to_string(),
+                Some("0.988831".to_string()),
+            ),
+            ("replaygain_album_peak".to_string(), None),
+        ]);
+        assert!(validate_write_tags(&valid).is_ok());
+
+        for (field, value) in [
+            ("replaygain_track_gain", "loud"),
+            ("replaygain_album_gain", "-6.54 LU"),
+            ("replaygain_track_peak", "-0.5"),
+            ("replaygain_album_peak", "NaN"),
+        ] {
+            let tags = HashMap::from([(field.to_string(), Some(value.to_string()))]);
+            assert!(
+                validate_write_tags(&tags).is_err(),
+                "{field}={value} should be rejected"
+            );
+        }
+    }
+
     #[test]
     fn parse_picture_type_known() {
         assert_eq!(parse_picture_type("front_cover"), PictureType::CoverFront);
@@ -1445,6 +1544,60 @@ mod tests {
         }
     }
 
+    #[test]
+    fn replaygain_survives_later_id3v2_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav_path = dir.path().join("gain.wav");
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&38u32.to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+        wav.extend_from_slice(&44100u32.to_le_bytes());
+        wav.extend_from_slice(&88200u32.to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&2u32.to_le_bytes());
+        wav.extend_from_slice(&[0u8; 2]);
+        std::fs::write(&wav_path, &wav).unwrap();
+
+        let write = |tags: HashMap<String, Option<String>>| {
+            let entry = WriteEntry {
+                path: wav_path.clone(),
+                tags,
+                wav_targets: vec![],
+                comment_mode: CommentMode::default(),
+            };
+            assert!(matches!(
+                write_file_tags(&entry),
+                FileWriteResult::Ok { .. }
+            ));
+        };
+        write(HashMap::from([(
+            "replaygain_track_gain".to_string(),
+            Some("-7.03 dB".to_string()),
+        )]));
+        write(HashMap::from([(
+            "title".to_string(),
+            Some("Retitled".to_string()),
+        )]));
+
+        let fields = vec!["replaygain_track_gain".to_string(), "title".to_string()];
+        match read_file_tags(&wav_path, Some(&fields), false) {
+            FileReadResult::Wav {
+                id3v2, riff_info, ..
+            } => {
+                assert_eq!(id3v2["replaygain_track_gain"].as_deref(), Some("-7.03 dB"));
+                assert_eq!(id3v2["title"].as_deref(), Some("Retitled"));
+                assert_eq!(riff_info["replaygain_track_gain"], None);
+            }
+            other => panic!("expected WAV tags, got {other:?}"),
+        }
+    }
+
     #[test]
     fn merge_comment_replace() {
         assert_eq!(
@@ -1,5 +1,13 @@
+use std::future::Future;
+use std::sync::Arc;
+
 use rusqlite::Connection;
+use serde::Serialize;
 
+use super::ReklawdboxServer;
+use crate::beatport::BeatportResult;
+use crate::normalize::normalize_for_matching;
+use crate::types::Track;
 use crate::{audio, store};
 
 /// Check analysis cache. Returns `Some(json_string)` on valid hit (matching
@@ -21,17 +29,378 @@ pub(super) fn check_analysis_cache(
     }
 }
 
-/// Decode audio and run stratum-dsp analysis (uses `spawn_blocking`).
-pub(super) async fn analyze_stratum(file_path: &str) -> Result<audio::StratumResult, String> {
-    let path = file_path.to_string();
-    let (samples, sample_rate) =
-        tokio::task::spawn_blocking(move || audio::decode_to_samples(&path))
+/// A track decoded on first use and shared by every analyser that needs the
+/// whole signal, so one request decodes each file at most once.
+pub(super) struct SharedDecode {
+    file_path: String,
+    /// Whether stratum or structure will decode the whole signal, so the
+    /// native features share that buffer rather than streaming the file.
+    buffered: bool,
+    audio: tokio::sync::OnceCell<Result<Arc<audio::DecodedAudio>, String>>,
+    /// Signal quality metered while the native features streamed the file.
+    streamed_quality: std::sync::OnceLock<audio::SignalQuality>,
+}
+
+impl SharedDecode {
+    pub(super) fn new(file_path: &str, buffered: bool) -> Self {
+        Self {
+            file_path: file_path.to_string(),
+            buffered,
+            audio: tokio::sync::OnceCell::new(),
+            streamed_quality: std::sync::OnceLock::new(),
+        }
+    }
+
+    /// The decoded track, decoding it on the first call (uses
+    /// `spawn_blocking`).
+    pub(super) async fn audio(&self) -> Result<Arc<audio::DecodedAudio>, String> {
+        self.audio
+            .get_or_init(|| async {
+                let path = self.file_path.clone();
+                tokio::task::spawn_blocking(move || audio::decode_for_analysis(&path))
+                    .await
+                    .map_err(|e| format!("Decode task failed: {e}"))?
+                    .map(Arc::new)
+                    .map_err(|e| format!("Decode error: {e}"))
+            })
             .await
-            .map_err(|e| format!("Decode task failed: {e}"))?
-            .map_err(|e| format!("Decode error: {e}"))?;
+            .clone()
+    }
+
+    /// Whether an analyser has already decoded the track, into the shared
+    /// buffer or streamed.
+    pub(super) fn is_decoded(&self) -> bool {
+        matches!(self.audio.get(), Some(Ok(_))) || self.streamed_quality.get().is_some()
+    }
+
+    /// Signal quality metered during the shared or streamed decode, or
+    /// streamed through the meter on its own when nothing decoded the track.
+    pub(super) async fn quality(&self) -> Result<audio::SignalQuality, String> {
+        if let Some(Ok(audio)) = self.audio.get() {
+            return Ok(audio.quality.clone());
+        }
+        if let Some(quality) = self.streamed_quality.get() {
+            return Ok(quality.clone());
+        }
+        analyze_signal_quality(&self.file_path).await
+    }
+}
+
+/// Run stratum-dsp on the shared decode (uses `spawn_blocking`).
+pub(super) async fn analyze_stratum(decode: &SharedDecode) -> Result<audio::StratumResult, String> {
+    let audio = decode.audio().await?;
+    tokio::task::spawn_blocking(move || {
+        audio::analyze_with_stratum(&audio.samples, audio.sample_rate)
+    })
+    .await
+    .map_err(|e| format!("Analysis task failed: {e}"))?
+    .map_err(|e| format!("Analysis error: {e}"))
+}
+
+/// Stream audio through the meter for peaks, clipping and DC offset without
+/// keeping the samples (uses `spawn_blocking`).
+pub(super) async fn analyze_signal_quality(
+    file_path: &str,
+) -> Result<audio::SignalQuality, String> {
+    let path = file_path.to_string();
+    tokio::task::spawn_blocking(move || {
+        audio::measure_signal_quality(&path).map_err(|e| format!("Decode error: {e}"))
+    })
+    .await
+    .map_err(|e| format!("Analysis task failed: {e}"))?
+}
 
-    tokio::task::spawn_blocking(move || audio::analyze_with_stratum(&samples, sample_rate))
+/// Compute the native Essentia-equivalent features on the shared decode, or
+/// stream them with signal quality when nothing else needs the whole signal
+/// (uses `spawn_blocking`).
+pub(super) async fn analyze_native(decode: &SharedDecode) -> Result<audio::EssentiaOutput, String> {
+    if !decode.buffered {
+        let path = decode.file_path.clone();
+        let (features, quality) =
+            tokio::task::spawn_blocking(move || audio::measure_native_features(&path))
+                .await
+                .map_err(|e| format!("Analysis task failed: {e}"))?
+                .map_err(|e| format!("Analysis error: {e}"))?;
+        let _ = decode.streamed_quality.set(quality);
+        return Ok(features);
+    }
+    let audio = decode.audio().await?;
+    tokio::task::spawn_blocking(move || audio::analyze_native_features(&audio))
         .await
         .map_err(|e| format!("Analysis task failed: {e}"))?
         .map_err(|e| format!("Analysis error: {e}"))
 }
+
+/// Detect the arrangement of the shared decode on stratum's beat grid (uses
+/// `spawn_blocking`).
+pub(super) async fn analyze_track_structure(
+    decode: &SharedDecode,
+    stratum: &audio::StratumResult,
+) -> Result<audio::TrackStructure, String> {
+    let bpm = stratum.bpm;
+    let first_beat = stratum.first_beat_sec.zip(stratum.first_beat_in_bar);
+    let audio = decode.audio().await?;
+    tokio::task::spawn_blocking(move || {
+        audio::analyze_structure(&audio.samples, audio.sample_rate, bpm, first_beat)
+    })
+    .await
+    .map_err(|e| format!("Analysis task failed: {e}"))?
+    .map_err(|e| format!("Analysis error: {e}"))
+}
+
+/// An analysis result that records the analyzer version it was cached under.
+pub(super) trait CachedAnalysis: Serialize {
+    fn analyzer_version(&self) -> &str;
+}
+
+impl CachedAnalysis for audio::StratumResult {
+    fn analyzer_version(&self) -> &str {
+        &self.analyzer_version
+    }
+}
+
+impl CachedAnalysis for audio::EssentiaOutput {
+    fn analyzer_version(&self) -> &str {
+        &self.analyzer_version
+    }
+}
+
+impl CachedAnalysis for audio::TrackStructure {
+    fn analyzer_version(&self) -> &str {
+        &self.analyzer_version
+    }
+}
+
+impl CachedAnalysis for audio::SignalQuality {
+    fn analyzer_version(&self) -> &str {
+        &self.analyzer_version
+    }
+}
+
+/// The file an analysis is cached against: its path and the size and mtime
+/// that keep the entry fresh.
+pub(super) struct CacheKey<'a> {
+    pub(super) file_path: &'a str,
+    pub(super) file_size: i64,
+    pub(super) file_mtime: i64,
+}
+
+impl<'a> CacheKey<'a> {
+    /// Stat `file_path` for the size and mtime its cache entries are keyed on.
+    pub(super) fn stat(file_path: &'a str) -> Result<Self, String> {
+        let metadata = std::fs::metadata(file_path)
+            .map_err(|e| format!("Cannot stat file '{file_path}': {e}"))?;
+        Ok(Self {
+            file_path,
+            file_size: metadata.len() as i64,
+            file_mtime: store::file_mtime_unix(&metadata),
+        })
+    }
+}
+
+pub(super) enum CacheWriteMsg {
+    Audio {
+        file_path: String,
+        analyzer: &'static str,
+        file_size: i64,
+        file_mtime: i64,
+        analyzer_version: String,
+        features_json: String,
+    },
+}
+
+/// Where fresh analyses are cached: the store directly, or the batch
+/// writer's queue.
+pub(super) enum CacheSink<'a> {
+    Store(&'a ReklawdboxServer),
+    Queue(&'a tokio::sync::mpsc::Sender<CacheWriteMsg>),
+}
+
+impl CacheSink<'_> {
+    pub(super) async fn write(
+        &self,
+        key: &CacheKey<'_>,
+        analyzer: &'static str,
+        analyzer_version: &str,
+        features_json: String,
+    ) -> Result<(), String> {
+        match self {
+            CacheSink::Store(server) => {
+                let store = server
+                    .cache_store_conn()
+                    .map_err(|e| e.message.to_string())?;
+                store::set_audio_analysis(
+                    &store,
+                    key.file_path,
+                    analyzer,
+                    key.file_size,
+                    key.file_mtime,
+                    analyzer_version,
+                    &features_json,
+                )
+                .map_err(|e| format!("Cache write error: {e}"))
+            }
+            CacheSink::Queue(cache_tx) => {
+                let _ = cache_tx
+                    .send(CacheWriteMsg::Audio {
+                        file_path: key.file_path.to_string(),
+                        analyzer,
+                        file_size: key.file_size,
+                        file_mtime: key.file_mtime,
+                        analyzer_version: analyzer_version.to_string(),
+                        features_json,
+                    })
+                    .await;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// One analyzer's part of a tool result: its output, whether that came from
+/// the cache, and why it failed.
+#[derive(Debug, Default)]
+pub(super) struct AnalyzerOutcome {
+    pub(super) value: Option<serde_json::Value>,
+    pub(super) cache_hit: Option<bool>,
+    pub(super) error: Option<String>,
+}
+
+impl AnalyzerOutcome {
+    fn failed(error: String) -> Self {
+        Self {
+            error: Some(error),
+            ..Self::default()
+        }
+    }
+}
+
+/// Use the `cached` JSON when there is one; otherwise run the analyzer and
+/// cache its result under `analyzer`.
+pub(super) async fn cached_or_run<T, F>(
+    cached: Option<String>,
+    key: &CacheKey<'_>,
+    analyzer: &'static str,
+    sink: &CacheSink<'_>,
+    run: F,
+) -> AnalyzerOutcome
+where
+    T: CachedAnalysis,
+    F: Future<Output = Result<T, String>>,
+{
+    if let Some(json_str) = cached {
+        return match serde_json::from_str(&json_str) {
+            Ok(value) => AnalyzerOutcome {
+                value: Some(value),
+                cache_hit: Some(true),
+                error: None,
+            },
+            Err(e) => AnalyzerOutcome::failed(format!("Cache parse error: {e}")),
+        };
+    }
+    let analysis = match run.await {
+        Ok(analysis) => analysis,
+        Err(e) => return AnalyzerOutcome::failed(e),
+    };
+    let (features_json, value) = match (
+        serde_json::to_string(&analysis),
+        serde_json::to_value(&analysis),
+    ) {
+        (Ok(features_json), Ok(value)) => (features_json, value),
+        (Err(e), _) | (_, Err(e)) => {
+            return AnalyzerOutcome::failed(format!("Serialize error: {e}"));
+        }
+    };
+    let version = match analysis.analyzer_version() {
+        "" => "unknown",
+        version => version,
+    };
+    if let Err(e) = sink.write(key, analyzer, version, features_json).await {
+        return AnalyzerOutcome::failed(e);
+    }
+    AnalyzerOutcome {
+        value: Some(value),
+        cache_hit: Some(false),
+        error: None,
+    }
+}
+
+/// Read a cached structure analysis without a freshness check. Entries from
+/// older analyzer versions are ignored.
+pub(super) fn cached_structure(
+    store: &Connection,
+    cache_key: &str,
+) -> Result<Option<audio::TrackStructure>, String> {
+    let cached = store::get_audio_analysis(store, cache_key, audio::ANALYZER_STRUCTURE)
+        .map_err(|e| format!("Cache read error: {e}"))?;
+    Ok(cached
+        .filter(|entry| entry.analysis_version == audio::STRUCTURE_VERSION)
+        .and_then(|entry| serde_json::from_str(&entry.features_json).ok()))
+}
+
+/// Read the cached Beatport enrichment for a track, matched on its normalised
+/// artist and title. Nothing is looked up here.
+pub(super) fn cached_beatport(
+    store: &Connection,
+    track: &Track,
+) -> Result<Option<BeatportResult>, String> {
+    let cached = store::get_enrichment(
+        store,
+        "beatport",
+        &normalize_for_matching(&track.artist),
+        &normalize_for_matching(&track.title),
+    )
+    .map_err(|e| format!("Cache read error: {e}"))?;
+    Ok(cached
+        .and_then(|entry| entry.response_json)
+        .and_then(|json| serde_json::from_str(&json).ok()))
+}
+
+/// Whether cached structure JSON came from the current analyzer version, so
+/// entries that predate phrase segmentation are recomputed.
+pub(super) fn is_current_structure(features_json: &str) -> bool {
+    serde_json::from_str::<audio::TrackStructure>(features_json)
+        .is_ok_and(|structure| structure.analyzer_version == audio::STRUCTURE_VERSION)
+}
+
+/// Whether cached stratum-dsp JSON covers the whole track, so results from a
+/// windowed CLI run are replaced when the full track is analysed.
+pub(super) fn is_whole_track_stratum(features_json: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(features_json).is_ok_and(|stratum| {
+        !stratum["analyzer_version"]
+            .as_str()
+            .is_some_and(audio::is_windowed_version)
+    })
+}
+
+/// Whether cached Essentia JSON came from the current feature script, so
+/// entries that predate the key estimate are recomputed.
+pub(super) fn is_current_essentia(features_json: &str) -> bool {
+    serde_json::from_str::<audio::EssentiaOutput>(features_json)
+        .is_ok_and(|features| features.features_version == audio::ESSENTIA_FEATURES_VERSION)
+}
+
+/// Whether cached native features JSON came from the current analyzer, so
+/// entries metered on the old downmix are recomputed.
+pub(super) fn is_current_native(features_json: &str) -> bool {
+    serde_json::from_str::<audio::EssentiaOutput>(features_json)
+        .is_ok_and(|features| features.analyzer_version == audio::NATIVE_FEATURES_VERSION)
+}
+
+/// Whether cached signal quality JSON came from the current meter.
+pub(super) fn is_current_quality(features_json: &str) -> bool {
+    serde_json::from_str::<audio::SignalQuality>(features_json)
+        .is_ok_and(|quality| quality.analyzer_version == audio::QUALITY_VERSION)
+}
+
+/// Mix-in/mix-out summary of a structure analysis for tool output.
+pub(super) fn mix_points_json(structure: &audio::TrackStructure) -> serde_json::Value {
+    serde_json::json!({
+        "mix_in_sec": structure.mix_in_sec,
+        "mix_in_end_sec": structure.mix_in_end_sec,
+        "mix_out_sec": structure.mix_out_sec,
+        "mix_out_end_sec": structure.mix_out_end_sec,
+        "intro_bars": structure.intro_bars,
+        "outro_bars": structure.outro_bars,
+    })
+}
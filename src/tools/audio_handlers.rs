@@ -24,120 +24,108 @@ pub(super) async fn handle_analyze_track_audio(
     };
 
     let file_path = resolve_file_path(&track.file_path)?;
-    let metadata = std::fs::metadata(&file_path)
-        .map_err(|e| mcp_internal_error(format!("Cannot stat file '{}': {e}", file_path)))?;
-    let file_size = metadata.len() as i64;
-    let file_mtime = metadata
-        .modified()
-        .ok()
-        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-        .map(|d| d.as_secs() as i64)
-        .unwrap_or(0);
-
-    // Stratum-dsp: check cache then analyze
-    let stratum_cached = if skip_cached {
-        let store = server.cache_store_conn()?;
-        check_analysis_cache(
-            &store,
-            &file_path,
-            audio::ANALYZER_STRATUM,
-            file_size,
-            file_mtime,
-        )
-        .map_err(mcp_internal_error)?
-    } else {
-        None
-    };
+    let key = CacheKey::stat(&file_path).map_err(mcp_internal_error)?;
 
-    let (stratum_dsp, stratum_cache_hit) = if let Some(json_str) = stratum_cached {
-        let val = serde_json::from_str(&json_str)
-            .map_err(|e| mcp_internal_error(format!("Cache parse error: {e}")))?;
-        (val, true)
-    } else {
-        let analysis = analyze_stratum(&file_path)
-            .await
-            .map_err(mcp_internal_error)?;
-        let features_json =
-            serde_json::to_string(&analysis).map_err(|e| mcp_internal_error(format!("{e}")))?;
-        let store = server.cache_store_conn()?;
-        store::set_audio_analysis(
-            &store,
-            &file_path,
-            audio::ANALYZER_STRATUM,
-            file_size,
-            file_mtime,
-            &analysis.analyzer_version,
-            &features_json,
-        )
-        .map_err(|e| mcp_internal_error(format!("Cache write error: {e}")))?;
-        (
-            serde_json::to_value(&analysis).map_err(|e| mcp_internal_error(format!("{e}")))?,
-            false,
-        )
-    };
-
-    // Essentia: check cache then analyze
     let essentia_python = server.essentia_python_path();
     let essentia_available = essentia_python.is_some();
-    let mut essentia: Option<serde_json::Value> = None;
-    let mut essentia_cache_hit: Option<bool> = None;
-    let mut essentia_error: Option<String> = None;
-
-    if let Some(python_path) = essentia_python.as_deref() {
-        let essentia_cached = if skip_cached {
-            let store = server.cache_store_conn()?;
-            check_analysis_cache(
-                &store,
-                &file_path,
-                audio::ANALYZER_ESSENTIA,
-                file_size,
-                file_mtime,
-            )
-            .map_err(mcp_internal_error)?
-        } else {
-            None
-        };
 
-        if let Some(json_str) = essentia_cached {
-            essentia = Some(
-                serde_json::from_str(&json_str)
-                    .map_err(|e| mcp_internal_error(format!("Cache parse error: {e}")))?,
-            );
-            essentia_cache_hit = Some(true);
-        } else {
-            match audio::run_essentia(python_path, &file_path)
-                .await
-                .map_err(|e| e.to_string())
-            {
-                Ok(features) => {
-                    let version = if features.analyzer_version.is_empty() {
-                        "unknown"
-                    } else {
-                        &features.analyzer_version
-                    };
-                    let features_json = serde_json::to_string(&features)
-                        .map_err(|e| mcp_internal_error(format!("{e}")))?;
-                    let store = server.cache_store_conn()?;
-                    store::set_audio_analysis(
-                        &store,
-                        &file_path,
+    let cached = |analyzer: &str| -> Result<Option<String>, McpError> {
+        if !skip_cached {
+            return Ok(None);
+        }
+        let store = server.cache_store_conn()?;
+        check_analysis_cache(&store, &file_path, analyzer, key.file_size, key.file_mtime)
+            .map_err(mcp_internal_error)
+    };
+    let stratum_cached =
+        cached(audio::ANALYZER_STRATUM)?.filter(|json| is_whole_track_stratum(json));
+    let essentia_cached =
+        cached(audio::ANALYZER_ESSENTIA)?.filter(|json| is_current_essentia(json));
+    let native_cached = cached(audio::ANALYZER_NATIVE)?.filter(|json| is_current_native(json));
+    let structure_cached =
+        cached(audio::ANALYZER_STRUCTURE)?.filter(|json| is_current_structure(json));
+    let quality_cached = cached(audio::ANALYZER_QUALITY)?.filter(|json| is_current_quality(json));
+
+    let sink = CacheSink::Store(server);
+    // Stratum and structure need the whole signal unless both are cached
+    let decode = SharedDecode::new(
+        &file_path,
+        stratum_cached.is_none() || structure_cached.is_none(),
+    );
+
+    // Stratum, Essentia and the native features (which stand in for Essentia
+    // when it isn't installed) share one decode
+    let (stratum, essentia, native) = tokio::join!(
+        cached_or_run(
+            stratum_cached,
+            &key,
+            audio::ANALYZER_STRATUM,
+            &sink,
+            analyze_stratum(&decode),
+        ),
+        async {
+            match essentia_python.as_deref() {
+                Some(python_path) => {
+                    cached_or_run(
+                        essentia_cached,
+                        &key,
                         audio::ANALYZER_ESSENTIA,
-                        file_size,
-                        file_mtime,
-                        version,
-                        &features_json,
+                        &sink,
+                        async {
+                            audio::run_essentia(python_path, &file_path)
+                                .await
+                                .map_err(|e| e.to_string())
+                        },
                     )
-                    .map_err(|e| mcp_internal_error(format!("Cache write error: {e}")))?;
-                    essentia = Some(
-                        serde_json::to_value(&features)
-                            .map_err(|e| mcp_internal_error(format!("{e}")))?,
-                    );
-                    essentia_cache_hit = Some(false);
+                    .await
                 }
-                Err(e) => essentia_error = Some(e),
+                None => AnalyzerOutcome::default(),
             }
-        }
-    }
+        },
+        async {
+            if essentia_available {
+                return AnalyzerOutcome::default();
+            }
+            cached_or_run(
+                native_cached,
+                &key,
+                audio::ANALYZER_NATIVE,
+                &sink,
+                analyze_native(&decode),
+            )
+            .await
+        },
+    );
+    let stratum_cache_hit = stratum.cache_hit == Some(true);
+    let stratum_dsp = stratum.value.ok_or_else(|| {
+        mcp_internal_error(
+            stratum
+                .error
+                .unwrap_or_else(|| "Analysis error".to_string()),
+        )
+    })?;
+
+    // Structure: laid out on stratum's beat grid, so only reused alongside a
+    // cached stratum result
+    let structure = cached_or_run(
+        structure_cached.filter(|_| stratum_cache_hit),
+        &key,
+        audio::ANALYZER_STRUCTURE,
+        &sink,
+        structure_from_stratum(&decode, &stratum_dsp),
+    )
+    .await;
+
+    // Signal quality: metered during the shared or streamed decode when there
+    // was one, otherwise cached or streamed through the meter
+    let quality = cached_or_run(
+        quality_cached.filter(|_| !decode.is_decoded()),
+        &key,
+        audio::ANALYZER_QUALITY,
+        &sink,
+        decode.quality(),
+    )
+    .await;
 
     let mut result = serde_json::json!({
         "track_id": track.id,
@@ -145,10 +133,19 @@ pub(super) async fn handle_analyze_track_audio(
         "artist": track.artist,
         "stratum_dsp": stratum_dsp,
         "stratum_cache_hit": stratum_cache_hit,
-        "essentia": essentia,
-        "essentia_cache_hit": essentia_cache_hit,
+        "structure": structure.value,
+        "structure_cache_hit": structure.cache_hit,
+        "structure_error": structure.error,
+        "quality": quality.value,
+        "quality_cache_hit": quality.cache_hit,
+        "quality_error": quality.error,
+        "essentia": essentia.value,
+        "essentia_cache_hit": essentia.cache_hit,
         "essentia_available": essentia_available,
-        "essentia_error": essentia_error,
+        "essentia_error": essentia.error,
+        "native_features": native.value,
+        "native_cache_hit": native.cache_hit,
+        "native_error": native.error,
     });
     if !essentia_available {
         result["essentia_setup_hint"] = serde_json::Value::String(essentia_setup_hint());
@@ -158,6 +155,16 @@ pub(super) async fn handle_analyze_track_audio(
     Ok(CallToolResult::success(vec![Content::text(json)]))
 }
 
+/// Structure analysis on the beat grid of a stratum-dsp result.
+async fn structure_from_stratum(
+    decode: &SharedDecode,
+    stratum_dsp: &serde_json::Value,
+) -> Result<audio::TrackStructure, String> {
+    let stratum: audio::StratumResult = serde_json::from_value(stratum_dsp.clone())
+        .map_err(|e| format!("Stratum result parse error: {e}"))?;
+    analyze_track_structure(decode, &stratum).await
+}
+
 pub(super) struct BatchTrackAnalysis {
     pub(super) track_id: String,
     pub(super) title: String,
@@ -167,17 +174,15 @@ pub(super) struct BatchTrackAnalysis {
     pub(super) essentia: Option<serde_json::Value>,
     pub(super) essentia_cache_hit: Option<bool>,
     pub(super) essentia_error: Option<String>,
-}
-
-enum CacheWriteMsg {
-    Audio {
-        file_path: String,
-        analyzer: &'static str,
-        file_size: i64,
-        file_mtime: i64,
-        analyzer_version: String,
-        features_json: String,
-    },
+    pub(super) native_features: Option<serde_json::Value>,
+    pub(super) native_cache_hit: Option<bool>,
+    pub(super) native_error: Option<String>,
+    pub(super) structure: Option<serde_json::Value>,
+    pub(super) structure_cache_hit: Option<bool>,
+    pub(super) structure_error: Option<String>,
+    pub(super) quality: Option<serde_json::Value>,
+    pub(super) quality_cache_hit: Option<bool>,
+    pub(super) quality_error: Option<String>,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -200,155 +205,144 @@ async fn analyze_single_track(
         })
     })?;
 
-    let metadata = std::fs::metadata(&file_path).map_err(|e| {
+    let key = CacheKey::stat(&file_path).map_err(|e| {
         serde_json::json!({
             "track_id": &track_id, "artist": &artist, "title": &title,
             "analyzer": audio::ANALYZER_STRATUM,
-            "error": format!("Cannot stat file: {e}"),
+            "error": e,
         })
     })?;
-    let file_size = metadata.len() as i64;
-    let file_mtime = metadata
-        .modified()
-        .ok()
-        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-        .map(|d| d.as_secs() as i64)
-        .unwrap_or(0);
-
-    // Open read-only cache connection for this task
-    let cache_conn = store::open_read_only(&store_path).map_err(|e| {
-        serde_json::json!({
-            "track_id": &track_id, "artist": &artist, "title": &title,
-            "analyzer": audio::ANALYZER_STRATUM,
-            "error": format!("Cache open error: {e}"),
-        })
-    })?;
-
-    // Check stratum cache
-    let stratum_cached = if skip_cached {
-        check_analysis_cache(
-            &cache_conn,
-            &file_path,
-            audio::ANALYZER_STRATUM,
-            file_size,
-            file_mtime,
-        )
-        .ok()
-        .flatten()
-    } else {
-        None
-    };
-
-    // Check essentia cache
-    let essentia_cached = if skip_cached && essentia_python.is_some() {
-        check_analysis_cache(
-            &cache_conn,
-            &file_path,
-            audio::ANALYZER_ESSENTIA,
-            file_size,
-            file_mtime,
-        )
-        .ok()
-        .flatten()
-    } else {
-        None
-    };
 
-    // Drop the read connection before running analysis
-    drop(cache_conn);
-
-    // Run uncached analyzers in parallel via tokio::join!
-    let stratum_fut = async {
-        if let Some(json_str) = &stratum_cached {
-            let val: serde_json::Value =
-                serde_json::from_str(json_str).map_err(|e| format!("Cache parse error: {e}"))?;
-            return Ok::<(serde_json::Value, bool), String>((val, true));
-        }
-        let analysis = analyze_stratum(&file_path).await?;
-        let features_json =
-            serde_json::to_string(&analysis).map_err(|e| format!("Serialize error: {e}"))?;
-        let val =
-            serde_json::to_value(&analysis).map_err(|e| format!("Serialize error: {e}"))?;
-        let _ = cache_tx
-            .send(CacheWriteMsg::Audio {
-                file_path: file_path.clone(),
-                analyzer: audio::ANALYZER_STRATUM,
-                file_size,
-                file_mtime,
-                analyzer_version: analysis.analyzer_version,
-                features_json,
+    // Read the cache on a read-only connection, closed before analysis runs
+    let (stratum_cached, essentia_cached, native_cached, structure_cached, quality_cached) = {
+        let cache_conn = store::open_read_only(&store_path).map_err(|e| {
+            serde_json::json!({
+                "track_id": &track_id, "artist": &artist, "title": &title,
+                "analyzer": audio::ANALYZER_STRATUM,
+                "error": format!("Cache open error: {e}"),
             })
-            .await;
-        Ok((val, false))
+        })?;
+        let cached = |analyzer: &str| {
+            if !skip_cached {
+                return None;
+            }
+            check_analysis_cache(
+                &cache_conn,
+                &file_path,
+                analyzer,
+                key.file_size,
+                key.file_mtime,
+            )
+            .ok()
+            .flatten()
+        };
+        (
+            cached(audio::ANALYZER_STRATUM).filter(|json| is_whole_track_stratum(json)),
+            cached(audio::ANALYZER_ESSENTIA).filter(|json| is_current_essentia(json)),
+            cached(audio::ANALYZER_NATIVE).filter(|json| is_current_native(json)),
+            cached(audio::ANALYZER_STRUCTURE).filter(|json| is_current_structure(json)),
+            cached(audio::ANALYZER_QUALITY).filter(|json| is_current_quality(json)),
+        )
     };
 
-    let essentia_python_clone = essentia_python.clone();
-    let file_path_clone = file_path.clone();
-    let cache_tx_clone = cache_tx.clone();
-    let essentia_fut = async {
-        let python_path = match &essentia_python_clone {
-            Some(p) => p,
-            None => return (None, None, None), // essentia not available
-        };
-        if let Some(json_str) = &essentia_cached {
-            match serde_json::from_str(json_str) {
-                Ok(val) => return (Some(val), Some(true), None),
-                Err(e) => return (None, None, Some(format!("Cache parse error: {e}"))),
+    let sink = CacheSink::Queue(&cache_tx);
+    // Stratum and structure need the whole signal unless both are cached
+    let decode = SharedDecode::new(
+        &file_path,
+        stratum_cached.is_none() || structure_cached.is_none(),
+    );
+
+    // Run uncached analyzers in parallel on one shared decode
+    let (stratum, essentia, native) = tokio::join!(
+        cached_or_run(
+            stratum_cached,
+            &key,
+            audio::ANALYZER_STRATUM,
+            &sink,
+            analyze_stratum(&decode),
+        ),
+        async {
+            match essentia_python.as_deref() {
+                Some(python_path) => {
+                    cached_or_run(
+                        essentia_cached,
+                        &key,
+                        audio::ANALYZER_ESSENTIA,
+                        &sink,
+                        async {
+                            audio::run_essentia(python_path, &file_path)
+                                .await
+                                .map_err(|e| e.to_string())
+                        },
+                    )
+                    .await
+                }
+                None => AnalyzerOutcome::default(),
             }
-        }
-        match audio::run_essentia(python_path, &file_path_clone)
-            .await
-            .map_err(|e| e.to_string())
-        {
-            Ok(features) => {
-                let version = if features.analyzer_version.is_empty() {
-                    "unknown".to_string()
-                } else {
-                    features.analyzer_version.clone()
-                };
-                let features_json = match serde_json::to_string(&features) {
-                    Ok(j) => j,
-                    Err(e) => return (None, None, Some(format!("Serialize error: {e}"))),
-                };
-                let val = match serde_json::to_value(&features) {
-                    Ok(v) => v,
-                    Err(e) => return (None, None, Some(format!("Serialize error: {e}"))),
-                };
-                let _ = cache_tx_clone
-                    .send(CacheWriteMsg::Audio {
-                        file_path: file_path_clone.clone(),
-                        analyzer: audio::ANALYZER_ESSENTIA,
-                        file_size,
-                        file_mtime,
-                        analyzer_version: version,
-                        features_json,
-                    })
-                    .await;
-                (Some(val), Some(false), None)
+        },
+        async {
+            if essentia_python.is_some() {
+                return AnalyzerOutcome::default();
             }
-            Err(e) => (None, None, Some(e)),
-        }
-    };
-
-    let (stratum_result, (essentia_val, essentia_cache_hit, essentia_error)) =
-        tokio::join!(stratum_fut, essentia_fut);
+            cached_or_run(
+                native_cached,
+                &key,
+                audio::ANALYZER_NATIVE,
+                &sink,
+                analyze_native(&decode),
+            )
+            .await
+        },
+    );
 
-    let (stratum_dsp, stratum_cache_hit) = stratum_result.map_err(|e| {
+    let stratum_cache_hit = stratum.cache_hit == Some(true);
+    let stratum_dsp = stratum.value.ok_or_else(|| {
         serde_json::json!({
             "track_id": &track_id, "artist": &artist, "title": &title,
-            "analyzer": audio::ANALYZER_STRATUM, "error": e,
+            "analyzer": audio::ANALYZER_STRATUM, "error": stratum.error,
         })
     })?;
 
+    // Structure needs the beat grid, so it runs after stratum and is only
+    // reused alongside a cached stratum result
+    let structure = cached_or_run(
+        structure_cached.filter(|_| stratum_cache_hit),
+        &key,
+        audio::ANALYZER_STRUCTURE,
+        &sink,
+        structure_from_stratum(&decode, &stratum_dsp),
+    )
+    .await;
+
+    // Quality comes free with the shared or streamed decode; otherwise it's
+    // cached or streamed on its own
+    let quality = cached_or_run(
+        quality_cached.filter(|_| !decode.is_decoded()),
+        &key,
+        audio::ANALYZER_QUALITY,
+        &sink,
+        decode.quality(),
+    )
+    .await;
+
     Ok(BatchTrackAnalysis {
         track_id,
         title,
         artist,
         stratum_dsp,
         stratum_cache_hit,
-        essentia: essentia_val,
-        essentia_cache_hit,
-        essentia_error,
+        essentia: essentia.value,
+        essentia_cache_hit: essentia.cache_hit,
+        essentia_error: essentia.error,
+        native_features: native.value,
+        native_cache_hit: native.cache_hit,
+        native_error: native.error,
+        structure: structure.value,
+        structure_cache_hit: structure.cache_hit,
+        structure_error: structure.error,
+        quality: quality.value,
+        quality_cache_hit: quality.cache_hit,
+        quality_error: quality.error,
     })
 }
 
@@ -472,6 +466,15 @@ pub(super) async fn handle_analyze_audio_batch(
     let mut essentia_analyzed = 0usize;
     let mut essentia_cached = 0usize;
     let mut essentia_failed = 0usize;
+    let mut native_analyzed = 0usize;
+    let mut native_cached = 0usize;
+    let mut native_failed = 0usize;
+    let mut structure_analyzed = 0usize;
+    let mut structure_cached = 0usize;
+    let mut structure_failed = 0usize;
+    let mut quality_analyzed = 0usize;
+    let mut quality_cached = 0usize;
+    let mut quality_failed = 0usize;
     let mut rows: Vec<BatchTrackAnalysis> = Vec::new();
 
     for handle in handles {
@@ -495,6 +498,45 @@ pub(super) async fn handle_analyze_audio_batch(
                         "error": err,
                     }));
                 }
+                match row.native_cache_hit {
+                    Some(true) => native_cached += 1,
+                    Some(false) => native_analyzed += 1,
+                    None if row.native_error.is_some() => native_failed += 1,
+                    _ => {}
+                }
+                if let Some(ref err) = row.native_error {
+                    progress.failures.push(serde_json::json!({
+                        "track_id": &row.track_id, "artist": &row.artist,
+                        "title": &row.title, "analyzer": audio::ANALYZER_NATIVE,
+                        "error": err,
+                    }));
+                }
+                match row.structure_cache_hit {
+                    Some(true) => structure_cached += 1,
+                    Some(false) => structure_analyzed += 1,
+                    None if row.structure_error.is_some() => structure_failed += 1,
+                    _ => {}
+                }
+                if let Some(ref err) = row.structure_error {
+                    progress.failures.push(serde_json::json!({
+                        "track_id": &row.track_id, "artist": &row.artist,
+                        "title": &row.title, "analyzer": audio::ANALYZER_STRUCTURE,
+                        "error": err,
+                    }));
+                }
+                match row.quality_cache_hit {
+                    Some(true) => quality_cached += 1,
+                    Some(false) => quality_analyzed += 1,
+                    None if row.quality_error.is_some() => quality_failed += 1,
+                    _ => {}
+                }
+                if let Some(ref err) = row.quality_error {
+                    progress.failures.push(serde_json::json!({
+                        "track_id": &row.track_id, "artist": &row.artist,
+                        "title": &row.title, "analyzer": audio::ANALYZER_QUALITY,
+                        "error": err,
+                    }));
+                }
                 rows.push(row);
             }
             Ok(Err(failure)) => {
@@ -521,10 +563,19 @@ pub(super) async fn handle_analyze_audio_batch(
                 "artist": row.artist,
                 "stratum_dsp": row.stratum_dsp,
                 "stratum_cache_hit": row.stratum_cache_hit,
+                "structure": row.structure,
+                "structure_cache_hit": row.structure_cache_hit,
+                "structure_error": row.structure_error,
+                "quality": row.quality,
+                "quality_cache_hit": row.quality_cache_hit,
+                "quality_error": row.quality_error,
                 "essentia": row.essentia,
                 "essentia_cache_hit": row.essentia_cache_hit,
                 "essentia_available": essentia_available,
                 "essentia_error": row.essentia_error,
+                "native_features": row.native_features,
+                "native_cache_hit": row.native_cache_hit,
+                "native_error": row.native_error,
             })
         })
         .collect();
@@ -539,6 +590,15 @@ pub(super) async fn handle_analyze_audio_batch(
             "essentia_analyzed": essentia_analyzed,
             "essentia_cached": essentia_cached,
             "essentia_failed": essentia_failed,
+            "native_analyzed": native_analyzed,
+            "native_cached": native_cached,
+            "native_failed": native_failed,
+            "structure_analyzed": structure_analyzed,
+            "structure_cached": structure_cached,
+            "structure_failed": structure_failed,
+            "quality_analyzed": quality_analyzed,
+            "quality_cached": quality_cached,
+            "quality_failed": quality_failed,
             "concurrency": concurrency,
         },
         "results": results,
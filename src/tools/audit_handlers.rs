@@ -17,9 +17,11 @@ pub(super) async fn handle_audit_state(
         AuditOperation::Scan {
             path_prefix,
             revalidate,
+            analyze,
             skip_issue_types,
         } => {
             let revalidate = revalidate.unwrap_or(false);
+            let analyze = analyze.unwrap_or(false);
             let skip: HashSet<audit::IssueType> = skip_issue_types
                 .unwrap_or_default()
                 .iter()
@@ -49,7 +51,14 @@ pub(super) async fn handle_audit_state(
                     }
                 });
 
-                audit::scan(&conn, &path_prefix, revalidate, &skip, imported.as_ref())
+                audit::scan(
+                    &conn,
+                    &path_prefix,
+                    revalidate,
+                    analyze,
+                    &skip,
+                    imported.as_ref(),
+                )
             })
             .await
             .map_err(|e| mcp_internal_error(format!("join error: {e}")))?
@@ -125,5 +134,38 @@ pub(super) async fn handle_audit_state(
                 .map_err(|e| mcp_internal_error(format!("{e}")))?;
             Ok(CallToolResult::success(vec![Content::text(json)]))
         }
+
+        AuditOperation::Orphans {
+            path_prefix,
+            limit,
+            xml_output_path,
+        } => {
+            let Some(db_path) = rekordbox_db_path else {
+                return Err(mcp_internal_error(
+                    "Rekordbox database not found. Set REKORDBOX_DB_PATH env var.".to_string(),
+                ));
+            };
+            let limit = limit.unwrap_or(100) as usize;
+
+            let report = tokio::task::spawn_blocking(move || {
+                let rb_conn = db::open(&db_path)
+                    .map_err(|e| format!("Failed to open Rekordbox database: {e}"))?;
+                let imported =
+                    db::imported_paths(&rb_conn).map_err(|e| format!("DB error: {e}"))?;
+                audit::find_orphans(
+                    &path_prefix,
+                    &imported,
+                    limit,
+                    xml_output_path.as_deref().map(std::path::Path::new),
+                )
+            })
+            .await
+            .map_err(|e| mcp_internal_error(format!("join error: {e}")))?
+            .map_err(mcp_internal_error)?;
+
+            let json = serde_json::to_string_pretty(&report)
+                .map_err(|e| mcp_internal_error(format!("{e}")))?;
+            Ok(CallToolResult::success(vec![Content::text(json)]))
+        }
     }
 }
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use rmcp::ErrorData as McpError;
+use rmcp::model::{CallToolResult, Content};
+
+use super::*;
+use crate::audio;
+use crate::db;
+use crate::duplicates::{self, LibraryCopy, TempoKeySignature};
+use crate::store;
+use crate::types::{MatchConfidence, TrackChange};
+
+/// Default number of duplicate clusters returned per call.
+const DEFAULT_DUPLICATE_LIMIT: u32 = 50;
+/// Default number of uncached copies fingerprinted per call.
+const DEFAULT_DUPLICATE_FINGERPRINT_LIMIT: u32 = 50;
+
+/// Tempo and key from a cached stratum-dsp analysis, if the track has one.
+fn cached_tempo_key(
+    store_conn: &Connection,
+    file_path: &str,
+) -> Result<Option<TempoKeySignature>, McpError> {
+    let cache_key = resolve_file_path(file_path).unwrap_or_else(|_| file_path.to_string());
+    let cached = store::get_audio_analysis(store_conn, &cache_key, audio::ANALYZER_STRATUM)
+        .map_err(|e| mcp_internal_error(format!("Cache read error: {e}")))?;
+    Ok(cached
+        .and_then(|entry| serde_json::from_str::<audio::StratumResult>(&entry.features_json).ok())
+        .map(|analysis| TempoKeySignature {
+            bpm: analysis.bpm,
+            key_camelot: analysis.key_camelot,
+        }))
+}
+
+pub(super) async fn handle_find_duplicates(
+    server: &ReklawdboxServer,
+    params: FindDuplicatesParams,
+) -> Result<CallToolResult, McpError> {
+    let limit = params.limit.unwrap_or(DEFAULT_DUPLICATE_LIMIT) as usize;
+    let fingerprint_limit = params
+        .fingerprint_limit
+        .unwrap_or(DEFAULT_DUPLICATE_FINGERPRINT_LIMIT) as usize;
+
+    let (scanned_tracks, mut candidates, memberships, playlist_names) = {
+        let conn = server.rekordbox_conn()?;
+        let search = params.filters.into_search_params(true, None, None);
+        let tracks = db::search_tracks_unbounded(&conn, &search)
+            .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+        let scanned_tracks = tracks.len();
+        let mut candidates = duplicates::candidate_tracks(tracks);
+        db::attach_cues(&conn, &mut candidates)
+            .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+        let ids: Vec<String> = candidates.iter().map(|t| t.id.clone()).collect();
+        let memberships = db::get_track_playlist_ids(&conn, &ids)
+            .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+        let playlist_names: HashMap<String, String> = db::get_playlists(&conn)
+            .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?
+            .into_iter()
+            .map(|p| (p.id, p.name))
+            .collect();
+        (scanned_tracks, candidates, memberships, playlist_names)
+    };
+
+    let paths: Vec<String> = candidates.iter().map(|t| t.file_path.clone()).collect();
+    let mut batch = fingerprints_for_paths(server, &paths, fingerprint_limit).await?;
+
+    let store_conn = server.cache_store_conn()?;
+    let mut copies = Vec::with_capacity(candidates.len());
+    for mut track in candidates.drain(..) {
+        let tempo_key = cached_tempo_key(&store_conn, &track.file_path)?;
+        // A missing file can still be compared by the fingerprint cached
+        // before it went missing.
+        let fingerprint = match batch.fingerprints.remove(&track.file_path) {
+            Some(fp) => Some(fp),
+            None => cached_fingerprint(&store_conn, &track.file_path)?,
+        };
+        let playlists = memberships
+            .get(&track.id)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| playlist_names.get(id).cloned())
+            .collect();
+        let cue_count = std::mem::take(&mut track.cues).len();
+        copies.push(LibraryCopy {
+            track,
+            playlists,
+            cue_count,
+            tempo_key,
+            fingerprint,
+        });
+    }
+    drop(store_conn);
+
+    let clusters = duplicates::find_duplicates(copies);
+    let count = |confidence: MatchConfidence| {
+        clusters
+            .iter()
+            .filter(|cluster| cluster.confidence == confidence)
+            .count()
+    };
+    let summary = serde_json::json!({
+        "scanned_tracks": scanned_tracks,
+        "clusters": clusters.len(),
+        "duplicate_tracks": clusters.iter().map(|c| c.copies.len() - 1).sum::<usize>(),
+        "high": count(MatchConfidence::High),
+        "medium": count(MatchConfidence::Medium),
+        "low": count(MatchConfidence::Low),
+        "fingerprints_computed": batch.computed,
+        "fingerprints_deferred": batch.deferred,
+    });
+
+    let mut staged = 0;
+    if params.stage_merges.unwrap_or(false) {
+        let changes: Vec<TrackChange> = clusters
+            .iter()
+            .filter(|cluster| cluster.confidence == MatchConfidence::High)
+            .flat_map(|cluster| {
+                cluster.duplicate_ids().map(|id| TrackChange {
+                    track_id: id.to_string(),
+                    merge_into: Some(cluster.keep.clone()),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        if !changes.is_empty() {
+            staged = server.state.changes.stage("find_duplicates", changes).0;
+            server.persist_staged_changes();
+        }
+    }
+
+    let result = serde_json::json!({
+        "summary": summary,
+        "clusters": clusters.iter().take(limit).collect::<Vec<_>>(),
+        "staged": staged,
+    });
+    let json =
+        serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
@@ -60,7 +60,12 @@ mod tests {
                 sample_rate: 44100,
                 file_kind: crate::types::FileKind::Flac,
                 date_added: String::new(),
+                my_tags: Vec::new(),
+                hot_cue_banks: Vec::new(),
+                related_track_lists: Vec::new(),
                 position: None,
+                cues: Vec::new(),
+                tempo_markers: Vec::new(),
             },
             camelot_key: parse_camelot_key(key),
             key_display: key.to_string(),
@@ -71,6 +76,9 @@ mod tests {
             loudness_range,
             canonical_genre: Some(genre.to_string()),
             genre_family: genre_family_for(genre),
+            opening: None,
+            closing: None,
+            played_before: HashMap::new(),
         }
     }
 
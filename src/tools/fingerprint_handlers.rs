@@ -0,0 +1,268 @@
+use std::collections::{HashMap, HashSet};
+
+use rmcp::ErrorData as McpError;
+use rmcp::model::{CallToolResult, Content};
+
+use super::*;
+use crate::audio;
+use crate::db;
+use crate::fingerprint::{self, FINGERPRINT_VERSION, Fingerprint};
+use crate::store;
+
+/// Max tracks plus files compared in one call (all pairs are scored).
+const MAX_FINGERPRINT_ITEMS: usize = 20;
+
+fn parse_fingerprint(features_json: &str) -> Option<Fingerprint> {
+    serde_json::from_str::<Fingerprint>(features_json)
+        .ok()
+        .filter(|fp| fp.version == FINGERPRINT_VERSION)
+}
+
+/// Cached fingerprint for `file_path`, without checking that the file is
+/// unchanged (or still exists). Used to match moved and duplicate files.
+pub(super) fn cached_fingerprint(
+    store_conn: &Connection,
+    file_path: &str,
+) -> Result<Option<Fingerprint>, McpError> {
+    let cache_key = resolve_file_path(file_path).unwrap_or_else(|_| file_path.to_string());
+    let cached = store::get_audio_analysis(store_conn, &cache_key, audio::ANALYZER_FINGERPRINT)
+        .map_err(|e| mcp_internal_error(format!("Cache read error: {e}")))?;
+    Ok(cached.and_then(|entry| parse_fingerprint(&entry.features_json)))
+}
+
+/// Fingerprints computed at once by `fingerprints_for_paths`.
+const FINGERPRINT_CONCURRENCY: usize = 4;
+
+/// Result of `fingerprints_for_paths`, keyed by the path as given.
+pub(super) struct FingerprintBatch {
+    pub fingerprints: HashMap<String, Fingerprint>,
+    /// Files decoded and fingerprinted (now cached).
+    pub computed: usize,
+    /// Uncached files left for a later call by `max_computed`.
+    pub deferred: usize,
+}
+
+/// Fingerprints for many files. Fresh cache entries are reused; up to
+/// `max_computed` uncached files are fingerprinted concurrently and cached.
+/// Missing or undecodable files are left out.
+pub(super) async fn fingerprints_for_paths(
+    server: &ReklawdboxServer,
+    paths: &[String],
+    max_computed: usize,
+) -> Result<FingerprintBatch, McpError> {
+    let mut batch = FingerprintBatch {
+        fingerprints: HashMap::new(),
+        computed: 0,
+        deferred: 0,
+    };
+    // (path as given, resolved path, size, mtime)
+    let mut uncached = Vec::new();
+    {
+        let store = server.cache_store_conn()?;
+        let mut seen = HashSet::new();
+        for raw_path in paths {
+            if !seen.insert(raw_path) {
+                continue;
+            }
+            let Ok(file_path) = resolve_file_path(raw_path) else {
+                continue;
+            };
+            let Ok(CacheKey {
+                file_size,
+                file_mtime,
+                ..
+            }) = CacheKey::stat(&file_path)
+            else {
+                continue;
+            };
+            let cached = check_analysis_cache(
+                &store,
+                &file_path,
+                audio::ANALYZER_FINGERPRINT,
+                file_size,
+                file_mtime,
+            )
+            .map_err(mcp_internal_error)?;
+            match cached.as_deref().and_then(parse_fingerprint) {
+                Some(fp) => {
+                    batch.fingerprints.insert(raw_path.clone(), fp);
+                }
+                None => uncached.push((raw_path.clone(), file_path, file_size, file_mtime)),
+            }
+        }
+    }
+    batch.deferred = uncached.len().saturating_sub(max_computed);
+    uncached.truncate(max_computed);
+
+    let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(FINGERPRINT_CONCURRENCY));
+    let mut handles = Vec::with_capacity(uncached.len());
+    for (raw_path, file_path, file_size, file_mtime) in uncached {
+        let sem = semaphore.clone();
+        handles.push(tokio::task::spawn(async move {
+            let _permit = sem.acquire().await.unwrap();
+            let path = file_path.clone();
+            let fp = tokio::task::spawn_blocking(move || fingerprint::fingerprint_file(&path))
+                .await
+                .ok()
+                .and_then(Result::ok);
+            (raw_path, file_path, file_size, file_mtime, fp)
+        }));
+    }
+    for handle in handles {
+        let (raw_path, file_path, file_size, file_mtime, fp) = handle
+            .await
+            .map_err(|e| mcp_internal_error(format!("join error: {e}")))?;
+        let Some(fp) = fp else { continue };
+        let features_json =
+            serde_json::to_string(&fp).map_err(|e| mcp_internal_error(format!("{e}")))?;
+        let store = server.cache_store_conn()?;
+        store::set_audio_analysis(
+            &store,
+            &file_path,
+            audio::ANALYZER_FINGERPRINT,
+            file_size,
+            file_mtime,
+            FINGERPRINT_VERSION,
+            &features_json,
+        )
+        .map_err(|e| mcp_internal_error(format!("Cache write error: {e}")))?;
+        batch.computed += 1;
+        batch.fingerprints.insert(raw_path, fp);
+    }
+    Ok(batch)
+}
+
+/// Fingerprint an audio file, reusing a fresh cache entry when allowed.
+/// Returns the fingerprint and whether it came from the cache.
+async fn fingerprint_for_path(
+    server: &ReklawdboxServer,
+    raw_path: &str,
+    skip_cached: bool,
+) -> Result<(String, Fingerprint, bool), String> {
+    let file_path = resolve_file_path(raw_path).map_err(|e| e.message.to_string())?;
+    let CacheKey {
+        file_size,
+        file_mtime,
+        ..
+    } = CacheKey::stat(&file_path)?;
+
+    if skip_cached {
+        let store = server
+            .cache_store_conn()
+            .map_err(|e| e.message.to_string())?;
+        let cached = check_analysis_cache(
+            &store,
+            &file_path,
+            audio::ANALYZER_FINGERPRINT,
+            file_size,
+            file_mtime,
+        )?;
+        if let Some(fp) = cached.as_deref().and_then(parse_fingerprint) {
+            return Ok((file_path, fp, true));
+        }
+    }
+
+    let path = file_path.clone();
+    let fp = tokio::task::spawn_blocking(move || fingerprint::fingerprint_file(&path))
+        .await
+        .map_err(|e| format!("Fingerprint task failed: {e}"))?
+        .map_err(|e| format!("Fingerprint error: {e}"))?;
+    let features_json = serde_json::to_string(&fp).map_err(|e| e.to_string())?;
+    let store = server
+        .cache_store_conn()
+        .map_err(|e| e.message.to_string())?;
+    store::set_audio_analysis(
+        &store,
+        &file_path,
+        audio::ANALYZER_FINGERPRINT,
+        file_size,
+        file_mtime,
+        FINGERPRINT_VERSION,
+        &features_json,
+    )
+    .map_err(|e| format!("Cache write error: {e}"))?;
+    Ok((file_path, fp, false))
+}
+
+pub(super) async fn handle_fingerprint_match(
+    server: &ReklawdboxServer,
+    params: FingerprintMatchParams,
+) -> Result<CallToolResult, McpError> {
+    let skip_cached = params.skip_cached.unwrap_or(true);
+    let track_ids = params.track_ids.unwrap_or_default();
+    let paths = params.paths.unwrap_or_default();
+    let count = track_ids.len() + paths.len();
+    if !(2..=MAX_FINGERPRINT_ITEMS).contains(&count) {
+        return Err(McpError::invalid_params(
+            format!(
+                "Provide 2-{MAX_FINGERPRINT_ITEMS} track_ids and/or paths to compare, got {count}"
+            ),
+            None,
+        ));
+    }
+
+    // (label key, label, raw path)
+    let mut sources: Vec<(&str, String, String)> = Vec::with_capacity(count);
+    if !track_ids.is_empty() {
+        let conn = server.rekordbox_conn()?;
+        let tracks = db::get_tracks_by_ids(&conn, &track_ids)
+            .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+        for id in &track_ids {
+            let track = tracks
+                .iter()
+                .find(|t| &t.id == id)
+                .ok_or_else(|| McpError::invalid_params(format!("Track '{id}' not found"), None))?;
+            sources.push(("track_id", id.clone(), track.file_path.clone()));
+        }
+    }
+    sources.extend(paths.into_iter().map(|p| ("path", p.clone(), p)));
+
+    let mut items = Vec::with_capacity(sources.len());
+    let mut fingerprints: Vec<(String, Fingerprint)> = Vec::new();
+    for (key, label, raw_path) in sources {
+        let mut item = serde_json::json!({ key: label });
+        match fingerprint_for_path(server, &raw_path, skip_cached).await {
+            Ok((file_path, fp, cache_hit)) => {
+                item["file_path"] = serde_json::json!(file_path);
+                item["duration_seconds"] = serde_json::json!(fp.duration_seconds);
+                item["cache_hit"] = serde_json::json!(cache_hit);
+                fingerprints.push((label, fp));
+            }
+            Err(e) => item["error"] = serde_json::json!(e),
+        }
+        items.push(item);
+    }
+
+    let mut pairs = Vec::new();
+    for (i, (label_a, a)) in fingerprints.iter().enumerate() {
+        for (label_b, b) in &fingerprints[i + 1..] {
+            let mut pair = serde_json::json!({ "a": label_a, "b": label_b });
+            match fingerprint::compare(a, b) {
+                Some(comparison) => {
+                    let fields = serde_json::to_value(comparison)
+                        .map_err(|e| mcp_internal_error(format!("{e}")))?;
+                    if let (Some(pair), Some(fields)) = (pair.as_object_mut(), fields.as_object()) {
+                        pair.extend(fields.clone());
+                    }
+                }
+                None => pair["error"] = serde_json::json!("Fingerprints too short to compare"),
+            }
+            pairs.push(pair);
+        }
+    }
+    pairs.sort_by(|x, y| {
+        let similarity = |v: &serde_json::Value| v["similarity"].as_f64().unwrap_or(-1.0);
+        similarity(y).total_cmp(&similarity(x))
+    });
+
+    let result = serde_json::json!({
+        "items": items,
+        "pairs": pairs,
+        "match_threshold": {
+            "max_bit_error_rate": fingerprint::MATCH_MAX_BIT_ERROR_RATE,
+        },
+    });
+    let json =
+        serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
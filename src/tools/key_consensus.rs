@@ -0,0 +1,200 @@
+//! Key consensus across Rekordbox, stratum-dsp, Essentia and Beatport.
+//!
+//! Every key is folded to Camelot via `key_to_camelot`, weighted by its
+//! source and, for the analysers, by their own confidence. The consensus is
+//! the key with the most weight behind it; readings a relative major/minor
+//! or a fifth away — the usual detector confusions — lend it partial
+//! support. Agreement is the share of weight that reads the consensus key
+//! exactly.
+
+use serde::Serialize;
+
+use super::scoring::{CamelotKey, camelot_to_musical, format_camelot, key_to_camelot};
+use crate::types::MatchConfidence;
+
+/// Support a relative or fifth-away reading lends a candidate key.
+const CONFUSION_SUPPORT: f64 = 0.3;
+const HIGH_AGREEMENT: f64 = 0.7;
+const MEDIUM_AGREEMENT: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum KeySource {
+    Rekordbox,
+    Stratum,
+    Essentia,
+    Beatport,
+}
+
+impl KeySource {
+    fn name(self) -> &'static str {
+        match self {
+            KeySource::Rekordbox => "rekordbox",
+            KeySource::Stratum => "stratum",
+            KeySource::Essentia => "essentia",
+            KeySource::Beatport => "beatport",
+        }
+    }
+
+    fn base_weight(self) -> f64 {
+        match self {
+            KeySource::Beatport => 1.0,
+            KeySource::Stratum => 0.9,
+            KeySource::Essentia => 0.8,
+            KeySource::Rekordbox => 0.7,
+        }
+    }
+}
+
+/// How a reading relates to another key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum KeyRelation {
+    Same,
+    /// Same Camelot number, other mode (Am / C).
+    Relative,
+    /// Adjacent Camelot number, same mode (Am / Em).
+    Fifth,
+    Unrelated,
+}
+
+pub(super) fn key_relation(a: CamelotKey, b: CamelotKey) -> KeyRelation {
+    let distance = (a.number as i16 - b.number as i16).rem_euclid(12);
+    if a == b {
+        KeyRelation::Same
+    } else if a.number == b.number {
+        KeyRelation::Relative
+    } else if a.letter == b.letter && (distance == 1 || distance == 11) {
+        KeyRelation::Fifth
+    } else {
+        KeyRelation::Unrelated
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct KeyReading {
+    pub(super) source: KeySource,
+    pub(super) raw: String,
+    pub(super) key: CamelotKey,
+    pub(super) weight: f64,
+}
+
+impl KeyReading {
+    /// A reading from `raw`, scaled by the source's own 0-1 confidence where
+    /// it reports one. `None` when the key does not parse.
+    pub(super) fn new(source: KeySource, raw: &str, confidence: Option<f64>) -> Option<Self> {
+        let key = key_to_camelot(raw)?;
+        let scale = confidence.map_or(1.0, |c| 0.25 + 0.75 * c.clamp(0.0, 1.0));
+        Some(Self {
+            source,
+            raw: raw.trim().to_string(),
+            key,
+            weight: source.base_weight() * scale,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct KeyConsensus {
+    pub(super) key: CamelotKey,
+    /// Share of reading weight on the consensus key, 0-1.
+    pub(super) agreement: f64,
+    pub(super) level: MatchConfidence,
+    /// Library key to stage, in musical notation, when Rekordbox disagrees
+    /// with the consensus or has no key.
+    pub(super) corrected_key: Option<String>,
+    pub(super) readings: Vec<KeyReading>,
+    pub(super) explanation: Vec<String>,
+}
+
+impl KeyConsensus {
+    pub(super) fn relation_of(&self, reading: &KeyReading) -> KeyRelation {
+        key_relation(reading.key, self.key)
+    }
+
+    /// Readings that are not the consensus key.
+    pub(super) fn disputed(&self) -> impl Iterator<Item = &KeyReading> {
+        self.readings.iter().filter(|r| r.key != self.key)
+    }
+}
+
+/// Consensus over `readings`; `None` when there are none.
+pub(super) fn key_consensus(readings: Vec<KeyReading>) -> Option<KeyConsensus> {
+    let total: f64 = readings.iter().map(|r| r.weight).sum();
+    if readings.is_empty() || total <= 0.0 {
+        return None;
+    }
+    let support = |candidate: CamelotKey| -> f64 {
+        readings
+            .iter()
+            .map(|r| match key_relation(r.key, candidate) {
+                KeyRelation::Same => r.weight,
+                KeyRelation::Relative | KeyRelation::Fifth => CONFUSION_SUPPORT * r.weight,
+                KeyRelation::Unrelated => 0.0,
+            })
+            .sum()
+    };
+    // Ties go to the earlier reading; with Rekordbox listed first a tie
+    // never produces a correction.
+    let key = readings
+        .iter()
+        .map(|r| r.key)
+        .fold(None, |best: Option<(CamelotKey, f64)>, candidate| {
+            let score = support(candidate);
+            match best {
+                Some((_, best_score)) if best_score >= score => best,
+                _ => Some((candidate, score)),
+            }
+        })
+        .map(|(key, _)| key)?;
+
+    let agreeing: Vec<&KeyReading> = readings.iter().filter(|r| r.key == key).collect();
+    let agreement = agreeing.iter().map(|r| r.weight).sum::<f64>() / total;
+    let agreement = (agreement * 100.0).round() / 100.0;
+    let level = if agreement >= HIGH_AGREEMENT && agreeing.len() >= 2 {
+        MatchConfidence::High
+    } else if agreement >= MEDIUM_AGREEMENT {
+        MatchConfidence::Medium
+    } else {
+        MatchConfidence::Low
+    };
+
+    let mut explanation = vec![format!(
+        "{} ({}) read by {}",
+        format_camelot(key),
+        camelot_to_musical(key),
+        agreeing
+            .iter()
+            .map(|r| r.source.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )];
+    for reading in readings.iter().filter(|r| r.key != key) {
+        let relation = match key_relation(reading.key, key) {
+            KeyRelation::Relative => "the relative major/minor",
+            KeyRelation::Fifth => "a fifth away",
+            KeyRelation::Same | KeyRelation::Unrelated => "unrelated",
+        };
+        explanation.push(format!(
+            "{} reads {} ({}), {relation}",
+            reading.source.name(),
+            reading.raw,
+            format_camelot(reading.key)
+        ));
+    }
+
+    let rekordbox = readings.iter().find(|r| r.source == KeySource::Rekordbox);
+    let corrected_key = match rekordbox {
+        Some(reading) if reading.key == key => None,
+        _ => Some(camelot_to_musical(key)),
+    };
+
+    Some(KeyConsensus {
+        key,
+        agreement,
+        level,
+        corrected_key,
+        readings,
+        explanation,
+    })
+}
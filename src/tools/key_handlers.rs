@@ -0,0 +1,180 @@
+use rmcp::ErrorData as McpError;
+use rmcp::model::{CallToolResult, Content};
+
+use super::key_consensus::{self, KeyConsensus, KeyReading, KeyRelation, KeySource};
+use super::*;
+use crate::audio;
+use crate::store;
+use crate::types::{Track, TrackChange};
+
+/// Key readings for a track from the library and the analysis and enrichment
+/// caches, Rekordbox first. Nothing is analysed or looked up here.
+fn cached_key_readings(
+    store_conn: &Connection,
+    track: &Track,
+) -> Result<Vec<KeyReading>, McpError> {
+    let cache_key = resolve_file_path(&track.file_path).unwrap_or_else(|_| track.file_path.clone());
+    let read_analysis = |analyzer: &str| {
+        store::get_audio_analysis(store_conn, &cache_key, analyzer)
+            .map_err(|e| mcp_internal_error(format!("Cache read error: {e}")))
+    };
+
+    let mut readings = Vec::new();
+    readings.extend(KeyReading::new(KeySource::Rekordbox, &track.key, None));
+    if let Some(stratum) = read_analysis(audio::ANALYZER_STRATUM)?
+        .and_then(|entry| serde_json::from_str::<audio::StratumResult>(&entry.features_json).ok())
+    {
+        let confidence = (stratum.key_confidence * stratum.key_clarity)
+            .max(0.0)
+            .sqrt();
+        readings.extend(KeyReading::new(
+            KeySource::Stratum,
+            &stratum.key_camelot,
+            Some(confidence),
+        ));
+    }
+    if let Some(essentia) = read_analysis(audio::ANALYZER_ESSENTIA)?
+        .and_then(|entry| serde_json::from_str::<audio::EssentiaOutput>(&entry.features_json).ok())
+        && let Some(key) = essentia.key_essentia.as_deref()
+    {
+        readings.extend(KeyReading::new(
+            KeySource::Essentia,
+            key,
+            essentia.key_strength,
+        ));
+    }
+    let beatport = cached_beatport(store_conn, track).map_err(mcp_internal_error)?;
+    if let Some(beatport) = beatport {
+        readings.extend(KeyReading::new(KeySource::Beatport, &beatport.key, None));
+    }
+    Ok(readings)
+}
+
+fn track_report(track: &Track, consensus: &KeyConsensus) -> serde_json::Value {
+    let readings: Vec<serde_json::Value> = consensus
+        .readings
+        .iter()
+        .map(|reading| {
+            serde_json::json!({
+                "source": reading.source,
+                "key": reading.raw,
+                "camelot": format_camelot(reading.key),
+                "weight": (reading.weight * 100.0).round() / 100.0,
+                "relation": consensus.relation_of(reading),
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "track_id": track.id,
+        "title": track.title,
+        "artist": track.artist,
+        "current_key": track.key,
+        "consensus_key": camelot_to_musical(consensus.key),
+        "consensus_camelot": format_camelot(consensus.key),
+        "agreement": consensus.agreement,
+        "level": consensus.level,
+        "corrected_key": consensus.corrected_key,
+        "readings": readings,
+        "explanation": consensus.explanation,
+    })
+}
+
+pub(super) fn handle_reconcile_keys(
+    server: &ReklawdboxServer,
+    params: ReconcileKeysParams,
+) -> Result<CallToolResult, McpError> {
+    let limit = params.limit.unwrap_or(DEFAULT_RECONCILE_LIMIT) as usize;
+
+    let tracks = resolve_reconcile_tracks(
+        server,
+        params.track_ids.as_deref(),
+        params.playlist_id.as_deref(),
+        params.filters,
+        params.max_tracks,
+        params.offset,
+    )?;
+
+    let store_conn = server.cache_store_conn()?;
+    let mut reconciled = Vec::new();
+    for track in &tracks {
+        let readings = cached_key_readings(&store_conn, track)?;
+        if let Some(consensus) = key_consensus::key_consensus(readings) {
+            reconciled.push((track, consensus));
+        }
+    }
+    drop(store_conn);
+
+    let confusions = |relation: KeyRelation| {
+        reconciled
+            .iter()
+            .flat_map(|(_, c)| c.disputed().filter(move |r| c.relation_of(r) == relation))
+            .count()
+    };
+    let corrected = || reconciled.iter().filter(|(_, c)| c.corrected_key.is_some());
+    let unanimous = reconciled
+        .iter()
+        .filter(|(_, c)| c.disputed().next().is_none())
+        .count();
+    let mean_agreement = if reconciled.is_empty() {
+        None
+    } else {
+        let mean =
+            reconciled.iter().map(|(_, c)| c.agreement).sum::<f64>() / reconciled.len() as f64;
+        Some((mean * 100.0).round() / 100.0)
+    };
+    let mut summary = serde_json::json!({
+        "scanned_tracks": tracks.len(),
+        "reconciled": reconciled.len(),
+        "no_key_evidence": tracks.len() - reconciled.len(),
+        "unanimous": unanimous,
+        "disputed": reconciled.len() - unanimous,
+        "mean_agreement": mean_agreement,
+        "corrections": corrected().count(),
+        "confusions": {
+            "relative": confusions(KeyRelation::Relative),
+            "fifth": confusions(KeyRelation::Fifth),
+            "unrelated": confusions(KeyRelation::Unrelated),
+        },
+    });
+    add_confidence_counts(&mut summary, corrected().map(|(_, c)| c.level));
+    if let Some(playlist_id) = &params.playlist_id {
+        summary["playlist_id"] = serde_json::json!(playlist_id);
+    }
+
+    let mut staged = 0;
+    if params.stage_corrections.unwrap_or(false) {
+        staged = stage_high_confidence(
+            server,
+            "reconcile_keys",
+            reconciled.iter().filter_map(|(track, consensus)| {
+                let change = TrackChange {
+                    track_id: track.id.clone(),
+                    key: Some(consensus.corrected_key.clone()?),
+                    ..Default::default()
+                };
+                Some((consensus.level, change))
+            }),
+        );
+    }
+
+    // Most contested first; unanimous tracks are only counted.
+    let mut disputed: Vec<_> = reconciled
+        .iter()
+        .filter(|(_, c)| c.disputed().next().is_some())
+        .collect();
+    disputed.sort_by(|(_, a), (_, b)| a.agreement.total_cmp(&b.agreement));
+    let reports: Vec<serde_json::Value> = disputed
+        .iter()
+        .take(limit)
+        .map(|(track, consensus)| track_report(track, consensus))
+        .collect();
+
+    let result = serde_json::json!({
+        "summary": summary,
+        "tracks": reports,
+        "staged": staged,
+    });
+    let json =
+        serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
@@ -18,7 +18,9 @@ pub(super) fn handle_search_tracks(
         params.offset,
     );
     search.playlist = params.playlist;
-    let tracks = db::search_tracks(&conn, &search)
+    let mut tracks = db::search_tracks(&conn, &search)
+        .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+    db::attach_track_lists(&conn, &mut tracks)
         .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
     let json =
         serde_json::to_string_pretty(&tracks).map_err(|e| mcp_internal_error(format!("{e}")))?;
@@ -32,7 +34,9 @@ pub(super) fn handle_get_track(
     let track = db::get_track(&conn, &params.track_id)
         .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
     match track {
-        Some(t) => {
+        Some(mut t) => {
+            db::attach_track_lists(&conn, std::slice::from_mut(&mut t))
+                .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
             let json =
                 serde_json::to_string_pretty(&t).map_err(|e| mcp_internal_error(format!("{e}")))?;
             Ok(CallToolResult::success(vec![Content::text(json)]))
@@ -58,7 +62,49 @@ pub(super) fn handle_get_playlist_tracks(
     conn: MutexGuard<'_, Connection>,
     params: GetPlaylistTracksParams,
 ) -> Result<CallToolResult, McpError> {
-    let tracks = db::get_playlist_tracks(&conn, &params.playlist_id, params.limit)
+    let mut tracks = db::get_playlist_tracks(&conn, &params.playlist_id, params.limit)
+        .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+    db::attach_track_lists(&conn, &mut tracks)
+        .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+    let json =
+        serde_json::to_string_pretty(&tracks).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
+
+/// Parse a `YYYY-MM-DD` play-history date, returning it zero-padded so it
+/// compares correctly against `djmdHistory.DateCreated`.
+pub(super) fn validate_history_date(field: &str, value: &str) -> Result<String, McpError> {
+    chrono::NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .map_err(|_| {
+            McpError::invalid_params(
+                format!("{field} must be a date in YYYY-MM-DD format, got '{value}'"),
+                None,
+            )
+        })
+}
+
+pub(super) fn handle_get_history_sessions(
+    conn: MutexGuard<'_, Connection>,
+    params: GetHistorySessionsParams,
+) -> Result<CallToolResult, McpError> {
+    let since = params
+        .since
+        .as_deref()
+        .map(|since| validate_history_date("since", since))
+        .transpose()?;
+    let sessions = db::get_history_sessions(&conn, since.as_deref())
+        .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+    let json =
+        serde_json::to_string_pretty(&sessions).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
+
+pub(super) fn handle_get_history_tracks(
+    conn: MutexGuard<'_, Connection>,
+    params: GetHistoryTracksParams,
+) -> Result<CallToolResult, McpError> {
+    let tracks = db::get_history_tracks(&conn, &params.history_id, params.limit)
         .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
     let json =
         serde_json::to_string_pretty(&tracks).map_err(|e| mcp_internal_error(format!("{e}")))?;
@@ -13,18 +13,30 @@ mod audit_handlers;
 mod batch;
 mod corpus_helpers;
 mod discogs_auth;
+mod duplicate_handlers;
 mod enrich_handlers;
 mod enrichment;
 mod essentia;
 mod file_tag_handlers;
+mod fingerprint_handlers;
 mod help_handler;
+mod key_consensus;
+mod key_handlers;
 mod library_handlers;
 mod params;
+mod quality_handlers;
+mod reconcile;
+mod relocate_handlers;
+mod replaygain_handlers;
 mod resolve;
 mod resolve_handlers;
 mod scoring;
 mod sequencing_handlers;
+mod session_handlers;
 mod staging_handlers;
+mod tempo;
+mod tempo_handlers;
+mod xml_handlers;
 
 use analysis::*;
 use audio_handlers::*;
@@ -33,19 +45,29 @@ use audit_handlers::*;
 use batch::*;
 use corpus_helpers::*;
 use discogs_auth::*;
+use duplicate_handlers::*;
 use enrich_handlers::*;
 use enrichment::*;
 pub(crate) use essentia::probe_essentia_python_path;
 use essentia::*;
 use file_tag_handlers::*;
+use fingerprint_handlers::*;
 use help_handler::*;
+use key_handlers::*;
 use library_handlers::*;
 use params::*;
+use quality_handlers::*;
+use reconcile::*;
+use relocate_handlers::*;
+use replaygain_handlers::*;
 use resolve::*;
 use resolve_handlers::*;
 use scoring::*;
 use sequencing_handlers::*;
+use session_handlers::*;
 use staging_handlers::*;
+use tempo_handlers::*;
+use xml_handlers::*;
 
 use crate::changes::ChangeManager;
 use crate::db;
@@ -131,6 +153,46 @@ impl ReklawdboxServer {
             .unwrap_or_else(|_| store::default_path().to_string_lossy().to_string())
     }
 
+    /// Reload the active staging session from the internal store, or open a new one.
+    /// Once a session is active, every staging mutation is persisted to it.
+    /// Returns the number of restored staged changes.
+    pub fn restore_staging_session(&self) -> Result<usize, String> {
+        let conn = self.cache_store_conn().map_err(|e| e.message.to_string())?;
+        let active = store::get_active_staging_session(&conn)
+            .map_err(|e| format!("Failed to read staging sessions: {e}"))?;
+        let (session_id, changes) = match active {
+            Some(session) => {
+                let changes = store::load_staged_changes(&conn, &session.id)
+                    .map_err(|e| format!("Failed to load staged changes: {e}"))?;
+                (session.id, changes)
+            }
+            None => {
+                let id = start_staging_session(&conn)
+                    .map_err(|e| format!("Failed to create staging session: {e}"))?;
+                (id, Vec::new())
+            }
+        };
+        let restored = self.state.changes.replace_all(Some(session_id), changes);
+        Ok(restored)
+    }
+
+    /// Write staged changes made since the last call to the active staging
+    /// session, if any. Best-effort: failures are logged, the in-memory queue
+    /// stays authoritative and the unsaved tracks are retried next time.
+    pub(super) fn persist_staged_changes(&self) {
+        let result = self.state.changes.persist_with(|session_id, entries| {
+            let conn = self.cache_store_conn()?;
+            store::save_staged_changes(&conn, session_id, entries).map_err(|e| {
+                mcp_internal_error(format!(
+                    "Failed to persist staged changes to session {session_id}: {e}"
+                ))
+            })
+        });
+        if let Err(e) = result {
+            tracing::warn!("{}", e.message);
+        }
+    }
+
     pub(super) fn essentia_python_path(&self) -> Option<String> {
         if let Ok(guard) = self.state.essentia_python_override.lock()
             && let Some(ref path) = *guard
@@ -197,6 +259,24 @@ impl ReklawdboxServer {
         handle_get_playlist_tracks(self.rekordbox_conn()?, params.0)
     }
 
+    #[tool(
+        description = "List play history sessions (newest first) with track counts. Sessions are grouped in year/month folders. Use since to limit to recent gigs."
+    )]
+    async fn get_history_sessions(
+        &self,
+        params: Parameters<GetHistorySessionsParams>,
+    ) -> Result<CallToolResult, McpError> {
+        handle_get_history_sessions(self.rekordbox_conn()?, params.0)
+    }
+
+    #[tool(description = "List tracks of a play history session in the order they were played")]
+    async fn get_history_tracks(
+        &self,
+        params: Parameters<GetHistoryTracksParams>,
+    ) -> Result<CallToolResult, McpError> {
+        handle_get_history_tracks(self.rekordbox_conn()?, params.0)
+    }
+
     #[tool(
         name = "read_library",
         description = "Get library summary: track count, genre distribution, stats"
@@ -216,13 +296,15 @@ impl ReklawdboxServer {
     }
 
     #[tool(
-        description = "Stage changes to track metadata (genre, comments, rating, color). Changes are held in memory until write_xml is called."
+        description = "Stage changes to track metadata (genre, comments, rating, color, title, artist, album, label, remixer, year, bpm, key, my_tags, file_path, cues). Changes are held in the active staging session (persisted across restarts) until write_xml is called."
     )]
     async fn update_tracks(
         &self,
         params: Parameters<UpdateTracksParams>,
     ) -> Result<CallToolResult, McpError> {
-        handle_update_tracks(&self.state.changes, params.0)
+        let result = handle_update_tracks(&self.state.changes, params.0);
+        self.persist_staged_changes();
+        result
     }
 
     #[tool(
@@ -244,13 +326,85 @@ impl ReklawdboxServer {
     }
 
     #[tool(
-        description = "Write staged changes and optional playlists to a Rekordbox-compatible XML file. Runs backup first."
+        description = "Write staged changes and optional playlists (nested in folders, or copied from existing Rekordbox playlist trees) to a Rekordbox-compatible XML file, optionally adding analysed beat grids and phrase memory cues. Runs backup first."
     )]
     async fn write_xml(
         &self,
         params: Parameters<WriteXmlParams>,
     ) -> Result<CallToolResult, McpError> {
-        handle_write_xml(self, params.0).await
+        let result = handle_write_xml(self, params.0).await;
+        self.persist_staged_changes();
+        result
+    }
+
+    #[tool(
+        description = "Compare a Rekordbox XML file against master.db (or another XML file), reporting added, removed and changed tracks per field. Tracks are matched by file path."
+    )]
+    async fn diff_xml(
+        &self,
+        params: Parameters<DiffXmlParams>,
+    ) -> Result<CallToolResult, McpError> {
+        handle_diff_xml(self, params.0)
+    }
+
+    #[tool(
+        description = "Find tracks whose audio file is missing and search root directories for the moved file, matching on file name, size, duration and tags. Returns a ranked relocation plan; stage reviewed matches as file_path changes so write_xml exports corrected Locations."
+    )]
+    async fn find_relocations(
+        &self,
+        params: Parameters<FindRelocationsParams>,
+    ) -> Result<CallToolResult, McpError> {
+        handle_find_relocations(self, params.0).await
+    }
+
+    #[tool(
+        description = "Find tracks imported more than once (e.g. FLAC and MP3 copies, promo and retail) by normalized artist/title, duration and acoustic fingerprints (computed on demand, falling back to cached tempo/key analysis). Recommends a copy to keep per cluster by format/bitrate, cues, playlists and plays; stage reviewed merges as merge_into changes so write_xml points playlist entries at the keeper."
+    )]
+    async fn find_duplicates(
+        &self,
+        params: Parameters<FindDuplicatesParams>,
+    ) -> Result<CallToolResult, McpError> {
+        handle_find_duplicates(self, params.0).await
+    }
+
+    #[tool(
+        description = "Compare tracks and/or audio files by acoustic fingerprint (chroma-based, robust to format, bitrate and leading silence). Returns pairwise similarity, bit error rate and alignment offset; fingerprints are cached and reused by find_duplicates and find_relocations."
+    )]
+    async fn fingerprint_match(
+        &self,
+        params: Parameters<FingerprintMatchParams>,
+    ) -> Result<CallToolResult, McpError> {
+        handle_fingerprint_match(self, params.0).await
+    }
+
+    #[tool(
+        description = "Rank a playlist's tracks by how likely they are to clip the mixer: sample peak, 4x-oversampled true peak, hard-clipped sample runs and DC offset, flagged as CLIPPING, LOW_HEADROOM or DC_OFFSET. Measurements are cached and shared with analyze_track_audio, analyze_audio_batch and analyzing audit scans."
+    )]
+    async fn quality_report(
+        &self,
+        params: Parameters<QualityReportParams>,
+    ) -> Result<CallToolResult, McpError> {
+        handle_quality_report(self, params.0).await
+    }
+
+    #[tool(
+        description = "Resolve half-time/double-time BPM disagreements (87 vs 174, 65 vs 130) between Rekordbox, cached stratum-dsp and Essentia analysis and cached Beatport enrichment, using genre-family tempo ranges and onset rate. Returns BPM corrections with explanations and a confidence; stage reviewed corrections as bpm changes."
+    )]
+    async fn reconcile_bpm(
+        &self,
+        params: Parameters<ReconcileBpmParams>,
+    ) -> Result<CallToolResult, McpError> {
+        handle_reconcile_bpm(self, params.0)
+    }
+
+    #[tool(
+        description = "Find the key to trust when Rekordbox, cached stratum-dsp and Essentia analysis and cached Beatport enrichment disagree. Weighs each source by its confidence, flags relative major/minor and fifth confusions, and reports per-track and per-playlist agreement; stage reviewed corrections as key changes."
+    )]
+    async fn reconcile_keys(
+        &self,
+        params: Parameters<ReconcileKeysParams>,
+    ) -> Result<CallToolResult, McpError> {
+        handle_reconcile_keys(self, params.0)
     }
 
     #[tool(description = "Clear staged changes for specific tracks or all")]
@@ -258,14 +412,82 @@ impl ReklawdboxServer {
         &self,
         params: Parameters<ClearChangesParams>,
     ) -> Result<CallToolResult, McpError> {
-        handle_clear_changes(&self.state.changes, params.0)
+        let result = handle_clear_changes(&self.state.changes, params.0);
+        self.persist_staged_changes();
+        result
+    }
+
+    #[tool(
+        description = "Undo the most recent staging steps (update_tracks, clear_changes, clear_caches), restoring the previously staged values. Use change_history to see what will be undone."
+    )]
+    async fn undo_changes(
+        &self,
+        params: Parameters<UndoRedoParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let result = handle_undo_changes(&self.state.changes, params.0);
+        self.persist_staged_changes();
+        result
+    }
+
+    #[tool(description = "Redo staging steps previously reverted with undo_changes")]
+    async fn redo_changes(
+        &self,
+        params: Parameters<UndoRedoParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let result = handle_redo_changes(&self.state.changes, params.0);
+        self.persist_staged_changes();
+        result
+    }
+
+    #[tool(
+        description = "List undoable and redoable staging steps with the tool call that produced each. History resets when changes are written or a session is resumed."
+    )]
+    async fn change_history(&self) -> Result<CallToolResult, McpError> {
+        handle_change_history(&self.state.changes)
+    }
+
+    #[tool(
+        description = "List saved staging sessions (persisted staged-change queues) with change counts. The active session receives all new staged changes."
+    )]
+    async fn list_staging_sessions(&self) -> Result<CallToolResult, McpError> {
+        handle_list_staging_sessions(self)
+    }
+
+    #[tool(
+        description = "Resume a saved staging session: saves the current queue, then loads the chosen session's staged changes and makes it active."
+    )]
+    async fn resume_staging_session(
+        &self,
+        params: Parameters<ResumeStagingSessionParams>,
+    ) -> Result<CallToolResult, McpError> {
+        handle_resume_staging_session(self, params.0)
+    }
+
+    #[tool(description = "Rename a staging session (defaults to the active session)")]
+    async fn rename_staging_session(
+        &self,
+        params: Parameters<RenameStagingSessionParams>,
+    ) -> Result<CallToolResult, McpError> {
+        handle_rename_staging_session(self, params.0)
+    }
+
+    #[tool(
+        description = "Delete a saved staging session and its staged changes. Discarding the active session clears the in-memory queue and starts a new empty session."
+    )]
+    async fn discard_staging_session(
+        &self,
+        params: Parameters<DiscardStagingSessionParams>,
+    ) -> Result<CallToolResult, McpError> {
+        handle_discard_staging_session(self, params.0)
     }
 
     #[tool(
         description = "Clear all caches (enrichment, audio analysis, audit state) and staged changes. Preserves Discogs broker session. Use this to reset to a clean slate before a fresh test run."
     )]
     async fn clear_caches(&self) -> Result<CallToolResult, McpError> {
-        handle_clear_caches(self)
+        let result = handle_clear_caches(self);
+        self.persist_staged_changes();
+        result
     }
 
     #[tool(
@@ -299,7 +521,7 @@ impl ReklawdboxServer {
     }
 
     #[tool(
-        description = "Analyze a single track's audio file with stratum-dsp and Essentia (when installed). Returns BPM, key, rhythm/loudness descriptors, and confidence scores. Results are cached."
+        description = "Analyze a single track's audio file with stratum-dsp and Essentia (when installed; otherwise built-in native loudness, spectral and rhythm features). Returns BPM, key, rhythm/loudness descriptors, confidence scores, key/BPM over the opening and closing 32 bars, and beat-grid structure (intro/outro bars, breakdowns, drops, labelled 8/16/32-bar phrases, mix-in/mix-out points) and signal quality (sample/true peak, clipped runs, DC offset). Results are cached."
     )]
     async fn analyze_track_audio(
         &self,
@@ -309,7 +531,7 @@ impl ReklawdboxServer {
     }
 
     #[tool(
-        description = "Batch analyze audio files with stratum-dsp and Essentia (when installed). Select tracks by IDs, playlist, or search filters. Results are cached."
+        description = "Batch analyze audio files with stratum-dsp and Essentia (when installed; otherwise built-in native features) plus beat-grid structure, mix points and signal quality. Select tracks by IDs, playlist, or search filters. Results are cached."
     )]
     async fn analyze_audio_batch(
         &self,
@@ -326,7 +548,7 @@ impl ReklawdboxServer {
     }
 
     #[tool(
-        description = "Score a single transition between two tracks using key, BPM, energy, genre, brightness, and rhythm compatibility."
+        description = "Score a single transition between two tracks using key, BPM, energy, genre, brightness, and rhythm compatibility. Key and BPM are scored on the outgoing track's closing bars and the incoming track's opening bars when windowed analysis is cached. Reports how often the pair was played back to back in play history, and whether their phrase structures line up when structure analysis is cached."
     )]
     async fn score_transition(
         &self,
@@ -346,7 +568,7 @@ impl ReklawdboxServer {
     }
 
     #[tool(
-        description = "Generate candidate set orderings from a track pool using beam search sequencing. Use beam_width to control search breadth (1=greedy, higher=more candidates). Use bpm_range for BPM trajectory planning."
+        description = "Generate candidate set orderings from a track pool using beam search sequencing. Use beam_width to control search breadth (1=greedy, higher=more candidates). Use bpm_range for BPM trajectory planning. Tracks and transitions include cached mix-in/mix-out points when structure has been analyzed."
     )]
     async fn build_set(
         &self,
@@ -356,7 +578,7 @@ impl ReklawdboxServer {
     }
 
     #[tool(
-        description = "Get all available data for a track in one call: Rekordbox metadata, cached audio analysis, cached enrichment, staged changes, and genre taxonomy mappings. Cache-only — never triggers external calls."
+        description = "Get all available data for a track in one call: Rekordbox metadata, cached audio analysis and structure (suggested mix-in/mix-out points), cached enrichment, staged changes, and genre taxonomy mappings. Cache-only — never triggers external calls."
     )]
     async fn resolve_track_data(
         &self,
@@ -390,7 +612,7 @@ impl ReklawdboxServer {
     // -----------------------------------------------------------------------
 
     #[tool(
-        description = "Read metadata tags directly from audio files on disk. Supports FLAC, MP3, WAV, M4A, AAC, AIFF. Provide exactly one input selector: paths, track_ids, or directory."
+        description = "Read metadata tags directly from audio files on disk. Supports FLAC, MP3, WAV, M4A/ALAC, AAC, AIFF/AIFF-C, OGG Vorbis; Opus files are read only when given by path, since they are not scanned or analyzed. Provide exactly one input selector: paths, track_ids, or directory."
     )]
     async fn read_file_tags(
         &self,
@@ -409,6 +631,16 @@ impl ReklawdboxServer {
         handle_write_file_tags(params.0).await
     }
 
+    #[tool(
+        description = "Loudness-normalise audio files by writing ReplayGain 2.0 tags (REPLAYGAIN_TRACK_GAIN/PEAK, and ALBUM_GAIN/PEAK for album directories) against -18 LUFS. Uses cached integrated loudness and peaks from audio analysis, measuring natively when missing (up to max_analyze decodes per call; the rest are deferred). WAV files carry the tags in ID3v2 only. Use dry_run to preview."
+    )]
+    async fn write_replaygain(
+        &self,
+        params: Parameters<WriteReplayGainParams>,
+    ) -> Result<CallToolResult, McpError> {
+        handle_write_replaygain(self, params.0).await
+    }
+
     #[tool(description = "Extract cover art from an audio file and save to disk.")]
     async fn extract_cover_art(
         &self,
@@ -430,7 +662,7 @@ impl ReklawdboxServer {
     // -----------------------------------------------------------------------
 
     #[tool(
-        description = "Collection audit engine. Scan files for convention violations, query/resolve issues, and get summaries. Operations: scan, query_issues, resolve_issues, get_summary."
+        description = "Collection audit engine. Scan files for convention violations (and, with analyze, lossless files whose spectrum reveals a lossy transcode), query/resolve issues, and get summaries. Operations: scan, query_issues, resolve_issues, get_summary, orphans (audio files in scope that are not in the Rekordbox collection)."
     )]
     async fn audit_state(
         &self,
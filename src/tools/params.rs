@@ -26,6 +26,8 @@ pub struct SearchFilterParams {
     pub has_genre: Option<bool>,
     #[schemars(description = "Filter by label name (partial match)")]
     pub label: Option<String>,
+    #[schemars(description = "Filter by My Tag name (exact, case-insensitive)")]
+    pub my_tag: Option<String>,
     #[schemars(description = "Filter by file path/folder (substring match)")]
     pub path: Option<String>,
     #[schemars(
@@ -58,6 +60,7 @@ impl SearchFilterParams {
             bpm_max: self.bpm_max,
             key: self.key,
             playlist: None,
+            my_tag: self.my_tag,
             has_genre: self.has_genre,
             label: self.label,
             path: self.path,
@@ -75,7 +78,7 @@ impl SearchFilterParams {
 pub struct SearchTracksParams {
     #[serde(flatten)]
     pub filters: SearchFilterParams,
-    #[schemars(description = "Filter by playlist ID")]
+    #[schemars(description = "Filter by playlist ID (smart playlists match by their rules)")]
     pub playlist: Option<String>,
     #[schemars(description = "Include Rekordbox factory samples (default false)")]
     pub include_samples: Option<bool>,
@@ -99,13 +102,27 @@ pub struct GetPlaylistTracksParams {
     pub limit: Option<u32>,
 }
 
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetHistorySessionsParams {
+    #[schemars(description = "Only sessions on or after this date (YYYY-MM-DD)")]
+    pub since: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GetHistoryTracksParams {
+    #[schemars(description = "History session ID (from get_history_sessions)")]
+    pub history_id: String,
+    #[schemars(description = "Max results (default all)")]
+    pub limit: Option<u32>,
+}
+
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct UpdateTracksParams {
     #[schemars(description = "Array of track changes to stage")]
     pub changes: Vec<TrackChangeInput>,
 }
 
-#[derive(Debug, Deserialize, JsonSchema)]
+#[derive(Debug, Default, Deserialize, JsonSchema)]
 #[schemars(inline)]
 pub struct TrackChangeInput {
     #[schemars(description = "Track ID")]
@@ -118,6 +135,40 @@ pub struct TrackChangeInput {
     pub rating: Option<u8>,
     #[schemars(description = "New color name")]
     pub color: Option<String>,
+    #[schemars(description = "New title (must not be empty)")]
+    pub title: Option<String>,
+    #[schemars(description = "New artist")]
+    pub artist: Option<String>,
+    #[schemars(description = "New album")]
+    pub album: Option<String>,
+    #[schemars(description = "New label")]
+    pub label: Option<String>,
+    #[schemars(description = "New remixer")]
+    pub remixer: Option<String>,
+    #[schemars(description = "New release year (1900 to next year)")]
+    pub year: Option<i32>,
+    #[schemars(description = "New BPM (20-300)")]
+    pub bpm: Option<f64>,
+    #[schemars(
+        description = "New key in musical (e.g. 'Am', 'F#', 'Dbm') or Camelot (e.g. '8A') notation; stored in Rekordbox notation (e.g. '8A' becomes 'Am')"
+    )]
+    pub key: Option<String>,
+    #[schemars(
+        description = "Replacement My Tag names (full set; [] removes all). Exported via Rekordbox's '/* Tag / Tag */' Comments convention, since XML has no My Tag element"
+    )]
+    pub my_tags: Option<Vec<String>>,
+    #[schemars(
+        description = "Relocated audio file path (must exist). Exported as the track's XML Location; see find_relocations"
+    )]
+    pub file_path: Option<String>,
+    #[schemars(
+        description = "Track ID of the copy to keep when this track is a duplicate. write_xml drops this track and points its playlist entries at the keeper; see find_duplicates"
+    )]
+    pub merge_into: Option<String>,
+    #[schemars(
+        description = "Cues to add on export: {kind: 'memory'|'hot_cue', position: seconds, bank: 'A'-'H' (hot cues only), name?}. A hot cue replaces the one in its bank and a memory cue replaces one at the same position; repeated calls add to the staged list"
+    )]
+    pub cues: Option<Vec<crate::types::StagedCue>>,
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
@@ -127,6 +178,10 @@ pub struct WriteXmlPlaylistInput {
     pub name: String,
     #[schemars(description = "Track IDs in playlist order")]
     pub track_ids: Vec<String>,
+    #[schemars(
+        description = "Enclosing folder path, '/'-separated (e.g. \"Sets/2026\"). Playlists sharing a folder path are grouped under one folder."
+    )]
+    pub folder: Option<String>,
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
@@ -139,6 +194,30 @@ pub struct WriteXmlParams {
         description = "Optional playlist exports. Each playlist includes a name and ordered track_ids."
     )]
     pub playlists: Option<Vec<WriteXmlPlaylistInput>>,
+    #[schemars(
+        description = "Rekordbox playlist or folder IDs (from get_playlists) to export with their nested folders and playlists, preserving the hierarchy. Smart playlists are exported with their current matches."
+    )]
+    pub rekordbox_playlist_ids: Option<Vec<String>>,
+    #[schemars(
+        description = "Emit TEMPO beat grids from cached stratum-dsp analysis for tracks whose stored Rekordbox grid (or, without a readable ANLZ file, library BPM) disagrees with analysis in tempo or beat phase, or whose BPM is staged (default false). Half/double-time disagreements and staged BPMs that contradict analysis are reported as conflicts instead of overwritten. Run analyze_audio_batch first."
+    )]
+    pub beat_grids: Option<bool>,
+    #[schemars(
+        description = "Add memory cues at phrase section starts (intro, groove, build, drop, break, outro) from cached structure analysis (default false). Cues near an existing cue are skipped. Run analyze_audio_batch first."
+    )]
+    pub phrase_cues: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct DiffXmlParams {
+    #[schemars(description = "Path to the Rekordbox XML file to compare")]
+    pub path: String,
+    #[schemars(
+        description = "Baseline XML file to compare against (default: the Rekordbox library in master.db)"
+    )]
+    pub against_path: Option<String>,
+    #[schemars(description = "Max tracks listed per section (added/removed/changed, default 50)")]
+    pub limit: Option<u32>,
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
@@ -152,11 +231,37 @@ pub struct ClearChangesParams {
     #[schemars(description = "Track IDs to clear (if empty, clears all)")]
     pub track_ids: Option<Vec<String>>,
     #[schemars(
-        description = "Specific fields to unstage: \"genre\", \"comments\", \"rating\", \"color\". If omitted, clears all fields (removes entire entries)."
+        description = "Specific fields to unstage: \"genre\", \"comments\", \"rating\", \"color\", \"title\", \"artist\", \"album\", \"label\", \"remixer\", \"year\", \"bpm\", \"key\", \"my_tags\", \"file_path\", \"merge_into\", \"cues\". If omitted, clears all fields (removes entire entries)."
     )]
     pub fields: Option<Vec<String>>,
 }
 
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UndoRedoParams {
+    #[schemars(description = "Number of steps to undo/redo (default 1)")]
+    pub steps: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ResumeStagingSessionParams {
+    #[schemars(description = "Staging session ID (from list_staging_sessions)")]
+    pub session_id: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RenameStagingSessionParams {
+    #[schemars(description = "Staging session ID (defaults to the active session)")]
+    pub session_id: Option<String>,
+    #[schemars(description = "New session name")]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct DiscardStagingSessionParams {
+    #[schemars(description = "Staging session ID to delete along with its staged changes")]
+    pub session_id: String,
+}
+
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct SuggestNormalizationsParams {
     #[schemars(description = "Only show genres with at least this many tracks (default 1)")]
@@ -240,6 +345,108 @@ pub struct AnalyzeAudioBatchParams {
     pub concurrency: Option<u32>,
 }
 
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FindRelocationsParams {
+    #[schemars(description = "Directories to search recursively for moved audio files")]
+    pub roots: Vec<String>,
+    #[serde(flatten)]
+    pub filters: SearchFilterParams,
+    #[schemars(description = "Only check these track IDs (highest priority selector)")]
+    pub track_ids: Option<Vec<String>>,
+    #[schemars(description = "Only check tracks in this playlist")]
+    pub playlist_id: Option<String>,
+    #[schemars(description = "Max missing tracks to match (default 100)")]
+    pub limit: Option<u32>,
+    #[schemars(description = "Candidates listed per missing track (default 3, max 10)")]
+    pub max_candidates: Option<u32>,
+    #[schemars(
+        description = "Stage the top candidate of every high-confidence match as a file_path change (default false). Review medium/low matches and stage them with update_tracks."
+    )]
+    pub stage_confident: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FindDuplicatesParams {
+    #[serde(flatten)]
+    pub filters: SearchFilterParams,
+    #[schemars(description = "Max duplicate clusters to return (default 50)")]
+    pub limit: Option<u32>,
+    #[schemars(
+        description = "Stage every non-keeper in high-confidence clusters as a merge_into change (default false). Review medium/low clusters and stage them with update_tracks."
+    )]
+    pub stage_merges: Option<bool>,
+    #[schemars(
+        description = "Max copies without a cached fingerprint to fingerprint this call (default 50; 0 uses cached fingerprints only). Call again to cover the rest."
+    )]
+    pub fingerprint_limit: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct QualityReportParams {
+    #[schemars(description = "Playlist whose tracks to check")]
+    pub playlist_id: String,
+    #[schemars(description = "Max tracks to return, worst first (default 20)")]
+    pub limit: Option<u32>,
+    #[schemars(
+        description = "Decode tracks without a current cached measurement (default true). When false, only cached measurements are ranked."
+    )]
+    pub analyze_missing: Option<bool>,
+    #[schemars(
+        description = "Max uncached tracks to decode this call (default 50, max 200); the rest are reported as deferred"
+    )]
+    pub max_analyze: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ReconcileBpmParams {
+    #[serde(flatten)]
+    pub filters: SearchFilterParams,
+    #[schemars(description = "Only check these track IDs (highest priority selector)")]
+    pub track_ids: Option<Vec<String>>,
+    #[schemars(description = "Only check tracks in this playlist")]
+    pub playlist_id: Option<String>,
+    #[schemars(description = "Max tracks to check (default 500, max 2000)")]
+    pub max_tracks: Option<u32>,
+    #[schemars(description = "Offset for pagination (skip first N tracks in result set)")]
+    pub offset: Option<u32>,
+    #[schemars(description = "Max corrections to return, most confident first (default 50)")]
+    pub limit: Option<u32>,
+    #[schemars(
+        description = "Stage every high-confidence correction as a bpm change (default false). Review medium/low corrections and stage them with update_tracks. Export with write_xml beat_grids: true so the beat grid follows the new BPM."
+    )]
+    pub stage_corrections: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ReconcileKeysParams {
+    #[serde(flatten)]
+    pub filters: SearchFilterParams,
+    #[schemars(description = "Only check these track IDs (highest priority selector)")]
+    pub track_ids: Option<Vec<String>>,
+    #[schemars(description = "Only check tracks in this playlist")]
+    pub playlist_id: Option<String>,
+    #[schemars(description = "Max tracks to check (default 500, max 2000)")]
+    pub max_tracks: Option<u32>,
+    #[schemars(description = "Offset for pagination (skip first N tracks in result set)")]
+    pub offset: Option<u32>,
+    #[schemars(description = "Max disputed tracks to report, least agreement first (default 50)")]
+    pub limit: Option<u32>,
+    #[schemars(
+        description = "Stage every high-confidence correction as a key change (default false). Review medium/low corrections and stage them with update_tracks."
+    )]
+    pub stage_corrections: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FingerprintMatchParams {
+    #[schemars(description = "Track IDs whose audio files to compare")]
+    pub track_ids: Option<Vec<String>>,
+    #[schemars(description = "Audio file paths to compare (tracks and paths combined: 2-20)")]
+    pub paths: Option<Vec<String>>,
+    #[schemars(description = "Reuse cached fingerprints for unchanged files (default true)")]
+    pub skip_cached: Option<bool>,
+}
+
 #[derive(Debug, Deserialize, JsonSchema)]
 pub struct ResolveTrackDataParams {
     #[schemars(description = "Track ID to resolve")]
@@ -356,6 +563,10 @@ pub struct BuildSetParams {
         description = "BPM range as [start_bpm, end_bpm]. When set, plans a BPM trajectory from start to end across the set's energy curve, and outputs per-track play_at_bpm, pitch_adjustment_pct, and effective_key."
     )]
     pub bpm_range: Option<(f64, f64)>,
+    #[schemars(
+        description = "Leave out tracks played in any Rekordbox history session on or after this date (YYYY-MM-DD), so the set avoids recently played tracks. opening_track_id is always kept. Excluded IDs are listed in excluded_recently_played."
+    )]
+    pub avoid_played_since: Option<String>,
 }
 
 #[derive(Debug, Deserialize, JsonSchema)]
@@ -436,7 +647,7 @@ pub(super) struct ReadFileTagsParams {
     pub recursive: Option<bool>,
 
     #[schemars(
-        description = "Return only these fields (default: all). Valid: artist, title, album, album_artist, genre, year, track, disc, comment, publisher, bpm, key, composer, remixer"
+        description = "Return only these fields (default: all). Valid: artist, title, album, album_artist, genre, year, track, disc, comment, publisher, bpm, key, composer, remixer, plus replaygain_track_gain, replaygain_track_peak, replaygain_album_gain, replaygain_album_peak (only when requested)"
     )]
     pub fields: Option<Vec<String>>,
 
@@ -505,6 +716,42 @@ pub(super) struct EmbedCoverArtParams {
     pub picture_type: Option<String>,
 }
 
+#[derive(Debug, Deserialize, JsonSchema)]
+pub(super) struct WriteReplayGainParams {
+    #[schemars(description = "Explicit file paths to normalise")]
+    pub paths: Option<Vec<String>>,
+
+    #[schemars(description = "Resolve file paths from Rekordbox track IDs")]
+    pub track_ids: Option<Vec<String>>,
+
+    #[schemars(description = "Scan directory for audio files")]
+    pub directory: Option<String>,
+
+    #[schemars(
+        description = "Glob filter within directory (default: all audio files). Only used with directory."
+    )]
+    pub glob: Option<String>,
+
+    #[schemars(description = "Scan subdirectories (default: false). Only used with directory.")]
+    pub recursive: Option<bool>,
+
+    #[schemars(
+        description = "Also write album gain, grouping album tracks by album directory and album tag (default: true). Select every track of an album so its gain covers the whole record."
+    )]
+    pub album_gain: Option<bool>,
+
+    #[schemars(description = "Preview tag changes without writing (default: false)")]
+    pub dry_run: Option<bool>,
+
+    #[schemars(description = "Max files to process (default: 200, max: 2000)")]
+    pub limit: Option<usize>,
+
+    #[schemars(
+        description = "Max files without cached loudness to decode this call (default: 50, max: 200). The rest, and the other tracks of their albums, are listed as deferred."
+    )]
+    pub max_analyze: Option<u32>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(tag = "operation")]
 pub(super) enum AuditOperation {
@@ -513,6 +760,7 @@ pub(super) enum AuditOperation {
         #[serde(rename = "scope")]
         path_prefix: String,
         revalidate: Option<bool>,
+        analyze: Option<bool>,
         skip_issue_types: Option<Vec<String>>,
     },
 
@@ -538,6 +786,14 @@ pub(super) enum AuditOperation {
         #[serde(rename = "scope")]
         path_prefix: String,
     },
+
+    #[serde(rename = "orphans")]
+    Orphans {
+        #[serde(rename = "scope")]
+        path_prefix: String,
+        limit: Option<u32>,
+        xml_output_path: Option<String>,
+    },
 }
 
 impl schemars::JsonSchema for AuditOperation {
@@ -552,17 +808,21 @@ impl schemars::JsonSchema for AuditOperation {
             "properties": {
                 "operation": {
                     "type": "string",
-                    "enum": ["scan", "query_issues", "resolve_issues", "get_summary"],
+                    "enum": ["scan", "query_issues", "resolve_issues", "get_summary", "orphans"],
                     "description": "The audit operation to perform"
                 },
                 "scope": {
                     "type": "string",
-                    "description": "Directory path prefix (required for scan, query_issues, get_summary)"
+                    "description": "Directory path prefix (required for scan, query_issues, get_summary, orphans)"
                 },
                 "revalidate": {
                     "type": "boolean",
                     "description": "Re-read all files including unchanged (default: false). Only for scan."
                 },
+                "analyze": {
+                    "type": "boolean",
+                    "description": "Decode lossless files (FLAC/WAV/AIFF) and check their spectrum for lossy transcodes, raising SUSPECT_TRANSCODE (default: false). Slow; combine with revalidate to analyze files already scanned. Only for scan."
+                },
                 "skip_issue_types": {
                     "type": "array",
                     "items": { "type": "string" },
@@ -578,7 +838,7 @@ impl schemars::JsonSchema for AuditOperation {
                 },
                 "limit": {
                     "type": "integer",
-                    "description": "Max results (default: 100). Only for query_issues."
+                    "description": "Max results (default: 100). For query_issues (issues) and orphans (directory groups)."
                 },
                 "offset": {
                     "type": "integer",
@@ -596,6 +856,10 @@ impl schemars::JsonSchema for AuditOperation {
                 "note": {
                     "type": "string",
                     "description": "Optional user comment. Only for resolve_issues."
+                },
+                "xml_output_path": {
+                    "type": "string",
+                    "description": "Write a Rekordbox XML importing only the orphaned files, one playlist per directory. Only for orphans."
                 }
             }
         })
@@ -0,0 +1,209 @@
+use std::cmp::Ordering;
+
+use rmcp::ErrorData as McpError;
+use rmcp::model::{CallToolResult, Content};
+
+use super::*;
+use crate::audio::{self, SignalQuality};
+use crate::db;
+
+/// Uncached tracks `quality_report` decodes per call by default, and at most.
+const DEFAULT_QUALITY_ANALYZE_LIMIT: u32 = 50;
+const MAX_QUALITY_ANALYZE_LIMIT: u32 = 200;
+
+/// Tracks `quality_report` decodes at once.
+const QUALITY_CONCURRENCY: usize = 4;
+
+/// A current cached measurement for the file behind `key`, if any.
+fn cached_quality(
+    server: &ReklawdboxServer,
+    key: &CacheKey<'_>,
+) -> Result<Option<SignalQuality>, String> {
+    let store = server
+        .cache_store_conn()
+        .map_err(|e| e.message.to_string())?;
+    let cached = check_analysis_cache(
+        &store,
+        key.file_path,
+        audio::ANALYZER_QUALITY,
+        key.file_size,
+        key.file_mtime,
+    )?;
+    Ok(cached
+        .filter(|json_str| is_current_quality(json_str))
+        .and_then(|json_str| serde_json::from_str(&json_str).ok()))
+}
+
+/// Worst first: most clipped samples, then hottest true peak, then largest
+/// DC offset.
+fn worst_first(a: &SignalQuality, b: &SignalQuality) -> Ordering {
+    b.clipped_samples
+        .cmp(&a.clipped_samples)
+        .then(b.true_peak_dbtp.total_cmp(&a.true_peak_dbtp))
+        .then(b.dc_offset.abs().total_cmp(&a.dc_offset.abs()))
+}
+
+pub(super) async fn handle_quality_report(
+    server: &ReklawdboxServer,
+    params: QualityReportParams,
+) -> Result<CallToolResult, McpError> {
+    let limit = params.limit.unwrap_or(20) as usize;
+    let analyze_missing = params.analyze_missing.unwrap_or(true);
+    let max_analyze = params
+        .max_analyze
+        .unwrap_or(DEFAULT_QUALITY_ANALYZE_LIMIT)
+        .min(MAX_QUALITY_ANALYZE_LIMIT) as usize;
+
+    let tracks = {
+        let conn = server.rekordbox_conn()?;
+        db::get_playlist_tracks(&conn, &params.playlist_id, None)
+            .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?
+    };
+    if tracks.is_empty() {
+        return Err(McpError::invalid_params(
+            format!("No tracks found in playlist '{}'", params.playlist_id),
+            None,
+        ));
+    }
+
+    let unmeasured_entry = |track: &crate::types::Track, error: String| {
+        serde_json::json!({
+            "track_id": track.id,
+            "title": track.title,
+            "artist": track.artist,
+            "error": error,
+        })
+    };
+
+    // Cached measurements first; uncached tracks are decoded up to the cap
+    let mut measured = Vec::with_capacity(tracks.len());
+    let mut unmeasured = Vec::new();
+    // (track, resolved path, size, mtime)
+    let mut uncached = Vec::new();
+    for track in &tracks {
+        let file_path = match resolve_file_path(&track.file_path) {
+            Ok(file_path) => file_path,
+            Err(e) => {
+                unmeasured.push(unmeasured_entry(track, e.message.to_string()));
+                continue;
+            }
+        };
+        let key = match CacheKey::stat(&file_path) {
+            Ok(key) => key,
+            Err(e) => {
+                unmeasured.push(unmeasured_entry(track, e));
+                continue;
+            }
+        };
+        match cached_quality(server, &key) {
+            Ok(Some(quality)) => measured.push((track, quality)),
+            Ok(None) if analyze_missing => {
+                let (file_size, file_mtime) = (key.file_size, key.file_mtime);
+                uncached.push((track, file_path, file_size, file_mtime));
+            }
+            Ok(None) => unmeasured.push(unmeasured_entry(
+                track,
+                "No cached measurement (run with analyze_missing)".to_string(),
+            )),
+            Err(e) => unmeasured.push(unmeasured_entry(track, e)),
+        }
+    }
+    let cached = measured.len();
+    let deferred = uncached.len().saturating_sub(max_analyze);
+    for (track, ..) in uncached.drain(max_analyze.min(uncached.len())..) {
+        unmeasured.push(unmeasured_entry(
+            track,
+            "Deferred: over max_analyze for this call".to_string(),
+        ));
+    }
+
+    let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(QUALITY_CONCURRENCY));
+    let mut handles = Vec::with_capacity(uncached.len());
+    for (_, file_path, ..) in &uncached {
+        let sem = semaphore.clone();
+        let file_path = file_path.clone();
+        handles.push(tokio::task::spawn(async move {
+            let _permit = sem.acquire().await.unwrap();
+            analyze_signal_quality(&file_path).await
+        }));
+    }
+    let mut analyzed = 0usize;
+    let sink = CacheSink::Store(server);
+    for ((track, file_path, file_size, file_mtime), handle) in uncached.into_iter().zip(handles) {
+        let quality = handle
+            .await
+            .map_err(|e| mcp_internal_error(format!("join error: {e}")))?;
+        let quality = match quality {
+            Ok(quality) => quality,
+            Err(e) => {
+                unmeasured.push(unmeasured_entry(track, e));
+                continue;
+            }
+        };
+        let key = CacheKey {
+            file_path: &file_path,
+            file_size,
+            file_mtime,
+        };
+        let features_json =
+            serde_json::to_string(&quality).map_err(|e| mcp_internal_error(format!("{e}")))?;
+        sink.write(
+            &key,
+            audio::ANALYZER_QUALITY,
+            audio::QUALITY_VERSION,
+            features_json,
+        )
+        .await
+        .map_err(mcp_internal_error)?;
+        analyzed += 1;
+        measured.push((track, quality));
+    }
+    measured.sort_by(|(_, a), (_, b)| worst_first(a, b));
+
+    let count_flag = |flag: audio::QualityFlag| {
+        measured
+            .iter()
+            .filter(|(_, quality)| quality.flags().contains(&flag))
+            .count()
+    };
+    let summary = serde_json::json!({
+        "total": tracks.len(),
+        "measured": measured.len(),
+        "analyzed": analyzed,
+        "cached": cached,
+        "deferred": deferred,
+        "unmeasured": unmeasured.len(),
+        "clipping": count_flag(audio::QualityFlag::Clipping),
+        "low_headroom": count_flag(audio::QualityFlag::LowHeadroom),
+        "dc_offset": count_flag(audio::QualityFlag::DcOffset),
+    });
+
+    let ranked: Vec<serde_json::Value> = measured
+        .iter()
+        .take(limit)
+        .map(|(track, quality)| {
+            serde_json::json!({
+                "track_id": track.id,
+                "title": track.title,
+                "artist": track.artist,
+                "file_path": track.file_path,
+                "flags": quality.flags(),
+                "sample_peak_dbfs": quality.sample_peak_dbfs,
+                "true_peak_dbtp": quality.true_peak_dbtp,
+                "clipped_runs": quality.clipped_runs,
+                "clipped_samples": quality.clipped_samples,
+                "dc_offset": quality.dc_offset,
+            })
+        })
+        .collect();
+
+    let result = serde_json::json!({
+        "playlist_id": params.playlist_id,
+        "summary": summary,
+        "tracks": ranked,
+        "unmeasured": unmeasured,
+    });
+    let json =
+        serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
@@ -0,0 +1,75 @@
+use rmcp::ErrorData as McpError;
+
+use super::*;
+use crate::types::{MatchConfidence, Track, TrackChange};
+
+/// Default number of corrections or reports returned per call.
+pub(super) const DEFAULT_RECONCILE_LIMIT: u32 = 50;
+
+/// Tracks checked per call by default, and at most.
+const DEFAULT_RECONCILE_TRACKS: u32 = 500;
+const MAX_RECONCILE_TRACKS: u32 = 2000;
+
+/// Tracks a reconcile tool checks: track IDs, else a playlist, else search
+/// filters, without samplers.
+pub(super) fn resolve_reconcile_tracks(
+    server: &ReklawdboxServer,
+    track_ids: Option<&[String]>,
+    playlist_id: Option<&str>,
+    filters: SearchFilterParams,
+    max_tracks: Option<u32>,
+    offset: Option<u32>,
+) -> Result<Vec<Track>, McpError> {
+    let conn = server.rekordbox_conn()?;
+    resolve_tracks(
+        &conn,
+        track_ids,
+        playlist_id,
+        filters,
+        max_tracks,
+        offset,
+        &ResolveTracksOpts {
+            default_max_tracks: Some(DEFAULT_RECONCILE_TRACKS),
+            max_tracks_cap: Some(MAX_RECONCILE_TRACKS),
+            exclude_samplers: true,
+        },
+    )
+}
+
+/// Add `high`, `medium` and `low` counts of `levels` to a summary object.
+pub(super) fn add_confidence_counts(
+    summary: &mut serde_json::Value,
+    levels: impl IntoIterator<Item = MatchConfidence>,
+) {
+    let (mut high, mut medium, mut low) = (0usize, 0usize, 0usize);
+    for level in levels {
+        match level {
+            MatchConfidence::High => high += 1,
+            MatchConfidence::Medium => medium += 1,
+            MatchConfidence::Low => low += 1,
+        }
+    }
+    summary["high"] = high.into();
+    summary["medium"] = medium.into();
+    summary["low"] = low.into();
+}
+
+/// Stage the high-confidence corrections under `tool` and persist the queue;
+/// medium and low ones are left for review. Returns how many were staged.
+pub(super) fn stage_high_confidence(
+    server: &ReklawdboxServer,
+    tool: &str,
+    corrections: impl IntoIterator<Item = (MatchConfidence, TrackChange)>,
+) -> usize {
+    let changes: Vec<TrackChange> = corrections
+        .into_iter()
+        .filter(|(level, _)| *level == MatchConfidence::High)
+        .map(|(_, change)| change)
+        .collect();
+    if changes.is_empty() {
+        return 0;
+    }
+    let staged = server.state.changes.stage(tool, changes).0;
+    server.persist_staged_changes();
+    staged
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use rmcp::ErrorData as McpError;
+use rmcp::model::{CallToolResult, Content};
+
+use super::*;
+use crate::db;
+use crate::relocate::{self, CandidateIndex};
+use crate::types::{MatchConfidence, TrackChange};
+
+/// Default number of missing tracks matched per call.
+const DEFAULT_RELOCATION_LIMIT: u32 = 100;
+const DEFAULT_MAX_CANDIDATES: u32 = 3;
+/// Max uncached candidate files fingerprinted per call.
+const MAX_CANDIDATE_FINGERPRINTS: usize = 50;
+
+pub(super) async fn handle_find_relocations(
+    server: &ReklawdboxServer,
+    params: FindRelocationsParams,
+) -> Result<CallToolResult, McpError> {
+    if params.roots.is_empty() {
+        return Err(McpError::invalid_params(
+            "roots must include at least one directory to search".to_string(),
+            None,
+        ));
+    }
+    let limit = params.limit.unwrap_or(DEFAULT_RELOCATION_LIMIT) as usize;
+    let max_candidates = params
+        .max_candidates
+        .unwrap_or(DEFAULT_MAX_CANDIDATES)
+        .clamp(1, 10) as usize;
+
+    let (scanned_tracks, total_missing, missing, sizes) = {
+        let conn = server.rekordbox_conn()?;
+        let tracks = resolve_tracks(
+            &conn,
+            params.track_ids.as_deref(),
+            params.playlist_id.as_deref(),
+            params.filters,
+            None,
+            None,
+            &ResolveTracksOpts {
+                default_max_tracks: None,
+                max_tracks_cap: None,
+                exclude_samplers: true,
+            },
+        )?;
+        let scanned_tracks = tracks.len();
+        let mut missing: Vec<_> = tracks
+            .into_iter()
+            .filter(|t| relocate::is_missing(&t.file_path))
+            .collect();
+        let total_missing = missing.len();
+        missing.truncate(limit);
+        let ids: Vec<String> = missing.iter().map(|t| t.id.clone()).collect();
+        let sizes = db::get_file_sizes(&conn, &ids)
+            .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+        (scanned_tracks, total_missing, missing, sizes)
+    };
+
+    let fingerprints: HashMap<String, _> = {
+        let store_conn = server.cache_store_conn()?;
+        let mut fingerprints = HashMap::new();
+        for track in &missing {
+            if let Some(fp) = cached_fingerprint(&store_conn, &track.file_path)? {
+                fingerprints.insert(track.id.clone(), fp);
+            }
+        }
+        fingerprints
+    };
+
+    let roots: Vec<PathBuf> = params.roots.iter().map(PathBuf::from).collect();
+    let index = tokio::task::spawn_blocking(move || CandidateIndex::build(&roots))
+        .await
+        .map_err(|e| mcp_internal_error(format!("Relocation task failed: {e}")))?
+        .map_err(|e| McpError::invalid_params(e, None))?;
+
+    // Only candidates of tracks with a known fingerprint are worth decoding.
+    let candidate_paths: Vec<String> = missing
+        .iter()
+        .filter(|track| fingerprints.contains_key(&track.id))
+        .flat_map(|track| index.shortlisted_paths(track, sizes.get(&track.id).copied()))
+        .collect();
+    let candidate_fingerprints =
+        fingerprints_for_paths(server, &candidate_paths, MAX_CANDIDATE_FINGERPRINTS).await?;
+
+    let (computed, deferred) = (
+        candidate_fingerprints.computed,
+        candidate_fingerprints.deferred,
+    );
+    let (entries, indexed_files, warnings) = tokio::task::spawn_blocking(move || {
+        let entries: Vec<_> = missing
+            .iter()
+            .map(|track| {
+                relocate::plan_relocation(
+                    track,
+                    sizes.get(&track.id).copied(),
+                    fingerprints.get(&track.id),
+                    &candidate_fingerprints.fingerprints,
+                    &index,
+                    max_candidates,
+                )
+            })
+            .collect();
+        (entries, index.len(), index.warnings)
+    })
+    .await
+    .map_err(|e| mcp_internal_error(format!("Relocation task failed: {e}")))?;
+
+    let count = |confidence: Option<MatchConfidence>| {
+        entries
+            .iter()
+            .filter(|entry| entry.confidence == confidence)
+            .count()
+    };
+    let summary = serde_json::json!({
+        "scanned_tracks": scanned_tracks,
+        "missing_tracks": total_missing,
+        "matched_tracks": entries.len(),
+        "indexed_files": indexed_files,
+        "fingerprints_computed": computed,
+        "fingerprints_deferred": deferred,
+        "high": count(Some(MatchConfidence::High)),
+        "medium": count(Some(MatchConfidence::Medium)),
+        "low": count(Some(MatchConfidence::Low)),
+        "unmatched": count(None),
+    });
+
+    let mut staged = 0;
+    if params.stage_confident.unwrap_or(false) {
+        let changes: Vec<TrackChange> = entries
+            .iter()
+            .filter_map(|entry| {
+                entry.confident_path().map(|path| TrackChange {
+                    track_id: entry.track_id.clone(),
+                    file_path: Some(path.to_string()),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        if !changes.is_empty() {
+            staged = server.state.changes.stage("find_relocations", changes).0;
+            server.persist_staged_changes();
+        }
+    }
+
+    let mut result = serde_json::json!({
+        "summary": summary,
+        "relocations": entries,
+        "staged": staged,
+    });
+    if !warnings.is_empty() {
+        result["warnings"] = serde_json::json!(warnings);
+    }
+    let json =
+        serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
@@ -0,0 +1,350 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use rmcp::ErrorData as McpError;
+use rmcp::model::{CallToolResult, Content};
+
+use super::*;
+use crate::audio::{self, EssentiaOutput, SignalQuality};
+use crate::audit;
+use crate::db;
+use crate::replaygain::{self, TrackLoudness};
+use crate::tags;
+
+/// Uncached files `write_replaygain` decodes per call by default, and at most.
+const DEFAULT_REPLAYGAIN_ANALYZE_LIMIT: u32 = 50;
+const MAX_REPLAYGAIN_ANALYZE_LIMIT: u32 = 200;
+
+/// Files `write_replaygain` measures at once.
+const REPLAYGAIN_CONCURRENCY: usize = 4;
+
+/// Cached integrated loudness (from Essentia or native features) and signal
+/// quality for one file, keyed on its size and mtime.
+struct CachedLoudness {
+    file_size: i64,
+    file_mtime: i64,
+    loudness: Option<f64>,
+    quality: Option<SignalQuality>,
+}
+
+impl CachedLoudness {
+    fn is_complete(&self) -> bool {
+        self.loudness.is_some() && self.quality.is_some()
+    }
+}
+
+/// Native features and signal quality from a fresh decode, to be cached for
+/// the other tools.
+type FreshMeasurement = (EssentiaOutput, SignalQuality);
+
+fn cached_loudness(server: &ReklawdboxServer, file_path: &str) -> Result<CachedLoudness, String> {
+    let key = CacheKey::stat(file_path)?;
+    let store = server
+        .cache_store_conn()
+        .map_err(|e| e.message.to_string())?;
+    let mut loudness = None;
+    for analyzer in [audio::ANALYZER_ESSENTIA, audio::ANALYZER_NATIVE] {
+        let cached =
+            check_analysis_cache(&store, file_path, analyzer, key.file_size, key.file_mtime)?
+                .filter(|json_str| {
+                    analyzer != audio::ANALYZER_NATIVE || is_current_native(json_str)
+                });
+        loudness = cached
+            .and_then(|json_str| serde_json::from_str::<EssentiaOutput>(&json_str).ok())
+            .and_then(|features| features.loudness_integrated);
+        if loudness.is_some() {
+            break;
+        }
+    }
+    let quality = check_analysis_cache(
+        &store,
+        file_path,
+        audio::ANALYZER_QUALITY,
+        key.file_size,
+        key.file_mtime,
+    )?
+    .filter(|json_str| is_current_quality(json_str))
+    .and_then(|json_str| serde_json::from_str::<SignalQuality>(&json_str).ok());
+    Ok(CachedLoudness {
+        file_size: key.file_size,
+        file_mtime: key.file_mtime,
+        loudness,
+        quality,
+    })
+}
+
+/// Integrated loudness and linear peak for one file, from `cached` when it is
+/// complete or else a fresh native measurement, which is returned alongside
+/// (even when the file turns out silent) so the caller can cache it.
+async fn measure_loudness(
+    file_path: &str,
+    cached: CachedLoudness,
+) -> (Result<TrackLoudness, String>, Option<FreshMeasurement>) {
+    let fresh = if cached.is_complete() {
+        None
+    } else {
+        // Loudness and peaks only: streamed, never buffered
+        let decode = SharedDecode::new(file_path, false);
+        let measured = match analyze_native(&decode).await {
+            Ok(features) => decode.quality().await.map(|quality| (features, quality)),
+            Err(e) => Err(e),
+        };
+        match measured {
+            Ok(measured) => Some(measured),
+            Err(e) => return (Err(e), None),
+        }
+    };
+
+    let track = async {
+        let (loudness, quality) = match (&fresh, &cached.quality) {
+            (Some((features, quality)), _) => {
+                (cached.loudness.or(features.loudness_integrated), quality)
+            }
+            (None, Some(quality)) => (cached.loudness, quality),
+            (None, None) => return Err("No signal quality measured".to_string()),
+        };
+        let loudness_lufs =
+            loudness.ok_or_else(|| "No measurable loudness (silent file)".to_string())?;
+        let peak = replaygain::dbfs_to_linear(quality.sample_peak_dbfs);
+
+        let path = PathBuf::from(file_path);
+        let (duration_secs, album_tag) = tokio::task::spawn_blocking(move || {
+            (
+                replaygain::read_duration_secs(&path),
+                replaygain::read_album_tag(&path),
+            )
+        })
+        .await
+        .map_err(|e| format!("Tag read task failed: {e}"))?;
+        let duration_secs =
+            duration_secs.ok_or_else(|| format!("Cannot read duration of '{file_path}'"))?;
+
+        Ok(TrackLoudness {
+            path: PathBuf::from(file_path),
+            loudness_lufs,
+            peak,
+            duration_secs,
+            album_tag,
+        })
+    }
+    .await;
+    (track, fresh)
+}
+
+/// Album directory a file would be grouped under for album gain.
+fn album_dir_of(path: &Path) -> Option<PathBuf> {
+    audit::effective_album_dir_name(path).map(|(dir, _)| dir.to_path_buf())
+}
+
+pub(super) async fn handle_write_replaygain(
+    server: &ReklawdboxServer,
+    params: WriteReplayGainParams,
+) -> Result<CallToolResult, McpError> {
+    let dry_run = params.dry_run.unwrap_or(false);
+    let album_gain = params.album_gain.unwrap_or(true);
+    let limit = params.limit.unwrap_or(200).min(2000);
+    let max_analyze = params
+        .max_analyze
+        .unwrap_or(DEFAULT_REPLAYGAIN_ANALYZE_LIMIT)
+        .min(MAX_REPLAYGAIN_ANALYZE_LIMIT) as usize;
+
+    let mut errors: Vec<serde_json::Value> = Vec::new();
+    let mut file_paths: Vec<String> = match (params.paths, params.track_ids, params.directory) {
+        (Some(paths), None, None) => paths,
+        (None, Some(track_ids), None) => {
+            let conn = server.rekordbox_conn()?;
+            let mut resolved = Vec::with_capacity(track_ids.len());
+            for id in &track_ids {
+                let error = match db::get_track(&conn, id) {
+                    Ok(Some(track)) => match resolve_file_path(&track.file_path) {
+                        Ok(path) => {
+                            resolved.push(path);
+                            continue;
+                        }
+                        Err(e) => format!("Failed to resolve path: {e}"),
+                    },
+                    Ok(None) => format!("Track '{id}' not found"),
+                    Err(e) => format!("DB error: {e}"),
+                };
+                errors
+                    .push(serde_json::json!({ "path": format!("track_id:{id}"), "error": error }));
+            }
+            resolved
+        }
+        (None, None, Some(directory)) => scan_audio_directory(
+            &directory,
+            params.recursive.unwrap_or(false),
+            params.glob.as_deref(),
+        )
+        .map_err(mcp_internal_error)?,
+        _ => {
+            return Err(McpError::invalid_params(
+                "Provide exactly one of: paths, track_ids, directory".to_string(),
+                None,
+            ));
+        }
+    };
+    file_paths.truncate(limit);
+
+    // Cached measurements first; uncached files are decoded up to the cap
+    let mut pending = Vec::with_capacity(file_paths.len());
+    let mut deferred: Vec<String> = Vec::new();
+    let mut uncached = 0usize;
+    for file_path in &file_paths {
+        match cached_loudness(server, file_path) {
+            Ok(cached) => {
+                if !cached.is_complete() {
+                    if uncached == max_analyze {
+                        deferred.push(file_path.clone());
+                        continue;
+                    }
+                    uncached += 1;
+                }
+                pending.push((file_path.clone(), cached));
+            }
+            Err(e) => errors.push(serde_json::json!({ "path": file_path, "error": e })),
+        }
+    }
+    let semaphore = std::sync::Arc::new(tokio::sync::Semaphore::new(REPLAYGAIN_CONCURRENCY));
+    let mut handles = Vec::with_capacity(pending.len());
+    for (file_path, cached) in pending {
+        let sem = semaphore.clone();
+        handles.push(tokio::task::spawn(async move {
+            let _permit = sem.acquire().await.unwrap();
+            let (file_size, file_mtime) = (cached.file_size, cached.file_mtime);
+            let (track, fresh) = measure_loudness(&file_path, cached).await;
+            (file_path, file_size, file_mtime, track, fresh)
+        }));
+    }
+    let sink = CacheSink::Store(server);
+    let mut measured = Vec::with_capacity(handles.len());
+    let (mut analyzed, mut cached) = (0usize, 0usize);
+    for handle in handles {
+        let (file_path, file_size, file_mtime, track, fresh) = handle
+            .await
+            .map_err(|e| mcp_internal_error(format!("join error: {e}")))?;
+        if let Some((features, quality)) = &fresh {
+            let key = CacheKey {
+                file_path: &file_path,
+                file_size,
+                file_mtime,
+            };
+            for (analyzer, version, json) in [
+                (
+                    audio::ANALYZER_NATIVE,
+                    audio::NATIVE_FEATURES_VERSION,
+                    serde_json::to_string(features),
+                ),
+                (
+                    audio::ANALYZER_QUALITY,
+                    audio::QUALITY_VERSION,
+                    serde_json::to_string(quality),
+                ),
+            ] {
+                let json = json.map_err(|e| mcp_internal_error(format!("{e}")))?;
+                sink.write(&key, analyzer, version, json)
+                    .await
+                    .map_err(mcp_internal_error)?;
+            }
+        }
+        match track {
+            Ok(track) => {
+                if fresh.is_some() {
+                    analyzed += 1;
+                } else {
+                    cached += 1;
+                }
+                measured.push(track);
+            }
+            Err(e) => errors.push(serde_json::json!({ "path": file_path, "error": e })),
+        }
+    }
+
+    // Album gain over part of an album would be wrong, so the rest of an
+    // album with a deferred track is measured and cached but not written
+    if album_gain && !deferred.is_empty() {
+        let held: HashSet<PathBuf> = deferred
+            .iter()
+            .filter_map(|p| album_dir_of(Path::new(p)))
+            .collect();
+        measured.retain(|track| {
+            let hold = album_dir_of(&track.path).is_some_and(|dir| held.contains(&dir));
+            if hold {
+                deferred.push(track.path.display().to_string());
+            }
+            !hold
+        });
+    }
+
+    let (plans, albums) = replaygain::plan_gains(&measured, album_gain);
+    let mut results = Vec::with_capacity(plans.len());
+    let (mut files_changed, mut files_failed) = (0usize, 0usize);
+    for plan in &plans {
+        let album = plan.album.map(|i| &albums[i]);
+        let entry = tags::WriteEntry {
+            path: PathBuf::from(&plan.path),
+            tags: replaygain::replaygain_tags(plan, album),
+            // RIFF INFO has no ReplayGain fields; its existing layer is left as is
+            wav_targets: vec![tags::WavTarget::Id3v2],
+            comment_mode: tags::CommentMode::default(),
+        };
+        let outcome = if dry_run {
+            let result = tokio::task::spawn_blocking(move || tags::write_file_tags_dry_run(&entry))
+                .await
+                .map_err(|e| mcp_internal_error(format!("join error: {e}")))?;
+            match &result {
+                tags::FileDryRunResult::Preview { .. } => files_changed += 1,
+                tags::FileDryRunResult::Error { .. } => files_failed += 1,
+            }
+            serde_json::to_value(result)
+        } else {
+            let result = tokio::task::spawn_blocking(move || tags::write_file_tags(&entry))
+                .await
+                .map_err(|e| mcp_internal_error(format!("join error: {e}")))?;
+            match &result {
+                tags::FileWriteResult::Ok { .. } => files_changed += 1,
+                tags::FileWriteResult::Error { .. } => files_failed += 1,
+            }
+            serde_json::to_value(result)
+        }
+        .map_err(|e| mcp_internal_error(format!("{e}")))?;
+        results.push(serde_json::json!({
+            "path": plan.path,
+            "loudness_lufs": (plan.loudness_lufs * 100.0).round() / 100.0,
+            "track_gain_db": plan.track_gain_db,
+            "track_peak": plan.track_peak,
+            "album_gain_db": album.map(|a| a.gain_db),
+            "album_peak": album.map(|a| a.peak),
+            "tags": outcome,
+        }));
+    }
+
+    let mut summary = serde_json::json!({
+        "files": file_paths.len(),
+        "measured": measured.len(),
+        "cached": cached,
+        "analyzed": analyzed,
+        "deferred": deferred.len(),
+        "files_failed": files_failed + errors.len(),
+        "albums": albums.len(),
+    });
+    let changed_key = if dry_run {
+        "files_previewed"
+    } else {
+        "files_written"
+    };
+    summary[changed_key] = files_changed.into();
+
+    let output = serde_json::json!({
+        "dry_run": dry_run,
+        "reference_lufs": replaygain::REFERENCE_LUFS,
+        "summary": summary,
+        "albums": albums,
+        "results": results,
+        "deferred": deferred,
+        "errors": errors,
+    });
+    let json =
+        serde_json::to_string_pretty(&output).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
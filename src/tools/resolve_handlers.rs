@@ -13,11 +13,14 @@ pub(super) fn handle_resolve_track_data(
 ) -> Result<CallToolResult, McpError> {
     let track = {
         let conn = server.rekordbox_conn()?;
-        db::get_track(&conn, &params.track_id)
+        let mut track = db::get_track(&conn, &params.track_id)
             .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?
             .ok_or_else(|| {
                 McpError::invalid_params(format!("Track '{}' not found", params.track_id), None)
-            })?
+            })?;
+        db::attach_track_lists(&conn, std::slice::from_mut(&mut track))
+            .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+        track
     };
 
     let norm_artist = crate::normalize::normalize_for_matching(&track.artist);
@@ -42,9 +45,16 @@ pub(super) fn handle_resolve_track_data(
         (discogs_cache, beatport_cache, stratum_cache, essentia_cache)
     };
 
+    let structure = {
+        let store = server.cache_store_conn()?;
+        let audio_cache_key =
+            resolve_file_path(&track.file_path).unwrap_or_else(|_| track.file_path.clone());
+        cached_structure(&store, &audio_cache_key).map_err(mcp_internal_error)?
+    };
+
     let staged = server.state.changes.get(&track.id);
 
-    let result = resolve_single_track(
+    let mut result = resolve_single_track(
         &track,
         discogs_cache.as_ref(),
         beatport_cache.as_ref(),
@@ -53,6 +63,7 @@ pub(super) fn handle_resolve_track_data(
         essentia_installed,
         staged.as_ref(),
     );
+    attach_structure(&mut result, structure.as_ref());
 
     let json =
         serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
@@ -65,7 +76,7 @@ pub(super) fn handle_resolve_tracks_data(
 ) -> Result<CallToolResult, McpError> {
     let tracks = {
         let conn = server.rekordbox_conn()?;
-        resolve_tracks(
+        let mut tracks = resolve_tracks(
             &conn,
             params.track_ids.as_deref(),
             params.playlist_id.as_deref(),
@@ -77,7 +88,10 @@ pub(super) fn handle_resolve_tracks_data(
                 max_tracks_cap: Some(200),
                 exclude_samplers: false,
             },
-        )?
+        )?;
+        db::attach_track_lists(&conn, &mut tracks)
+            .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+        tracks
     };
 
     let params_format = params.format.unwrap_or_default();
@@ -87,7 +101,7 @@ pub(super) fn handle_resolve_tracks_data(
         let norm_artist = crate::normalize::normalize_for_matching(&track.artist);
         let norm_title = crate::normalize::normalize_for_matching(&track.title);
 
-        let (discogs_cache, beatport_cache, stratum_cache, essentia_cache) = {
+        let (discogs_cache, beatport_cache, stratum_cache, essentia_cache, structure) = {
             let store = server.cache_store_conn()?;
             let discogs_cache = store::get_enrichment(&store, "discogs", &norm_artist, &norm_title)
                 .map_err(|e| mcp_internal_error(format!("Cache read error: {e}")))?;
@@ -102,13 +116,21 @@ pub(super) fn handle_resolve_tracks_data(
             let essentia_cache =
                 store::get_audio_analysis(&store, &audio_cache_key, audio::ANALYZER_ESSENTIA)
                     .map_err(|e| mcp_internal_error(format!("Cache read error: {e}")))?;
-            (discogs_cache, beatport_cache, stratum_cache, essentia_cache)
+            let structure =
+                cached_structure(&store, &audio_cache_key).map_err(mcp_internal_error)?;
+            (
+                discogs_cache,
+                beatport_cache,
+                stratum_cache,
+                essentia_cache,
+                structure,
+            )
         };
 
         let result = match params_format {
             ResolveFormat::Full => {
                 let staged = server.state.changes.get(&track.id);
-                resolve_single_track(
+                let mut result = resolve_single_track(
                     track,
                     discogs_cache.as_ref(),
                     beatport_cache.as_ref(),
@@ -116,7 +138,9 @@ pub(super) fn handle_resolve_tracks_data(
                     essentia_cache.as_ref(),
                     essentia_installed,
                     staged.as_ref(),
-                )
+                );
+                attach_structure(&mut result, structure.as_ref());
+                result
             }
             ResolveFormat::Classification => resolve_single_track_compact(
                 track,
@@ -324,6 +348,9 @@ pub(crate) fn resolve_single_track(
         "color": track.color,
         "play_count": track.play_count,
         "date_added": track.date_added,
+        "my_tags": track.my_tags,
+        "hot_cue_banks": track.hot_cue_banks,
+        "related_track_lists": track.related_track_lists,
     });
 
     let (stratum_json, stratum_parse_error) = match stratum_cache {
@@ -382,12 +409,11 @@ pub(crate) fn resolve_single_track(
     let beatport_val = parse_enrichment_cache(beatport_cache);
 
     let staged_val = staged.map(|s| {
-        serde_json::json!({
-            "genre": s.genre,
-            "comments": s.comments,
-            "rating": s.rating,
-            "color": s.color,
-        })
+        let mut val = staged_change_json(s);
+        if let Some(obj) = val.as_object_mut() {
+            obj.remove("track_id");
+        }
+        val
     });
 
     let data_completeness = serde_json::json!({
@@ -461,6 +487,18 @@ pub(crate) fn resolve_single_track(
     })
 }
 
+/// Add the cached structure analysis and its suggested mix points to a
+/// resolved track.
+fn attach_structure(resolved: &mut serde_json::Value, structure: Option<&audio::TrackStructure>) {
+    resolved["structure"] = structure
+        .and_then(|s| serde_json::to_value(s).ok())
+        .unwrap_or(serde_json::Value::Null);
+    resolved["mix_points"] = structure
+        .map(mix_points_json)
+        .unwrap_or(serde_json::Value::Null);
+    resolved["data_completeness"]["structure"] = serde_json::json!(structure.is_some());
+}
+
 /// Build a compact resolved JSON for classification workflows.
 /// Returns only fields needed for the decision tree, ~400-500 bytes per track.
 fn resolve_single_track_compact(
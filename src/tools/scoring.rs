@@ -15,6 +15,8 @@ const PEAKONLY_RELEASE_END: f64 = 0.85;
 
 // Scoring factors
 const BPM_DRIFT_PENALTY_FACTOR: f64 = 0.7;
+/// Composite bonus for a transition already played live.
+const PLAYED_BEFORE_BONUS: f64 = 0.05;
 // Harmonic penalty factor is now per-style; see harmonic_penalty_factor()
 
 // Brightness axis thresholds (Hz)
@@ -27,6 +29,13 @@ const RHYTHM_MATCHED_DELTA: f64 = 0.1;
 const RHYTHM_MANAGEABLE_DELTA: f64 = 0.25;
 const RHYTHM_CHALLENGING_DELTA: f64 = 0.5;
 
+// Edge-window tempos further than this from the whole-track BPM are treated
+// as half/double-time misreads and ignored.
+const EDGE_BPM_MAX_DRIFT_PCT: f64 = 8.0;
+// Edge-window keys read with less stratum confidence than this fall back to
+// the whole-track key; 32 bars is a short window to read a key from.
+const EDGE_KEY_MIN_CONFIDENCE: f64 = 0.3;
+
 #[derive(Debug, Clone)]
 pub(super) struct TrackProfile {
     pub(super) track: crate::types::Track,
@@ -39,12 +48,52 @@ pub(super) struct TrackProfile {
     pub(super) loudness_range: Option<f64>,
     pub(super) canonical_genre: Option<String>,
     pub(super) genre_family: GenreFamily,
+    /// Tempo and key over the first and last bars, when windowed analysis
+    /// found them; an edge key read with low confidence is left out. Transitions
+    /// mix out of `closing` into `opening`.
+    pub(super) opening: Option<EdgeProfile>,
+    pub(super) closing: Option<EdgeProfile>,
+    /// Times each track (by ID) was played live straight after this one, from
+    /// Rekordbox play history. Empty unless the caller loaded it.
+    pub(super) played_before: HashMap<String, u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct EdgeProfile {
+    pub(super) bpm: f64,
+    pub(super) camelot_key: Option<CamelotKey>,
+}
+
+impl TrackProfile {
+    /// Tempo where the track is mixed in.
+    pub(super) fn start_bpm(&self) -> f64 {
+        self.opening.map_or(self.bpm, |edge| edge.bpm)
+    }
+
+    /// Tempo where the track is mixed out.
+    pub(super) fn end_bpm(&self) -> f64 {
+        self.closing.map_or(self.bpm, |edge| edge.bpm)
+    }
+
+    /// Key where the track is mixed in.
+    pub(super) fn start_key(&self) -> Option<CamelotKey> {
+        self.opening
+            .and_then(|edge| edge.camelot_key)
+            .or(self.camelot_key)
+    }
+
+    /// Key where the track is mixed out.
+    pub(super) fn end_key(&self) -> Option<CamelotKey> {
+        self.closing
+            .and_then(|edge| edge.camelot_key)
+            .or(self.camelot_key)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(super) struct CamelotKey {
-    number: u8,
-    letter: char,
+    pub(super) number: u8,
+    pub(super) letter: char,
 }
 
 pub(super) use crate::genre::GenreFamily;
@@ -621,12 +670,20 @@ pub(super) fn build_track_profile(
             .and_then(|cached| {
                 serde_json::from_str::<serde_json::Value>(&cached.features_json).ok()
             });
-    let essentia_data =
-        store::get_audio_analysis(store_conn, &cache_key, crate::audio::ANALYZER_ESSENTIA)
-            .map_err(|e| format!("essentia cache read error: {e}"))?
-            .and_then(|cached| {
-                serde_json::from_str::<crate::audio::EssentiaOutput>(&cached.features_json).ok()
-            });
+    let read_features = |analyzer: &str| {
+        store::get_audio_analysis(store_conn, &cache_key, analyzer)
+            .map_err(|e| format!("{analyzer} cache read error: {e}"))
+            .map(|cached| {
+                cached.and_then(|cached| {
+                    serde_json::from_str::<crate::audio::EssentiaOutput>(&cached.features_json).ok()
+                })
+            })
+    };
+    // Native features stand in on machines where Essentia never ran.
+    let essentia_data = match read_features(crate::audio::ANALYZER_ESSENTIA)? {
+        Some(features) => Some(features),
+        None => read_features(crate::audio::ANALYZER_NATIVE)?,
+    };
 
     let bpm = stratum_json
         .as_ref()
@@ -648,6 +705,33 @@ pub(super) fn build_track_profile(
             _ => track.key.clone(),
         });
 
+    let edge = |name: &str| {
+        let window = stratum_json.as_ref()?.get(name)?;
+        let edge_bpm = window
+            .get("bpm")
+            .and_then(serde_json::Value::as_f64)
+            .filter(|edge_bpm| {
+                bpm > 0.0 && (edge_bpm - bpm).abs() / bpm * 100.0 <= EDGE_BPM_MAX_DRIFT_PCT
+            })
+            .unwrap_or(bpm);
+        let edge_key = window
+            .get("key_camelot")
+            .and_then(serde_json::Value::as_str)
+            .and_then(parse_camelot_key)
+            .filter(|_| {
+                window
+                    .get("key_confidence")
+                    .and_then(serde_json::Value::as_f64)
+                    .is_some_and(|confidence| confidence >= EDGE_KEY_MIN_CONFIDENCE)
+            });
+        Some(EdgeProfile {
+            bpm: edge_bpm,
+            camelot_key: edge_key,
+        })
+    };
+    let opening = edge("opening");
+    let closing = edge("closing");
+
     let energy = compute_track_energy(essentia_data.as_ref(), bpm);
     let brightness = essentia_data
         .as_ref()
@@ -671,6 +755,9 @@ pub(super) fn build_track_profile(
         loudness_range,
         canonical_genre,
         genre_family,
+        opening,
+        closing,
+        played_before: HashMap::new(),
     })
 }
 
@@ -689,6 +776,10 @@ pub(super) fn score_transition_profiles(
     // When play_bpms is set, both tracks are pitched to target BPMs.
     // Compute effective keys based on the pitch shift from native BPM to play BPM.
     // When play_bpms is None, fall back to the existing master_tempo logic.
+    // Keys (and native tempos when not pitching to targets) come from the
+    // outgoing track's ending and the incoming track's opening.
+    let (from_key, to_key) = (from.end_key(), to.start_key());
+    let (from_bpm, to_bpm) = (from.end_bpm(), to.start_bpm());
     let (effective_to_key, pitch_shift_semitones, scoring_from_key, scoring_to_key, bpm) =
         if let Some((from_play_bpm, to_play_bpm)) = play_bpms {
             // Compute effective keys for both tracks based on play BPMs
@@ -704,15 +795,14 @@ pub(super) fn score_transition_profiles(
             };
 
             let effective_from_key = if !master_tempo && from_shift != 0 {
-                from.camelot_key
-                    .map(|k| transpose_camelot_key(k, from_shift))
+                from_key.map(|k| transpose_camelot_key(k, from_shift))
             } else {
-                from.camelot_key
+                from_key
             };
             let effective_to_key = if !master_tempo && to_shift != 0 {
-                to.camelot_key.map(|k| transpose_camelot_key(k, to_shift))
+                to_key.map(|k| transpose_camelot_key(k, to_shift))
             } else {
-                to.camelot_key
+                to_key
             };
 
             let effective_to_key_display = if !master_tempo && to_shift != 0 {
@@ -733,10 +823,10 @@ pub(super) fn score_transition_profiles(
             )
         } else {
             // Original master_tempo logic
-            let (eff_to_key, shift) = if !master_tempo && from.bpm > 0.0 && to.bpm > 0.0 {
-                let shift = (12.0 * (from.bpm / to.bpm).log2()).round() as i32;
+            let (eff_to_key, shift) = if !master_tempo && from_bpm > 0.0 && to_bpm > 0.0 {
+                let shift = (12.0 * (from_bpm / to_bpm).log2()).round() as i32;
                 if shift != 0 {
-                    let transposed = to.camelot_key.map(|k| transpose_camelot_key(k, shift));
+                    let transposed = to_key.map(|k| transpose_camelot_key(k, shift));
                     (transposed.map(format_camelot), shift)
                 } else {
                     (None, 0)
@@ -748,12 +838,12 @@ pub(super) fn score_transition_profiles(
             let scoring_to = if let Some(ref ek) = eff_to_key {
                 parse_camelot_key(ek)
             } else {
-                to.camelot_key
+                to_key
             };
 
-            let bpm_score = score_bpm_axis(from.bpm, to.bpm);
+            let bpm_score = score_bpm_axis(from_bpm, to_bpm);
 
-            (eff_to_key, shift, from.camelot_key, scoring_to, bpm_score)
+            (eff_to_key, shift, from_key, scoring_to, bpm_score)
         };
 
     let key = score_key_axis(scoring_from_key, scoring_to_key);
@@ -870,6 +960,20 @@ pub(super) fn score_transition_profiles(
         }
     }
 
+    // A transition that has worked in a real set is a proven pairing.
+    if let Some(&count) = from.played_before.get(&to.track.id)
+        && count > 0
+    {
+        let composite_without = composite;
+        composite = (composite + PLAYED_BEFORE_BONUS).min(1.0);
+        adjustments.push(ScoreAdjustment {
+            kind: "played_before",
+            delta: composite - composite_without,
+            composite_without,
+            reason: format!("Played live {count} time(s) before (+{PLAYED_BEFORE_BONUS})"),
+        });
+    }
+
     let key_relation = key.label.clone();
     let bpm_adjustment_pct = if let Some((_, to_play_bpm)) = play_bpms {
         if to.bpm > 0.0 {
@@ -877,8 +981,8 @@ pub(super) fn score_transition_profiles(
         } else {
             0.0
         }
-    } else if to.bpm > 0.0 {
-        (from.bpm - to.bpm).abs() / to.bpm * 100.0
+    } else if to_bpm > 0.0 {
+        (from_bpm - to_bpm).abs() / to_bpm * 100.0
     } else {
         0.0
     };
@@ -1336,7 +1440,7 @@ pub(super) fn compute_track_energy(
     }
 }
 
-fn canonicalize_genre(raw_genre: &str) -> Option<String> {
+pub(super) fn canonicalize_genre(raw_genre: &str) -> Option<String> {
     let trimmed = raw_genre.trim();
     if trimmed.is_empty() {
         return None;
@@ -1465,6 +1569,19 @@ pub(super) fn format_camelot(key: CamelotKey) -> String {
     format!("{}{}", key.number, key.letter)
 }
 
+/// Musical notation for a Camelot key, spelled the way Rekordbox does
+/// (e.g. `8A` → `Am`, `3B` → `Db`).
+pub(super) fn camelot_to_musical(key: CamelotKey) -> String {
+    const MINOR: [&str; 12] = [
+        "Abm", "Ebm", "Bbm", "Fm", "Cm", "Gm", "Dm", "Am", "Em", "Bm", "F#m", "C#m",
+    ];
+    const MAJOR: [&str; 12] = [
+        "B", "F#", "Db", "Ab", "Eb", "Bb", "F", "C", "G", "D", "A", "E",
+    ];
+    let names = if key.letter == 'A' { &MINOR } else { &MAJOR };
+    names[(key.number - 1) as usize].to_string()
+}
+
 /// Transpose a Camelot key by the given number of semitones.
 /// +1 semitone = +7 Camelot positions mod 12 (circle of fifths).
 /// Letter (A/B) is unchanged.
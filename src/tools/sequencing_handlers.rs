@@ -4,7 +4,9 @@ use rmcp::ErrorData as McpError;
 use rmcp::model::{CallToolResult, Content};
 
 use super::*;
+use crate::audio;
 use crate::db;
+use crate::phrase;
 
 pub(super) fn handle_score_transition(
     server: &ReklawdboxServer,
@@ -12,7 +14,7 @@ pub(super) fn handle_score_transition(
 ) -> Result<CallToolResult, McpError> {
     let priority = params.priority.unwrap_or(SequencingPriority::Balanced);
 
-    let (from_track, to_track) = {
+    let (from_track, to_track, (played_count, last_played)) = {
         let conn = server.rekordbox_conn()?;
         let from = db::get_track(&conn, &params.source_track_id)
             .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?
@@ -30,18 +32,32 @@ pub(super) fn handle_score_transition(
                     None,
                 )
             })?;
-        (from, to)
+        let played = db::get_played_transition(&conn, &from.id, &to.id)
+            .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+        (from, to, played)
     };
 
-    let (from_profile, to_profile) = {
+    let (from_profile, to_profile, phrase_alignment) = {
         let store = server.cache_store_conn()?;
-        let from = build_track_profile(from_track, &store).map_err(|e| {
+        let mut phrases = Vec::with_capacity(2);
+        for track in [&from_track, &to_track] {
+            let audio_cache_key =
+                resolve_file_path(&track.file_path).unwrap_or_else(|_| track.file_path.clone());
+            phrases.push(
+                cached_structure(&store, &audio_cache_key)
+                    .map_err(mcp_internal_error)?
+                    .map(|structure| structure.phrases)
+                    .unwrap_or_default(),
+            );
+        }
+        let mut from = build_track_profile(from_track, &store).map_err(|e| {
             mcp_internal_error(format!("Failed to build source track profile: {e}"))
         })?;
+        from.played_before.insert(to_track.id.clone(), played_count);
         let to = build_track_profile(to_track, &store).map_err(|e| {
             mcp_internal_error(format!("Failed to build destination track profile: {e}"))
         })?;
-        (from, to)
+        (from, to, phrase::phrase_alignment(&phrases[0], &phrases[1]))
     };
 
     let master_tempo = params.use_master_tempo.unwrap_or(true);
@@ -92,12 +108,35 @@ pub(super) fn handle_score_transition(
     if scores.pitch_shift_semitones != 0 {
         result["pitch_shift_semitones"] = serde_json::json!(scores.pitch_shift_semitones);
     }
+    // Key and BPM were scored on the edges that actually overlap in the mix.
+    if let Some(closing) = from_profile.closing {
+        result["from"]["closing"] = edge_json(closing);
+    }
+    if let Some(opening) = to_profile.opening {
+        result["to"]["opening"] = edge_json(opening);
+    }
+    // Informational only: phrase alignment doesn't move the composite.
+    if let Some(alignment) = phrase_alignment {
+        result["phrase_alignment"] = serde_json::json!(alignment);
+    }
+    // Past plays already moved the composite (see the played_before adjustment).
+    result["played_before"] = serde_json::json!({
+        "count": played_count,
+        "last_played": last_played,
+    });
 
     let json =
         serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
     Ok(CallToolResult::success(vec![Content::text(json)]))
 }
 
+fn edge_json(edge: EdgeProfile) -> serde_json::Value {
+    serde_json::json!({
+        "key": edge.camelot_key.map(format_camelot),
+        "bpm": round_to_3_decimals(edge.bpm),
+    })
+}
+
 pub(super) fn handle_query_transition_candidates(
     server: &ReklawdboxServer,
     params: QueryTransitionCandidatesParams,
@@ -152,11 +191,25 @@ pub(super) fn handle_query_transition_candidates(
         ));
     }
 
+    let played_from_source = {
+        let conn = server.rekordbox_conn()?;
+        let ids: Vec<String> = std::iter::once(from_track.id.clone())
+            .chain(pool_tracks.iter().map(|t| t.id.clone()))
+            .collect();
+        db::get_played_transitions(&conn, &ids)
+            .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?
+            .remove(&from_track.id)
+            .unwrap_or_default()
+    };
+
     // Build profiles
     let from_profile = {
         let store = server.cache_store_conn()?;
-        build_track_profile(from_track, &store)
-            .map_err(|e| mcp_internal_error(format!("Failed to build source track profile: {e}")))?
+        let mut profile = build_track_profile(from_track, &store).map_err(|e| {
+            mcp_internal_error(format!("Failed to build source track profile: {e}"))
+        })?;
+        profile.played_before = played_from_source;
+        profile
     };
 
     let mut pool_profiles: Vec<TrackProfile> = Vec::new();
@@ -315,11 +368,44 @@ pub(super) fn handle_build_set(
     let requested_target = params.target_tracks as usize;
     let priority = params.priority.unwrap_or(SequencingPriority::Balanced);
 
-    let tracks = {
+    let avoid_since = params
+        .avoid_played_since
+        .as_deref()
+        .map(|since| validate_history_date("avoid_played_since", since))
+        .transpose()?;
+
+    let (tracks, mut played_transitions, recently_played) = {
         let conn = server.rekordbox_conn()?;
-        db::get_tracks_by_ids(&conn, &deduped_ids)
-            .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?
+        let mut recently_played = Vec::new();
+        let mut pool_ids = deduped_ids;
+        if let Some(ref since) = avoid_since {
+            let played = db::get_tracks_played_since(&conn, since)
+                .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+            // An explicitly requested opener stays even if it was played recently.
+            pool_ids.retain(|id| {
+                let keep = !played.contains(id) || params.opening_track_id.as_ref() == Some(id);
+                if !keep {
+                    recently_played.push(id.clone());
+                }
+                keep
+            });
+        }
+        let tracks = db::get_tracks_by_ids(&conn, &pool_ids)
+            .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+        let played_transitions = db::get_played_transitions(&conn, &pool_ids)
+            .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+        (tracks, played_transitions, recently_played)
     };
+    if tracks.is_empty() && !recently_played.is_empty() {
+        return Err(McpError::invalid_params(
+            format!(
+                "All {} tracks were played since {}; nothing left to build from",
+                recently_played.len(),
+                avoid_since.unwrap_or_default()
+            ),
+            None,
+        ));
+    }
     if tracks.is_empty() {
         return Err(McpError::invalid_params(
             "No valid tracks found for provided track_ids".to_string(),
@@ -328,11 +414,22 @@ pub(super) fn handle_build_set(
     }
 
     let mut profiles_by_id: HashMap<String, TrackProfile> = HashMap::new();
+    let mut structures_by_id: HashMap<String, audio::TrackStructure> = HashMap::new();
     {
         let store = server.cache_store_conn()?;
         for track in tracks {
-            let profile = build_track_profile(track, &store)
+            let audio_cache_key =
+                resolve_file_path(&track.file_path).unwrap_or_else(|_| track.file_path.clone());
+            if let Some(structure) =
+                cached_structure(&store, &audio_cache_key).map_err(mcp_internal_error)?
+            {
+                structures_by_id.insert(track.id.clone(), structure);
+            }
+            let mut profile = build_track_profile(track, &store)
                 .map_err(|e| mcp_internal_error(format!("Failed to build track profile: {e}")))?;
+            profile.played_before = played_transitions
+                .remove(&profile.track.id)
+                .unwrap_or_default();
             profiles_by_id.insert(profile.track.id.clone(), profile);
         }
     }
@@ -474,6 +571,9 @@ pub(super) fn handle_build_set(
                         "energy": profile.energy,
                         "genre": profile.track.genre,
                     });
+                    if let Some(structure) = structures_by_id.get(track_id) {
+                        track_json["mix_points"] = mix_points_json(structure);
+                    }
 
                     // Enrich with BPM trajectory fields when bpm_range is set
                     if let Some(ref trajectory) = bpm_trajectory
@@ -525,6 +625,19 @@ pub(super) fn handle_build_set(
                     t["pitch_shift_semitones"] =
                         serde_json::json!(transition.scores.pitch_shift_semitones);
                 }
+                // Blend the outgoing outro over the incoming intro
+                if let (Some(from), Some(to)) = (
+                    plan.ordered_ids
+                        .get(transition.from_index)
+                        .and_then(|id| structures_by_id.get(id)),
+                    plan.ordered_ids
+                        .get(transition.to_index)
+                        .and_then(|id| structures_by_id.get(id)),
+                ) {
+                    t["mix_out_sec"] = serde_json::json!(from.mix_out_sec);
+                    t["mix_in_sec"] = serde_json::json!(to.mix_in_sec);
+                    t["blend_bars"] = serde_json::json!(from.outro_bars.min(to.intro_bars));
+                }
                 t
             })
             .collect();
@@ -590,6 +703,9 @@ pub(super) fn handle_build_set(
                 .collect::<Vec<f64>>()
         );
     }
+    if !recently_played.is_empty() {
+        result["excluded_recently_played"] = serde_json::json!(recently_played);
+    }
 
     let json =
         serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
@@ -0,0 +1,157 @@
+use rmcp::ErrorData as McpError;
+use rmcp::model::{CallToolResult, Content};
+use rusqlite::Connection;
+
+use super::*;
+use crate::store;
+
+pub(super) fn new_staging_session_id() -> String {
+    format!(
+        "{}-{:04x}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        rand::random::<u16>()
+    )
+}
+
+/// Create a fresh active session named after its creation time and return its ID.
+pub(super) fn start_staging_session(conn: &Connection) -> Result<String, rusqlite::Error> {
+    let id = new_staging_session_id();
+    let name = format!(
+        "Session {}",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+    );
+    store::create_staging_session(conn, &id, &name)?;
+    Ok(id)
+}
+
+fn session_json(session: &store::StagingSession) -> serde_json::Value {
+    serde_json::json!({
+        "session_id": session.id,
+        "name": session.name,
+        "active": session.is_active,
+        "change_count": session.change_count,
+        "created_at": session.created_at,
+        "updated_at": session.updated_at,
+    })
+}
+
+pub(super) fn handle_list_staging_sessions(
+    server: &ReklawdboxServer,
+) -> Result<CallToolResult, McpError> {
+    // Flush in-memory state first so the active session's count is current.
+    server.persist_staged_changes();
+    let conn = server.cache_store_conn()?;
+    let sessions = store::list_staging_sessions(&conn)
+        .map_err(|e| mcp_internal_error(format!("Store error: {e}")))?;
+    let result = serde_json::json!({
+        "active_session_id": server.state.changes.session_id(),
+        "sessions": sessions.iter().map(session_json).collect::<Vec<_>>(),
+    });
+    let json =
+        serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
+
+pub(super) fn handle_resume_staging_session(
+    server: &ReklawdboxServer,
+    params: ResumeStagingSessionParams,
+) -> Result<CallToolResult, McpError> {
+    server.persist_staged_changes();
+    let conn = server.cache_store_conn()?;
+    if !store::activate_staging_session(&conn, &params.session_id)
+        .map_err(|e| mcp_internal_error(format!("Store error: {e}")))?
+    {
+        return Err(McpError::invalid_params(
+            format!("staging session '{}' not found", params.session_id),
+            None,
+        ));
+    }
+    let changes = store::load_staged_changes(&conn, &params.session_id)
+        .map_err(|e| mcp_internal_error(format!("Store error: {e}")))?;
+    let previous = server.state.changes.session_id();
+    let total = server
+        .state
+        .changes
+        .replace_all(Some(params.session_id.clone()), changes);
+
+    let result = serde_json::json!({
+        "session_id": params.session_id,
+        "previous_session_id": previous,
+        "total_pending": total,
+    });
+    let json =
+        serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
+
+pub(super) fn handle_rename_staging_session(
+    server: &ReklawdboxServer,
+    params: RenameStagingSessionParams,
+) -> Result<CallToolResult, McpError> {
+    let name = params.name.trim();
+    if name.is_empty() {
+        return Err(McpError::invalid_params("name must not be empty", None));
+    }
+    let Some(session_id) = params
+        .session_id
+        .or_else(|| server.state.changes.session_id())
+    else {
+        return Err(McpError::invalid_params(
+            "no active staging session; pass session_id",
+            None,
+        ));
+    };
+    let conn = server.cache_store_conn()?;
+    if !store::rename_staging_session(&conn, &session_id, name)
+        .map_err(|e| mcp_internal_error(format!("Store error: {e}")))?
+    {
+        return Err(McpError::invalid_params(
+            format!("staging session '{session_id}' not found"),
+            None,
+        ));
+    }
+    let result = serde_json::json!({
+        "session_id": session_id,
+        "name": name,
+    });
+    let json =
+        serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
+
+pub(super) fn handle_discard_staging_session(
+    server: &ReklawdboxServer,
+    params: DiscardStagingSessionParams,
+) -> Result<CallToolResult, McpError> {
+    let conn = server.cache_store_conn()?;
+    let discarded = store::get_staging_session(&conn, &params.session_id)
+        .map_err(|e| mcp_internal_error(format!("Store error: {e}")))?;
+    let Some(discarded) = discarded else {
+        return Err(McpError::invalid_params(
+            format!("staging session '{}' not found", params.session_id),
+            None,
+        ));
+    };
+    store::delete_staging_session(&conn, &params.session_id)
+        .map_err(|e| mcp_internal_error(format!("Store error: {e}")))?;
+
+    // Discarding the active session drops its in-memory changes and opens a fresh session.
+    let mut result = serde_json::json!({
+        "discarded_session_id": discarded.id,
+        "discarded_changes": discarded.change_count,
+    });
+    if server.state.changes.session_id().as_deref() == Some(params.session_id.as_str()) {
+        let cleared = server.state.changes.snapshot().len();
+        let new_id = start_staging_session(&conn)
+            .map_err(|e| mcp_internal_error(format!("Store error: {e}")))?;
+        server
+            .state
+            .changes
+            .replace_all(Some(new_id.clone()), Vec::new());
+        result["discarded_changes"] = serde_json::json!(cleared);
+        result["active_session_id"] = serde_json::json!(new_id);
+    }
+    let json =
+        serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
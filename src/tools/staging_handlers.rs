@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::MutexGuard;
 
@@ -7,13 +7,38 @@ use rmcp::model::{CallToolResult, Content};
 use rusqlite::Connection;
 
 use super::*;
-use crate::changes::ChangeManager;
+use crate::anlz;
+use crate::audio;
+use crate::changes::{ChangeManager, HistoryEntry};
 use crate::color;
 use crate::db;
 use crate::genre;
-use crate::types::TrackChange;
+use crate::phrase;
+use crate::types::{CueKind, CuePoint, Playlist, StagedCue, TempoMarker, Track, TrackChange};
 use crate::xml;
 
+const STAGED_BPM_MIN: f64 = 20.0;
+const STAGED_BPM_MAX: f64 = 300.0;
+const STAGED_YEAR_MIN: i32 = 1900;
+/// Rekordbox BPM further than this from the analysed BPM counts as a disagreeing grid.
+const BEAT_GRID_BPM_TOLERANCE: f64 = 0.5;
+/// Rekordbox's first beat further than this from the analysed beat phase
+/// counts as a shifted grid.
+const BEAT_GRID_PHASE_TOLERANCE_SECS: f64 = 0.02;
+/// Minimum stratum-dsp `grid_stability` for an analysed grid to be exported.
+const BEAT_GRID_MIN_STABILITY: f64 = 0.5;
+/// Phrase cue suggestions closer than this to an existing cue are dropped.
+const PHRASE_CUE_MIN_GAP_SECS: f64 = 0.5;
+
+/// Trim tag names, dropping blanks and case-insensitive duplicates (first spelling wins).
+fn normalize_my_tags(tags: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    tags.into_iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty() && seen.insert(tag.to_lowercase()))
+        .collect()
+}
+
 pub(super) fn handle_update_tracks(
     changes: &ChangeManager,
     params: UpdateTracksParams,
@@ -40,6 +65,118 @@ pub(super) fn handle_update_tracks(
                 None,
             ));
         }
+        if let Some(ref title) = c.title
+            && title.trim().is_empty()
+        {
+            return Err(McpError::invalid_params(
+                format!("title must not be empty (track {})", c.track_id),
+                None,
+            ));
+        }
+        if let Some(bpm) = c.bpm
+            && !(STAGED_BPM_MIN..=STAGED_BPM_MAX).contains(&bpm)
+        {
+            return Err(McpError::invalid_params(
+                format!("bpm must be {STAGED_BPM_MIN}-{STAGED_BPM_MAX}, got {bpm}"),
+                None,
+            ));
+        }
+        if let Some(year) = c.year {
+            let year_max = chrono::Datelike::year(&chrono::Local::now()) + 1;
+            if !(STAGED_YEAR_MIN..=year_max).contains(&year) {
+                return Err(McpError::invalid_params(
+                    format!("year must be {STAGED_YEAR_MIN}-{year_max}, got {year}"),
+                    None,
+                ));
+            }
+        }
+        if let Some(tag) = c
+            .my_tags
+            .iter()
+            .flatten()
+            .find(|tag| tag.contains('/') || tag.contains('*'))
+        {
+            return Err(McpError::invalid_params(
+                format!(
+                    "My Tag '{tag}' must not contain '/' or '*' (reserved by the Comments tag block)"
+                ),
+                None,
+            ));
+        }
+        if let Some(ref file_path) = c.file_path
+            && !std::path::Path::new(file_path.trim()).is_file()
+        {
+            return Err(McpError::invalid_params(
+                format!(
+                    "file_path '{file_path}' is not an existing file (track {})",
+                    c.track_id
+                ),
+                None,
+            ));
+        }
+        if let Some(ref keeper) = c.merge_into {
+            let keeper = keeper.trim();
+            if keeper.is_empty() || keeper == c.track_id {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "merge_into must name a different track (track {})",
+                        c.track_id
+                    ),
+                    None,
+                ));
+            }
+            let keeper_is_merged = changes
+                .get(keeper)
+                .is_some_and(|pending| pending.merge_into.is_some())
+                || params
+                    .changes
+                    .iter()
+                    .any(|other| other.track_id == keeper && other.merge_into.is_some());
+            if keeper_is_merged {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "merge_into target {keeper} is itself staged to merge into another track"
+                    ),
+                    None,
+                ));
+            }
+        }
+        for cue in c.cues.iter().flatten() {
+            if !cue.position.is_finite() || cue.position < 0.0 {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "cue position must be a non-negative number of seconds, got {} (track {})",
+                        cue.position, c.track_id
+                    ),
+                    None,
+                ));
+            }
+            match cue.kind {
+                CueKind::HotCue if cue.hot_cue_slot().is_none() => {
+                    return Err(McpError::invalid_params(
+                        format!("hot cues need a bank A-H (track {})", c.track_id),
+                        None,
+                    ));
+                }
+                CueKind::Memory if cue.bank.is_some() => {
+                    return Err(McpError::invalid_params(
+                        format!("memory cues have no bank (track {})", c.track_id),
+                        None,
+                    ));
+                }
+                _ => {}
+            }
+        }
+        if let Some(ref key) = c.key
+            && key_to_camelot(key).is_none()
+        {
+            return Err(McpError::invalid_params(
+                format!(
+                    "unrecognized key '{key}'. Use musical notation (e.g. Am, F#, Dbm) or Camelot (e.g. 8A)"
+                ),
+                None,
+            ));
+        }
     }
 
     let mut warnings: Vec<String> = Vec::new();
@@ -64,23 +201,38 @@ pub(super) fn handle_update_tracks(
                     .map(String::from)
                     .unwrap_or(col)
             }),
+            title: c.title.map(|t| t.trim().to_string()),
+            artist: c.artist,
+            album: c.album,
+            label: c.label,
+            remixer: c.remixer,
+            year: c.year,
+            bpm: c.bpm.map(|b| (b * 100.0).round() / 100.0),
+            // Validated above; stored in Rekordbox's own spelling.
+            key: c
+                .key
+                .map(|k| key_to_camelot(&k).map_or(k, camelot_to_musical)),
+            my_tags: c.my_tags.map(normalize_my_tags),
+            file_path: c.file_path.map(|p| p.trim().to_string()),
+            merge_into: c.merge_into.map(|id| id.trim().to_string()),
+            cues: c.cues.map(|cues| {
+                cues.into_iter()
+                    .map(|cue| StagedCue {
+                        bank: cue.bank.map(|b| b.to_ascii_uppercase()),
+                        name: cue
+                            .name
+                            .map(|n| n.trim().to_string())
+                            .filter(|n| !n.is_empty()),
+                        ..cue
+                    })
+                    .collect()
+            }),
         })
         .collect();
 
-    let echo: Vec<serde_json::Value> = track_changes
-        .iter()
-        .map(|c| {
-            serde_json::json!({
-                "track_id": c.track_id,
-                "genre": c.genre,
-                "comments": c.comments,
-                "rating": c.rating,
-                "color": c.color,
-            })
-        })
-        .collect();
+    let echo: Vec<serde_json::Value> = track_changes.iter().map(staged_change_json).collect();
 
-    let (staged, total) = changes.stage(track_changes);
+    let (staged, total) = changes.stage("update_tracks", track_changes);
     let mut result = serde_json::json!({
         "staged": staged,
         "total_pending": total,
@@ -95,6 +247,15 @@ pub(super) fn handle_update_tracks(
     Ok(CallToolResult::success(vec![Content::text(json)]))
 }
 
+/// JSON echo of a staged change with only the fields that are set.
+pub(super) fn staged_change_json(change: &TrackChange) -> serde_json::Value {
+    let mut value = serde_json::to_value(change).unwrap_or(serde_json::Value::Null);
+    if let Some(obj) = value.as_object_mut() {
+        obj.retain(|_, v| !v.is_null());
+    }
+    value
+}
+
 pub(super) fn handle_suggest_normalizations(
     conn: MutexGuard<'_, Connection>,
     params: SuggestNormalizationsParams,
@@ -189,7 +350,11 @@ pub(super) fn handle_preview_changes(
     }
 
     let conn = server.rekordbox_conn()?;
-    let current_tracks = db::get_tracks_by_ids(&conn, &ids)
+    let mut current_tracks = db::get_tracks_by_ids(&conn, &ids)
+        .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+    db::attach_cues(&conn, &mut current_tracks)
+        .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+    db::attach_track_lists(&conn, &mut current_tracks)
         .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
 
     let diffs = server.state.changes.preview(&current_tracks);
@@ -209,7 +374,8 @@ pub(super) async fn handle_write_xml(
     params: WriteXmlParams,
 ) -> Result<CallToolResult, McpError> {
     let playlists = params.playlists.unwrap_or_default();
-    let has_playlists = !playlists.is_empty();
+    let rekordbox_playlist_ids = params.rekordbox_playlist_ids.unwrap_or_default();
+    let has_playlists = !playlists.is_empty() || !rekordbox_playlist_ids.is_empty();
     let snapshot = server.state.changes.take(None);
     if snapshot.is_empty() && !has_playlists {
         let mut result = serde_json::json!({
@@ -256,14 +422,67 @@ pub(super) async fn handle_write_xml(
         }
     }
 
+    let conn = match server.rekordbox_conn() {
+        Ok(conn) => conn,
+        Err(e) => {
+            server.state.changes.restore(snapshot);
+            return Err(e);
+        }
+    };
+
+    let mut playlist_defs: Vec<xml::PlaylistDef> = playlists
+        .iter()
+        .map(|playlist| xml::PlaylistDef {
+            name: playlist.name.clone(),
+            track_ids: playlist.track_ids.clone(),
+            folder: parse_folder_path(playlist.folder.as_deref()),
+        })
+        .collect();
+    let mut skipped_smart_playlists = Vec::new();
+    if !rekordbox_playlist_ids.is_empty() {
+        match rekordbox_playlist_defs(&conn, &rekordbox_playlist_ids) {
+            Ok((defs, skipped)) => {
+                playlist_defs.extend(defs);
+                skipped_smart_playlists = skipped;
+            }
+            Err(e) => {
+                server.state.changes.restore(snapshot);
+                return Err(e);
+            }
+        }
+    }
+
+    let merges = merge_targets(&snapshot);
+    let mut merged_playlists = 0;
+    if !merges.is_empty() {
+        match merged_playlist_defs(&conn, &merges, &playlist_defs) {
+            Ok(defs) => playlist_defs.extend(defs),
+            Err(e) => {
+                server.state.changes.restore(snapshot);
+                return Err(e);
+            }
+        }
+        merged_playlists = rewrite_merged_entries(&mut playlist_defs, &merges);
+    }
+
     let mut ids = Vec::new();
     let mut seen_ids = HashSet::new();
     for change in &snapshot {
+        if merges.contains_key(&change.track_id) {
+            continue;
+        }
         if seen_ids.insert(change.track_id.clone()) {
             ids.push(change.track_id.clone());
         }
     }
-    for playlist in &playlists {
+    // Keepers are always exported, so every retargeted entry and merged
+    // duplicate resolves to a COLLECTION track on reimport.
+    for keeper in merges.values() {
+        if seen_ids.insert(keeper.clone()) {
+            ids.push(keeper.clone());
+        }
+    }
+    for playlist in &playlist_defs {
         for track_id in &playlist.track_ids {
             if seen_ids.insert(track_id.clone()) {
                 ids.push(track_id.clone());
@@ -271,20 +490,56 @@ pub(super) async fn handle_write_xml(
         }
     }
 
-    let conn = match server.rekordbox_conn() {
-        Ok(conn) => conn,
-        Err(e) => {
-            server.state.changes.restore(snapshot);
-            return Err(e);
-        }
-    };
-    let current_tracks = match db::get_tracks_by_ids(&conn, &ids) {
+    let mut current_tracks = match db::get_tracks_by_ids(&conn, &ids) {
         Ok(tracks) => tracks,
         Err(e) => {
             server.state.changes.restore(snapshot);
             return Err(mcp_internal_error(format!("DB error: {e}")));
         }
     };
+    if let Err(e) = db::attach_cues(&conn, &mut current_tracks) {
+        server.state.changes.restore(snapshot);
+        return Err(mcp_internal_error(format!("DB error: {e}")));
+    }
+    let want_beat_grids = params.beat_grids.unwrap_or(false);
+    let analysis_paths = if want_beat_grids {
+        match db::get_analysis_data_paths(&conn, &ids) {
+            Ok(paths) => paths,
+            Err(e) => {
+                server.state.changes.restore(snapshot);
+                return Err(mcp_internal_error(format!("DB error: {e}")));
+            }
+        }
+    } else {
+        HashMap::new()
+    };
+    drop(conn);
+    let beat_grid_report = if want_beat_grids {
+        let staged_bpms: HashMap<&str, f64> = snapshot
+            .iter()
+            .filter_map(|c| Some((c.track_id.as_str(), c.bpm?)))
+            .collect();
+        match attach_beat_grids(server, &mut current_tracks, &analysis_paths, &staged_bpms) {
+            Ok(report) => Some(report),
+            Err(e) => {
+                server.state.changes.restore(snapshot);
+                return Err(e);
+            }
+        }
+    } else {
+        None
+    };
+    let phrase_cue_report = if params.phrase_cues.unwrap_or(false) {
+        match attach_phrase_cues(server, &mut current_tracks) {
+            Ok(report) => Some(report),
+            Err(e) => {
+                server.state.changes.restore(snapshot);
+                return Err(e);
+            }
+        }
+    } else {
+        None
+    };
     let found_ids: HashSet<&str> = current_tracks.iter().map(|t| t.id.as_str()).collect();
     let missing_ids: Vec<String> = ids
         .iter()
@@ -303,14 +558,6 @@ pub(super) async fn handle_write_xml(
         .state
         .changes
         .apply_snapshot(&ordered_tracks, &snapshot);
-    let playlist_defs: Vec<xml::PlaylistDef> = playlists
-        .iter()
-        .map(|playlist| xml::PlaylistDef {
-            name: playlist.name.clone(),
-            track_ids: playlist.track_ids.clone(),
-        })
-        .collect();
-
     let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
     let output_path = params
         .output_path
@@ -322,6 +569,9 @@ pub(super) async fn handle_write_xml(
         return Err(mcp_internal_error(format!("Write error: {e}")));
     }
 
+    // Written changes leave the queue, so earlier steps can no longer be undone coherently.
+    server.state.changes.clear_history();
+
     let track_count = modified_tracks.len();
     let changes_applied = snapshot.len();
 
@@ -330,8 +580,31 @@ pub(super) async fn handle_write_xml(
         "track_count": track_count,
         "changes_applied": changes_applied,
     });
-    if has_playlists {
-        result["playlist_count"] = serde_json::json!(playlists.len());
+    if has_playlists || !playlist_defs.is_empty() {
+        result["playlist_count"] = serde_json::json!(playlist_defs.len());
+    }
+    if !merges.is_empty() {
+        result["merged_duplicates"] = serde_json::json!(merges.len());
+        result["merged_playlists"] = serde_json::json!(merged_playlists);
+    }
+    if !skipped_smart_playlists.is_empty() {
+        result["skipped_smart_playlists"] = serde_json::json!(skipped_smart_playlists);
+    }
+    if let Some(report) = beat_grid_report {
+        result["beat_grids"] = report;
+    } else {
+        // Without TEMPO markers Rekordbox keeps the grid it had at the old BPM
+        let bpm_only: Vec<&str> = snapshot
+            .iter()
+            .filter(|c| c.bpm.is_some())
+            .map(|c| c.track_id.as_str())
+            .collect();
+        if !bpm_only.is_empty() {
+            result["bpm_changes_without_beat_grid"] = serde_json::json!(bpm_only);
+        }
+    }
+    if let Some(report) = phrase_cue_report {
+        result["phrase_cues"] = report;
     }
     attach_corpus_provenance(&mut result, consult_xml_workflow_docs());
     let json =
@@ -339,6 +612,385 @@ pub(super) async fn handle_write_xml(
     Ok(CallToolResult::success(vec![Content::text(json)]))
 }
 
+/// Split a '/'-separated folder path into trimmed, non-empty folder names.
+fn parse_folder_path(folder: Option<&str>) -> Vec<String> {
+    folder
+        .unwrap_or_default()
+        .split('/')
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Flatten the Rekordbox playlist subtrees rooted at `root_ids` into playlist
+/// definitions whose folder paths mirror the DB hierarchy (each root folder
+/// included). Folders without playlists are not exported. Smart playlists are
+/// exported with their current matches; those whose rules can't be evaluated
+/// are returned separately as skipped.
+fn rekordbox_playlist_defs(
+    conn: &Connection,
+    root_ids: &[String],
+) -> Result<(Vec<xml::PlaylistDef>, Vec<serde_json::Value>), McpError> {
+    let all = db::get_playlists(conn).map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+    let by_id: HashMap<&str, &Playlist> = all.iter().map(|p| (p.id.as_str(), p)).collect();
+    let mut children: HashMap<&str, Vec<&Playlist>> = HashMap::new();
+    for playlist in &all {
+        children
+            .entry(playlist.parent_id.as_str())
+            .or_default()
+            .push(playlist);
+    }
+
+    let mut defs = Vec::new();
+    let mut skipped_smart = Vec::new();
+    let mut visited = HashSet::new();
+    for root_id in root_ids {
+        let Some(root) = by_id.get(root_id.as_str()) else {
+            return Err(McpError::invalid_params(
+                format!("Rekordbox playlist '{root_id}' not found"),
+                None,
+            ));
+        };
+        let mut path = Vec::new();
+        let mut stack = vec![(*root, 0usize)];
+        // Depth-first walk; `depth` trims `path` back to the node's parent folder.
+        while let Some((playlist, depth)) = stack.pop() {
+            if !visited.insert(playlist.id.as_str()) {
+                continue;
+            }
+            path.truncate(depth);
+            if playlist.is_folder {
+                path.push(playlist.name.clone());
+                if let Some(kids) = children.get(playlist.id.as_str()) {
+                    stack.extend(kids.iter().rev().map(|kid| (*kid, depth + 1)));
+                }
+            } else {
+                let track_ids = match db::get_playlist_track_ids(conn, &playlist.id) {
+                    Ok(ids) => ids,
+                    Err(db::PlaylistError::SmartList(e)) => {
+                        skipped_smart.push(serde_json::json!({
+                            "playlist_id": playlist.id,
+                            "name": playlist.name,
+                            "reason": e.to_string(),
+                        }));
+                        continue;
+                    }
+                    Err(e) => return Err(mcp_internal_error(format!("DB error: {e}"))),
+                };
+                defs.push(xml::PlaylistDef {
+                    name: playlist.name.clone(),
+                    track_ids,
+                    folder: path.clone(),
+                });
+            }
+        }
+    }
+    Ok((defs, skipped_smart))
+}
+
+/// Map each staged duplicate to its final keeper, following `merge_into`
+/// chains. Cycles resolve to no merge.
+fn merge_targets(snapshot: &[TrackChange]) -> HashMap<String, String> {
+    let direct: HashMap<&str, &str> = snapshot
+        .iter()
+        .filter_map(|c| Some((c.track_id.as_str(), c.merge_into.as_deref()?)))
+        .collect();
+    let mut targets = HashMap::new();
+    for (&duplicate, &first) in &direct {
+        let mut keeper = first;
+        let mut seen = HashSet::from([duplicate]);
+        while let Some(&next) = direct.get(keeper) {
+            if !seen.insert(keeper) {
+                break;
+            }
+            keeper = next;
+        }
+        if !seen.contains(keeper) {
+            targets.insert(duplicate.to_string(), keeper.to_string());
+        }
+    }
+    targets
+}
+
+/// Playlists containing a merged duplicate that are not already exported,
+/// placed under their Rekordbox folder path so the reimport lines up.
+fn merged_playlist_defs(
+    conn: &Connection,
+    merges: &HashMap<String, String>,
+    exported: &[xml::PlaylistDef],
+) -> Result<Vec<xml::PlaylistDef>, McpError> {
+    let duplicate_ids: Vec<String> = merges.keys().cloned().collect();
+    let memberships = db::get_track_playlist_ids(conn, &duplicate_ids)
+        .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+    let affected: HashSet<&str> = memberships.values().flatten().map(String::as_str).collect();
+    if affected.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let all = db::get_playlists(conn).map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+    let by_id: HashMap<&str, &Playlist> = all.iter().map(|p| (p.id.as_str(), p)).collect();
+    let exported: HashSet<(&[String], &str)> = exported
+        .iter()
+        .map(|def| (def.folder.as_slice(), def.name.as_str()))
+        .collect();
+
+    let mut defs = Vec::new();
+    for playlist in all.iter().filter(|p| affected.contains(p.id.as_str())) {
+        let mut folder = Vec::new();
+        let mut parent = by_id.get(playlist.parent_id.as_str());
+        while let Some(folder_playlist) = parent
+            && folder.len() < all.len()
+        {
+            folder.push(folder_playlist.name.clone());
+            parent = by_id.get(folder_playlist.parent_id.as_str());
+        }
+        folder.reverse();
+        if exported.contains(&(folder.as_slice(), playlist.name.as_str())) {
+            continue;
+        }
+        let track_ids = db::get_playlist_track_ids(conn, &playlist.id)
+            .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+        defs.push(xml::PlaylistDef {
+            name: playlist.name.clone(),
+            track_ids,
+            folder,
+        });
+    }
+    Ok(defs)
+}
+
+/// Point merged duplicates' playlist entries at their keeper, dropping the
+/// entry when the keeper is already in the playlist. Returns the number of
+/// playlists changed.
+fn rewrite_merged_entries(
+    defs: &mut [xml::PlaylistDef],
+    merges: &HashMap<String, String>,
+) -> usize {
+    let mut changed = 0;
+    for def in defs.iter_mut() {
+        if !def.track_ids.iter().any(|id| merges.contains_key(id)) {
+            continue;
+        }
+        changed += 1;
+        let original = std::mem::take(&mut def.track_ids);
+        let present: HashSet<&str> = original.iter().map(String::as_str).collect();
+        let mut added = HashSet::new();
+        for id in &original {
+            match merges.get(id) {
+                Some(keeper) => {
+                    if !present.contains(keeper.as_str()) && added.insert(keeper.as_str()) {
+                        def.track_ids.push(keeper.clone());
+                    }
+                }
+                None => def.track_ids.push(id.clone()),
+            }
+        }
+    }
+    changed
+}
+
+/// Attach TEMPO markers from cached stratum-dsp analysis. Each track's
+/// analysis is compared against the grid Rekordbox stored in its ANLZ file
+/// (falling back to the library BPM when that file can't be read), and
+/// against the staged BPM when one exists. Returns a report of tracks that
+/// received a grid, half/double-time conflicts left for review, and tracks
+/// that needed a grid but couldn't get one.
+fn attach_beat_grids(
+    server: &ReklawdboxServer,
+    tracks: &mut [Track],
+    analysis_paths: &HashMap<String, String>,
+    staged_bpms: &HashMap<&str, f64>,
+) -> Result<serde_json::Value, McpError> {
+    let db_path = server.state.db_path.clone().or_else(db::resolve_db_path);
+    let store = server.cache_store_conn()?;
+    let mut written = Vec::new();
+    let mut conflicts = Vec::new();
+    let mut skipped = Vec::new();
+    for track in tracks.iter_mut() {
+        let cache_key =
+            resolve_file_path(&track.file_path).unwrap_or_else(|_| track.file_path.clone());
+        let analysis = store::get_audio_analysis(&store, &cache_key, audio::ANALYZER_STRATUM)
+            .map_err(|e| mcp_internal_error(format!("Cache read error: {e}")))?
+            .and_then(|cached| {
+                serde_json::from_str::<audio::StratumResult>(&cached.features_json).ok()
+            });
+        let Some(analysis) = analysis else {
+            skipped.push(serde_json::json!({ "track_id": track.id, "reason": "not_analyzed" }));
+            continue;
+        };
+        let stored_grid = db_path
+            .as_deref()
+            .zip(analysis_paths.get(&track.id))
+            .and_then(|(db_path, rel)| anlz::analysis_file_path(db_path, rel))
+            .and_then(|path| match anlz::read_beat_grid(&path) {
+                Ok(grid) => grid,
+                Err(e) => {
+                    tracing::debug!("Unreadable Rekordbox beat grid: {e}");
+                    None
+                }
+            })
+            .filter(|grid| !grid.is_empty());
+        let reference = GridReference {
+            stored: stored_grid.as_deref(),
+            library_bpm: track.bpm,
+            staged_bpm: staged_bpms.get(track.id.as_str()).copied(),
+        };
+        match tempo_marker_from_analysis(&reference, &analysis) {
+            Ok(Some(marker)) => {
+                written.push(track.id.clone());
+                track.tempo_markers = vec![marker];
+            }
+            Ok(None) => {}
+            Err(GridSkip::Conflict(reason)) => {
+                conflicts.push(serde_json::json!({
+                    "track_id": track.id,
+                    "reason": reason,
+                    "rekordbox_bpm": reference.bpm(),
+                    "analysed_bpm": analysis.bpm,
+                }));
+            }
+            Err(GridSkip::Unreliable(reason)) => {
+                skipped.push(serde_json::json!({ "track_id": track.id, "reason": reason }));
+            }
+        }
+    }
+    Ok(serde_json::json!({
+        "written": written,
+        "conflicts": conflicts,
+        "skipped": skipped,
+    }))
+}
+
+/// Add memory cues at the phrase sections of cached structure analysis.
+/// Suggestions within `PHRASE_CUE_MIN_GAP_SECS` of an existing cue are
+/// dropped, so re-exporting doesn't stack duplicates. Returns a report of
+/// cues added per track and tracks without a phrase analysis.
+fn attach_phrase_cues(
+    server: &ReklawdboxServer,
+    tracks: &mut [Track],
+) -> Result<serde_json::Value, McpError> {
+    let store = server.cache_store_conn()?;
+    let mut written = Vec::new();
+    let mut skipped = Vec::new();
+    for track in tracks.iter_mut() {
+        let cache_key =
+            resolve_file_path(&track.file_path).unwrap_or_else(|_| track.file_path.clone());
+        let phrases = cached_structure(&store, &cache_key)
+            .map_err(mcp_internal_error)?
+            .map(|structure| structure.phrases)
+            .unwrap_or_default();
+        if phrases.is_empty() {
+            skipped.push(serde_json::json!({ "track_id": track.id, "reason": "not_analyzed" }));
+            continue;
+        }
+        let new_cues: Vec<CuePoint> = phrase::phrase_cues(&phrases)
+            .into_iter()
+            .filter(|cue| {
+                track
+                    .cues
+                    .iter()
+                    .all(|existing| (existing.start - cue.start).abs() >= PHRASE_CUE_MIN_GAP_SECS)
+            })
+            .collect();
+        if new_cues.is_empty() {
+            continue;
+        }
+        written.push(serde_json::json!({ "track_id": track.id, "cues_added": new_cues.len() }));
+        track.cues.extend(new_cues);
+    }
+    Ok(serde_json::json!({
+        "written": written,
+        "skipped": skipped,
+    }))
+}
+
+/// What an analysed grid is checked against: Rekordbox's stored grid (if its
+/// ANLZ file was readable), the library BPM and any staged BPM correction.
+struct GridReference<'a> {
+    stored: Option<&'a [TempoMarker]>,
+    library_bpm: f64,
+    staged_bpm: Option<f64>,
+}
+
+impl GridReference<'_> {
+    /// The tempo the exported track will carry without an analysed grid.
+    fn bpm(&self) -> f64 {
+        self.staged_bpm
+            .or_else(|| self.stored.and_then(|grid| grid.first()).map(|m| m.bpm))
+            .unwrap_or(self.library_bpm)
+    }
+
+    /// Whether Rekordbox's own grid already matches the analysis in tempo
+    /// and beat phase. Without a readable grid only the library BPM is compared.
+    fn agrees_with(&self, analysis: &audio::StratumResult) -> bool {
+        if let Some(staged) = self.staged_bpm
+            && (staged - self.library_bpm).abs() > BEAT_GRID_BPM_TOLERANCE
+        {
+            // The stored grid was laid at the old tempo.
+            return false;
+        }
+        let Some(grid) = self.stored else {
+            return self.library_bpm > 0.0
+                && (self.library_bpm - analysis.bpm).abs() <= BEAT_GRID_BPM_TOLERANCE;
+        };
+        let tempo_agrees = grid
+            .iter()
+            .all(|m| (m.bpm - analysis.bpm).abs() <= BEAT_GRID_BPM_TOLERANCE);
+        let phase_agrees = match (grid.first(), analysis.first_beat_sec) {
+            (Some(first), Some(analysed)) => {
+                let period = 60.0 / analysis.bpm;
+                let offset = (first.start - analysed).rem_euclid(period);
+                offset.min(period - offset) <= BEAT_GRID_PHASE_TOLERANCE_SECS
+            }
+            _ => true,
+        };
+        tempo_agrees && phase_agrees
+    }
+}
+
+/// Why an analysed grid wasn't written.
+enum GridSkip {
+    /// Analysis and Rekordbox disagree in a way that needs a human decision.
+    Conflict(&'static str),
+    /// The analysis can't supply a reliable grid.
+    Unreliable(&'static str),
+}
+
+/// Decide whether a track should get an analysed beat grid.
+/// `Ok(None)` means Rekordbox's grid already agrees with analysis.
+fn tempo_marker_from_analysis(
+    reference: &GridReference,
+    analysis: &audio::StratumResult,
+) -> Result<Option<TempoMarker>, GridSkip> {
+    if analysis.bpm <= 0.0 {
+        return Err(GridSkip::Unreliable("no_analysed_bpm"));
+    }
+    let reference_bpm = reference.bpm();
+    if reference_bpm > 0.0 && tempo::octave_apart(reference_bpm, analysis.bpm) {
+        return Err(GridSkip::Conflict("half_double_time"));
+    }
+    if reference.agrees_with(analysis) {
+        return Ok(None);
+    }
+    if let Some(staged) = reference.staged_bpm
+        && (staged - analysis.bpm).abs() > BEAT_GRID_BPM_TOLERANCE
+    {
+        return Err(GridSkip::Conflict("staged_bpm_disagrees"));
+    }
+    if analysis.grid_stability < BEAT_GRID_MIN_STABILITY {
+        return Err(GridSkip::Unreliable("low_grid_stability"));
+    }
+    let Some(start) = analysis.first_beat_sec else {
+        return Err(GridSkip::Unreliable("no_first_beat_offset"));
+    };
+    Ok(Some(TempoMarker {
+        start,
+        bpm: (analysis.bpm * 100.0).round() / 100.0,
+        beat_in_bar: analysis.first_beat_in_bar.unwrap_or(1),
+    }))
+}
+
 pub(super) fn handle_clear_changes(
     changes: &ChangeManager,
     params: ClearChangesParams,
@@ -356,7 +1008,7 @@ pub(super) fn handle_clear_changes(
                 ));
             }
         }
-        let (affected, remaining) = changes.clear_fields(params.track_ids, fields);
+        let (affected, remaining) = changes.clear_fields("clear_changes", params.track_ids, fields);
         let result = serde_json::json!({
             "affected": affected,
             "remaining": remaining,
@@ -366,7 +1018,7 @@ pub(super) fn handle_clear_changes(
             .map_err(|e| mcp_internal_error(format!("{e}")))?;
         Ok(CallToolResult::success(vec![Content::text(json)]))
     } else {
-        let (cleared, remaining) = changes.clear(params.track_ids);
+        let (cleared, remaining) = changes.clear("clear_changes", params.track_ids);
         let result = serde_json::json!({
             "cleared": cleared,
             "remaining": remaining,
@@ -384,7 +1036,7 @@ pub(super) fn handle_clear_caches(
     let result =
         crate::store::clear_caches(&conn).map_err(|e| mcp_internal_error(format!("{e}")))?;
 
-    let staged = server.state.changes.clear(None).0;
+    let staged = server.state.changes.clear("clear_caches", None).0;
 
     let json = serde_json::json!({
         "cleared": {
@@ -400,3 +1052,60 @@ pub(super) fn handle_clear_caches(
         .map_err(|e| mcp_internal_error(format!("{e}")))?;
     Ok(CallToolResult::success(vec![Content::text(text)]))
 }
+
+fn history_entry_json(entry: &HistoryEntry) -> serde_json::Value {
+    serde_json::json!({
+        "step": entry.step,
+        "tool": entry.tool,
+        "summary": entry.summary,
+        "track_ids": entry.track_ids,
+        "recorded_at": entry.recorded_at,
+    })
+}
+
+pub(super) fn handle_undo_changes(
+    changes: &ChangeManager,
+    params: UndoRedoParams,
+) -> Result<CallToolResult, McpError> {
+    let steps = params.steps.unwrap_or(1);
+    if steps == 0 {
+        return Err(McpError::invalid_params("steps must be at least 1", None));
+    }
+    let (undone, total) = changes.undo(steps);
+    let result = serde_json::json!({
+        "undone": undone.iter().map(history_entry_json).collect::<Vec<_>>(),
+        "total_pending": total,
+    });
+    let json =
+        serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
+
+pub(super) fn handle_redo_changes(
+    changes: &ChangeManager,
+    params: UndoRedoParams,
+) -> Result<CallToolResult, McpError> {
+    let steps = params.steps.unwrap_or(1);
+    if steps == 0 {
+        return Err(McpError::invalid_params("steps must be at least 1", None));
+    }
+    let (redone, total) = changes.redo(steps);
+    let result = serde_json::json!({
+        "redone": redone.iter().map(history_entry_json).collect::<Vec<_>>(),
+        "total_pending": total,
+    });
+    let json =
+        serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
+
+pub(super) fn handle_change_history(changes: &ChangeManager) -> Result<CallToolResult, McpError> {
+    let (undo, redo) = changes.history();
+    let result = serde_json::json!({
+        "undo": undo.iter().map(history_entry_json).collect::<Vec<_>>(),
+        "redo": redo.iter().map(history_entry_json).collect::<Vec<_>>(),
+    });
+    let json =
+        serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
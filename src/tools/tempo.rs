@@ -0,0 +1,399 @@
+//! BPM octave reconciliation: settle half-time/double-time disagreements
+//! (87 vs 174, 65 vs 130) between Rekordbox, stratum-dsp, Essentia and
+//! Beatport.
+//!
+//! Every reading is expanded into half, same and double-time candidates.
+//! A candidate scores for each source that reads it directly (full weight)
+//! or an octave away (half weight), scaled by how typical the tempo is for
+//! the track's genre family and whether the onset rate can carry that many
+//! beats. Confidence is the winning candidate's share of the total score.
+//! Nothing is written here — corrections are staged as `bpm` changes.
+
+use serde::Serialize;
+
+use crate::genre::GenreFamily;
+use crate::types::MatchConfidence;
+
+/// Max relative difference for two readings to be the same tempo.
+const BPM_TOLERANCE_RATIO: f64 = 0.02;
+/// Candidates outside this range are not plausible dance-music tempos.
+const MIN_CANDIDATE_BPM: f64 = 55.0;
+const MAX_CANDIDATE_BPM: f64 = 210.0;
+/// Fewer onsets per beat than this means the pulse is too fast for the audio.
+const MIN_ONSETS_PER_BEAT: f64 = 0.75;
+/// More onsets per beat than this suggests a double-time pulse was missed.
+const MAX_ONSETS_PER_BEAT: f64 = 4.0;
+const HIGH_CONFIDENCE: f64 = 0.75;
+const MEDIUM_CONFIDENCE: f64 = 0.55;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum BpmSource {
+    Rekordbox,
+    Stratum,
+    Essentia,
+    Beatport,
+}
+
+impl BpmSource {
+    fn name(self) -> &'static str {
+        match self {
+            BpmSource::Rekordbox => "rekordbox",
+            BpmSource::Stratum => "stratum",
+            BpmSource::Essentia => "essentia",
+            BpmSource::Beatport => "beatport",
+        }
+    }
+
+    /// How much a reading from this source counts. Beatport tempos come from
+    /// the label; stratum-dsp tracks the beat grid more reliably than
+    /// Essentia's rhythm extractor.
+    fn weight(self) -> f64 {
+        match self {
+            BpmSource::Beatport => 1.0,
+            BpmSource::Stratum => 0.8,
+            BpmSource::Rekordbox => 0.7,
+            BpmSource::Essentia => 0.6,
+        }
+    }
+}
+
+/// Everything known about one track's tempo.
+#[derive(Debug, Clone, Default)]
+pub(super) struct BpmEvidence {
+    pub(super) rekordbox: Option<f64>,
+    pub(super) stratum: Option<f64>,
+    pub(super) essentia: Option<f64>,
+    pub(super) beatport: Option<f64>,
+    /// Onsets per second from the Essentia or native analysis.
+    pub(super) onset_rate: Option<f64>,
+    pub(super) family: Option<GenreFamily>,
+}
+
+impl BpmEvidence {
+    /// Usable readings, Rekordbox first so its grid precision is kept when
+    /// it only needs doubling or halving.
+    fn readings(&self) -> Vec<BpmReading> {
+        [
+            (BpmSource::Rekordbox, self.rekordbox),
+            (BpmSource::Stratum, self.stratum),
+            (BpmSource::Essentia, self.essentia),
+            (BpmSource::Beatport, self.beatport),
+        ]
+        .into_iter()
+        .filter_map(|(source, bpm)| {
+            bpm.filter(|bpm| bpm.is_finite() && *bpm > 0.0)
+                .map(|bpm| BpmReading { source, bpm })
+        })
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub(super) struct BpmReading {
+    pub(super) source: BpmSource,
+    pub(super) bpm: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(super) struct BpmReconciliation {
+    /// Best-supported tempo.
+    pub(super) bpm: f64,
+    /// Winning candidate's share of the total score, 0-1.
+    pub(super) confidence: f64,
+    pub(super) level: MatchConfidence,
+    /// New library BPM when Rekordbox is an octave off or has no tempo.
+    pub(super) corrected_bpm: Option<f64>,
+    pub(super) readings: Vec<BpmReading>,
+    pub(super) explanation: Vec<String>,
+}
+
+/// Typical tempo (BPM) and spread (octaves) per genre family.
+fn family_prior(family: Option<GenreFamily>) -> (f64, f64) {
+    match family {
+        Some(GenreFamily::House) => (124.0, 0.15),
+        Some(GenreFamily::Techno) => (132.0, 0.2),
+        Some(GenreFamily::Bass) => (150.0, 0.35),
+        Some(GenreFamily::Downtempo) => (95.0, 0.35),
+        Some(GenreFamily::Other) | None => (120.0, 0.8),
+    }
+}
+
+fn family_name(family: GenreFamily) -> &'static str {
+    match family {
+        GenreFamily::House => "house",
+        GenreFamily::Techno => "techno",
+        GenreFamily::Bass => "bass",
+        GenreFamily::Downtempo => "downtempo",
+        GenreFamily::Other => "other",
+    }
+}
+
+fn same_tempo(a: f64, b: f64) -> bool {
+    (a - b).abs() <= BPM_TOLERANCE_RATIO * a.max(b)
+}
+
+pub(super) fn octave_apart(a: f64, b: f64) -> bool {
+    same_tempo(a * 2.0, b) || same_tempo(a, b * 2.0)
+}
+
+fn prior_score(bpm: f64, family: Option<GenreFamily>) -> f64 {
+    let (center, spread) = family_prior(family);
+    let octaves = (bpm / center).log2() / spread;
+    (-0.5 * octaves * octaves).exp()
+}
+
+fn onset_score(bpm: f64, onset_rate: Option<f64>) -> f64 {
+    let Some(onset_rate) = onset_rate.filter(|rate| *rate > 0.0) else {
+        return 1.0;
+    };
+    let onsets_per_beat = onset_rate / (bpm / 60.0);
+    if onsets_per_beat < MIN_ONSETS_PER_BEAT {
+        0.4
+    } else if onsets_per_beat > MAX_ONSETS_PER_BEAT {
+        0.75
+    } else {
+        1.0
+    }
+}
+
+fn round_bpm(bpm: f64) -> f64 {
+    (bpm * 100.0).round() / 100.0
+}
+
+/// Resolve the track's tempo octave. Returns `None` when no source has a
+/// tempo.
+pub(super) fn reconcile(evidence: &BpmEvidence) -> Option<BpmReconciliation> {
+    let readings = evidence.readings();
+    if readings.is_empty() {
+        return None;
+    }
+
+    let mut candidates: Vec<f64> = Vec::new();
+    for reading in &readings {
+        for factor in [1.0, 0.5, 2.0] {
+            let bpm = reading.bpm * factor;
+            if (MIN_CANDIDATE_BPM..=MAX_CANDIDATE_BPM).contains(&bpm)
+                && !candidates.iter().any(|&c| same_tempo(c, bpm))
+            {
+                candidates.push(bpm);
+            }
+        }
+    }
+    if candidates.is_empty() {
+        return None;
+    }
+
+    let scores: Vec<f64> = candidates
+        .iter()
+        .map(|&bpm| {
+            let agreement: f64 = readings
+                .iter()
+                .map(|reading| {
+                    if same_tempo(reading.bpm, bpm) {
+                        reading.source.weight()
+                    } else if octave_apart(reading.bpm, bpm) {
+                        0.5 * reading.source.weight()
+                    } else {
+                        0.0
+                    }
+                })
+                .sum();
+            agreement * prior_score(bpm, evidence.family) * onset_score(bpm, evidence.onset_rate)
+        })
+        .collect();
+    let total: f64 = scores.iter().sum();
+    let (best, best_score) = scores
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, &score)| (i, score))?;
+    let bpm = round_bpm(candidates[best]);
+    let confidence = if total > 0.0 {
+        ((best_score / total) * 100.0).round() / 100.0
+    } else {
+        0.0
+    };
+    let level = if confidence >= HIGH_CONFIDENCE {
+        MatchConfidence::High
+    } else if confidence >= MEDIUM_CONFIDENCE {
+        MatchConfidence::Medium
+    } else {
+        MatchConfidence::Low
+    };
+
+    let mut explanation = Vec::new();
+    let agreeing: Vec<&str> = readings
+        .iter()
+        .filter(|r| same_tempo(r.bpm, bpm))
+        .map(|r| r.source.name())
+        .collect();
+    let octave_off: Vec<&str> = readings
+        .iter()
+        .filter(|r| octave_apart(r.bpm, bpm))
+        .map(|r| r.source.name())
+        .collect();
+    let unrelated: Vec<String> = readings
+        .iter()
+        .filter(|r| !same_tempo(r.bpm, bpm) && !octave_apart(r.bpm, bpm))
+        .map(|r| format!("{} ({:.2})", r.source.name(), r.bpm))
+        .collect();
+    if !agreeing.is_empty() {
+        explanation.push(format!("{bpm:.2} BPM read by {}", agreeing.join(", ")));
+    }
+    if !octave_off.is_empty() {
+        explanation.push(format!("{} read half/double time", octave_off.join(", ")));
+    }
+    if !unrelated.is_empty() {
+        explanation.push(format!(
+            "{} disagree beyond an octave",
+            unrelated.join(", ")
+        ));
+    }
+    if let Some(family) = evidence.family {
+        let (center, _) = family_prior(Some(family));
+        explanation.push(format!(
+            "{} family centres on ~{center:.0} BPM",
+            family_name(family)
+        ));
+    }
+    if let Some(onset_rate) = evidence.onset_rate.filter(|rate| *rate > 0.0) {
+        explanation.push(format!(
+            "onset rate {onset_rate:.1}/s is {:.1} onsets per beat",
+            onset_rate / (bpm / 60.0)
+        ));
+    }
+
+    let corrected_bpm = match evidence.rekordbox.filter(|bpm| *bpm > 0.0) {
+        Some(current) if octave_apart(current, bpm) => {
+            let direction = if bpm > current { "half" } else { "double" };
+            explanation.push(format!(
+                "Rekordbox {current:.2} is {direction} time; correct to {bpm:.2}"
+            ));
+            Some(bpm)
+        }
+        Some(_) => None,
+        None => {
+            explanation.push(format!("Rekordbox has no tempo; set {bpm:.2}"));
+            Some(bpm)
+        }
+    };
+
+    Some(BpmReconciliation {
+        bpm,
+        confidence,
+        level,
+        corrected_bpm,
+        readings,
+        explanation,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evidence(
+        rekordbox: f64,
+        stratum: Option<f64>,
+        essentia: Option<f64>,
+        beatport: Option<f64>,
+        family: Option<GenreFamily>,
+    ) -> BpmEvidence {
+        BpmEvidence {
+            rekordbox: Some(rekordbox),
+            stratum,
+            essentia,
+            beatport,
+            onset_rate: None,
+            family,
+        }
+    }
+
+    #[test]
+    fn half_time_drum_and_bass_is_doubled() {
+        let result = reconcile(&evidence(
+            87.0,
+            Some(174.1),
+            Some(86.9),
+            Some(174.0),
+            Some(GenreFamily::Bass),
+        ))
+        .unwrap();
+        assert_eq!(result.bpm, 174.0);
+        assert_eq!(result.corrected_bpm, Some(174.0));
+        assert_eq!(result.level, MatchConfidence::High);
+        assert!(
+            result
+                .explanation
+                .iter()
+                .any(|line| line.contains("half time")),
+            "{:?}",
+            result.explanation
+        );
+    }
+
+    #[test]
+    fn downtempo_prior_keeps_slow_tempo() {
+        let result = reconcile(&evidence(
+            87.0,
+            Some(174.0),
+            None,
+            None,
+            Some(GenreFamily::Downtempo),
+        ))
+        .unwrap();
+        assert_eq!(result.bpm, 87.0);
+        assert_eq!(result.corrected_bpm, None);
+    }
+
+    #[test]
+    fn double_time_house_is_halved() {
+        let result = reconcile(&evidence(
+            250.0,
+            Some(125.0),
+            Some(125.2),
+            None,
+            Some(GenreFamily::House),
+        ))
+        .unwrap();
+        assert_eq!(result.bpm, 125.0);
+        assert_eq!(result.corrected_bpm, Some(125.0));
+        assert_eq!(result.level, MatchConfidence::High);
+    }
+
+    #[test]
+    fn onset_rate_breaks_ties_without_genre() {
+        // Sparse onsets cannot carry 130 BPM.
+        let mut sparse = evidence(130.0, Some(65.0), None, None, None);
+        sparse.onset_rate = Some(1.0);
+        assert_eq!(reconcile(&sparse).unwrap().bpm, 65.0);
+
+        let mut busy = sparse.clone();
+        busy.onset_rate = Some(4.5);
+        assert_eq!(reconcile(&busy).unwrap().bpm, 130.0);
+    }
+
+    #[test]
+    fn unrelated_disagreement_is_not_corrected() {
+        let result = reconcile(&evidence(
+            128.0,
+            Some(140.0),
+            None,
+            None,
+            Some(GenreFamily::Techno),
+        ))
+        .unwrap();
+        assert_eq!(result.corrected_bpm, None);
+        assert_ne!(result.level, MatchConfidence::High);
+    }
+
+    #[test]
+    fn missing_rekordbox_tempo_is_filled() {
+        let mut evidence = evidence(0.0, Some(124.0), None, Some(124.0), None);
+        evidence.rekordbox = None;
+        let result = reconcile(&evidence).unwrap();
+        assert_eq!(result.corrected_bpm, Some(124.0));
+        assert_eq!(reconcile(&BpmEvidence::default()).map(|r| r.bpm), None);
+    }
+}
@@ -0,0 +1,144 @@
+use rmcp::ErrorData as McpError;
+use rmcp::model::{CallToolResult, Content};
+
+use super::tempo::{self, BpmEvidence};
+use super::*;
+use crate::audio;
+use crate::store;
+use crate::types::{Track, TrackChange};
+
+/// Tempo evidence for a track from the library and the analysis and
+/// enrichment caches. Nothing is analysed or looked up here.
+fn cached_evidence(store_conn: &Connection, track: &Track) -> Result<BpmEvidence, McpError> {
+    let cache_key = resolve_file_path(&track.file_path).unwrap_or_else(|_| track.file_path.clone());
+    let read_analysis = |analyzer: &str| {
+        store::get_audio_analysis(store_conn, &cache_key, analyzer)
+            .map_err(|e| mcp_internal_error(format!("Cache read error: {e}")))
+    };
+
+    let stratum = read_analysis(audio::ANALYZER_STRATUM)?
+        .and_then(|entry| serde_json::from_str::<audio::StratumResult>(&entry.features_json).ok())
+        .map(|analysis| analysis.bpm);
+    let mut features = None;
+    for analyzer in [audio::ANALYZER_ESSENTIA, audio::ANALYZER_NATIVE] {
+        features = read_analysis(analyzer)?.and_then(|entry| {
+            serde_json::from_str::<audio::EssentiaOutput>(&entry.features_json).ok()
+        });
+        if features.is_some() {
+            break;
+        }
+    }
+    let beatport = cached_beatport(store_conn, track).map_err(mcp_internal_error)?;
+
+    let family = canonicalize_genre(&track.genre)
+        .or_else(|| {
+            beatport
+                .as_ref()
+                .and_then(|result| canonicalize_genre(&result.genre))
+        })
+        .map(|genre| genre_family_for(&genre));
+    Ok(BpmEvidence {
+        rekordbox: Some(track.bpm),
+        stratum,
+        essentia: features.as_ref().and_then(|f| f.bpm_essentia),
+        beatport: beatport
+            .as_ref()
+            .and_then(|result| result.bpm)
+            .map(f64::from),
+        onset_rate: features.as_ref().and_then(|f| f.onset_rate),
+        family,
+    })
+}
+
+pub(super) fn handle_reconcile_bpm(
+    server: &ReklawdboxServer,
+    params: ReconcileBpmParams,
+) -> Result<CallToolResult, McpError> {
+    let limit = params.limit.unwrap_or(DEFAULT_RECONCILE_LIMIT) as usize;
+
+    let tracks = resolve_reconcile_tracks(
+        server,
+        params.track_ids.as_deref(),
+        params.playlist_id.as_deref(),
+        params.filters,
+        params.max_tracks,
+        params.offset,
+    )?;
+
+    let store_conn = server.cache_store_conn()?;
+    let mut reconciled = 0;
+    let mut corrections = Vec::new();
+    for track in &tracks {
+        let evidence = cached_evidence(&store_conn, track)?;
+        let Some(result) = tempo::reconcile(&evidence) else {
+            continue;
+        };
+        reconciled += 1;
+        if result.corrected_bpm.is_some() {
+            corrections.push((track, result));
+        }
+    }
+    drop(store_conn);
+    corrections.sort_by(|(_, a), (_, b)| b.confidence.total_cmp(&a.confidence));
+
+    let mut summary = serde_json::json!({
+        "scanned_tracks": tracks.len(),
+        "reconciled": reconciled,
+        "no_tempo_evidence": tracks.len() - reconciled,
+        "corrections": corrections.len(),
+    });
+    add_confidence_counts(
+        &mut summary,
+        corrections.iter().map(|(_, result)| result.level),
+    );
+
+    let mut staged = 0;
+    if params.stage_corrections.unwrap_or(false) {
+        staged = stage_high_confidence(
+            server,
+            "reconcile_bpm",
+            corrections.iter().filter_map(|(track, result)| {
+                let change = TrackChange {
+                    track_id: track.id.clone(),
+                    bpm: Some(result.corrected_bpm?),
+                    ..Default::default()
+                };
+                Some((result.level, change))
+            }),
+        );
+    }
+
+    let entries: Vec<serde_json::Value> = corrections
+        .iter()
+        .take(limit)
+        .map(|(track, result)| {
+            serde_json::json!({
+                "track_id": track.id,
+                "title": track.title,
+                "artist": track.artist,
+                "genre": track.genre,
+                "current_bpm": track.bpm,
+                "corrected_bpm": result.corrected_bpm,
+                "confidence": result.confidence,
+                "level": result.level,
+                "readings": result.readings,
+                "explanation": result.explanation,
+            })
+        })
+        .collect();
+
+    let mut result = serde_json::json!({
+        "summary": summary,
+        "corrections": entries,
+        "staged": staged,
+    });
+    // A staged BPM alone leaves Rekordbox's beat grid at the old tempo
+    if staged > 0 {
+        result["beat_grid"] = serde_json::json!(
+            "Staged BPM changes don't move the beat grid. Export with write_xml beat_grids: true to write a matching TEMPO grid from stratum-dsp analysis."
+        );
+    }
+    let json =
+        serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
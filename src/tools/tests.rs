@@ -11,6 +11,8 @@ use serde::Deserialize;
 use tempfile::TempDir;
 
 use crate::genre;
+use crate::tags;
+use crate::types::CueKind;
 
 fn extract_json(result: &CallToolResult) -> serde_json::Value {
     let text = result
@@ -110,6 +112,16 @@ fn create_server_with_store_path(
     store_conn: Connection,
     http: reqwest::Client,
     store_path: Option<String>,
+) -> ReklawdboxServer {
+    create_server_with_paths(db_conn, store_conn, http, None, store_path)
+}
+
+fn create_server_with_paths(
+    db_conn: Connection,
+    store_conn: Connection,
+    http: reqwest::Client,
+    db_path: Option<String>,
+    store_path: Option<String>,
 ) -> ReklawdboxServer {
     let server = ReklawdboxServer {
         state: Arc::new(ServerState {
@@ -119,7 +131,7 @@ fn create_server_with_store_path(
             essentia_python_override: Mutex::new(None),
             essentia_setup_lock: tokio::sync::Mutex::new(()),
             discogs_pending: Mutex::new(None),
-            db_path: None,
+            db_path,
             store_path,
             changes: ChangeManager::new(),
             http,
@@ -232,9 +244,48 @@ fn create_single_track_test_db(track_id: &str, raw_file_path: &str) -> Connectio
                 BitRate INTEGER DEFAULT 0,
                 SampleRate INTEGER DEFAULT 0,
                 FileType INTEGER DEFAULT 0,
+                FileSize INTEGER DEFAULT 0,
                 created_at TEXT DEFAULT '',
                 rb_local_deleted INTEGER DEFAULT 0
             );
+            CREATE TABLE djmdCue (
+                ID VARCHAR(255) PRIMARY KEY,
+                ContentID VARCHAR(255),
+                InMsec INTEGER,
+                OutMsec INTEGER DEFAULT -1,
+                Kind INTEGER DEFAULT 0,
+                Comment VARCHAR(255) DEFAULT '',
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+            CREATE TABLE djmdMyTag (
+                ID VARCHAR(255) PRIMARY KEY,
+                Seq INTEGER,
+                Name VARCHAR(255),
+                ParentID VARCHAR(255) DEFAULT 'root',
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+            CREATE TABLE djmdSongMyTag (
+                ID VARCHAR(255) PRIMARY KEY,
+                MyTagID VARCHAR(255),
+                ContentID VARCHAR(255),
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+            CREATE TABLE djmdHistory (
+                ID VARCHAR(255) PRIMARY KEY,
+                Seq INTEGER,
+                Name VARCHAR(255),
+                Attribute INTEGER DEFAULT 0,
+                ParentID VARCHAR(255) DEFAULT 'root',
+                DateCreated VARCHAR(255),
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+            CREATE TABLE djmdSongHistory (
+                ID VARCHAR(255) PRIMARY KEY,
+                HistoryID VARCHAR(255),
+                ContentID VARCHAR(255),
+                TrackNo INTEGER,
+                rb_local_deleted INTEGER DEFAULT 0
+            );
 
             INSERT INTO djmdArtist (ID, Name) VALUES ('a1', 'Aníbal');
             INSERT INTO djmdAlbum (ID, Name) VALUES ('al1', 'Encoded Paths');
@@ -464,6 +515,7 @@ async fn build_set_generates_candidates_and_transition_scores() {
             harmonic_style: None,
             bpm_drift_pct: None,
             bpm_range: None,
+            avoid_played_since: None,
         }))
         .await
         .expect("build_set should succeed for fixture pool");
@@ -544,6 +596,101 @@ async fn build_set_generates_candidates_and_transition_scores() {
     );
 }
 
+fn seed_structure(store_conn: &Connection, path: &str, intro_bars: usize, outro_bars: usize) {
+    let bar_seconds = 1.875;
+    let total_bars = 96;
+    let structure = crate::audio::TrackStructure {
+        analyzer_version: crate::audio::STRUCTURE_VERSION.to_string(),
+        bpm: 128.0,
+        bar_seconds,
+        first_downbeat_sec: 0.25,
+        total_bars,
+        intro_bars,
+        outro_bars,
+        sections: vec![],
+        phrases: [
+            (crate::phrase::PhraseLabel::Intro, 0, intro_bars),
+            (
+                crate::phrase::PhraseLabel::Groove,
+                intro_bars,
+                total_bars - intro_bars - outro_bars,
+            ),
+            (
+                crate::phrase::PhraseLabel::Outro,
+                total_bars - outro_bars,
+                outro_bars,
+            ),
+        ]
+        .into_iter()
+        .map(|(label, start_bar, bars)| crate::phrase::PhraseSection {
+            label,
+            start_bar,
+            bars,
+            start_sec: 0.25 + start_bar as f64 * bar_seconds,
+        })
+        .collect(),
+        mix_in_sec: 0.25,
+        mix_in_end_sec: 0.25 + intro_bars as f64 * bar_seconds,
+        mix_out_sec: 0.25 + (total_bars - outro_bars) as f64 * bar_seconds,
+        mix_out_end_sec: 0.25 + total_bars as f64 * bar_seconds,
+    };
+    store::set_audio_analysis(
+        store_conn,
+        path,
+        crate::audio::ANALYZER_STRUCTURE,
+        1,
+        1,
+        crate::audio::STRUCTURE_VERSION,
+        &serde_json::to_string(&structure).unwrap(),
+    )
+    .expect("structure cache seed should succeed");
+}
+
+#[tokio::test]
+async fn build_set_reports_cached_mix_points() {
+    let (db_conn, track_ids) = create_build_set_test_db();
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(store_path.to_str().unwrap()).expect("store should open");
+    seed_build_set_cache(&store_conn);
+    for index in 1..=6 {
+        seed_structure(&store_conn, &format!("/tmp/set-track-{index}.flac"), 16, 8);
+    }
+
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+    let result = server
+        .build_set(Parameters(BuildSetParams {
+            track_ids,
+            target_tracks: 3,
+            priority: None,
+            energy_curve: None,
+            opening_track_id: None,
+            candidates: None,
+            beam_width: Some(1),
+            use_master_tempo: None,
+            harmonic_style: None,
+            bpm_drift_pct: None,
+            bpm_range: None,
+            avoid_played_since: None,
+        }))
+        .await
+        .expect("build_set should succeed");
+    let payload = extract_json(&result);
+    let candidate = &payload["candidates"][0];
+
+    for track in candidate["tracks"].as_array().unwrap() {
+        assert_eq!(track["mix_points"]["intro_bars"], 16);
+        assert_eq!(track["mix_points"]["mix_in_sec"], 0.25);
+        assert_eq!(track["mix_points"]["mix_out_sec"], 0.25 + 88.0 * 1.875);
+    }
+    for transition in candidate["transitions"].as_array().unwrap() {
+        assert_eq!(transition["mix_out_sec"], 0.25 + 88.0 * 1.875);
+        assert_eq!(transition["mix_in_sec"], 0.25);
+        assert_eq!(transition["blend_bars"], 8, "outro is the shorter side");
+    }
+}
+
 #[tokio::test]
 async fn build_set_adapts_energy_curve_to_single_track_pool() {
     let db_conn = create_single_track_test_db("single-set-1", "/tmp/single-set-1.flac");
@@ -583,6 +730,7 @@ async fn build_set_adapts_energy_curve_to_single_track_pool() {
             harmonic_style: None,
             bpm_drift_pct: None,
             bpm_range: None,
+            avoid_played_since: None,
         }))
         .await
         .expect("build_set should succeed for single-track pool");
@@ -663,6 +811,7 @@ async fn build_set_produces_candidates_from_homogeneous_key_pool() {
             harmonic_style: None,
             bpm_drift_pct: None,
             bpm_range: None,
+            avoid_played_since: None,
         }))
         .await
         .expect("build_set should succeed when all tracks share the same key");
@@ -721,6 +870,7 @@ async fn build_set_recomputes_preset_curve_when_pool_is_smaller_than_target() {
             harmonic_style: None,
             bpm_drift_pct: None,
             bpm_range: None,
+            avoid_played_since: None,
         }))
         .await
         .expect("build_set should succeed when pool is smaller than target");
@@ -858,12 +1008,7 @@ async fn analyze_track_audio_reports_essentia_unavailable_when_probe_is_none() {
 
     let metadata = std::fs::metadata(&audio_path).expect("temp audio metadata should load");
     let file_size = metadata.len() as i64;
-    let file_mtime = metadata
-        .modified()
-        .ok()
-        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-        .map(|d| d.as_secs() as i64)
-        .unwrap_or(0);
+    let file_mtime = store::file_mtime_unix(&metadata);
 
     let db_conn = create_single_track_test_db("essentia-missing-1", &audio_path_str);
     let store_dir = tempfile::tempdir().expect("temp store dir should create");
@@ -1064,6 +1209,9 @@ async fn write_xml_no_change_path_includes_provenance() {
         .write_xml(Parameters(WriteXmlParams {
             output_path: None,
             playlists: None,
+            rekordbox_playlist_ids: None,
+            beat_grids: None,
+            phrase_cues: None,
         }))
         .await
         .expect("write_xml should succeed when no changes are staged");
@@ -1097,6 +1245,13 @@ async fn write_xml_no_change_path_via_router_includes_provenance() {
 #[tokio::test]
 async fn write_xml_with_playlists_exports_without_staged_changes() {
     let db_conn = create_single_track_test_db("playlist-track-1", "/tmp/playlist-track-1.flac");
+    db_conn
+        .execute(
+            "INSERT INTO djmdCue (ID, ContentID, InMsec, Kind, Comment)
+             VALUES ('cue1', 'playlist-track-1', 1500, 1, 'Intro')",
+            [],
+        )
+        .expect("test cue should insert");
     let store_dir = tempfile::tempdir().expect("temp store dir should create");
     let store_path = store_dir.path().join("internal.sqlite3");
     let store_conn = store::open(
@@ -1118,7 +1273,11 @@ async fn write_xml_with_playlists_exports_without_staged_changes() {
             playlists: Some(vec![WriteXmlPlaylistInput {
                 name: "Set & Test".to_string(),
                 track_ids: vec!["playlist-track-1".to_string()],
+                folder: None,
             }]),
+            rekordbox_playlist_ids: None,
+            beat_grids: None,
+            phrase_cues: None,
         }))
         .await
         .expect("write_xml should export playlist-only requests");
@@ -1133,52 +1292,149 @@ async fn write_xml_with_playlists_exports_without_staged_changes() {
     );
 
     let xml = std::fs::read_to_string(&output_path).expect("XML output should be readable");
+    assert!(xml.contains("<POSITION_MARK Name=\"Intro\" Type=\"0\" Start=\"1.500\" Num=\"0\"/>"));
     assert!(xml.contains("<PLAYLISTS>"));
     assert!(xml.contains("Name=\"Set &amp; Test\""));
     assert!(xml.contains("<TRACK Key=\"1\"/>"));
 }
 
 #[tokio::test]
-async fn write_xml_with_playlists_reports_missing_track_ids() {
-    let db_conn = create_single_track_test_db("playlist-track-1", "/tmp/playlist-track-1.flac");
+async fn staged_cues_preview_undo_and_export_as_position_marks() {
+    let db_conn = create_single_track_test_db("cue-track-1", "/tmp/cue-track-1.flac");
+    db_conn
+        .execute(
+            "INSERT INTO djmdCue (ID, ContentID, InMsec, Kind, Comment)
+             VALUES ('cue1', 'cue-track-1', 1500, 1, 'Intro')",
+            [],
+        )
+        .expect("test cue should insert");
     let store_dir = tempfile::tempdir().expect("temp store dir should create");
-    let store_path = store_dir.path().join("internal.sqlite3");
     let store_conn = store::open(
-        store_path
+        store_dir
+            .path()
+            .join("internal.sqlite3")
             .to_str()
             .expect("temp store path should be UTF-8"),
     )
     .expect("temp internal store should open");
     let server =
         create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+    let stage = |cues: Vec<crate::types::StagedCue>| {
+        server.update_tracks(Parameters(UpdateTracksParams {
+            changes: vec![TrackChangeInput {
+                track_id: "cue-track-1".to_string(),
+                cues: Some(cues),
+                ..Default::default()
+            }],
+        }))
+    };
+    let cue = |kind, position, bank: Option<char>, name: Option<&str>| crate::types::StagedCue {
+        kind,
+        position,
+        bank,
+        name: name.map(str::to_string),
+    };
 
-    let err = server
+    for (bad, expected) in [
+        (cue(CueKind::HotCue, 1.0, None, None), "need a bank"),
+        (cue(CueKind::HotCue, 1.0, Some('J'), None), "need a bank"),
+        (cue(CueKind::Memory, 1.0, Some('A'), None), "no bank"),
+        (cue(CueKind::Memory, -1.0, None, None), "non-negative"),
+    ] {
+        let err = stage(vec![bad]).await.expect_err("invalid cue should fail");
+        assert!(err.message.contains(expected), "{}", err.message);
+    }
+
+    let payload = extract_json(
+        &stage(vec![cue(
+            CueKind::Memory,
+            0.5,
+            None,
+            Some("First downbeat"),
+        )])
+        .await
+        .expect("memory cue should stage"),
+    );
+    assert_eq!(payload["changes"][0]["cues"][0]["kind"], "memory");
+    stage(vec![cue(CueKind::HotCue, 30.0, Some('a'), Some("Drop"))])
+        .await
+        .expect("hot cue should stage");
+    let staged = server.state.changes.get("cue-track-1").unwrap();
+    assert_eq!(
+        staged.cues.as_ref().map(Vec::len),
+        Some(2),
+        "cues accumulate"
+    );
+    assert_eq!(staged.cues.unwrap()[1].bank, Some('A'));
+
+    let preview = extract_json(
+        &server
+            .preview_changes(Parameters(PreviewChangesParams { track_ids: None }))
+            .await
+            .expect("preview should succeed"),
+    );
+    let diff = &preview[0]["changes"][0];
+    assert_eq!(diff["field"], "cues");
+    assert_eq!(diff["old_value"], "hot A 1.500s \"Intro\"");
+    assert_eq!(
+        diff["new_value"],
+        "memory 0.500s \"First downbeat\", hot A 30.000s \"Drop\""
+    );
+
+    server
+        .undo_changes(Parameters(UndoRedoParams { steps: Some(1) }))
+        .await
+        .expect("undo should succeed");
+    let staged = server.state.changes.get("cue-track-1").unwrap();
+    assert_eq!(staged.cues.as_ref().map(Vec::len), Some(1));
+    server
+        .redo_changes(Parameters(UndoRedoParams { steps: Some(1) }))
+        .await
+        .expect("redo should succeed");
+
+    let output_dir = tempfile::tempdir().expect("temp output dir should create");
+    let output_path = output_dir.path().join("cues.xml");
+    server
         .write_xml(Parameters(WriteXmlParams {
-            output_path: None,
-            playlists: Some(vec![WriteXmlPlaylistInput {
-                name: "Bad Set".to_string(),
-                track_ids: vec!["does-not-exist".to_string()],
-            }]),
+            output_path: Some(output_path.to_string_lossy().to_string()),
+            playlists: None,
+            rekordbox_playlist_ids: None,
+            beat_grids: None,
+            phrase_cues: None,
         }))
         .await
-        .expect_err("missing playlist track IDs should fail");
-
-    let msg = format!("{err:?}");
-    assert!(msg.contains("Track IDs not found in database"));
-    assert!(msg.contains("does-not-exist"));
+        .expect("write_xml should export staged cues");
+    let xml = std::fs::read_to_string(&output_path).expect("XML output should be readable");
+    let memory = xml
+        .find("<POSITION_MARK Name=\"First downbeat\" Type=\"0\" Start=\"0.500\" Num=\"-1\"/>")
+        .expect("staged memory cue should be exported");
+    let hot = xml
+        .find("<POSITION_MARK Name=\"Drop\" Type=\"0\" Start=\"30.000\" Num=\"0\"/>")
+        .expect("staged hot cue should be exported");
+    assert!(memory < hot, "cues are written in position order");
+    assert!(
+        !xml.contains("Name=\"Intro\""),
+        "the staged hot cue replaces the library cue in bank A"
+    );
 }
 
 #[tokio::test]
-async fn write_xml_deduplicates_playlist_and_staged_tracks() {
-    let db_conn = create_single_track_test_db("staged-track-1", "/tmp/staged-track-1.flac");
+async fn write_xml_beat_grids_export_tempo_for_disagreeing_tracks() {
+    let db_conn = create_single_track_test_db("grid-track-1", "/tmp/grid-track-1.flac");
     insert_test_track(
         &db_conn,
-        "playlist-track-2",
-        "Playlist Only",
+        "grid-track-2",
+        "Agrees",
         "g1",
-        "/tmp/playlist-track-2.flac",
+        "/tmp/grid-track-2.flac",
+    );
+    insert_test_track(
+        &db_conn,
+        "grid-track-3",
+        "Unanalyzed",
+        "g1",
+        "/tmp/grid-track-3.flac",
     );
-
     let store_dir = tempfile::tempdir().expect("temp store dir should create");
     let store_path = store_dir.path().join("internal.sqlite3");
     let store_conn = store::open(
@@ -1187,204 +1443,378 @@ async fn write_xml_deduplicates_playlist_and_staged_tracks() {
             .expect("temp store path should be UTF-8"),
     )
     .expect("temp internal store should open");
+    // Rekordbox has 128.00 and 127.00 BPM for tracks 1 and 2.
+    for (path, bpm) in [
+        ("/tmp/grid-track-1.flac", 126.0),
+        ("/tmp/grid-track-2.flac", 127.2),
+    ] {
+        let features = serde_json::json!({
+            "bpm": bpm, "bpm_confidence": 0.9, "key": "Am", "key_camelot": "8A",
+            "key_confidence": 0.8, "key_clarity": 0.7, "grid_stability": 0.9,
+            "duration_seconds": 240.0, "processing_time_ms": 1.0,
+            "analyzer_version": "1.0.0", "flags": [], "warnings": [],
+            "first_beat_sec": 0.214, "first_beat_in_bar": 2,
+        });
+        store::set_audio_analysis(
+            &store_conn,
+            path,
+            "stratum-dsp",
+            1,
+            1,
+            "1.0.0",
+            &features.to_string(),
+        )
+        .expect("analysis cache should write");
+    }
     let server =
         create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
 
-    server
-        .update_tracks(Parameters(UpdateTracksParams {
-            changes: vec![TrackChangeInput {
-                track_id: "staged-track-1".to_string(),
-                genre: None,
-                comments: Some("staged only comment".to_string()),
-                rating: Some(5),
-                color: None,
-            }],
-        }))
-        .await
-        .expect("staging update should succeed");
-
     let output_dir = tempfile::tempdir().expect("temp output dir should create");
-    let output_path = output_dir.path().join("mixed-export.xml");
-    let output_path_str = output_path.to_string_lossy().to_string();
-
+    let output_path = output_dir.path().join("grid-export.xml");
     let result = server
         .write_xml(Parameters(WriteXmlParams {
-            output_path: Some(output_path_str.clone()),
+            output_path: Some(output_path.to_string_lossy().to_string()),
             playlists: Some(vec![WriteXmlPlaylistInput {
-                name: "Mixed Export".to_string(),
-                track_ids: vec!["playlist-track-2".to_string(), "staged-track-1".to_string()],
+                name: "Grids".to_string(),
+                track_ids: vec![
+                    "grid-track-1".to_string(),
+                    "grid-track-2".to_string(),
+                    "grid-track-3".to_string(),
+                ],
+                folder: None,
             }]),
+            rekordbox_playlist_ids: None,
+            beat_grids: Some(true),
+            phrase_cues: None,
         }))
         .await
-        .expect("write_xml should succeed for mixed staged + playlist exports");
+        .expect("write_xml with beat grids should succeed");
 
     let payload = extract_json(&result);
-    assert_eq!(payload["track_count"], 2);
-    assert_eq!(payload["changes_applied"], 1);
-    assert_eq!(payload["playlist_count"], 1);
     assert_eq!(
-        payload["path"].as_str().expect("path should be present"),
-        output_path_str
+        payload["beat_grids"]["written"],
+        serde_json::json!(["grid-track-1"])
+    );
+    assert_eq!(
+        payload["beat_grids"]["skipped"],
+        serde_json::json!([{ "track_id": "grid-track-3", "reason": "not_analyzed" }])
     );
 
     let xml = std::fs::read_to_string(&output_path).expect("XML output should be readable");
-    assert!(xml.contains("<COLLECTION Entries=\"2\">"));
-    assert_eq!(xml.matches("<TRACK TrackID=\"").count(), 2);
-    assert_eq!(xml.matches("Name=\"Señorita\"").count(), 1);
-    assert_eq!(xml.matches("Name=\"Playlist Only\"").count(), 1);
+    assert_eq!(xml.matches("<TEMPO ").count(), 1);
+    assert!(xml.contains("<TEMPO Inizio=\"0.214\" Bpm=\"126.00\" Metro=\"4/4\" Battito=\"2\"/>"));
+}
 
-    let staged_line = xml
-        .lines()
-        .find(|line| line.contains("Name=\"Señorita\""))
-        .expect("staged track line should exist");
-    assert!(
-        staged_line.contains("Comments=\"staged only comment\""),
-        "staged comment should be applied to staged track"
-    );
-    assert!(
-        staged_line.contains("Rating=\"255\""),
-        "5-star staged rating should be encoded as 255"
+#[tokio::test]
+async fn write_xml_beat_grids_compare_against_stored_grid_and_staged_bpm() {
+    let db_conn = create_single_track_test_db("anlz-agrees", "/tmp/anlz-agrees.flac");
+    insert_test_track(
+        &db_conn,
+        "anlz-octave",
+        "Octave",
+        "g1",
+        "/tmp/anlz-octave.flac",
     );
-
-    let playlist_only_line = xml
-        .lines()
-        .find(|line| line.contains("Name=\"Playlist Only\""))
-        .expect("playlist-only track line should exist");
-    assert!(
-        playlist_only_line.contains("Comments=\"cache coverage test\""),
-        "playlist-only track should keep DB comments when no staged changes exist"
+    insert_test_track(
+        &db_conn,
+        "anlz-staged",
+        "Staged",
+        "g1",
+        "/tmp/anlz-staged.flac",
+    );
+    let rekordbox_dir = tempfile::tempdir().expect("temp Rekordbox dir should create");
+    let anlz_dir = rekordbox_dir.path().join("share/PIONEER/USBANLZ/a1");
+    std::fs::create_dir_all(&anlz_dir).expect("ANLZ dir should create");
+    // Library BPM says 128, but Rekordbox's own grid sits at 126 on the analysed phase.
+    std::fs::write(
+        anlz_dir.join("ANLZ0000.DAT"),
+        crate::anlz::encode_beat_grid(&[(2, 126.0, 214), (3, 126.0, 690)]),
+    )
+    .expect("ANLZ file should write");
+    db_conn
+        .execute_batch(
+            "ALTER TABLE djmdContent ADD COLUMN AnalysisDataPath VARCHAR(255);
+             UPDATE djmdContent SET AnalysisDataPath = '/PIONEER/USBANLZ/a1/ANLZ0000.DAT'
+              WHERE ID = 'anlz-agrees';
+             UPDATE djmdContent SET BPM = 12800 WHERE ID = 'anlz-octave';",
+        )
+        .expect("analysis paths should set");
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_conn = store::open(
+        store_dir
+            .path()
+            .join("internal.sqlite3")
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+    for (path, bpm) in [
+        ("/tmp/anlz-agrees.flac", 126.0),
+        ("/tmp/anlz-octave.flac", 64.0),
+        ("/tmp/anlz-staged.flac", 124.0),
+    ] {
+        let features = serde_json::json!({
+            "bpm": bpm, "bpm_confidence": 0.9, "key": "Am", "key_camelot": "8A",
+            "key_confidence": 0.8, "key_clarity": 0.7, "grid_stability": 0.9,
+            "duration_seconds": 240.0, "processing_time_ms": 1.0,
+            "analyzer_version": "1.0.0", "flags": [], "warnings": [],
+            "first_beat_sec": 0.214, "first_beat_in_bar": 2,
+        });
+        store::set_audio_analysis(
+            &store_conn,
+            path,
+            "stratum-dsp",
+            1,
+            1,
+            "1.0.0",
+            &features.to_string(),
+        )
+        .expect("analysis cache should write");
+    }
+    let server = create_server_with_paths(
+        db_conn,
+        store_conn,
+        default_http_client_for_tests(),
+        Some(
+            rekordbox_dir
+                .path()
+                .join("master.db")
+                .to_string_lossy()
+                .to_string(),
+        ),
+        None,
     );
-    assert!(
-        playlist_only_line.contains("Rating=\"102\""),
-        "playlist-only track should keep DB-derived rating when not staged"
+    // Library BPM is 127; the staged correction to 124 matches the analysis.
+    server.state.changes.stage(
+        "update_tracks",
+        vec![crate::types::TrackChange {
+            track_id: "anlz-staged".to_string(),
+            bpm: Some(124.0),
+            ..Default::default()
+        }],
     );
 
-    let playlist_start = xml
-        .find("<NODE Type=\"1\" Name=\"Mixed Export\" Entries=\"2\" KeyType=\"0\">")
-        .expect("playlist node should exist");
-    let playlist_end = playlist_start
-        + xml[playlist_start..]
-            .find("</NODE>")
-            .expect("playlist node should close");
-    let playlist_block = &xml[playlist_start..playlist_end];
-    let key2 = playlist_block
-        .find("<TRACK Key=\"2\"/>")
-        .expect("playlist should reference playlist-only track");
-    let key1 = playlist_block
-        .find("<TRACK Key=\"1\"/>")
-        .expect("playlist should reference staged track");
-    assert!(
-        key2 < key1,
-        "playlist key order should follow input track_ids order"
+    let output_dir = tempfile::tempdir().expect("temp output dir should create");
+    let output_path = output_dir.path().join("anlz-export.xml");
+    let result = server
+        .write_xml(Parameters(WriteXmlParams {
+            output_path: Some(output_path.to_string_lossy().to_string()),
+            playlists: Some(vec![WriteXmlPlaylistInput {
+                name: "Grids".to_string(),
+                track_ids: vec![
+                    "anlz-agrees".to_string(),
+                    "anlz-octave".to_string(),
+                    "anlz-staged".to_string(),
+                ],
+                folder: None,
+            }]),
+            rekordbox_playlist_ids: None,
+            beat_grids: Some(true),
+            phrase_cues: None,
+        }))
+        .await
+        .expect("write_xml with beat grids should succeed");
+
+    let payload = extract_json(&result);
+    assert_eq!(
+        payload["beat_grids"]["written"],
+        serde_json::json!(["anlz-staged"]),
+        "the stored grid agrees, so only the staged BPM needs a new grid"
+    );
+    assert_eq!(
+        payload["beat_grids"]["conflicts"],
+        serde_json::json!([{
+            "track_id": "anlz-octave",
+            "reason": "half_double_time",
+            "rekordbox_bpm": 128.0,
+            "analysed_bpm": 64.0,
+        }])
     );
+    let xml = std::fs::read_to_string(&output_path).expect("XML output should be readable");
+    assert_eq!(xml.matches("<TEMPO ").count(), 1);
+    assert!(xml.contains("<TEMPO Inizio=\"0.214\" Bpm=\"124.00\" Metro=\"4/4\" Battito=\"2\"/>"));
 }
 
 #[tokio::test]
-async fn update_tracks_includes_provenance() {
-    let server = ReklawdboxServer::new(None);
-    let known_genre = genre::GENRES
-        .first()
-        .copied()
-        .unwrap_or("House")
-        .to_string();
-
+async fn write_xml_phrase_cues_add_memory_cues_from_structure() {
+    let db_conn = create_single_track_test_db("phrase-track-1", "/tmp/phrase-track-1.flac");
+    insert_test_track(
+        &db_conn,
+        "phrase-track-2",
+        "Unanalyzed",
+        "g1",
+        "/tmp/phrase-track-2.flac",
+    );
+    // An existing cue on the first downbeat stands in for the Intro suggestion.
+    db_conn
+        .execute(
+            "INSERT INTO djmdCue (ID, ContentID, InMsec, Kind, Comment)
+             VALUES ('cue1', 'phrase-track-1', 250, 0, 'Start')",
+            [],
+        )
+        .expect("test cue should insert");
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(store_path.to_str().unwrap()).expect("store should open");
+    seed_structure(&store_conn, "/tmp/phrase-track-1.flac", 16, 8);
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+
+    let output_dir = tempfile::tempdir().expect("temp output dir should create");
+    let output_path = output_dir.path().join("phrase-export.xml");
     let result = server
-        .update_tracks(Parameters(UpdateTracksParams {
-            changes: vec![TrackChangeInput {
-                track_id: "test-track-1".to_string(),
-                genre: Some(known_genre),
-                comments: Some("staged by test".to_string()),
-                rating: Some(4),
-                color: None,
-            }],
+        .write_xml(Parameters(WriteXmlParams {
+            output_path: Some(output_path.to_string_lossy().to_string()),
+            playlists: Some(vec![WriteXmlPlaylistInput {
+                name: "Phrases".to_string(),
+                track_ids: vec!["phrase-track-1".to_string(), "phrase-track-2".to_string()],
+                folder: None,
+            }]),
+            rekordbox_playlist_ids: None,
+            beat_grids: None,
+            phrase_cues: Some(true),
         }))
         .await
-        .expect("update_tracks should succeed");
+        .expect("write_xml with phrase cues should succeed");
 
     let payload = extract_json(&result);
     assert_eq!(
-        payload
-            .get("staged")
-            .and_then(serde_json::Value::as_u64)
-            .expect("staged should be present"),
-        1
+        payload["phrase_cues"]["written"],
+        serde_json::json!([{ "track_id": "phrase-track-1", "cues_added": 2 }])
     );
     assert_eq!(
-        payload
-            .get("total_pending")
-            .and_then(serde_json::Value::as_u64)
-            .expect("total_pending should be present"),
-        1
+        payload["phrase_cues"]["skipped"],
+        serde_json::json!([{ "track_id": "phrase-track-2", "reason": "not_analyzed" }])
     );
-    assert_has_provenance(&payload);
+
+    let xml = std::fs::read_to_string(&output_path).expect("XML output should be readable");
+    assert!(!xml.contains("Intro (16 bars)"));
+    assert!(xml.contains(
+        "<POSITION_MARK Name=\"Groove (72 bars)\" Type=\"0\" Start=\"30.250\" Num=\"-1\"/>"
+    ));
+    assert!(xml.contains("Name=\"Outro (8 bars)\""));
 }
 
 #[tokio::test]
-async fn update_tracks_via_router_includes_provenance() {
-    let result = call_tool_via_router(
-        "update_tracks",
-        serde_json::json!({
-            "changes": [{
-                "track_id": "router-test-track-1",
-                "genre": "NotInTaxonomy"
-            }]
-        })
-        .as_object()
-        .cloned(),
+async fn write_xml_exports_rekordbox_playlist_tree_and_folder_paths() {
+    let db_conn = create_single_track_test_db("tree-track-1", "/tmp/tree-track-1.flac");
+    insert_test_track(
+        &db_conn,
+        "tree-track-2",
+        "Second",
+        "g1",
+        "/tmp/tree-track-2.flac",
+    );
+    db_conn
+        .execute_batch(
+            "CREATE TABLE djmdPlaylist (
+                ID VARCHAR(255) PRIMARY KEY,
+                Seq INTEGER,
+                Name VARCHAR(255),
+                Attribute INTEGER DEFAULT 0,
+                ParentID VARCHAR(255) DEFAULT '',
+                SmartList TEXT,
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+            CREATE TABLE djmdSongPlaylist (
+                ID VARCHAR(255) PRIMARY KEY,
+                PlaylistID VARCHAR(255),
+                ContentID VARCHAR(255),
+                TrackNo INTEGER
+            );
+            INSERT INTO djmdPlaylist (ID, Seq, Name, Attribute, ParentID, SmartList) VALUES
+                ('f-sets', 1, 'Sets', 1, 'root', NULL),
+                ('f-2026', 1, '2026', 1, 'f-sets', NULL),
+                ('p-club', 1, 'Club X', 0, 'f-2026', NULL),
+                ('p-smart', 2, 'Seconds', 4, 'f-sets',
+                    '<NODE Id=\"1\" LogicalOperator=\"1\"><CONDITION PropertyName=\"name\" Operator=\"8\" ValueUnit=\"\" ValueLeft=\"second\" ValueRight=\"\"/></NODE>'),
+                ('p-broken', 3, 'Grouped', 4, 'f-sets',
+                    '<NODE Id=\"2\" LogicalOperator=\"1\"><CONDITION PropertyName=\"grouping\" Operator=\"1\" ValueUnit=\"\" ValueLeft=\"x\" ValueRight=\"\"/></NODE>'),
+                ('p-other', 2, 'Elsewhere', 0, 'root', NULL);
+            INSERT INTO djmdSongPlaylist (ID, PlaylistID, ContentID, TrackNo) VALUES
+                ('sp1', 'p-club', 'tree-track-2', 1),
+                ('sp2', 'p-club', 'tree-track-1', 2),
+                ('sp3', 'p-other', 'tree-track-1', 1);",
+        )
+        .expect("playlist tables should be created for test");
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
     )
-    .await;
+    .expect("temp internal store should open");
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+
+    let output_dir = tempfile::tempdir().expect("temp output dir should create");
+    let output_path = output_dir.path().join("tree-export.xml");
+    let result = server
+        .write_xml(Parameters(WriteXmlParams {
+            output_path: Some(output_path.to_string_lossy().to_string()),
+            playlists: Some(vec![WriteXmlPlaylistInput {
+                name: "Warmup".to_string(),
+                track_ids: vec!["tree-track-1".to_string()],
+                folder: Some(" Sets / 2026 ".to_string()),
+            }]),
+            rekordbox_playlist_ids: Some(vec!["f-sets".to_string()]),
+            beat_grids: None,
+            phrase_cues: None,
+        }))
+        .await
+        .expect("write_xml should export the playlist tree");
 
     let payload = extract_json(&result);
+    assert_eq!(payload["track_count"], 2);
+    assert_eq!(payload["playlist_count"], 3);
     assert_eq!(
-        payload
-            .get("staged")
-            .and_then(serde_json::Value::as_u64)
-            .expect("staged should be present"),
-        1
+        payload["skipped_smart_playlists"][0]["playlist_id"],
+        "p-broken"
     );
-    let warnings = payload
-        .get("warnings")
-        .and_then(serde_json::Value::as_array)
-        .expect("warnings should be present for non-taxonomy genre");
     assert!(
-        !warnings.is_empty(),
-        "warnings should include at least one non-taxonomy genre warning"
+        payload["skipped_smart_playlists"][0]["reason"]
+            .as_str()
+            .is_some_and(|reason| reason.contains("'grouping'"))
     );
-    assert_has_provenance(&payload);
-}
-
-#[tokio::test]
-async fn get_genre_taxonomy_via_router_includes_provenance() {
-    let result = call_tool_via_router("get_genre_taxonomy", None).await;
-    let payload = extract_json(&result);
 
-    let genres = payload
-        .get("genres")
-        .and_then(serde_json::Value::as_array)
-        .expect("genres should be present");
+    let xml = std::fs::read_to_string(&output_path).expect("XML output should be readable");
+    assert!(xml.contains("<NODE Type=\"0\" Name=\"ROOT\" Count=\"1\">"));
+    assert!(xml.contains("<NODE Type=\"0\" Name=\"Sets\" Count=\"2\">"));
+    assert!(xml.contains("<NODE Type=\"1\" Name=\"Seconds\" Entries=\"1\" KeyType=\"0\">"));
+    assert!(xml.contains("<NODE Type=\"0\" Name=\"2026\" Count=\"2\">"));
+    let club = xml
+        .find("<NODE Type=\"1\" Name=\"Club X\" Entries=\"2\" KeyType=\"0\">")
+        .expect("DB playlist should be exported inside its folders");
+    let club_block = &xml[club..club + xml[club..].find("</NODE>").unwrap()];
+    assert!(club_block.find("Key=\"2\"").unwrap() < club_block.find("Key=\"1\"").unwrap());
     assert!(
-        !genres.is_empty(),
-        "genres should include configured taxonomy entries"
+        !xml.contains("Elsewhere"),
+        "playlists outside the subtree are not exported"
     );
-    assert_has_provenance(&payload);
-}
 
-#[test]
-fn enrich_tracks_invalid_provider_rejected_by_serde() {
-    let json = serde_json::json!({
-        "providers": ["spotify"],
-    });
-    let result = serde_json::from_value::<EnrichTracksParams>(json);
-    assert!(
-        result.is_err(),
-        "serde should reject unknown provider variant"
-    );
+    let err = server
+        .write_xml(Parameters(WriteXmlParams {
+            output_path: Some(output_path.to_string_lossy().to_string()),
+            playlists: None,
+            rekordbox_playlist_ids: Some(vec!["missing".to_string()]),
+            beat_grids: None,
+            phrase_cues: None,
+        }))
+        .await
+        .expect_err("unknown playlist IDs should be rejected");
+    assert!(err.message.contains("'missing' not found"));
 }
 
 #[tokio::test]
-async fn lookup_discogs_no_match_payload_is_consistent_across_live_and_cache_paths() {
-    let db_conn =
-        create_single_track_test_db("discogs-no-match-track", "/tmp/discogs-no-match.flac");
+async fn update_tracks_my_tags_export_through_comments_block() {
+    let db_conn = create_single_track_test_db("tag-track-1", "/tmp/tag-track-1.flac");
+    db_conn
+        .execute_batch(
+            "INSERT INTO djmdMyTag (ID, Seq, Name) VALUES ('mt1', 1, 'Warmup');
+             INSERT INTO djmdSongMyTag (ID, MyTagID, ContentID) VALUES ('smt1', 'mt1', 'tag-track-1');",
+        )
+        .expect("test tags should insert");
     let store_dir = tempfile::tempdir().expect("temp store dir should create");
     let store_path = store_dir.path().join("internal.sqlite3");
     let store_conn = store::open(
@@ -1396,71 +1826,73 @@ async fn lookup_discogs_no_match_payload_is_consistent_across_live_and_cache_pat
     let server =
         create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
 
-    let artist = "Discogs NoMatch Artist";
-    let title = "Discogs NoMatch Title";
-    set_test_discogs_lookup_override(artist, title, None, Ok(None));
-
-    let live_result = server
-        .lookup_discogs(Parameters(LookupDiscogsParams {
-            track_id: None,
-            artist: Some(artist.to_string()),
-            title: Some(title.to_string()),
-            album: None,
-            force_refresh: Some(true),
+    let err = server
+        .update_tracks(Parameters(UpdateTracksParams {
+            changes: vec![TrackChangeInput {
+                track_id: "tag-track-1".to_string(),
+                my_tags: Some(vec!["A/B".to_string()]),
+                ..Default::default()
+            }],
         }))
         .await
-        .expect("live discogs no-match should succeed");
-    let live_payload = extract_json(&live_result);
-    assert_eq!(live_payload["result"], serde_json::Value::Null);
-    assert_eq!(live_payload["cache_hit"], false);
-    assert!(
-        live_payload.get("cached_at").is_none(),
-        "live payload should omit cached_at"
-    );
+        .expect_err("tags containing '/' should be rejected");
+    assert!(err.message.contains("must not contain"));
 
-    let cache_result = server
-        .lookup_discogs(Parameters(LookupDiscogsParams {
-            track_id: None,
-            artist: Some(artist.to_string()),
-            title: Some(title.to_string()),
-            album: None,
-            force_refresh: Some(false),
+    server
+        .update_tracks(Parameters(UpdateTracksParams {
+            changes: vec![TrackChangeInput {
+                track_id: "tag-track-1".to_string(),
+                my_tags: Some(vec![
+                    " Peak ".to_string(),
+                    "Dark".to_string(),
+                    "peak".to_string(),
+                ]),
+                ..Default::default()
+            }],
         }))
         .await
-        .expect("cached discogs no-match should succeed");
-    let cache_payload = extract_json(&cache_result);
-    assert_eq!(cache_payload["result"], serde_json::Value::Null);
-    assert_eq!(cache_payload["cache_hit"], true);
+        .expect("staging tags should succeed");
 
-    let cache_hit_timestamp = cache_payload
-        .get("cached_at")
-        .and_then(serde_json::Value::as_str)
-        .expect("cached no-match payload should include cached_at");
-    let norm_artist = crate::normalize::normalize_for_matching(artist);
-    let norm_title = crate::normalize::normalize_for_matching(title);
-    let cache_entry = {
-        let store = server
-            .cache_store_conn()
-            .expect("internal store should be available");
-        store::get_enrichment(&store, "discogs", &norm_artist, &norm_title)
-            .expect("cache read should succeed")
-            .expect("discogs no-match lookup should create cache entry")
-    };
-    assert!(
-        cache_entry.response_json.is_none(),
-        "discogs no-match cache entry should store null response as no payload"
-    );
-    assert_eq!(
-        cache_hit_timestamp,
-        cache_entry.created_at.as_str(),
-        "cached_at should match persisted cache timestamp"
-    );
+    let preview = server
+        .preview_changes(Parameters(PreviewChangesParams { track_ids: None }))
+        .await
+        .expect("preview should succeed");
+    let preview = extract_json(&preview);
+    let comments = &preview[0]["changes"][0];
+    assert_eq!(comments["field"], "comments");
+    assert_eq!(comments["old_value"], "percent path test");
+    assert_eq!(comments["new_value"], "percent path test /* Peak / Dark */");
+    let change = &preview[0]["changes"][1];
+    assert_eq!(change["field"], "my_tags");
+    assert_eq!(change["old_value"], "Warmup");
+    assert_eq!(change["new_value"], "Peak / Dark");
+
+    let output_dir = tempfile::tempdir().expect("temp output dir should create");
+    let output_path = output_dir.path().join("tags.xml");
+    server
+        .write_xml(Parameters(WriteXmlParams {
+            output_path: Some(output_path.to_string_lossy().to_string()),
+            playlists: None,
+            rekordbox_playlist_ids: None,
+            beat_grids: None,
+            phrase_cues: None,
+        }))
+        .await
+        .expect("write_xml should export staged tags");
+    let xml = std::fs::read_to_string(&output_path).expect("XML output should be readable");
+    assert!(xml.contains("Comments=\"percent path test /* Peak / Dark */\""));
 }
 
 #[tokio::test]
-async fn lookup_beatport_no_match_payload_is_consistent_across_live_and_cache_paths() {
-    let db_conn =
-        create_single_track_test_db("beatport-no-match-track", "/tmp/beatport-no-match.flac");
+async fn diff_xml_reports_staged_edits_and_tracks_missing_from_export() {
+    let db_conn = create_single_track_test_db("diff-track-1", "/tmp/diff-track-1.flac");
+    insert_test_track(
+        &db_conn,
+        "diff-track-2",
+        "Not Exported",
+        "g1",
+        "/tmp/diff-track-2.flac",
+    );
     let store_dir = tempfile::tempdir().expect("temp store dir should create");
     let store_path = store_dir.path().join("internal.sqlite3");
     let store_conn = store::open(
@@ -1472,522 +1904,645 @@ async fn lookup_beatport_no_match_payload_is_consistent_across_live_and_cache_pa
     let server =
         create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
 
-    let artist = "Beatport NoMatch Artist";
-    let title = "Beatport NoMatch Title";
-    set_test_beatport_lookup_override(artist, title, Ok(None));
+    server
+        .update_tracks(Parameters(UpdateTracksParams {
+            changes: vec![TrackChangeInput {
+                track_id: "diff-track-1".to_string(),
+                comments: Some("pending import".to_string()),
+                ..Default::default()
+            }],
+        }))
+        .await
+        .expect("staging update should succeed");
 
-    let live_result = server
-        .lookup_beatport(Parameters(LookupBeatportParams {
-            track_id: None,
-            artist: Some(artist.to_string()),
-            title: Some(title.to_string()),
-            force_refresh: Some(true),
+    let output_dir = tempfile::tempdir().expect("temp output dir should create");
+    let output_path = output_dir.path().join("diff-export.xml");
+    let output_path_str = output_path.to_string_lossy().to_string();
+    server
+        .write_xml(Parameters(WriteXmlParams {
+            output_path: Some(output_path_str.clone()),
+            playlists: None,
+            rekordbox_playlist_ids: None,
+            beat_grids: None,
+            phrase_cues: None,
         }))
         .await
-        .expect("live beatport no-match should succeed");
-    let live_payload = extract_json(&live_result);
-    assert_eq!(live_payload["result"], serde_json::Value::Null);
-    assert_eq!(live_payload["cache_hit"], false);
-    assert!(
-        live_payload.get("cached_at").is_none(),
-        "live payload should omit cached_at"
-    );
+        .expect("write_xml should export staged changes");
 
-    let cache_result = server
-        .lookup_beatport(Parameters(LookupBeatportParams {
-            track_id: None,
-            artist: Some(artist.to_string()),
-            title: Some(title.to_string()),
-            force_refresh: Some(false),
+    let result = server
+        .diff_xml(Parameters(DiffXmlParams {
+            path: output_path_str.clone(),
+            against_path: None,
+            limit: None,
         }))
         .await
-        .expect("cached beatport no-match should succeed");
-    let cache_payload = extract_json(&cache_result);
-    assert_eq!(cache_payload["result"], serde_json::Value::Null);
-    assert_eq!(cache_payload["cache_hit"], true);
+        .expect("diff_xml against master.db should succeed");
+    let payload = extract_json(&result);
+    assert_eq!(payload["baseline"], "master.db");
+    assert_eq!(payload["summary"]["added"], 0);
+    assert_eq!(payload["summary"]["removed"], 1);
+    assert_eq!(payload["summary"]["changed"], 1);
+    assert_eq!(payload["removed"][0]["track_id"], "diff-track-2");
+    let changed = &payload["changed"][0];
+    assert_eq!(changed["track_id"], "diff-track-1");
+    assert_eq!(changed["changes"].as_array().map(Vec::len), Some(1));
+    assert_eq!(changed["changes"][0]["field"], "comments");
+    assert_eq!(changed["changes"][0]["new_value"], "pending import");
 
-    let cache_hit_timestamp = cache_payload
-        .get("cached_at")
-        .and_then(serde_json::Value::as_str)
-        .expect("cached no-match payload should include cached_at");
-    let norm_artist = crate::normalize::normalize_for_matching(artist);
-    let norm_title = crate::normalize::normalize_for_matching(title);
-    let cache_entry = {
-        let store = server
-            .cache_store_conn()
-            .expect("internal store should be available");
-        store::get_enrichment(&store, "beatport", &norm_artist, &norm_title)
-            .expect("cache read should succeed")
-            .expect("beatport no-match lookup should create cache entry")
-    };
-    assert!(
-        cache_entry.response_json.is_none(),
-        "beatport no-match cache entry should store null response as no payload"
-    );
-    assert_eq!(
-        cache_hit_timestamp,
-        cache_entry.created_at.as_str(),
-        "cached_at should match persisted cache timestamp"
-    );
+    let result = server
+        .diff_xml(Parameters(DiffXmlParams {
+            path: output_path_str.clone(),
+            against_path: Some(output_path_str),
+            limit: Some(0),
+        }))
+        .await
+        .expect("diff_xml against the same XML should succeed");
+    let payload = extract_json(&result);
+    assert_eq!(payload["summary"]["unchanged"], 1);
+    assert_eq!(payload["summary"]["changed"], 0);
+
+    let err = server
+        .diff_xml(Parameters(DiffXmlParams {
+            path: output_dir
+                .path()
+                .join("missing.xml")
+                .to_string_lossy()
+                .to_string(),
+            against_path: None,
+            limit: None,
+        }))
+        .await
+        .expect_err("missing XML files should be rejected");
+    assert!(err.message.contains("Failed to read"));
 }
 
 #[tokio::test]
-async fn enrich_tracks_discogs_skip_cached_reports_cached_counts() {
-    let db_conn = create_single_track_test_db("cached-track-1", "/tmp/cached-track-1.flac");
-    db_conn
-        .execute(
-            "INSERT INTO djmdContent (
-                    ID, Title, ArtistID, AlbumID, GenreID, KeyID, ColorID, LabelID, RemixerID,
-                    BPM, Rating, Commnt, ReleaseYear, Length, FolderPath, DJPlayCount, BitRate,
-                    SampleRate, FileType, created_at, rb_local_deleted
-                ) VALUES (
-                    ?1, ?2, 'a1', 'al1', 'g1', 'k1', 'c1', 'l1', '',
-                    12700, 153, 'cached batch test', 2025, 230, ?3, '0', 1411,
-                    44100, 5, '2025-01-01', 0
-                )",
-            params![
-                "cached-track-2",
-                "Corazón Cached",
-                "/tmp/cached-track-2.flac"
-            ],
-        )
-        .expect("second test track should insert");
-
+async fn write_xml_with_playlists_reports_missing_track_ids() {
+    let db_conn = create_single_track_test_db("playlist-track-1", "/tmp/playlist-track-1.flac");
     let store_dir = tempfile::tempdir().expect("temp store dir should create");
     let store_path = store_dir.path().join("internal.sqlite3");
-    let store_path_str = store_path
-        .to_str()
-        .expect("temp store path should be UTF-8")
-        .to_string();
-    let store_conn = store::open(&store_path_str).expect("temp internal store should open");
-
-    let artist = "Aníbal";
-    let title_one = "Señorita";
-    let title_two = "Corazón Cached";
-    let norm_artist = crate::normalize::normalize_for_matching(artist);
-    let norm_title_one = crate::normalize::normalize_for_matching(title_one);
-    let norm_title_two = crate::normalize::normalize_for_matching(title_two);
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
 
-    let cached_one = serde_json::json!({
-        "title": "Anibal - Senorita",
-        "genres": ["Electronic"],
-        "styles": ["Deep House"],
-        "fuzzy_match": false
-    })
-    .to_string();
-    let cached_two = serde_json::json!({
-        "title": "Anibal - Corazon Cached",
-        "genres": ["Electronic"],
-        "styles": ["House"],
-        "fuzzy_match": false
-    })
-    .to_string();
+    let err = server
+        .write_xml(Parameters(WriteXmlParams {
+            output_path: None,
+            playlists: Some(vec![WriteXmlPlaylistInput {
+                name: "Bad Set".to_string(),
+                track_ids: vec!["does-not-exist".to_string()],
+                folder: None,
+            }]),
+            rekordbox_playlist_ids: None,
+            beat_grids: None,
+            phrase_cues: None,
+        }))
+        .await
+        .expect_err("missing playlist track IDs should fail");
 
-    store::set_enrichment(
-        &store_conn,
-        "discogs",
-        &norm_artist,
-        &norm_title_one,
-        Some("exact"),
-        Some(&cached_one),
-    )
-    .expect("first sentinel discogs cache entry should write");
-    store::set_enrichment(
-        &store_conn,
-        "discogs",
-        &norm_artist,
-        &norm_title_two,
-        Some("exact"),
-        Some(&cached_two),
-    )
-    .expect("second sentinel discogs cache entry should write");
+    let msg = format!("{err:?}");
+    assert!(msg.contains("Track IDs not found in database"));
+    assert!(msg.contains("does-not-exist"));
+}
 
-    let server = create_server_with_store_path(
-        db_conn,
-        store_conn,
-        default_http_client_for_tests(),
-        Some(store_path_str),
+#[tokio::test]
+async fn write_xml_deduplicates_playlist_and_staged_tracks() {
+    let db_conn = create_single_track_test_db("staged-track-1", "/tmp/staged-track-1.flac");
+    insert_test_track(
+        &db_conn,
+        "playlist-track-2",
+        "Playlist Only",
+        "g1",
+        "/tmp/playlist-track-2.flac",
     );
 
-    let params = EnrichTracksParams {
-        filters: SearchFilterParams::default(),
-        track_ids: Some(vec![
-            "cached-track-1".to_string(),
-            "cached-track-2".to_string(),
-        ]),
-        playlist_id: None,
-        max_tracks: Some(10),
-        offset: None,
-        providers: Some(vec![crate::types::Provider::Discogs]),
-        skip_cached: Some(true),
-        force_refresh: Some(false),
-        concurrency: None,
-    };
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
 
-    let first_result = server
-        .enrich_tracks(Parameters(params))
+    server
+        .update_tracks(Parameters(UpdateTracksParams {
+            changes: vec![TrackChangeInput {
+                track_id: "staged-track-1".to_string(),
+                genre: None,
+                comments: Some("staged only comment".to_string()),
+                rating: Some(5),
+                color: None,
+                ..Default::default()
+            }],
+        }))
         .await
-        .expect("enrich_tracks should succeed when everything is cached");
-    let first_payload = extract_json(&first_result);
-    assert_eq!(first_payload["summary"]["total"], 2);
-    assert_eq!(first_payload["summary"]["enriched"], 0);
-    assert_eq!(first_payload["summary"]["cached"], 2);
-    assert_eq!(first_payload["summary"]["skipped"], 0);
-    assert_eq!(first_payload["summary"]["failed"], 0);
-    assert_eq!(
-        first_payload["failures"]
-            .as_array()
-            .expect("failures should be an array")
-            .len(),
-        0
-    );
+        .expect("staging update should succeed");
 
-    let second_result = server
-        .enrich_tracks(Parameters(EnrichTracksParams {
-            filters: SearchFilterParams::default(),
-            track_ids: Some(vec![
-                "cached-track-1".to_string(),
-                "cached-track-2".to_string(),
-            ]),
-            playlist_id: None,
-            max_tracks: Some(10),
-            offset: None,
-            providers: Some(vec![crate::types::Provider::Discogs]),
-            skip_cached: Some(true),
-            force_refresh: Some(false),
-            concurrency: None,
+    let output_dir = tempfile::tempdir().expect("temp output dir should create");
+    let output_path = output_dir.path().join("mixed-export.xml");
+    let output_path_str = output_path.to_string_lossy().to_string();
+
+    let result = server
+        .write_xml(Parameters(WriteXmlParams {
+            output_path: Some(output_path_str.clone()),
+            playlists: Some(vec![WriteXmlPlaylistInput {
+                name: "Mixed Export".to_string(),
+                track_ids: vec!["playlist-track-2".to_string(), "staged-track-1".to_string()],
+                folder: None,
+            }]),
+            rekordbox_playlist_ids: None,
+            beat_grids: None,
+            phrase_cues: None,
         }))
         .await
-        .expect("second enrich_tracks run should also be fully cached");
-    let second_payload = extract_json(&second_result);
-    assert_eq!(second_payload["summary"]["total"], 2);
-    assert_eq!(second_payload["summary"]["enriched"], 0);
-    assert_eq!(second_payload["summary"]["cached"], 2);
-    assert_eq!(second_payload["summary"]["skipped"], 0);
-    assert_eq!(second_payload["summary"]["failed"], 0);
+        .expect("write_xml should succeed for mixed staged + playlist exports");
 
-    let store = server
-        .cache_store_conn()
-        .expect("internal store should be available");
-    let entry_one = store::get_enrichment(&store, "discogs", &norm_artist, &norm_title_one)
-        .expect("cache read should succeed")
-        .expect("first cache entry should still exist");
-    let entry_two = store::get_enrichment(&store, "discogs", &norm_artist, &norm_title_two)
-        .expect("cache read should succeed")
-        .expect("second cache entry should still exist");
-    assert_eq!(
-        entry_one.response_json.as_deref(),
-        Some(cached_one.as_str())
-    );
+    let payload = extract_json(&result);
+    assert_eq!(payload["track_count"], 2);
+    assert_eq!(payload["changes_applied"], 1);
+    assert_eq!(payload["playlist_count"], 1);
     assert_eq!(
-        entry_two.response_json.as_deref(),
-        Some(cached_two.as_str())
+        payload["path"].as_str().expect("path should be present"),
+        output_path_str
     );
-}
 
-#[tokio::test]
-async fn enrich_tracks_summary_uses_provider_attempt_totals() {
-    let db_conn = create_single_track_test_db("cached-track-1", "/tmp/cached-track-1.flac");
-    let store_dir = tempfile::tempdir().expect("temp store dir should create");
-    let store_path = store_dir.path().join("internal.sqlite3");
-    let store_path_str = store_path
-        .to_str()
-        .expect("temp store path should be UTF-8")
-        .to_string();
-    let store_conn = store::open(&store_path_str).expect("temp internal store should open");
+    let xml = std::fs::read_to_string(&output_path).expect("XML output should be readable");
+    assert!(xml.contains("<COLLECTION Entries=\"2\">"));
+    assert_eq!(xml.matches("<TRACK TrackID=\"").count(), 2);
+    assert_eq!(xml.matches("Name=\"Señorita\"").count(), 1);
+    assert_eq!(xml.matches("Name=\"Playlist Only\"").count(), 1);
 
-    let norm_artist = crate::normalize::normalize_for_matching("Aníbal");
-    let norm_title = crate::normalize::normalize_for_matching("Señorita");
-    store::set_enrichment(
-        &store_conn,
-        "discogs",
-        &norm_artist,
-        &norm_title,
-        Some("exact"),
-        Some(r#"{"styles":["Deep House"]}"#),
-    )
-    .expect("discogs cache should seed");
-    store::set_enrichment(
-        &store_conn,
-        "beatport",
-        &norm_artist,
-        &norm_title,
-        Some("exact"),
-        Some(r#"{"genre":"Deep House"}"#),
-    )
-    .expect("beatport cache should seed");
+    let staged_line = xml
+        .lines()
+        .find(|line| line.contains("Name=\"Señorita\""))
+        .expect("staged track line should exist");
+    assert!(
+        staged_line.contains("Comments=\"staged only comment\""),
+        "staged comment should be applied to staged track"
+    );
+    assert!(
+        staged_line.contains("Rating=\"255\""),
+        "5-star staged rating should be encoded as 255"
+    );
 
-    let server = create_server_with_store_path(
-        db_conn,
-        store_conn,
-        default_http_client_for_tests(),
-        Some(store_path_str),
+    let playlist_only_line = xml
+        .lines()
+        .find(|line| line.contains("Name=\"Playlist Only\""))
+        .expect("playlist-only track line should exist");
+    assert!(
+        playlist_only_line.contains("Comments=\"cache coverage test\""),
+        "playlist-only track should keep DB comments when no staged changes exist"
+    );
+    assert!(
+        playlist_only_line.contains("Rating=\"102\""),
+        "playlist-only track should keep DB-derived rating when not staged"
+    );
+
+    let playlist_start = xml
+        .find("<NODE Type=\"1\" Name=\"Mixed Export\" Entries=\"2\" KeyType=\"0\">")
+        .expect("playlist node should exist");
+    let playlist_end = playlist_start
+        + xml[playlist_start..]
+            .find("</NODE>")
+            .expect("playlist node should close");
+    let playlist_block = &xml[playlist_start..playlist_end];
+    let key2 = playlist_block
+        .find("<TRACK Key=\"2\"/>")
+        .expect("playlist should reference playlist-only track");
+    let key1 = playlist_block
+        .find("<TRACK Key=\"1\"/>")
+        .expect("playlist should reference staged track");
+    assert!(
+        key2 < key1,
+        "playlist key order should follow input track_ids order"
     );
+}
+
+#[tokio::test]
+async fn update_tracks_includes_provenance() {
+    let server = ReklawdboxServer::new(None);
+    let known_genre = genre::GENRES
+        .first()
+        .copied()
+        .unwrap_or("House")
+        .to_string();
+
     let result = server
-        .enrich_tracks(Parameters(EnrichTracksParams {
-            filters: SearchFilterParams::default(),
-            track_ids: Some(vec!["cached-track-1".to_string()]),
-            playlist_id: None,
-            max_tracks: Some(1),
-            offset: None,
-            providers: Some(vec![
-                crate::types::Provider::Discogs,
-                crate::types::Provider::Beatport,
-            ]),
-            skip_cached: Some(true),
-            force_refresh: Some(false),
-            concurrency: None,
+        .update_tracks(Parameters(UpdateTracksParams {
+            changes: vec![TrackChangeInput {
+                track_id: "test-track-1".to_string(),
+                genre: Some(known_genre),
+                comments: Some("staged by test".to_string()),
+                rating: Some(4),
+                color: None,
+                ..Default::default()
+            }],
         }))
         .await
-        .expect("enrich_tracks should resolve from cache for both providers");
+        .expect("update_tracks should succeed");
+
     let payload = extract_json(&result);
+    assert_eq!(
+        payload
+            .get("staged")
+            .and_then(serde_json::Value::as_u64)
+            .expect("staged should be present"),
+        1
+    );
+    assert_eq!(
+        payload
+            .get("total_pending")
+            .and_then(serde_json::Value::as_u64)
+            .expect("total_pending should be present"),
+        1
+    );
+    assert_has_provenance(&payload);
+}
 
-    assert_eq!(payload["summary"]["tracks_total"], 1);
-    assert_eq!(payload["summary"]["total"], 2);
-    assert_eq!(payload["summary"]["cached"], 2);
-    assert_eq!(payload["summary"]["enriched"], 0);
-    assert_eq!(payload["summary"]["skipped"], 0);
-    assert_eq!(payload["summary"]["failed"], 0);
+#[tokio::test]
+async fn update_tracks_validates_bpm_year_and_key() {
+    let server = ReklawdboxServer::new(None);
+    let stage = |change: TrackChangeInput| {
+        server.update_tracks(Parameters(UpdateTracksParams {
+            changes: vec![change],
+        }))
+    };
+
+    for (change, expected) in [
+        (
+            TrackChangeInput {
+                track_id: "t1".to_string(),
+                bpm: Some(512.0),
+                ..Default::default()
+            },
+            "bpm must be",
+        ),
+        (
+            TrackChangeInput {
+                track_id: "t1".to_string(),
+                year: Some(1850),
+                ..Default::default()
+            },
+            "year must be",
+        ),
+        (
+            TrackChangeInput {
+                track_id: "t1".to_string(),
+                key: Some("H minor".to_string()),
+                ..Default::default()
+            },
+            "unrecognized key",
+        ),
+        (
+            TrackChangeInput {
+                track_id: "t1".to_string(),
+                title: Some("   ".to_string()),
+                ..Default::default()
+            },
+            "title must not be empty",
+        ),
+    ] {
+        let err = stage(change)
+            .await
+            .expect_err("invalid change should be rejected");
+        assert!(
+            err.message.contains(expected),
+            "expected '{expected}' in error, got: {}",
+            err.message
+        );
+    }
+
+    let result = stage(TrackChangeInput {
+        track_id: "t1".to_string(),
+        bpm: Some(126.456),
+        year: Some(2019),
+        key: Some("8A".to_string()),
+        artist: Some("Burial".to_string()),
+        ..Default::default()
+    })
+    .await
+    .expect("valid extended change should stage");
+    let payload = extract_json(&result);
+    let echo = &payload["changes"][0];
+    assert_eq!(echo["bpm"], 126.46);
+    assert_eq!(echo["year"], 2019);
+    assert_eq!(echo["key"], "Am", "keys are stored in Rekordbox notation");
+    assert_eq!(echo["artist"], "Burial");
+    assert!(echo.get("genre").is_none(), "unset fields are omitted");
+
+    for (raw, expected) in [
+        ("8a", "Am"),
+        ("A minor", "Am"),
+        ("f#m", "F#m"),
+        ("C#", "Db"),
+    ] {
+        let result = stage(TrackChangeInput {
+            track_id: "t1".to_string(),
+            key: Some(raw.to_string()),
+            ..Default::default()
+        })
+        .await
+        .expect("valid key should stage");
+        assert_eq!(
+            extract_json(&result)["changes"][0]["key"],
+            expected,
+            "{raw}"
+        );
+    }
 }
 
 #[tokio::test]
-async fn resolve_track_data_uses_decoded_path_for_audio_cache_lookup() {
-    let temp_audio_dir = tempfile::tempdir().expect("temp audio dir should create");
-    let decoded_path = temp_audio_dir.path().join("Aníbal Track.flac");
-    std::fs::write(&decoded_path, b"fake-audio-data")
-        .expect("decoded path file should exist for resolve_file_path");
+async fn undo_and_redo_changes_walk_staging_history() {
+    let server = ReklawdboxServer::new(None);
+    for genre in ["Techno", "House"] {
+        server
+            .update_tracks(Parameters(UpdateTracksParams {
+                changes: vec![TrackChangeInput {
+                    track_id: "t1".to_string(),
+                    genre: Some(genre.to_string()),
+                    ..Default::default()
+                }],
+            }))
+            .await
+            .expect("update_tracks should succeed");
+    }
+    server
+        .clear_changes(Parameters(ClearChangesParams {
+            track_ids: None,
+            fields: None,
+        }))
+        .await
+        .expect("clear_changes should succeed");
 
-    let decoded_path_str = decoded_path.to_string_lossy().to_string();
-    let raw_path = decoded_path_str
-        .replace("Aníbal", "An%C3%ADbal")
-        .replace(' ', "%20");
-    assert_ne!(
-        raw_path, decoded_path_str,
-        "raw path must differ from decoded path for this regression test"
+    let history = extract_json(&server.change_history().await.unwrap());
+    let tools: Vec<&str> = history["undo"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["tool"].as_str().unwrap())
+        .collect();
+    assert_eq!(tools, ["clear_changes", "update_tracks", "update_tracks"]);
+
+    let undone = extract_json(
+        &server
+            .undo_changes(Parameters(UndoRedoParams { steps: Some(2) }))
+            .await
+            .unwrap(),
     );
-    assert!(
-        std::fs::metadata(&decoded_path_str).is_ok(),
-        "decoded file path should exist"
+    assert_eq!(undone["undone"].as_array().unwrap().len(), 2);
+    assert_eq!(undone["total_pending"], 1);
+    assert_eq!(
+        server.state.changes.get("t1").unwrap().genre.as_deref(),
+        Some("Techno")
     );
-    assert!(
-        std::fs::metadata(&raw_path).is_err(),
-        "raw encoded path should not exist"
+
+    let redone = extract_json(
+        &server
+            .redo_changes(Parameters(UndoRedoParams { steps: None }))
+            .await
+            .unwrap(),
+    );
+    assert_eq!(redone["redone"][0]["tool"], "update_tracks");
+    assert_eq!(
+        server.state.changes.get("t1").unwrap().genre.as_deref(),
+        Some("House")
     );
 
-    let db_conn = create_single_track_test_db("encoded-track-1", &raw_path);
-    let store_dir = tempfile::tempdir().expect("temp store dir should create");
-    let store_path = store_dir.path().join("internal.sqlite3");
-    let store_conn = store::open(
-        store_path
-            .to_str()
-            .expect("temp store path should be UTF-8"),
-    )
-    .expect("temp internal store should open");
+    let err = server
+        .undo_changes(Parameters(UndoRedoParams { steps: Some(0) }))
+        .await
+        .expect_err("zero steps should be rejected");
+    assert!(err.message.contains("at least 1"));
+}
 
-    store::set_audio_analysis(
-        &store_conn,
-        &decoded_path_str,
-        "stratum-dsp",
-        12,
-        1_700_000_000,
-        "stratum-dsp-1.0.0",
-        r#"{"bpm":128.0,"key":"Am","analyzer_version":"stratum-dsp-1.0.0"}"#,
+fn create_staging_test_server(store_path: &str) -> ReklawdboxServer {
+    let store_conn = store::open(store_path).expect("temp store should open");
+    create_server_with_store_path(
+        Connection::open_in_memory().expect("in-memory DB should open"),
+        store_conn,
+        default_http_client_for_tests(),
+        Some(store_path.to_string()),
     )
-    .expect("audio cache entry should write with decoded cache key");
+}
 
-    let server =
-        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
-    let result = server
-        .resolve_track_data(Parameters(ResolveTrackDataParams {
-            track_id: "encoded-track-1".to_string(),
+#[tokio::test]
+async fn staged_changes_survive_server_restart() {
+    let store_dir = tempfile::tempdir().expect("temp dir should be created");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_path = store_path.to_str().expect("temp path should be UTF-8");
+
+    let server = create_staging_test_server(store_path);
+    assert_eq!(server.restore_staging_session(), Ok(0));
+    server
+        .update_tracks(Parameters(UpdateTracksParams {
+            changes: vec![TrackChangeInput {
+                track_id: "t1".to_string(),
+                genre: Some("Techno".to_string()),
+                bpm: Some(132.0),
+                ..Default::default()
+            }],
         }))
         .await
-        .expect("resolve_track_data should succeed");
-    let payload = extract_json(&result);
+        .expect("update_tracks should succeed");
+    let session_id = server
+        .state
+        .changes
+        .session_id()
+        .expect("restore should open a session");
+    drop(server);
 
+    let restarted = create_staging_test_server(store_path);
+    assert_eq!(restarted.restore_staging_session(), Ok(1));
     assert_eq!(
-        payload["data_completeness"]["stratum_dsp"], true,
-        "decoded path lookup should find stratum cache entry"
+        restarted.state.changes.session_id(),
+        Some(session_id.clone())
     );
-    assert!(
-        payload["audio_analysis"]["stratum_dsp"].is_object(),
-        "audio_analysis.stratum_dsp should be populated from cache"
+    let restored = restarted
+        .state
+        .changes
+        .get("t1")
+        .expect("staged change should be restored");
+    assert_eq!(restored.genre.as_deref(), Some("Techno"));
+    assert_eq!(restored.bpm, Some(132.0));
+
+    restarted
+        .rename_staging_session(Parameters(RenameStagingSessionParams {
+            session_id: None,
+            name: "Techno pass".to_string(),
+        }))
+        .await
+        .expect("rename should succeed");
+    let listed = extract_json(
+        &restarted
+            .list_staging_sessions()
+            .await
+            .expect("list should succeed"),
     );
-    assert_eq!(payload["audio_analysis"]["stratum_dsp"]["key"], "Am");
+    assert_eq!(listed["active_session_id"], session_id.as_str());
+    assert_eq!(listed["sessions"][0]["name"], "Techno pass");
+    assert_eq!(listed["sessions"][0]["change_count"], 1);
+
+    let discarded = extract_json(
+        &restarted
+            .discard_staging_session(Parameters(DiscardStagingSessionParams {
+                session_id: session_id.clone(),
+            }))
+            .await
+            .expect("discard should succeed"),
+    );
+    assert_eq!(discarded["discarded_changes"], 1);
+    assert!(restarted.state.changes.get("t1").is_none());
+    let new_session = discarded["active_session_id"]
+        .as_str()
+        .expect("discarding the active session should open a new one");
+    assert_ne!(new_session, session_id);
 }
 
 #[tokio::test]
-async fn cache_coverage_reports_provider_coverage_and_gap_counts() {
-    let db_conn = create_single_track_test_db("coverage-with-genre", "/music/coverage-1.flac");
-    insert_test_track(
-        &db_conn,
-        "coverage-no-genre-1",
-        "No Genre One",
-        "",
-        "/music/coverage-2.flac",
-    );
-    insert_test_track(
-        &db_conn,
-        "coverage-no-genre-2",
-        "No Genre Two",
-        "",
-        "/music/coverage-3.flac",
-    );
-    insert_test_track(
-        &db_conn,
-        "coverage-no-genre-3",
-        "No Genre Three",
-        "",
-        "/music/coverage-4.flac",
-    );
-
-    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+async fn resume_staging_session_swaps_queues() {
+    let store_dir = tempfile::tempdir().expect("temp dir should be created");
     let store_path = store_dir.path().join("internal.sqlite3");
-    let store_conn = store::open(
-        store_path
-            .to_str()
-            .expect("temp store path should be UTF-8"),
-    )
-    .expect("temp internal store should open");
-
-    let norm_artist = crate::normalize::normalize_for_matching("Aníbal");
-    let norm_title_one = crate::normalize::normalize_for_matching("No Genre One");
-    let norm_title_two = crate::normalize::normalize_for_matching("No Genre Two");
-
-    store::set_audio_analysis(
-        &store_conn,
-        "/music/coverage-2.flac",
-        "stratum-dsp",
-        1234,
-        1_700_000_001,
-        "stratum-dsp-1.0.0",
-        r#"{"bpm":127.1,"key":"Am"}"#,
-    )
-    .expect("stratum cache should be seeded");
-    store::set_audio_analysis(
-        &store_conn,
-        "/music/coverage-2.flac",
-        "essentia",
-        1234,
-        1_700_000_001,
-        "essentia-2.1",
-        r#"{"danceability":0.81}"#,
-    )
-    .expect("essentia cache should be seeded");
-    store::set_enrichment(
-        &store_conn,
-        "discogs",
-        &norm_artist,
-        &norm_title_one,
-        Some("exact"),
-        Some(r#"{"styles":["Deep House"]}"#),
-    )
-    .expect("discogs cache should be seeded for first ungenred track");
-    store::set_enrichment(
-        &store_conn,
-        "beatport",
-        &norm_artist,
-        &norm_title_one,
-        Some("exact"),
-        Some(r#"{"genre":"Deep House"}"#),
-    )
-    .expect("beatport cache should be seeded for first ungenred track");
-    store::set_enrichment(
-        &store_conn,
-        "discogs",
-        &norm_artist,
-        &norm_title_two,
-        Some("exact"),
-        Some(r#"{"styles":["Tech House"]}"#),
-    )
-    .expect("discogs cache should be seeded for second ungenred track");
+    let store_path = store_path.to_str().expect("temp path should be UTF-8");
 
-    let server =
-        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+    let server = create_staging_test_server(store_path);
     server
-        .state
-        .essentia_python
-        .set(Some("/tmp/fake-essentia-python".to_string()))
-        .expect("essentia probe cache should be set exactly once");
-
-    let result = server
-        .cache_coverage(Parameters(ResolveTracksDataParams {
-            filters: SearchFilterParams {
-                has_genre: Some(false),
+        .restore_staging_session()
+        .expect("restore should succeed");
+    let first_session = server.state.changes.session_id().unwrap();
+    server
+        .update_tracks(Parameters(UpdateTracksParams {
+            changes: vec![TrackChangeInput {
+                track_id: "t1".to_string(),
+                rating: Some(5),
                 ..Default::default()
-            },
-            track_ids: None,
-            playlist_id: None,
-            max_tracks: None,
-            format: None,
+            }],
         }))
         .await
-        .expect("cache_coverage should succeed");
-    let payload = extract_json(&result);
+        .expect("update_tracks should succeed");
 
-    assert_eq!(payload["scope"]["total_tracks"], 4);
-    assert_eq!(payload["scope"]["matched_tracks"], 3);
-    assert_eq!(payload["scope"]["filter_description"], "has_genre = false");
+    {
+        let conn = server.cache_store_conn().unwrap();
+        store::create_staging_session(&conn, "other", "Other").unwrap();
+        store::save_staged_changes(
+            &conn,
+            "other",
+            &[(
+                "t9".to_string(),
+                Some(crate::types::TrackChange {
+                    track_id: "t9".to_string(),
+                    comments: Some("from other session".to_string()),
+                    ..Default::default()
+                }),
+            )],
+        )
+        .unwrap();
+    }
 
-    assert_eq!(payload["coverage"]["stratum_dsp"]["cached"], 1);
-    assert_eq!(payload["coverage"]["stratum_dsp"]["percent"], 33.3);
+    let payload = extract_json(
+        &server
+            .resume_staging_session(Parameters(ResumeStagingSessionParams {
+                session_id: "other".to_string(),
+            }))
+            .await
+            .expect("resume should succeed"),
+    );
+    assert_eq!(payload["previous_session_id"], first_session.as_str());
+    assert_eq!(payload["total_pending"], 1);
+    assert!(server.state.changes.get("t1").is_none());
+    assert!(server.state.changes.get("t9").is_some());
 
-    assert_eq!(payload["coverage"]["essentia"]["cached"], 1);
-    assert_eq!(payload["coverage"]["essentia"]["percent"], 33.3);
-    assert_eq!(payload["coverage"]["essentia"]["installed"], true);
+    // The previous queue is still saved and can be resumed again.
+    server
+        .resume_staging_session(Parameters(ResumeStagingSessionParams {
+            session_id: first_session,
+        }))
+        .await
+        .expect("resume back should succeed");
+    assert_eq!(server.state.changes.get("t1").unwrap().rating, Some(5));
 
-    assert_eq!(payload["coverage"]["discogs"]["cached"], 2);
-    assert_eq!(payload["coverage"]["discogs"]["percent"], 66.7);
+    let err = server
+        .resume_staging_session(Parameters(ResumeStagingSessionParams {
+            session_id: "missing".to_string(),
+        }))
+        .await
+        .expect_err("unknown session should be rejected");
+    assert!(err.message.contains("not found"));
+}
 
-    assert_eq!(payload["coverage"]["beatport"]["cached"], 1);
-    assert_eq!(payload["coverage"]["beatport"]["percent"], 33.3);
+#[tokio::test]
+async fn update_tracks_via_router_includes_provenance() {
+    let result = call_tool_via_router(
+        "update_tracks",
+        serde_json::json!({
+            "changes": [{
+                "track_id": "router-test-track-1",
+                "genre": "NotInTaxonomy"
+            }]
+        })
+        .as_object()
+        .cloned(),
+    )
+    .await;
 
-    assert_eq!(payload["gaps"]["no_audio_analysis"], 2);
-    assert_eq!(payload["gaps"]["no_enrichment"], 1);
-    assert_eq!(payload["gaps"]["no_data_at_all"], 1);
+    let payload = extract_json(&result);
+    assert_eq!(
+        payload
+            .get("staged")
+            .and_then(serde_json::Value::as_u64)
+            .expect("staged should be present"),
+        1
+    );
+    let warnings = payload
+        .get("warnings")
+        .and_then(serde_json::Value::as_array)
+        .expect("warnings should be present for non-taxonomy genre");
+    assert!(
+        !warnings.is_empty(),
+        "warnings should include at least one non-taxonomy genre warning"
+    );
+    assert_has_provenance(&payload);
 }
 
 #[tokio::test]
-async fn cache_coverage_excludes_sampler_tracks_for_id_and_playlist_scopes() {
-    let db_conn = create_single_track_test_db("coverage-base", "/music/coverage-base.flac");
-    insert_test_track(
-        &db_conn,
-        "coverage-nonsample",
-        "Coverage Non Sample",
-        "",
-        "/music/coverage-nonsample.flac",
-    );
-    let sampler_path = format!("/music{}CoverageSampler.wav", db::SAMPLER_PATH_FRAGMENT);
-    insert_test_track(
-        &db_conn,
-        "coverage-sampler",
-        "Coverage Sampler",
-        "",
-        &sampler_path,
+async fn get_genre_taxonomy_via_router_includes_provenance() {
+    let result = call_tool_via_router("get_genre_taxonomy", None).await;
+    let payload = extract_json(&result);
+
+    let genres = payload
+        .get("genres")
+        .and_then(serde_json::Value::as_array)
+        .expect("genres should be present");
+    assert!(
+        !genres.is_empty(),
+        "genres should include configured taxonomy entries"
     );
+    assert_has_provenance(&payload);
+}
 
-    db_conn
-        .execute_batch(
-            "CREATE TABLE djmdSongPlaylist (
-                    PlaylistID VARCHAR(255),
-                    ContentID VARCHAR(255),
-                    TrackNo INTEGER
-                );",
-        )
-        .expect("playlist table should be created for test");
-    db_conn
-        .execute(
-            "INSERT INTO djmdSongPlaylist (PlaylistID, ContentID, TrackNo) VALUES (?1, ?2, ?3)",
-            params!["pl-cache", "coverage-nonsample", 1],
-        )
-        .expect("non-sampler playlist entry should insert");
-    db_conn
-        .execute(
-            "INSERT INTO djmdSongPlaylist (PlaylistID, ContentID, TrackNo) VALUES (?1, ?2, ?3)",
-            params!["pl-cache", "coverage-sampler", 2],
-        )
-        .expect("sampler playlist entry should insert");
+#[test]
+fn enrich_tracks_invalid_provider_rejected_by_serde() {
+    let json = serde_json::json!({
+        "providers": ["spotify"],
+    });
+    let result = serde_json::from_value::<EnrichTracksParams>(json);
+    assert!(
+        result.is_err(),
+        "serde should reject unknown provider variant"
+    );
+}
 
+#[tokio::test]
+async fn lookup_discogs_no_match_payload_is_consistent_across_live_and_cache_paths() {
+    let db_conn =
+        create_single_track_test_db("discogs-no-match-track", "/tmp/discogs-no-match.flac");
     let store_dir = tempfile::tempdir().expect("temp store dir should create");
     let store_path = store_dir.path().join("internal.sqlite3");
     let store_conn = store::open(
@@ -1999,492 +2554,1146 @@ async fn cache_coverage_excludes_sampler_tracks_for_id_and_playlist_scopes() {
     let server =
         create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
 
-    let id_scope = server
-        .cache_coverage(Parameters(ResolveTracksDataParams {
-            filters: SearchFilterParams::default(),
-            track_ids: Some(vec![
-                "coverage-nonsample".to_string(),
-                "coverage-sampler".to_string(),
-            ]),
-            playlist_id: None,
-            max_tracks: None,
-            format: None,
-        }))
-        .await
-        .expect("cache_coverage track_ids scope should succeed");
-    let id_payload = extract_json(&id_scope);
-    assert!(id_payload["scope"]["total_tracks"].as_u64().unwrap() >= 2);
-    assert_eq!(id_payload["scope"]["matched_tracks"], 1);
-    assert_eq!(id_payload["gaps"]["no_data_at_all"], 1);
+    let artist = "Discogs NoMatch Artist";
+    let title = "Discogs NoMatch Title";
+    set_test_discogs_lookup_override(artist, title, None, Ok(None));
 
-    let playlist_scope = server
-        .cache_coverage(Parameters(ResolveTracksDataParams {
-            filters: SearchFilterParams::default(),
-            track_ids: None,
-            playlist_id: Some("pl-cache".to_string()),
-            max_tracks: None,
-            format: None,
+    let live_result = server
+        .lookup_discogs(Parameters(LookupDiscogsParams {
+            track_id: None,
+            artist: Some(artist.to_string()),
+            title: Some(title.to_string()),
+            album: None,
+            force_refresh: Some(true),
         }))
         .await
-        .expect("cache_coverage playlist scope should succeed");
-    let playlist_payload = extract_json(&playlist_scope);
-    assert!(playlist_payload["scope"]["total_tracks"].as_u64().unwrap() >= 2);
-    assert_eq!(playlist_payload["scope"]["matched_tracks"], 1);
-    assert_eq!(playlist_payload["gaps"]["no_data_at_all"], 1);
-}
-
-#[tokio::test]
-#[ignore]
-async fn force_refresh_bypasses_enrichment_cache() {
-    let offline_http = reqwest::Client::builder()
-        .user_agent("Reklawdbox/0.1")
-        .proxy(reqwest::Proxy::all("http://127.0.0.1:9").expect("offline proxy URL should parse"))
-        .build()
-        .expect("offline HTTP client should build");
-
-    let Some((server, _store_dir)) = create_real_server_with_temp_store(offline_http) else {
-        eprintln!("Skipping: backup tarball not found (set REKORDBOX_TEST_BACKUP)");
-        return;
-    };
-
-    let track = sample_real_tracks(&server, 1)
-        .into_iter()
-        .next()
-        .expect("integration test needs at least one real track");
-    let norm_artist = crate::normalize::normalize_for_matching(&track.artist);
-    let norm_title = crate::normalize::normalize_for_matching(&track.title);
-    let cached_json = serde_json::json!({"genre":"Sentinel Genre","key":"Am","bpm":128});
-    let cached_json_str = cached_json.to_string();
-
-    {
-        let store = server
-            .cache_store_conn()
-            .expect("internal store should be available");
-        store::set_enrichment(
-            &store,
-            "beatport",
-            &norm_artist,
-            &norm_title,
-            Some("exact"),
-            Some(&cached_json_str),
-        )
-        .expect("sentinel cache entry should write");
-    }
+        .expect("live discogs no-match should succeed");
+    let live_payload = extract_json(&live_result);
+    assert_eq!(live_payload["result"], serde_json::Value::Null);
+    assert_eq!(live_payload["cache_hit"], false);
+    assert!(
+        live_payload.get("cached_at").is_none(),
+        "live payload should omit cached_at"
+    );
 
-    let cache_hit = server
-        .lookup_beatport(Parameters(LookupBeatportParams {
+    let cache_result = server
+        .lookup_discogs(Parameters(LookupDiscogsParams {
             track_id: None,
-            artist: Some(track.artist.clone()),
-            title: Some(track.title.clone()),
+            artist: Some(artist.to_string()),
+            title: Some(title.to_string()),
+            album: None,
             force_refresh: Some(false),
         }))
         .await
-        .expect("lookup_beatport(force_refresh=false) should return cache");
-    let cache_hit_json = extract_json(&cache_hit);
-    assert_eq!(cache_hit_json["cache_hit"], true);
-    assert_eq!(cache_hit_json["genre"], "Sentinel Genre");
-
-    let refresh_err = server
-        .lookup_beatport(Parameters(LookupBeatportParams {
-            track_id: None,
-            artist: Some(track.artist.clone()),
-            title: Some(track.title.clone()),
-            force_refresh: Some(true),
-        }))
-        .await
-        .expect_err("force_refresh=true should bypass cache and attempt HTTP call");
-    assert!(
-        format!("{refresh_err}").contains("Beatport error"),
-        "force refresh should fail via offline HTTP path, got: {refresh_err}"
-    );
-}
-
-#[tokio::test]
-async fn enrich_tracks_beatport_schema_matches_individual_lookup() {
-    let Some((server, _store_dir)) =
-        create_real_server_with_temp_store(default_http_client_for_tests())
-    else {
-        eprintln!("Skipping: backup tarball not found (set REKORDBOX_TEST_BACKUP)");
-        return;
-    };
-
-    let candidates = sample_real_tracks(&server, 30);
-    if candidates.is_empty() {
-        eprintln!("Skipping: integration test needs candidate tracks from real DB");
-        return;
-    }
-
-    let mut selected_track: Option<crate::types::Track> = None;
-    for track in candidates.into_iter().take(10) {
-        let lookup = server
-            .lookup_beatport(Parameters(LookupBeatportParams {
-                track_id: None,
-                artist: Some(track.artist.clone()),
-                title: Some(track.title.clone()),
-                force_refresh: Some(true),
-            }))
-            .await;
-
-        let Ok(result) = lookup else {
-            continue;
-        };
-        let payload = extract_json(&result);
-        if payload
-            .get("genre")
-            .and_then(serde_json::Value::as_str)
-            .is_some()
-        {
-            selected_track = Some(track);
-            break;
-        }
-    }
-
-    let Some(track) = selected_track else {
-        eprintln!(
-            "Skipping: could not find a track with a successful Beatport match; \
-                 rerun when network/providers are available"
-        );
-        return;
-    };
-    let norm_artist = crate::normalize::normalize_for_matching(&track.artist);
-    let norm_title = crate::normalize::normalize_for_matching(&track.title);
+        .expect("cached discogs no-match should succeed");
+    let cache_payload = extract_json(&cache_result);
+    assert_eq!(cache_payload["result"], serde_json::Value::Null);
+    assert_eq!(cache_payload["cache_hit"], true);
 
-    let individual_cache = {
+    let cache_hit_timestamp = cache_payload
+        .get("cached_at")
+        .and_then(serde_json::Value::as_str)
+        .expect("cached no-match payload should include cached_at");
+    let norm_artist = crate::normalize::normalize_for_matching(artist);
+    let norm_title = crate::normalize::normalize_for_matching(title);
+    let cache_entry = {
         let store = server
             .cache_store_conn()
             .expect("internal store should be available");
-        store::get_enrichment(&store, "beatport", &norm_artist, &norm_title)
+        store::get_enrichment(&store, "discogs", &norm_artist, &norm_title)
             .expect("cache read should succeed")
-            .expect("individual lookup should have created cache entry")
+            .expect("discogs no-match lookup should create cache entry")
     };
-    let individual_json: serde_json::Value = serde_json::from_str(
-        individual_cache
-            .response_json
-            .as_deref()
-            .expect("individual beatport cache should contain JSON"),
-    )
-    .expect("individual beatport cache JSON should parse");
     assert!(
-        individual_json
-            .get("genre")
-            .and_then(serde_json::Value::as_str)
-            .is_some(),
-        "individual beatport cache should have string 'genre'"
+        cache_entry.response_json.is_none(),
+        "discogs no-match cache entry should store null response as no payload"
     );
-    let individual_fields: HashSet<String> = individual_json
-        .as_object()
-        .expect("individual beatport cache should be object")
-        .keys()
-        .cloned()
-        .collect();
+    assert_eq!(
+        cache_hit_timestamp,
+        cache_entry.created_at.as_str(),
+        "cached_at should match persisted cache timestamp"
+    );
+}
 
-    {
-        let store = server
-            .cache_store_conn()
-            .expect("internal store should be available");
-        store
-            .execute(
-                "DELETE FROM enrichment_cache
-                     WHERE provider = ?1 AND query_artist = ?2 AND query_title = ?3",
-                params!["beatport", &norm_artist, &norm_title],
-            )
-            .expect("cache clear should succeed");
-    }
+#[tokio::test]
+async fn lookup_beatport_no_match_payload_is_consistent_across_live_and_cache_paths() {
+    let db_conn =
+        create_single_track_test_db("beatport-no-match-track", "/tmp/beatport-no-match.flac");
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
 
-    let enrich_result = server
-        .enrich_tracks(Parameters(EnrichTracksParams {
-            filters: SearchFilterParams::default(),
-            track_ids: Some(vec![track.id.clone()]),
-            playlist_id: None,
-            max_tracks: Some(1),
-            offset: None,
-            providers: Some(vec![crate::types::Provider::Beatport]),
-            skip_cached: Some(false),
+    let artist = "Beatport NoMatch Artist";
+    let title = "Beatport NoMatch Title";
+    set_test_beatport_lookup_override(artist, title, Ok(None));
+
+    let live_result = server
+        .lookup_beatport(Parameters(LookupBeatportParams {
+            track_id: None,
+            artist: Some(artist.to_string()),
+            title: Some(title.to_string()),
             force_refresh: Some(true),
-            concurrency: None,
         }))
         .await
-        .expect("enrich_tracks should succeed for beatport provider");
-    let enrich_payload = extract_json(&enrich_result);
-    assert_eq!(enrich_payload["summary"]["total"], 1);
+        .expect("live beatport no-match should succeed");
+    let live_payload = extract_json(&live_result);
+    assert_eq!(live_payload["result"], serde_json::Value::Null);
+    assert_eq!(live_payload["cache_hit"], false);
+    assert!(
+        live_payload.get("cached_at").is_none(),
+        "live payload should omit cached_at"
+    );
 
-    let batch_cache = {
+    let cache_result = server
+        .lookup_beatport(Parameters(LookupBeatportParams {
+            track_id: None,
+            artist: Some(artist.to_string()),
+            title: Some(title.to_string()),
+            force_refresh: Some(false),
+        }))
+        .await
+        .expect("cached beatport no-match should succeed");
+    let cache_payload = extract_json(&cache_result);
+    assert_eq!(cache_payload["result"], serde_json::Value::Null);
+    assert_eq!(cache_payload["cache_hit"], true);
+
+    let cache_hit_timestamp = cache_payload
+        .get("cached_at")
+        .and_then(serde_json::Value::as_str)
+        .expect("cached no-match payload should include cached_at");
+    let norm_artist = crate::normalize::normalize_for_matching(artist);
+    let norm_title = crate::normalize::normalize_for_matching(title);
+    let cache_entry = {
         let store = server
             .cache_store_conn()
             .expect("internal store should be available");
         store::get_enrichment(&store, "beatport", &norm_artist, &norm_title)
             .expect("cache read should succeed")
-            .expect("batch enrich should have created beatport cache entry")
+            .expect("beatport no-match lookup should create cache entry")
     };
-    let batch_json: serde_json::Value = serde_json::from_str(
-        batch_cache
-            .response_json
-            .as_deref()
-            .expect("batch beatport cache should contain JSON"),
-    )
-    .expect("batch beatport cache JSON should parse");
     assert!(
-        batch_json
-            .get("genre")
-            .and_then(serde_json::Value::as_str)
-            .is_some(),
-        "batch beatport cache should have string 'genre'"
-    );
-    assert!(
-        batch_json.get("genres").is_none(),
-        "beatport cache should not be transformed into discogs-style 'genres' schema"
+        cache_entry.response_json.is_none(),
+        "beatport no-match cache entry should store null response as no payload"
     );
-
-    let batch_fields: HashSet<String> = batch_json
-        .as_object()
-        .expect("batch beatport cache should be object")
-        .keys()
-        .cloned()
-        .collect();
     assert_eq!(
-        batch_fields, individual_fields,
-        "batch and individual beatport cache JSON should share the same schema"
+        cache_hit_timestamp,
+        cache_entry.created_at.as_str(),
+        "cached_at should match persisted cache timestamp"
     );
 }
 
 #[tokio::test]
-#[ignore]
-async fn resolve_tracks_data_batch_consistency() {
-    let Some((server, _store_dir)) =
-        create_real_server_with_temp_store(default_http_client_for_tests())
-    else {
-        eprintln!("Skipping: backup tarball not found (set REKORDBOX_TEST_BACKUP)");
-        return;
+async fn enrich_tracks_discogs_skip_cached_reports_cached_counts() {
+    let db_conn = create_single_track_test_db("cached-track-1", "/tmp/cached-track-1.flac");
+    db_conn
+        .execute(
+            "INSERT INTO djmdContent (
+                    ID, Title, ArtistID, AlbumID, GenreID, KeyID, ColorID, LabelID, RemixerID,
+                    BPM, Rating, Commnt, ReleaseYear, Length, FolderPath, DJPlayCount, BitRate,
+                    SampleRate, FileType, created_at, rb_local_deleted
+                ) VALUES (
+                    ?1, ?2, 'a1', 'al1', 'g1', 'k1', 'c1', 'l1', '',
+                    12700, 153, 'cached batch test', 2025, 230, ?3, '0', 1411,
+                    44100, 5, '2025-01-01', 0
+                )",
+            params![
+                "cached-track-2",
+                "Corazón Cached",
+                "/tmp/cached-track-2.flac"
+            ],
+        )
+        .expect("second test track should insert");
+
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_path_str = store_path
+        .to_str()
+        .expect("temp store path should be UTF-8")
+        .to_string();
+    let store_conn = store::open(&store_path_str).expect("temp internal store should open");
+
+    let artist = "Aníbal";
+    let title_one = "Señorita";
+    let title_two = "Corazón Cached";
+    let norm_artist = crate::normalize::normalize_for_matching(artist);
+    let norm_title_one = crate::normalize::normalize_for_matching(title_one);
+    let norm_title_two = crate::normalize::normalize_for_matching(title_two);
+
+    let cached_one = serde_json::json!({
+        "title": "Anibal - Senorita",
+        "genres": ["Electronic"],
+        "styles": ["Deep House"],
+        "fuzzy_match": false
+    })
+    .to_string();
+    let cached_two = serde_json::json!({
+        "title": "Anibal - Corazon Cached",
+        "genres": ["Electronic"],
+        "styles": ["House"],
+        "fuzzy_match": false
+    })
+    .to_string();
+
+    store::set_enrichment(
+        &store_conn,
+        "discogs",
+        &norm_artist,
+        &norm_title_one,
+        Some("exact"),
+        Some(&cached_one),
+    )
+    .expect("first sentinel discogs cache entry should write");
+    store::set_enrichment(
+        &store_conn,
+        "discogs",
+        &norm_artist,
+        &norm_title_two,
+        Some("exact"),
+        Some(&cached_two),
+    )
+    .expect("second sentinel discogs cache entry should write");
+
+    let server = create_server_with_store_path(
+        db_conn,
+        store_conn,
+        default_http_client_for_tests(),
+        Some(store_path_str),
+    );
+
+    let params = EnrichTracksParams {
+        filters: SearchFilterParams::default(),
+        track_ids: Some(vec![
+            "cached-track-1".to_string(),
+            "cached-track-2".to_string(),
+        ]),
+        playlist_id: None,
+        max_tracks: Some(10),
+        offset: None,
+        providers: Some(vec![crate::types::Provider::Discogs]),
+        skip_cached: Some(true),
+        force_refresh: Some(false),
+        concurrency: None,
     };
 
-    let tracks = sample_real_tracks(&server, 5);
-    assert!(
-        !tracks.is_empty(),
-        "integration test needs tracks from real DB"
+    let first_result = server
+        .enrich_tracks(Parameters(params))
+        .await
+        .expect("enrich_tracks should succeed when everything is cached");
+    let first_payload = extract_json(&first_result);
+    assert_eq!(first_payload["summary"]["total"], 2);
+    assert_eq!(first_payload["summary"]["enriched"], 0);
+    assert_eq!(first_payload["summary"]["cached"], 2);
+    assert_eq!(first_payload["summary"]["skipped"], 0);
+    assert_eq!(first_payload["summary"]["failed"], 0);
+    assert_eq!(
+        first_payload["failures"]
+            .as_array()
+            .expect("failures should be an array")
+            .len(),
+        0
     );
-    let track_ids: Vec<String> = tracks.iter().map(|t| t.id.clone()).collect();
 
-    let batch_result = server
-        .resolve_tracks_data(Parameters(ResolveTracksDataParams {
+    let second_result = server
+        .enrich_tracks(Parameters(EnrichTracksParams {
             filters: SearchFilterParams::default(),
-            track_ids: Some(track_ids.clone()),
+            track_ids: Some(vec![
+                "cached-track-1".to_string(),
+                "cached-track-2".to_string(),
+            ]),
             playlist_id: None,
-            max_tracks: Some(track_ids.len() as u32),
-            format: None,
+            max_tracks: Some(10),
+            offset: None,
+            providers: Some(vec![crate::types::Provider::Discogs]),
+            skip_cached: Some(true),
+            force_refresh: Some(false),
+            concurrency: None,
         }))
         .await
-        .expect("batch resolve should succeed");
-    let batch_payload = extract_json(&batch_result);
-    let batch_items = batch_payload
-        .as_array()
-        .expect("batch resolve payload should be an array");
+        .expect("second enrich_tracks run should also be fully cached");
+    let second_payload = extract_json(&second_result);
+    assert_eq!(second_payload["summary"]["total"], 2);
+    assert_eq!(second_payload["summary"]["enriched"], 0);
+    assert_eq!(second_payload["summary"]["cached"], 2);
+    assert_eq!(second_payload["summary"]["skipped"], 0);
+    assert_eq!(second_payload["summary"]["failed"], 0);
+
+    let store = server
+        .cache_store_conn()
+        .expect("internal store should be available");
+    let entry_one = store::get_enrichment(&store, "discogs", &norm_artist, &norm_title_one)
+        .expect("cache read should succeed")
+        .expect("first cache entry should still exist");
+    let entry_two = store::get_enrichment(&store, "discogs", &norm_artist, &norm_title_two)
+        .expect("cache read should succeed")
+        .expect("second cache entry should still exist");
     assert_eq!(
-        batch_items.len(),
-        track_ids.len(),
-        "batch resolve should return one entry per requested track"
+        entry_one.response_json.as_deref(),
+        Some(cached_one.as_str())
+    );
+    assert_eq!(
+        entry_two.response_json.as_deref(),
+        Some(cached_two.as_str())
     );
+}
 
-    let mut by_track_id: HashMap<String, serde_json::Value> = HashMap::new();
-    for item in batch_items {
-        let track_id = item
-            .get("track_id")
-            .and_then(serde_json::Value::as_str)
-            .expect("resolved track item should include track_id");
-        by_track_id.insert(track_id.to_string(), item.clone());
-    }
+#[tokio::test]
+async fn enrich_tracks_summary_uses_provider_attempt_totals() {
+    let db_conn = create_single_track_test_db("cached-track-1", "/tmp/cached-track-1.flac");
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_path_str = store_path
+        .to_str()
+        .expect("temp store path should be UTF-8")
+        .to_string();
+    let store_conn = store::open(&store_path_str).expect("temp internal store should open");
 
-    for track_id in &track_ids {
-        let single_result = server
-            .resolve_track_data(Parameters(ResolveTrackDataParams {
-                track_id: track_id.clone(),
-            }))
-            .await
-            .expect("single resolve should succeed");
-        let single_payload = extract_json(&single_result);
-        assert_eq!(
-            by_track_id
-                .get(track_id)
-                .expect("batch output should include every requested track"),
-            &single_payload,
-            "batch resolve output should match single-track resolve output"
-        );
-    }
-}
+    let norm_artist = crate::normalize::normalize_for_matching("Aníbal");
+    let norm_title = crate::normalize::normalize_for_matching("Señorita");
+    store::set_enrichment(
+        &store_conn,
+        "discogs",
+        &norm_artist,
+        &norm_title,
+        Some("exact"),
+        Some(r#"{"styles":["Deep House"]}"#),
+    )
+    .expect("discogs cache should seed");
+    store::set_enrichment(
+        &store_conn,
+        "beatport",
+        &norm_artist,
+        &norm_title,
+        Some("exact"),
+        Some(r#"{"genre":"Deep House"}"#),
+    )
+    .expect("beatport cache should seed");
 
-#[test]
-fn golden_genres_fixture_is_well_formed() {
-    let entries = load_golden_genres_fixture();
-    assert!(
-        !entries.is_empty(),
-        "golden genres fixture should contain at least one entry"
+    let server = create_server_with_store_path(
+        db_conn,
+        store_conn,
+        default_http_client_for_tests(),
+        Some(store_path_str),
     );
+    let result = server
+        .enrich_tracks(Parameters(EnrichTracksParams {
+            filters: SearchFilterParams::default(),
+            track_ids: Some(vec!["cached-track-1".to_string()]),
+            playlist_id: None,
+            max_tracks: Some(1),
+            offset: None,
+            providers: Some(vec![
+                crate::types::Provider::Discogs,
+                crate::types::Provider::Beatport,
+            ]),
+            skip_cached: Some(true),
+            force_refresh: Some(false),
+            concurrency: None,
+        }))
+        .await
+        .expect("enrich_tracks should resolve from cache for both providers");
+    let payload = extract_json(&result);
 
-    let mut unique = HashSet::new();
-    for entry in &entries {
-        assert!(
-            !entry.artist.trim().is_empty(),
-            "fixture artist must be non-empty"
-        );
-        assert!(
-            !entry.title.trim().is_empty(),
-            "fixture title must be non-empty"
-        );
-        assert!(
-            !entry.notes.trim().is_empty(),
-            "fixture notes must be non-empty"
-        );
-        assert!(
-            genre::is_known_genre(&entry.expected_genre),
-            "expected_genre '{}' must be in taxonomy",
-            entry.expected_genre
-        );
-        assert!(
-            genre::canonical_genre_from_alias(&entry.expected_genre).is_none(),
-            "expected_genre '{}' must be canonical, not alias",
-            entry.expected_genre
-        );
-
-        let key = format!(
-            "{}::{}",
-            entry.artist.to_lowercase(),
-            entry.title.to_lowercase()
-        );
-        assert!(unique.insert(key), "duplicate (artist, title) in fixture");
-    }
+    assert_eq!(payload["summary"]["tracks_total"], 1);
+    assert_eq!(payload["summary"]["total"], 2);
+    assert_eq!(payload["summary"]["cached"], 2);
+    assert_eq!(payload["summary"]["enriched"], 0);
+    assert_eq!(payload["summary"]["skipped"], 0);
+    assert_eq!(payload["summary"]["failed"], 0);
 }
 
-#[test]
-#[ignore]
-fn golden_dataset_genre_accuracy() {
-    let entries = load_golden_genres_fixture();
-    let Some(conn) = db::open_real_db() else {
-        eprintln!("Skipping: backup tarball not found (set REKORDBOX_TEST_BACKUP)");
-        return;
-    };
+#[tokio::test]
+async fn resolve_track_data_uses_decoded_path_for_audio_cache_lookup() {
+    let temp_audio_dir = tempfile::tempdir().expect("temp audio dir should create");
+    let decoded_path = temp_audio_dir.path().join("Aníbal Track.flac");
+    std::fs::write(&decoded_path, b"fake-audio-data")
+        .expect("decoded path file should exist for resolve_file_path");
 
-    let mut compared = 0usize;
-    let mut correct = 0usize;
-    let mut missing_tracks = Vec::new();
-    let mut no_genre = Vec::new();
-    let mut mismatches = Vec::new();
+    let decoded_path_str = decoded_path.to_string_lossy().to_string();
+    let raw_path = decoded_path_str
+        .replace("Aníbal", "An%C3%ADbal")
+        .replace(' ', "%20");
+    assert_ne!(
+        raw_path, decoded_path_str,
+        "raw path must differ from decoded path for this regression test"
+    );
+    assert!(
+        std::fs::metadata(&decoded_path_str).is_ok(),
+        "decoded file path should exist"
+    );
+    assert!(
+        std::fs::metadata(&raw_path).is_err(),
+        "raw encoded path should not exist"
+    );
 
-    for entry in &entries {
-        let Some(track) = find_track_by_artist_and_title(&conn, &entry.artist, &entry.title) else {
-            missing_tracks.push(format!("{} - {}", entry.artist, entry.title));
-            continue;
-        };
+    let db_conn = create_single_track_test_db("encoded-track-1", &raw_path);
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
 
-        if track.genre.trim().is_empty() {
-            no_genre.push(format!("{} - {}", entry.artist, entry.title));
-            continue;
-        }
+    store::set_audio_analysis(
+        &store_conn,
+        &decoded_path_str,
+        "stratum-dsp",
+        12,
+        1_700_000_000,
+        "stratum-dsp-1.0.0",
+        r#"{"bpm":128.0,"key":"Am","analyzer_version":"stratum-dsp-1.0.0"}"#,
+    )
+    .expect("audio cache entry should write with decoded cache key");
 
-        compared += 1;
-        let actual = canonical_genre_name(&track.genre);
-        if actual.eq_ignore_ascii_case(&entry.expected_genre) {
-            correct += 1;
-        } else {
-            mismatches.push(format!(
-                "{} - {}: expected '{}', actual '{}' ({})",
-                entry.artist, entry.title, entry.expected_genre, actual, entry.notes
-            ));
-        }
-    }
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+    let result = server
+        .resolve_track_data(Parameters(ResolveTrackDataParams {
+            track_id: "encoded-track-1".to_string(),
+        }))
+        .await
+        .expect("resolve_track_data should succeed");
+    let payload = extract_json(&result);
 
-    let accuracy = if compared == 0 {
-        0.0
-    } else {
-        (correct as f64 / compared as f64) * 100.0
-    };
-    eprintln!(
-        "[integration] golden dataset: total={} compared={} correct={} accuracy={:.1}%",
-        entries.len(),
-        compared,
-        correct,
-        accuracy
+    assert_eq!(
+        payload["data_completeness"]["stratum_dsp"], true,
+        "decoded path lookup should find stratum cache entry"
     );
-    if !missing_tracks.is_empty() {
-        eprintln!("[integration] missing tracks ({}):", missing_tracks.len());
-        for item in &missing_tracks {
-            eprintln!("  - {item}");
-        }
-    }
-    if !no_genre.is_empty() {
-        eprintln!(
-            "[integration] tracks with empty genre ({}):",
-            no_genre.len()
-        );
-        for item in &no_genre {
-            eprintln!("  - {item}");
-        }
-    }
-    if !mismatches.is_empty() {
-        eprintln!("[integration] mismatches ({}):", mismatches.len());
-        for item in &mismatches {
-            eprintln!("  - {item}");
-        }
-    }
-
     assert!(
-        !missing_tracks.is_empty() || compared > 0,
-        "fixture should either report missing tracks or compare at least one track"
+        payload["audio_analysis"]["stratum_dsp"].is_object(),
+        "audio_analysis.stratum_dsp should be populated from cache"
     );
+    assert_eq!(payload["audio_analysis"]["stratum_dsp"]["key"], "Am");
 }
 
-// --- resolve_single_track unit tests ---
+#[tokio::test]
+async fn resolve_track_data_surfaces_cached_structure_and_mix_points() {
+    let db_conn = create_single_track_test_db("structure-track", "/music/structure.flac");
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(store_path.to_str().unwrap()).expect("store should open");
+    seed_structure(&store_conn, "/music/structure.flac", 32, 16);
 
-fn make_test_track(id: &str, genre: &str, bpm: f64, key: &str) -> crate::types::Track {
-    crate::types::Track {
-        id: id.to_string(),
-        title: format!("Track {id}"),
-        artist: "Test Artist".to_string(),
-        album: "Test Album".to_string(),
-        genre: genre.to_string(),
-        bpm,
-        key: key.to_string(),
-        rating: 3,
-        comments: "test comment".to_string(),
-        color: "Rose".to_string(),
-        color_code: 1,
-        label: "Test Label".to_string(),
-        remixer: "".to_string(),
-        year: 2023,
-        length: 300,
-        file_path: "/music/test.flac".to_string(),
-        play_count: 5,
-        bit_rate: 1411,
-        sample_rate: 44100,
-        file_kind: crate::types::FileKind::Flac,
-        date_added: "2023-01-15".to_string(),
-        position: None,
-    }
-}
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+    let result = server
+        .resolve_track_data(Parameters(ResolveTrackDataParams {
+            track_id: "structure-track".to_string(),
+        }))
+        .await
+        .expect("resolve_track_data should succeed");
+    let payload = extract_json(&result);
 
-#[test]
-fn resolve_single_track_rekordbox_only() {
-    let track = make_test_track("t1", "Deep House", 126.0, "Am");
-    let result = resolve_single_track(&track, None, None, None, None, false, None);
+    assert_eq!(payload["data_completeness"]["structure"], true);
+    assert_eq!(payload["structure"]["total_bars"], 96);
+    assert_eq!(payload["mix_points"]["intro_bars"], 32);
+    assert_eq!(payload["mix_points"]["outro_bars"], 16);
+    assert_eq!(payload["mix_points"]["mix_in_end_sec"], 0.25 + 32.0 * 1.875);
 
-    // Verify rekordbox section present
-    let rb = result
-        .get("rekordbox")
-        .expect("rekordbox section should exist");
-    assert_eq!(rb["title"], "Track t1");
-    assert_eq!(rb["artist"], "Test Artist");
-    assert_eq!(rb["genre"], "Deep House");
-    assert_eq!(rb["bpm"], 126.0);
-    assert_eq!(rb["key"], "Am");
-    assert_eq!(rb["duration_s"], 300);
-    assert_eq!(rb["year"], 2023);
-    assert_eq!(rb["rating"], 3);
-    assert_eq!(rb["label"], "Test Label");
+    let results = server
+        .resolve_tracks_data(Parameters(ResolveTracksDataParams {
+            track_ids: Some(vec!["structure-track".to_string()]),
+            playlist_id: None,
+            filters: Default::default(),
+            max_tracks: None,
+            format: None,
+        }))
+        .await
+        .expect("resolve_tracks_data should succeed");
+    let payload = extract_json(&results);
+    assert_eq!(payload[0]["mix_points"]["outro_bars"], 16);
+}
 
-    // Null sections when no cache
-    assert!(
-        result["audio_analysis"].is_null(),
-        "audio_analysis should be null without cache"
+#[tokio::test]
+async fn cache_coverage_reports_provider_coverage_and_gap_counts() {
+    let db_conn = create_single_track_test_db("coverage-with-genre", "/music/coverage-1.flac");
+    insert_test_track(
+        &db_conn,
+        "coverage-no-genre-1",
+        "No Genre One",
+        "",
+        "/music/coverage-2.flac",
     );
-    assert!(
+    insert_test_track(
+        &db_conn,
+        "coverage-no-genre-2",
+        "No Genre Two",
+        "",
+        "/music/coverage-3.flac",
+    );
+    insert_test_track(
+        &db_conn,
+        "coverage-no-genre-3",
+        "No Genre Three",
+        "",
+        "/music/coverage-4.flac",
+    );
+
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+
+    let norm_artist = crate::normalize::normalize_for_matching("Aníbal");
+    let norm_title_one = crate::normalize::normalize_for_matching("No Genre One");
+    let norm_title_two = crate::normalize::normalize_for_matching("No Genre Two");
+
+    store::set_audio_analysis(
+        &store_conn,
+        "/music/coverage-2.flac",
+        "stratum-dsp",
+        1234,
+        1_700_000_001,
+        "stratum-dsp-1.0.0",
+        r#"{"bpm":127.1,"key":"Am"}"#,
+    )
+    .expect("stratum cache should be seeded");
+    store::set_audio_analysis(
+        &store_conn,
+        "/music/coverage-2.flac",
+        "essentia",
+        1234,
+        1_700_000_001,
+        "essentia-2.1",
+        r#"{"danceability":0.81}"#,
+    )
+    .expect("essentia cache should be seeded");
+    store::set_enrichment(
+        &store_conn,
+        "discogs",
+        &norm_artist,
+        &norm_title_one,
+        Some("exact"),
+        Some(r#"{"styles":["Deep House"]}"#),
+    )
+    .expect("discogs cache should be seeded for first ungenred track");
+    store::set_enrichment(
+        &store_conn,
+        "beatport",
+        &norm_artist,
+        &norm_title_one,
+        Some("exact"),
+        Some(r#"{"genre":"Deep House"}"#),
+    )
+    .expect("beatport cache should be seeded for first ungenred track");
+    store::set_enrichment(
+        &store_conn,
+        "discogs",
+        &norm_artist,
+        &norm_title_two,
+        Some("exact"),
+        Some(r#"{"styles":["Tech House"]}"#),
+    )
+    .expect("discogs cache should be seeded for second ungenred track");
+
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+    server
+        .state
+        .essentia_python
+        .set(Some("/tmp/fake-essentia-python".to_string()))
+        .expect("essentia probe cache should be set exactly once");
+
+    let result = server
+        .cache_coverage(Parameters(ResolveTracksDataParams {
+            filters: SearchFilterParams {
+                has_genre: Some(false),
+                ..Default::default()
+            },
+            track_ids: None,
+            playlist_id: None,
+            max_tracks: None,
+            format: None,
+        }))
+        .await
+        .expect("cache_coverage should succeed");
+    let payload = extract_json(&result);
+
+    assert_eq!(payload["scope"]["total_tracks"], 4);
+    assert_eq!(payload["scope"]["matched_tracks"], 3);
+    assert_eq!(payload["scope"]["filter_description"], "has_genre = false");
+
+    assert_eq!(payload["coverage"]["stratum_dsp"]["cached"], 1);
+    assert_eq!(payload["coverage"]["stratum_dsp"]["percent"], 33.3);
+
+    assert_eq!(payload["coverage"]["essentia"]["cached"], 1);
+    assert_eq!(payload["coverage"]["essentia"]["percent"], 33.3);
+    assert_eq!(payload["coverage"]["essentia"]["installed"], true);
+
+    assert_eq!(payload["coverage"]["discogs"]["cached"], 2);
+    assert_eq!(payload["coverage"]["discogs"]["percent"], 66.7);
+
+    assert_eq!(payload["coverage"]["beatport"]["cached"], 1);
+    assert_eq!(payload["coverage"]["beatport"]["percent"], 33.3);
+
+    assert_eq!(payload["gaps"]["no_audio_analysis"], 2);
+    assert_eq!(payload["gaps"]["no_enrichment"], 1);
+    assert_eq!(payload["gaps"]["no_data_at_all"], 1);
+}
+
+#[tokio::test]
+async fn cache_coverage_excludes_sampler_tracks_for_id_and_playlist_scopes() {
+    let db_conn = create_single_track_test_db("coverage-base", "/music/coverage-base.flac");
+    insert_test_track(
+        &db_conn,
+        "coverage-nonsample",
+        "Coverage Non Sample",
+        "",
+        "/music/coverage-nonsample.flac",
+    );
+    let sampler_path = format!("/music{}CoverageSampler.wav", db::SAMPLER_PATH_FRAGMENT);
+    insert_test_track(
+        &db_conn,
+        "coverage-sampler",
+        "Coverage Sampler",
+        "",
+        &sampler_path,
+    );
+
+    db_conn
+        .execute_batch(
+            "CREATE TABLE djmdPlaylist (
+                    ID VARCHAR(255) PRIMARY KEY,
+                    Name VARCHAR(255),
+                    Attribute INTEGER DEFAULT 0,
+                    SmartList TEXT,
+                    rb_local_deleted INTEGER DEFAULT 0
+                );
+                INSERT INTO djmdPlaylist (ID, Name) VALUES ('pl-cache', 'Cache');
+                CREATE TABLE djmdSongPlaylist (
+                    PlaylistID VARCHAR(255),
+                    ContentID VARCHAR(255),
+                    TrackNo INTEGER
+                );",
+        )
+        .expect("playlist tables should be created for test");
+    db_conn
+        .execute(
+            "INSERT INTO djmdSongPlaylist (PlaylistID, ContentID, TrackNo) VALUES (?1, ?2, ?3)",
+            params!["pl-cache", "coverage-nonsample", 1],
+        )
+        .expect("non-sampler playlist entry should insert");
+    db_conn
+        .execute(
+            "INSERT INTO djmdSongPlaylist (PlaylistID, ContentID, TrackNo) VALUES (?1, ?2, ?3)",
+            params!["pl-cache", "coverage-sampler", 2],
+        )
+        .expect("sampler playlist entry should insert");
+
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+
+    let id_scope = server
+        .cache_coverage(Parameters(ResolveTracksDataParams {
+            filters: SearchFilterParams::default(),
+            track_ids: Some(vec![
+                "coverage-nonsample".to_string(),
+                "coverage-sampler".to_string(),
+            ]),
+            playlist_id: None,
+            max_tracks: None,
+            format: None,
+        }))
+        .await
+        .expect("cache_coverage track_ids scope should succeed");
+    let id_payload = extract_json(&id_scope);
+    assert!(id_payload["scope"]["total_tracks"].as_u64().unwrap() >= 2);
+    assert_eq!(id_payload["scope"]["matched_tracks"], 1);
+    assert_eq!(id_payload["gaps"]["no_data_at_all"], 1);
+
+    let playlist_scope = server
+        .cache_coverage(Parameters(ResolveTracksDataParams {
+            filters: SearchFilterParams::default(),
+            track_ids: None,
+            playlist_id: Some("pl-cache".to_string()),
+            max_tracks: None,
+            format: None,
+        }))
+        .await
+        .expect("cache_coverage playlist scope should succeed");
+    let playlist_payload = extract_json(&playlist_scope);
+    assert!(playlist_payload["scope"]["total_tracks"].as_u64().unwrap() >= 2);
+    assert_eq!(playlist_payload["scope"]["matched_tracks"], 1);
+    assert_eq!(playlist_payload["gaps"]["no_data_at_all"], 1);
+}
+
+#[tokio::test]
+#[ignore]
+async fn force_refresh_bypasses_enrichment_cache() {
+    let offline_http = reqwest::Client::builder()
+        .user_agent("Reklawdbox/0.1")
+        .proxy(reqwest::Proxy::all("http://127.0.0.1:9").expect("offline proxy URL should parse"))
+        .build()
+        .expect("offline HTTP client should build");
+
+    let Some((server, _store_dir)) = create_real_server_with_temp_store(offline_http) else {
+        eprintln!("Skipping: backup tarball not found (set REKORDBOX_TEST_BACKUP)");
+        return;
+    };
+
+    let track = sample_real_tracks(&server, 1)
+        .into_iter()
+        .next()
+        .expect("integration test needs at least one real track");
+    let norm_artist = crate::normalize::normalize_for_matching(&track.artist);
+    let norm_title = crate::normalize::normalize_for_matching(&track.title);
+    let cached_json = serde_json::json!({"genre":"Sentinel Genre","key":"Am","bpm":128});
+    let cached_json_str = cached_json.to_string();
+
+    {
+        let store = server
+            .cache_store_conn()
+            .expect("internal store should be available");
+        store::set_enrichment(
+            &store,
+            "beatport",
+            &norm_artist,
+            &norm_title,
+            Some("exact"),
+            Some(&cached_json_str),
+        )
+        .expect("sentinel cache entry should write");
+    }
+
+    let cache_hit = server
+        .lookup_beatport(Parameters(LookupBeatportParams {
+            track_id: None,
+            artist: Some(track.artist.clone()),
+            title: Some(track.title.clone()),
+            force_refresh: Some(false),
+        }))
+        .await
+        .expect("lookup_beatport(force_refresh=false) should return cache");
+    let cache_hit_json = extract_json(&cache_hit);
+    assert_eq!(cache_hit_json["cache_hit"], true);
+    assert_eq!(cache_hit_json["genre"], "Sentinel Genre");
+
+    let refresh_err = server
+        .lookup_beatport(Parameters(LookupBeatportParams {
+            track_id: None,
+            artist: Some(track.artist.clone()),
+            title: Some(track.title.clone()),
+            force_refresh: Some(true),
+        }))
+        .await
+        .expect_err("force_refresh=true should bypass cache and attempt HTTP call");
+    assert!(
+        format!("{refresh_err}").contains("Beatport error"),
+        "force refresh should fail via offline HTTP path, got: {refresh_err}"
+    );
+}
+
+#[tokio::test]
+async fn enrich_tracks_beatport_schema_matches_individual_lookup() {
+    let Some((server, _store_dir)) =
+        create_real_server_with_temp_store(default_http_client_for_tests())
+    else {
+        eprintln!("Skipping: backup tarball not found (set REKORDBOX_TEST_BACKUP)");
+        return;
+    };
+
+    let candidates = sample_real_tracks(&server, 30);
+    if candidates.is_empty() {
+        eprintln!("Skipping: integration test needs candidate tracks from real DB");
+        return;
+    }
+
+    let mut selected_track: Option<crate::types::Track> = None;
+    for track in candidates.into_iter().take(10) {
+        let lookup = server
+            .lookup_beatport(Parameters(LookupBeatportParams {
+                track_id: None,
+                artist: Some(track.artist.clone()),
+                title: Some(track.title.clone()),
+                force_refresh: Some(true),
+            }))
+            .await;
+
+        let Ok(result) = lookup else {
+            continue;
+        };
+        let payload = extract_json(&result);
+        if payload
+            .get("genre")
+            .and_then(serde_json::Value::as_str)
+            .is_some()
+        {
+            selected_track = Some(track);
+            break;
+        }
+    }
+
+    let Some(track) = selected_track else {
+        eprintln!(
+            "Skipping: could not find a track with a successful Beatport match; \
+                 rerun when network/providers are available"
+        );
+        return;
+    };
+    let norm_artist = crate::normalize::normalize_for_matching(&track.artist);
+    let norm_title = crate::normalize::normalize_for_matching(&track.title);
+
+    let individual_cache = {
+        let store = server
+            .cache_store_conn()
+            .expect("internal store should be available");
+        store::get_enrichment(&store, "beatport", &norm_artist, &norm_title)
+            .expect("cache read should succeed")
+            .expect("individual lookup should have created cache entry")
+    };
+    let individual_json: serde_json::Value = serde_json::from_str(
+        individual_cache
+            .response_json
+            .as_deref()
+            .expect("individual beatport cache should contain JSON"),
+    )
+    .expect("individual beatport cache JSON should parse");
+    assert!(
+        individual_json
+            .get("genre")
+            .and_then(serde_json::Value::as_str)
+            .is_some(),
+        "individual beatport cache should have string 'genre'"
+    );
+    let individual_fields: HashSet<String> = individual_json
+        .as_object()
+        .expect("individual beatport cache should be object")
+        .keys()
+        .cloned()
+        .collect();
+
+    {
+        let store = server
+            .cache_store_conn()
+            .expect("internal store should be available");
+        store
+            .execute(
+                "DELETE FROM enrichment_cache
+                     WHERE provider = ?1 AND query_artist = ?2 AND query_title = ?3",
+                params!["beatport", &norm_artist, &norm_title],
+            )
+            .expect("cache clear should succeed");
+    }
+
+    let enrich_result = server
+        .enrich_tracks(Parameters(EnrichTracksParams {
+            filters: SearchFilterParams::default(),
+            track_ids: Some(vec![track.id.clone()]),
+            playlist_id: None,
+            max_tracks: Some(1),
+            offset: None,
+            providers: Some(vec![crate::types::Provider::Beatport]),
+            skip_cached: Some(false),
+            force_refresh: Some(true),
+            concurrency: None,
+        }))
+        .await
+        .expect("enrich_tracks should succeed for beatport provider");
+    let enrich_payload = extract_json(&enrich_result);
+    assert_eq!(enrich_payload["summary"]["total"], 1);
+
+    let batch_cache = {
+        let store = server
+            .cache_store_conn()
+            .expect("internal store should be available");
+        store::get_enrichment(&store, "beatport", &norm_artist, &norm_title)
+            .expect("cache read should succeed")
+            .expect("batch enrich should have created beatport cache entry")
+    };
+    let batch_json: serde_json::Value = serde_json::from_str(
+        batch_cache
+            .response_json
+            .as_deref()
+            .expect("batch beatport cache should contain JSON"),
+    )
+    .expect("batch beatport cache JSON should parse");
+    assert!(
+        batch_json
+            .get("genre")
+            .and_then(serde_json::Value::as_str)
+            .is_some(),
+        "batch beatport cache should have string 'genre'"
+    );
+    assert!(
+        batch_json.get("genres").is_none(),
+        "beatport cache should not be transformed into discogs-style 'genres' schema"
+    );
+
+    let batch_fields: HashSet<String> = batch_json
+        .as_object()
+        .expect("batch beatport cache should be object")
+        .keys()
+        .cloned()
+        .collect();
+    assert_eq!(
+        batch_fields, individual_fields,
+        "batch and individual beatport cache JSON should share the same schema"
+    );
+}
+
+#[tokio::test]
+#[ignore]
+async fn resolve_tracks_data_batch_consistency() {
+    let Some((server, _store_dir)) =
+        create_real_server_with_temp_store(default_http_client_for_tests())
+    else {
+        eprintln!("Skipping: backup tarball not found (set REKORDBOX_TEST_BACKUP)");
+        return;
+    };
+
+    let tracks = sample_real_tracks(&server, 5);
+    assert!(
+        !tracks.is_empty(),
+        "integration test needs tracks from real DB"
+    );
+    let track_ids: Vec<String> = tracks.iter().map(|t| t.id.clone()).collect();
+
+    let batch_result = server
+        .resolve_tracks_data(Parameters(ResolveTracksDataParams {
+            filters: SearchFilterParams::default(),
+            track_ids: Some(track_ids.clone()),
+            playlist_id: None,
+            max_tracks: Some(track_ids.len() as u32),
+            format: None,
+        }))
+        .await
+        .expect("batch resolve should succeed");
+    let batch_payload = extract_json(&batch_result);
+    let batch_items = batch_payload
+        .as_array()
+        .expect("batch resolve payload should be an array");
+    assert_eq!(
+        batch_items.len(),
+        track_ids.len(),
+        "batch resolve should return one entry per requested track"
+    );
+
+    let mut by_track_id: HashMap<String, serde_json::Value> = HashMap::new();
+    for item in batch_items {
+        let track_id = item
+            .get("track_id")
+            .and_then(serde_json::Value::as_str)
+            .expect("resolved track item should include track_id");
+        by_track_id.insert(track_id.to_string(), item.clone());
+    }
+
+    for track_id in &track_ids {
+        let single_result = server
+            .resolve_track_data(Parameters(ResolveTrackDataParams {
+                track_id: track_id.clone(),
+            }))
+            .await
+            .expect("single resolve should succeed");
+        let single_payload = extract_json(&single_result);
+        assert_eq!(
+            by_track_id
+                .get(track_id)
+                .expect("batch output should include every requested track"),
+            &single_payload,
+            "batch resolve output should match single-track resolve output"
+        );
+    }
+}
+
+#[test]
+fn golden_genres_fixture_is_well_formed() {
+    let entries = load_golden_genres_fixture();
+    assert!(
+        !entries.is_empty(),
+        "golden genres fixture should contain at least one entry"
+    );
+
+    let mut unique = HashSet::new();
+    for entry in &entries {
+        assert!(
+            !entry.artist.trim().is_empty(),
+            "fixture artist must be non-empty"
+        );
+        assert!(
+            !entry.title.trim().is_empty(),
+            "fixture title must be non-empty"
+        );
+        assert!(
+            !entry.notes.trim().is_empty(),
+            "fixture notes must be non-empty"
+        );
+        assert!(
+            genre::is_known_genre(&entry.expected_genre),
+            "expected_genre '{}' must be in taxonomy",
+            entry.expected_genre
+        );
+        assert!(
+            genre::canonical_genre_from_alias(&entry.expected_genre).is_none(),
+            "expected_genre '{}' must be canonical, not alias",
+            entry.expected_genre
+        );
+
+        let key = format!(
+            "{}::{}",
+            entry.artist.to_lowercase(),
+            entry.title.to_lowercase()
+        );
+        assert!(unique.insert(key), "duplicate (artist, title) in fixture");
+    }
+}
+
+#[test]
+#[ignore]
+fn golden_dataset_genre_accuracy() {
+    let entries = load_golden_genres_fixture();
+    let Some(conn) = db::open_real_db() else {
+        eprintln!("Skipping: backup tarball not found (set REKORDBOX_TEST_BACKUP)");
+        return;
+    };
+
+    let mut compared = 0usize;
+    let mut correct = 0usize;
+    let mut missing_tracks = Vec::new();
+    let mut no_genre = Vec::new();
+    let mut mismatches = Vec::new();
+
+    for entry in &entries {
+        let Some(track) = find_track_by_artist_and_title(&conn, &entry.artist, &entry.title) else {
+            missing_tracks.push(format!("{} - {}", entry.artist, entry.title));
+            continue;
+        };
+
+        if track.genre.trim().is_empty() {
+            no_genre.push(format!("{} - {}", entry.artist, entry.title));
+            continue;
+        }
+
+        compared += 1;
+        let actual = canonical_genre_name(&track.genre);
+        if actual.eq_ignore_ascii_case(&entry.expected_genre) {
+            correct += 1;
+        } else {
+            mismatches.push(format!(
+                "{} - {}: expected '{}', actual '{}' ({})",
+                entry.artist, entry.title, entry.expected_genre, actual, entry.notes
+            ));
+        }
+    }
+
+    let accuracy = if compared == 0 {
+        0.0
+    } else {
+        (correct as f64 / compared as f64) * 100.0
+    };
+    eprintln!(
+        "[integration] golden dataset: total={} compared={} correct={} accuracy={:.1}%",
+        entries.len(),
+        compared,
+        correct,
+        accuracy
+    );
+    if !missing_tracks.is_empty() {
+        eprintln!("[integration] missing tracks ({}):", missing_tracks.len());
+        for item in &missing_tracks {
+            eprintln!("  - {item}");
+        }
+    }
+    if !no_genre.is_empty() {
+        eprintln!(
+            "[integration] tracks with empty genre ({}):",
+            no_genre.len()
+        );
+        for item in &no_genre {
+            eprintln!("  - {item}");
+        }
+    }
+    if !mismatches.is_empty() {
+        eprintln!("[integration] mismatches ({}):", mismatches.len());
+        for item in &mismatches {
+            eprintln!("  - {item}");
+        }
+    }
+
+    assert!(
+        !missing_tracks.is_empty() || compared > 0,
+        "fixture should either report missing tracks or compare at least one track"
+    );
+}
+
+// --- resolve_single_track unit tests ---
+
+fn make_test_track(id: &str, genre: &str, bpm: f64, key: &str) -> crate::types::Track {
+    crate::types::Track {
+        id: id.to_string(),
+        title: format!("Track {id}"),
+        artist: "Test Artist".to_string(),
+        album: "Test Album".to_string(),
+        genre: genre.to_string(),
+        bpm,
+        key: key.to_string(),
+        rating: 3,
+        comments: "test comment".to_string(),
+        color: "Rose".to_string(),
+        color_code: 1,
+        label: "Test Label".to_string(),
+        remixer: "".to_string(),
+        year: 2023,
+        length: 300,
+        file_path: "/music/test.flac".to_string(),
+        play_count: 5,
+        bit_rate: 1411,
+        sample_rate: 44100,
+        file_kind: crate::types::FileKind::Flac,
+        date_added: "2023-01-15".to_string(),
+        my_tags: Vec::new(),
+        hot_cue_banks: Vec::new(),
+        related_track_lists: Vec::new(),
+        position: None,
+        cues: Vec::new(),
+        tempo_markers: Vec::new(),
+    }
+}
+
+#[test]
+fn resolve_single_track_rekordbox_only() {
+    let track = make_test_track("t1", "Deep House", 126.0, "Am");
+    let result = resolve_single_track(&track, None, None, None, None, false, None);
+
+    // Verify rekordbox section present
+    let rb = result
+        .get("rekordbox")
+        .expect("rekordbox section should exist");
+    assert_eq!(rb["title"], "Track t1");
+    assert_eq!(rb["artist"], "Test Artist");
+    assert_eq!(rb["genre"], "Deep House");
+    assert_eq!(rb["bpm"], 126.0);
+    assert_eq!(rb["key"], "Am");
+    assert_eq!(rb["duration_s"], 300);
+    assert_eq!(rb["year"], 2023);
+    assert_eq!(rb["rating"], 3);
+    assert_eq!(rb["label"], "Test Label");
+
+    // Null sections when no cache
+    assert!(
+        result["audio_analysis"].is_null(),
+        "audio_analysis should be null without cache"
+    );
+    assert!(
         result["discogs"].is_null(),
         "discogs should be null without cache"
     );
@@ -2524,6 +3733,7 @@ fn resolve_single_track_with_staged_changes() {
         comments: None,
         rating: Some(5),
         color: None,
+        ..Default::default()
     };
     let result = resolve_single_track(&track, None, None, None, None, false, Some(&staged));
 
@@ -2805,804 +4015,1996 @@ fn resolve_single_track_enrichment_no_match_returns_null() {
         created_at: "2024-01-01".to_string(),
     };
 
-    let result = resolve_single_track(&track, Some(&discogs_cache), None, None, None, false, None);
+    let result = resolve_single_track(&track, Some(&discogs_cache), None, None, None, false, None);
+
+    // discogs cached but no match -> null enrichment data, but data_completeness = true
+    assert!(
+        result["discogs"].is_null(),
+        "discogs with no response_json should be null"
+    );
+    assert_eq!(
+        result["data_completeness"]["discogs"], true,
+        "cache entry exists so completeness is true"
+    );
+}
+
+#[test]
+fn musical_key_to_camelot_converts_major_minor_and_flats() {
+    assert_eq!(
+        musical_key_to_camelot("Am").map(format_camelot).as_deref(),
+        Some("8A")
+    );
+    assert_eq!(
+        musical_key_to_camelot("C").map(format_camelot).as_deref(),
+        Some("8B")
+    );
+    assert_eq!(
+        musical_key_to_camelot("F#m").map(format_camelot).as_deref(),
+        Some("11A")
+    );
+    assert_eq!(
+        musical_key_to_camelot("Bb").map(format_camelot).as_deref(),
+        Some("6B")
+    );
+    assert_eq!(
+        musical_key_to_camelot("Dbm").map(format_camelot).as_deref(),
+        Some("12A")
+    );
+    assert_eq!(
+        key_to_camelot("8a").map(format_camelot).as_deref(),
+        Some("8A")
+    );
+    assert_eq!(musical_key_to_camelot("not-a-key"), None);
+}
+
+#[test]
+fn camelot_to_musical_round_trips_every_key() {
+    for letter in ['A', 'B'] {
+        for number in 1..=12 {
+            let key = parse_camelot_key(&format!("{number}{letter}")).unwrap();
+            assert_eq!(musical_key_to_camelot(&camelot_to_musical(key)), Some(key));
+        }
+    }
+    assert_eq!(camelot_to_musical(parse_camelot_key("3B").unwrap()), "Db");
+}
+
+#[test]
+fn key_consensus_flags_relative_and_fifth_confusions() {
+    use key_consensus::{KeyReading, KeyRelation, KeySource, key_consensus, key_relation};
+
+    let key = |raw: &str| key_to_camelot(raw).unwrap();
+    assert_eq!(key_relation(key("Am"), key("C")), KeyRelation::Relative);
+    assert_eq!(key_relation(key("Am"), key("Em")), KeyRelation::Fifth);
+    assert_eq!(key_relation(key("12A"), key("1A")), KeyRelation::Fifth);
+    assert_eq!(key_relation(key("Am"), key("F#")), KeyRelation::Unrelated);
+
+    // Rekordbox reads the relative minor; analysers and Beatport agree on C.
+    let consensus = key_consensus(vec![
+        KeyReading::new(KeySource::Rekordbox, "Am", None).unwrap(),
+        KeyReading::new(KeySource::Stratum, "8B", Some(0.9)).unwrap(),
+        KeyReading::new(KeySource::Essentia, "C", Some(0.8)).unwrap(),
+        KeyReading::new(KeySource::Beatport, "C Major", None).unwrap(),
+    ])
+    .unwrap();
+    assert_eq!(format_camelot(consensus.key), "8B");
+    assert_eq!(consensus.level, crate::types::MatchConfidence::High);
+    assert_eq!(consensus.corrected_key.as_deref(), Some("C"));
+    assert!(
+        consensus.explanation[1].contains("relative major/minor"),
+        "{:?}",
+        consensus.explanation
+    );
+
+    // A lone low-confidence analyser does not outvote Rekordbox.
+    let consensus = key_consensus(vec![
+        KeyReading::new(KeySource::Rekordbox, "Am", None).unwrap(),
+        KeyReading::new(KeySource::Stratum, "9A", Some(0.2)).unwrap(),
+    ])
+    .unwrap();
+    assert_eq!(format_camelot(consensus.key), "8A");
+    assert_eq!(consensus.corrected_key, None);
+    assert_eq!(
+        consensus
+            .disputed()
+            .map(|r| consensus.relation_of(r))
+            .collect::<Vec<_>>(),
+        vec![KeyRelation::Fifth]
+    );
+
+    // An even split is not a confident correction.
+    let consensus = key_consensus(vec![
+        KeyReading::new(KeySource::Stratum, "Am", Some(1.0)).unwrap(),
+        KeyReading::new(KeySource::Beatport, "F#", None).unwrap(),
+    ])
+    .unwrap();
+    assert_ne!(consensus.level, crate::types::MatchConfidence::High);
+    assert!(key_consensus(Vec::new()).is_none());
+}
+
+#[test]
+fn camelot_distance_scoring_handles_wrap_and_mode_shift() {
+    let wrap_up = score_key_axis(parse_camelot_key("12A"), parse_camelot_key("1A"));
+    assert_eq!(wrap_up.value, 0.9);
+    assert!(
+        wrap_up.label.contains("Camelot adjacent"),
+        "wrap-around up should be treated as +1"
+    );
+
+    let wrap_down = score_key_axis(parse_camelot_key("1A"), parse_camelot_key("12A"));
+    assert_eq!(wrap_down.value, 0.9);
+    assert!(
+        wrap_down.label.contains("Camelot adjacent"),
+        "wrap-around down should be treated as -1"
+    );
+
+    let mood_shift = score_key_axis(parse_camelot_key("6A"), parse_camelot_key("6B"));
+    assert_eq!(mood_shift.value, 0.8);
+
+    let diagonal = score_key_axis(parse_camelot_key("6A"), parse_camelot_key("7B"));
+    assert_eq!(diagonal.value, 0.55);
+    assert!(
+        diagonal.label.contains("Energy diagonal"),
+        "cross-letter ±1 should be Energy diagonal"
+    );
+}
+
+#[test]
+fn key_axis_covers_all_relation_types() {
+    // Perfect match
+    let perfect = score_key_axis(parse_camelot_key("8A"), parse_camelot_key("8A"));
+    assert_eq!(perfect.value, 1.0);
+    assert_eq!(perfect.label, "Perfect");
+
+    // Camelot adjacent (+1)
+    let adjacent = score_key_axis(parse_camelot_key("8A"), parse_camelot_key("9A"));
+    assert_eq!(adjacent.value, 0.9);
+    assert!(adjacent.label.contains("Camelot adjacent"));
+
+    // Mood shift (A↔B same number)
+    let mood = score_key_axis(parse_camelot_key("8A"), parse_camelot_key("8B"));
+    assert_eq!(mood.value, 0.8);
+    assert!(mood.label.contains("Mood shift"));
+
+    // Energy diagonal (±1 cross letter)
+    let diagonal = score_key_axis(parse_camelot_key("8A"), parse_camelot_key("9B"));
+    assert_eq!(diagonal.value, 0.55);
+    assert!(diagonal.label.contains("Energy diagonal"));
+
+    // Extended (±2 same letter)
+    let extended = score_key_axis(parse_camelot_key("8A"), parse_camelot_key("10A"));
+    assert_eq!(extended.value, 0.45);
+    assert!(extended.label.contains("Extended"));
+
+    // Clash (distant keys)
+    let clash = score_key_axis(parse_camelot_key("1A"), parse_camelot_key("6A"));
+    assert_eq!(clash.value, 0.1);
+    assert_eq!(clash.label, "Clash");
+}
+
+#[test]
+fn bpm_exponential_scoring_curve() {
+    // Continuous curve: exp(-0.019 * pct²)
+    // 0% → 1.0, monotonically decreasing
+
+    // <2% → "Seamless", value near 1.0
+    let seamless = score_bpm_axis(128.0, 129.5); // 1.17%
+    assert!(
+        seamless.value > 0.97,
+        "1.17% should score near 1.0, got {}",
+        seamless.value
+    );
+    assert!(seamless.label.contains("Seamless"));
+
+    // 2-4% → "Comfortable"
+    let comfortable = score_bpm_axis(130.0, 126.5); // 2.69%
+    assert!(
+        comfortable.value > 0.85 && comfortable.value < 0.95,
+        "2.69% should be ~0.87, got {}",
+        comfortable.value
+    );
+    assert!(comfortable.label.contains("Comfortable"));
+
+    // 4-6% → "Noticeable"
+    let noticeable = score_bpm_axis(120.0, 125.5); // 4.58%
+    assert!(
+        noticeable.value > 0.55 && noticeable.value < 0.75,
+        "4.58% should be ~0.65, got {}",
+        noticeable.value
+    );
+    assert!(noticeable.label.contains("Noticeable"));
+
+    // 6-9% → "Creative transition needed"
+    let creative = score_bpm_axis(128.0, 138.0); // 7.81%
+    assert!(
+        creative.value > 0.25 && creative.value < 0.45,
+        "7.81% should be ~0.33, got {}",
+        creative.value
+    );
+    assert!(creative.label.contains("Creative transition needed"));
+
+    // ≥9% → "Jarring"
+    let jarring = score_bpm_axis(120.0, 132.0); // 10.0%
+    assert!(
+        jarring.value < 0.20,
+        "10% should be near 0, got {}",
+        jarring.value
+    );
+    assert!(jarring.label.contains("Jarring"));
+
+    // Guard: from_bpm <= 0 → 0.5 "Unknown BPM"
+    let unknown = score_bpm_axis(0.0, 128.0);
+    assert_eq!(unknown.value, 0.5);
+    assert_eq!(unknown.label, "Unknown BPM");
+
+    // Monotonicity: closer BPM always scores higher
+    let at_0 = score_bpm_axis(128.0, 128.0);
+    let at_1 = score_bpm_axis(128.0, 129.28); // ~1%
+    let at_3 = score_bpm_axis(128.0, 131.84); // ~3%
+    let at_5 = score_bpm_axis(128.0, 134.4); // ~5%
+    let at_8 = score_bpm_axis(128.0, 138.24); // ~8%
+    assert!(at_0.value > at_1.value);
+    assert!(at_1.value > at_3.value);
+    assert!(at_3.value > at_5.value);
+    assert!(at_5.value > at_8.value);
+}
+
+#[test]
+fn transpose_camelot_key_circle_of_fifths() {
+    // +1 semitone = +7 positions on Camelot wheel
+    // 8A + 1 semi → (8-1+7)%12+1 = 14%12+1 = 3 → 3A
+    let k8a = parse_camelot_key("8A").unwrap();
+    let up1 = transpose_camelot_key(k8a, 1);
+    assert_eq!(format_camelot(up1), "3A");
+
+    // Full octave (+12 semitones) = identity
+    let full = transpose_camelot_key(k8a, 12);
+    assert_eq!(format_camelot(full), "8A");
+
+    // -1 semitone from 8A → (8-1 + (-1*7)%12=5) → (7+5)%12=0 → 12 → but let's compute:
+    // steps = ((-1 % 12) * 7).rem_euclid(12) = (-7).rem_euclid(12) = 5
+    // (8-1+5)%12+1 = 12%12+1 = 1 → 1A
+    let down1 = transpose_camelot_key(k8a, -1);
+    assert_eq!(format_camelot(down1), "1A");
+
+    // Round-trip: +1 then -1
+    let round_trip = transpose_camelot_key(up1, -1);
+    assert_eq!(format_camelot(round_trip), "8A");
+
+    // Letter is preserved
+    let k5b = parse_camelot_key("5B").unwrap();
+    let up2 = transpose_camelot_key(k5b, 2);
+    assert!(
+        format_camelot(up2).ends_with('B'),
+        "letter should be preserved through transposition"
+    );
+}
+
+#[test]
+fn master_tempo_off_changes_key_scoring() {
+    let from = TrackProfile {
+        track: crate::types::Track {
+            id: "mt-from".to_string(),
+            title: "MT From".to_string(),
+            artist: "Test".to_string(),
+            album: String::new(),
+            genre: "House".to_string(),
+            key: "Am".to_string(),
+            bpm: 128.0,
+            rating: 0,
+            comments: String::new(),
+            color: String::new(),
+            color_code: 0,
+            label: String::new(),
+            remixer: String::new(),
+            year: 0,
+            length: 300,
+            file_path: "/tmp/mt-from.flac".to_string(),
+            play_count: 0,
+            bit_rate: 1411,
+            sample_rate: 44100,
+            file_kind: crate::types::FileKind::Flac,
+            date_added: String::new(),
+            my_tags: Vec::new(),
+            hot_cue_banks: Vec::new(),
+            related_track_lists: Vec::new(),
+            position: None,
+            cues: Vec::new(),
+            tempo_markers: Vec::new(),
+        },
+        camelot_key: parse_camelot_key("8A"),
+        key_display: "8A".to_string(),
+        bpm: 128.0,
+        energy: 0.6,
+        brightness: None,
+        rhythm_regularity: None,
+        loudness_range: None,
+        canonical_genre: Some("House".to_string()),
+        genre_family: GenreFamily::House,
+        opening: None,
+        closing: None,
+        played_before: HashMap::new(),
+    };
+
+    // to track at 135 BPM → when played at 128, pitch drops.
+    // shift = round(12 * log2(128/135)) = round(12 * -0.0758) = round(-0.91) = -1
+    // So effective key of to-track shifts -1 semitone from its natural key
+    let mut to = from.clone();
+    to.track.id = "mt-to".to_string();
+    to.bpm = 135.0;
+    to.camelot_key = parse_camelot_key("8A"); // same key naturally
 
-    // discogs cached but no match -> null enrichment data, but data_completeness = true
-    assert!(
-        result["discogs"].is_null(),
-        "discogs with no response_json should be null"
+    // With master_tempo ON: same key → perfect (1.0)
+    let scores_mt_on = score_transition_profiles(
+        &from,
+        &to,
+        None,
+        None,
+        SequencingPriority::Balanced,
+        true,
+        None,
+        &ScoringContext::default(),
+        None,
     );
     assert_eq!(
-        result["data_completeness"]["discogs"], true,
-        "cache entry exists so completeness is true"
+        scores_mt_on.key.value, 1.0,
+        "master_tempo on: same key should be perfect"
     );
-}
+    assert_eq!(scores_mt_on.pitch_shift_semitones, 0);
+    assert!(scores_mt_on.effective_to_key.is_none());
 
-#[test]
-fn musical_key_to_camelot_converts_major_minor_and_flats() {
-    assert_eq!(
-        musical_key_to_camelot("Am").map(format_camelot).as_deref(),
-        Some("8A")
-    );
-    assert_eq!(
-        musical_key_to_camelot("C").map(format_camelot).as_deref(),
-        Some("8B")
-    );
-    assert_eq!(
-        musical_key_to_camelot("F#m").map(format_camelot).as_deref(),
-        Some("11A")
+    // With master_tempo OFF: pitch shift changes effective key
+    let scores_mt_off = score_transition_profiles(
+        &from,
+        &to,
+        None,
+        None,
+        SequencingPriority::Balanced,
+        false,
+        None,
+        &ScoringContext::default(),
+        None,
     );
     assert_eq!(
-        musical_key_to_camelot("Bb").map(format_camelot).as_deref(),
-        Some("6B")
+        scores_mt_off.pitch_shift_semitones, -1,
+        "128→135 BPM should yield -1 semitone shift"
     );
     assert_eq!(
-        musical_key_to_camelot("Dbm").map(format_camelot).as_deref(),
-        Some("12A")
+        scores_mt_off.effective_to_key,
+        Some("1A".to_string()),
+        "8A shifted -1 semitone = 1A"
     );
+    assert_eq!(scores_mt_off.key.value, 0.1, "8A→1A is a clash (score 0.1)");
     assert_eq!(
-        key_to_camelot("8a").map(format_camelot).as_deref(),
-        Some("8A")
+        scores_mt_on.key.value, 1.0,
+        "master_tempo on: same key is perfect (1.0)"
     );
-    assert_eq!(musical_key_to_camelot("not-a-key"), None);
+}
+
+fn make_test_profile(id: &str, key: &str, bpm: f64, energy: f64, genre: &str) -> TrackProfile {
+    TrackProfile {
+        track: crate::types::Track {
+            id: id.to_string(),
+            title: id.to_string(),
+            artist: "Test".to_string(),
+            album: String::new(),
+            genre: genre.to_string(),
+            key: key.to_string(),
+            bpm,
+            rating: 0,
+            comments: String::new(),
+            color: String::new(),
+            color_code: 0,
+            label: String::new(),
+            remixer: String::new(),
+            year: 0,
+            length: 300,
+            file_path: format!("/tmp/{id}.flac"),
+            play_count: 0,
+            bit_rate: 1411,
+            sample_rate: 44100,
+            file_kind: crate::types::FileKind::Flac,
+            date_added: String::new(),
+            my_tags: Vec::new(),
+            hot_cue_banks: Vec::new(),
+            related_track_lists: Vec::new(),
+            position: None,
+            cues: Vec::new(),
+            tempo_markers: Vec::new(),
+        },
+        camelot_key: parse_camelot_key(key),
+        key_display: key.to_string(),
+        bpm,
+        energy,
+        brightness: None,
+        rhythm_regularity: None,
+        loudness_range: None,
+        canonical_genre: Some(genre.to_string()),
+        genre_family: genre_family_for(genre),
+        opening: None,
+        closing: None,
+        played_before: HashMap::new(),
+    }
 }
 
 #[test]
-fn camelot_distance_scoring_handles_wrap_and_mode_shift() {
-    let wrap_up = score_key_axis(parse_camelot_key("12A"), parse_camelot_key("1A"));
-    assert_eq!(wrap_up.value, 0.9);
-    assert!(
-        wrap_up.label.contains("Camelot adjacent"),
-        "wrap-around up should be treated as +1"
-    );
+fn harmonic_style_conservative_penalizes_poor_transitions() {
+    // Two tracks where key score = 0.55 (energy diagonal: 8A → 9B)
+    let from = make_test_profile("hs-from", "8A", 128.0, 0.7, "House");
+    let to = make_test_profile("hs-to", "9B", 128.0, 0.7, "House");
 
-    let wrap_down = score_key_axis(parse_camelot_key("1A"), parse_camelot_key("12A"));
-    assert_eq!(wrap_down.value, 0.9);
-    assert!(
-        wrap_down.label.contains("Camelot adjacent"),
-        "wrap-around down should be treated as -1"
+    // Conservative + peak phase + key=0.55 (< 0.8 threshold) → penalty
+    let conservative = score_transition_profiles(
+        &from,
+        &to,
+        Some(EnergyPhase::Peak),
+        Some(EnergyPhase::Peak),
+        SequencingPriority::Balanced,
+        true,
+        Some(HarmonicMixingStyle::Conservative),
+        &ScoringContext::default(),
+        None,
     );
 
-    let mood_shift = score_key_axis(parse_camelot_key("6A"), parse_camelot_key("6B"));
-    assert_eq!(mood_shift.value, 0.8);
+    // Same without harmonic_style → no penalty (baseline)
+    let baseline = score_transition_profiles(
+        &from,
+        &to,
+        Some(EnergyPhase::Peak),
+        Some(EnergyPhase::Peak),
+        SequencingPriority::Balanced,
+        true,
+        None,
+        &ScoringContext::default(),
+        None,
+    );
 
-    let diagonal = score_key_axis(parse_camelot_key("6A"), parse_camelot_key("7B"));
-    assert_eq!(diagonal.value, 0.55);
     assert!(
-        diagonal.label.contains("Energy diagonal"),
-        "cross-letter ±1 should be Energy diagonal"
+        conservative.composite < baseline.composite,
+        "conservative should penalize key=0.55 at peak phase"
+    );
+    // Penalty is 0.1x for Conservative style
+    let expected = baseline.composite * 0.1;
+    assert!(
+        (conservative.composite - expected).abs() < 1e-9,
+        "conservative penalty should be 0.1x; got {} vs expected {}",
+        conservative.composite,
+        expected
     );
-}
-
-#[test]
-fn key_axis_covers_all_relation_types() {
-    // Perfect match
-    let perfect = score_key_axis(parse_camelot_key("8A"), parse_camelot_key("8A"));
-    assert_eq!(perfect.value, 1.0);
-    assert_eq!(perfect.label, "Perfect");
-
-    // Camelot adjacent (+1)
-    let adjacent = score_key_axis(parse_camelot_key("8A"), parse_camelot_key("9A"));
-    assert_eq!(adjacent.value, 0.9);
-    assert!(adjacent.label.contains("Camelot adjacent"));
-
-    // Mood shift (A↔B same number)
-    let mood = score_key_axis(parse_camelot_key("8A"), parse_camelot_key("8B"));
-    assert_eq!(mood.value, 0.8);
-    assert!(mood.label.contains("Mood shift"));
-
-    // Energy diagonal (±1 cross letter)
-    let diagonal = score_key_axis(parse_camelot_key("8A"), parse_camelot_key("9B"));
-    assert_eq!(diagonal.value, 0.55);
-    assert!(diagonal.label.contains("Energy diagonal"));
 
-    // Extended (±2 same letter)
-    let extended = score_key_axis(parse_camelot_key("8A"), parse_camelot_key("10A"));
-    assert_eq!(extended.value, 0.45);
-    assert!(extended.label.contains("Extended"));
+    // Adventurous + peak phase + key=0.55 → no penalty (threshold is 0.1)
+    let adventurous = score_transition_profiles(
+        &from,
+        &to,
+        Some(EnergyPhase::Peak),
+        Some(EnergyPhase::Peak),
+        SequencingPriority::Balanced,
+        true,
+        Some(HarmonicMixingStyle::Adventurous),
+        &ScoringContext::default(),
+        None,
+    );
+    assert_eq!(
+        adventurous.composite, baseline.composite,
+        "adventurous should not penalize key=0.55 at peak phase"
+    );
 
-    // Clash (distant keys)
-    let clash = score_key_axis(parse_camelot_key("1A"), parse_camelot_key("6A"));
-    assert_eq!(clash.value, 0.1);
-    assert_eq!(clash.label, "Clash");
+    // Balanced + build phase + key=0.45 (Extended: 8A→10A) → threshold is 0.45, exactly at threshold
+    let from2 = make_test_profile("hs-from2", "8A", 128.0, 0.5, "House");
+    let to2 = make_test_profile("hs-to2", "10A", 128.0, 0.6, "House");
+    let balanced_build = score_transition_profiles(
+        &from2,
+        &to2,
+        Some(EnergyPhase::Build),
+        Some(EnergyPhase::Build),
+        SequencingPriority::Balanced,
+        true,
+        Some(HarmonicMixingStyle::Balanced),
+        &ScoringContext::default(),
+        None,
+    );
+    let baseline_build = score_transition_profiles(
+        &from2,
+        &to2,
+        Some(EnergyPhase::Build),
+        Some(EnergyPhase::Build),
+        SequencingPriority::Balanced,
+        true,
+        None,
+        &ScoringContext::default(),
+        None,
+    );
+    // key=0.45, threshold=0.45 → NOT below threshold → no penalty
+    assert_eq!(
+        balanced_build.composite, baseline_build.composite,
+        "balanced should not penalize key=0.45 at build phase (exactly at threshold)"
+    );
 }
 
 #[test]
-fn bpm_exponential_scoring_curve() {
-    // Continuous curve: exp(-0.019 * pct²)
-    // 0% → 1.0, monotonically decreasing
+fn harmonic_style_adventurous_is_phase_dependent() {
+    // Clash pair: 8A → 2A gives key=0.1
+    let from = make_test_profile("adv-from", "8A", 128.0, 0.7, "House");
+    let to = make_test_profile("adv-to", "2A", 128.0, 0.7, "House");
 
-    // <2% → "Seamless", value near 1.0
-    let seamless = score_bpm_axis(128.0, 129.5); // 1.17%
-    assert!(
-        seamless.value > 0.97,
-        "1.17% should score near 1.0, got {}",
-        seamless.value
+    // Adventurous at Peak: threshold=0.1, key=0.1 → NOT below → no penalty
+    let adv_peak = score_transition_profiles(
+        &from,
+        &to,
+        Some(EnergyPhase::Peak),
+        Some(EnergyPhase::Peak),
+        SequencingPriority::Balanced,
+        true,
+        Some(HarmonicMixingStyle::Adventurous),
+        &ScoringContext::default(),
+        None,
     );
-    assert!(seamless.label.contains("Seamless"));
-
-    // 2-4% → "Comfortable"
-    let comfortable = score_bpm_axis(130.0, 126.5); // 2.69%
-    assert!(
-        comfortable.value > 0.85 && comfortable.value < 0.95,
-        "2.69% should be ~0.87, got {}",
-        comfortable.value
+    let baseline_peak = score_transition_profiles(
+        &from,
+        &to,
+        Some(EnergyPhase::Peak),
+        Some(EnergyPhase::Peak),
+        SequencingPriority::Balanced,
+        true,
+        None,
+        &ScoringContext::default(),
+        None,
     );
-    assert!(comfortable.label.contains("Comfortable"));
-
-    // 4-6% → "Noticeable"
-    let noticeable = score_bpm_axis(120.0, 125.5); // 4.58%
-    assert!(
-        noticeable.value > 0.55 && noticeable.value < 0.75,
-        "4.58% should be ~0.65, got {}",
-        noticeable.value
+    assert_eq!(
+        adv_peak.composite, baseline_peak.composite,
+        "adventurous at peak should not penalize key=0.1 (threshold is 0.1)"
     );
-    assert!(noticeable.label.contains("Noticeable"));
 
-    // 6-9% → "Creative transition needed"
-    let creative = score_bpm_axis(128.0, 138.0); // 7.81%
+    // Adventurous at Warmup: threshold=0.45, key=0.1 → below → penalty
+    let adv_warmup = score_transition_profiles(
+        &from,
+        &to,
+        Some(EnergyPhase::Warmup),
+        Some(EnergyPhase::Warmup),
+        SequencingPriority::Balanced,
+        true,
+        Some(HarmonicMixingStyle::Adventurous),
+        &ScoringContext::default(),
+        None,
+    );
+    let baseline_warmup = score_transition_profiles(
+        &from,
+        &to,
+        Some(EnergyPhase::Warmup),
+        Some(EnergyPhase::Warmup),
+        SequencingPriority::Balanced,
+        true,
+        None,
+        &ScoringContext::default(),
+        None,
+    );
     assert!(
-        creative.value > 0.25 && creative.value < 0.45,
-        "7.81% should be ~0.33, got {}",
-        creative.value
+        adv_warmup.composite < baseline_warmup.composite,
+        "adventurous at warmup should penalize key=0.1 (threshold is 0.45)"
     );
-    assert!(creative.label.contains("Creative transition needed"));
-
-    // ≥9% → "Jarring"
-    let jarring = score_bpm_axis(120.0, 132.0); // 10.0%
+    // Adventurous uses 0.5x penalty factor
+    let expected = baseline_warmup.composite * 0.5;
     assert!(
-        jarring.value < 0.20,
-        "10% should be near 0, got {}",
-        jarring.value
+        (adv_warmup.composite - expected).abs() < 1e-9,
+        "adventurous penalty should be 0.5x; got {} vs expected {}",
+        adv_warmup.composite,
+        expected
     );
-    assert!(jarring.label.contains("Jarring"));
-
-    // Guard: from_bpm <= 0 → 0.5 "Unknown BPM"
-    let unknown = score_bpm_axis(0.0, 128.0);
-    assert_eq!(unknown.value, 0.5);
-    assert_eq!(unknown.label, "Unknown BPM");
 
-    // Monotonicity: closer BPM always scores higher
-    let at_0 = score_bpm_axis(128.0, 128.0);
-    let at_1 = score_bpm_axis(128.0, 129.28); // ~1%
-    let at_3 = score_bpm_axis(128.0, 131.84); // ~3%
-    let at_5 = score_bpm_axis(128.0, 134.4); // ~5%
-    let at_8 = score_bpm_axis(128.0, 138.24); // ~8%
-    assert!(at_0.value > at_1.value);
-    assert!(at_1.value > at_3.value);
-    assert!(at_3.value > at_5.value);
-    assert!(at_5.value > at_8.value);
+    // Conservative is phase-independent: always 0.8 threshold
+    let cons_peak = score_transition_profiles(
+        &from,
+        &to,
+        Some(EnergyPhase::Peak),
+        Some(EnergyPhase::Peak),
+        SequencingPriority::Balanced,
+        true,
+        Some(HarmonicMixingStyle::Conservative),
+        &ScoringContext::default(),
+        None,
+    );
+    let cons_warmup = score_transition_profiles(
+        &from,
+        &to,
+        Some(EnergyPhase::Warmup),
+        Some(EnergyPhase::Warmup),
+        SequencingPriority::Balanced,
+        true,
+        Some(HarmonicMixingStyle::Conservative),
+        &ScoringContext::default(),
+        None,
+    );
+    // Both should be penalized (key=0.1 < 0.8)
+    assert!(cons_peak.composite < baseline_peak.composite);
+    assert!(cons_warmup.composite < baseline_warmup.composite);
 }
 
 #[test]
-fn transpose_camelot_key_circle_of_fifths() {
-    // +1 semitone = +7 positions on Camelot wheel
-    // 8A + 1 semi → (8-1+7)%12+1 = 14%12+1 = 3 → 3A
-    let k8a = parse_camelot_key("8A").unwrap();
-    let up1 = transpose_camelot_key(k8a, 1);
-    assert_eq!(format_camelot(up1), "3A");
+fn composite_scoring_changes_by_priority_axis() {
+    let approx = |left: f64, right: f64| (left - right).abs() < 1e-9;
 
-    // Full octave (+12 semitones) = identity
-    let full = transpose_camelot_key(k8a, 12);
-    assert_eq!(format_camelot(full), "8A");
+    assert!(approx(
+        composite_score(
+            1.0,
+            0.0,
+            0.0,
+            0.0,
+            Some(0.0),
+            Some(0.0),
+            SequencingPriority::Balanced
+        ),
+        0.30
+    ));
+    assert!(approx(
+        composite_score(
+            1.0,
+            0.0,
+            0.0,
+            0.0,
+            Some(0.0),
+            Some(0.0),
+            SequencingPriority::Harmonic
+        ),
+        0.48
+    ));
+    assert!(approx(
+        composite_score(
+            1.0,
+            0.0,
+            0.0,
+            0.0,
+            Some(0.0),
+            Some(0.0),
+            SequencingPriority::Energy
+        ),
+        0.12
+    ));
+    assert!(approx(
+        composite_score(
+            1.0,
+            0.0,
+            0.0,
+            0.0,
+            Some(0.0),
+            Some(0.0),
+            SequencingPriority::Genre
+        ),
+        0.18
+    ));
 
-    // -1 semitone from 8A → (8-1 + (-1*7)%12=5) → (7+5)%12=0 → 12 → but let's compute:
-    // steps = ((-1 % 12) * 7).rem_euclid(12) = (-7).rem_euclid(12) = 5
-    // (8-1+5)%12+1 = 12%12+1 = 1 → 1A
-    let down1 = transpose_camelot_key(k8a, -1);
-    assert_eq!(format_camelot(down1), "1A");
+    assert!(approx(
+        composite_score(
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+            Some(0.0),
+            Some(0.0),
+            SequencingPriority::Balanced
+        ),
+        0.17
+    ));
+    assert!(approx(
+        composite_score(
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+            Some(0.0),
+            Some(0.0),
+            SequencingPriority::Genre
+        ),
+        0.38
+    ));
 
-    // Round-trip: +1 then -1
-    let round_trip = transpose_camelot_key(up1, -1);
-    assert_eq!(format_camelot(round_trip), "8A");
+    assert!(approx(
+        composite_score(1.0, 0.0, 0.0, 0.0, None, None, SequencingPriority::Balanced),
+        0.30 / 0.85
+    ));
+}
 
-    // Letter is preserved
-    let k5b = parse_camelot_key("5B").unwrap();
-    let up2 = transpose_camelot_key(k5b, 2);
-    assert!(
-        format_camelot(up2).ends_with('B'),
-        "letter should be preserved through transposition"
+#[test]
+fn score_genre_axis_treats_missing_genre_as_neutral() {
+    let unknown_source = score_genre_axis(
+        None,
+        Some("House"),
+        GenreFamily::Other,
+        GenreFamily::House,
+        0,
+    );
+    assert_eq!(unknown_source.value, 0.5);
+    assert_eq!(unknown_source.label, "Unknown genre");
+
+    let unknown_destination = score_genre_axis(
+        Some("House"),
+        None,
+        GenreFamily::House,
+        GenreFamily::Other,
+        0,
     );
+    assert_eq!(unknown_destination.value, 0.5);
+    assert_eq!(unknown_destination.label, "Unknown genre");
 }
 
 #[test]
-fn master_tempo_off_changes_key_scoring() {
-    let from = TrackProfile {
-        track: crate::types::Track {
-            id: "mt-from".to_string(),
-            title: "MT From".to_string(),
-            artist: "Test".to_string(),
-            album: String::new(),
-            genre: "House".to_string(),
-            key: "Am".to_string(),
-            bpm: 128.0,
-            rating: 0,
-            comments: String::new(),
-            color: String::new(),
-            color_code: 0,
-            label: String::new(),
-            remixer: String::new(),
-            year: 0,
-            length: 300,
-            file_path: "/tmp/mt-from.flac".to_string(),
-            play_count: 0,
-            bit_rate: 1411,
-            sample_rate: 44100,
-            file_kind: crate::types::FileKind::Flac,
-            date_added: String::new(),
-            position: None,
-        },
-        camelot_key: parse_camelot_key("8A"),
-        key_display: "8A".to_string(),
-        bpm: 128.0,
-        energy: 0.6,
-        brightness: None,
-        rhythm_regularity: None,
-        loudness_range: None,
-        canonical_genre: Some("House".to_string()),
-        genre_family: GenreFamily::House,
-    };
-
-    // to track at 135 BPM → when played at 128, pitch drops.
-    // shift = round(12 * log2(128/135)) = round(12 * -0.0758) = round(-0.91) = -1
-    // So effective key of to-track shifts -1 semitone from its natural key
-    let mut to = from.clone();
-    to.track.id = "mt-to".to_string();
-    to.bpm = 135.0;
-    to.camelot_key = parse_camelot_key("8A"); // same key naturally
+fn genre_stickiness_bonus_and_penalty() {
+    let approx = |a: f64, b: f64| (a - b).abs() < 1e-9;
 
-    // With master_tempo ON: same key → perfect (1.0)
-    let scores_mt_on = score_transition_profiles(
-        &from,
-        &to,
-        None,
-        None,
-        SequencingPriority::Balanced,
-        true,
-        None,
-        &ScoringContext::default(),
-        None,
+    // Streak bonus: same family, run_length=3 (< 5) → +0.1
+    let bonus = score_genre_axis(
+        Some("Deep House"),
+        Some("Tech House"),
+        GenreFamily::House,
+        GenreFamily::House,
+        3,
     );
-    assert_eq!(
-        scores_mt_on.key.value, 1.0,
-        "master_tempo on: same key should be perfect"
+    assert!(
+        approx(bonus.value, 0.8),
+        "0.7 + 0.1 streak bonus; got {}",
+        bonus.value
     );
-    assert_eq!(scores_mt_on.pitch_shift_semitones, 0);
-    assert!(scores_mt_on.effective_to_key.is_none());
+    assert!(bonus.label.contains("streak bonus"));
 
-    // With master_tempo OFF: pitch shift changes effective key
-    let scores_mt_off = score_transition_profiles(
-        &from,
-        &to,
-        None,
-        None,
-        SequencingPriority::Balanced,
-        false,
-        None,
-        &ScoringContext::default(),
-        None,
+    // No bonus at run=5 (cap)
+    let no_bonus = score_genre_axis(
+        Some("Deep House"),
+        Some("Tech House"),
+        GenreFamily::House,
+        GenreFamily::House,
+        5,
     );
-    assert_eq!(
-        scores_mt_off.pitch_shift_semitones, -1,
-        "128→135 BPM should yield -1 semitone shift"
+    assert_eq!(no_bonus.value, 0.7);
+    assert!(!no_bonus.label.contains("streak bonus"));
+
+    // Early switch penalty: different family, run_length=1 (< 2) → -0.1
+    let penalty = score_genre_axis(
+        Some("House"),
+        Some("Drum & Bass"),
+        GenreFamily::House,
+        GenreFamily::Bass,
+        1,
     );
-    assert_eq!(
-        scores_mt_off.effective_to_key,
-        Some("1A".to_string()),
-        "8A shifted -1 semitone = 1A"
+    assert!(
+        approx(penalty.value, 0.2),
+        "0.3 - 0.1 early switch penalty; got {}",
+        penalty.value
     );
-    assert_eq!(scores_mt_off.key.value, 0.1, "8A→1A is a clash (score 0.1)");
-    assert_eq!(
-        scores_mt_on.key.value, 1.0,
-        "master_tempo on: same key is perfect (1.0)"
+    assert!(penalty.label.contains("early switch penalty"));
+
+    // No penalty at run=2
+    let no_penalty = score_genre_axis(
+        Some("House"),
+        Some("Drum & Bass"),
+        GenreFamily::House,
+        GenreFamily::Bass,
+        2,
     );
-}
+    assert_eq!(no_penalty.value, 0.3);
+    assert!(!no_penalty.label.contains("early switch penalty"));
 
-fn make_test_profile(id: &str, key: &str, bpm: f64, energy: f64, genre: &str) -> TrackProfile {
-    TrackProfile {
-        track: crate::types::Track {
-            id: id.to_string(),
-            title: id.to_string(),
-            artist: "Test".to_string(),
-            album: String::new(),
-            genre: genre.to_string(),
-            key: key.to_string(),
-            bpm,
-            rating: 0,
-            comments: String::new(),
-            color: String::new(),
-            color_code: 0,
-            label: String::new(),
-            remixer: String::new(),
-            year: 0,
-            length: 300,
-            file_path: format!("/tmp/{id}.flac"),
-            play_count: 0,
-            bit_rate: 1411,
-            sample_rate: 44100,
-            file_kind: crate::types::FileKind::Flac,
-            date_added: String::new(),
-            position: None,
-        },
-        camelot_key: parse_camelot_key(key),
-        key_display: key.to_string(),
-        bpm,
-        energy,
-        brightness: None,
-        rhythm_regularity: None,
-        loudness_range: None,
-        canonical_genre: Some(genre.to_string()),
-        genre_family: genre_family_for(genre),
-    }
+    // No bonus at run=0 (first transition)
+    let first = score_genre_axis(
+        Some("House"),
+        Some("Tech House"),
+        GenreFamily::House,
+        GenreFamily::House,
+        0,
+    );
+    assert_eq!(first.value, 0.7);
+    assert!(!first.label.contains("streak bonus"));
 }
 
 #[test]
-fn harmonic_style_conservative_penalizes_poor_transitions() {
-    // Two tracks where key score = 0.55 (energy diagonal: 8A → 9B)
-    let from = make_test_profile("hs-from", "8A", 128.0, 0.7, "House");
-    let to = make_test_profile("hs-to", "9B", 128.0, 0.7, "House");
+fn bpm_trajectory_drift_penalty() {
+    use std::collections::HashMap;
 
-    // Conservative + peak phase + key=0.55 (< 0.8 threshold) → penalty
-    let conservative = score_transition_profiles(
-        &from,
-        &to,
-        Some(EnergyPhase::Peak),
-        Some(EnergyPhase::Peak),
-        SequencingPriority::Balanced,
+    // Pool: start at 128 BPM, candidates at 130 (+1.56%) and 145 (+13.28%)
+    // With bpm_drift_pct=3.0 and target_tracks=3 (divisor = 2):
+    //   position 1: budget_pct = 3.0 * (1/2) = 1.5% → budget_bpm = 128 * 1.5% = 1.92
+    //   130 drifts 2 (>1.92) → penalized; 145 drifts 17 (>1.92) → penalized
+    // With bpm_drift_pct=6.0 and target_tracks=3 (divisor = 2):
+    //   position 1: budget_pct = 6.0 * (1/2) = 3.0% → budget_bpm = 128 * 3% = 3.84
+    //   130 drifts 2 (<=3.84) → no penalty; 145 drifts 17 (>3.84) → penalized
+    let start = make_test_profile("bpm-start", "8A", 128.0, 0.7, "House");
+    let close = make_test_profile("bpm-close", "8A", 130.0, 0.7, "House");
+    let far = make_test_profile("bpm-far", "8A", 145.0, 0.7, "House");
+
+    let mut profiles: HashMap<String, TrackProfile> = HashMap::new();
+    profiles.insert("bpm-start".to_string(), start);
+    profiles.insert("bpm-close".to_string(), close);
+    profiles.insert("bpm-far".to_string(), far);
+
+    // 3% limit: both candidates penalized at position 1 (budget 1.92 BPM)
+    // but close (drift 2) is barely over while far (drift 17) is way over
+    // close still wins on composite even with penalty
+    let tight = build_candidate_plan(
+        &profiles,
+        "bpm-start",
+        3,
+        &[EnergyPhase::Build, EnergyPhase::Build, EnergyPhase::Build],
+        SequencingPriority::Harmonic,
+        0,
+        true,
+        None,
+        3.0,
+        None,
+    );
+    assert_eq!(tight.ordered_ids[1], "bpm-close");
+
+    // 6% limit (default): close (1.56%) within budget, far (13.28%) penalized
+    let moderate = build_candidate_plan(
+        &profiles,
+        "bpm-start",
+        3,
+        &[EnergyPhase::Build, EnergyPhase::Build, EnergyPhase::Build],
+        SequencingPriority::Harmonic,
+        0,
         true,
-        Some(HarmonicMixingStyle::Conservative),
-        &ScoringContext::default(),
+        None,
+        6.0,
         None,
     );
+    assert_eq!(moderate.ordered_ids[1], "bpm-close");
+    // Far still included (only penalized, not excluded)
+    assert!(moderate.ordered_ids.contains(&"bpm-far".to_string()));
 
-    // Same without harmonic_style → no penalty (baseline)
-    let baseline = score_transition_profiles(
-        &from,
-        &to,
-        Some(EnergyPhase::Peak),
-        Some(EnergyPhase::Peak),
-        SequencingPriority::Balanced,
+    // Very generous limit: no penalty for either
+    let generous = build_candidate_plan(
+        &profiles,
+        "bpm-start",
+        3,
+        &[EnergyPhase::Build, EnergyPhase::Build, EnergyPhase::Build],
+        SequencingPriority::Harmonic,
+        0,
         true,
         None,
-        &ScoringContext::default(),
+        50.0,
         None,
     );
+    assert_eq!(generous.ordered_ids[1], "bpm-close");
+    assert!(generous.ordered_ids.contains(&"bpm-far".to_string()));
+}
 
-    assert!(
-        conservative.composite < baseline.composite,
-        "conservative should penalize key=0.55 at peak phase"
-    );
-    // Penalty is 0.1x for Conservative style
-    let expected = baseline.composite * 0.1;
-    assert!(
-        (conservative.composite - expected).abs() < 1e-9,
-        "conservative penalty should be 0.1x; got {} vs expected {}",
-        conservative.composite,
-        expected
-    );
-
-    // Adventurous + peak phase + key=0.55 → no penalty (threshold is 0.1)
-    let adventurous = score_transition_profiles(
-        &from,
-        &to,
+#[test]
+fn bpm_proxy_energy_keeps_peak_phase_reachable_without_essentia() {
+    let from_energy = compute_track_energy(None, 126.0);
+    let to_energy = compute_track_energy(None, 130.0);
+    let peak = score_energy_axis(
+        from_energy,
+        to_energy,
         Some(EnergyPhase::Peak),
         Some(EnergyPhase::Peak),
-        SequencingPriority::Balanced,
-        true,
-        Some(HarmonicMixingStyle::Adventurous),
-        &ScoringContext::default(),
         None,
     );
-    assert_eq!(
-        adventurous.composite, baseline.composite,
-        "adventurous should not penalize key=0.55 at peak phase"
-    );
 
-    // Balanced + build phase + key=0.45 (Extended: 8A→10A) → threshold is 0.45, exactly at threshold
-    let from2 = make_test_profile("hs-from2", "8A", 128.0, 0.5, "House");
-    let to2 = make_test_profile("hs-to2", "10A", 128.0, 0.6, "House");
-    let balanced_build = score_transition_profiles(
-        &from2,
-        &to2,
-        Some(EnergyPhase::Build),
-        Some(EnergyPhase::Build),
-        SequencingPriority::Balanced,
-        true,
-        Some(HarmonicMixingStyle::Balanced),
-        &ScoringContext::default(),
-        None,
+    assert!(
+        to_energy >= 0.65,
+        "fallback energy should allow peak thresholds"
     );
-    let baseline_build = score_transition_profiles(
-        &from2,
-        &to2,
-        Some(EnergyPhase::Build),
-        Some(EnergyPhase::Build),
-        SequencingPriority::Balanced,
-        true,
-        None,
-        &ScoringContext::default(),
-        None,
+    assert_eq!(peak.value, 1.0);
+    assert_eq!(peak.label, "High and stable (peak phase)");
+}
+
+#[test]
+fn build_track_profile_falls_back_to_native_features_without_essentia() {
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+    let track = make_test_track("native-track", "Techno", 130.0, "8A");
+    let native = serde_json::json!({
+        "analyzer_version": crate::audio::NATIVE_FEATURES_VERSION,
+        "danceability": 1.5,
+        "loudness_integrated": -9.0,
+        "loudness_range": 5.0,
+        "onset_rate": 4.0,
+        "rhythm_regularity": 1.8,
+        "spectral_centroid_mean": 2100.0,
+    });
+    store::set_audio_analysis(
+        &store_conn,
+        &track.file_path,
+        crate::audio::ANALYZER_NATIVE,
+        1,
+        1,
+        crate::audio::NATIVE_FEATURES_VERSION,
+        &native.to_string(),
+    )
+    .expect("native analysis should cache");
+
+    let profile = build_track_profile(track.clone(), &store_conn).expect("profile should build");
+    assert_eq!(profile.brightness, Some(2100.0));
+    assert_eq!(profile.rhythm_regularity, Some(1.8));
+    assert_eq!(profile.loudness_range, Some(5.0));
+    assert_ne!(
+        profile.energy,
+        compute_track_energy(None, 130.0),
+        "energy should come from the native descriptors, not the BPM proxy"
     );
-    // key=0.45, threshold=0.45 → NOT below threshold → no penalty
+
+    // Essentia, when it has run, takes precedence.
+    let essentia = serde_json::json!({ "spectral_centroid_mean": 900.0 });
+    store::set_audio_analysis(
+        &store_conn,
+        &track.file_path,
+        crate::audio::ANALYZER_ESSENTIA,
+        1,
+        1,
+        "essentia-test",
+        &essentia.to_string(),
+    )
+    .expect("essentia analysis should cache");
+    let profile = build_track_profile(track, &store_conn).expect("profile should build");
+    assert_eq!(profile.brightness, Some(900.0));
+}
+
+#[test]
+fn build_track_profile_reads_edge_windows_from_stratum() {
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+    let track = make_test_track("edge-track", "Techno", 128.0, "8A");
+    let stratum = serde_json::json!({
+        "bpm": 128.0,
+        "key_camelot": "8A",
+        "opening": { "bpm": 126.0, "key_camelot": "7A", "key_confidence": 0.8 },
+        // Half-time misread: tempo is ignored, key is kept.
+        "closing": { "bpm": 64.0, "key_camelot": "9A", "key_confidence": 0.6 },
+    });
+    store::set_audio_analysis(
+        &store_conn,
+        &track.file_path,
+        crate::audio::ANALYZER_STRATUM,
+        1,
+        1,
+        "stratum-test",
+        &stratum.to_string(),
+    )
+    .expect("stratum analysis should cache");
+
+    let profile = build_track_profile(track, &store_conn).expect("profile should build");
+    assert_eq!(profile.start_bpm(), 126.0);
+    assert_eq!(profile.start_key(), parse_camelot_key("7A"));
+    assert_eq!(profile.end_bpm(), 128.0);
+    assert_eq!(profile.end_key(), parse_camelot_key("9A"));
+    assert_eq!(profile.camelot_key, parse_camelot_key("8A"));
+}
+
+#[test]
+fn build_track_profile_ignores_low_confidence_edge_keys() {
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+    let track = make_test_track("edge-track", "Techno", 128.0, "8A");
+    let stratum = serde_json::json!({
+        "bpm": 128.0,
+        "key_camelot": "8A",
+        "key_confidence": 0.7,
+        "opening": { "bpm": 127.0, "key_camelot": "3B", "key_confidence": 0.05 },
+        "closing": { "bpm": 128.0, "key_camelot": "9A" },
+    });
+    store::set_audio_analysis(
+        &store_conn,
+        &track.file_path,
+        crate::audio::ANALYZER_STRATUM,
+        1,
+        1,
+        "stratum-test",
+        &stratum.to_string(),
+    )
+    .expect("stratum analysis should cache");
+
+    let profile = build_track_profile(track, &store_conn).expect("profile should build");
+    // The edge tempo still counts; its key is too uncertain to.
+    assert_eq!(profile.start_bpm(), 127.0);
+    assert_eq!(profile.start_key(), parse_camelot_key("8A"));
     assert_eq!(
-        balanced_build.composite, baseline_build.composite,
-        "balanced should not penalize key=0.45 at build phase (exactly at threshold)"
+        profile.end_key(),
+        parse_camelot_key("8A"),
+        "an edge key without a confidence falls back too"
     );
 }
 
 #[test]
-fn harmonic_style_adventurous_is_phase_dependent() {
-    // Clash pair: 8A → 2A gives key=0.1
-    let from = make_test_profile("adv-from", "8A", 128.0, 0.7, "House");
-    let to = make_test_profile("adv-to", "2A", 128.0, 0.7, "House");
-
-    // Adventurous at Peak: threshold=0.1, key=0.1 → NOT below → no penalty
-    let adv_peak = score_transition_profiles(
+fn transition_scoring_uses_outgoing_ending_and_incoming_opening() {
+    // Whole-track keys clash (8A → 3A), but the outgoing track ends in 10A
+    // and the incoming one opens in 10A at a matching tempo.
+    let mut from = make_test_profile("edge-from", "8A", 128.0, 0.7, "House");
+    let mut to = make_test_profile("edge-to", "3A", 124.0, 0.7, "House");
+    let whole_track = score_transition_profiles(
         &from,
         &to,
-        Some(EnergyPhase::Peak),
-        Some(EnergyPhase::Peak),
+        None,
+        None,
         SequencingPriority::Balanced,
         true,
-        Some(HarmonicMixingStyle::Adventurous),
+        None,
         &ScoringContext::default(),
         None,
     );
-    let baseline_peak = score_transition_profiles(
+
+    from.closing = Some(EdgeProfile {
+        bpm: 126.0,
+        camelot_key: parse_camelot_key("10A"),
+    });
+    to.opening = Some(EdgeProfile {
+        bpm: 126.0,
+        camelot_key: parse_camelot_key("10A"),
+    });
+    // Edges that don't take part in this transition are ignored.
+    from.opening = Some(EdgeProfile {
+        bpm: 100.0,
+        camelot_key: parse_camelot_key("1B"),
+    });
+    to.closing = Some(EdgeProfile {
+        bpm: 100.0,
+        camelot_key: parse_camelot_key("1B"),
+    });
+    let edges = score_transition_profiles(
         &from,
         &to,
-        Some(EnergyPhase::Peak),
-        Some(EnergyPhase::Peak),
+        None,
+        None,
         SequencingPriority::Balanced,
         true,
         None,
         &ScoringContext::default(),
         None,
     );
-    assert_eq!(
-        adv_peak.composite, baseline_peak.composite,
-        "adventurous at peak should not penalize key=0.1 (threshold is 0.1)"
-    );
 
-    // Adventurous at Warmup: threshold=0.45, key=0.1 → below → penalty
-    let adv_warmup = score_transition_profiles(
-        &from,
-        &to,
-        Some(EnergyPhase::Warmup),
-        Some(EnergyPhase::Warmup),
-        SequencingPriority::Balanced,
-        true,
-        Some(HarmonicMixingStyle::Adventurous),
-        &ScoringContext::default(),
-        None,
+    assert!(whole_track.key.value < 0.5, "8A→3A should clash");
+    assert_eq!(edges.key.value, 1.0, "10A→10A should be a perfect match");
+    assert_eq!(edges.bpm.value, 1.0);
+    assert_eq!(edges.bpm_adjustment_pct, 0.0);
+    assert!(edges.composite > whole_track.composite);
+}
+
+#[tokio::test]
+async fn score_transition_returns_expected_axis_scores() {
+    let db_conn = create_single_track_test_db("from-track", "/tmp/from-track.flac");
+    db_conn
+        .execute(
+            "INSERT INTO djmdKey (ID, ScaleName) VALUES ('k2', 'Em')",
+            [],
+        )
+        .expect("second key should insert");
+    db_conn
+        .execute(
+            "INSERT INTO djmdContent (
+                    ID, Title, ArtistID, AlbumID, GenreID, KeyID, ColorID, LabelID, RemixerID,
+                    BPM, Rating, Commnt, ReleaseYear, Length, FolderPath, DJPlayCount, BitRate,
+                    SampleRate, FileType, created_at, rb_local_deleted
+                ) VALUES (
+                    ?1, 'Second Track', 'a1', 'al1', 'g1', 'k2', 'c1', 'l1', '',
+                    12350, 153, 'score transition test', 2025, 260, ?2, '0', 1411,
+                    44100, 5, '2025-01-03', 0
+                )",
+            params!["to-track", "/tmp/to-track.flac"],
+        )
+        .expect("second track should insert");
+
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+
+    store::set_audio_analysis(
+        &store_conn,
+        "/tmp/from-track.flac",
+        "stratum-dsp",
+        1,
+        1,
+        "stratum-dsp-1.0.0",
+        r#"{"bpm":122.0,"key":"Am","key_camelot":"8A"}"#,
+    )
+    .expect("source stratum cache should seed");
+    store::set_audio_analysis(
+        &store_conn,
+        "/tmp/to-track.flac",
+        "stratum-dsp",
+        1,
+        1,
+        "stratum-dsp-1.0.0",
+        r#"{"bpm":123.5,"key":"Em","key_camelot":"9A"}"#,
+    )
+    .expect("destination stratum cache should seed");
+
+    store::set_audio_analysis(
+        &store_conn,
+        "/tmp/from-track.flac",
+        "essentia",
+        1,
+        1,
+        "essentia-2.1",
+        r#"{"danceability":0.90,"loudness_integrated":-12.0,"onset_rate":3.0}"#,
+    )
+    .expect("source essentia cache should seed");
+    store::set_audio_analysis(
+        &store_conn,
+        "/tmp/to-track.flac",
+        "essentia",
+        1,
+        1,
+        "essentia-2.1",
+        r#"{"danceability":1.80,"loudness_integrated":-8.0,"onset_rate":5.0}"#,
+    )
+    .expect("destination essentia cache should seed");
+
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+    let result = server
+        .score_transition(Parameters(ScoreTransitionParams {
+            source_track_id: "from-track".to_string(),
+            target_track_id: "to-track".to_string(),
+            energy_phase: Some(EnergyPhase::Build),
+            priority: Some(SequencingPriority::Balanced),
+            use_master_tempo: None,
+            harmonic_style: None,
+        }))
+        .await
+        .expect("score_transition should succeed");
+
+    let payload = extract_json(&result);
+    assert_eq!(payload["from"]["track_id"], "from-track");
+    assert_eq!(payload["from"]["key"], "8A");
+    assert_eq!(payload["to"]["track_id"], "to-track");
+    assert_eq!(payload["to"]["key"], "9A");
+
+    assert_eq!(payload["scores"]["key"]["value"], 0.9);
+    // BPM uses exponential curve: 122→123.5 = 1.23% → exp(-0.019*1.23²) ≈ 0.972
+    assert_eq!(payload["scores"]["bpm"]["value"], 0.972);
+    assert_eq!(payload["scores"]["energy"]["value"], 1.0);
+    assert_eq!(payload["scores"]["genre"]["value"], 1.0);
+    assert_eq!(payload["scores"]["brightness"]["value"], 0.5);
+    assert_eq!(payload["scores"]["rhythm"]["value"], 0.5);
+    assert_eq!(payload["scores"]["composite"], 0.958);
+
+    // Top-level transition metadata fields
+    assert!(
+        payload["key_relation"].is_string(),
+        "key_relation should be present"
     );
-    let baseline_warmup = score_transition_profiles(
-        &from,
-        &to,
-        Some(EnergyPhase::Warmup),
-        Some(EnergyPhase::Warmup),
-        SequencingPriority::Balanced,
-        true,
-        None,
-        &ScoringContext::default(),
-        None,
+    assert!(
+        payload["key_relation"]
+            .as_str()
+            .unwrap()
+            .contains("Camelot adjacent")
     );
     assert!(
-        adv_warmup.composite < baseline_warmup.composite,
-        "adventurous at warmup should penalize key=0.1 (threshold is 0.45)"
+        payload["bpm_adjustment_pct"].is_number(),
+        "bpm_adjustment_pct should be present"
     );
-    // Adventurous uses 0.5x penalty factor
-    let expected = baseline_warmup.composite * 0.5;
+    let bpm_pct = payload["bpm_adjustment_pct"].as_f64().unwrap();
     assert!(
-        (adv_warmup.composite - expected).abs() < 1e-9,
-        "adventurous penalty should be 0.5x; got {} vs expected {}",
-        adv_warmup.composite,
-        expected
+        bpm_pct > 1.0 && bpm_pct < 2.0,
+        "122→123.5 is ~1.23%; got {bpm_pct}"
     );
+}
 
-    // Conservative is phase-independent: always 0.8 threshold
-    let cons_peak = score_transition_profiles(
-        &from,
-        &to,
-        Some(EnergyPhase::Peak),
-        Some(EnergyPhase::Peak),
-        SequencingPriority::Balanced,
-        true,
-        Some(HarmonicMixingStyle::Conservative),
-        &ScoringContext::default(),
-        None,
-    );
-    let cons_warmup = score_transition_profiles(
-        &from,
-        &to,
-        Some(EnergyPhase::Warmup),
-        Some(EnergyPhase::Warmup),
-        SequencingPriority::Balanced,
-        true,
-        Some(HarmonicMixingStyle::Conservative),
-        &ScoringContext::default(),
-        None,
+#[tokio::test]
+async fn history_tools_list_sessions_and_report_played_transitions() {
+    let db_conn = create_single_track_test_db("hist-a", "/tmp/hist-a.flac");
+    insert_test_track(&db_conn, "hist-b", "Second Play", "g1", "/tmp/hist-b.flac");
+    db_conn
+        .execute_batch(
+            "INSERT INTO djmdHistory (ID, Seq, Name, ParentID, DateCreated)
+                 VALUES ('h1', 1, 'HISTORY 2025-06-01', 'root', '2025-06-01');
+             INSERT INTO djmdSongHistory (ID, HistoryID, ContentID, TrackNo)
+                 VALUES ('sh1', 'h1', 'hist-a', 1);
+             INSERT INTO djmdSongHistory (ID, HistoryID, ContentID, TrackNo)
+                 VALUES ('sh2', 'h1', 'hist-b', 2);",
+        )
+        .expect("history rows should insert");
+
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+
+    let sessions = server
+        .get_history_sessions(Parameters(GetHistorySessionsParams {
+            since: Some("2025-01-01".to_string()),
+        }))
+        .await
+        .expect("get_history_sessions should succeed");
+    let sessions = extract_json(&sessions);
+    assert_eq!(sessions[0]["id"], "h1");
+    assert_eq!(sessions[0]["date"], "2025-06-01");
+    assert_eq!(sessions[0]["track_count"], 2);
+
+    let tracks = server
+        .get_history_tracks(Parameters(GetHistoryTracksParams {
+            history_id: "h1".to_string(),
+            limit: None,
+        }))
+        .await
+        .expect("get_history_tracks should succeed");
+    let tracks = extract_json(&tracks);
+    assert_eq!(tracks[0]["id"], "hist-a");
+    assert_eq!(tracks[1]["id"], "hist-b");
+
+    let forward = server
+        .score_transition(Parameters(ScoreTransitionParams {
+            source_track_id: "hist-a".to_string(),
+            target_track_id: "hist-b".to_string(),
+            energy_phase: None,
+            priority: None,
+            use_master_tempo: None,
+            harmonic_style: None,
+        }))
+        .await
+        .expect("score_transition should succeed");
+    let forward = extract_json(&forward);
+    assert_eq!(forward["played_before"]["count"], 1);
+    assert_eq!(forward["played_before"]["last_played"], "2025-06-01");
+    let adjustments = forward["scores"]["adjustments"]
+        .as_array()
+        .expect("played pair should carry adjustments");
+    assert!(adjustments.iter().any(|a| a["kind"] == "played_before"));
+
+    let reverse = server
+        .score_transition(Parameters(ScoreTransitionParams {
+            source_track_id: "hist-b".to_string(),
+            target_track_id: "hist-a".to_string(),
+            energy_phase: None,
+            priority: None,
+            use_master_tempo: None,
+            harmonic_style: None,
+        }))
+        .await
+        .expect("score_transition should succeed");
+    let reverse = extract_json(&reverse);
+    assert_eq!(reverse["played_before"]["count"], 0);
+    assert!(reverse["played_before"]["last_played"].is_null());
+    assert!(
+        reverse["scores"]["adjustments"]
+            .as_array()
+            .is_none_or(|a| a.iter().all(|a| a["kind"] != "played_before"))
     );
-    // Both should be penalized (key=0.1 < 0.8)
-    assert!(cons_peak.composite < baseline_peak.composite);
-    assert!(cons_warmup.composite < baseline_warmup.composite);
-}
 
-#[test]
-fn composite_scoring_changes_by_priority_axis() {
-    let approx = |left: f64, right: f64| (left - right).abs() < 1e-9;
+    let err = server
+        .get_history_sessions(Parameters(GetHistorySessionsParams {
+            since: Some("June 2025".to_string()),
+        }))
+        .await
+        .expect_err("non-ISO since should be rejected");
+    assert!(err.message.contains("since"), "{}", err.message);
+}
 
-    assert!(approx(
-        composite_score(
-            1.0,
-            0.0,
-            0.0,
-            0.0,
-            Some(0.0),
-            Some(0.0),
-            SequencingPriority::Balanced
-        ),
-        0.30
-    ));
-    assert!(approx(
-        composite_score(
-            1.0,
-            0.0,
-            0.0,
-            0.0,
-            Some(0.0),
-            Some(0.0),
-            SequencingPriority::Harmonic
-        ),
-        0.48
-    ));
-    assert!(approx(
-        composite_score(
-            1.0,
-            0.0,
-            0.0,
-            0.0,
-            Some(0.0),
-            Some(0.0),
-            SequencingPriority::Energy
-        ),
-        0.12
-    ));
-    assert!(approx(
-        composite_score(
-            1.0,
-            0.0,
-            0.0,
-            0.0,
-            Some(0.0),
-            Some(0.0),
-            SequencingPriority::Genre
-        ),
-        0.18
-    ));
+#[tokio::test]
+async fn build_set_avoids_recently_played_tracks() {
+    let (db_conn, track_ids) = create_build_set_test_db();
+    db_conn
+        .execute_batch(
+            "INSERT INTO djmdHistory (ID, Seq, Name, ParentID, DateCreated)
+                 VALUES ('h-old', 1, 'HISTORY 2024-01-01', 'root', '2024-01-01');
+             INSERT INTO djmdHistory (ID, Seq, Name, ParentID, DateCreated)
+                 VALUES ('h-new', 2, 'HISTORY 2025-06-01', 'root', '2025-06-01');
+             INSERT INTO djmdSongHistory (ID, HistoryID, ContentID, TrackNo)
+                 VALUES ('sh1', 'h-old', 'set-track-1', 1);
+             INSERT INTO djmdSongHistory (ID, HistoryID, ContentID, TrackNo)
+                 VALUES ('sh2', 'h-new', 'set-track-2', 1);
+             INSERT INTO djmdSongHistory (ID, HistoryID, ContentID, TrackNo)
+                 VALUES ('sh3', 'h-new', 'set-track-3', 2);",
+        )
+        .expect("history rows should insert");
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(store_path.to_str().unwrap()).expect("store should open");
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
 
-    assert!(approx(
-        composite_score(
-            0.0,
-            0.0,
-            0.0,
-            1.0,
-            Some(0.0),
-            Some(0.0),
-            SequencingPriority::Balanced
-        ),
-        0.17
-    ));
-    assert!(approx(
-        composite_score(
-            0.0,
-            0.0,
-            0.0,
-            1.0,
-            Some(0.0),
-            Some(0.0),
-            SequencingPriority::Genre
-        ),
-        0.38
-    ));
+    let params = |since: &str| BuildSetParams {
+        track_ids: track_ids.clone(),
+        target_tracks: 3,
+        priority: None,
+        energy_curve: None,
+        opening_track_id: Some("set-track-3".to_string()),
+        candidates: Some(1),
+        beam_width: Some(1),
+        use_master_tempo: None,
+        harmonic_style: None,
+        bpm_drift_pct: None,
+        bpm_range: None,
+        avoid_played_since: Some(since.to_string()),
+    };
+    let result = server
+        .build_set(Parameters(params("2025-01-01")))
+        .await
+        .expect("build_set should succeed");
+    let payload = extract_json(&result);
+    assert_eq!(
+        payload["excluded_recently_played"],
+        serde_json::json!(["set-track-2"]),
+        "opener stays, older plays are ignored"
+    );
+    let used: Vec<&str> = payload["candidates"][0]["tracks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["track_id"].as_str().unwrap())
+        .collect();
+    assert_eq!(used[0], "set-track-3");
+    assert!(!used.contains(&"set-track-2"));
 
-    assert!(approx(
-        composite_score(1.0, 0.0, 0.0, 0.0, None, None, SequencingPriority::Balanced),
-        0.30 / 0.85
-    ));
+    let err = server
+        .build_set(Parameters(params("2025-13-01")))
+        .await
+        .expect_err("invalid date should be rejected");
+    assert!(
+        err.message.contains("avoid_played_since"),
+        "{}",
+        err.message
+    );
 }
 
-#[test]
-fn score_genre_axis_treats_missing_genre_as_neutral() {
-    let unknown_source = score_genre_axis(
-        None,
-        Some("House"),
-        GenreFamily::Other,
-        GenreFamily::House,
-        0,
+#[tokio::test]
+async fn find_relocations_stages_confident_matches_for_xml_export() {
+    let music_dir = tempfile::tempdir().expect("temp music dir should create");
+    let moved = music_dir.path().join("New Drive/Aníbal/Moved Track.flac");
+    std::fs::create_dir_all(moved.parent().unwrap()).expect("dirs should create");
+    std::fs::write(&moved, vec![0u8; 2048]).expect("moved file should write");
+
+    let db_conn =
+        create_single_track_test_db("moved-track", "/Volumes/Old Drive/Aníbal/Moved Track.flac");
+    db_conn
+        .execute(
+            "UPDATE djmdContent SET FileSize = 2048 WHERE ID = 'moved-track'",
+            [],
+        )
+        .expect("file size should update");
+
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+
+    let result = server
+        .find_relocations(Parameters(FindRelocationsParams {
+            roots: vec![music_dir.path().to_string_lossy().to_string()],
+            filters: SearchFilterParams::default(),
+            track_ids: None,
+            playlist_id: None,
+            limit: None,
+            max_candidates: None,
+            stage_confident: Some(true),
+        }))
+        .await
+        .expect("find_relocations should succeed");
+    let payload = extract_json(&result);
+    assert_eq!(payload["summary"]["missing_tracks"], 1);
+    assert_eq!(payload["summary"]["high"], 1);
+    assert_eq!(payload["staged"], 1);
+    let entry = &payload["relocations"][0];
+    assert_eq!(entry["track_id"], "moved-track");
+    assert_eq!(entry["confidence"], "high");
+    assert_eq!(
+        entry["candidates"][0]["path"],
+        moved.to_string_lossy().as_ref()
     );
-    assert_eq!(unknown_source.value, 0.5);
-    assert_eq!(unknown_source.label, "Unknown genre");
 
-    let unknown_destination = score_genre_axis(
-        Some("House"),
-        None,
-        GenreFamily::House,
-        GenreFamily::Other,
-        0,
+    let preview = server
+        .preview_changes(Parameters(PreviewChangesParams { track_ids: None }))
+        .await
+        .expect("preview should succeed");
+    let preview = extract_json(&preview);
+    assert_eq!(preview[0]["changes"][0]["field"], "file_path");
+
+    let output_path = music_dir.path().join("relocated.xml");
+    server
+        .write_xml(Parameters(WriteXmlParams {
+            output_path: Some(output_path.to_string_lossy().to_string()),
+            playlists: None,
+            rekordbox_playlist_ids: None,
+            beat_grids: None,
+            phrase_cues: None,
+        }))
+        .await
+        .expect("write_xml should succeed");
+    let xml = std::fs::read_to_string(&output_path).expect("exported XML should read");
+    let expected_location = crate::xml::path_to_rekordbox_location_uri(&moved.to_string_lossy());
+    assert!(
+        xml.contains(&format!("Location=\"{expected_location}\"")),
+        "XML should carry the relocated Location: {xml}"
     );
-    assert_eq!(unknown_destination.value, 0.5);
-    assert_eq!(unknown_destination.label, "Unknown genre");
 }
 
-#[test]
-fn genre_stickiness_bonus_and_penalty() {
-    let approx = |a: f64, b: f64| (a - b).abs() < 1e-9;
-
-    // Streak bonus: same family, run_length=3 (< 5) → +0.1
-    let bonus = score_genre_axis(
-        Some("Deep House"),
-        Some("Tech House"),
-        GenreFamily::House,
-        GenreFamily::House,
-        3,
+#[tokio::test]
+async fn find_duplicates_stages_merges_that_rewrite_playlists_on_export() {
+    let db_conn = create_single_track_test_db("dup-flac", "/Music/FLAC/Senorita.flac");
+    insert_test_track(
+        &db_conn,
+        "dup-mp3",
+        "Señorita",
+        "g1",
+        "/Music/MP3/Senorita.mp3",
     );
-    assert!(
-        approx(bonus.value, 0.8),
-        "0.7 + 0.1 streak bonus; got {}",
-        bonus.value
+    insert_test_track(
+        &db_conn,
+        "other",
+        "Something Else",
+        "g1",
+        "/Music/other.flac",
     );
-    assert!(bonus.label.contains("streak bonus"));
+    insert_test_track(&db_conn, "solo-keep", "Solo", "g1", "/Music/solo-keep.flac");
+    insert_test_track(
+        &db_conn,
+        "solo-dup",
+        "Solo Dub",
+        "g1",
+        "/Music/solo-dup.mp3",
+    );
+    db_conn
+        .execute_batch(
+            "UPDATE djmdContent SET FileType = 1, BitRate = 320, Length = 241 WHERE ID = 'dup-mp3';
+            CREATE TABLE djmdPlaylist (
+                ID VARCHAR(255) PRIMARY KEY,
+                Seq INTEGER,
+                Name VARCHAR(255),
+                Attribute INTEGER DEFAULT 0,
+                ParentID VARCHAR(255) DEFAULT '',
+                SmartList TEXT,
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+            CREATE TABLE djmdSongPlaylist (
+                ID VARCHAR(255) PRIMARY KEY,
+                PlaylistID VARCHAR(255),
+                ContentID VARCHAR(255),
+                TrackNo INTEGER
+            );
+            INSERT INTO djmdPlaylist (ID, Seq, Name, Attribute, ParentID) VALUES
+                ('f-sets', 1, 'Sets', 1, 'root'),
+                ('p-warmup', 1, 'Warmup', 0, 'f-sets');
+            INSERT INTO djmdSongPlaylist (ID, PlaylistID, ContentID, TrackNo) VALUES
+                ('sp1', 'p-warmup', 'other', 1),
+                ('sp2', 'p-warmup', 'dup-mp3', 2);",
+        )
+        .expect("duplicate fixture should apply");
 
-    // No bonus at run=5 (cap)
-    let no_bonus = score_genre_axis(
-        Some("Deep House"),
-        Some("Tech House"),
-        GenreFamily::House,
-        GenreFamily::House,
-        5,
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+    for (path, bpm) in [
+        ("/Music/FLAC/Senorita.flac", 128.0),
+        ("/Music/MP3/Senorita.mp3", 64.0),
+    ] {
+        let features = serde_json::json!({
+            "bpm": bpm, "bpm_confidence": 0.9, "key": "Am", "key_camelot": "8A",
+            "key_confidence": 0.8, "key_clarity": 0.7, "grid_stability": 0.9,
+            "duration_seconds": 240.0, "processing_time_ms": 1.0,
+            "analyzer_version": "test", "flags": [], "warnings": [],
+        });
+        store::set_audio_analysis(
+            &store_conn,
+            path,
+            crate::audio::ANALYZER_STRATUM,
+            1,
+            1,
+            "test",
+            &features.to_string(),
+        )
+        .expect("analysis should cache");
+    }
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+
+    let result = server
+        .find_duplicates(Parameters(FindDuplicatesParams {
+            filters: SearchFilterParams::default(),
+            limit: None,
+            stage_merges: Some(true),
+            fingerprint_limit: None,
+        }))
+        .await
+        .expect("find_duplicates should succeed");
+    let payload = extract_json(&result);
+    assert_eq!(payload["summary"]["clusters"], 1);
+    assert_eq!(payload["summary"]["high"], 1);
+    assert_eq!(payload["staged"], 1);
+    let cluster = &payload["clusters"][0];
+    assert_eq!(cluster["keep"], "dup-flac");
+    assert_eq!(cluster["copies"][1]["track_id"], "dup-mp3");
+    assert_eq!(cluster["copies"][1]["playlists"][0], "Warmup");
+    assert_eq!(
+        cluster["matched_on"],
+        serde_json::json!(["artist_title", "duration", "tempo_key"])
     );
-    assert_eq!(no_bonus.value, 0.7);
-    assert!(!no_bonus.label.contains("streak bonus"));
 
-    // Early switch penalty: different family, run_length=1 (< 2) → -0.1
-    let penalty = score_genre_axis(
-        Some("House"),
-        Some("Drum & Bass"),
-        GenreFamily::House,
-        GenreFamily::Bass,
-        1,
+    let preview = server
+        .preview_changes(Parameters(PreviewChangesParams { track_ids: None }))
+        .await
+        .expect("preview should succeed");
+    let preview = extract_json(&preview);
+    assert_eq!(preview[0]["track_id"], "dup-mp3");
+    assert_eq!(preview[0]["changes"][0]["field"], "merge_into");
+    assert_eq!(preview[0]["changes"][0]["new_value"], "dup-flac");
+
+    // A merge whose keeper is in no playlist and has no other changes.
+    server.state.changes.stage(
+        "find_duplicates",
+        vec![crate::types::TrackChange {
+            track_id: "solo-dup".to_string(),
+            merge_into: Some("solo-keep".to_string()),
+            ..Default::default()
+        }],
     );
+
+    let out_dir = tempfile::tempdir().expect("temp output dir should create");
+    let output_path = out_dir.path().join("merged.xml");
+    let written = server
+        .write_xml(Parameters(WriteXmlParams {
+            output_path: Some(output_path.to_string_lossy().to_string()),
+            playlists: None,
+            rekordbox_playlist_ids: None,
+            beat_grids: None,
+            phrase_cues: None,
+        }))
+        .await
+        .expect("write_xml should succeed");
+    let written = extract_json(&written);
+    assert_eq!(written["merged_duplicates"], 2);
+    assert_eq!(written["merged_playlists"], 1);
+
+    let xml = std::fs::read_to_string(&output_path).expect("exported XML should read");
+    assert!(!xml.contains("Senorita.mp3"), "duplicate is dropped: {xml}");
+    assert!(!xml.contains("solo-dup.mp3"), "duplicate is dropped: {xml}");
     assert!(
-        approx(penalty.value, 0.2),
-        "0.3 - 0.1 early switch penalty; got {}",
-        penalty.value
-    );
-    assert!(penalty.label.contains("early switch penalty"));
+        xml.contains("solo-keep.flac"),
+        "merge target is always exported: {xml}"
+    );
+    let library = crate::xml_import::parse_xml(&xml).expect("exported XML should parse");
+    let warmup = library
+        .playlist_tracks
+        .get("Sets/Warmup")
+        .expect("affected playlist should be exported under its folder");
+    let titles: Vec<&str> = warmup
+        .iter()
+        .map(|id| {
+            library
+                .tracks
+                .iter()
+                .find(|t| &t.id == id)
+                .map(|t| t.title.as_str())
+                .unwrap_or_default()
+        })
+        .collect();
+    assert_eq!(titles, ["Something Else", "Señorita"]);
+}
 
-    // No penalty at run=2
-    let no_penalty = score_genre_axis(
-        Some("House"),
-        Some("Drum & Bass"),
-        GenreFamily::House,
-        GenreFamily::Bass,
-        2,
-    );
-    assert_eq!(no_penalty.value, 0.3);
-    assert!(!no_penalty.label.contains("early switch penalty"));
+#[tokio::test]
+async fn find_duplicates_fingerprints_uncached_copies_up_to_the_limit() {
+    let dir = tempfile::tempdir().expect("temp audio dir should create");
+    let loud = dir.path().join("Twin.wav");
+    let quiet = dir.path().join("Twin (copy).wav");
+    write_chord_wav(&loud, 30, 1, 1.0);
+    write_chord_wav(&quiet, 30, 1, 0.5);
+
+    let db_conn = create_single_track_test_db("unrelated", "/Music/unrelated.flac");
+    insert_test_track(&db_conn, "twin-a", "Twin", "g1", &loud.to_string_lossy());
+    insert_test_track(&db_conn, "twin-b", "Twin", "g1", &quiet.to_string_lossy());
+    db_conn
+        .execute_batch(
+            "CREATE TABLE djmdPlaylist (
+                ID VARCHAR(255) PRIMARY KEY,
+                Seq INTEGER,
+                Name VARCHAR(255),
+                Attribute INTEGER DEFAULT 0,
+                ParentID VARCHAR(255) DEFAULT '',
+                SmartList TEXT,
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+            CREATE TABLE djmdSongPlaylist (
+                ID VARCHAR(255) PRIMARY KEY,
+                PlaylistID VARCHAR(255),
+                ContentID VARCHAR(255),
+                TrackNo INTEGER
+            );",
+        )
+        .expect("playlist tables should create");
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(store_path.to_str().unwrap()).expect("store should open");
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
 
-    // No bonus at run=0 (first transition)
-    let first = score_genre_axis(
-        Some("House"),
-        Some("Tech House"),
-        GenreFamily::House,
-        GenreFamily::House,
-        0,
+    let params = |fingerprint_limit| FindDuplicatesParams {
+        filters: SearchFilterParams::default(),
+        limit: None,
+        stage_merges: None,
+        fingerprint_limit,
+    };
+    let first = server
+        .find_duplicates(Parameters(params(Some(1))))
+        .await
+        .expect("find_duplicates should succeed");
+    let first = extract_json(&first);
+    assert_eq!(first["summary"]["fingerprints_computed"], 1);
+    assert_eq!(first["summary"]["fingerprints_deferred"], 1);
+    assert_eq!(first["clusters"][0]["confidence"], "medium");
+
+    let second = server
+        .find_duplicates(Parameters(params(None)))
+        .await
+        .expect("find_duplicates should succeed");
+    let second = extract_json(&second);
+    assert_eq!(second["summary"]["fingerprints_computed"], 1);
+    assert_eq!(second["summary"]["fingerprints_deferred"], 0);
+    let cluster = &second["clusters"][0];
+    assert_eq!(cluster["confidence"], "high");
+    assert_eq!(
+        cluster["matched_on"],
+        serde_json::json!(["artist_title", "duration", "fingerprint"])
     );
-    assert_eq!(first.value, 0.7);
-    assert!(!first.label.contains("streak bonus"));
 }
 
-#[test]
-fn bpm_trajectory_drift_penalty() {
-    use std::collections::HashMap;
+#[tokio::test]
+async fn reconcile_bpm_stages_octave_corrections_from_cached_evidence() {
+    let db_conn = create_single_track_test_db("house", "/Music/house.flac");
+    insert_test_track(&db_conn, "dnb", "Amen Roller", "g2", "/Music/dnb.flac");
+    db_conn
+        .execute_batch(
+            "INSERT INTO djmdGenre (ID, Name) VALUES ('g2', 'Drum & Bass');
+            UPDATE djmdContent SET BPM = 8700 WHERE ID = 'dnb';",
+        )
+        .expect("tempo fixture should apply");
 
-    // Pool: start at 128 BPM, candidates at 130 (+1.56%) and 145 (+13.28%)
-    // With bpm_drift_pct=3.0 and target_tracks=3 (divisor = 2):
-    //   position 1: budget_pct = 3.0 * (1/2) = 1.5% → budget_bpm = 128 * 1.5% = 1.92
-    //   130 drifts 2 (>1.92) → penalized; 145 drifts 17 (>1.92) → penalized
-    // With bpm_drift_pct=6.0 and target_tracks=3 (divisor = 2):
-    //   position 1: budget_pct = 6.0 * (1/2) = 3.0% → budget_bpm = 128 * 3% = 3.84
-    //   130 drifts 2 (<=3.84) → no penalty; 145 drifts 17 (>3.84) → penalized
-    let start = make_test_profile("bpm-start", "8A", 128.0, 0.7, "House");
-    let close = make_test_profile("bpm-close", "8A", 130.0, 0.7, "House");
-    let far = make_test_profile("bpm-far", "8A", 145.0, 0.7, "House");
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+    for (path, bpm) in [("/Music/house.flac", 128.0), ("/Music/dnb.flac", 174.0)] {
+        let features = serde_json::json!({
+            "bpm": bpm, "bpm_confidence": 0.9, "key": "Am", "key_camelot": "8A",
+            "key_confidence": 0.8, "key_clarity": 0.7, "grid_stability": 0.9,
+            "duration_seconds": 240.0, "processing_time_ms": 1.0,
+            "analyzer_version": "test", "flags": [], "warnings": [],
+        });
+        store::set_audio_analysis(
+            &store_conn,
+            path,
+            crate::audio::ANALYZER_STRATUM,
+            1,
+            1,
+            "test",
+            &features.to_string(),
+        )
+        .expect("analysis should cache");
+    }
+    let beatport = serde_json::json!({
+        "genre": "Drum & Bass", "bpm": 174, "key": "A min",
+        "track_name": "Amen Roller", "artists": ["Aníbal"],
+    });
+    store::set_enrichment(
+        &store_conn,
+        "beatport",
+        &crate::normalize::normalize_for_matching("Aníbal"),
+        &crate::normalize::normalize_for_matching("Amen Roller"),
+        Some("exact"),
+        Some(&beatport.to_string()),
+    )
+    .expect("enrichment should cache");
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
 
-    let mut profiles: HashMap<String, TrackProfile> = HashMap::new();
-    profiles.insert("bpm-start".to_string(), start);
-    profiles.insert("bpm-close".to_string(), close);
-    profiles.insert("bpm-far".to_string(), far);
+    let result = server
+        .reconcile_bpm(Parameters(ReconcileBpmParams {
+            filters: SearchFilterParams::default(),
+            track_ids: None,
+            playlist_id: None,
+            max_tracks: None,
+            offset: None,
+            limit: None,
+            stage_corrections: Some(true),
+        }))
+        .await
+        .expect("reconcile_bpm should succeed");
+    let payload = extract_json(&result);
+    assert_eq!(payload["summary"]["reconciled"], 2);
+    assert_eq!(payload["summary"]["corrections"], 1);
+    assert_eq!(payload["summary"]["high"], 1);
+    assert_eq!(payload["staged"], 1);
+    let correction = &payload["corrections"][0];
+    assert_eq!(correction["track_id"], "dnb");
+    assert_eq!(correction["current_bpm"], 87.0);
+    assert_eq!(correction["corrected_bpm"], 174.0);
+    assert_eq!(correction["level"], "high");
+    assert!(
+        correction["explanation"]
+            .as_array()
+            .expect("explanation should be a list")
+            .iter()
+            .any(|line| line.as_str().is_some_and(|s| s.contains("half time"))),
+        "{correction}"
+    );
 
-    // 3% limit: both candidates penalized at position 1 (budget 1.92 BPM)
-    // but close (drift 2) is barely over while far (drift 17) is way over
-    // close still wins on composite even with penalty
-    let tight = build_candidate_plan(
-        &profiles,
-        "bpm-start",
-        3,
-        &[EnergyPhase::Build, EnergyPhase::Build, EnergyPhase::Build],
-        SequencingPriority::Harmonic,
-        0,
-        true,
-        None,
-        3.0,
-        None,
+    let preview = server
+        .preview_changes(Parameters(PreviewChangesParams { track_ids: None }))
+        .await
+        .expect("preview should succeed");
+    let preview = extract_json(&preview);
+    assert_eq!(preview[0]["track_id"], "dnb");
+    assert_eq!(preview[0]["changes"][0]["field"], "bpm");
+    assert_eq!(preview[0]["changes"][0]["new_value"], "174.00");
+    assert!(payload["beat_grid"].is_string(), "{payload}");
+
+    let output_dir = tempfile::tempdir().expect("temp output dir should create");
+    let written = server
+        .write_xml(Parameters(WriteXmlParams {
+            output_path: Some(
+                output_dir
+                    .path()
+                    .join("bpm.xml")
+                    .to_string_lossy()
+                    .to_string(),
+            ),
+            playlists: None,
+            rekordbox_playlist_ids: None,
+            beat_grids: None,
+            phrase_cues: None,
+        }))
+        .await
+        .expect("write_xml should succeed");
+    let written = extract_json(&written);
+    assert_eq!(
+        written["bpm_changes_without_beat_grid"],
+        serde_json::json!(["dnb"])
     );
-    assert_eq!(tight.ordered_ids[1], "bpm-close");
+}
+
+#[tokio::test]
+async fn reconcile_keys_reports_confusions_and_stages_consensus_key() {
+    let db_conn = create_single_track_test_db("relative", "/Music/relative.flac");
+    insert_test_track(&db_conn, "agreed", "Steady", "g1", "/Music/agreed.flac");
+
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+    for (path, key, camelot) in [
+        ("/Music/relative.flac", "C", "8B"),
+        ("/Music/agreed.flac", "Am", "8A"),
+    ] {
+        let features = serde_json::json!({
+            "bpm": 124.0, "bpm_confidence": 0.9, "key": key, "key_camelot": camelot,
+            "key_confidence": 0.9, "key_clarity": 0.8, "grid_stability": 0.9,
+            "duration_seconds": 240.0, "processing_time_ms": 1.0,
+            "analyzer_version": "test", "flags": [], "warnings": [],
+        });
+        store::set_audio_analysis(
+            &store_conn,
+            path,
+            crate::audio::ANALYZER_STRATUM,
+            1,
+            1,
+            "test",
+            &features.to_string(),
+        )
+        .expect("analysis should cache");
+    }
+    store::set_audio_analysis(
+        &store_conn,
+        "/Music/relative.flac",
+        crate::audio::ANALYZER_ESSENTIA,
+        1,
+        1,
+        "test",
+        r#"{"key_essentia":"C","key_strength":0.7}"#,
+    )
+    .expect("essentia analysis should cache");
+    let beatport = serde_json::json!({
+        "genre": "Deep House", "bpm": 124, "key": "C Major",
+        "track_name": "Señorita", "artists": ["Aníbal"],
+    });
+    store::set_enrichment(
+        &store_conn,
+        "beatport",
+        &crate::normalize::normalize_for_matching("Aníbal"),
+        &crate::normalize::normalize_for_matching("Señorita"),
+        Some("exact"),
+        Some(&beatport.to_string()),
+    )
+    .expect("enrichment should cache");
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
 
-    // 6% limit (default): close (1.56%) within budget, far (13.28%) penalized
-    let moderate = build_candidate_plan(
-        &profiles,
-        "bpm-start",
-        3,
-        &[EnergyPhase::Build, EnergyPhase::Build, EnergyPhase::Build],
-        SequencingPriority::Harmonic,
-        0,
-        true,
-        None,
-        6.0,
-        None,
-    );
-    assert_eq!(moderate.ordered_ids[1], "bpm-close");
-    // Far still included (only penalized, not excluded)
-    assert!(moderate.ordered_ids.contains(&"bpm-far".to_string()));
+    let result = server
+        .reconcile_keys(Parameters(ReconcileKeysParams {
+            filters: SearchFilterParams::default(),
+            track_ids: None,
+            playlist_id: None,
+            max_tracks: None,
+            offset: None,
+            limit: None,
+            stage_corrections: Some(true),
+        }))
+        .await
+        .expect("reconcile_keys should succeed");
+    let payload = extract_json(&result);
+    assert_eq!(payload["summary"]["reconciled"], 2);
+    assert_eq!(payload["summary"]["unanimous"], 1);
+    assert_eq!(payload["summary"]["confusions"]["relative"], 1);
+    assert_eq!(payload["summary"]["high"], 1);
+    assert_eq!(payload["staged"], 1);
+    let report = &payload["tracks"][0];
+    assert_eq!(report["track_id"], "relative");
+    assert_eq!(report["current_key"], "Am");
+    assert_eq!(report["consensus_camelot"], "8B");
+    assert_eq!(report["corrected_key"], "C");
+    assert_eq!(report["readings"][0]["source"], "rekordbox");
+    assert_eq!(report["readings"][0]["relation"], "relative");
+    assert_eq!(payload["tracks"].as_array().map(Vec::len), Some(1));
+
+    let preview = server
+        .preview_changes(Parameters(PreviewChangesParams { track_ids: None }))
+        .await
+        .expect("preview should succeed");
+    let preview = extract_json(&preview);
+    assert_eq!(preview[0]["track_id"], "relative");
+    assert_eq!(preview[0]["changes"][0]["field"], "key");
+    assert_eq!(preview[0]["changes"][0]["new_value"], "C");
+}
 
-    // Very generous limit: no penalty for either
-    let generous = build_candidate_plan(
-        &profiles,
-        "bpm-start",
-        3,
-        &[EnergyPhase::Build, EnergyPhase::Build, EnergyPhase::Build],
-        SequencingPriority::Harmonic,
-        0,
-        true,
-        None,
-        50.0,
-        None,
-    );
-    assert_eq!(generous.ordered_ids[1], "bpm-close");
-    assert!(generous.ordered_ids.contains(&"bpm-far".to_string()));
+/// Write a mono 16-bit PCM WAV of `seconds` of chords: a new chord every
+/// half second, chosen from `seed`.
+fn write_chord_wav(path: &std::path::Path, seconds: usize, seed: usize, gain: f32) {
+    let sample_rate = 22050u32;
+    let mut samples = Vec::with_capacity(seconds * sample_rate as usize);
+    for segment in 0..seconds * 2 {
+        let root = 48 + (segment * 7 + seed * 13 + segment * segment * seed) % 24;
+        for i in 0..sample_rate as usize / 2 {
+            let t = i as f32 / sample_rate as f32;
+            let value: f32 = [root, root + 4, root + 7]
+                .iter()
+                .map(|&n| {
+                    let freq = 440.0 * 2f32.powf((n as f32 - 69.0) / 12.0);
+                    (std::f32::consts::TAU * freq * t).sin()
+                })
+                .sum();
+            samples.push((value * gain * 0.3 * f32::from(i16::MAX)) as i16);
+        }
+    }
+    let data_len = (samples.len() * 2) as u32;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    std::fs::write(path, bytes).expect("test WAV should write");
 }
 
-#[test]
-fn bpm_proxy_energy_keeps_peak_phase_reachable_without_essentia() {
-    let from_energy = compute_track_energy(None, 126.0);
-    let to_energy = compute_track_energy(None, 130.0);
-    let peak = score_energy_axis(
-        from_energy,
-        to_energy,
-        Some(EnergyPhase::Peak),
-        Some(EnergyPhase::Peak),
-        None,
-    );
+#[tokio::test]
+async fn fingerprint_match_pairs_same_audio_and_reuses_cache() {
+    let dir = tempfile::tempdir().expect("temp audio dir should create");
+    let original = dir.path().join("original.wav");
+    let quieter = dir.path().join("quieter copy.wav");
+    let other = dir.path().join("other.wav");
+    write_chord_wav(&original, 30, 1, 1.0);
+    write_chord_wav(&quieter, 30, 1, 0.5);
+    write_chord_wav(&other, 30, 2, 1.0);
+    let paths: Vec<String> = [&original, &quieter, &other]
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+
+    let db_conn = create_single_track_test_db("t1", &paths[0]);
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+
+    let too_few = server
+        .fingerprint_match(Parameters(FingerprintMatchParams {
+            track_ids: Some(vec!["t1".to_string()]),
+            paths: None,
+            skip_cached: None,
+        }))
+        .await;
+    assert!(too_few.is_err(), "a single item has nothing to compare");
 
+    let result = server
+        .fingerprint_match(Parameters(FingerprintMatchParams {
+            track_ids: Some(vec!["t1".to_string()]),
+            paths: Some(paths[1..].to_vec()),
+            skip_cached: None,
+        }))
+        .await
+        .expect("fingerprint_match should succeed");
+    let payload = extract_json(&result);
+    assert_eq!(payload["items"][0]["track_id"], "t1");
+    assert_eq!(payload["items"][0]["cache_hit"], false);
+    let pairs = payload["pairs"]
+        .as_array()
+        .expect("pairs should be an array");
+    assert_eq!(pairs.len(), 3);
+    assert_eq!(pairs[0]["a"], "t1");
+    assert_eq!(pairs[0]["b"], paths[1]);
+    assert_eq!(pairs[0]["match"], true, "{payload}");
     assert!(
-        to_energy >= 0.65,
-        "fallback energy should allow peak thresholds"
+        pairs[1..].iter().all(|pair| pair["match"] == false),
+        "{payload}"
     );
-    assert_eq!(peak.value, 1.0);
-    assert_eq!(peak.label, "High and stable (peak phase)");
+
+    let again = server
+        .fingerprint_match(Parameters(FingerprintMatchParams {
+            track_ids: None,
+            paths: Some(paths[..2].to_vec()),
+            skip_cached: None,
+        }))
+        .await
+        .expect("cached fingerprint_match should succeed");
+    let again = extract_json(&again);
+    assert_eq!(again["items"][0]["cache_hit"], true);
+    assert_eq!(again["items"][1]["cache_hit"], true);
 }
 
 #[tokio::test]
-async fn score_transition_returns_expected_axis_scores() {
-    let db_conn = create_single_track_test_db("from-track", "/tmp/from-track.flac");
-    db_conn
-        .execute(
-            "INSERT INTO djmdKey (ID, ScaleName) VALUES ('k2', 'Em')",
-            [],
-        )
-        .expect("second key should insert");
+async fn quality_report_ranks_clipped_tracks_first_and_caches() {
+    let dir = tempfile::tempdir().expect("temp audio dir should create");
+    let clean = dir.path().join("clean.wav");
+    let clipped = dir.path().join("clipped.wav");
+    write_chord_wav(&clean, 5, 1, 0.5);
+    write_chord_wav(&clipped, 5, 2, 4.0);
+
+    let db_conn = create_single_track_test_db("clean", &clean.to_string_lossy());
     db_conn
         .execute(
             "INSERT INTO djmdContent (
@@ -3610,14 +6012,37 @@ async fn score_transition_returns_expected_axis_scores() {
                     BPM, Rating, Commnt, ReleaseYear, Length, FolderPath, DJPlayCount, BitRate,
                     SampleRate, FileType, created_at, rb_local_deleted
                 ) VALUES (
-                    ?1, 'Second Track', 'a1', 'al1', 'g1', 'k2', 'c1', 'l1', '',
-                    12350, 153, 'score transition test', 2025, 260, ?2, '0', 1411,
-                    44100, 5, '2025-01-03', 0
+                    'clipped', 'Too Hot', 'a1', 'al1', 'g1', 'k1', 'c1', 'l1', '',
+                    12800, 0, '', 2025, 5, ?1, '0', 1411,
+                    22050, 11, '2025-01-03', 0
                 )",
-            params!["to-track", "/tmp/to-track.flac"],
+            params![clipped.to_string_lossy()],
         )
         .expect("second track should insert");
-
+    db_conn
+        .execute_batch(
+            "CREATE TABLE djmdPlaylist (
+                ID VARCHAR(255) PRIMARY KEY,
+                Seq INTEGER,
+                Name VARCHAR(255),
+                Attribute INTEGER DEFAULT 0,
+                ParentID VARCHAR(255) DEFAULT '',
+                SmartList TEXT,
+                rb_local_deleted INTEGER DEFAULT 0
+            );
+            CREATE TABLE djmdSongPlaylist (
+                ID VARCHAR(255) PRIMARY KEY,
+                PlaylistID VARCHAR(255),
+                ContentID VARCHAR(255),
+                TrackNo INTEGER
+            );
+            INSERT INTO djmdPlaylist (ID, Seq, Name, Attribute, ParentID, SmartList) VALUES
+                ('p-gig', 1, 'Gig', 0, 'root', NULL);
+            INSERT INTO djmdSongPlaylist (ID, PlaylistID, ContentID, TrackNo) VALUES
+                ('sp1', 'p-gig', 'clean', 1),
+                ('sp2', 'p-gig', 'clipped', 2);",
+        )
+        .expect("playlist tables should be created for test");
     let store_dir = tempfile::tempdir().expect("temp store dir should create");
     let store_path = store_dir.path().join("internal.sqlite3");
     let store_conn = store::open(
@@ -3626,98 +6051,286 @@ async fn score_transition_returns_expected_axis_scores() {
             .expect("temp store path should be UTF-8"),
     )
     .expect("temp internal store should open");
-
-    store::set_audio_analysis(
-        &store_conn,
-        "/tmp/from-track.flac",
-        "stratum-dsp",
-        1,
-        1,
-        "stratum-dsp-1.0.0",
-        r#"{"bpm":122.0,"key":"Am","key_camelot":"8A"}"#,
-    )
-    .expect("source stratum cache should seed");
-    store::set_audio_analysis(
-        &store_conn,
-        "/tmp/to-track.flac",
-        "stratum-dsp",
-        1,
-        1,
-        "stratum-dsp-1.0.0",
-        r#"{"bpm":123.5,"key":"Em","key_camelot":"9A"}"#,
-    )
-    .expect("destination stratum cache should seed");
-
-    store::set_audio_analysis(
-        &store_conn,
-        "/tmp/from-track.flac",
-        "essentia",
-        1,
-        1,
-        "essentia-2.1",
-        r#"{"danceability":0.90,"loudness_integrated":-12.0,"onset_rate":3.0}"#,
-    )
-    .expect("source essentia cache should seed");
-    store::set_audio_analysis(
-        &store_conn,
-        "/tmp/to-track.flac",
-        "essentia",
-        1,
-        1,
-        "essentia-2.1",
-        r#"{"danceability":1.80,"loudness_integrated":-8.0,"onset_rate":5.0}"#,
-    )
-    .expect("destination essentia cache should seed");
-
     let server =
         create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
-    let result = server
-        .score_transition(Parameters(ScoreTransitionParams {
-            source_track_id: "from-track".to_string(),
-            target_track_id: "to-track".to_string(),
-            energy_phase: Some(EnergyPhase::Build),
-            priority: Some(SequencingPriority::Balanced),
-            use_master_tempo: None,
-            harmonic_style: None,
+
+    let cached_only = server
+        .quality_report(Parameters(QualityReportParams {
+            playlist_id: "p-gig".to_string(),
+            limit: None,
+            analyze_missing: Some(false),
+            max_analyze: None,
         }))
         .await
-        .expect("score_transition should succeed");
+        .expect("cache-only quality_report should succeed");
+    let cached_only = extract_json(&cached_only);
+    assert_eq!(cached_only["summary"]["measured"], 0);
+    assert_eq!(cached_only["summary"]["unmeasured"], 2);
+
+    let capped = server
+        .quality_report(Parameters(QualityReportParams {
+            playlist_id: "p-gig".to_string(),
+            limit: None,
+            analyze_missing: None,
+            max_analyze: Some(1),
+        }))
+        .await
+        .expect("capped quality_report should succeed");
+    let capped = extract_json(&capped);
+    assert_eq!(capped["summary"]["analyzed"], 1, "{capped}");
+    assert_eq!(capped["summary"]["deferred"], 1);
+    assert_eq!(capped["summary"]["unmeasured"], 1);
 
+    let result = server
+        .quality_report(Parameters(QualityReportParams {
+            playlist_id: "p-gig".to_string(),
+            limit: None,
+            analyze_missing: None,
+            max_analyze: None,
+        }))
+        .await
+        .expect("quality_report should succeed");
     let payload = extract_json(&result);
-    assert_eq!(payload["from"]["track_id"], "from-track");
-    assert_eq!(payload["from"]["key"], "8A");
-    assert_eq!(payload["to"]["track_id"], "to-track");
-    assert_eq!(payload["to"]["key"], "9A");
+    assert_eq!(payload["summary"]["analyzed"], 1, "{payload}");
+    assert_eq!(payload["summary"]["cached"], 1);
+    assert_eq!(payload["summary"]["deferred"], 0);
+    assert_eq!(payload["summary"]["clipping"], 1);
+    assert_eq!(payload["tracks"][0]["track_id"], "clipped");
+    assert_eq!(payload["tracks"][0]["flags"][0], "CLIPPING");
+    assert_eq!(payload["tracks"][0]["sample_peak_dbfs"], 0.0);
+    assert_eq!(payload["tracks"][1]["track_id"], "clean");
+    assert_eq!(payload["tracks"][1]["clipped_runs"], 0);
+    assert_eq!(payload["tracks"][1]["flags"], serde_json::json!([]));
+
+    let again = server
+        .quality_report(Parameters(QualityReportParams {
+            playlist_id: "p-gig".to_string(),
+            limit: Some(1),
+            analyze_missing: None,
+            max_analyze: None,
+        }))
+        .await
+        .expect("cached quality_report should succeed");
+    let again = extract_json(&again);
+    assert_eq!(again["summary"]["cached"], 2);
+    assert_eq!(again["tracks"].as_array().map(Vec::len), Some(1));
+
+    let missing = server
+        .quality_report(Parameters(QualityReportParams {
+            playlist_id: "p-none".to_string(),
+            limit: None,
+            analyze_missing: None,
+            max_analyze: None,
+        }))
+        .await;
+    assert!(missing.is_err());
+}
 
-    assert_eq!(payload["scores"]["key"]["value"], 0.9);
-    // BPM uses exponential curve: 122→123.5 = 1.23% → exp(-0.019*1.23²) ≈ 0.972
-    assert_eq!(payload["scores"]["bpm"]["value"], 0.972);
-    assert_eq!(payload["scores"]["energy"]["value"], 1.0);
-    assert_eq!(payload["scores"]["genre"]["value"], 1.0);
-    assert_eq!(payload["scores"]["brightness"]["value"], 0.5);
-    assert_eq!(payload["scores"]["rhythm"]["value"], 0.5);
-    assert_eq!(payload["scores"]["composite"], 0.958);
+#[tokio::test]
+async fn write_replaygain_tags_album_and_loose_tracks() {
+    let dir = tempfile::tempdir().expect("temp audio dir should create");
+    let album_dir = dir.path().join("Artist - EP (2021)");
+    let loose_dir = dir.path().join("loose");
+    std::fs::create_dir_all(&album_dir).expect("album dir should create");
+    std::fs::create_dir_all(&loose_dir).expect("loose dir should create");
+    let first = album_dir.join("01 Loud.wav");
+    let second = album_dir.join("02 Quiet.wav");
+    let loose = loose_dir.join("Single.wav");
+    write_chord_wav(&first, 5, 1, 0.8);
+    write_chord_wav(&second, 5, 2, 0.2);
+    write_chord_wav(&loose, 5, 3, 0.4);
+    std::fs::write(loose_dir.join("Voice.opus"), b"OggS").expect("opus file should write");
+    let riff_written = tags::write_file_tags(&tags::WriteEntry {
+        path: first.clone(),
+        tags: HashMap::from([
+            ("artist".to_string(), Some("Artist".to_string())),
+            ("title".to_string(), Some("Loud".to_string())),
+        ]),
+        wav_targets: vec![tags::WavTarget::RiffInfo],
+        comment_mode: tags::CommentMode::default(),
+    });
+    assert!(
+        matches!(riff_written, tags::FileWriteResult::Ok { .. }),
+        "{riff_written:?}"
+    );
 
-    // Top-level transition metadata fields
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(
+        store_path
+            .to_str()
+            .expect("temp store path should be UTF-8"),
+    )
+    .expect("temp internal store should open");
+    let db_conn = create_single_track_test_db("t1", &first.to_string_lossy());
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+    let params = |dry_run: bool| WriteReplayGainParams {
+        paths: None,
+        track_ids: None,
+        directory: Some(dir.path().to_string_lossy().to_string()),
+        glob: None,
+        recursive: Some(true),
+        album_gain: None,
+        dry_run: Some(dry_run),
+        limit: None,
+        max_analyze: None,
+    };
+
+    let no_selector = server
+        .write_replaygain(Parameters(WriteReplayGainParams {
+            directory: None,
+            ..params(true)
+        }))
+        .await;
+    assert!(no_selector.is_err());
+
+    let capped = server
+        .write_replaygain(Parameters(WriteReplayGainParams {
+            max_analyze: Some(1),
+            ..params(true)
+        }))
+        .await
+        .expect("capped write_replaygain should succeed");
+    let capped = extract_json(&capped);
+    assert_eq!(capped["summary"]["analyzed"], 1, "{capped}");
+    let deferred = capped["deferred"].as_array().expect("deferred array");
+    assert!(!deferred.is_empty());
+    let deferred_album_tracks = deferred
+        .iter()
+        .filter(|p| p.as_str().is_some_and(|p| p.contains("Artist - EP")))
+        .count();
+    assert_eq!(
+        deferred_album_tracks, 2,
+        "an album is written whole or not at all: {capped}"
+    );
+    assert_eq!(capped["summary"]["albums"], 0, "{capped}");
+
+    let preview = server
+        .write_replaygain(Parameters(params(true)))
+        .await
+        .expect("dry-run write_replaygain should succeed");
+    let preview = extract_json(&preview);
+    assert_eq!(preview["summary"]["measured"], 3, "{preview}");
+    assert_eq!(preview["summary"]["analyzed"], 2);
+    assert_eq!(preview["summary"]["deferred"], 0);
+    assert_eq!(preview["summary"]["files_previewed"], 3);
+    assert_eq!(preview["summary"]["albums"], 1);
+    assert_eq!(preview["albums"][0]["tracks"], 2);
+    assert_eq!(
+        preview["errors"],
+        serde_json::json!([]),
+        "undecodable Opus files are not scanned"
+    );
+    let results = preview["results"].as_array().expect("results array");
+    let result_for = |name: &str| {
+        results
+            .iter()
+            .find(|r| r["path"].as_str().is_some_and(|p| p.ends_with(name)))
+            .unwrap_or_else(|| panic!("missing result for {name}"))
+            .clone()
+    };
+    let loud = result_for("01 Loud.wav");
+    let quiet = result_for("02 Quiet.wav");
+    let single = result_for("Single.wav");
+    let loud_gain = loud["track_gain_db"].as_f64().unwrap();
+    let quiet_gain = quiet["track_gain_db"].as_f64().unwrap();
     assert!(
-        payload["key_relation"].is_string(),
-        "key_relation should be present"
+        (quiet_gain - loud_gain - 12.04).abs() < 0.2,
+        "a quarter of the amplitude needs ~12 dB more gain: {loud_gain} vs {quiet_gain}"
     );
+    assert_eq!(loud["album_gain_db"], quiet["album_gain_db"]);
+    assert!(single["album_gain_db"].is_null());
     assert!(
-        payload["key_relation"]
+        loud["tags"]["changes"]["replaygain_track_gain"]["new"]
             .as_str()
-            .unwrap()
-            .contains("Camelot adjacent")
+            .is_some_and(|gain| gain.ends_with(" dB"))
     );
     assert!(
-        payload["bpm_adjustment_pct"].is_number(),
-        "bpm_adjustment_pct should be present"
+        single["tags"]["changes"]
+            .get("replaygain_album_gain")
+            .is_none()
     );
-    let bpm_pct = payload["bpm_adjustment_pct"].as_f64().unwrap();
-    assert!(
-        bpm_pct > 1.0 && bpm_pct < 2.0,
-        "122→123.5 is ~1.23%; got {bpm_pct}"
+
+    let written = server
+        .write_replaygain(Parameters(params(false)))
+        .await
+        .expect("write_replaygain should succeed");
+    let written = extract_json(&written);
+    assert_eq!(written["summary"]["cached"], 3, "{written}");
+    assert_eq!(written["summary"]["files_written"], 3);
+
+    let fields: Vec<String> = [
+        "replaygain_track_gain",
+        "replaygain_album_peak",
+        "artist",
+        "title",
+    ]
+    .iter()
+    .map(|f| f.to_string())
+    .collect();
+    match tags::read_file_tags(&first, Some(&fields), false) {
+        tags::FileReadResult::Wav {
+            id3v2, riff_info, ..
+        } => {
+            assert_eq!(
+                id3v2["replaygain_track_gain"].as_deref(),
+                loud["tags"]["changes"]["replaygain_track_gain"]["new"].as_str()
+            );
+            assert!(id3v2["replaygain_album_peak"].is_some());
+            // The RIFF INFO layer is left exactly as it was.
+            assert_eq!(riff_info["replaygain_track_gain"], None);
+            assert_eq!(riff_info["artist"].as_deref(), Some("Artist"));
+            assert_eq!(riff_info["title"].as_deref(), Some("Loud"));
+        }
+        other => panic!("expected WAV tags, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn score_transition_reports_phrase_alignment_from_cached_structure() {
+    let db_conn = create_single_track_test_db("phrase-from", "/tmp/phrase-from.flac");
+    insert_test_track(
+        &db_conn,
+        "phrase-to",
+        "Incoming",
+        "g1",
+        "/tmp/phrase-to.flac",
+    );
+    insert_test_track(
+        &db_conn,
+        "phrase-offset",
+        "Offset",
+        "g1",
+        "/tmp/phrase-offset.flac",
     );
+    let store_dir = tempfile::tempdir().expect("temp store dir should create");
+    let store_path = store_dir.path().join("internal.sqlite3");
+    let store_conn = store::open(store_path.to_str().unwrap()).expect("store should open");
+    seed_structure(&store_conn, "/tmp/phrase-from.flac", 16, 16);
+    seed_structure(&store_conn, "/tmp/phrase-to.flac", 16, 8);
+    seed_structure(&store_conn, "/tmp/phrase-offset.flac", 8, 8);
+    let server =
+        create_server_with_connections(db_conn, store_conn, default_http_client_for_tests());
+
+    let score = |target: &str| {
+        server.score_transition(Parameters(ScoreTransitionParams {
+            source_track_id: "phrase-from".to_string(),
+            target_track_id: target.to_string(),
+            energy_phase: None,
+            priority: None,
+            use_master_tempo: None,
+            harmonic_style: None,
+        }))
+    };
+    let aligned = extract_json(&score("phrase-to").await.expect("score should succeed"));
+    assert_eq!(aligned["phrase_alignment"]["blend_bars"], 16);
+    assert_eq!(aligned["phrase_alignment"]["score"], 1.0);
+    assert_eq!(aligned["phrase_alignment"]["aligned"], true);
+
+    let offset = extract_json(&score("phrase-offset").await.expect("score should succeed"));
+    assert_eq!(offset["phrase_alignment"]["shared_boundaries"], 0);
+    assert_eq!(offset["phrase_alignment"]["aligned"], false);
 }
 
 #[tokio::test]
@@ -4635,6 +7248,7 @@ async fn build_set_beam_search_produces_multiple_candidates() {
             harmonic_style: None,
             bpm_drift_pct: None,
             bpm_range: None,
+            avoid_played_since: None,
         }))
         .await
         .expect("build_set with beam_width=5 should succeed");
@@ -4680,6 +7294,7 @@ async fn build_set_with_bpm_range_includes_trajectory_fields() {
             harmonic_style: None,
             bpm_drift_pct: None,
             bpm_range: Some((124.0, 131.0)),
+            avoid_played_since: None,
         }))
         .await
         .expect("build_set with bpm_range should succeed");
@@ -4741,6 +7356,7 @@ async fn build_set_beam_width_1_backward_compatible() {
             harmonic_style: None,
             bpm_drift_pct: None,
             bpm_range: None,
+            avoid_played_since: None,
         }))
         .await
         .expect("build_set with candidates=1 should succeed");
@@ -4819,4 +7435,10 @@ fn tool_schemas_are_claude_api_compatible() {
     check::<ReadFileTagsParams>("ReadFileTagsParams");
     check::<ExtractCoverArtParams>("ExtractCoverArtParams");
     check::<EmbedCoverArtParams>("EmbedCoverArtParams");
+    check::<DiffXmlParams>("DiffXmlParams");
+    check::<GetHistorySessionsParams>("GetHistorySessionsParams");
+    check::<GetHistoryTracksParams>("GetHistoryTracksParams");
+    check::<FindRelocationsParams>("FindRelocationsParams");
+    check::<FindDuplicatesParams>("FindDuplicatesParams");
+    check::<FingerprintMatchParams>("FingerprintMatchParams");
 }
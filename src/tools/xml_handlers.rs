@@ -0,0 +1,62 @@
+use std::path::Path;
+
+use rmcp::ErrorData as McpError;
+use rmcp::model::{CallToolResult, Content};
+
+use super::*;
+use crate::types::Track;
+use crate::xml_import;
+
+const DEFAULT_DIFF_LIMIT: u32 = 50;
+
+fn load_xml_library(path: &str) -> Result<xml_import::XmlLibrary, McpError> {
+    xml_import::load_xml(Path::new(path)).map_err(|e| McpError::invalid_params(e.to_string(), None))
+}
+
+fn track_summary_json(track: &Track) -> serde_json::Value {
+    serde_json::json!({
+        "track_id": track.id,
+        "title": track.title,
+        "artist": track.artist,
+        "file_path": track.file_path,
+    })
+}
+
+pub(super) fn handle_diff_xml(
+    server: &ReklawdboxServer,
+    params: DiffXmlParams,
+) -> Result<CallToolResult, McpError> {
+    let limit = params.limit.unwrap_or(DEFAULT_DIFF_LIMIT) as usize;
+    let library = load_xml_library(&params.path)?;
+
+    let (baseline_source, baseline_tracks) = match &params.against_path {
+        Some(against) => (against.clone(), load_xml_library(against)?.tracks),
+        None => {
+            let conn = server.rekordbox_conn()?;
+            let tracks = db::search_tracks_unbounded(&conn, &db::SearchParams::default())
+                .map_err(|e| mcp_internal_error(format!("DB error: {e}")))?;
+            ("master.db".to_string(), tracks)
+        }
+    };
+
+    let diff = xml_import::diff_libraries(&baseline_tracks, &library.tracks);
+    let result = serde_json::json!({
+        "path": params.path,
+        "baseline": baseline_source,
+        "summary": {
+            "xml_tracks": library.tracks.len(),
+            "xml_playlists": library.playlists.iter().filter(|p| !p.is_folder).count(),
+            "baseline_tracks": baseline_tracks.len(),
+            "added": diff.added.len(),
+            "removed": diff.removed.len(),
+            "changed": diff.changed.len(),
+            "unchanged": diff.unchanged,
+        },
+        "added": diff.added.iter().take(limit).map(|t| track_summary_json(t)).collect::<Vec<_>>(),
+        "removed": diff.removed.iter().take(limit).map(|t| track_summary_json(t)).collect::<Vec<_>>(),
+        "changed": diff.changed.iter().take(limit).collect::<Vec<_>>(),
+    });
+    let json =
+        serde_json::to_string_pretty(&result).map_err(|e| mcp_internal_error(format!("{e}")))?;
+    Ok(CallToolResult::success(vec![Content::text(json)]))
+}
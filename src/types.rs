@@ -11,6 +11,12 @@ pub enum FileKind {
     Flac,
     Wav,
     Aiff,
+    /// Ogg Vorbis. Not a Rekordbox file type, so only inferred from the
+    /// extension.
+    Ogg,
+    /// Ogg Opus. Not a Rekordbox file type, so only inferred from the
+    /// extension.
+    Opus,
     Unknown(i32),
 }
 
@@ -27,6 +33,34 @@ impl FileKind {
         }
     }
 
+    /// Infer the kind from a file extension (case-insensitive).
+    pub fn from_extension(ext: &str) -> Self {
+        match ext.to_ascii_lowercase().as_str() {
+            "mp3" => Self::Mp3,
+            "m4a" | "aac" => Self::M4a,
+            "flac" => Self::Flac,
+            "wav" => Self::Wav,
+            "aiff" | "aif" | "aifc" => Self::Aiff,
+            "ogg" | "oga" => Self::Ogg,
+            "opus" => Self::Opus,
+            _ => Self::Unknown(0),
+        }
+    }
+
+    /// Parse a Rekordbox XML `Kind` attribute (inverse of `as_kind_str`).
+    pub fn from_kind_str(kind: &str) -> Self {
+        match kind {
+            "MP3 File" => Self::Mp3,
+            "M4A File" => Self::M4a,
+            "FLAC File" => Self::Flac,
+            "WAV File" => Self::Wav,
+            "AIFF File" => Self::Aiff,
+            "OGG File" => Self::Ogg,
+            "OPUS File" => Self::Opus,
+            _ => Self::Unknown(0),
+        }
+    }
+
     /// Human-readable kind string matching Rekordbox XML `Kind` attribute.
     pub fn as_kind_str(&self) -> &'static str {
         match self {
@@ -35,6 +69,8 @@ impl FileKind {
             Self::Flac => "FLAC File",
             Self::Wav => "WAV File",
             Self::Aiff => "AIFF File",
+            Self::Ogg => "OGG File",
+            Self::Opus => "OPUS File",
             Self::Unknown(_) => "Audio File",
         }
     }
@@ -49,14 +85,7 @@ impl Serialize for FileKind {
 impl<'de> Deserialize<'de> for FileKind {
     fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         let s = String::deserialize(deserializer)?;
-        Ok(match s.as_str() {
-            "MP3 File" => Self::Mp3,
-            "M4A File" => Self::M4a,
-            "FLAC File" => Self::Flac,
-            "WAV File" => Self::Wav,
-            "AIFF File" => Self::Aiff,
-            _ => Self::Unknown(0),
-        })
+        Ok(Self::from_kind_str(&s))
     }
 }
 
@@ -68,7 +97,10 @@ impl JsonSchema for FileKind {
     fn json_schema(_gen: &mut schemars::SchemaGenerator) -> schemars::Schema {
         schemars::json_schema!({
             "type": "string",
-            "enum": ["MP3 File", "M4A File", "FLAC File", "WAV File", "AIFF File", "Audio File"]
+            "enum": [
+                "MP3 File", "M4A File", "FLAC File", "WAV File", "AIFF File", "OGG File",
+                "OPUS File", "Audio File"
+            ]
         })
     }
 }
@@ -97,8 +129,121 @@ pub struct Track {
     #[serde(rename = "file_type_name")]
     pub file_kind: FileKind,
     pub date_added: String,
+    /// My Tag names, in category then tag order. Empty unless loaded with
+    /// `db::attach_track_lists`, as are the two lists below.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub my_tags: Vec<String>,
+    /// Hot Cue Bank lists the track belongs to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hot_cue_banks: Vec<String>,
+    /// Related Tracks lists the track belongs to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub related_track_lists: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub position: Option<u32>,
+    /// Cue points loaded for XML export; empty unless explicitly attached.
+    #[serde(skip)]
+    pub cues: Vec<CuePoint>,
+    /// Beat grid markers for XML export; empty unless explicitly attached.
+    #[serde(skip)]
+    pub tempo_markers: Vec<TempoMarker>,
+}
+
+/// A constant-tempo beat grid segment, written as an XML `TEMPO` element.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TempoMarker {
+    /// Segment start (first beat) in seconds.
+    pub start: f64,
+    pub bpm: f64,
+    /// Bar position (1-4) of the beat at `start`.
+    pub beat_in_bar: u8,
+}
+
+/// A memory cue, hot cue or loop from `djmdCue`, positioned in seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CuePoint {
+    pub name: String,
+    pub start: f64,
+    /// Loop end; `None` for single-point cues.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<f64>,
+    /// Hot cue slot 0-7 (A-H); `None` for memory cues.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hot_cue: Option<u8>,
+}
+
+/// Hot cue banks, in slot order.
+pub const HOT_CUE_BANKS: [char; 8] = ['A', 'B', 'C', 'D', 'E', 'F', 'G', 'H'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[schemars(inline)]
+#[serde(rename_all = "snake_case")]
+pub enum CueKind {
+    Memory,
+    HotCue,
+}
+
+/// A memory or hot cue staged with `update_tracks`, added to the track's
+/// existing cues on export.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[schemars(inline)]
+pub struct StagedCue {
+    pub kind: CueKind,
+    /// Seconds from the start of the track.
+    pub position: f64,
+    /// Hot cue bank A-H; required for hot cues, not used by memory cues.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bank: Option<char>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl StagedCue {
+    /// Hot cue slot 0-7 for the bank; `None` for memory cues.
+    pub fn hot_cue_slot(&self) -> Option<u8> {
+        match self.kind {
+            CueKind::Memory => None,
+            CueKind::HotCue => {
+                let bank = self.bank?.to_ascii_uppercase();
+                HOT_CUE_BANKS
+                    .iter()
+                    .position(|&b| b == bank)
+                    .map(|slot| slot as u8)
+            }
+        }
+    }
+
+    pub fn to_cue_point(&self) -> CuePoint {
+        CuePoint {
+            name: self.name.clone().unwrap_or_default(),
+            start: self.position,
+            end: None,
+            hot_cue: self.hot_cue_slot(),
+        }
+    }
+
+    /// Whether this cue takes `cue`'s place: the same hot cue bank, or a
+    /// memory cue at the same position (to the millisecond).
+    pub fn replaces(&self, cue: &CuePoint) -> bool {
+        match self.hot_cue_slot() {
+            Some(slot) => cue.hot_cue == Some(slot),
+            None => {
+                cue.hot_cue.is_none()
+                    && cue.end.is_none()
+                    && (cue.start - self.position).abs() < 0.0005
+            }
+        }
+    }
+}
+
+/// Add `staged` cues to `cues`, replacing any cue each one takes the place
+/// of, and keep the list in position order.
+pub fn apply_staged_cues(cues: &mut Vec<CuePoint>, staged: &[StagedCue]) {
+    for cue in staged {
+        cues.retain(|existing| !cue.replaces(existing));
+        cues.push(cue.to_cue_point());
+    }
+    cues.sort_by(|a, b| a.start.total_cmp(&b.start));
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
@@ -111,13 +256,42 @@ pub struct Playlist {
     pub is_smart: bool,
 }
 
+/// A play session (or year/month folder) from `djmdHistory`.
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HistorySession {
+    pub id: String,
+    pub name: String,
+    pub parent_id: String,
+    pub is_folder: bool,
+    /// Session date as recorded by Rekordbox (`YYYY-MM-DD`).
+    pub date: String,
+    pub track_count: i32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct TrackChange {
     pub track_id: String,
     pub genre: Option<String>,
     pub comments: Option<String>,
     pub rating: Option<u8>, // 1-5 stars
     pub color: Option<String>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub label: Option<String>,
+    pub remixer: Option<String>,
+    pub year: Option<i32>,
+    pub bpm: Option<f64>,
+    pub key: Option<String>,
+    /// Full replacement My Tag set; `Some(vec![])` removes all tags.
+    pub my_tags: Option<Vec<String>>,
+    /// Relocated file path, exported as the XML `Location`.
+    pub file_path: Option<String>,
+    /// Keeper track this duplicate is merged into. `write_xml` leaves the
+    /// duplicate out and points its playlist entries at the keeper.
+    pub merge_into: Option<String>,
+    /// Cues to add on export, on top of the track's cues in the library.
+    pub cues: Option<Vec<StagedCue>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -126,10 +300,39 @@ pub enum EditableField {
     Comments,
     Rating,
     Color,
+    Title,
+    Artist,
+    Album,
+    Label,
+    Remixer,
+    Year,
+    Bpm,
+    Key,
+    MyTags,
+    FilePath,
+    MergeInto,
+    Cues,
 }
 
 impl EditableField {
-    pub const ALL: &[Self] = &[Self::Genre, Self::Comments, Self::Rating, Self::Color];
+    pub const ALL: &[Self] = &[
+        Self::Genre,
+        Self::Comments,
+        Self::Rating,
+        Self::Color,
+        Self::Title,
+        Self::Artist,
+        Self::Album,
+        Self::Label,
+        Self::Remixer,
+        Self::Year,
+        Self::Bpm,
+        Self::Key,
+        Self::MyTags,
+        Self::FilePath,
+        Self::MergeInto,
+        Self::Cues,
+    ];
 
     pub const fn as_str(&self) -> &'static str {
         match self {
@@ -137,6 +340,18 @@ impl EditableField {
             Self::Comments => "comments",
             Self::Rating => "rating",
             Self::Color => "color",
+            Self::Title => "title",
+            Self::Artist => "artist",
+            Self::Album => "album",
+            Self::Label => "label",
+            Self::Remixer => "remixer",
+            Self::Year => "year",
+            Self::Bpm => "bpm",
+            Self::Key => "key",
+            Self::MyTags => "my_tags",
+            Self::FilePath => "file_path",
+            Self::MergeInto => "merge_into",
+            Self::Cues => "cues",
         }
     }
 
@@ -146,6 +361,18 @@ impl EditableField {
             "comments" => Some(Self::Comments),
             "rating" => Some(Self::Rating),
             "color" => Some(Self::Color),
+            "title" => Some(Self::Title),
+            "artist" => Some(Self::Artist),
+            "album" => Some(Self::Album),
+            "label" => Some(Self::Label),
+            "remixer" => Some(Self::Remixer),
+            "year" => Some(Self::Year),
+            "bpm" => Some(Self::Bpm),
+            "key" => Some(Self::Key),
+            "my_tags" => Some(Self::MyTags),
+            "file_path" => Some(Self::FilePath),
+            "merge_into" => Some(Self::MergeInto),
+            "cues" => Some(Self::Cues),
             _ => None,
         }
     }
@@ -216,6 +443,16 @@ pub enum Confidence {
     Canonical,
 }
 
+/// How sure a match or correction is (relocation candidates, duplicate
+/// clusters, BPM and key reconciliation); only `High` is acted on unattended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchConfidence {
+    High,
+    Medium,
+    Low,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 #[schemars(inline)]
 #[serde(rename_all = "lowercase")]
@@ -321,6 +558,7 @@ mod tests {
             FileKind::Flac => 5,
             FileKind::Wav => 11,
             FileKind::Aiff => 12,
+            FileKind::Ogg | FileKind::Opus => unreachable!("no Rekordbox FileType code"),
             FileKind::Unknown(raw) => raw,
         }
     }
@@ -366,18 +604,32 @@ mod tests {
         let kind: FileKind = serde_json::from_value(serde_json::json!("MP3 File")).unwrap();
         assert_eq!(kind, FileKind::Mp3);
 
-        let kind: FileKind = serde_json::from_value(serde_json::json!("Ogg File")).unwrap();
+        let kind: FileKind = serde_json::from_value(serde_json::json!("OGG File")).unwrap();
+        assert_eq!(kind, FileKind::Ogg);
+
+        let kind: FileKind = serde_json::from_value(serde_json::json!("WMA File")).unwrap();
         assert_eq!(kind, FileKind::Unknown(0));
     }
 
+    #[test]
+    fn file_kind_from_extension_covers_ogg_opus_and_aifc() {
+        for (ext, kind) in [
+            ("OGG", FileKind::Ogg),
+            ("opus", FileKind::Opus),
+            ("aifc", FileKind::Aiff),
+            ("m4a", FileKind::M4a),
+            ("wma", FileKind::Unknown(0)),
+        ] {
+            assert_eq!(FileKind::from_extension(ext), kind, "{ext}");
+            assert_eq!(FileKind::from_kind_str(kind.as_kind_str()), kind, "{ext}");
+        }
+    }
+
     #[test]
     fn editable_field_count_matches_track_change() {
         let json = serde_json::to_value(TrackChange {
             track_id: "x".into(),
-            genre: None,
-            comments: None,
-            rating: None,
-            color: None,
+            ..Default::default()
         })
         .unwrap();
         let field_count = json.as_object().unwrap().len() - 1; // minus track_id
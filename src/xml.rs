@@ -3,7 +3,7 @@ use std::fmt::Write;
 use std::fs;
 use std::path::Path;
 
-use crate::types::Track;
+use crate::types::{CuePoint, TempoMarker, Track};
 
 const TARGET_REKORDBOX_VERSION: &str = "7.2.10";
 
@@ -11,6 +11,88 @@ const TARGET_REKORDBOX_VERSION: &str = "7.2.10";
 pub struct PlaylistDef {
     pub name: String,
     pub track_ids: Vec<String>,
+    /// Enclosing folder names from the root, e.g. `["Sets", "2026"]`. Empty for top level.
+    pub folder: Vec<String>,
+}
+
+/// Playlist tree assembled from `PlaylistDef` folder paths, in first-seen order.
+enum PlaylistNode<'a> {
+    Folder {
+        name: &'a str,
+        children: Vec<PlaylistNode<'a>>,
+    },
+    Playlist(&'a PlaylistDef),
+}
+
+fn build_playlist_tree(playlists: &[PlaylistDef]) -> Vec<PlaylistNode<'_>> {
+    let mut root = Vec::new();
+    for playlist in playlists {
+        let mut level = &mut root;
+        for folder_name in &playlist.folder {
+            let existing = level.iter().position(|node| {
+                matches!(node, PlaylistNode::Folder { name, .. } if *name == folder_name.as_str())
+            });
+            let index = existing.unwrap_or_else(|| {
+                level.push(PlaylistNode::Folder {
+                    name: folder_name,
+                    children: Vec::new(),
+                });
+                level.len() - 1
+            });
+            let PlaylistNode::Folder { children, .. } = &mut level[index] else {
+                unreachable!("index points at a folder node");
+            };
+            level = children;
+        }
+        level.push(PlaylistNode::Playlist(playlist));
+    }
+    root
+}
+
+fn write_playlist_nodes(
+    out: &mut String,
+    nodes: &[PlaylistNode<'_>],
+    track_id_map: &HashMap<String, usize>,
+    depth: usize,
+) -> Result<(), String> {
+    let indent = "  ".repeat(depth + 3);
+    for node in nodes {
+        match node {
+            PlaylistNode::Folder { name, children } => {
+                writeln!(
+                    out,
+                    "{indent}<NODE Type=\"0\" Name=\"{}\" Count=\"{}\">",
+                    xml_escape(name),
+                    children.len()
+                )
+                .unwrap();
+                write_playlist_nodes(out, children, track_id_map, depth + 1)?;
+                writeln!(out, "{indent}</NODE>").unwrap();
+            }
+            PlaylistNode::Playlist(playlist) => {
+                writeln!(
+                    out,
+                    "{indent}<NODE Type=\"1\" Name=\"{}\" Entries=\"{}\" KeyType=\"0\">",
+                    xml_escape(&playlist.name),
+                    playlist.track_ids.len()
+                )
+                .unwrap();
+
+                for track_id in &playlist.track_ids {
+                    let xml_track_id = track_id_map.get(track_id).ok_or_else(|| {
+                        format!(
+                            "Playlist '{}' references unknown track ID '{}'",
+                            playlist.name, track_id
+                        )
+                    })?;
+                    writeln!(out, "{indent}  <TRACK Key=\"{xml_track_id}\"/>").unwrap();
+                }
+
+                writeln!(out, "{indent}</NODE>").unwrap();
+            }
+        }
+    }
+    Ok(())
 }
 
 pub fn xml_escape(s: &str) -> String {
@@ -74,6 +156,28 @@ pub fn path_to_rekordbox_location_uri(file_path: &str) -> String {
     format!("file://localhost{encoded}")
 }
 
+/// Convert a Rekordbox Location URI back to a file system path (inverse of
+/// `path_to_rekordbox_location_uri`). Non-URI input is returned unchanged.
+pub fn rekordbox_location_uri_to_path(location: &str) -> String {
+    use percent_encoding::percent_decode_str;
+
+    let Some(uri_path) = location
+        .strip_prefix("file://localhost")
+        .or_else(|| location.strip_prefix("file://"))
+    else {
+        return location.to_string();
+    };
+    let decoded = percent_decode_str(uri_path)
+        .decode_utf8_lossy()
+        .into_owned();
+    // Windows paths are written as `/C:/...`; drop the slash before the drive letter.
+    let bytes = decoded.as_bytes();
+    if bytes.len() >= 3 && bytes[0] == b'/' && bytes[1].is_ascii_alphabetic() && bytes[2] == b':' {
+        return decoded[1..].to_string();
+    }
+    decoded
+}
+
 fn write_collection_track(out: &mut String, track: &Track, track_id: usize) {
     let rating = crate::types::stars_to_rating(track.rating);
     let location = path_to_rekordbox_location_uri(&track.file_path);
@@ -129,7 +233,47 @@ fn write_collection_track(out: &mut String, track: &Track, track_id: usize) {
         write!(out, " Colour=\"0x{:06X}\"", track.color_code).unwrap();
     }
 
-    out.push_str("/>\n");
+    if track.cues.is_empty() && track.tempo_markers.is_empty() {
+        out.push_str("/>\n");
+        return;
+    }
+    out.push_str(">\n");
+    for marker in &track.tempo_markers {
+        write_tempo(out, marker);
+    }
+    for cue in &track.cues {
+        write_position_mark(out, cue);
+    }
+    out.push_str("      </TRACK>\n");
+}
+
+/// Write a TEMPO child element (4/4 grid segment starting at `Inizio`).
+fn write_tempo(out: &mut String, marker: &TempoMarker) {
+    writeln!(
+        out,
+        "        <TEMPO Inizio=\"{start:.3}\" Bpm=\"{bpm:.2}\" Metro=\"4/4\" Battito=\"{beat}\"/>",
+        start = marker.start,
+        bpm = marker.bpm,
+        beat = marker.beat_in_bar,
+    )
+    .unwrap();
+}
+
+/// Write a POSITION_MARK child element. Type 0 is a cue, 4 a loop; Num -1 marks a memory cue.
+fn write_position_mark(out: &mut String, cue: &CuePoint) {
+    let num = cue.hot_cue.map_or(-1, i32::from);
+    write!(
+        out,
+        "        <POSITION_MARK Name=\"{name}\" Type=\"{mark_type}\" Start=\"{start:.3}\"",
+        name = xml_escape(&cue.name),
+        mark_type = if cue.end.is_some() { 4 } else { 0 },
+        start = cue.start,
+    )
+    .unwrap();
+    if let Some(end) = cue.end {
+        write!(out, " End=\"{end:.3}\"").unwrap();
+    }
+    writeln!(out, " Num=\"{num}\"/>").unwrap();
 }
 
 #[cfg(test)]
@@ -164,36 +308,15 @@ pub fn generate_xml_with_playlists(
     out.push_str("  </COLLECTION>\n");
 
     if !playlists.is_empty() {
+        let tree = build_playlist_tree(playlists);
         out.push_str("  <PLAYLISTS>\n");
         writeln!(
             out,
             "    <NODE Type=\"0\" Name=\"ROOT\" Count=\"{}\">",
-            playlists.len()
+            tree.len()
         )
         .unwrap();
-
-        for playlist in playlists {
-            writeln!(
-                out,
-                "      <NODE Type=\"1\" Name=\"{}\" Entries=\"{}\" KeyType=\"0\">",
-                xml_escape(&playlist.name),
-                playlist.track_ids.len()
-            )
-            .unwrap();
-
-            for track_id in &playlist.track_ids {
-                let xml_track_id = track_id_map.get(track_id).ok_or_else(|| {
-                    format!(
-                        "Playlist '{}' references unknown track ID '{}'",
-                        playlist.name, track_id
-                    )
-                })?;
-                writeln!(out, "        <TRACK Key=\"{xml_track_id}\"/>").unwrap();
-            }
-
-            out.push_str("      </NODE>\n");
-        }
-
+        write_playlist_nodes(&mut out, &tree, &track_id_map, 0)?;
         out.push_str("    </NODE>\n");
         out.push_str("  </PLAYLISTS>\n");
     }
@@ -247,7 +370,12 @@ mod tests {
             sample_rate: 44100,
             file_kind: crate::types::FileKind::Flac,
             date_added: "2023-01-15".to_string(),
+            my_tags: Vec::new(),
+            hot_cue_banks: Vec::new(),
+            related_track_lists: Vec::new(),
             position: None,
+            cues: Vec::new(),
+            tempo_markers: Vec::new(),
         }
     }
 
@@ -311,6 +439,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_rekordbox_location_uri_to_path_round_trips() {
+        for path in [
+            "/Users/vz/Music/file name.flac",
+            "/Users/vz/Music/100% Señorita (Dub).mp3",
+            "C:/Music/track.wav",
+        ] {
+            let uri = path_to_rekordbox_location_uri(path);
+            assert_eq!(rekordbox_location_uri_to_path(&uri), path);
+        }
+        assert_eq!(
+            rekordbox_location_uri_to_path("file:///Users/vz/a%20b.flac"),
+            "/Users/vz/a b.flac"
+        );
+        assert_eq!(
+            rekordbox_location_uri_to_path("/plain/path.mp3"),
+            "/plain/path.mp3"
+        );
+    }
+
     #[test]
     fn test_generate_xml_structure() {
         let tracks = vec![make_test_track()];
@@ -361,6 +509,53 @@ mod tests {
         assert!(xml.contains("Comments=\"&quot;great&quot; &lt;track&gt;\""));
     }
 
+    #[test]
+    fn test_generate_xml_writes_position_marks() {
+        let mut track = make_test_track();
+        track.cues = vec![
+            CuePoint {
+                name: String::new(),
+                start: 0.25,
+                end: None,
+                hot_cue: None,
+            },
+            CuePoint {
+                name: "Drop & Roll".to_string(),
+                start: 64.0,
+                end: Some(72.0),
+                hot_cue: Some(1),
+            },
+        ];
+        let xml = generate_xml(&[track]);
+        assert!(xml.contains("<POSITION_MARK Name=\"\" Type=\"0\" Start=\"0.250\" Num=\"-1\"/>"));
+        assert!(xml.contains(
+            "<POSITION_MARK Name=\"Drop &amp; Roll\" Type=\"4\" Start=\"64.000\" End=\"72.000\" Num=\"1\"/>"
+        ));
+        assert!(xml.contains("</TRACK>"));
+    }
+
+    #[test]
+    fn test_generate_xml_writes_tempo_before_position_marks() {
+        let mut track = make_test_track();
+        track.tempo_markers = vec![TempoMarker {
+            start: 0.1234,
+            bpm: 127.996,
+            beat_in_bar: 2,
+        }];
+        track.cues = vec![CuePoint {
+            name: String::new(),
+            start: 0.1234,
+            end: None,
+            hot_cue: None,
+        }];
+        let xml = generate_xml(&[track]);
+        let tempo = xml
+            .find("<TEMPO Inizio=\"0.123\" Bpm=\"128.00\" Metro=\"4/4\" Battito=\"2\"/>")
+            .expect("TEMPO element should be written");
+        let mark = xml.find("<POSITION_MARK").unwrap();
+        assert!(tempo < mark, "TEMPO precedes POSITION_MARK");
+    }
+
     #[test]
     fn test_generate_xml_with_playlists_structure_and_key_mapping() {
         let mut t1 = make_test_track();
@@ -382,6 +577,7 @@ mod tests {
                 "db-id-1".to_string(),
                 "db-id-3".to_string(),
             ],
+            folder: Vec::new(),
         }];
 
         let xml = generate_xml_with_playlists(&[t1, t2, t3], &playlists)
@@ -406,12 +602,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_generate_xml_nests_playlists_in_folders() {
+        let mut t1 = make_test_track();
+        t1.id = "db-id-1".to_string();
+        let mut t2 = make_test_track();
+        t2.id = "db-id-2".to_string();
+
+        let folder = |path: &[&str]| path.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let playlists = vec![
+            PlaylistDef {
+                name: "Club X".to_string(),
+                track_ids: vec!["db-id-1".to_string()],
+                folder: folder(&["Sets", "2026"]),
+            },
+            PlaylistDef {
+                name: "Loose".to_string(),
+                track_ids: vec!["db-id-2".to_string()],
+                folder: Vec::new(),
+            },
+            PlaylistDef {
+                name: "Club Y".to_string(),
+                track_ids: vec!["db-id-2".to_string()],
+                folder: folder(&["Sets", "2026"]),
+            },
+        ];
+
+        let xml = generate_xml_with_playlists(&[t1, t2], &playlists)
+            .expect("nested playlist XML should generate");
+
+        assert!(xml.contains("    <NODE Type=\"0\" Name=\"ROOT\" Count=\"2\">"));
+        assert!(xml.contains("      <NODE Type=\"0\" Name=\"Sets\" Count=\"1\">"));
+        assert!(xml.contains("        <NODE Type=\"0\" Name=\"2026\" Count=\"2\">"));
+        assert!(xml.contains(
+            "          <NODE Type=\"1\" Name=\"Club X\" Entries=\"1\" KeyType=\"0\">\n            <TRACK Key=\"1\"/>"
+        ));
+        assert_eq!(
+            xml.matches("Name=\"Sets\"").count(),
+            1,
+            "folders are merged"
+        );
+        let club_y = xml.find("Name=\"Club Y\"").unwrap();
+        let loose = xml.find("Name=\"Loose\"").unwrap();
+        assert!(club_y < loose, "folder keeps its first-seen position");
+        assert_eq!(xml.matches("<NODE").count(), xml.matches("</NODE>").count());
+    }
+
     #[test]
     fn test_generate_xml_with_playlists_errors_for_unknown_track_id() {
         let track = make_test_track();
         let playlists = vec![PlaylistDef {
             name: "Bad Playlist".to_string(),
             track_ids: vec!["missing-id".to_string()],
+            folder: Vec::new(),
         }];
 
         let err = generate_xml_with_playlists(&[track], &playlists)
@@ -437,10 +680,12 @@ mod tests {
             PlaylistDef {
                 name: "First Set".to_string(),
                 track_ids: vec!["db-id-1".to_string(), "db-id-3".to_string()],
+                folder: Vec::new(),
             },
             PlaylistDef {
                 name: "Second & Set".to_string(),
                 track_ids: vec!["db-id-2".to_string()],
+                folder: Vec::new(),
             },
         ];
 
@@ -511,6 +756,7 @@ mod tests {
             bpm_max: None,
             key: None,
             playlist: None,
+            my_tag: None,
             has_genre: None,
             label: None,
             path: None,
@@ -0,0 +1,584 @@
+//! Rekordbox XML import: parses `DJ_PLAYLISTS` exports (as written by Rekordbox
+//! or by `xml.rs`) back into `Track` and `Playlist` structures, and diffs track
+//! lists from different libraries.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use quick_xml::XmlVersion;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+
+use crate::color;
+use crate::types::{
+    CuePoint, EditableField, FieldDiff, FileKind, Playlist, TempoMarker, Track, TrackDiff,
+    rating_to_stars,
+};
+use crate::xml::rekordbox_location_uri_to_path;
+
+#[derive(Debug, thiserror::Error)]
+pub enum XmlImportError {
+    #[error("Failed to read '{path}': {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("Malformed XML at byte {position}: {message}")]
+    Parse { position: u64, message: String },
+    #[error("Not a Rekordbox XML export (missing DJ_PLAYLISTS root element)")]
+    NotRekordbox,
+}
+
+/// A parsed Rekordbox XML library.
+#[derive(Debug, Default)]
+pub struct XmlLibrary {
+    /// Collection tracks. `id` is the XML `TrackID`; `file_path` is the decoded `Location`.
+    pub tracks: Vec<Track>,
+    /// Folders and playlists in document order. IDs are '/'-joined node paths below
+    /// ROOT (e.g. `Sets/2026/Club X`); top-level nodes have parent ID `root`.
+    pub playlists: Vec<Playlist>,
+    /// Track IDs per playlist ID, in playlist order.
+    pub playlist_tracks: HashMap<String, Vec<String>>,
+}
+
+/// Playlist NODE entry keys: `TrackID`s (KeyType 0) or `Location`s (KeyType 1).
+struct OpenNode {
+    id: String,
+    is_playlist: bool,
+    keys_are_locations: bool,
+}
+
+pub fn load_xml(path: &Path) -> Result<XmlLibrary, XmlImportError> {
+    let content = std::fs::read_to_string(path).map_err(|source| XmlImportError::Io {
+        path: path.display().to_string(),
+        source,
+    })?;
+    parse_xml(&content)
+}
+
+pub fn parse_xml(content: &str) -> Result<XmlLibrary, XmlImportError> {
+    let mut reader = Reader::from_str(content);
+    let mut library = XmlLibrary::default();
+    let mut saw_root = false;
+    let mut in_collection = false;
+    let mut open_track: Option<Track> = None;
+    let mut nodes: Vec<OpenNode> = Vec::new();
+    // Location-keyed playlist entries are resolved once the collection is known.
+    let mut location_keyed: HashSet<String> = HashSet::new();
+
+    loop {
+        let event = reader.read_event().map_err(|e| XmlImportError::Parse {
+            position: reader.error_position(),
+            message: e.to_string(),
+        })?;
+        let (element, is_empty) = match &event {
+            Event::Start(e) => (e, false),
+            Event::Empty(e) => (e, true),
+            Event::End(e) => {
+                match e.name().into_inner() {
+                    "COLLECTION" => in_collection = false,
+                    "TRACK" => {
+                        if let Some(track) = open_track.take() {
+                            library.tracks.push(track);
+                        }
+                    }
+                    "NODE" => {
+                        nodes.pop();
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+        let attrs = attributes(element, reader.error_position())?;
+
+        match element.name().into_inner() {
+            "DJ_PLAYLISTS" => saw_root = true,
+            "COLLECTION" => in_collection = !is_empty,
+            "TRACK" if in_collection => {
+                let track = track_from_attributes(&attrs);
+                if is_empty {
+                    library.tracks.push(track);
+                } else {
+                    open_track = Some(track);
+                }
+            }
+            "TRACK" => {
+                if let (Some(node), Some(key)) = (nodes.last(), attrs.get("Key"))
+                    && node.is_playlist
+                {
+                    if node.keys_are_locations {
+                        location_keyed.insert(node.id.clone());
+                    }
+                    library
+                        .playlist_tracks
+                        .entry(node.id.clone())
+                        .or_default()
+                        .push(key.clone());
+                }
+            }
+            "POSITION_MARK" => {
+                if let Some(track) = open_track.as_mut() {
+                    track.cues.push(cue_from_attributes(&attrs));
+                }
+            }
+            "TEMPO" => {
+                if let Some(track) = open_track.as_mut() {
+                    track.tempo_markers.push(TempoMarker {
+                        start: parse_num(&attrs, "Inizio"),
+                        bpm: parse_num(&attrs, "Bpm"),
+                        beat_in_bar: parse_num(&attrs, "Battito"),
+                    });
+                }
+            }
+            "NODE" => {
+                let name = attrs.get("Name").cloned().unwrap_or_default();
+                let is_folder = attrs.get("Type").map(String::as_str) == Some("0");
+                if nodes.is_empty() && is_folder && name == "ROOT" {
+                    if !is_empty {
+                        nodes.push(OpenNode {
+                            id: String::new(),
+                            is_playlist: false,
+                            keys_are_locations: false,
+                        });
+                    }
+                    continue;
+                }
+                let parent_id = nodes
+                    .last()
+                    .map(|node| node.id.clone())
+                    .filter(|id| !id.is_empty());
+                let base_id = match &parent_id {
+                    Some(parent) => format!("{parent}/{name}"),
+                    None => name.clone(),
+                };
+                let id = unique_playlist_id(&library.playlists, base_id);
+                library.playlists.push(Playlist {
+                    id: id.clone(),
+                    name,
+                    track_count: 0,
+                    parent_id: parent_id.unwrap_or_else(|| "root".to_string()),
+                    is_folder,
+                    is_smart: false,
+                });
+                if !is_folder {
+                    library.playlist_tracks.entry(id.clone()).or_default();
+                }
+                if !is_empty {
+                    nodes.push(OpenNode {
+                        id,
+                        is_playlist: !is_folder,
+                        keys_are_locations: attrs.get("KeyType").map(String::as_str) == Some("1"),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !saw_root {
+        return Err(XmlImportError::NotRekordbox);
+    }
+
+    if !location_keyed.is_empty() {
+        let id_by_path: HashMap<String, String> = library
+            .tracks
+            .iter()
+            .map(|t| (t.file_path.clone(), t.id.clone()))
+            .collect();
+        for playlist_id in &location_keyed {
+            if let Some(keys) = library.playlist_tracks.get_mut(playlist_id) {
+                for key in keys.iter_mut() {
+                    let path = rekordbox_location_uri_to_path(key);
+                    if let Some(id) = id_by_path.get(&path) {
+                        *key = id.clone();
+                    }
+                }
+            }
+        }
+    }
+    for playlist in &mut library.playlists {
+        if let Some(keys) = library.playlist_tracks.get(&playlist.id) {
+            playlist.track_count = keys.len() as i32;
+        }
+    }
+
+    Ok(library)
+}
+
+fn attributes(
+    element: &BytesStart<'_>,
+    position: u64,
+) -> Result<HashMap<String, String>, XmlImportError> {
+    let mut attrs = HashMap::new();
+    for attr in element.attributes() {
+        let attr = attr.map_err(|e| XmlImportError::Parse {
+            position,
+            message: e.to_string(),
+        })?;
+        let value = attr
+            .normalized_value(XmlVersion::Implicit1_0)
+            .map_err(|e| XmlImportError::Parse {
+                position,
+                message: e.to_string(),
+            })?;
+        attrs.insert(attr.key.into_inner().to_string(), value.into_owned());
+    }
+    Ok(attrs)
+}
+
+/// Disambiguate sibling nodes that share a name by suffixing ` (2)`, ` (3)`, ...
+fn unique_playlist_id(existing: &[Playlist], base_id: String) -> String {
+    let taken = |id: &str| existing.iter().any(|p| p.id == id);
+    if !taken(&base_id) {
+        return base_id;
+    }
+    (2..)
+        .map(|n| format!("{base_id} ({n})"))
+        .find(|id| !taken(id))
+        .expect("unbounded suffix search always finds a free ID")
+}
+
+fn parse_num<T: std::str::FromStr + Default>(attrs: &HashMap<String, String>, key: &str) -> T {
+    attrs
+        .get(key)
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or_default()
+}
+
+fn text(attrs: &HashMap<String, String>, key: &str) -> String {
+    attrs
+        .get(key)
+        .map(|v| v.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn color_name_for_code(color_code: i32) -> Option<&'static str> {
+    color::COLORS
+        .iter()
+        .find(|(_, code)| *code == color_code)
+        .map(|(name, _)| *name)
+}
+
+fn track_from_attributes(attrs: &HashMap<String, String>) -> Track {
+    let color_code = attrs
+        .get("Colour")
+        .and_then(|v| i32::from_str_radix(v.trim().trim_start_matches("0x"), 16).ok())
+        .unwrap_or(0);
+    let color = color_name_for_code(color_code)
+        .unwrap_or_default()
+        .to_string();
+
+    Track {
+        id: text(attrs, "TrackID"),
+        title: text(attrs, "Name"),
+        artist: text(attrs, "Artist"),
+        album: text(attrs, "Album"),
+        genre: text(attrs, "Genre"),
+        bpm: parse_num(attrs, "AverageBpm"),
+        key: text(attrs, "Tonality"),
+        rating: rating_to_stars(parse_num(attrs, "Rating")),
+        comments: text(attrs, "Comments"),
+        color,
+        color_code,
+        label: text(attrs, "Label"),
+        remixer: text(attrs, "Remixer"),
+        year: parse_num(attrs, "Year"),
+        length: parse_num(attrs, "TotalTime"),
+        file_path: rekordbox_location_uri_to_path(&text(attrs, "Location")),
+        play_count: parse_num(attrs, "PlayCount"),
+        bit_rate: parse_num(attrs, "BitRate"),
+        sample_rate: parse_num(attrs, "SampleRate"),
+        file_kind: FileKind::from_kind_str(&text(attrs, "Kind")),
+        date_added: text(attrs, "DateAdded"),
+        my_tags: Vec::new(),
+        hot_cue_banks: Vec::new(),
+        related_track_lists: Vec::new(),
+        position: None,
+        cues: Vec::new(),
+        tempo_markers: Vec::new(),
+    }
+}
+
+fn cue_from_attributes(attrs: &HashMap<String, String>) -> CuePoint {
+    let num: i32 = attrs
+        .get("Num")
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(-1);
+    let is_loop = attrs.get("Type").map(String::as_str) == Some("4");
+    CuePoint {
+        name: text(attrs, "Name"),
+        start: parse_num(attrs, "Start"),
+        end: if is_loop {
+            attrs.get("End").and_then(|v| v.trim().parse().ok())
+        } else {
+            None
+        },
+        hot_cue: u8::try_from(num).ok(),
+    }
+}
+
+/// Track-level differences between a baseline library and another library.
+#[derive(Debug, Default)]
+pub struct LibraryDiff<'a> {
+    /// Tracks only in the other library.
+    pub added: Vec<&'a Track>,
+    /// Tracks only in the baseline.
+    pub removed: Vec<&'a Track>,
+    /// Tracks in both with differing editable fields. `track_id` is the baseline ID;
+    /// `old_value` is the baseline value and `new_value` the other library's.
+    pub changed: Vec<TrackDiff>,
+    pub unchanged: usize,
+}
+
+/// Key used to pair tracks across libraries. Track IDs are library-local, so
+/// tracks are matched by file path.
+fn match_key(track: &Track) -> String {
+    track.file_path.trim().replace('\\', "/")
+}
+
+fn field_value(track: &Track, field: EditableField) -> String {
+    match field {
+        EditableField::Genre => track.genre.trim().to_string(),
+        EditableField::Comments => track.comments.trim().to_string(),
+        EditableField::Rating => track.rating.to_string(),
+        // XML carries only the code, so compare codes and name them where known.
+        EditableField::Color => {
+            if track.color_code > 0 {
+                color_name_for_code(track.color_code)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("0x{:06X}", track.color_code))
+            } else {
+                color::canonical_color_name(&track.color)
+                    .unwrap_or_default()
+                    .to_string()
+            }
+        }
+        EditableField::Title => track.title.trim().to_string(),
+        EditableField::Artist => track.artist.trim().to_string(),
+        EditableField::Album => track.album.trim().to_string(),
+        EditableField::Label => track.label.trim().to_string(),
+        EditableField::Remixer => track.remixer.trim().to_string(),
+        EditableField::Year => track.year.to_string(),
+        EditableField::Bpm => format!("{:.2}", track.bpm),
+        EditableField::Key => track.key.trim().to_string(),
+        EditableField::MyTags => track.my_tags.join(" / "),
+        // Always equal for paired tracks, since pairing is by path.
+        EditableField::FilePath => match_key(track),
+        // Staging-only; not a track attribute.
+        EditableField::MergeInto => String::new(),
+        // Staged cues are additions, not a comparable attribute.
+        EditableField::Cues => String::new(),
+    }
+}
+
+/// Compare `other` against `baseline`, pairing tracks by file path.
+/// When a path appears more than once in a library, the first track wins.
+pub fn diff_libraries<'a>(baseline: &'a [Track], other: &'a [Track]) -> LibraryDiff<'a> {
+    let mut baseline_by_key: HashMap<String, &Track> = HashMap::new();
+    for track in baseline {
+        baseline_by_key.entry(match_key(track)).or_insert(track);
+    }
+
+    let mut diff = LibraryDiff::default();
+    let mut matched: HashSet<String> = HashSet::new();
+    for track in other {
+        let key = match_key(track);
+        if !matched.insert(key.clone()) {
+            continue;
+        }
+        let Some(base) = baseline_by_key.get(&key) else {
+            diff.added.push(track);
+            continue;
+        };
+        let changes: Vec<FieldDiff> = EditableField::ALL
+            .iter()
+            // XML has no My Tag element, so tags would always differ from master.db.
+            .filter(|&&field| field != EditableField::MyTags)
+            .filter_map(|&field| {
+                let old_value = field_value(base, field);
+                let new_value = field_value(track, field);
+                (old_value != new_value).then(|| FieldDiff {
+                    field: field.as_str().to_string(),
+                    old_value,
+                    new_value,
+                })
+            })
+            .collect();
+        if changes.is_empty() {
+            diff.unchanged += 1;
+        } else {
+            diff.changed.push(TrackDiff {
+                track_id: base.id.clone(),
+                title: base.title.clone(),
+                artist: base.artist.clone(),
+                changes,
+            });
+        }
+    }
+
+    let mut seen_baseline: HashSet<String> = HashSet::new();
+    diff.removed = baseline
+        .iter()
+        .filter(|track| {
+            let key = match_key(track);
+            !matched.contains(&key) && seen_baseline.insert(key)
+        })
+        .collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xml::{PlaylistDef, generate_xml_with_playlists};
+
+    fn make_track(id: &str, path: &str) -> Track {
+        Track {
+            id: id.to_string(),
+            title: "Archangel".to_string(),
+            artist: "Burial".to_string(),
+            album: "Untrue".to_string(),
+            genre: "Dubstep".to_string(),
+            bpm: 139.5,
+            key: "Am".to_string(),
+            rating: 4,
+            comments: "iconic & \"vocal\"".to_string(),
+            color: "Rose".to_string(),
+            color_code: 0xFF007F,
+            label: "Hyperdub".to_string(),
+            remixer: String::new(),
+            year: 2007,
+            length: 240,
+            file_path: path.to_string(),
+            play_count: 12,
+            bit_rate: 1411,
+            sample_rate: 44100,
+            file_kind: FileKind::Flac,
+            date_added: "2023-01-15".to_string(),
+            my_tags: Vec::new(),
+            hot_cue_banks: Vec::new(),
+            related_track_lists: Vec::new(),
+            position: None,
+            cues: Vec::new(),
+            tempo_markers: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn parse_round_trips_generated_xml() {
+        let mut t1 = make_track("db-1", "/Music/Señorita 100%.flac");
+        t1.cues = vec![CuePoint {
+            name: "Drop".to_string(),
+            start: 64.0,
+            end: Some(72.0),
+            hot_cue: Some(2),
+        }];
+        t1.tempo_markers = vec![TempoMarker {
+            start: 0.125,
+            bpm: 139.5,
+            beat_in_bar: 1,
+        }];
+        let t2 = make_track("db-2", "/Music/other.flac");
+        let playlists = vec![PlaylistDef {
+            name: "Club X".to_string(),
+            track_ids: vec!["db-2".to_string(), "db-1".to_string()],
+            folder: vec!["Sets".to_string(), "2026".to_string()],
+        }];
+        let xml = generate_xml_with_playlists(&[t1, t2], &playlists).unwrap();
+
+        let library = parse_xml(&xml).expect("generated XML should parse");
+        assert_eq!(library.tracks.len(), 2);
+        let track = &library.tracks[0];
+        assert_eq!(track.id, "1");
+        assert_eq!(track.file_path, "/Music/Señorita 100%.flac");
+        assert_eq!(track.comments, "iconic & \"vocal\"");
+        assert_eq!(track.rating, 4);
+        assert_eq!(track.color, "Rose");
+        assert_eq!(track.bpm, 139.5);
+        assert_eq!(track.file_kind, FileKind::Flac);
+        assert_eq!(track.cues[0].hot_cue, Some(2));
+        assert_eq!(track.cues[0].end, Some(72.0));
+        assert_eq!(track.tempo_markers[0].start, 0.125);
+
+        let ids: Vec<&str> = library.playlists.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["Sets", "Sets/2026", "Sets/2026/Club X"]);
+        assert_eq!(library.playlists[0].parent_id, "root");
+        assert!(library.playlists[1].is_folder);
+        let club = &library.playlists[2];
+        assert_eq!(club.parent_id, "Sets/2026");
+        assert_eq!(club.track_count, 2);
+        assert_eq!(library.playlist_tracks["Sets/2026/Club X"], ["2", "1"]);
+    }
+
+    #[test]
+    fn parse_resolves_location_keyed_playlists_and_duplicate_names() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<DJ_PLAYLISTS Version="1.0.0">
+  <COLLECTION Entries="1">
+    <TRACK TrackID="77" Name="A" Location="file://localhost/M/a%20b.mp3" Kind="MP3 File"/>
+  </COLLECTION>
+  <PLAYLISTS>
+    <NODE Type="0" Name="ROOT" Count="2">
+      <NODE Type="1" Name="Set" KeyType="1" Entries="1">
+        <TRACK Key="file://localhost/M/a%20b.mp3"/>
+      </NODE>
+      <NODE Type="1" Name="Set" KeyType="0" Entries="0"/>
+    </NODE>
+  </PLAYLISTS>
+</DJ_PLAYLISTS>"#;
+        let library = parse_xml(xml).unwrap();
+        assert_eq!(library.tracks[0].file_path, "/M/a b.mp3");
+        assert_eq!(library.tracks[0].file_kind, FileKind::Mp3);
+        assert_eq!(library.playlist_tracks["Set"], ["77"]);
+        assert_eq!(library.playlists[1].id, "Set (2)");
+        assert_eq!(library.playlists[1].track_count, 0);
+    }
+
+    #[test]
+    fn parse_rejects_non_rekordbox_and_malformed_xml() {
+        assert!(matches!(
+            parse_xml("<plist><dict/></plist>"),
+            Err(XmlImportError::NotRekordbox)
+        ));
+        assert!(matches!(
+            parse_xml("<DJ_PLAYLISTS><COLLECTION></DJ_PLAYLISTS>"),
+            Err(XmlImportError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn diff_libraries_pairs_tracks_by_path() {
+        let baseline = vec![
+            make_track("db-1", "/Music/a.flac"),
+            make_track("db-2", "/Music/b.flac"),
+            make_track("db-3", "/Music/c.flac"),
+        ];
+        let mut changed = make_track("7", "/Music/a.flac");
+        changed.genre = "Garage".to_string();
+        changed.bpm = 140.0;
+        changed.color = String::new();
+        changed.color_code = 0xFF007F;
+        let other = vec![
+            changed,
+            make_track("8", "/Music/b.flac"),
+            make_track("9", "/Music/new.flac"),
+        ];
+
+        let diff = diff_libraries(&baseline, &other);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].file_path, "/Music/new.flac");
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].id, "db-3");
+        assert_eq!(diff.changed.len(), 1);
+        let change = &diff.changed[0];
+        assert_eq!(change.track_id, "db-1");
+        let fields: Vec<&str> = change.changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, ["genre", "bpm"], "color compares by code");
+        assert_eq!(change.changes[1].old_value, "139.50");
+        assert_eq!(change.changes[1].new_value, "140.00");
+    }
+}